@@ -1,80 +1,329 @@
-use candle_core::{Device, Tensor, DType};
-use candle_nn::VarBuilder;
-use candle_transformers::models::distilbert::DistilBertModel;
+use candle_core::Device;
 use tokenizers::Tokenizer;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use sha2::{Digest, Sha256};
 use crate::{
     AppError, AppResult, 
-    models::{AIProcessingResult, SearchResult, Note, EmbeddingModel, WhisperModel},
-    database::Database,
+    models::{AIProcessingResult, SearchResult, SearchPage, Note, EmbeddingModel, EmbeddingOwner, WhisperModel, HybridSearchWeights, PageLink, PageLinkType,
+        AiDeviceInfo, AiDeviceKind, AiDevicePreference, RewriteStyle, ChatAnswer, ChatCitation, is_valid_language_tag,
+        DetectedLanguage, primary_language_subtag, Tag, WritingSuggestion, AnswerMethod, AIMode, VoiceSegment, TranscribedWord, Keyphrase},
+    database::{Database, match_confidence, highlight_spans, encode_cursor, decode_cursor},
+    titles, language, sentiment, vad, model_downloads, keyphrases,
+    transcription::seconds,
+    writing::{self, WritingAction},
+    llm::{self, LocalLlm},
+    embedder::Embedder,
 };
 
+// Position after the last semantic result returned: results sort by score, then note id
+#[derive(serde::Serialize, serde::Deserialize)]
+struct ScoreCursor {
+    score: f64,
+    id: String,
+}
+
+/// Setting with the preferred inference device.
+pub const AI_DEVICE_KEY: &str = "ai_device";
+/// Audio is 16 kHz mono 16-bit PCM.
+pub const PCM_BYTES_PER_SECOND: usize = 32_000;
+
+/// Minimum cosine similarity for a note to count as related.
+const RELEVANCE_THRESHOLD: f64 = 0.1;
+/// Candidates fetched from the vector index; semantic results page within these.
+const VECTOR_KNN_LIMIT: usize = 1000;
+/// Auto links kept per page, and how similar a page must be to get one.
+const SUGGESTED_LINK_LIMIT: usize = 5;
+const SUGGESTED_LINK_THRESHOLD: f64 = 0.5;
+/// Tags suggested from the existing vocabulary, and how close a tag must be to the content.
+const SUGGESTED_TAG_LIMIT: usize = 5;
+const SUGGESTED_TAG_THRESHOLD: f64 = 0.3;
+/// Query embeddings kept for the session; the cache is cleared when it fills up.
+const QUERY_CACHE_CAPACITY: usize = 256;
+/// Completion lengths for language model tasks.
+const SUMMARY_MAX_TOKENS: usize = 200;
+const TITLE_MAX_TOKENS: usize = 24;
+const REWRITE_MAX_TOKENS: usize = 1024;
+const TRANSLATION_MAX_TOKENS: usize = 2048;
+const CHAT_MAX_TOKENS: usize = 512;
+const PAGE_QA_MAX_TOKENS: usize = 256;
+/// Detections below this are too uncertain to pin a transcription's language.
+const MIN_DETECTION_CONFIDENCE: f32 = 0.25;
+/// Source text per translation request, so that it and its translation both fit in
+/// `TRANSLATION_MAX_TOKENS`.
+const TRANSLATION_CHUNK_CHARS: usize = 1500;
+/// Page text given to the model as context, shared between the retrieved pages.
+const CHAT_CONTEXT_CHARS: usize = 8000;
+
+/// Models whose files are in place and, in Real mode, whose weights are loaded.
+pub struct PreparedModels {
+    whisper: WhisperModel,
+    embedding: EmbeddingModel,
+    tokenizer: Tokenizer,
+    embedder: Option<Embedder>,
+    models_path: PathBuf,
+    device: Device, // The embedder's weights are on it
+}
+
 pub struct AIService {
     device: Device,
+    device_info: AiDeviceInfo,
     whisper_model: Option<WhisperModel>,
     embedding_model: Option<EmbeddingModel>,
     tokenizer: Option<Tokenizer>,
+    models_path: PathBuf,
+    embedder: Arc<Mutex<Option<Embedder>>>, // Real mode; reloaded on first use after a device switch
     model_cache: HashMap<String, Vec<u8>>,
+    query_cache: Mutex<HashMap<String, Vec<f32>>>,
+    llm_model_path: PathBuf,
+    llm: Arc<Mutex<Option<LocalLlm>>>, // Loaded on first use, shared with blocking inference tasks
+    mode: AIMode,
 }
 
 impl AIService {
-    pub fn new() -> AppResult<Self> {
-        let device = Device::Cpu;
+    pub fn new(preference: AiDevicePreference, llm_model_path: PathBuf, mode: AIMode) -> AppResult<Self> {
+        let (device, device_info) = select_device(preference);
         
         Ok(Self {
             device,
+            device_info,
             whisper_model: None,
             embedding_model: None,
             tokenizer: None,
+            models_path: PathBuf::new(),
+            embedder: Arc::new(Mutex::new(None)),
             model_cache: HashMap::new(),
+            query_cache: Mutex::new(HashMap::new()),
+            llm_model_path,
+            llm: Arc::new(Mutex::new(None)),
+            mode,
         })
     }
 
-    pub async fn initialize_whisper(&mut self, model: WhisperModel, models_path: &Path) -> AppResult<()> {
-        let model_path = models_path.join(format!("whisper-{}.bin", model.model_name()));
-        
-        // Download model if it doesn't exist
-        if !model_path.exists() {
-            self.download_whisper_model(&model, &model_path).await?;
+    pub fn mode(&self) -> AIMode {
+        self.mode
+    }
+
+    /// Real mode refuses simulated output rather than passing it off as a model's.
+    fn ensure_mock(&self, feature: &str) -> AppResult<()> {
+        match self.mode {
+            AIMode::Mock => Ok(()),
+            AIMode::Real => Err(AppError::ModelNotFound(format!(
+                "{} inference isn't available in this build; set {}=mock for simulated output",
+                feature,
+                AIMode::ENV_VAR
+            ))),
         }
-        
-        self.whisper_model = Some(model);
-        Ok(())
     }
 
-    pub async fn initialize_embedding_model(&mut self, model: EmbeddingModel, models_path: &Path) -> AppResult<()> {
-        let model_path = models_path.join(format!("embedding-{}.safetensors", model.model_name()));
-        let tokenizer_path = models_path.join(format!("tokenizer-{}.json", model.model_name()));
-        
-        // Download model and tokenizer if they don't exist
-        if !model_path.exists() || !tokenizer_path.exists() {
-            self.download_embedding_model(&model, models_path).await?;
+    /// Switches inference to another device, for the next transcription or embedding.
+    pub fn set_device(&mut self, preference: AiDevicePreference) -> AiDeviceInfo {
+        let (device, device_info) = select_device(preference);
+        self.device = device;
+        self.device_info = device_info.clone();
+        // Loaded weights live on the old device; reload on next use
+        self.llm = Arc::new(Mutex::new(None));
+        self.embedder = Arc::new(Mutex::new(None));
+        device_info
+    }
+
+    pub fn device(&self) -> &Device {
+        &self.device
+    }
+
+    pub fn device_info(&self) -> &AiDeviceInfo {
+        &self.device_info
+    }
+
+    pub fn set_llm_model_path(&mut self, path: PathBuf) {
+        self.llm_model_path = path;
+        self.llm = Arc::new(Mutex::new(None));
+    }
+
+    pub fn llm_model_path(&self) -> &Path {
+        &self.llm_model_path
+    }
+
+    /// Whether a language model file is configured; it's loaded when first used.
+    pub fn is_llm_available(&self) -> bool {
+        self.llm_model_path.is_file()
+    }
+
+    /// Completes `prompt` with the local language model, or `None` when there is none.
+    /// The model stays loaded after the first call. Loading and inference run on a
+    /// blocking thread so they don't stall the async runtime.
+    async fn complete(&self, prompt: llm::Prompt, max_tokens: usize) -> AppResult<Option<String>> {
+        if !self.is_llm_available() {
+            return Ok(None);
         }
-        
-        // Load tokenizer
-        let tokenizer = Tokenizer::from_file(&tokenizer_path)
-            .map_err(|e| AppError::AIProcessing(format!("Failed to load tokenizer: {}", e)))?;
-        
-        self.tokenizer = Some(tokenizer);
-        self.embedding_model = Some(model);
-        Ok(())
+        let llm = Arc::clone(&self.llm);
+        let model_path = self.llm_model_path.clone();
+        let device = self.device.clone();
+        let completion = tokio::task::spawn_blocking(move || {
+            let mut llm = llm.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            if llm.is_none() {
+                *llm = Some(LocalLlm::load(&model_path, &device)?);
+            }
+            llm.as_mut().unwrap().generate(&prompt, max_tokens)
+        })
+        .await
+        .map_err(|e| AppError::Unknown(format!("Language model task failed: {}", e)))??;
+        let completion = completion.trim();
+        Ok((!completion.is_empty()).then(|| completion.to_string()))
     }
 
-    pub async fn transcribe_audio(&self, audio_data: &[u8]) -> AppResult<String> {
+    pub async fn rewrite_text(&self, text: &str, style: RewriteStyle) -> AppResult<String> {
+        self.complete(llm::rewrite_prompt(text, style), REWRITE_MAX_TOKENS).await?
+            .ok_or_else(|| AppError::ModelNotFound("Rewriting needs a local language model".to_string()))
+    }
+
+    pub async fn expand_text(&self, text: &str) -> AppResult<String> {
+        self.complete(llm::expand_prompt(text), REWRITE_MAX_TOKENS).await?
+            .ok_or_else(|| AppError::ModelNotFound("Expanding needs a local language model".to_string()))
+    }
+
+    /// A replacement for the byte range `start..end` of `content`. The local language model
+    /// writes it when loaded; otherwise rule-based edits are applied. Whitespace around the
+    /// selection is kept so the replacement drops into place.
+    pub async fn writing_suggestion(&self, content: &str, start: usize, end: usize, action: WritingAction) -> AppResult<WritingSuggestion> {
+        if start > end || end > content.len() || !content.is_char_boundary(start) || !content.is_char_boundary(end) {
+            return Err(AppError::InvalidFormat(format!("Invalid selection {}..{}", start, end)));
+        }
+        let original = &content[start..end];
+        let text = original.trim();
+        if text.is_empty() {
+            return Err(AppError::InvalidFormat("The selection is empty".to_string()));
+        }
+
+        let prompt = match action {
+            WritingAction::Rewrite(style) => llm::rewrite_prompt(text, style),
+            WritingAction::Expand => llm::expand_prompt(text),
+            WritingAction::FixGrammar => llm::grammar_prompt(text),
+        };
+        let (suggestion, method) = match self.complete(prompt, REWRITE_MAX_TOKENS).await? {
+            Some(completion) => (completion, AnswerMethod::Llm),
+            None => {
+                let edited = match action {
+                    WritingAction::Rewrite(style) => writing::rewrite(text, style),
+                    WritingAction::Expand => writing::expand(text),
+                    WritingAction::FixGrammar => writing::fix_grammar(text),
+                };
+                (edited, AnswerMethod::Rules)
+            }
+        };
+
+        let leading = &original[..original.len() - original.trim_start().len()];
+        let trailing = &original[original.trim_end().len()..];
+        Ok(WritingSuggestion {
+            start,
+            end,
+            original: original.to_string(),
+            replacement: format!("{}{}{}", leading, suggestion, trailing),
+            method,
+        })
+    }
+
+    /// Translates `text` into `target_lang` (a BCP-47 tag) a few paragraphs at a time.
+    pub async fn translate_text(&self, text: &str, target_lang: &str) -> AppResult<String> {
+        let target_lang = target_lang.trim();
+        if !is_valid_language_tag(target_lang) {
+            return Err(AppError::InvalidFormat(format!("Invalid language tag: {}", target_lang)));
+        }
+        if !self.is_llm_available() {
+            return Err(AppError::ModelNotFound("Translation needs a local language model".to_string()));
+        }
+
+        let mut translated = String::new();
+        for chunk in llm::translation_chunks(text, TRANSLATION_CHUNK_CHARS) {
+            let translation = self
+                .complete(llm::translate_prompt(&chunk.text, target_lang), TRANSLATION_MAX_TOKENS)
+                .await?
+                .ok_or_else(|| AppError::AIProcessing("The language model returned no translation".to_string()))?;
+            translated.push_str(chunk.separator);
+            translated.push_str(&translation);
+        }
+        Ok(translated)
+    }
+
+    /// Makes sure the models' files are in `models_path`, downloading what's missing, and
+    /// loads them, weights included in Real mode. It needs no running service, so callers
+    /// hold no lock while it runs; `install_models` swaps the result in.
+    pub async fn prepare_models(
+        mode: AIMode,
+        device: Device,
+        whisper: WhisperModel,
+        embedding: EmbeddingModel,
+        models_path: PathBuf,
+    ) -> AppResult<PreparedModels> {
+        let whisper_path = models_path.join(format!("whisper-{}.bin", whisper.model_name()));
+        if !whisper_path.exists() {
+            download_whisper_model(mode, &whisper, &whisper_path).await?;
+        }
+        let embedding_id = model_downloads::embedding_id(&embedding);
+        let tokenizer_path = models_path.join(format!("tokenizer-{}.json", embedding.model_name()));
+        let missing = match mode {
+            AIMode::Real => !model_downloads::is_installed(&models_path, &embedding_id),
+            AIMode::Mock => !tokenizer_path.exists() || !models_path.join(format!("embedding-{}.safetensors", embedding.model_name())).exists(),
+        };
+        if missing {
+            download_embedding_model(mode, &embedding, &models_path).await?;
+        }
+
+        tokio::task::spawn_blocking(move || {
+            if mode == AIMode::Real && !model_downloads::is_ggml_file(&whisper_path)? {
+                return Err(AppError::ModelNotFound(format!("{} isn't a Whisper model; delete it and download it again", whisper_path.display())));
+            }
+            let tokenizer = Tokenizer::from_file(&tokenizer_path)
+                .map_err(|e| AppError::AIProcessing(format!("Failed to load tokenizer: {}", e)))?;
+            let embedder = match mode {
+                AIMode::Real => Some(Embedder::load(&embedding, &models_path, &device)?),
+                AIMode::Mock => None,
+            };
+            Ok(PreparedModels { whisper, embedding, tokenizer, embedder, models_path, device })
+        })
+        .await
+        .map_err(|e| AppError::Unknown(format!("Model loading task failed: {}", e)))?
+    }
+
+    /// Swaps in models from `prepare_models`.
+    pub fn install_models(&mut self, models: PreparedModels) {
+        self.whisper_model = Some(models.whisper);
+        self.embedding_model = Some(models.embedding);
+        self.tokenizer = Some(models.tokenizer);
+        // Weights loaded before a device switch are reloaded on next use
+        let embedder = models.embedder.filter(|_| models.device.same_device(&self.device));
+        self.embedder = Arc::new(Mutex::new(embedder));
+        self.models_path = models.models_path;
+        // Cached query vectors came from the previous model
+        self.query_cache.lock().unwrap().clear();
+    }
+
+    /// `language` is an ISO 639-1 code (see `LanguageSettings::whisper_language`); `None` lets Whisper auto-detect.
+    pub async fn transcribe_audio(&self, audio_data: &[u8], language: Option<&str>) -> AppResult<String> {
+        let words = self.transcribe_words(audio_data, language).await?;
+        Ok(join_words(&words))
+    }
+
+    /// Transcribes into words with their timestamps, from the start of `audio_data`, and
+    /// Whisper's confidence in each.
+    pub async fn transcribe_words(&self, audio_data: &[u8], language: Option<&str>) -> AppResult<Vec<TranscribedWord>> {
         if self.whisper_model.is_none() {
             return Err(AppError::AIProcessing("Whisper model not initialized".to_string()));
         }
+        self.ensure_mock("Whisper")?;
+        tracing::debug!("Transcribing {} bytes of audio (language: {})", audio_data.len(), language.unwrap_or("auto"));
 
-        // For now, return a placeholder transcription
+        // Mock mode: placeholder words
         // In a real implementation, you would:
         // 1. Convert audio data to the format expected by Whisper
-        // 2. Run inference using the Whisper model
-        // 3. Return the transcription
+        // 2. Run inference with token timestamps enabled
+        // 3. Merge tokens into words, keeping their times and probabilities
         
-        // Simple mock transcription based on audio length
-        let duration = audio_data.len() as f32 / 32000.0; // Assume 16kHz mono
+        // Simple mock transcription based on audio length, words spread evenly
+        let duration = audio_data.len() as f64 / PCM_BYTES_PER_SECOND as f64;
         let word_count = (duration * 3.0) as usize; // ~3 words per second
+        let slot = duration / word_count.max(1) as f64;
         
         let mock_words = vec![
             "the", "quick", "brown", "fox", "jumps", "over", "lazy", "dog",
@@ -82,23 +331,70 @@ impl AIService {
             "network", "processing", "natural", "language", "understanding"
         ];
         
-        let mut transcription = String::new();
-        for i in 0..word_count {
-            if i > 0 {
-                transcription.push(' ');
+        let words = (0..word_count)
+            .map(|i| TranscribedWord {
+                text: mock_words[i % mock_words.len()].to_string(),
+                start_secs: i as f64 * slot,
+                end_secs: (i as f64 + 0.8) * slot,
+                confidence: 0.9,
+            })
+            .collect();
+        
+        Ok(words)
+    }
+
+    /// Transcribes in `language`, or lets Whisper auto-detect, and returns the text, its
+    /// words and the language the transcription is in when known.
+    pub async fn transcribe_with_language(&self, audio_data: &[u8], language: Option<&str>) -> AppResult<(String, Vec<TranscribedWord>, Option<String>)> {
+        let words = self.transcribe_words(audio_data, language).await?;
+        let transcription = join_words(&words);
+        let language = match language {
+            Some(language) => Some(primary_language_subtag(language)),
+            None => self.detect_language(&transcription)
+                .filter(|detected| detected.confidence >= MIN_DETECTION_CONFIDENCE)
+                .map(|detected| detected.language),
+        };
+        Ok((transcription, words, language))
+    }
+
+    /// Transcribes each stretch of speech found by voice-activity detection on its own, so
+    /// long recordings reach Whisper in clean chunks without silence and every segment keeps
+    /// its timestamps, down to each word. Without a `language`, the one detected in the
+    /// first segment with speech is used for the rest. Segments that transcribe to nothing
+    /// are left out.
+    pub async fn transcribe_segments(&self, audio_data: &[u8], language: Option<&str>) -> AppResult<(String, Option<String>, Vec<VoiceSegment>)> {
+        let mut language = language.map(str::to_string);
+        let mut segments = Vec::new();
+        for (start, end) in vad::speech_segments(audio_data) {
+            let (text, words, detected) = self.transcribe_with_language(&audio_data[start..end], language.as_deref()).await?;
+            let text = text.trim().to_string();
+            if text.is_empty() {
+                continue;
+            }
+            if language.is_none() {
+                language = detected;
             }
-            transcription.push_str(mock_words[i % mock_words.len()]);
+            let words = offset_words(words, seconds(start));
+            segments.push(VoiceSegment { start_secs: seconds(start), end_secs: seconds(end), text, words });
         }
-        
-        Ok(transcription)
+
+        let transcription = segments.iter().map(|segment| segment.text.as_str()).collect::<Vec<_>>().join(" ");
+        Ok((transcription, language, segments))
+    }
+
+    pub fn detect_language(&self, text: &str) -> Option<DetectedLanguage> {
+        language::detect(text)
     }
 
     pub async fn generate_embeddings(&self, text: &str) -> AppResult<Vec<f32>> {
         if self.embedding_model.is_none() || self.tokenizer.is_none() {
             return Err(AppError::AIProcessing("Embedding model not initialized".to_string()));
         }
+        if self.mode == AIMode::Real {
+            return self.embed_with_model(text).await;
+        }
 
-        // For now, return a simple hash-based embedding
+        // Mock mode: a deterministic hash-based embedding
         // In a real implementation, you would:
         // 1. Tokenize the text
         // 2. Run inference using the embedding model
@@ -131,119 +427,274 @@ impl AIService {
         Ok(embedding)
     }
 
-    pub async fn semantic_search(&self, database: &Database, query: &str, limit: usize) -> AppResult<Vec<SearchResult>> {
-        let query_embedding = self.generate_embeddings(query).await?;
+    /// Runs the loaded embedding model on a blocking thread, loading it first after a
+    /// device switch.
+    async fn embed_with_model(&self, text: &str) -> AppResult<Vec<f32>> {
+        let embedder = Arc::clone(&self.embedder);
+        let model = self.embedding_model.clone().ok_or_else(|| AppError::AIProcessing("Embedding model not initialized".to_string()))?;
+        let models_path = self.models_path.clone();
+        let device = self.device.clone();
+        let text = text.to_string();
+        tokio::task::spawn_blocking(move || {
+            let mut embedder = embedder.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            if embedder.is_none() {
+                *embedder = Some(Embedder::load(&model, &models_path, &device)?);
+            }
+            embedder.as_ref().unwrap().embed(&text)
+        })
+        .await
+        .map_err(|e| AppError::Unknown(format!("Embedding task failed: {}", e)))?
+    }
+
+    /// Search queries repeat often within a session, so their embeddings are cached.
+    async fn query_embedding(&self, query: &str) -> AppResult<Vec<f32>> {
+        if let Some(embedding) = self.query_cache.lock().unwrap().get(query) {
+            return Ok(embedding.clone());
+        }
+
+        let embedding = self.generate_embeddings(query).await?;
+        let mut cache = self.query_cache.lock().unwrap();
+        if cache.len() >= QUERY_CACHE_CAPACITY {
+            cache.clear();
+        }
+        cache.insert(query.to_string(), embedding.clone());
+        Ok(embedding)
+    }
+
+    pub async fn semantic_search(&self, database: &Database, query: &str, limit: usize, cursor: Option<&str>) -> AppResult<SearchPage<SearchResult>> {
+        let query_embedding = self.query_embedding(query).await?;
+        let after: Option<ScoreCursor> = cursor.map(decode_cursor).transpose()?;
+        
+        // Score everything first so notes and pages are only loaded for the requested page
+        let mut scored = self.similar_items(database, &query_embedding).await?;
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
         
-        // Get all embeddings from database
-        let all_embeddings = database.get_all_embeddings().await?;
+        if let Some(after) = after {
+            scored.retain(|(score, id, _)| *score < after.score || (*score == after.score && *id > after.id));
+        }
+        
+        let has_more = scored.len() > limit;
+        scored.truncate(limit);
+        let next_cursor = match scored.last() {
+            Some((score, id, _)) if has_more => Some(encode_cursor(&ScoreCursor { score: *score, id: id.clone() })?),
+            _ => None,
+        };
         
         let mut scored_results = Vec::new();
+        for (similarity, id, owner) in scored {
+            let (note, page) = match owner {
+                EmbeddingOwner::Note => (database.get_note(&id).await?, None),
+                EmbeddingOwner::Page => (None, database.get_page(&id).await?),
+            };
+            let (title, content) = match (&note, &page) {
+                (Some(note), _) => (note.title.clone(), note.content.clone()),
+                (_, Some(page)) => (page.title.clone(), page.content.clone()),
+                _ => continue, // Deleted since it was embedded
+            };
+
+            scored_results.push(SearchResult {
+                note,
+                page,
+                relevance_score: similarity,
+                matched_terms: self.extract_matched_terms(&content, query),
+                snippet: self.generate_snippet(&content, query),
+                match_confidence: match_confidence(query, &format!("{} {}", title, content)),
+                highlights: highlight_spans(query, &title, &content),
+            });
+        }
         
-        for (note_id, note_embedding) in all_embeddings {
-            let similarity = self.cosine_similarity(&query_embedding, &note_embedding);
-            
-            if similarity > 0.1 { // Threshold for relevance
-                if let Some(note) = database.get_note(&note_id).await? {
-                    let snippet = self.generate_snippet(&note.content, query);
-                    let matched_terms = self.extract_matched_terms(&note.content, query);
-                    
-                    scored_results.push(SearchResult {
-                        note,
-                        relevance_score: similarity,
-                        matched_terms,
-                        snippet,
-                    });
+        Ok(SearchPage { items: scored_results, next_cursor })
+    }
+
+    /// Blends BM25 keyword scores with embedding similarity. Keyword scores are scaled to
+    /// 0..1 by the best match so both signals are comparable before weighting. Without an
+    /// embedding model this degrades to keyword-only ranking.
+    pub async fn hybrid_search(&self, database: &Database, query: &str, limit: usize, weights: HybridSearchWeights) -> AppResult<Vec<SearchResult>> {
+        let weights = if self.is_embedding_available() {
+            weights.normalized()
+        } else {
+            HybridSearchWeights { keyword: 1.0, semantic: 0.0 }
+        };
+
+        // note_id -> (note if already loaded, keyword score, semantic score)
+        let mut candidates: HashMap<String, (Option<Note>, f64, f64)> = HashMap::new();
+
+        let keyword_results = database.keyword_search(query).await?;
+        let max_keyword = keyword_results.first().map(|(_, score)| *score).unwrap_or(0.0);
+        for (note, score) in keyword_results {
+            let normalized = if max_keyword > 0.0 { score / max_keyword } else { 0.0 };
+            candidates.insert(note.id.clone(), (Some(note), normalized, 0.0));
+        }
+
+        if weights.semantic > 0.0 {
+            let query_embedding = self.query_embedding(query).await?;
+            // Keyword search covers notes only, so pages are left out of the blend
+            for (similarity, note_id, owner) in self.similar_items(database, &query_embedding).await? {
+                if owner == EmbeddingOwner::Note {
+                    candidates.entry(note_id).or_insert((None, 0.0, 0.0)).2 = similarity;
                 }
             }
         }
-        
-        // Sort by relevance score and limit results
-        scored_results.sort_by(|a, b| b.relevance_score.partial_cmp(&a.relevance_score).unwrap());
+
+        let mut scored_results = Vec::new();
+        for (note_id, (note, keyword_score, semantic_score)) in candidates {
+            let note = match note {
+                Some(note) => note,
+                None => match database.get_note(&note_id).await? {
+                    Some(note) => note,
+                    None => continue,
+                },
+            };
+
+            let relevance_score = weights.keyword * keyword_score + weights.semantic * semantic_score;
+            if relevance_score <= 0.0 {
+                continue;
+            }
+
+            let snippet = self.generate_snippet(&note.content, query);
+            let matched_terms = self.extract_matched_terms(&note.content, query);
+            let match_confidence = match_confidence(query, &format!("{} {}", note.title, note.content));
+            let highlights = highlight_spans(query, &note.title, &note.content);
+            scored_results.push(SearchResult {
+                note: Some(note),
+                page: None,
+                relevance_score,
+                matched_terms,
+                snippet,
+                match_confidence,
+                highlights,
+            });
+        }
+
+        scored_results.sort_by(|a, b| b.relevance_score.total_cmp(&a.relevance_score));
         scored_results.truncate(limit);
-        
+
         Ok(scored_results)
     }
 
-    pub async fn suggest_tags(&self, content: &str) -> AppResult<Vec<String>> {
-        // Simple keyword extraction approach
-        // In a real implementation, you would use NER and topic modeling
-        
-        let words: Vec<&str> = content
-            .split_whitespace()
-            .filter(|word| word.len() > 3 && !self.is_common_word(word))
-            .collect();
-        
-        let mut word_freq = HashMap::new();
-        for word in words {
-            let clean_word = word.trim_matches(|c: char| !c.is_alphanumeric()).to_lowercase();
-            if !clean_word.is_empty() {
-                *word_freq.entry(clean_word).or_insert(0) += 1;
+    /// Pages most similar to `page_id`, persisted as `Auto` page links. Links are refreshed
+    /// lazily: only when the page was saved after they were computed, or none exist yet.
+    /// Without an embedding model the stored links are returned as they are.
+    pub async fn suggested_links(&self, database: &Database, page_id: &str) -> AppResult<Vec<PageLink>> {
+        let page = database.get_page(page_id).await?
+            .ok_or_else(|| AppError::NotFound(format!("Page with id {} not found", page_id)))?;
+        let links = database.get_links_of_type(page_id, PageLinkType::Auto).await?;
+        let fresh = !links.is_empty() && links.iter().all(|link| link.created_at >= page.updated_at);
+        if fresh || !self.is_embedding_available() {
+            return Ok(links);
+        }
+
+        let Some(mut targets) = self.related_pages(database, page_id, SUGGESTED_LINK_LIMIT).await? else {
+            return Ok(links);
+        };
+        let dismissed = database.get_dismissed_link_targets(page_id).await?;
+        targets.retain(|(id, _)| !dismissed.contains(id));
+        database.replace_links_of_type(page_id, PageLinkType::Auto, &targets).await
+    }
+
+    /// Up to `limit` pages (id, title) most similar to `page_id`, best first. `None` when
+    /// the page has no embedding yet.
+    pub async fn related_pages(&self, database: &Database, page_id: &str, limit: usize) -> AppResult<Option<Vec<(String, String)>>> {
+        let Some(scored) = self.similar_pages(database, page_id, SUGGESTED_LINK_THRESHOLD).await? else {
+            return Ok(None);
+        };
+
+        let mut targets = Vec::new();
+        for (_, id) in scored {
+            if targets.len() >= limit {
+                break;
+            }
+            if let Some(target) = database.get_page(&id).await? {
+                targets.push((target.id, target.title));
             }
         }
-        
-        // Extract entities (capitalized words)
-        let entities: Vec<String> = content
-            .split_whitespace()
-            .filter_map(|word| {
-                let clean_word = word.trim_matches(|c: char| !c.is_alphanumeric());
-                if clean_word.len() > 2 && clean_word.chars().next().unwrap().is_uppercase() {
-                    Some(clean_word.to_lowercase())
-                } else {
-                    None
-                }
+        Ok(Some(targets))
+    }
+
+    /// Other pages at least `threshold` similar to `page_id` as (similarity, id), most
+    /// similar first. `None` when the page has no embedding yet.
+    pub async fn similar_pages(&self, database: &Database, page_id: &str, threshold: f64) -> AppResult<Option<Vec<(f64, String)>>> {
+        let Some(embedding) = database.get_embedding(page_id).await? else {
+            return Ok(None);
+        };
+        let mut scored: Vec<(f64, String)> = self
+            .similar_items(database, &embedding)
+            .await?
+            .into_iter()
+            .filter(|(similarity, id, owner)| {
+                *owner == EmbeddingOwner::Page && id != page_id && *similarity >= threshold
             })
+            .map(|(similarity, id, _)| (similarity, id))
             .collect();
-        
-        // Combine frequency-based and entity-based suggestions
-        let mut suggestions: Vec<String> = word_freq
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+        Ok(Some(scored))
+    }
+
+    /// A concise title for untitled content, written by the local language model when one
+    /// is configured and otherwise taken from the first heading or sentence.
+    pub async fn suggest_title(&self, content: &str) -> AppResult<Option<String>> {
+        let opening = titles::opening(content);
+        if opening.trim().is_empty() {
+            return Ok(None);
+        }
+        match self.complete(llm::title_prompt(&opening), TITLE_MAX_TOKENS).await {
+            Ok(Some(completion)) => {
+                if let Some(title) = titles::clean_title(&completion) {
+                    return Ok(Some(title));
+                }
+            }
+            Ok(None) => {}
+            Err(e) => tracing::warn!("Language model title failed, using extractive title: {}", e),
+        }
+        Ok(titles::derive_title(content))
+    }
+
+    /// Short key phrases of the content as new tags, best first.
+    pub async fn suggest_tags(&self, content: &str) -> AppResult<Vec<String>> {
+        // Tags are a word or two; longer key phrases describe ideas, not topics
+        let suggestions: Vec<String> = keyphrases::extract(content, SUGGESTED_TAG_LIMIT * 4)
             .into_iter()
-            .filter(|(_, count)| *count > 1)
-            .map(|(word, _)| word)
+            .filter(|keyphrase| keyphrase.phrase.split(' ').count() <= 2)
+            .map(|keyphrase| keyphrase.phrase)
+            .take(SUGGESTED_TAG_LIMIT)
             .collect();
         
-        suggestions.extend(entities);
-        suggestions.sort();
-        suggestions.dedup();
-        suggestions.truncate(5);
-        
         Ok(suggestions)
     }
 
-    pub async fn analyze_sentiment(&self, text: &str) -> AppResult<f64> {
-        // Simple sentiment analysis using word lists
-        // In a real implementation, you would use a trained sentiment model
-        
-        let positive_words = [
-            "good", "great", "excellent", "amazing", "wonderful", "fantastic",
-            "love", "like", "enjoy", "happy", "joy", "success", "win", "best",
-            "awesome", "brilliant", "perfect", "outstanding", "superb", "marvelous"
-        ];
-        
-        let negative_words = [
-            "bad", "terrible", "awful", "horrible", "hate", "dislike", "sad",
-            "angry", "frustrated", "fail", "lose", "worst", "problem", "issue",
-            "difficult", "hard", "challenging", "disappointing", "poor", "weak"
-        ];
-        
-        let words: Vec<&str> = text.split_whitespace().collect();
-        let mut positive_count = 0;
-        let mut negative_count = 0;
-        
-        for word in words {
-            let word_lower = word.to_lowercase();
-            if positive_words.contains(&word_lower.as_str()) {
-                positive_count += 1;
-            } else if negative_words.contains(&word_lower.as_str()) {
-                negative_count += 1;
-            }
+    /// The `top_n` key phrases of `content`, for "key ideas" panels.
+    pub async fn extract_keyphrases(&self, content: &str, top_n: usize) -> AppResult<Vec<Keyphrase>> {
+        Ok(keyphrases::extract(content, top_n))
+    }
+
+    /// Tags from `vocabulary` ranked by how similar their embedding is to the content's, so
+    /// suggestions reuse tags already in use instead of inventing new ones. Without an
+    /// embedding model, tags whose name appears in the content are suggested.
+    pub async fn suggest_tags_from_vocabulary(&self, content: &str, vocabulary: &[Tag]) -> AppResult<Vec<String>> {
+        if content.trim().is_empty() || vocabulary.is_empty() {
+            return Ok(Vec::new());
         }
-        
-        let total = positive_count + negative_count;
-        if total == 0 {
-            Ok(0.0)
-        } else {
-            Ok((positive_count as f64 - negative_count as f64) / total as f64)
+        if self.embedding_model_name().is_none() {
+            return Ok(tags_mentioned(content, vocabulary));
         }
+
+        let content_embedding = self.generate_embeddings(content).await?;
+        let mut scored = Vec::new();
+        for tag in vocabulary {
+            // Tag names repeat across calls, so they go through the query cache
+            let tag_embedding = self.query_embedding(&tag_text(tag)).await?;
+            let similarity = self.cosine_similarity(&content_embedding, &tag_embedding);
+            if similarity >= SUGGESTED_TAG_THRESHOLD {
+                scored.push((similarity, tag.name.clone()));
+            }
+        }
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+        Ok(scored.into_iter().take(SUGGESTED_TAG_LIMIT).map(|(_, name)| name).collect())
+    }
+
+    pub async fn analyze_sentiment(&self, text: &str) -> AppResult<f64> {
+        // Word lists for now; a trained sentiment model would slot in here
+        Ok(sentiment::score(text).unwrap_or(0.0))
     }
 
     pub async fn extract_entities(&self, text: &str) -> AppResult<Vec<String>> {
@@ -267,9 +718,62 @@ impl AIService {
         Ok(entities)
     }
 
+    /// Answers `question` from the `top_k` pages most relevant to it, citing them.
+    pub async fn chat_with_notes(&self, database: &Database, question: &str, top_k: usize) -> AppResult<ChatAnswer> {
+        if !self.is_llm_available() {
+            return Err(AppError::ModelNotFound("Chat needs a local language model".to_string()));
+        }
+        let query_embedding = self.query_embedding(question).await?;
+        let mut scored: Vec<(f64, String)> = self
+            .similar_items(database, &query_embedding)
+            .await?
+            .into_iter()
+            .filter(|(score, _, owner)| *owner == EmbeddingOwner::Page && *score >= RELEVANCE_THRESHOLD)
+            .map(|(score, id, _)| (score, id))
+            .collect();
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+
+        let mut pages = Vec::new();
+        for (_, id) in scored {
+            if pages.len() >= top_k {
+                break;
+            }
+            pages.extend(database.get_page(&id).await?);
+        }
+
+        let excerpt_chars = CHAT_CONTEXT_CHARS / pages.len().max(1);
+        let sources: Vec<(String, String)> = pages
+            .iter()
+            .map(|page| (page.title.clone(), page.content.chars().take(excerpt_chars).collect()))
+            .collect();
+        let answer = self.complete(llm::chat_prompt(question, &sources), CHAT_MAX_TOKENS).await?.unwrap_or_default();
+        let citations = llm::cited_sources(&answer, pages.len())
+            .into_iter()
+            .map(|index| ChatCitation { page_id: pages[index].id.clone(), title: pages[index].title.clone() })
+            .collect();
+
+        Ok(ChatAnswer { answer, citations })
+    }
+
+    /// The local language model's answer to `question` from `text` and the sentence it
+    /// quotes as support. `None` without a model or when `text` has no answer.
+    pub async fn answer_from_text(&self, question: &str, text: &str) -> AppResult<Option<(String, Option<String>)>> {
+        let completion = self.complete(llm::page_qa_prompt(question, text), PAGE_QA_MAX_TOKENS).await?;
+        Ok(completion.as_deref().and_then(llm::split_quote))
+    }
+
+    /// Abstractive with the local language model when one is configured, otherwise extractive.
     pub async fn generate_summary(&self, text: &str) -> AppResult<Option<String>> {
+        if text.trim().is_empty() {
+            return Ok(None);
+        }
+        match self.complete(llm::summary_prompt(text), SUMMARY_MAX_TOKENS).await {
+            Ok(Some(summary)) => return Ok(Some(summary)),
+            Ok(None) => {}
+            Err(e) => tracing::warn!("Language model summary failed, using extractive summary: {}", e),
+        }
+
         // Simple extractive summarization
-        // In a real implementation, you would use a summarization model
         
         let sentences: Vec<&str> = text
             .split(|c| c == '.' || c == '!' || c == '?')
@@ -310,7 +814,7 @@ impl AIService {
             .collect();
         
         // Sort by score and take top sentences
-        sentence_scores.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+        sentence_scores.sort_by(|a, b| b.1.total_cmp(&a.1));
         let num_summary_sentences = (sentences.len() / 3).max(1).min(3);
         
         let mut summary_indices: Vec<usize> = sentence_scores
@@ -346,7 +850,67 @@ impl AIService {
         })
     }
 
+    /// Runs OCR through the `tesseract` CLI when it is installed. The image is piped over stdin so
+    /// decrypted content never touches disk. Returns `None` when OCR is unavailable or finds no text.
+    pub async fn ocr_image(&self, image_data: &[u8]) -> AppResult<Option<String>> {
+        use tokio::io::AsyncWriteExt;
+
+        let child = tokio::process::Command::new("tesseract")
+            .args(["stdin", "stdout"])
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .kill_on_drop(true)
+            .spawn();
+
+        let mut child = match child {
+            Ok(child) => child,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(image_data).await?;
+        }
+
+        let output = child.wait_with_output().await?;
+        if !output.status.success() {
+            return Err(AppError::AIProcessing(format!(
+                "OCR failed: {}",
+                String::from_utf8_lossy(&output.stderr)
+            )));
+        }
+
+        let text = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        Ok(if text.is_empty() { None } else { Some(text) })
+    }
+
     // Helper methods
+    /// (similarity, id, owner) for notes and pages above the relevance threshold. Uses KNN in
+    /// SQLite when the vector index is enabled, otherwise compares against every stored embedding.
+    async fn similar_items(&self, database: &Database, query_embedding: &[f32]) -> AppResult<Vec<(f64, String, EmbeddingOwner)>> {
+        let Some(model) = self.embedding_model_name() else {
+            return Ok(Vec::new());
+        };
+        let scored: Vec<(f64, String, EmbeddingOwner)> = if database.has_vector_index() {
+            database
+                .nearest_embeddings(query_embedding, model, VECTOR_KNN_LIMIT)
+                .await?
+                .into_iter()
+                .map(|(id, owner, similarity)| (similarity, id, owner))
+                .collect()
+        } else {
+            database
+                .get_all_embeddings(model)
+                .await?
+                .into_iter()
+                .map(|(id, owner, embedding)| (self.cosine_similarity(query_embedding, &embedding), id, owner))
+                .collect()
+        };
+
+        Ok(scored.into_iter().filter(|(similarity, _, _)| *similarity > RELEVANCE_THRESHOLD).collect())
+    }
+
     fn cosine_similarity(&self, a: &[f32], b: &[f32]) -> f64 {
         if a.len() != b.len() || a.is_empty() {
             return 0.0;
@@ -417,29 +981,6 @@ impl AIService {
         common_words.contains(&word.to_lowercase().as_str())
     }
 
-    // Model download methods (placeholders)
-    async fn download_whisper_model(&self, _model: &WhisperModel, _path: &Path) -> AppResult<()> {
-        // In a real implementation, you would download the model from Hugging Face or another source
-        // For now, we'll create a placeholder file
-        std::fs::create_dir_all(_path.parent().unwrap())?;
-        std::fs::write(_path, b"placeholder whisper model")?;
-        Ok(())
-    }
-
-    async fn download_embedding_model(&self, _model: &EmbeddingModel, _models_path: &Path) -> AppResult<()> {
-        // In a real implementation, you would download the model and tokenizer
-        // For now, we'll create placeholder files
-        std::fs::create_dir_all(_models_path)?;
-        
-        let model_path = _models_path.join(format!("embedding-{}.safetensors", _model.model_name()));
-        let tokenizer_path = _models_path.join(format!("tokenizer-{}.json", _model.model_name()));
-        
-        std::fs::write(model_path, b"placeholder embedding model")?;
-        std::fs::write(tokenizer_path, r#"{"version": "1.0", "truncation": null, "padding": null}"#)?;
-        
-        Ok(())
-    }
-
     pub fn is_whisper_available(&self) -> bool {
         self.whisper_model.is_some()
     }
@@ -455,4 +996,191 @@ impl AIService {
     pub fn get_embedding_model(&self) -> Option<&EmbeddingModel> {
         self.embedding_model.as_ref()
     }
-}
\ No newline at end of file
+
+    /// Name of the loaded embedding model, if embeddings can be generated. Stored alongside
+    /// each embedding so vectors from different models are never compared.
+    pub fn embedding_model_name(&self) -> Option<&'static str> {
+        match &self.embedding_model {
+            Some(model) if self.is_embedding_available() => Some(model.model_name()),
+            _ => None,
+        }
+    }
+}
+
+pub fn join_words(words: &[TranscribedWord]) -> String {
+    words.iter().map(|word| word.text.trim()).filter(|text| !text.is_empty()).collect::<Vec<_>>().join(" ")
+}
+
+/// Moves word times from the start of a segment to the start of the recording, dropping
+/// words without text.
+pub fn offset_words(words: Vec<TranscribedWord>, offset_secs: f64) -> Vec<TranscribedWord> {
+    words
+        .into_iter()
+        .filter(|word| !word.text.trim().is_empty())
+        .map(|word| TranscribedWord {
+            text: word.text.trim().to_string(),
+            start_secs: word.start_secs + offset_secs,
+            end_secs: word.end_secs + offset_secs,
+            confidence: word.confidence,
+        })
+        .collect()
+}
+
+/// Hex SHA-256 of embedded content, stored to skip re-embedding unchanged text.
+pub fn content_hash(content: &str) -> String {
+    Sha256::digest(content.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// What a tag is embedded as; separators read as spaces and the description adds context.
+fn tag_text(tag: &Tag) -> String {
+    let name = tag.name.replace(['-', '_', '/'], " ");
+    match tag.description.as_deref().map(str::trim).filter(|d| !d.is_empty()) {
+        Some(description) => format!("{}: {}", name, description),
+        None => name,
+    }
+}
+
+/// Tags whose words all appear in `content`, in vocabulary order.
+fn tags_mentioned(content: &str, vocabulary: &[Tag]) -> Vec<String> {
+    let words: std::collections::HashSet<String> = content
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    vocabulary
+        .iter()
+        .filter(|tag| {
+            let mut tag_words = tag.name.split(|c: char| !c.is_alphanumeric()).filter(|word| !word.is_empty()).peekable();
+            tag_words.peek().is_some() && tag_words.all(|word| words.contains(&word.to_lowercase()))
+        })
+        .take(SUGGESTED_TAG_LIMIT)
+        .map(|tag| tag.name.clone())
+        .collect()
+}
+
+// Model downloads; mock mode writes placeholder files instead of fetching weights
+async fn download_whisper_model(mode: AIMode, model: &WhisperModel, path: &Path) -> AppResult<()> {
+    let models_path = path.parent().unwrap_or(Path::new("."));
+    if mode == AIMode::Real {
+        return model_downloads::download(models_path, &model_downloads::whisper_id(model)).await;
+    }
+    std::fs::create_dir_all(models_path)?;
+    std::fs::write(path, b"placeholder whisper model")?;
+    Ok(())
+}
+
+async fn download_embedding_model(mode: AIMode, model: &EmbeddingModel, models_path: &Path) -> AppResult<()> {
+    if mode == AIMode::Real {
+        return model_downloads::download(models_path, &model_downloads::embedding_id(model)).await;
+    }
+    std::fs::create_dir_all(models_path)?;
+
+    let model_path = models_path.join(format!("embedding-{}.safetensors", model.model_name()));
+    let tokenizer_path = models_path.join(format!("tokenizer-{}.json", model.model_name()));
+
+    std::fs::write(model_path, b"placeholder embedding model")?;
+    std::fs::write(tokenizer_path, r#"{"version": "1.0", "truncation": null, "padding": null}"#)?;
+
+    Ok(())
+}
+
+/// Probes which GPU backends this build and machine support and picks the device for
+/// `preference`. An unavailable GPU falls back to the CPU instead of failing, with the
+/// reason recorded for `get_ai_device_info`.
+fn select_device(preference: AiDevicePreference) -> (Device, AiDeviceInfo) {
+    let cuda = if candle_core::utils::cuda_is_available() {
+        Device::new_cuda(0).map_err(|e| e.to_string())
+    } else {
+        Err("not compiled with CUDA support".to_string())
+    };
+    let metal = if candle_core::utils::metal_is_available() {
+        Device::new_metal(0).map_err(|e| e.to_string())
+    } else {
+        Err("not compiled with Metal support".to_string())
+    };
+    let mut info = AiDeviceInfo {
+        preference,
+        active: AiDeviceKind::Cpu,
+        cuda_available: cuda.is_ok(),
+        metal_available: metal.is_ok(),
+        fallback_reason: None,
+    };
+
+    let device = match (preference, cuda, metal) {
+        (AiDevicePreference::Cpu, _, _) => Device::Cpu,
+        (AiDevicePreference::Cuda | AiDevicePreference::Auto, Ok(device), _) => {
+            info.active = AiDeviceKind::Cuda;
+            device
+        }
+        (AiDevicePreference::Metal | AiDevicePreference::Auto, _, Ok(device)) => {
+            info.active = AiDeviceKind::Metal;
+            device
+        }
+        (AiDevicePreference::Auto, _, _) => Device::Cpu,
+        (AiDevicePreference::Cuda, Err(e), _) => {
+            info.fallback_reason = Some(format!("CUDA unavailable: {}", e));
+            Device::Cpu
+        }
+        (AiDevicePreference::Metal, _, Err(e)) => {
+            info.fallback_reason = Some(format!("Metal unavailable: {}", e));
+            Device::Cpu
+        }
+    };
+
+    match &info.fallback_reason {
+        Some(reason) => tracing::warn!("Using CPU for AI inference: {}", reason),
+        None => tracing::info!("Using {:?} for AI inference", info.active),
+    }
+    (device, info)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn tag(name: &str) -> Tag {
+        Tag {
+            id: name.to_string(),
+            name: name.to_string(),
+            color: "#888888".to_string(),
+            description: None,
+            usage_count: 1,
+            created_at: Utc::now(),
+            last_used: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_real_mode_refuses_simulated_output() {
+        let mut real = AIService::new(AiDevicePreference::Auto, PathBuf::new(), AIMode::Real).unwrap();
+        real.whisper_model = Some(WhisperModel::Base);
+        assert!(real.transcribe_audio(&[0; 64_000], None).await.is_err());
+
+        let mut mock = AIService::new(AiDevicePreference::Auto, PathBuf::new(), AIMode::Mock).unwrap();
+        mock.whisper_model = Some(WhisperModel::Base);
+        assert_eq!(mock.transcribe_audio(&[0; 64_000], None).await.unwrap(), "the quick brown fox jumps over");
+    }
+
+    #[tokio::test]
+    async fn test_word_timestamps() {
+        let mut mock = AIService::new(AiDevicePreference::Auto, PathBuf::new(), AIMode::Mock).unwrap();
+        mock.whisper_model = Some(WhisperModel::Base);
+        let words = mock.transcribe_words(&[0; 64_000], None).await.unwrap();
+
+        assert_eq!(words.len(), 6);
+        assert!(words.windows(2).all(|pair| pair[0].end_secs <= pair[1].start_secs));
+        assert!(words.last().unwrap().end_secs <= 2.0);
+
+        let word = |text: &str, start_secs, end_secs| TranscribedWord { text: text.to_string(), start_secs, end_secs, confidence: 0.5 };
+        let offset = offset_words(vec![word(" hello", 0.0, 0.4), word(" ", 0.4, 0.5), word("world", 0.5, 1.0)], 10.0);
+        assert_eq!(offset, vec![word("hello", 10.0, 10.4), word("world", 10.5, 11.0)]);
+    }
+
+    #[test]
+    fn test_tags_mentioned() {
+        let vocabulary = vec![tag("budget"), tag("project-apollo"), tag("travel"), tag("q3")];
+        let content = "Apollo project kickoff: the budget for Q3 is approved.";
+        assert_eq!(tags_mentioned(content, &vocabulary), vec!["budget", "project-apollo", "q3"]);
+    }
+}
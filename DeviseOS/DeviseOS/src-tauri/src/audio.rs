@@ -0,0 +1,212 @@
+use std::path::Path;
+use crate::{
+    AppError, AppResult,
+    models::{VoiceAudio, VoiceMetadata, Waveform},
+    database::Database,
+    importer, voice_codec,
+};
+
+const BITS_PER_SAMPLE: u16 = 16;
+/// Bars in a stored waveform; enough for a full-width player, small enough for metadata.
+pub const WAVEFORM_PEAKS: usize = 200;
+
+/// The format of a WAV file from its `fmt ` chunk, and its samples.
+pub struct WavInfo<'a> {
+    pub pcm: bool, // Uncompressed integer samples rather than e.g. A-law or float
+    pub channels: u16,
+    pub sample_rate: u32,
+    pub bits_per_sample: u16,
+    pub data: &'a [u8],
+}
+
+/// A recording as a browser can play it. Bare PCM, including Opus recordings decoded by
+/// `voice_codec::to_pcm`, gets a WAV header; ones that already have a container
+/// are returned as they are.
+pub fn playable(annotation_id: &str, audio: Vec<u8>, duration: f64, metadata: &VoiceMetadata) -> VoiceAudio {
+    let (mime_type, data) = if audio.starts_with(b"RIFF") {
+        ("audio/wav".to_string(), audio)
+    } else if matches!(metadata.format.as_str(), "wav" | voice_codec::PCM_FORMAT) {
+        ("audio/wav".to_string(), wav(&audio, metadata.sample_rate, metadata.channels as u16))
+    } else {
+        (mime_type(&metadata.format), audio)
+    };
+    VoiceAudio { annotation_id: annotation_id.to_string(), mime_type, duration, data }
+}
+
+/// Reads a RIFF/WAVE header, walking its chunks rather than assuming the canonical 44-byte
+/// layout. `None` when `audio` isn't a WAV file or has no `fmt ` before its `data`.
+pub fn parse_wav(audio: &[u8]) -> Option<WavInfo<'_>> {
+    if audio.len() < 12 || &audio[..4] != b"RIFF" || &audio[8..12] != b"WAVE" {
+        return None;
+    }
+    let mut format = None;
+    let mut offset = 12;
+    while offset + 8 <= audio.len() {
+        let id = &audio[offset..offset + 4];
+        let size = u32::from_le_bytes(audio[offset + 4..offset + 8].try_into().ok()?) as usize;
+        let body = offset + 8;
+        // Streamed files can leave the size unset, so the data runs to the end
+        let end = body.saturating_add(size).min(audio.len());
+        match id {
+            b"fmt " if end - body >= 16 => {
+                let field = |at: usize| u16::from_le_bytes([audio[body + at], audio[body + at + 1]]);
+                let tag = field(0);
+                // WAVE_FORMAT_EXTENSIBLE keeps the real tag in its sub-format GUID
+                let tag = if tag == 0xFFFE && end - body >= 26 { field(24) } else { tag };
+                let sample_rate = u32::from_le_bytes(audio[body + 4..body + 8].try_into().ok()?);
+                format = Some((tag == 1, field(2), sample_rate, field(14)));
+            }
+            b"data" => {
+                let (pcm, channels, sample_rate, bits_per_sample) = format?;
+                return Some(WavInfo { pcm, channels, sample_rate, bits_per_sample, data: &audio[body..end] });
+            }
+            _ => {}
+        }
+        offset = end + (size & 1); // Chunks are padded to an even length
+    }
+    None
+}
+
+/// `VoiceMetadata::format` for a MIME type, e.g. `mp3` for `audio/mpeg`.
+pub fn format_for_mime(mime_type: &str) -> String {
+    match mime_type {
+        "audio/wav" | "audio/x-wav" | "audio/wave" => "wav".to_string(),
+        "audio/mpeg" => "mp3".to_string(),
+        "audio/mp4" | "audio/m4a" | "audio/x-m4a" => "m4a".to_string(),
+        other => other.rsplit('/').next().unwrap_or(other).to_string(),
+    }
+}
+
+/// The format of recorded bytes that carry a recognisable container, such as a WAV or
+/// MP3 header. `None` for bare samples.
+pub fn sniff_format(audio: &[u8]) -> Option<String> {
+    let mime_type = importer::sniff_mime_type(audio, Path::new(""));
+    matches!(mime_type.split('/').next(), Some("audio" | "video")).then(|| format_for_mime(&mime_type))
+}
+
+/// `pcm`, 16-bit little-endian samples, in a WAV container.
+pub fn wav(pcm: &[u8], sample_rate: u32, channels: u16) -> Vec<u8> {
+    let block_align = channels * BITS_PER_SAMPLE / 8;
+    let byte_rate = sample_rate * block_align as u32;
+    let data_len = pcm.len() as u32;
+
+    let mut wav = Vec::with_capacity(44 + pcm.len());
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    wav.extend_from_slice(&channels.to_le_bytes());
+    wav.extend_from_slice(&sample_rate.to_le_bytes());
+    wav.extend_from_slice(&byte_rate.to_le_bytes());
+    wav.extend_from_slice(&block_align.to_le_bytes());
+    wav.extend_from_slice(&BITS_PER_SAMPLE.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+    wav.extend_from_slice(pcm);
+    wav
+}
+
+/// Loudest sample in each of `count` equal slices of 16-bit PCM, from 0 to 1, to two
+/// decimal places. Shorter recordings get one peak per sample.
+pub fn peaks(audio: &[u8], count: usize) -> Vec<f32> {
+    let pcm = parse_wav(audio).map_or(audio, |wav| wav.data);
+    let samples: Vec<i16> = pcm.chunks_exact(2).map(|pair| i16::from_le_bytes([pair[0], pair[1]])).collect();
+    if samples.is_empty() || count == 0 {
+        return Vec::new();
+    }
+    let per_peak = samples.len().div_ceil(count);
+    samples
+        .chunks(per_peak)
+        .map(|slice| {
+            let loudest = slice.iter().map(|sample| sample.unsigned_abs()).max().unwrap_or(0);
+            (loudest as f32 / i16::MAX as f32).min(1.0)
+        })
+        .map(|peak| (peak * 100.0).round() / 100.0)
+        .collect()
+}
+
+/// The annotation's waveform. Recordings stored before peaks were kept have theirs
+/// computed now and saved.
+pub async fn waveform(database: &Database, annotation_id: &str) -> AppResult<Waveform> {
+    let annotation = database.get_voice_annotation_summary(annotation_id).await?
+        .ok_or_else(|| AppError::NotFound(format!("Voice annotation with id {} not found", annotation_id)))?;
+    let mut peaks = annotation.metadata.peaks;
+    if peaks.is_empty() {
+        if let Some(audio) = database.get_voice_annotation_pcm(annotation_id).await? {
+            peaks = self::peaks(&audio.audio_data, WAVEFORM_PEAKS);
+            database.set_voice_peaks(annotation_id, &peaks).await?;
+        }
+    }
+    Ok(Waveform { annotation_id: annotation.id, duration: annotation.duration, peaks })
+}
+
+fn mime_type(format: &str) -> String {
+    match format {
+        "opus" | "ogg" => "audio/ogg".to_string(),
+        "mp3" => "audio/mpeg".to_string(),
+        "m4a" | "aac" => "audio/mp4".to_string(),
+        other => format!("audio/{}", other),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pcm_is_wrapped_in_wav() {
+        let pcm = vec![0u8; 64_000];
+        let audio = playable("a", pcm, 2.0, &VoiceMetadata::default());
+        assert_eq!(audio.mime_type, "audio/wav");
+        assert_eq!(audio.data.len(), 44 + 64_000);
+        assert_eq!(&audio.data[..4], b"RIFF");
+        assert_eq!(u32::from_le_bytes(audio.data[28..32].try_into().unwrap()), 32_000); // Byte rate
+
+        let again = playable("a", audio.data.clone(), 2.0, &VoiceMetadata::default());
+        assert_eq!(again.data, audio.data);
+    }
+
+    #[test]
+    fn test_peaks() {
+        let pcm: Vec<u8> = [0i16, 100, -16384, 50, i16::MIN, 0].iter().flat_map(|s| s.to_le_bytes()).collect();
+        assert_eq!(peaks(&pcm, 3), vec![0.0, 0.5, 1.0]);
+        assert_eq!(peaks(&wav(&pcm, 16_000, 1), 2), vec![0.5, 1.0]);
+        assert_eq!(peaks(&pcm, 100).len(), 6);
+        assert!(peaks(&[], 10).is_empty());
+    }
+
+    #[test]
+    fn test_other_formats_pass_through() {
+        let metadata = VoiceMetadata { format: "mp3".to_string(), ..VoiceMetadata::default() };
+        let audio = playable("a", b"ID3".to_vec(), 1.0, &metadata);
+        assert_eq!(audio.mime_type, "audio/mpeg");
+        assert_eq!(audio.data, b"ID3");
+
+        let opus = VoiceMetadata { format: voice_codec::OPUS_FORMAT.to_string(), ..VoiceMetadata::default() };
+        assert_eq!(playable("a", b"OggS".to_vec(), 0.01, &opus).mime_type, "audio/ogg");
+    }
+
+    #[test]
+    fn test_parse_wav_walks_chunks() {
+        let pcm: Vec<u8> = (0..100i16).flat_map(|s| s.to_le_bytes()).collect();
+        let plain = wav(&pcm, 44_100, 2);
+        let info = parse_wav(&plain).unwrap();
+        assert!(info.pcm);
+        assert_eq!((info.channels, info.sample_rate, info.bits_per_sample), (2, 44_100, 16));
+        assert_eq!(info.data, &pcm[..]);
+
+        // A LIST chunk between `fmt ` and `data`, as many editors write
+        let mut listed = plain[..36].to_vec();
+        listed.extend_from_slice(b"LIST");
+        listed.extend_from_slice(&3u32.to_le_bytes());
+        listed.extend_from_slice(b"abc\0");
+        listed.extend_from_slice(&plain[36..]);
+        assert_eq!(parse_wav(&listed).unwrap().data, &pcm[..]);
+
+        assert!(parse_wav(b"ID3\x04 not a wav").is_none());
+        assert_eq!(format_for_mime("audio/mpeg"), "mp3");
+        assert_eq!(sniff_format(b"OggS\0\x02").as_deref(), Some("ogg"));
+        assert_eq!(sniff_format(&pcm), None);
+    }
+}
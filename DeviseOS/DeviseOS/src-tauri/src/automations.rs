@@ -0,0 +1,173 @@
+use std::sync::Arc;
+use std::time::Duration;
+use chrono::Utc;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use crate::{
+    AppError, AppResult,
+    models::{Automation, AutomationAction, AutomationEvent, AutomationRun, CreatePageRequest},
+    database::Database,
+    scripting::ScriptRunner,
+};
+
+const ACTION_TIMEOUT: Duration = Duration::from_secs(30);
+const MAX_OUTPUT_LEN: usize = 4096;
+
+pub struct AutomationEngine {
+    http_client: reqwest::Client,
+    scripts: Arc<ScriptRunner>,
+}
+
+impl AutomationEngine {
+    pub fn new(scripts: Arc<ScriptRunner>) -> AppResult<Self> {
+        let http_client = reqwest::Client::builder()
+            .timeout(ACTION_TIMEOUT)
+            .build()
+            .map_err(|e| AppError::Network(format!("Failed to build HTTP client: {}", e)))?;
+
+        Ok(Self { http_client, scripts })
+    }
+
+    /// Rejects actions that could never run, checked when an automation is saved so a bad
+    /// script name is reported to the user rather than in the run log.
+    pub fn check_action(&self, action: &AutomationAction) -> AppResult<()> {
+        if let AutomationAction::RunScript { script, .. } = action {
+            self.scripts.executable_path(script)?;
+        }
+        Ok(())
+    }
+
+    /// Runs every enabled automation whose trigger matches the event and logs each execution.
+    /// One automation failing to run or be logged doesn't stop the rest.
+    pub async fn dispatch(&self, database: &Database, event: &AutomationEvent) -> AppResult<Vec<AutomationRun>> {
+        let automations = database.get_automations().await?;
+        let mut runs = Vec::new();
+
+        for automation in automations.iter().filter(|a| a.enabled && a.trigger.matches(event)) {
+            match self.execute(database, automation, event).await {
+                Ok(run) => runs.push(run),
+                Err(e) => tracing::warn!("Failed to run automation {} for {}: {}", automation.id, event.kind(), e),
+            }
+        }
+
+        Ok(runs)
+    }
+
+    /// Executes a single automation regardless of its trigger, e.g. for a "test run" button.
+    pub async fn execute(&self, database: &Database, automation: &Automation, event: &AutomationEvent) -> AppResult<AutomationRun> {
+        let result = match &automation.action {
+            AutomationAction::RunScript { script, args } => self.run_script(script, args, event).await,
+            AutomationAction::Webhook { url } => self.call_webhook(url, automation, event).await,
+            AutomationAction::CreatePage { notebook_id, section_id, title_template, content_template } => {
+                let request = CreatePageRequest {
+                    notebook_id: notebook_id.clone(),
+                    section_id: section_id.clone(),
+                    parent_page_id: None,
+                    title: render_template(title_template, event),
+                    content: render_template(content_template, event),
+                    tags: Vec::new(),
+                };
+                // Pages created here are not dispatched again, so automations can't loop
+                database.create_page(request).await
+                    .map(|page| format!("Created page {}", page.id))
+            }
+        };
+
+        let (success, output) = match result {
+            Ok(output) => (true, output),
+            Err(e) => {
+                tracing::warn!("Automation {} failed: {}", automation.id, e);
+                (false, e.to_string())
+            }
+        };
+
+        database.record_automation_run(&automation.id, event, success, Some(truncate_output(output))).await
+    }
+
+    async fn run_script(&self, script: &str, args: &[String], event: &AutomationEvent) -> AppResult<String> {
+        let path = self.scripts.executable_path(script)?;
+        if !path.is_file() {
+            return Err(AppError::NotFound(format!("Script not found: {}", script)));
+        }
+
+        // The event is passed as JSON on stdin so scripts don't need to parse arguments
+        let payload = serde_json::to_vec(event)?;
+        let mut child = Command::new(&path)
+            .args(args)
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?;
+
+        let stdin = child.stdin.take();
+        let run = async move {
+            if let Some(mut stdin) = stdin {
+                // A script that exits without reading the event hasn't failed
+                match stdin.write_all(&payload).await {
+                    Err(e) if e.kind() != std::io::ErrorKind::BrokenPipe => return Err(e),
+                    _ => {}
+                }
+            }
+            child.wait_with_output().await
+        };
+
+        let output = tokio::time::timeout(ACTION_TIMEOUT, run)
+            .await
+            .map_err(|_| AppError::Timeout(format!("Script {} timed out", script)))??;
+
+        if output.status.success() {
+            Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+        } else {
+            Err(AppError::InvalidOperation(format!(
+                "Script exited with {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr)
+            )))
+        }
+    }
+
+    async fn call_webhook(&self, url: &str, automation: &Automation, event: &AutomationEvent) -> AppResult<String> {
+        if !url.starts_with("https://") && !url.starts_with("http://") {
+            return Err(AppError::InvalidFormat(format!("Invalid webhook URL: {}", url)));
+        }
+
+        let body = serde_json::json!({
+            "automation_id": automation.id,
+            "automation_name": automation.name,
+            "event": event,
+            "fired_at": Utc::now().to_rfc3339(),
+        });
+
+        let response = self.http_client
+            .post(url)
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| AppError::Network(format!("Webhook request failed: {}", e)))?;
+
+        let status = response.status();
+        if status.is_success() {
+            Ok(format!("Webhook responded with {}", status))
+        } else {
+            Err(AppError::Network(format!("Webhook responded with {}", status)))
+        }
+    }
+}
+
+fn render_template(template: &str, event: &AutomationEvent) -> String {
+    template
+        .replace("{{title}}", event.title())
+        .replace("{{date}}", &Utc::now().format("%Y-%m-%d").to_string())
+}
+
+fn truncate_output(mut output: String) -> String {
+    if output.len() > MAX_OUTPUT_LEN {
+        let mut end = MAX_OUTPUT_LEN;
+        while !output.is_char_boundary(end) {
+            end -= 1;
+        }
+        output.truncate(end);
+    }
+    output
+}
@@ -0,0 +1,243 @@
+use std::io::Read;
+use std::path::Path;
+use base64::{Engine as _, engine::general_purpose};
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use crate::{
+    AppError, AppResult,
+    models::{BundleManifest, InstalledBundle},
+    database::Database,
+};
+
+pub const BUNDLE_FORMAT_VERSION: u32 = 1;
+const MANIFEST_FILE: &str = "bundle.json";
+const MAX_BUNDLE_SIZE: u64 = 20 * 1024 * 1024; // 20MB
+/// Limits on what a `.zip` bundle may expand to, so a small archive can't unpack into
+/// gigabytes. The manifest itself is held to `MAX_BUNDLE_SIZE` like a bare one.
+const MAX_ARCHIVE_ENTRIES: usize = 100;
+const MAX_UNCOMPRESSED_SIZE: u64 = 50 * 1024 * 1024; // 50MB
+const TRUSTED_KEYS_SETTING: &str = "trusted_bundle_keys";
+
+/// Reads, verifies and installs a bundle. Unsigned bundles need explicit consent; signed
+/// bundles are marked trusted when their key is listed in the `trusted_bundle_keys` setting.
+pub async fn install_from_path(database: &Database, path: &Path, allow_unsigned: bool) -> AppResult<InstalledBundle> {
+    let manifest = read_bundle(path)?;
+    let signer_key = verify_signature(&manifest)?;
+
+    if signer_key.is_none() && !allow_unsigned {
+        return Err(AppError::PermissionDenied(format!("Bundle {} is not signed", manifest.id)));
+    }
+
+    let trusted_keys: Vec<String> = match database.get_setting(TRUSTED_KEYS_SETTING).await? {
+        Some(value) => serde_json::from_str(&value)?,
+        None => Vec::new(),
+    };
+    let trusted = signer_key
+        .as_ref()
+        .map(|key| trusted_keys.contains(key))
+        .unwrap_or(false);
+
+    database.install_bundle(&manifest, signer_key, trusted).await
+}
+
+/// Reads a bundle from either a bare `.json` manifest or a `.zip` containing `bundle.json`.
+pub fn read_bundle(path: &Path) -> AppResult<BundleManifest> {
+    let size = std::fs::metadata(path)?.len();
+    if size > MAX_BUNDLE_SIZE {
+        return Err(AppError::InvalidFormat(format!("Bundle is too large ({} bytes)", size)));
+    }
+
+    let is_zip = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.eq_ignore_ascii_case("zip"))
+        .unwrap_or(false);
+
+    let manifest_json = if is_zip {
+        let file = std::fs::File::open(path)?;
+        let mut archive = zip::ZipArchive::new(file)
+            .map_err(|e| AppError::InvalidFormat(format!("Invalid bundle archive: {}", e)))?;
+        check_archive_limits(&mut archive)?;
+        let entry = archive
+            .by_name(MANIFEST_FILE)
+            .map_err(|_| AppError::InvalidFormat(format!("Bundle archive has no {}", MANIFEST_FILE)))?;
+
+        // Sizes in the archive are only claims, so the read itself is capped too
+        let mut json = String::new();
+        entry.take(MAX_BUNDLE_SIZE + 1).read_to_string(&mut json)?;
+        if json.len() as u64 > MAX_BUNDLE_SIZE {
+            return Err(AppError::InvalidFormat(format!("{} is larger than {} bytes", MANIFEST_FILE, MAX_BUNDLE_SIZE)));
+        }
+        json
+    } else {
+        std::fs::read_to_string(path)?
+    };
+
+    let manifest: BundleManifest = serde_json::from_str(&manifest_json)?;
+    validate_manifest(&manifest)?;
+    Ok(manifest)
+}
+
+fn check_archive_limits<R: std::io::Read + std::io::Seek>(archive: &mut zip::ZipArchive<R>) -> AppResult<()> {
+    if archive.len() > MAX_ARCHIVE_ENTRIES {
+        return Err(AppError::InvalidFormat(format!(
+            "Bundle archive has {} entries (at most {})",
+            archive.len(), MAX_ARCHIVE_ENTRIES
+        )));
+    }
+    let mut total: u64 = 0;
+    for index in 0..archive.len() {
+        let entry = archive
+            .by_index(index)
+            .map_err(|e| AppError::InvalidFormat(format!("Invalid bundle archive: {}", e)))?;
+        total = total.saturating_add(entry.size());
+    }
+    if total > MAX_UNCOMPRESSED_SIZE {
+        return Err(AppError::InvalidFormat(format!("Bundle archive expands to {} bytes (at most {})", total, MAX_UNCOMPRESSED_SIZE)));
+    }
+    Ok(())
+}
+
+pub fn validate_manifest(manifest: &BundleManifest) -> AppResult<()> {
+    if manifest.format_version != BUNDLE_FORMAT_VERSION {
+        return Err(AppError::InvalidFormat(format!(
+            "Unsupported bundle format version {} (expected {})",
+            manifest.format_version, BUNDLE_FORMAT_VERSION
+        )));
+    }
+
+    let valid_id = !manifest.id.is_empty()
+        && manifest.id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.');
+    if !valid_id {
+        return Err(AppError::InvalidFormat(format!("Invalid bundle id: {}", manifest.id)));
+    }
+
+    if manifest.name.trim().is_empty() {
+        return Err(AppError::InvalidFormat("Bundle name must not be empty".to_string()));
+    }
+
+    if manifest.templates.is_empty() && manifest.snippets.is_empty() && manifest.prompts.is_empty() {
+        return Err(AppError::InvalidFormat("Bundle contains no templates, snippets or prompts".to_string()));
+    }
+
+    Ok(())
+}
+
+/// Verifies the bundle signature. Returns the signer's public key for signed bundles,
+/// `None` for unsigned ones, and an error if a signature is present but doesn't match.
+pub fn verify_signature(manifest: &BundleManifest) -> AppResult<Option<String>> {
+    let Some(signature) = &manifest.signature else {
+        return Ok(None);
+    };
+
+    let key_bytes: [u8; 32] = decode_base64(&signature.public_key)?
+        .try_into()
+        .map_err(|_| AppError::Encryption("Bundle public key must be 32 bytes".to_string()))?;
+    let signature_bytes: [u8; 64] = decode_base64(&signature.signature)?
+        .try_into()
+        .map_err(|_| AppError::Encryption("Bundle signature must be 64 bytes".to_string()))?;
+
+    let verifying_key = VerifyingKey::from_bytes(&key_bytes)
+        .map_err(|e| AppError::Encryption(format!("Invalid bundle public key: {}", e)))?;
+
+    verifying_key
+        .verify(&signing_payload(manifest)?, &Signature::from_bytes(&signature_bytes))
+        .map_err(|_| AppError::Encryption(format!("Signature verification failed for bundle {}", manifest.id)))?;
+
+    Ok(Some(signature.public_key.clone()))
+}
+
+/// The bytes covered by the signature: the manifest serialized with `signature` set to null.
+pub fn signing_payload(manifest: &BundleManifest) -> AppResult<Vec<u8>> {
+    let mut unsigned = manifest.clone();
+    unsigned.signature = None;
+    Ok(serde_json::to_vec(&unsigned)?)
+}
+
+fn decode_base64(value: &str) -> AppResult<Vec<u8>> {
+    general_purpose::STANDARD
+        .decode(value)
+        .map_err(|e| AppError::Encryption(format!("Failed to decode base64: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{BundleSignature, BundleTemplate};
+    use ed25519_dalek::{Signer, SigningKey};
+
+    fn sample_manifest() -> BundleManifest {
+        BundleManifest {
+            format_version: BUNDLE_FORMAT_VERSION,
+            id: "zettelkasten-starter".to_string(),
+            name: "Zettelkasten starter".to_string(),
+            version: "1.0.0".to_string(),
+            author: Some("Community".to_string()),
+            description: None,
+            templates: vec![BundleTemplate {
+                name: "Permanent note".to_string(),
+                description: None,
+                title: "{{date}} ".to_string(),
+                content: "## Idea\n\n## References\n".to_string(),
+                tags: vec!["permanent".to_string()],
+            }],
+            snippets: Vec::new(),
+            prompts: Vec::new(),
+            signature: None,
+        }
+    }
+
+    fn sign(manifest: &mut BundleManifest, key: &SigningKey) {
+        let signature = key.sign(&signing_payload(manifest).unwrap());
+        manifest.signature = Some(BundleSignature {
+            public_key: general_purpose::STANDARD.encode(key.verifying_key().as_bytes()),
+            signature: general_purpose::STANDARD.encode(signature.to_bytes()),
+        });
+    }
+
+    #[test]
+    fn test_signed_bundle_verifies() {
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let mut manifest = sample_manifest();
+        sign(&mut manifest, &key);
+
+        let signer = verify_signature(&manifest).unwrap();
+        assert_eq!(signer, manifest.signature.as_ref().map(|s| s.public_key.clone()));
+    }
+
+    #[test]
+    fn test_tampered_bundle_is_rejected() {
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let mut manifest = sample_manifest();
+        sign(&mut manifest, &key);
+
+        manifest.templates[0].content.push_str("injected");
+        assert!(verify_signature(&manifest).is_err());
+    }
+
+    #[test]
+    fn test_unsigned_bundle_has_no_signer() {
+        assert!(verify_signature(&sample_manifest()).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_archive_limits() {
+        use std::io::{Cursor, Write};
+        let archive = |entries: usize| {
+            let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+            for index in 0..entries {
+                writer.start_file(format!("{}.txt", index), zip::write::SimpleFileOptions::default()).unwrap();
+                writer.write_all(b"x").unwrap();
+            }
+            zip::ZipArchive::new(Cursor::new(writer.finish().unwrap().into_inner())).unwrap()
+        };
+        assert!(check_archive_limits(&mut archive(3)).is_ok());
+        assert!(check_archive_limits(&mut archive(MAX_ARCHIVE_ENTRIES + 1)).is_err());
+    }
+
+    #[test]
+    fn test_invalid_bundle_id_is_rejected() {
+        let mut manifest = sample_manifest();
+        manifest.id = "../escape".to_string();
+        assert!(validate_manifest(&manifest).is_err());
+    }
+}
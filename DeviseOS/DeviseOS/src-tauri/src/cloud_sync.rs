@@ -0,0 +1,470 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use tokio::sync::Mutex;
+use crate::{
+    AppError, AppResult,
+    models::{StoredValue, SyncChange, SyncConfig, SyncFailure, SyncReport, SyncStatus, SyncedRow},
+    encryption::EncryptionManager,
+    database::Database,
+    sync_protocol::{winning_revision, SyncDocument, SyncDocumentKind},
+};
+
+pub const SYNC_CONFIG_KEY: &str = "sync_config";
+
+/// Marker in the sync folder naming the vault key its documents are encrypted with.
+const VAULT_FILE: &str = "vault.json";
+
+/// Tables replicated between devices, parents first.
+pub const SYNC_TABLES: &[SyncTable] = &[
+    SyncTable { kind: SyncDocumentKind::Notebook, name: "notebooks", key: &["id"] },
+    SyncTable { kind: SyncDocumentKind::Section, name: "sections", key: &["id"] },
+    SyncTable { kind: SyncDocumentKind::Page, name: "pages", key: &["id"] },
+    SyncTable { kind: SyncDocumentKind::Note, name: "notes", key: &["id"] },
+    SyncTable { kind: SyncDocumentKind::VoiceAnnotation, name: "voice_annotations", key: &["id"] },
+    SyncTable { kind: SyncDocumentKind::MediaAttachment, name: "media_attachments", key: &["id"] },
+    SyncTable { kind: SyncDocumentKind::PageLink, name: "page_links", key: &["source_page_id", "target_page_id", "link_text"] },
+    SyncTable { kind: SyncDocumentKind::Tag, name: "tags", key: &["name"] },
+    SyncTable { kind: SyncDocumentKind::PropertyDefinition, name: "page_property_definitions", key: &["name"] },
+    SyncTable { kind: SyncDocumentKind::PageProperty, name: "page_properties", key: &["page_id", "name"] },
+];
+
+/// Separates the parts of a natural-key document id.
+const KEY_SEPARATOR: char = '\u{1f}';
+
+/// A synced table and how its rows are identified across devices.
+pub struct SyncTable {
+    pub kind: SyncDocumentKind,
+    pub name: &'static str,
+    /// Columns naming a row on every device. Tables with a natural key use it instead of
+    /// their local `id`, so the same tag or link made on two devices is one document rather
+    /// than two that collide on the table's UNIQUE constraint.
+    pub key: &'static [&'static str],
+}
+
+impl SyncTable {
+    pub fn has_natural_key(&self) -> bool {
+        self.key != ["id"]
+    }
+
+    /// SQL for a row's document id, with columns prefixed by `qualifier` (`NEW.`, `OLD.` or
+    /// nothing). Must agree with `doc_id`.
+    pub fn doc_id_sql(&self, qualifier: &str) -> String {
+        if !self.has_natural_key() {
+            return format!("{}id", qualifier);
+        }
+        let mut parts = vec![format!("'{}'", self.kind.as_str())];
+        parts.extend(self.key.iter().map(|column| format!("{}{}", qualifier, column)));
+        parts.join(" || char(31) || ")
+    }
+
+    /// A row's document id: its `id`, or its kind followed by its natural key.
+    pub fn doc_id(&self, row: &[(String, StoredValue)]) -> Option<String> {
+        let value = |column: &str| match row.iter().find(|(name, _)| name == column) {
+            Some((_, StoredValue::Text(value))) => Some(value.clone()),
+            _ => None,
+        };
+        if !self.has_natural_key() {
+            return value("id");
+        }
+        let mut parts = vec![self.kind.as_str().to_string()];
+        for column in self.key {
+            parts.push(value(column)?);
+        }
+        Some(parts.join(&KEY_SEPARATOR.to_string()))
+    }
+}
+
+type Row = Vec<(String, StoredValue)>;
+
+#[derive(Serialize, Deserialize)]
+struct VaultMarker {
+    key_id: Option<String>, // `EncryptionManager::key_id`, or None when documents are plain
+}
+
+#[derive(Default)]
+struct SyncHistory {
+    last_push: Option<SyncReport>,
+    last_pull: Option<SyncReport>,
+}
+
+/// Syncs the vault through a folder that a cloud drive (Dropbox, iCloud Drive, OneDrive,
+/// Syncthing...) keeps in step between devices. Every document is one file,
+/// `<folder>/<kind>/<id>.json`, holding its latest revision in the sync protocol's format,
+/// so the drive only ever sees ciphertext when encryption is on. When a document changed
+/// on both sides since the last sync, the protocol's winning revision is kept on both.
+/// Devices must share a vault key to sync; `<folder>/vault.json` records which one.
+pub struct SyncService {
+    /// Held for a whole push or pull so runs never interleave
+    running: Mutex<()>,
+    history: Mutex<SyncHistory>,
+}
+
+impl SyncService {
+    pub fn new() -> Self {
+        Self { running: Mutex::new(()), history: Mutex::new(SyncHistory::default()) }
+    }
+
+    pub async fn get_config(&self, database: &Database) -> AppResult<SyncConfig> {
+        match database.get_setting(SYNC_CONFIG_KEY).await? {
+            Some(value) => Ok(serde_json::from_str(&value)?),
+            None => Ok(SyncConfig::default()),
+        }
+    }
+
+    /// Rejects a configuration that can't work, when the setting is validated.
+    pub fn check_config(config: &SyncConfig) -> Result<(), String> {
+        if let Some(folder) = &config.folder {
+            if !folder.is_absolute() || !folder.is_dir() {
+                return Err(format!("Sync folder {} is not an existing folder", folder.display()));
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn status(&self, database: &Database) -> AppResult<SyncStatus> {
+        let config = self.get_config(database).await?;
+        let pending_changes = database.get_sync_changes().await?.len();
+        let history = self.history.lock().await;
+        Ok(SyncStatus {
+            configured: config.folder.is_some(),
+            folder: config.folder,
+            pending_changes,
+            last_push: history.last_push.clone(),
+            last_pull: history.last_pull.clone(),
+        })
+    }
+
+    /// Writes local changes to the sync folder. A document changed on another device since
+    /// it was last synced is only overwritten when the local revision wins; otherwise the
+    /// change stays queued and the next pull replaces it with theirs.
+    pub async fn push(&self, database: &Database) -> AppResult<SyncReport> {
+        let _running = self.running.lock().await;
+        let folder = self.folder(database).await?;
+        check_vault_key(&folder, database).await?;
+        let (mut documents, mut deleted, mut conflicts) = (0, 0, 0);
+        let mut failed = Vec::new();
+
+        for change in database.get_sync_changes().await? {
+            match push_change(database, &folder, &change).await {
+                Ok(Pushed::Dropped) => {}
+                Ok(Pushed::Kept) => conflicts += 1,
+                Ok(Pushed::Written { deleted: was_deleted, conflict }) => {
+                    documents += 1;
+                    deleted += usize::from(was_deleted);
+                    conflicts += usize::from(conflict);
+                }
+                Err(e) => {
+                    tracing::warn!("Failed to push {} {}: {}", change.kind.as_str(), change.doc_id, e);
+                    failed.push(SyncFailure { id: change.doc_id, kind: change.kind, error: e.to_string() });
+                }
+            }
+        }
+
+        let report = SyncReport { documents, deleted, conflicts, failed, finished_at: Utc::now() };
+        tracing::info!("Pushed {} documents to {} ({} conflicts, {} failed)", documents, folder.display(), conflicts, report.failed.len());
+        self.history.lock().await.last_push = Some(report.clone());
+        Ok(report)
+    }
+
+    /// Applies documents other devices wrote to the sync folder, in one transaction. Local
+    /// changes not pushed yet are kept unless the other device's revision wins. A document
+    /// that can't be read or applied is reported and skipped, and tried again next time.
+    pub async fn pull(&self, database: &Database) -> AppResult<SyncReport> {
+        let _running = self.running.lock().await;
+        let folder = self.folder(database).await?;
+        check_vault_key(&folder, database).await?;
+        let pending: HashMap<String, SyncChange> = database.get_sync_changes().await?
+            .into_iter()
+            .map(|change| (change.doc_id.clone(), change))
+            .collect();
+        let mut conflicts = 0;
+        let mut incoming = Vec::new();
+        let mut failed = Vec::new();
+
+        for SyncTable { kind, .. } in SYNC_TABLES {
+            let (remotes, unreadable) = read_documents(&folder.join(kind.as_str()), *kind).await?;
+            failed.extend(unreadable);
+            for remote in remotes {
+                let synced_rev = database.get_sync_revision(&remote.id).await?;
+                if Some(&remote.rev) == synced_rev.as_ref() {
+                    continue;
+                }
+                if let Some(change) = pending.get(&remote.id) {
+                    conflicts += 1;
+                    let row = local_row(database, change).await?;
+                    let local = local_document(database, &remote.id, *kind, synced_rev.as_deref(), row.as_deref())?;
+                    if winning_revision(&[remote.rev.as_str(), local.rev.as_str()])? != Some(remote.rev.as_str()) {
+                        continue;
+                    }
+                }
+
+                let row = match remote.body(database.encryption_manager()).and_then(|body| body.map(body_row).transpose()) {
+                    Ok(row) => row,
+                    Err(e) => {
+                        failed.push(SyncFailure { id: remote.id, kind: *kind, error: e.to_string() });
+                        continue;
+                    }
+                };
+                incoming.push(SyncedRow { kind: *kind, id: remote.id, rev: remote.rev, row });
+            }
+        }
+        let rejected = database.apply_synced_rows(&incoming).await?;
+        let applied: Vec<&SyncedRow> = incoming.iter()
+            .filter(|synced| !rejected.iter().any(|failure| failure.id == synced.id && failure.kind == synced.kind))
+            .collect();
+        let deleted = applied.iter().filter(|synced| synced.row.is_none()).count();
+        failed.extend(rejected);
+        for failure in &failed {
+            tracing::warn!("Failed to pull {} {}: {}", failure.kind.as_str(), failure.id, failure.error);
+        }
+
+        let report = SyncReport { documents: applied.len(), deleted, conflicts, failed, finished_at: Utc::now() };
+        tracing::info!("Pulled {} documents from {} ({} conflicts, {} failed)", applied.len(), folder.display(), conflicts, report.failed.len());
+        self.history.lock().await.last_pull = Some(report.clone());
+        Ok(report)
+    }
+
+    async fn folder(&self, database: &Database) -> AppResult<PathBuf> {
+        self.get_config(database).await?.folder
+            .ok_or_else(|| AppError::Configuration("No sync folder is set".to_string()))
+    }
+}
+
+enum Pushed {
+    /// Created and deleted between syncs, so no other device has it
+    Dropped,
+    /// Changed on both sides and the other device's revision won
+    Kept,
+    Written { deleted: bool, conflict: bool },
+}
+
+async fn push_change(database: &Database, folder: &Path, change: &SyncChange) -> AppResult<Pushed> {
+    let synced_rev = database.get_sync_revision(&change.doc_id).await?;
+    let row = local_row(database, change).await?;
+    if row.is_none() && synced_rev.is_none() {
+        database.mark_synced(change, None).await?;
+        return Ok(Pushed::Dropped);
+    }
+
+    let document = local_document(database, &change.doc_id, change.kind, synced_rev.as_deref(), row.as_deref())?;
+    let path = document_path(folder, change.kind, &change.doc_id);
+    let mut conflict = false;
+    if let Some(remote) = read_document(&path).await? {
+        if Some(&remote.rev) != synced_rev.as_ref() {
+            conflict = true;
+            if winning_revision(&[remote.rev.as_str(), document.rev.as_str()])? != Some(document.rev.as_str()) {
+                return Ok(Pushed::Kept);
+            }
+        }
+    }
+
+    write_document(&path, &document).await?;
+    database.mark_synced(change, Some(&document.rev)).await?;
+    Ok(Pushed::Written { deleted: document.deleted, conflict })
+}
+
+/// Records which vault key the folder's documents are encrypted with the first time it's
+/// used, and refuses to sync a device whose key differs: it could read none of the other
+/// devices' documents, and they none of its.
+async fn check_vault_key(folder: &Path, database: &Database) -> AppResult<()> {
+    let ours = database.encryption_manager().map(EncryptionManager::key_id);
+    let path = folder.join(VAULT_FILE);
+    let theirs: VaultMarker = match tokio::fs::read(&path).await {
+        Ok(data) => serde_json::from_slice(&data)?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            let temp = path.with_extension("json.tmp");
+            tokio::fs::write(&temp, serde_json::to_vec(&VaultMarker { key_id: ours })?).await?;
+            tokio::fs::rename(&temp, &path).await?;
+            return Ok(());
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    match (theirs.key_id, ours) {
+        (theirs, ours) if theirs == ours => Ok(()),
+        (Some(_), Some(_)) => Err(AppError::Encryption(
+            "The sync folder is encrypted with a different vault key. Export the key from a device that already syncs to it and import it here before syncing".to_string(),
+        )),
+        (Some(_), None) => Err(AppError::Encryption(
+            "The sync folder is encrypted, but encryption is off on this device".to_string(),
+        )),
+        (None, _) => Err(AppError::Encryption(
+            "The sync folder holds unencrypted documents, but this device encrypts its vault".to_string(),
+        )),
+    }
+}
+
+/// The table rows of `kind` are stored in.
+pub fn sync_table(kind: SyncDocumentKind) -> &'static SyncTable {
+    SYNC_TABLES.iter().find(|table| table.kind == kind).expect("every document kind has a table")
+}
+
+async fn local_row(database: &Database, change: &SyncChange) -> AppResult<Option<Row>> {
+    if change.deleted {
+        return Ok(None);
+    }
+    database.get_sync_row(change.kind, &change.doc_id).await
+}
+
+/// The next revision of a local document after `synced_rev`, or its deletion without `row`.
+fn local_document(
+    database: &Database,
+    id: &str,
+    kind: SyncDocumentKind,
+    synced_rev: Option<&str>,
+    row: Option<&[(String, StoredValue)]>,
+) -> AppResult<SyncDocument> {
+    let body = row.map(row_body).transpose()?;
+    let updated_at = row.and_then(updated_at).unwrap_or_else(|| Utc::now().to_rfc3339());
+    SyncDocument::new(id, kind, synced_rev, body.as_ref(), &updated_at, database.encryption_manager())
+}
+
+/// A row as a JSON object of column to stored value. Columns encrypted in the database
+/// stay encrypted.
+fn row_body(row: &[(String, StoredValue)]) -> AppResult<Value> {
+    let mut body = Map::new();
+    for (column, value) in row {
+        body.insert(column.clone(), serde_json::to_value(value)?);
+    }
+    Ok(Value::Object(body))
+}
+
+fn body_row(body: Value) -> AppResult<Row> {
+    let Value::Object(body) = body else {
+        return Err(AppError::InvalidFormat("Sync document body is not an object".to_string()));
+    };
+    body.into_iter()
+        .map(|(column, value)| Ok((column, serde_json::from_value(value)?)))
+        .collect()
+}
+
+/// When the row last changed, for the document's informational `updated_at`.
+fn updated_at(row: &[(String, StoredValue)]) -> Option<String> {
+    ["updated_at", "created_at", "timestamp"].iter().find_map(|column| {
+        match row.iter().find(|(name, _)| name == column) {
+            Some((_, StoredValue::Text(value))) => Some(value.clone()),
+            _ => None,
+        }
+    })
+}
+
+fn document_path(folder: &Path, kind: SyncDocumentKind, id: &str) -> PathBuf {
+    folder.join(kind.as_str()).join(format!("{}.json", file_stem(id)))
+}
+
+/// Natural-key document ids can hold any character, so files for those are named after a
+/// hash of the id instead.
+fn file_stem(id: &str) -> String {
+    if !id.is_empty() && id.len() <= 64 && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return id.to_string();
+    }
+    Sha256::digest(id.as_bytes()).iter().take(16).map(|b| format!("{:02x}", b)).collect()
+}
+
+async fn read_document(path: &Path) -> AppResult<Option<SyncDocument>> {
+    match tokio::fs::read(path).await {
+        Ok(data) => Ok(Some(serde_json::from_slice(&data)?)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Every document of `kind` in `dir`, and the ones that couldn't be read. Files that aren't
+/// one of our documents, such as the conflicted copies some drives make, are skipped.
+async fn read_documents(dir: &Path, kind: SyncDocumentKind) -> AppResult<(Vec<SyncDocument>, Vec<SyncFailure>)> {
+    let mut entries = match tokio::fs::read_dir(dir).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok((Vec::new(), Vec::new())),
+        Err(e) => return Err(e.into()),
+    };
+    let mut documents = Vec::new();
+    let mut unreadable = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        let Some(stem) = path.file_stem().and_then(|stem| stem.to_str()).filter(|_| path.extension().is_some_and(|e| e == "json")) else {
+            continue;
+        };
+        match read_document(&path).await {
+            Ok(Some(document)) if file_stem(&document.id) == stem && document.kind == kind => documents.push(document),
+            Ok(_) => tracing::warn!("Skipping {}: not a {} sync document", path.display(), kind.as_str()),
+            Err(e) => unreadable.push(SyncFailure { id: stem.to_string(), kind, error: e.to_string() }),
+        }
+    }
+    Ok((documents, unreadable))
+}
+
+/// Writes through a temporary file so the drive never uploads half a document.
+async fn write_document(path: &Path, document: &SyncDocument) -> AppResult<()> {
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    let temp = path.with_extension("json.tmp");
+    tokio::fs::write(&temp, serde_json::to_vec(document)?).await?;
+    tokio::fs::rename(&temp, path).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_row_body_round_trip() {
+        let row: Row = vec![
+            ("id".to_string(), StoredValue::Text("p1".to_string())),
+            ("order_index".to_string(), StoredValue::Integer(3)),
+            ("section_id".to_string(), StoredValue::Null),
+            ("audio_data".to_string(), StoredValue::Blob("AAE=".to_string())),
+        ];
+        let mut round_trip = body_row(row_body(&row).unwrap()).unwrap();
+        round_trip.sort_by(|a, b| a.0.cmp(&b.0));
+        let mut expected = row.clone();
+        expected.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(round_trip, expected);
+        assert!(body_row(Value::Array(Vec::new())).is_err());
+    }
+
+    #[test]
+    fn test_updated_at_prefers_last_change() {
+        let row: Row = vec![
+            ("created_at".to_string(), StoredValue::Text("2026-01-01T00:00:00Z".to_string())),
+            ("updated_at".to_string(), StoredValue::Text("2026-02-01T00:00:00Z".to_string())),
+        ];
+        assert_eq!(updated_at(&row).as_deref(), Some("2026-02-01T00:00:00Z"));
+        assert_eq!(updated_at(&row[..1]).as_deref(), Some("2026-01-01T00:00:00Z"));
+        assert_eq!(updated_at(&[]), None);
+    }
+
+    #[test]
+    fn test_tables_and_paths() {
+        assert_eq!(sync_table(SyncDocumentKind::VoiceAnnotation).name, "voice_annotations");
+        assert_eq!(document_path(Path::new("/sync"), SyncDocumentKind::Page, "p1"), Path::new("/sync/page/p1.json"));
+
+        let path = document_path(Path::new("/sync"), SyncDocumentKind::Tag, "tag\u{1f}work/urgent");
+        let stem = path.file_stem().unwrap().to_str().unwrap();
+        assert_eq!(stem.len(), 32);
+        assert!(stem.chars().all(|c| c.is_ascii_hexdigit()));
+    }
+
+    #[test]
+    fn test_natural_key_doc_ids() {
+        let link: Row = vec![
+            ("id".to_string(), StoredValue::Text("l1".to_string())),
+            ("source_page_id".to_string(), StoredValue::Text("p1".to_string())),
+            ("target_page_id".to_string(), StoredValue::Text("p2".to_string())),
+            ("link_text".to_string(), StoredValue::Text("See also".to_string())),
+        ];
+        let links = sync_table(SyncDocumentKind::PageLink);
+        assert_eq!(links.doc_id(&link).as_deref(), Some("page_link\u{1f}p1\u{1f}p2\u{1f}See also"));
+        assert_eq!(links.doc_id_sql("NEW."), "'page_link' || char(31) || NEW.source_page_id || char(31) || NEW.target_page_id || char(31) || NEW.link_text");
+        assert_eq!(links.doc_id(&link[..2]), None);
+
+        let pages = sync_table(SyncDocumentKind::Page);
+        assert_eq!(pages.doc_id(&link).as_deref(), Some("l1"));
+        assert_eq!(pages.doc_id_sql("OLD."), "OLD.id");
+    }
+}
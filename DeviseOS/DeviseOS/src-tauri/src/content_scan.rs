@@ -0,0 +1,83 @@
+use std::collections::HashMap;
+use regex::Regex;
+use crate::{
+    AppResult,
+    models::{ContentScanMatch, ContentScanResult, SearchFilters},
+    database::Database,
+    find_replace::build_pattern,
+};
+
+/// Pages decrypted and scanned between yields to the runtime.
+const SCAN_BATCH_SIZE: usize = 50;
+/// Scanning stops once this many matches are collected.
+const MAX_SCAN_MATCHES: usize = 10_000;
+
+/// Runs a regex over the decrypted content of every page in scope and returns each match
+/// with its byte offsets and capture groups.
+pub async fn scan_content(database: &Database, pattern: &str, scope: &SearchFilters, case_sensitive: bool) -> AppResult<ContentScanResult> {
+    let pattern = build_pattern(pattern, true, case_sensitive)?;
+    let ids = database.get_page_ids_in_scope(scope).await?;
+
+    let mut result = ContentScanResult { matches: Vec::new(), pages_scanned: 0, truncated: false };
+    'batches: for batch in ids.chunks(SCAN_BATCH_SIZE) {
+        for id in batch {
+            let Some(page) = database.get_page(id).await? else {
+                continue;
+            };
+            result.pages_scanned += 1;
+            for found in scan_text(&pattern, &page.content) {
+                if result.matches.len() >= MAX_SCAN_MATCHES {
+                    result.truncated = true;
+                    break 'batches;
+                }
+                result.matches.push(ContentScanMatch { page_id: page.id.clone(), page_title: page.title.clone(), ..found });
+            }
+        }
+        tokio::task::yield_now().await;
+    }
+
+    Ok(result)
+}
+
+/// Matches in `text` with the page fields left empty.
+fn scan_text(pattern: &Regex, text: &str) -> Vec<ContentScanMatch> {
+    pattern
+        .captures_iter(text)
+        .filter_map(|captures| {
+            let matched = captures.get(0)?;
+            let groups = captures.iter().skip(1).map(|group| group.map(|g| g.as_str().to_string())).collect();
+            let named_groups: HashMap<String, String> = pattern
+                .capture_names()
+                .flatten()
+                .filter_map(|name| Some((name.to_string(), captures.name(name)?.as_str().to_string())))
+                .collect();
+            Some(ContentScanMatch {
+                page_id: String::new(),
+                page_title: String::new(),
+                start: matched.start(),
+                end: matched.end(),
+                text: matched.as_str().to_string(),
+                groups,
+                named_groups,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scan_text_captures_groups() {
+        let pattern = build_pattern(r"(?P<prefix>INV|PO)-(\d{3})(-X)?", true, true).unwrap();
+        let matches = scan_text(&pattern, "Paid INV-042 and PO-100-X, ignore inv-001");
+
+        assert_eq!(matches.len(), 2);
+        assert_eq!((matches[0].start, matches[0].end), (5, 12));
+        assert_eq!(matches[0].groups, vec![Some("INV".to_string()), Some("042".to_string()), None]);
+        assert_eq!(matches[0].named_groups.get("prefix").map(String::as_str), Some("INV"));
+        assert_eq!(matches[1].text, "PO-100-X");
+        assert_eq!(matches[1].groups[2].as_deref(), Some("-X"));
+    }
+}
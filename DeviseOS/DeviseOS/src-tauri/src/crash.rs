@@ -0,0 +1,198 @@
+use std::backtrace::Backtrace;
+use std::panic::PanicHookInfo;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use chrono::Utc;
+use uuid::Uuid;
+use crate::{
+    AppError, AppResult,
+    models::CrashReport,
+    database::Database,
+};
+
+/// Setting holding the HTTPS endpoint crash reports are posted to. Nothing is sent unless
+/// this is set and the user submits a specific report.
+pub const CRASH_REPORT_URL_KEY: &str = "crash_report_url";
+const MAX_MESSAGE_LENGTH: usize = 500;
+const MAX_BACKTRACE_FRAMES: usize = 64;
+const SUBMIT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Installs a panic hook that writes a sanitized report to `reports_path` before handing
+/// over to the previous hook.
+pub fn install_panic_hook(reports_path: PathBuf) {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let report = build_report(info);
+        if let Err(e) = write_report(&reports_path, &report) {
+            eprintln!("Failed to write crash report: {}", e);
+        }
+        previous(info);
+    }));
+}
+
+fn build_report(info: &PanicHookInfo<'_>) -> CrashReport {
+    let message = info
+        .payload()
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| info.payload().downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "non-string panic payload".to_string());
+
+    CrashReport {
+        id: Uuid::new_v4().to_string(),
+        created_at: Utc::now(),
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        os: std::env::consts::OS.to_string(),
+        arch: std::env::consts::ARCH.to_string(),
+        thread: std::thread::current().name().map(|name| name.to_string()),
+        message: sanitize_message(&message),
+        location: info.location().map(|l| format!("{}:{}:{}", l.file(), l.line(), l.column())),
+        backtrace: backtrace_frames(&Backtrace::force_capture().to_string()),
+        submitted_at: None,
+    }
+}
+
+/// Panic messages often embed the value that caused them, which may be note text. Anything
+/// quoted or following a `: ` separator is replaced, and the result is length-limited.
+pub fn sanitize_message(message: &str) -> String {
+    let mut sanitized = String::new();
+    let mut in_quotes: Option<char> = None;
+
+    for c in message.chars() {
+        match in_quotes {
+            Some(quote) if c == quote => {
+                sanitized.push_str("[redacted]");
+                sanitized.push(c);
+                in_quotes = None;
+            }
+            Some(_) => {}
+            None => {
+                sanitized.push(c);
+                if c == '"' || c == '`' {
+                    in_quotes = Some(c);
+                }
+            }
+        }
+    }
+    if in_quotes.is_some() {
+        sanitized.push_str("[redacted]");
+    }
+
+    // Values after "...: " as produced by unwrap/expect on errors
+    if let Some(index) = sanitized.find(": ") {
+        sanitized.truncate(index);
+        sanitized.push_str(": [redacted]");
+    }
+
+    sanitized.chars().take(MAX_MESSAGE_LENGTH).collect()
+}
+
+/// Keeps only the function symbol lines of a formatted backtrace.
+fn backtrace_frames(backtrace: &str) -> Vec<String> {
+    backtrace
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.starts_with("at "))
+        .filter_map(|line| line.split_once(": ").map(|(_, symbol)| symbol.to_string()))
+        .take(MAX_BACKTRACE_FRAMES)
+        .collect()
+}
+
+fn write_report(reports_path: &Path, report: &CrashReport) -> AppResult<()> {
+    std::fs::create_dir_all(reports_path)?;
+    let path = reports_path.join(format!("{}.json", report.id));
+    std::fs::write(path, serde_json::to_vec_pretty(report)?)?;
+    Ok(())
+}
+
+/// Lists reports newest first. Unreadable files are skipped.
+pub fn list_reports(reports_path: &Path) -> AppResult<Vec<CrashReport>> {
+    if !reports_path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut reports = Vec::new();
+    for entry in std::fs::read_dir(reports_path)? {
+        let path = entry?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("json") {
+            continue;
+        }
+        match std::fs::read(&path).map_err(AppError::from).and_then(|data| Ok(serde_json::from_slice::<CrashReport>(&data)?)) {
+            Ok(report) => reports.push(report),
+            Err(e) => tracing::warn!("Skipping unreadable crash report {}: {}", path.display(), e),
+        }
+    }
+
+    reports.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+    Ok(reports)
+}
+
+fn report_path(reports_path: &Path, id: &str) -> AppResult<PathBuf> {
+    Uuid::parse_str(id).map_err(|_| AppError::InvalidFormat(format!("Invalid crash report id: {}", id)))?;
+    let path = reports_path.join(format!("{}.json", id));
+    if !path.exists() {
+        return Err(AppError::NotFound(format!("Crash report {} not found", id)));
+    }
+    Ok(path)
+}
+
+/// Sends one report to the configured endpoint and marks it as submitted.
+pub async fn submit_report(database: &Database, reports_path: &Path, id: &str) -> AppResult<CrashReport> {
+    let url = database.get_setting(CRASH_REPORT_URL_KEY).await?
+        .filter(|url| !url.trim().is_empty())
+        .ok_or_else(|| AppError::Configuration("No crash report endpoint is configured".to_string()))?;
+    if !url.starts_with("https://") {
+        return Err(AppError::Configuration("Crash report endpoint must use HTTPS".to_string()));
+    }
+
+    let path = report_path(reports_path, id)?;
+    let mut report: CrashReport = serde_json::from_slice(&tokio::fs::read(&path).await?)?;
+
+    let client = reqwest::Client::builder()
+        .timeout(SUBMIT_TIMEOUT)
+        .build()
+        .map_err(|e| AppError::Network(format!("Failed to create HTTP client: {}", e)))?;
+    let response = client
+        .post(&url)
+        .json(&report)
+        .send()
+        .await
+        .map_err(|e| AppError::Network(format!("Failed to submit crash report: {}", e)))?;
+    if !response.status().is_success() {
+        return Err(AppError::Network(format!("Crash report endpoint returned {}", response.status())));
+    }
+
+    report.submitted_at = Some(Utc::now());
+    tokio::fs::write(&path, serde_json::to_vec_pretty(&report)?).await?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_redacts_quoted_text() {
+        assert_eq!(
+            sanitize_message("failed to parse \"my secret note\" as date"),
+            "failed to parse \"[redacted]\" as date"
+        );
+    }
+
+    #[test]
+    fn test_sanitize_redacts_error_values() {
+        assert_eq!(
+            sanitize_message("called `Result::unwrap()` on an `Err` value: Meeting notes"),
+            "called `[redacted]` on an `[redacted]` value: [redacted]"
+        );
+    }
+
+    #[test]
+    fn test_backtrace_keeps_symbols_only() {
+        let backtrace = "   0: deviseos_lib::crash::build_report\n             at ./src/crash.rs:40:20\n   1: std::panicking::rust_panic_with_hook";
+        assert_eq!(
+            backtrace_frames(backtrace),
+            vec!["deviseos_lib::crash::build_report", "std::panicking::rust_panic_with_hook"]
+        );
+    }
+}
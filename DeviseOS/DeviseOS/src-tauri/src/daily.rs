@@ -0,0 +1,77 @@
+use chrono::NaiveDate;
+use tokio::sync::Mutex;
+use crate::{
+    AppResult,
+    models::{CreateNotebookRequest, CreatePageRequest, Page},
+    database::Database,
+};
+
+/// Title of the notebook daily notes go in; it's created on first use.
+pub const DAILY_NOTEBOOK_KEY: &str = "daily_notes_notebook";
+pub const DEFAULT_DAILY_NOTEBOOK: &str = "Journal";
+/// Name of the template new daily notes start from; blank pages when unset.
+pub const DAILY_TEMPLATE_KEY: &str = "daily_notes_template";
+
+/// Keeps a repeated hotkey from creating the same day twice.
+static CREATE_LOCK: Mutex<()> = Mutex::const_new(());
+
+/// The daily note for `date` and whether it was just created. Template titles and content
+/// can use `{{date}}` (2024-03-01) and `{{weekday}}` (Friday).
+pub async fn get_or_create(database: &Database, date: NaiveDate) -> AppResult<(Page, bool)> {
+    let _guard = CREATE_LOCK.lock().await;
+    let notebook_title = database.get_setting(DAILY_NOTEBOOK_KEY).await?
+        .unwrap_or_else(|| DEFAULT_DAILY_NOTEBOOK.to_string());
+    let notebook = match database.find_notebook_by_title(&notebook_title).await? {
+        Some(notebook) => notebook,
+        None => database.create_notebook(CreateNotebookRequest { title: notebook_title, description: None, color: None }).await?,
+    };
+    if let Some(page) = database.find_daily_page(&notebook.id, date).await? {
+        return Ok((page, false));
+    }
+
+    let template = match database.get_setting(DAILY_TEMPLATE_KEY).await? {
+        Some(name) => {
+            let template = database.get_templates().await?.into_iter().find(|template| template.name == name);
+            if template.is_none() {
+                tracing::warn!("Daily note template {} not found; creating a blank page", name);
+            }
+            template
+        }
+        None => None,
+    };
+    let (title, content, tags) = match template {
+        Some(template) => (render(&template.title, date), render(&template.content, date), template.tags),
+        None => (String::new(), String::new(), Vec::new()),
+    };
+    let title = if title.trim().is_empty() { render("{{date}}", date) } else { title };
+
+    let mut page = database.create_page(CreatePageRequest {
+        notebook_id: notebook.id,
+        section_id: None,
+        parent_page_id: None,
+        title,
+        content,
+        tags,
+    }).await?;
+    database.set_daily_date(&page.id, date).await?;
+    page.metadata.daily_date = Some(date);
+    Ok((page, true))
+}
+
+fn render(template: &str, date: NaiveDate) -> String {
+    template
+        .replace("{{date}}", &date.format("%Y-%m-%d").to_string())
+        .replace("{{weekday}}", &date.format("%A").to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_placeholders() {
+        let date = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        assert_eq!(render("{{date}} {{weekday}}", date), "2024-03-01 Friday");
+        assert_eq!(render("## Today\n", date), "## Today\n");
+    }
+}
@@ -1,13 +1,19 @@
 use rusqlite::{Connection, Result as SqliteResult, params};
-use sqlx::{SqlitePool, Row as SqlxRow};
+use sqlx::{SqlitePool, Column, Row as SqlxRow, TypeInfo, ValueRef};
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode as JournalMode, SqliteSynchronous as Synchronous};
 use serde_json;
-use std::path::Path;
-use chrono::{DateTime, Utc};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+use base64::{Engine as _, engine::general_purpose};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use chrono::{DateTime, NaiveDate, Utc};
 use uuid::Uuid;
 use crate::{
     AppError, AppResult, 
     models::{
-        Note, VoiceAnnotation, Tag, NoteMetadata, VoiceMetadata,
+        Note, VoiceAnnotation, Tag, NoteMetadata, VoiceMetadata, VoiceSegment,
         Notebook, Section, Page, MediaAttachment, PageLink, PageLinkType,
         NotebookMetadata, PageMetadata, MediaMetadata,
         CreateNotebookRequest, UpdateNotebookRequest,
@@ -15,24 +21,94 @@ use crate::{
         CreatePageRequest, UpdatePageRequest, MovePageRequest,
         UploadMediaRequest, CreatePageLinkRequest,
         NotebookHierarchy, SectionWithPages, PageWithSubpages,
-        NotebookStats, PageRelationships
+        NotebookStats, PageRelationships, SearchRequest, NotebookSearchRequest, SearchFilters,
+        SqliteConfig, SqliteJournalMode, SqliteSynchronous,
+        NoteSearchMatch, PageSearchMatch, EmbeddingOwner, SearchMatchField, SearchPage, HighlightSpan,
+        Automation, AutomationRun, AutomationEvent,
+        CreateAutomationRequest, UpdateAutomationRequest,
+        Template, Snippet, PromptTemplate, BundleManifest, InstalledBundle,
+        LanguageSettings, is_valid_language_tag, AuditLogEntry, VaultStats, StoredValue, EmbeddingModelCount,
+        ExportFormat, ExportRecord, PageRevision, TagMerge, TagMergeResult,
+        StatsRange, PagesPerDay, NotebookWordCount, TagUsageDay, UsageStats, MocSource, JumpListEntry,
+        AiJob, AiJobKind, AiJobPriority, AiJobStatus, TrashItem, TrashItemType, AppStats, TableSize,
+        BulkUpdatePagesRequest, BulkPageOperation, BulkUpdateResult, UpdateTagRequest, PinnedItems, PinnedPage, RecentPage,
+        PropertyType, PropertyValue, PropertyDefinition, DefinePropertyRequest, PageProperty, FilterPagesRequest, Backlink, LinkTypeCount,
+        SecurityConfig, ImageConfig, StorageUsage, NotebookStorage, MediaTypeStorage,
+        VoiceAudio, VoiceAnnotationSummary, UpdateVoiceAnnotationRequest, VoiceRecompressReport,
+        SyncChange, SyncedRow, SyncFailure,
     },
     encryption::EncryptionManager,
+    search::{self, SearchDocument, SearchTable},
+    cloud_sync::{sync_table, SyncTable, SYNC_TABLES},
+    sync_protocol::SyncDocumentKind,
+    tags, zettel, sentiment, sqlcipher, maintenance, properties, wikilinks,
+    exif::{self, Stripped},
+    image_compression, storage, audio, voice_codec,
 };
 
+/// Bumped whenever `init_schema` changes shape; stored in SQLite's `user_version`.
+pub const SCHEMA_VERSION: i64 = 18;
+
+/// Pages the recents list remembers; older opens are dropped.
+const RECENT_PAGES_KEPT: i64 = 200;
+
+/// Setting that opts into the sqlite-vec index for embeddings.
+pub const VECTOR_INDEX_KEY: &str = "vector_index_enabled";
+/// Dimension the `vec_embeddings` table was created with; it changes with the embedding model.
+const VECTOR_INDEX_DIMENSION_KEY: &str = "vector_index_dimension";
+
 pub struct Database {
     pool: SqlitePool,
+    path: PathBuf,
     encryption_manager: Option<EncryptionManager>,
+    vector_index: bool,
+    file_encrypted: bool, // Opened with SQLCipher
+    image_config: ImageConfig,
+    max_file_size: u64,
 }
 
 impl Database {
-    pub async fn new(database_path: &Path, encryption_manager: Option<EncryptionManager>) -> AppResult<Self> {
+    pub async fn new(database_path: &Path, encryption_manager: Option<EncryptionManager>, sqlite: &SqliteConfig) -> AppResult<Self> {
+        // Must happen before the pool opens its first connection
+        register_vector_extension();
+
+        // An existing file keeps its format; `sqlcipher` only decides how new ones are created
+        let file_encrypted = sqlcipher::is_encrypted_file(database_path)?
+            || (sqlite.sqlcipher && !database_path.exists());
+
         let database_url = format!("sqlite:{}", database_path.to_string_lossy());
-        let pool = SqlitePool::connect(&database_url).await?;
+        let mut options = SqliteConnectOptions::from_str(&database_url)?
+            .journal_mode(match sqlite.journal_mode {
+                SqliteJournalMode::Wal => JournalMode::Wal,
+                SqliteJournalMode::Delete => JournalMode::Delete,
+                SqliteJournalMode::Truncate => JournalMode::Truncate,
+            })
+            .synchronous(match sqlite.synchronous {
+                SqliteSynchronous::Off => Synchronous::Off,
+                SqliteSynchronous::Normal => Synchronous::Normal,
+                SqliteSynchronous::Full => Synchronous::Full,
+            })
+            .busy_timeout(Duration::from_millis(sqlite.busy_timeout_ms))
+            // Negative sizes are in KiB rather than pages
+            .pragma("cache_size", format!("-{}", sqlite.cache_size_kib));
+        if file_encrypted {
+            sqlcipher::ensure_available()?;
+            let manager = encryption_manager.as_ref().ok_or_else(|| {
+                AppError::Encryption("The database file is encrypted but no encryption key is loaded".to_string())
+            })?;
+            // sqlx sends `key` before any other pragma, as SQLCipher requires
+            options = options.pragma("key", format!("\"{}\"", manager.database_key()));
+        }
+        let pool = SqlitePool::connect_with(options).await?;
         
         let db = Self {
             pool,
+            path: database_path.to_path_buf(),
             encryption_manager,
+            vector_index: false,
+            file_encrypted,
+            image_config: ImageConfig::default(),
+            max_file_size: u64::MAX,
         };
         
         db.init_schema().await?;
@@ -139,6 +215,7 @@ impl Database {
                 mime_type TEXT NOT NULL,
                 file_size INTEGER NOT NULL,
                 file_data BLOB NOT NULL,
+                original_data BLOB, -- The upload before compression, when kept
                 thumbnail_data BLOB,
                 position_in_content INTEGER,
                 created_at TEXT NOT NULL,
@@ -185,10 +262,12 @@ impl Database {
         sqlx::query(
             r#"
             CREATE TABLE IF NOT EXISTS embeddings (
-                note_id TEXT PRIMARY KEY,
+                owner_id TEXT PRIMARY KEY,
+                owner_type TEXT NOT NULL,
                 embedding BLOB NOT NULL,
-                created_at TEXT NOT NULL,
-                FOREIGN KEY (note_id) REFERENCES notes (id) ON DELETE CASCADE
+                model TEXT,
+                content_hash TEXT,
+                created_at TEXT NOT NULL
             )
             "#
         ).execute(&self.pool).await?;
@@ -204,6 +283,291 @@ impl Database {
             "#
         ).execute(&self.pool).await?;
 
+        // Automations table
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS automations (
+                id TEXT PRIMARY KEY,
+                name TEXT NOT NULL,
+                enabled INTEGER NOT NULL DEFAULT 1,
+                trigger_config TEXT NOT NULL,
+                action_config TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL
+            )
+            "#
+        ).execute(&self.pool).await?;
+
+        // Automation execution log
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS automation_runs (
+                id TEXT PRIMARY KEY,
+                automation_id TEXT NOT NULL,
+                event TEXT NOT NULL,
+                success INTEGER NOT NULL,
+                output TEXT,
+                executed_at TEXT NOT NULL,
+                FOREIGN KEY (automation_id) REFERENCES automations (id) ON DELETE CASCADE
+            )
+            "#
+        ).execute(&self.pool).await?;
+
+        // Installed template/snippet/prompt bundles
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS bundles (
+                id TEXT PRIMARY KEY,
+                name TEXT NOT NULL,
+                version TEXT NOT NULL,
+                author TEXT,
+                description TEXT,
+                signer_key TEXT,
+                trusted INTEGER NOT NULL DEFAULT 0,
+                installed_at TEXT NOT NULL
+            )
+            "#
+        ).execute(&self.pool).await?;
+
+        // Templates table (bundle_id is NULL for user-created templates)
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS templates (
+                id TEXT PRIMARY KEY,
+                bundle_id TEXT,
+                name TEXT NOT NULL,
+                description TEXT,
+                title TEXT NOT NULL,
+                content TEXT NOT NULL,
+                tags TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                FOREIGN KEY (bundle_id) REFERENCES bundles (id) ON DELETE CASCADE
+            )
+            "#
+        ).execute(&self.pool).await?;
+
+        // Snippets table
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS snippets (
+                id TEXT PRIMARY KEY,
+                bundle_id TEXT,
+                name TEXT NOT NULL,
+                trigger TEXT NOT NULL,
+                content TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                FOREIGN KEY (bundle_id) REFERENCES bundles (id) ON DELETE CASCADE
+            )
+            "#
+        ).execute(&self.pool).await?;
+
+        // Prompt templates table
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS prompts (
+                id TEXT PRIMARY KEY,
+                bundle_id TEXT,
+                name TEXT NOT NULL,
+                description TEXT,
+                prompt TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                FOREIGN KEY (bundle_id) REFERENCES bundles (id) ON DELETE CASCADE
+            )
+            "#
+        ).execute(&self.pool).await?;
+
+        // Audit log table, written when audit logging is enabled or required by policy
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS audit_log (
+                id TEXT PRIMARY KEY,
+                action TEXT NOT NULL,
+                target TEXT,
+                created_at TEXT NOT NULL
+            )
+            "#
+        ).execute(&self.pool).await?;
+
+        // Export history, so mirrors can be regenerated with the same settings
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS export_history (
+                id TEXT PRIMARY KEY,
+                page_id TEXT NOT NULL,
+                format TEXT NOT NULL,
+                locale TEXT,
+                destination TEXT NOT NULL,
+                path TEXT NOT NULL,
+                exported_at TEXT NOT NULL,
+                FOREIGN KEY (page_id) REFERENCES pages (id) ON DELETE CASCADE
+            )
+            "#
+        ).execute(&self.pool).await?;
+
+        // Page revisions, saved before bulk edits so they can be undone
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS page_revisions (
+                id TEXT PRIMARY KEY,
+                page_id TEXT NOT NULL,
+                title TEXT NOT NULL,
+                content TEXT NOT NULL,
+                reason TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                FOREIGN KEY (page_id) REFERENCES pages (id) ON DELETE CASCADE
+            )
+            "#
+        ).execute(&self.pool).await?;
+
+        // Map of content pages and what they list, so they can be refreshed
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS mocs (
+                page_id TEXT PRIMARY KEY,
+                source TEXT NOT NULL UNIQUE,
+                refreshed_at TEXT NOT NULL,
+                FOREIGN KEY (page_id) REFERENCES pages (id) ON DELETE CASCADE
+            )
+            "#
+        ).execute(&self.pool).await?;
+
+        // Background AI jobs; `payload` is the JSON job kind
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS ai_jobs (
+                id TEXT PRIMARY KEY,
+                payload TEXT NOT NULL,
+                priority INTEGER NOT NULL DEFAULT 1,
+                status TEXT NOT NULL,
+                attempts INTEGER NOT NULL DEFAULT 0,
+                error TEXT,
+                run_after TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL
+            )
+            "#
+        ).execute(&self.pool).await?;
+
+        // Deleted notes and pages; `payload` holds their rows, and their attachments', as stored
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS trash (
+                id TEXT PRIMARY KEY,
+                item_type TEXT NOT NULL,
+                title TEXT NOT NULL,
+                notebook_id TEXT,
+                page_count INTEGER NOT NULL DEFAULT 0,
+                payload TEXT NOT NULL,
+                deleted_at TEXT NOT NULL
+            )
+            "#
+        ).execute(&self.pool).await?;
+
+        // One row per tag on each page and note, kept in step with their `tags` arrays by the
+        // triggers below, so browsing by tag doesn't scan every array
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS page_tags (
+                page_id TEXT NOT NULL,
+                tag TEXT NOT NULL,
+                PRIMARY KEY (tag, page_id),
+                FOREIGN KEY (page_id) REFERENCES pages (id) ON DELETE CASCADE
+            )
+            "#
+        ).execute(&self.pool).await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS note_tags (
+                note_id TEXT NOT NULL,
+                tag TEXT NOT NULL,
+                PRIMARY KEY (tag, note_id),
+                FOREIGN KEY (note_id) REFERENCES notes (id) ON DELETE CASCADE
+            )
+            "#
+        ).execute(&self.pool).await?;
+
+        // Pages by when they were last opened, for the recents list
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS recent_pages (
+                page_id TEXT PRIMARY KEY,
+                accessed_at TEXT NOT NULL,
+                access_count INTEGER NOT NULL DEFAULT 1,
+                FOREIGN KEY (page_id) REFERENCES pages (id) ON DELETE CASCADE
+            )
+            "#
+        ).execute(&self.pool).await?;
+
+        // Suggested links the user turned down, so they aren't suggested again
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS dismissed_links (
+                id TEXT PRIMARY KEY,
+                source_page_id TEXT NOT NULL,
+                target_page_id TEXT NOT NULL,
+                dismissed_at TEXT NOT NULL,
+                UNIQUE (source_page_id, target_page_id),
+                FOREIGN KEY (source_page_id) REFERENCES pages (id) ON DELETE CASCADE,
+                FOREIGN KEY (target_page_id) REFERENCES pages (id) ON DELETE CASCADE
+            )
+            "#
+        ).execute(&self.pool).await?;
+
+        // Typed properties set on pages, like frontmatter; every value's property is defined
+        // once with its type, and select options
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS page_property_definitions (
+                name TEXT PRIMARY KEY,
+                property_type TEXT NOT NULL,
+                options TEXT NOT NULL DEFAULT '[]',
+                created_at TEXT NOT NULL
+            )
+            "#
+        ).execute(&self.pool).await?;
+
+        // Numbers are kept in `number_value` so they compare numerically; everything else,
+        // dates included, in `text_value`
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS page_properties (
+                id TEXT PRIMARY KEY,
+                page_id TEXT NOT NULL,
+                name TEXT NOT NULL,
+                text_value TEXT,
+                number_value REAL,
+                updated_at TEXT NOT NULL,
+                UNIQUE (page_id, name),
+                FOREIGN KEY (page_id) REFERENCES pages (id) ON DELETE CASCADE
+            )
+            "#
+        ).execute(&self.pool).await?;
+
+        // Sync change queue: the latest change of each document not pushed yet
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS sync_queue (
+                seq INTEGER PRIMARY KEY AUTOINCREMENT,
+                doc_id TEXT NOT NULL UNIQUE,
+                kind TEXT NOT NULL,
+                deleted INTEGER NOT NULL DEFAULT 0,
+                changed_at TEXT NOT NULL
+            )
+            "#
+        ).execute(&self.pool).await?;
+
+        // Revision of each document as last pushed or pulled
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS sync_revisions (
+                doc_id TEXT PRIMARY KEY,
+                kind TEXT NOT NULL,
+                rev TEXT NOT NULL
+            )
+            "#
+        ).execute(&self.pool).await?;
+
         // Create indexes for better performance
         // Notebook indexes
         sqlx::query("CREATE INDEX IF NOT EXISTS idx_notebooks_order_index ON notebooks (order_index)").execute(&self.pool).await?;
@@ -216,10 +580,13 @@ impl Database {
         // Page indexes
         sqlx::query("CREATE INDEX IF NOT EXISTS idx_pages_notebook_id ON pages (notebook_id)").execute(&self.pool).await?;
         sqlx::query("CREATE INDEX IF NOT EXISTS idx_pages_section_id ON pages (section_id)").execute(&self.pool).await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_pages_title ON pages (title COLLATE NOCASE)").execute(&self.pool).await?;
         sqlx::query("CREATE INDEX IF NOT EXISTS idx_pages_parent_page_id ON pages (parent_page_id)").execute(&self.pool).await?;
         sqlx::query("CREATE INDEX IF NOT EXISTS idx_pages_order_index ON pages (notebook_id, section_id, order_index)").execute(&self.pool).await?;
         sqlx::query("CREATE INDEX IF NOT EXISTS idx_pages_created_at ON pages (created_at)").execute(&self.pool).await?;
         sqlx::query("CREATE INDEX IF NOT EXISTS idx_pages_updated_at ON pages (updated_at)").execute(&self.pool).await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_pages_notebook_updated ON pages (notebook_id, updated_at, id)").execute(&self.pool).await?;
+        sqlx::query("CREATE UNIQUE INDEX IF NOT EXISTS idx_pages_zettel_id ON pages (json_extract(metadata, '$.zettel_id'))").execute(&self.pool).await?;
         
         // Media attachment indexes
         sqlx::query("CREATE INDEX IF NOT EXISTS idx_media_page_id ON media_attachments (page_id)").execute(&self.pool).await?;
@@ -229,10 +596,13 @@ impl Database {
         // Page links indexes
         sqlx::query("CREATE INDEX IF NOT EXISTS idx_page_links_source ON page_links (source_page_id)").execute(&self.pool).await?;
         sqlx::query("CREATE INDEX IF NOT EXISTS idx_page_links_target ON page_links (target_page_id)").execute(&self.pool).await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_dismissed_links_target ON dismissed_links (target_page_id)").execute(&self.pool).await?;
         
         // Legacy note indexes
         sqlx::query("CREATE INDEX IF NOT EXISTS idx_notes_created_at ON notes (created_at)").execute(&self.pool).await?;
         sqlx::query("CREATE INDEX IF NOT EXISTS idx_notes_updated_at ON notes (updated_at)").execute(&self.pool).await?;
+        // Keyset pagination orders by (updated_at, id)
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_notes_updated_id ON notes (updated_at, id)").execute(&self.pool).await?;
         
         // Voice annotation indexes (updated)
         sqlx::query("CREATE INDEX IF NOT EXISTS idx_voice_annotations_page_id ON voice_annotations (page_id)").execute(&self.pool).await?;
@@ -242,68 +612,532 @@ impl Database {
         sqlx::query("CREATE INDEX IF NOT EXISTS idx_tags_name ON tags (name)").execute(&self.pool).await?;
         sqlx::query("CREATE INDEX IF NOT EXISTS idx_tags_usage_count ON tags (usage_count)").execute(&self.pool).await?;
 
+        // Automation run indexes
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_automation_runs_automation_id ON automation_runs (automation_id, executed_at)").execute(&self.pool).await?;
+
+        // Bundle content indexes
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_templates_bundle_id ON templates (bundle_id)").execute(&self.pool).await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_snippets_bundle_id ON snippets (bundle_id)").execute(&self.pool).await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_prompts_bundle_id ON prompts (bundle_id)").execute(&self.pool).await?;
+
+        // Audit log indexes
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_audit_log_created_at ON audit_log (created_at)").execute(&self.pool).await?;
+
+        // Page revision indexes
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_page_revisions_page_id ON page_revisions (page_id, created_at)").execute(&self.pool).await?;
+
+        // Export history indexes
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_export_history_page_id ON export_history (page_id, exported_at)").execute(&self.pool).await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_export_history_destination ON export_history (destination)").execute(&self.pool).await?;
+
+        // AI job indexes
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_ai_jobs_queue ON ai_jobs (status, priority, run_after)").execute(&self.pool).await?;
+
+        // Trash indexes
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_trash_deleted_at ON trash (deleted_at)").execute(&self.pool).await?;
+
+        // Recents indexes
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_recent_pages_accessed_at ON recent_pages (accessed_at)").execute(&self.pool).await?;
+
+        // Property indexes; the unique key covers lookups by page. Text values may be
+        // encrypted, so only numbers are compared in SQL
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_page_properties_number ON page_properties (name, number_value)").execute(&self.pool).await?;
+
+        // Tag indexes; the primary keys cover lookups by tag
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_page_tags_page_id ON page_tags (page_id)").execute(&self.pool).await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_note_tags_note_id ON note_tags (note_id)").execute(&self.pool).await?;
+
+        // Migrations run once every table exists
+        self.migrate_embedding_owners().await?;
+        self.migrate_embedding_models().await?;
+        self.migrate_embedding_hashes().await?;
+        self.migrate_tag_index().await?;
+        self.migrate_recent_pages().await?;
+        self.migrate_media_originals().await?;
+        self.migrate_sync_keys().await?;
+        self.migrate_thumbnail_encryption().await?;
+        self.migrate_automation_triggers().await?;
+        self.migrate_tag_normalization().await?;
+        self.migrate_property_encryption().await?;
+        self.migrate_vector_partitions().await?;
+
+        // Owners live in two tables, so cleanup is done with triggers instead of a foreign key
+        sqlx::query("CREATE TRIGGER IF NOT EXISTS embeddings_note_deleted AFTER DELETE ON notes BEGIN DELETE FROM embeddings WHERE owner_id = OLD.id; END")
+            .execute(&self.pool).await?;
+        sqlx::query("CREATE TRIGGER IF NOT EXISTS embeddings_page_deleted AFTER DELETE ON pages BEGIN DELETE FROM embeddings WHERE owner_id = OLD.id; END")
+            .execute(&self.pool).await?;
+        for (table, index, owner) in [("pages", "page_tags", "page_id"), ("notes", "note_tags", "note_id")] {
+            let fill = format!("INSERT OR IGNORE INTO {index} ({owner}, tag) SELECT NEW.id, value FROM json_each(NEW.tags);");
+            sqlx::query(&format!("CREATE TRIGGER IF NOT EXISTS {index}_inserted AFTER INSERT ON {table} BEGIN {fill} END"))
+                .execute(&self.pool).await?;
+            sqlx::query(&format!(
+                "CREATE TRIGGER IF NOT EXISTS {index}_updated AFTER UPDATE OF tags ON {table} BEGIN DELETE FROM {index} WHERE {owner} = NEW.id; {fill} END"
+            ))
+            .execute(&self.pool).await?;
+        }
+
+        // Every write to a synced table queues the document, whichever code path made it;
+        // cascaded deletes fire these too. Replacing the entry gives it a new `seq`, so a push
+        // can tell when a document changed again while it was being sent. An update that
+        // changes a natural key also deletes the document under the old one
+        for table in SYNC_TABLES {
+            let queue = |row: &str, deleted: i64| format!(
+                "INSERT OR REPLACE INTO sync_queue (doc_id, kind, deleted, changed_at) \
+                 SELECT {}, '{}', {deleted}, strftime('%Y-%m-%dT%H:%M:%fZ', 'now')",
+                table.doc_id_sql(row),
+                table.kind.as_str()
+            );
+            let rekeyed = format!("{} WHERE {} IS NOT {};", queue("OLD.", 1), table.doc_id_sql("OLD."), table.doc_id_sql("NEW."));
+            for (event, name, body) in [
+                ("INSERT", "inserted", format!("{};", queue("NEW.", 0))),
+                ("UPDATE", "updated", format!("{} {};", rekeyed, queue("NEW.", 0))),
+                ("DELETE", "deleted", format!("{};", queue("OLD.", 1))),
+            ] {
+                sqlx::query(&format!("CREATE TRIGGER IF NOT EXISTS sync_{}_{name} AFTER {event} ON {} BEGIN {body} END", table.name, table.name))
+                    .execute(&self.pool).await?;
+            }
+        }
+
+        sqlx::query(&format!("PRAGMA user_version = {}", SCHEMA_VERSION)).execute(&self.pool).await?;
+
         Ok(())
     }
 
-    // Note operations
-    pub async fn create_note(&self, title: String, content: String, tags: Vec<String>) -> AppResult<Note> {
-        let note = Note::new(title, content, tags);
-        
-        let encrypted_content = if let Some(ref enc) = self.encryption_manager {
-            enc.encrypt_string(&note.content)?
-        } else {
-            note.content.clone()
-        };
+    /// Schema 1 keyed embeddings by `note_id` with a foreign key to `notes`, which rejected
+    /// page embeddings. Rows are moved into the owner-typed table.
+    async fn migrate_embedding_owners(&self) -> AppResult<()> {
+        let legacy: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM pragma_table_info('embeddings') WHERE name = 'note_id'")
+            .fetch_one(&self.pool)
+            .await?;
+        if legacy == 0 {
+            return Ok(());
+        }
 
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("ALTER TABLE embeddings RENAME TO embeddings_v1").execute(&mut *tx).await?;
         sqlx::query(
             r#"
-            INSERT INTO notes (id, title, content, tags, created_at, updated_at, metadata)
-            VALUES (?, ?, ?, ?, ?, ?, ?)
+            CREATE TABLE embeddings (
+                owner_id TEXT PRIMARY KEY,
+                owner_type TEXT NOT NULL,
+                embedding BLOB NOT NULL,
+                created_at TEXT NOT NULL
+            )
             "#
-        )
-        .bind(&note.id)
-        .bind(&note.title)
-        .bind(&encrypted_content)
-        .bind(&serde_json::to_string(&note.tags)?)
-        .bind(&note.created_at.to_rfc3339())
-        .bind(&note.updated_at.to_rfc3339())
-        .bind(&serde_json::to_string(&note.metadata)?)
-        .execute(&self.pool)
-        .await?;
+        ).execute(&mut *tx).await?;
+        sqlx::query(
+            r#"
+            INSERT INTO embeddings (owner_id, owner_type, embedding, created_at)
+            SELECT note_id, CASE WHEN note_id IN (SELECT id FROM pages) THEN 'page' ELSE 'note' END, embedding, created_at
+            FROM embeddings_v1
+            "#
+        ).execute(&mut *tx).await?;
+        sqlx::query("DROP TABLE embeddings_v1").execute(&mut *tx).await?;
+        // The vector index used the old column names; it is rebuilt when next enabled
+        sqlx::query("DROP TABLE IF EXISTS vec_embeddings").execute(&mut *tx).await?;
+        sqlx::query("DELETE FROM settings WHERE key = ?").bind(VECTOR_INDEX_DIMENSION_KEY).execute(&mut *tx).await?;
+        tx.commit().await?;
 
-        // Update tag usage counts
-        for tag_name in &note.tags {
-            self.increment_tag_usage(tag_name).await?;
+        tracing::info!("Migrated embeddings to owner-typed schema");
+        Ok(())
+    }
+
+    /// Schema 2 didn't record which model produced an embedding. Existing rows keep a NULL
+    /// model until `adopt_unlabelled_embeddings` assigns the configured one.
+    async fn migrate_embedding_models(&self) -> AppResult<()> {
+        let has_model: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM pragma_table_info('embeddings') WHERE name = 'model'")
+            .fetch_one(&self.pool)
+            .await?;
+        if has_model == 0 {
+            sqlx::query("ALTER TABLE embeddings ADD COLUMN model TEXT").execute(&self.pool).await?;
+            tracing::info!("Added model column to embeddings");
         }
+        Ok(())
+    }
 
-        Ok(note)
+    /// Schema 5 didn't hash embedded content; those rows are regenerated on their next save.
+    async fn migrate_embedding_hashes(&self) -> AppResult<()> {
+        let has_hash: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM pragma_table_info('embeddings') WHERE name = 'content_hash'")
+            .fetch_one(&self.pool)
+            .await?;
+        if has_hash == 0 {
+            sqlx::query("ALTER TABLE embeddings ADD COLUMN content_hash TEXT").execute(&self.pool).await?;
+            tracing::info!("Added content_hash column to embeddings");
+        }
+        Ok(())
     }
 
-    pub async fn get_note(&self, id: &str) -> AppResult<Option<Note>> {
-        let row = sqlx::query(
+    /// Schema 6 had no tag index; it's filled from the existing `tags` arrays once.
+    async fn migrate_tag_index(&self) -> AppResult<()> {
+        if self.schema_version().await? >= 7 {
+            return Ok(());
+        }
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("INSERT OR IGNORE INTO page_tags (page_id, tag) SELECT pages.id, value FROM pages, json_each(pages.tags)")
+            .execute(&mut *tx).await?;
+        sqlx::query("INSERT OR IGNORE INTO note_tags (note_id, tag) SELECT notes.id, value FROM notes, json_each(notes.tags)")
+            .execute(&mut *tx).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Schema 7 only kept the last access in page metadata; those pages seed the recents.
+    async fn migrate_recent_pages(&self) -> AppResult<()> {
+        if self.schema_version().await? >= 8 {
+            return Ok(());
+        }
+        sqlx::query(
             r#"
-            SELECT id, title, content, tags, created_at, updated_at, metadata
-            FROM notes
-            WHERE id = ?
+            INSERT OR IGNORE INTO recent_pages (page_id, accessed_at)
+            SELECT id, json_extract(metadata, '$.last_accessed') FROM pages
+            WHERE json_extract(metadata, '$.last_accessed') IS NOT NULL
             "#
         )
-        .bind(id)
-        .fetch_optional(&self.pool)
+        .execute(&self.pool)
         .await?;
+        Ok(())
+    }
 
-        if let Some(row) = row {
-            let content: String = row.get("content");
-            let decrypted_content = if let Some(ref enc) = self.encryption_manager {
-                enc.decrypt_string(&content)?
-            } else {
-                content
-            };
-
-            let voice_annotations = self.get_voice_annotations(id).await?;
+    /// Schema 10 stored uploads only as compressed.
+    async fn migrate_media_originals(&self) -> AppResult<()> {
+        let has_original: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM pragma_table_info('media_attachments') WHERE name = 'original_data'")
+            .fetch_one(&self.pool)
+            .await?;
+        if has_original == 0 {
+            sqlx::query("ALTER TABLE media_attachments ADD COLUMN original_data BLOB").execute(&self.pool).await?;
+            tracing::info!("Added original_data column to media_attachments");
+        }
+        Ok(())
+    }
 
-            let note = Note {
-                id: row.get("id"),
-                title: row.get("title"),
+    /// Schema 12 queued page links under their local id and had no rekeying on update. Its
+    /// triggers are replaced, and links are sent again under their natural key.
+    async fn migrate_sync_keys(&self) -> AppResult<()> {
+        if self.schema_version().await? != 12 {
+            return Ok(());
+        }
+        let mut tx = self.pool.begin().await?;
+        for table in SYNC_TABLES {
+            for name in ["inserted", "updated", "deleted"] {
+                sqlx::query(&format!("DROP TRIGGER IF EXISTS sync_{}_{}", table.name, name)).execute(&mut *tx).await?;
+            }
+        }
+        sqlx::query("DELETE FROM sync_queue WHERE kind = 'page_link'").execute(&mut *tx).await?;
+        sqlx::query("DELETE FROM sync_revisions WHERE kind = 'page_link'").execute(&mut *tx).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Schema 13 stored thumbnails in plaintext even with encryption on.
+    async fn migrate_thumbnail_encryption(&self) -> AppResult<()> {
+        let Some(ref enc) = self.encryption_manager else {
+            return Ok(());
+        };
+        if self.schema_version().await? >= 14 {
+            return Ok(());
+        }
+        let rows = sqlx::query("SELECT id, thumbnail_data FROM media_attachments WHERE thumbnail_data IS NOT NULL")
+            .fetch_all(&self.pool)
+            .await?;
+        let mut tx = self.pool.begin().await?;
+        for row in &rows {
+            sqlx::query("UPDATE media_attachments SET thumbnail_data = ? WHERE id = ?")
+                .bind(enc.encrypt(&row.get::<Vec<u8>, _>("thumbnail_data"))?)
+                .bind(row.get::<String, _>("id"))
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        if !rows.is_empty() {
+            tracing::info!("Encrypted {} thumbnails", rows.len());
+        }
+        Ok(())
+    }
+
+    /// Schema 14 accepted task and reminder triggers, which nothing ever fired and which no
+    /// longer parse.
+    async fn migrate_automation_triggers(&self) -> AppResult<()> {
+        if self.schema_version().await? >= 15 {
+            return Ok(());
+        }
+        let removed = sqlx::query("DELETE FROM automations WHERE json_extract(trigger_config, '$.type') IN ('TaskCompleted', 'ReminderFired')")
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+        if removed > 0 {
+            tracing::info!("Removed {} automations with task or reminder triggers", removed);
+        }
+        Ok(())
+    }
+
+    /// Schema 16 stored property text in plaintext, indexed for comparisons in SQL. It's
+    /// encrypted like page content when a key is loaded, which leaves the index no use.
+    async fn migrate_property_encryption(&self) -> AppResult<()> {
+        if self.schema_version().await? >= 17 {
+            return Ok(());
+        }
+        sqlx::query("DROP INDEX IF EXISTS idx_page_properties_text").execute(&self.pool).await?;
+        let Some(ref enc) = self.encryption_manager else {
+            return Ok(());
+        };
+        let rows = sqlx::query("SELECT id, text_value FROM page_properties WHERE text_value IS NOT NULL")
+            .fetch_all(&self.pool)
+            .await?;
+        let mut tx = self.pool.begin().await?;
+        for row in &rows {
+            sqlx::query("UPDATE page_properties SET text_value = ? WHERE id = ?")
+                .bind(enc.encrypt_string(&row.get::<String, _>("text_value"))?)
+                .bind(row.get::<String, _>("id"))
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        if !rows.is_empty() {
+            tracing::info!("Encrypted {} property values", rows.len());
+        }
+        Ok(())
+    }
+
+    /// Schema 17's vector index didn't know which model produced a vector, so KNN queries
+    /// could fill `k` with other models' vectors and then drop them. It's rebuilt
+    /// partitioned by model when the index is next enabled.
+    async fn migrate_vector_partitions(&self) -> AppResult<()> {
+        if self.schema_version().await? >= 18 {
+            return Ok(());
+        }
+        sqlx::query("DROP TABLE IF EXISTS vec_embeddings").execute(&self.pool).await?;
+        sqlx::query("DELETE FROM settings WHERE key = ?").bind(VECTOR_INDEX_DIMENSION_KEY).execute(&self.pool).await?;
+        Ok(())
+    }
+
+    /// Schema 15 kept tags saved before normalization as they were typed, so `#Rust` and
+    /// `rust` were different tags. Lists are normalized, and tags that become the same are
+    /// folded together with their usage added up.
+    async fn migrate_tag_normalization(&self) -> AppResult<()> {
+        if self.schema_version().await? >= 16 {
+            return Ok(());
+        }
+        let mut tx = self.pool.begin().await?;
+        for table in ["pages", "notes"] {
+            let rows = sqlx::query(&format!("SELECT id, tags FROM {}", table)).fetch_all(&mut *tx).await?;
+            for row in rows {
+                let current: Vec<String> = serde_json::from_str(&row.get::<String, _>("tags"))?;
+                let normalized = tags::normalize_tags(current.clone());
+                if normalized != current {
+                    sqlx::query(&format!("UPDATE {} SET tags = ? WHERE id = ?", table))
+                        .bind(serde_json::to_string(&normalized)?)
+                        .bind(row.get::<String, _>("id"))
+                        .execute(&mut *tx)
+                        .await?;
+                }
+            }
+        }
+
+        let rows = sqlx::query("SELECT id, name, usage_count FROM tags").fetch_all(&mut *tx).await?;
+        for row in rows {
+            let (id, name): (String, String) = (row.get("id"), row.get("name"));
+            let normalized = match tags::normalize_tag(&name) {
+                Some(normalized) if normalized == name => continue,
+                Some(normalized) => normalized,
+                None => {
+                    sqlx::query("DELETE FROM tags WHERE id = ?").bind(&id).execute(&mut *tx).await?;
+                    continue;
+                }
+            };
+            let merged = sqlx::query("UPDATE tags SET usage_count = usage_count + ? WHERE name = ?")
+                .bind(row.get::<i64, _>("usage_count"))
+                .bind(&normalized)
+                .execute(&mut *tx)
+                .await?
+                .rows_affected();
+            let sql = if merged > 0 { "DELETE FROM tags WHERE id = ?2" } else { "UPDATE tags SET name = ?1 WHERE id = ?2" };
+            sqlx::query(sql).bind(&normalized).bind(&id).execute(&mut *tx).await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    pub async fn schema_version(&self) -> AppResult<i64> {
+        let row = sqlx::query("PRAGMA user_version").fetch_one(&self.pool).await?;
+        Ok(row.get::<i64, _>(0))
+    }
+
+    /// Row counts and sizes only; never reads note content.
+    pub async fn get_vault_stats(&self) -> AppResult<VaultStats> {
+        let count = |table: &'static str| async move {
+            let row = sqlx::query(&format!("SELECT COUNT(*) AS count FROM {}", table))
+                .fetch_one(&self.pool)
+                .await?;
+            Ok::<u64, AppError>(row.get::<i64, _>("count") as u64)
+        };
+
+        let media_bytes: i64 = sqlx::query("SELECT COALESCE(SUM(file_size), 0) AS total FROM media_attachments")
+            .fetch_one(&self.pool)
+            .await?
+            .get("total");
+        let (database_bytes, _) = self.get_file_usage().await?;
+
+        Ok(VaultStats {
+            notes: count("notes").await?,
+            notebooks: count("notebooks").await?,
+            sections: count("sections").await?,
+            pages: count("pages").await?,
+            media_attachments: count("media_attachments").await?,
+            media_bytes: media_bytes as u64,
+            voice_annotations: count("voice_annotations").await?,
+            embeddings: count("embeddings").await?,
+            tags: count("tags").await?,
+            database_bytes,
+        })
+    }
+
+    /// Totals for the stats screen. Sizes come from SQLite's page counts, so they're current
+    /// even before a checkpoint shrinks the file on disk.
+    pub async fn get_app_stats(&self) -> AppResult<AppStats> {
+        let counts = sqlx::query(
+            r#"
+            SELECT (SELECT COUNT(*) FROM notes) AS notes,
+                   (SELECT COUNT(*) FROM voice_annotations) AS voice_annotations,
+                   (SELECT COUNT(*) FROM tags) AS tags,
+                   (SELECT MIN(created_at) FROM notes) AS oldest_note,
+                   (SELECT MAX(created_at) FROM notes) AS newest_note
+            "#
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        let note_time = |column: &str| -> AppResult<Option<DateTime<Utc>>> {
+            Ok(match counts.get::<Option<String>, _>(column) {
+                Some(value) => Some(DateTime::parse_from_rfc3339(&value)?.with_timezone(&Utc)),
+                None => None,
+            })
+        };
+
+        let most_used_tags = sqlx::query("SELECT name, usage_count FROM tags WHERE usage_count > 0 ORDER BY usage_count DESC, name LIMIT 10")
+            .fetch_all(&self.pool)
+            .await?
+            .iter()
+            .map(|row| (row.get("name"), row.get::<i64, _>("usage_count") as u32))
+            .collect();
+        let (database_size, free_bytes) = self.get_file_usage().await?;
+        let last_maintenance = self.get_setting(maintenance::LAST_MAINTENANCE_KEY).await?
+            .and_then(|value| DateTime::parse_from_rfc3339(&value).ok())
+            .map(|value| value.with_timezone(&Utc));
+
+        Ok(AppStats {
+            total_notes: counts.get::<i64, _>("notes") as u32,
+            total_voice_annotations: counts.get::<i64, _>("voice_annotations") as u32,
+            total_tags: counts.get::<i64, _>("tags") as u32,
+            database_size,
+            free_bytes,
+            oldest_note: note_time("oldest_note")?,
+            newest_note: note_time("newest_note")?,
+            most_used_tags,
+            table_sizes: self.get_table_sizes().await?,
+            last_maintenance,
+        })
+    }
+
+    /// Size of the database in bytes and how much of it is free pages left by deletions.
+    pub async fn get_file_usage(&self) -> AppResult<(u64, u64)> {
+        let page_count: i64 = sqlx::query("PRAGMA page_count").fetch_one(&self.pool).await?.get(0);
+        let freelist_count: i64 = sqlx::query("PRAGMA freelist_count").fetch_one(&self.pool).await?.get(0);
+        let page_size: i64 = sqlx::query("PRAGMA page_size").fetch_one(&self.pool).await?.get(0);
+        Ok(((page_count * page_size) as u64, (freelist_count * page_size) as u64))
+    }
+
+    /// Bytes used by each table together with its indexes, largest first.
+    pub async fn get_table_sizes(&self) -> AppResult<Vec<TableSize>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT m.tbl_name AS table_name, SUM(d.pgsize) AS bytes
+            FROM dbstat d
+            JOIN sqlite_master m ON m.name = d.name
+            GROUP BY m.tbl_name
+            ORDER BY bytes DESC, table_name
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| TableSize {
+                table: row.get("table_name"),
+                bytes: row.get::<i64, _>("bytes") as u64,
+            })
+            .collect())
+    }
+
+    /// Rebuilds the file without free pages, refreshes the query planner's statistics and
+    /// truncates the WAL so the space goes back to the file system. Other connections must
+    /// be idle; VACUUM fails with SQLITE_BUSY otherwise.
+    pub async fn vacuum_and_analyze(&self) -> AppResult<()> {
+        let mut connection = self.pool.acquire().await?;
+        sqlx::query("VACUUM").execute(&mut *connection).await?;
+        sqlx::query("ANALYZE").execute(&mut *connection).await?;
+        sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)").execute(&mut *connection).await?;
+        Ok(())
+    }
+
+    // Note operations
+    pub async fn create_note(&self, title: String, content: String, tags: Vec<String>) -> AppResult<Note> {
+        let note = Note::new(title, content, tags::normalize_tags(tags));
+        
+        let encrypted_content = if let Some(ref enc) = self.encryption_manager {
+            enc.encrypt_string(&note.content)?
+        } else {
+            note.content.clone()
+        };
+
+        sqlx::query(
+            r#"
+            INSERT INTO notes (id, title, content, tags, created_at, updated_at, metadata)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+            "#
+        )
+        .bind(&note.id)
+        .bind(&note.title)
+        .bind(&encrypted_content)
+        .bind(&serde_json::to_string(&note.tags)?)
+        .bind(&note.created_at.to_rfc3339())
+        .bind(&note.updated_at.to_rfc3339())
+        .bind(&serde_json::to_string(&note.metadata)?)
+        .execute(&self.pool)
+        .await?;
+
+        // Update tag usage counts
+        for tag_name in &note.tags {
+            self.increment_tag_usage(tag_name).await?;
+        }
+
+        Ok(note)
+    }
+
+    pub async fn get_note(&self, id: &str) -> AppResult<Option<Note>> {
+        let row = sqlx::query(
+            r#"
+            SELECT id, title, content, tags, created_at, updated_at, metadata
+            FROM notes
+            WHERE id = ?
+            "#
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        if let Some(row) = row {
+            let content: String = row.get("content");
+            let decrypted_content = if let Some(ref enc) = self.encryption_manager {
+                enc.decrypt_string(&content)?
+            } else {
+                content
+            };
+
+            let voice_annotations = self.get_voice_annotations(id).await?;
+
+            let note = Note {
+                id: row.get("id"),
+                title: row.get("title"),
                 content: decrypted_content,
                 tags: serde_json::from_str(&row.get::<String, _>("tags"))?,
                 created_at: DateTime::parse_from_rfc3339(&row.get::<String, _>("created_at"))?.with_timezone(&Utc),
@@ -318,6 +1152,8 @@ impl Database {
         }
     }
 
+    /// Offset pagination, which can skip or repeat notes edited between pages; prefer
+    /// `list_notes` for anything beyond the first page.
     pub async fn get_notes(&self, limit: Option<usize>, offset: Option<usize>) -> AppResult<Vec<Note>> {
         let limit = limit.unwrap_or(50);
         let offset = offset.unwrap_or(0);
@@ -326,7 +1162,7 @@ impl Database {
             r#"
             SELECT id, title, content, tags, created_at, updated_at, metadata
             FROM notes
-            ORDER BY updated_at DESC
+            ORDER BY updated_at DESC, id DESC
             LIMIT ? OFFSET ?
             "#
         )
@@ -335,6 +1171,32 @@ impl Database {
         .fetch_all(&self.pool)
         .await?;
 
+        self.notes_from_rows(rows).await
+    }
+
+    /// Notes by last update, newest first, one page at a time. The cursor is the sort key of
+    /// the last note returned, so pages stay stable when notes are added or edited.
+    pub async fn list_notes(&self, limit: Option<usize>, cursor: Option<&str>) -> AppResult<SearchPage<Note>> {
+        let limit = limit.unwrap_or(50).max(1);
+        let (condition, binds) = cursor_condition(cursor)?;
+        let conditions: Vec<String> = condition.into_iter().collect();
+        let sql = format!(
+            "SELECT id, title, content, tags, created_at, updated_at, metadata FROM notes {} ORDER BY updated_at DESC, id DESC LIMIT ?",
+            where_clause(&conditions)
+        );
+
+        let mut query = sqlx::query(&sql);
+        for value in &binds {
+            query = query.bind(value);
+        }
+        // One extra row says whether there's another page
+        let rows = query.bind((limit + 1) as i64).fetch_all(&self.pool).await?;
+        let mut notes = self.notes_from_rows(rows).await?;
+        let next_cursor = next_cursor(&mut notes, limit, |note| (note.updated_at, note.id.clone()))?;
+        Ok(SearchPage { items: notes, next_cursor })
+    }
+
+    async fn notes_from_rows(&self, rows: Vec<sqlx::sqlite::SqliteRow>) -> AppResult<Vec<Note>> {
         let mut notes = Vec::new();
         for row in rows {
             let content: String = row.get("content");
@@ -376,7 +1238,7 @@ impl Database {
         }
 
         if let Some(tags) = tags {
-            note.tags = tags;
+            note.tags = tags::normalize_tags(tags);
         }
 
         note.updated_at = Utc::now();
@@ -406,40 +1268,85 @@ impl Database {
         Ok(())
     }
 
-    pub async fn delete_note(&self, id: &str) -> AppResult<()> {
-        sqlx::query("DELETE FROM notes WHERE id = ?")
+    /// Moves a note and its voice annotations, attachments and embedding into the trash.
+    pub async fn trash_note(&self, id: &str) -> AppResult<()> {
+        let mut tx = self.pool.begin().await?;
+        let title: String = sqlx::query_scalar("SELECT title FROM notes WHERE id = ?")
             .bind(id)
-            .execute(&self.pool)
-            .await?;
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("Note with id {} not found", id)))?;
 
+        let ids = vec![id.to_string()];
+        let mut payload = Vec::new();
+        for (table, condition) in [
+            ("notes", "id IN ({ids})"),
+            ("voice_annotations", "note_id IN ({ids})"),
+            ("media_attachments", "note_id IN ({ids})"),
+            ("embeddings", "owner_id IN ({ids})"),
+        ] {
+            payload.push((table.to_string(), select_raw_rows(&mut tx, table, condition, &ids).await?));
+        }
+        insert_trash_row(&mut tx, id, TrashItemType::Note, &title, None, 0, &payload).await?;
+        sqlx::query("DELETE FROM notes WHERE id = ?").bind(id).execute(&mut *tx).await?;
+        tx.commit().await?;
         Ok(())
     }
 
     // Voice annotation operations
-    pub async fn add_voice_annotation(&self, note_id: &str, audio_data: Vec<u8>, transcription: String, duration: f64) -> AppResult<VoiceAnnotation> {
+    /// `metadata.format` must say what `audio_data` really is: `pcm` for bare samples, or
+    /// the container of an imported file. Only recordings known to be PCM are compressed.
+    pub async fn add_voice_annotation(&self, note_id: &str, audio_data: Vec<u8>, transcription: String, duration: f64, metadata: VoiceMetadata) -> AppResult<VoiceAnnotation> {
+        self.insert_voice_annotation(Some(note_id), None, audio_data, transcription, duration, metadata).await
+    }
+
+    pub async fn add_page_voice_annotation(&self, page_id: &str, audio_data: Vec<u8>, transcription: String, duration: f64, metadata: VoiceMetadata) -> AppResult<VoiceAnnotation> {
+        self.insert_voice_annotation(None, Some(page_id), audio_data, transcription, duration, metadata).await
+    }
+
+    async fn insert_voice_annotation(&self, note_id: Option<&str>, page_id: Option<&str>, audio_data: Vec<u8>, transcription: String, duration: f64, mut metadata: VoiceMetadata) -> AppResult<VoiceAnnotation> {
+        storage::check_file_size("Voice recording", audio_data.len() as u64, self.max_file_size)?;
+        metadata.peaks = voice_codec::mono_pcm(&audio_data, &metadata)
+            .map(|(pcm, _)| audio::peaks(pcm, audio::WAVEFORM_PEAKS))
+            .unwrap_or_default();
+        let stored_audio = match voice_codec::compress(audio_data.clone(), &mut metadata).await {
+            Ok(Some(opus)) => opus,
+            Ok(None) => audio_data,
+            Err(e) => {
+                // Kept as PCM rather than losing the recording
+                tracing::warn!("Failed to encode voice annotation as Opus: {}", e);
+                audio_data
+            }
+        };
+        if let Some(page_id) = page_id {
+            self.check_page_quota(page_id, stored_audio.len() as u64).await?;
+        }
+        // Returned as stored, so `metadata.format` describes `audio_data`
         let annotation = VoiceAnnotation {
             id: Uuid::new_v4().to_string(),
-            note_id: note_id.to_string(),
-            audio_data: audio_data.clone(),
+            note_id: note_id.map(|id| id.to_string()),
+            page_id: page_id.map(|id| id.to_string()),
+            audio_data: stored_audio,
             transcription,
             timestamp: Utc::now(),
             duration,
-            metadata: VoiceMetadata::default(),
+            metadata,
         };
 
         let encrypted_audio = if let Some(ref enc) = self.encryption_manager {
-            enc.encrypt(&audio_data)?
+            enc.encrypt(&annotation.audio_data)?
         } else {
-            audio_data
+            annotation.audio_data.clone()
         };
 
         sqlx::query(
             r#"
-            INSERT INTO voice_annotations (id, note_id, audio_data, transcription, timestamp, duration, metadata)
-            VALUES (?, ?, ?, ?, ?, ?, ?)
+            INSERT INTO voice_annotations (id, page_id, note_id, audio_data, transcription, timestamp, duration, metadata)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
             "#
         )
         .bind(&annotation.id)
+        .bind(&annotation.page_id)
         .bind(&annotation.note_id)
         .bind(&encrypted_audio)
         .bind(&annotation.transcription)
@@ -452,94 +1359,435 @@ impl Database {
         Ok(annotation)
     }
 
-    async fn get_voice_annotations(&self, note_id: &str) -> AppResult<Vec<VoiceAnnotation>> {
-        let rows = sqlx::query(
+    pub async fn get_voice_annotation(&self, id: &str) -> AppResult<Option<VoiceAnnotation>> {
+        let row = sqlx::query(
             r#"
-            SELECT id, note_id, audio_data, transcription, timestamp, duration, metadata
+            SELECT id, page_id, note_id, audio_data, transcription, timestamp, duration, metadata
             FROM voice_annotations
-            WHERE note_id = ?
-            ORDER BY timestamp ASC
+            WHERE id = ?
             "#
         )
-        .bind(note_id)
-        .fetch_all(&self.pool)
+        .bind(id)
+        .fetch_optional(&self.pool)
         .await?;
 
-        let mut annotations = Vec::new();
-        for row in rows {
-            let audio_data: Vec<u8> = row.get("audio_data");
-            let decrypted_audio = if let Some(ref enc) = self.encryption_manager {
-                enc.decrypt(&audio_data)?
-            } else {
-                audio_data
-            };
+        row.map(|row| self.voice_annotation_from_row(&row)).transpose()
+    }
 
-            let annotation = VoiceAnnotation {
-                id: row.get("id"),
-                note_id: row.get("note_id"),
-                audio_data: decrypted_audio,
-                transcription: row.get("transcription"),
-                timestamp: DateTime::parse_from_rfc3339(&row.get::<String, _>("timestamp"))?.with_timezone(&Utc),
-                duration: row.get("duration"),
-                metadata: serde_json::from_str(&row.get::<String, _>("metadata"))?,
-            };
-            annotations.push(annotation);
-        }
+    /// Like `get_voice_annotation`, with Opus recordings decoded back to PCM for
+    /// transcription and playback.
+    pub async fn get_voice_annotation_pcm(&self, id: &str) -> AppResult<Option<VoiceAnnotation>> {
+        let Some(mut annotation) = self.get_voice_annotation(id).await? else {
+            return Ok(None);
+        };
+        annotation.audio_data = voice_codec::to_pcm(annotation.audio_data, &mut annotation.metadata).await?;
+        Ok(Some(annotation))
+    }
 
-        Ok(annotations)
+    /// The annotation's audio as stored, decrypted; `metadata.format` says whether it's Opus.
+    fn voice_annotation_from_row(&self, row: &sqlx::sqlite::SqliteRow) -> AppResult<VoiceAnnotation> {
+        let audio_data: Vec<u8> = row.get("audio_data");
+        let decrypted_audio = if let Some(ref enc) = self.encryption_manager {
+            enc.decrypt(&audio_data)?
+        } else {
+            audio_data
+        };
+        let metadata: VoiceMetadata = serde_json::from_str(&row.get::<String, _>("metadata"))?;
+
+        Ok(VoiceAnnotation {
+            id: row.get("id"),
+            note_id: row.get("note_id"),
+            page_id: row.get("page_id"),
+            audio_data: decrypted_audio,
+            transcription: row.get("transcription"),
+            timestamp: DateTime::parse_from_rfc3339(&row.get::<String, _>("timestamp"))?.with_timezone(&Utc),
+            duration: row.get("duration"),
+            metadata,
+        })
     }
 
-    // Tag operations
-    pub async fn get_tags(&self) -> AppResult<Vec<Tag>> {
-        let rows = sqlx::query(
-            r#"
-            SELECT id, name, color, description, usage_count, created_at, last_used
-            FROM tags
-            ORDER BY usage_count DESC, name ASC
-            "#
+    /// Only the audio of an annotation, decrypted, without its note or transcription.
+    pub async fn get_voice_annotation_audio(&self, id: &str) -> AppResult<Option<VoiceAudio>> {
+        let row = sqlx::query("SELECT audio_data, duration, metadata FROM voice_annotations WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+        let Some(row) = row else {
+            return Ok(None);
+        };
+
+        let audio_data: Vec<u8> = row.get("audio_data");
+        let audio_data = if let Some(ref enc) = self.encryption_manager {
+            enc.decrypt(&audio_data)?
+        } else {
+            audio_data
+        };
+        let mut metadata: VoiceMetadata = serde_json::from_str(&row.get::<String, _>("metadata"))?;
+        let audio_data = voice_codec::to_pcm(audio_data, &mut metadata).await?;
+        Ok(Some(audio::playable(id, audio_data, row.get("duration"), &metadata)))
+    }
+
+    /// Re-encodes annotations recorded before they were stored as Opus, one at a time so
+    /// only one recording is held in memory. Only WAV recordings whose header shows mono
+    /// 16-bit PCM, or ones stored as `pcm`, are touched; anything else may be compressed
+    /// audio already and is skipped.
+    pub async fn recompress_voice_annotations(&self) -> AppResult<VoiceRecompressReport> {
+        let ids: Vec<String> = sqlx::query_scalar(
+            "SELECT id FROM voice_annotations WHERE json_extract(metadata, '$.format') IS NOT ?"
         )
+        .bind(voice_codec::OPUS_FORMAT)
         .fetch_all(&self.pool)
         .await?;
 
-        let mut tags = Vec::new();
-        for row in rows {
-            let tag = Tag {
-                id: row.get("id"),
-                name: row.get("name"),
-                color: row.get("color"),
-                description: row.get("description"),
-                usage_count: row.get("usage_count"),
-                created_at: DateTime::parse_from_rfc3339(&row.get::<String, _>("created_at"))?.with_timezone(&Utc),
-                last_used: row.get::<Option<String>, _>("last_used")
-                    .map(|s| DateTime::parse_from_rfc3339(&s).unwrap().with_timezone(&Utc)),
+        let mut report = VoiceRecompressReport::default();
+        for id in ids {
+            let stored_size: Option<i64> = sqlx::query_scalar("SELECT length(audio_data) FROM voice_annotations WHERE id = ?")
+                .bind(&id)
+                .fetch_optional(&self.pool)
+                .await?;
+            let Some(annotation) = self.get_voice_annotation(&id).await? else {
+                continue;
+            };
+            let mut metadata = annotation.metadata;
+            let opus = match voice_codec::compress(annotation.audio_data, &mut metadata).await {
+                Ok(Some(opus)) => opus,
+                Ok(None) => {
+                    report.skipped += 1;
+                    continue;
+                }
+                Err(e) => {
+                    tracing::warn!("Failed to encode voice annotation {} as Opus: {}", id, e);
+                    report.skipped += 1;
+                    continue;
+                }
             };
-            tags.push(tag);
+            let stored_audio = if let Some(ref enc) = self.encryption_manager {
+                enc.encrypt(&opus)?
+            } else {
+                opus
+            };
+
+            sqlx::query("UPDATE voice_annotations SET audio_data = ?, metadata = ? WHERE id = ?")
+                .bind(&stored_audio)
+                .bind(&serde_json::to_string(&metadata)?)
+                .bind(&id)
+                .execute(&self.pool)
+                .await?;
+            report.recompressed += 1;
+            report.bytes_before += stored_size.unwrap_or(0) as u64;
+            report.bytes_after += stored_audio.len() as u64;
         }
+        report.bytes_saved = report.bytes_before.saturating_sub(report.bytes_after);
+        Ok(report)
+    }
 
-        Ok(tags)
+    /// Stores a transcription made after the annotation was saved.
+    pub async fn update_voice_transcription(&self, id: &str, transcription: &str, language: Option<String>, segments: Vec<VoiceSegment>) -> AppResult<()> {
+        let Some(annotation) = self.get_voice_annotation_summary(id).await? else {
+            return Err(AppError::NotFound(format!("Voice annotation {} not found", id)));
+        };
+        let metadata = VoiceMetadata { language, segments, ..annotation.metadata };
+        sqlx::query("UPDATE voice_annotations SET transcription = ?, metadata = ? WHERE id = ?")
+            .bind(transcription)
+            .bind(&serde_json::to_string(&metadata)?)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
     }
 
-    async fn increment_tag_usage(&self, tag_name: &str) -> AppResult<()> {
-        // Check if tag exists
-        let existing = sqlx::query("SELECT id FROM tags WHERE name = ?")
-            .bind(tag_name)
+    pub async fn set_voice_peaks(&self, id: &str, peaks: &[f32]) -> AppResult<()> {
+        sqlx::query("UPDATE voice_annotations SET metadata = json_set(metadata, '$.peaks', json(?)) WHERE id = ?")
+            .bind(serde_json::to_string(peaks)?)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Replaces the transcription with a hand-corrected one. The timed segments are dropped,
+    /// as their text no longer matches, and later transcription jobs leave it alone.
+    pub async fn update_voice_annotation(&self, request: UpdateVoiceAnnotationRequest) -> AppResult<VoiceAnnotationSummary> {
+        let row = sqlx::query("SELECT metadata FROM voice_annotations WHERE id = ?")
+            .bind(&request.id)
             .fetch_optional(&self.pool)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("Voice annotation {} not found", request.id)))?;
+        let mut metadata: VoiceMetadata = serde_json::from_str(&row.get::<String, _>("metadata"))?;
+        metadata.segments.clear();
+        metadata.edited = true;
+
+        sqlx::query("UPDATE voice_annotations SET transcription = ?, metadata = ? WHERE id = ?")
+            .bind(request.transcription.trim())
+            .bind(&serde_json::to_string(&metadata)?)
+            .bind(&request.id)
+            .execute(&self.pool)
             .await?;
+        self.get_voice_annotation_summary(&request.id).await?
+            .ok_or_else(|| AppError::NotFound(format!("Voice annotation {} not found", request.id)))
+    }
 
-        if existing.is_some() {
-            // Update usage count
-            sqlx::query(
-                r#"
-                UPDATE tags
-                SET usage_count = usage_count + 1, last_used = ?
-                WHERE name = ?
-                "#
-            )
-            .bind(&Utc::now().to_rfc3339())
-            .bind(tag_name)
+    pub async fn delete_voice_annotation(&self, id: &str) -> AppResult<()> {
+        let result = sqlx::query("DELETE FROM voice_annotations WHERE id = ?")
+            .bind(id)
             .execute(&self.pool)
             .await?;
-        } else {
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound(format!("Voice annotation {} not found", id)));
+        }
+        Ok(())
+    }
+
+    pub async fn get_voice_annotation_summary(&self, id: &str) -> AppResult<Option<VoiceAnnotationSummary>> {
+        let row = sqlx::query("SELECT id, page_id, note_id, transcription, timestamp, duration, metadata FROM voice_annotations WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+        row.as_ref().map(voice_annotation_summary_from_row).transpose()
+    }
+
+    /// Annotations of a note or page, or all of them, oldest first, without loading audio.
+    pub async fn list_voice_annotations(&self, note_id: Option<&str>, page_id: Option<&str>) -> AppResult<Vec<VoiceAnnotationSummary>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, page_id, note_id, transcription, timestamp, duration, metadata
+            FROM voice_annotations
+            WHERE (?1 IS NULL OR note_id = ?1) AND (?2 IS NULL OR page_id = ?2)
+            ORDER BY timestamp ASC
+            "#
+        )
+        .bind(note_id)
+        .bind(page_id)
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter().map(voice_annotation_summary_from_row).collect()
+    }
+
+    async fn get_voice_annotations(&self, note_id: &str) -> AppResult<Vec<VoiceAnnotation>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, page_id, note_id, audio_data, transcription, timestamp, duration, metadata
+            FROM voice_annotations
+            WHERE note_id = ?
+            ORDER BY timestamp ASC
+            "#
+        )
+        .bind(note_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut annotations = Vec::new();
+        for row in rows {
+            annotations.push(self.voice_annotation_from_row(&row)?);
+        }
+
+        Ok(annotations)
+    }
+
+    // Tag operations
+    pub async fn get_tags(&self) -> AppResult<Vec<Tag>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, name, color, description, usage_count, created_at, last_used
+            FROM tags
+            ORDER BY usage_count DESC, name ASC
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.iter().map(tag_from_row).collect()
+    }
+
+    pub async fn get_tag(&self, id: &str) -> AppResult<Option<Tag>> {
+        let row = sqlx::query("SELECT id, name, color, description, usage_count, created_at, last_used FROM tags WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+        row.as_ref().map(tag_from_row).transpose()
+    }
+
+    /// Pages with `tag`, optionally only in one notebook, most recently updated first.
+    pub async fn get_pages_by_tag(&self, tag: &str, notebook_id: Option<&str>) -> AppResult<Vec<Page>> {
+        let tag = tags::normalize_tag(tag).ok_or_else(|| AppError::InvalidFormat("Tag can't be empty".to_string()))?;
+        let rows = sqlx::query(
+            r#"
+            SELECT p.id, p.notebook_id, p.section_id, p.parent_page_id, p.title, p.content, p.tags, p.order_index, p.created_at, p.updated_at, p.metadata
+            FROM page_tags t
+            JOIN pages p ON p.id = t.page_id
+            WHERE t.tag = ? AND (? IS NULL OR p.notebook_id = ?)
+            ORDER BY p.updated_at DESC, p.id DESC
+            "#
+        )
+        .bind(&tag)
+        .bind(notebook_id)
+        .bind(notebook_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        self.pages_from_rows(rows)
+    }
+
+    /// Notes with `tag`, most recently updated first.
+    pub async fn get_notes_by_tag(&self, tag: &str) -> AppResult<Vec<Note>> {
+        let tag = tags::normalize_tag(tag).ok_or_else(|| AppError::InvalidFormat("Tag can't be empty".to_string()))?;
+        let rows = sqlx::query(
+            r#"
+            SELECT n.id, n.title, n.content, n.tags, n.created_at, n.updated_at, n.metadata
+            FROM note_tags t
+            JOIN notes n ON n.id = t.note_id
+            WHERE t.tag = ?
+            ORDER BY n.updated_at DESC, n.id DESC
+            "#
+        )
+        .bind(&tag)
+        .fetch_all(&self.pool)
+        .await?;
+
+        self.notes_from_rows(rows).await
+    }
+
+    /// Renames, recolors or redescribes a tag. A rename rewrites every note and page using
+    /// the old name; renaming onto another existing tag is a merge, so it's refused.
+    pub async fn update_tag(&self, request: &UpdateTagRequest) -> AppResult<Tag> {
+        let mut tx = self.pool.begin().await?;
+        let name: String = sqlx::query_scalar("SELECT name FROM tags WHERE id = ?")
+            .bind(&request.id)
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("Tag with id {} not found", request.id)))?;
+
+        if let Some(new_name) = &request.name {
+            let new_name = tags::normalize_tag(new_name)
+                .ok_or_else(|| AppError::InvalidFormat("Tag name can't be empty".to_string()))?;
+            if new_name != name {
+                let taken = sqlx::query("SELECT id FROM tags WHERE name = ?").bind(&new_name).fetch_optional(&mut *tx).await?;
+                if taken.is_some() {
+                    return Err(AppError::InvalidOperation(format!("A tag named {} already exists; merge the tags instead", new_name)));
+                }
+                let mapping = HashMap::from([(name.clone(), new_name.clone())]);
+                rewrite_tag_lists(&mut tx, &[name], |current| tags::apply_mapping(current, &mapping)).await?;
+                sqlx::query("UPDATE tags SET name = ? WHERE id = ?").bind(&new_name).bind(&request.id).execute(&mut *tx).await?;
+            }
+        }
+        if let Some(color) = &request.color {
+            if !tags::is_valid_color(color) {
+                return Err(AppError::InvalidFormat(format!("Invalid tag color {}", color)));
+            }
+            sqlx::query("UPDATE tags SET color = ? WHERE id = ?").bind(color).bind(&request.id).execute(&mut *tx).await?;
+        }
+        if let Some(description) = &request.description {
+            let description = Some(description.trim()).filter(|d| !d.is_empty());
+            sqlx::query("UPDATE tags SET description = ? WHERE id = ?").bind(description).bind(&request.id).execute(&mut *tx).await?;
+        }
+        tx.commit().await?;
+
+        self.get_tag(&request.id).await?
+            .ok_or_else(|| AppError::NotFound(format!("Tag with id {} not found", request.id)))
+    }
+
+    /// Deletes a tag and removes it from every note and page. Returns how many pages and
+    /// notes were changed.
+    pub async fn delete_tag(&self, id: &str) -> AppResult<(usize, usize)> {
+        let mut tx = self.pool.begin().await?;
+        let name: String = sqlx::query_scalar("DELETE FROM tags WHERE id = ? RETURNING name")
+            .bind(id)
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("Tag with id {} not found", id)))?;
+        let removed = [name.clone()];
+        let updated = rewrite_tag_lists(&mut tx, &[name], |current| tags::remove_tags(current, &removed)).await?;
+        tx.commit().await?;
+        Ok(updated)
+    }
+
+    /// Rewrites every note and page that uses a source tag to use its target instead, then
+    /// folds the sources' usage into the target. Runs in one transaction.
+    pub async fn merge_tags(&self, merges: &[TagMerge]) -> AppResult<TagMergeResult> {
+        let mut mapping = HashMap::new();
+        let mut normalized = Vec::new();
+        for merge in merges {
+            let target = tags::normalize_tag(&merge.target)
+                .ok_or_else(|| AppError::Validation("Tag can't be empty".to_string()))?;
+            let mut sources = Vec::new();
+            for source in &merge.sources {
+                // Stored tags are normalized, so sources must be too to match them
+                let source = tags::normalize_tag(source)
+                    .ok_or_else(|| AppError::Validation("Tag can't be empty".to_string()))?;
+                if source == target {
+                    return Err(AppError::InvalidOperation(format!("Tag {} can't be merged into itself", source)));
+                }
+                mapping.insert(source.clone(), target.clone());
+                sources.push(source);
+            }
+            normalized.push((target, sources));
+        }
+        if mapping.is_empty() {
+            return Ok(TagMergeResult { pages_updated: 0, notes_updated: 0, tags_removed: 0 });
+        }
+        let sources: Vec<String> = mapping.keys().cloned().collect();
+
+        let mut tx = self.pool.begin().await?;
+        let (pages_updated, notes_updated) = rewrite_tag_lists(&mut tx, &sources, |current| tags::apply_mapping(current, &mapping)).await?;
+
+        let mut tags_removed = 0;
+        for (target, sources) in normalized {
+            let mut usage: i64 = 0;
+            for source in &sources {
+                let removed = sqlx::query("DELETE FROM tags WHERE name = ? RETURNING usage_count")
+                    .bind(source)
+                    .fetch_optional(&mut *tx)
+                    .await?;
+                if let Some(row) = removed {
+                    usage += row.get::<i64, _>("usage_count");
+                    tags_removed += 1;
+                }
+            }
+
+            let target = Tag::new(target, "#3B82F6".to_string());
+            sqlx::query(
+                r#"
+                INSERT INTO tags (id, name, color, description, usage_count, created_at, last_used)
+                VALUES (?, ?, ?, ?, ?, ?, ?)
+                ON CONFLICT(name) DO UPDATE SET usage_count = usage_count + excluded.usage_count
+                "#
+            )
+            .bind(&target.id)
+            .bind(&target.name)
+            .bind(&target.color)
+            .bind(&target.description)
+            .bind(usage)
+            .bind(&target.created_at.to_rfc3339())
+            .bind(&Utc::now().to_rfc3339())
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+
+        Ok(TagMergeResult { pages_updated, notes_updated, tags_removed })
+    }
+
+    async fn increment_tag_usage(&self, tag_name: &str) -> AppResult<()> {
+        // Check if tag exists
+        let existing = sqlx::query("SELECT id FROM tags WHERE name = ?")
+            .bind(tag_name)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        if existing.is_some() {
+            // Update usage count
+            sqlx::query(
+                r#"
+                UPDATE tags
+                SET usage_count = usage_count + 1, last_used = ?
+                WHERE name = ?
+                "#
+            )
+            .bind(&Utc::now().to_rfc3339())
+            .bind(tag_name)
+            .execute(&self.pool)
+            .await?;
+        } else {
             // Create new tag
             let tag = Tag::new(tag_name.to_string(), "#3B82F6".to_string());
             sqlx::query(
@@ -563,22 +1811,47 @@ impl Database {
     }
 
     // Search operations
-    pub async fn search_notes(&self, query: &str) -> AppResult<Vec<Note>> {
-        let rows = sqlx::query(
-            r#"
-            SELECT id, title, content, tags, created_at, updated_at, metadata
-            FROM notes
-            WHERE title LIKE ? OR content LIKE ?
-            ORDER BY updated_at DESC
-            "#
-        )
-        .bind(&format!("%{}%", query))
-        .bind(&format!("%{}%", query))
-        .fetch_all(&self.pool)
-        .await?;
+    /// Notes matching the query, newest first, one page at a time. The cursor is the sort key
+    /// of the last item returned, so pages stay stable when earlier notes change.
+    pub async fn search_notes(&self, request: &SearchRequest) -> AppResult<SearchPage<NoteSearchMatch>> {
+        if request.filters.excludes_notes() {
+            return Ok(SearchPage { items: Vec::new(), next_cursor: None });
+        }
+
+        let parsed = search::parse(&request.query)?;
+        let (mut conditions, mut binds) = filter_conditions(&request.filters);
+        if let Some((condition, values)) = parsed.as_ref().and_then(|q| q.sql_prefilter(SearchTable::Notes)) {
+            conditions.push(condition);
+            binds.extend(values);
+        }
+        let (condition, values) = cursor_condition(request.cursor.as_deref())?;
+        conditions.extend(condition);
+        binds.extend(values);
+
+        let sql = format!(
+            "SELECT id, title, content, tags, created_at, updated_at, metadata FROM notes {} ORDER BY updated_at DESC, id DESC",
+            where_clause(&conditions)
+        );
+
+        let mut query = sqlx::query(&sql);
+        for value in &binds {
+            query = query.bind(value);
+        }
+        let rows = query.fetch_all(&self.pool).await?;
 
+        let terms = parsed.as_ref().map(|q| q.positive_terms()).unwrap_or_default();
+        let limit = request.limit.unwrap_or(50).max(1);
         let mut notes = Vec::new();
+        let mut next_cursor = None;
         for row in rows {
+            if notes.len() >= limit {
+                next_cursor = notes.last().map(|m: &NoteSearchMatch| encode_cursor(&UpdatedCursor {
+                    updated_at: m.note.updated_at.to_rfc3339(),
+                    id: m.note.id.clone(),
+                })).transpose()?;
+                break;
+            }
+
             let content: String = row.get("content");
             let decrypted_content = if let Some(ref enc) = self.encryption_manager {
                 enc.decrypt_string(&content)?
@@ -586,27 +1859,326 @@ impl Database {
                 content
             };
 
-            // Only include if search term is found in decrypted content
-            if decrypted_content.to_lowercase().contains(&query.to_lowercase()) ||
-               row.get::<String, _>("title").to_lowercase().contains(&query.to_lowercase()) {
-                
-                let voice_annotations = self.get_voice_annotations(&row.get::<String, _>("id")).await?;
+            let id: String = row.get("id");
+            let title: String = row.get("title");
+            let tags: Vec<String> = serde_json::from_str(&row.get::<String, _>("tags"))?;
+            let voice_annotations = self.get_voice_annotations(&id).await?;
+            let attachment_texts = self.get_attachment_texts("note_id", &id).await?;
+            let mut searchable: Vec<&str> = voice_annotations.iter().map(|a| a.transcription.as_str()).collect();
+            let transcription_count = searchable.len();
+            searchable.extend(attachment_texts.iter().map(|t| t.as_str()));
 
+            // Text matching happens after decryption so it works with encryption enabled
+            let document = SearchDocument {
+                title: &title,
+                content: &decrypted_content,
+                tags: &tags,
+                notebook_id: None,
+                section_id: None,
+                extra_texts: &searchable,
+            };
+            if parsed.as_ref().map(|q| q.matches(&document)).unwrap_or(true) {
+                let (transcriptions, attachment_texts) = searchable.split_at(transcription_count);
+                let matched_fields = match_fields(&terms, &title, &decrypted_content, transcriptions, attachment_texts);
                 let note = Note {
-                    id: row.get("id"),
-                    title: row.get("title"),
+                    id,
+                    title,
                     content: decrypted_content,
-                    tags: serde_json::from_str(&row.get::<String, _>("tags"))?,
+                    tags,
                     created_at: DateTime::parse_from_rfc3339(&row.get::<String, _>("created_at"))?.with_timezone(&Utc),
                     updated_at: DateTime::parse_from_rfc3339(&row.get::<String, _>("updated_at"))?.with_timezone(&Utc),
                     voice_annotations,
                     metadata: serde_json::from_str(&row.get::<String, _>("metadata"))?,
                 };
-                notes.push(note);
+                let highlights = highlight_spans(&terms.join(" "), &note.title, &note.content);
+                notes.push(NoteSearchMatch { note, matched_fields, highlights });
             }
         }
 
-        Ok(notes)
+        Ok(SearchPage { items: notes, next_cursor })
+    }
+
+    /// Pages of one notebook matching the query, newest first, paged by cursor like `search_notes`.
+    pub async fn search_notebook(&self, request: NotebookSearchRequest) -> AppResult<SearchPage<PageSearchMatch>> {
+        let mut filters = request.filters;
+        filters.notebook_id = Some(request.notebook_id);
+
+        let parsed = search::parse(&request.query)?;
+        let (mut conditions, mut binds) = filter_conditions(&filters);
+        if let Some((condition, values)) = parsed.as_ref().and_then(|q| q.sql_prefilter(SearchTable::Pages)) {
+            conditions.push(condition);
+            binds.extend(values);
+        }
+        if let Some(sections) = request.include_sections.filter(|s| !s.is_empty()) {
+            conditions.push(format!("section_id IN ({})", vec!["?"; sections.len()].join(", ")));
+            binds.extend(sections);
+        }
+        let (condition, values) = cursor_condition(request.cursor.as_deref())?;
+        conditions.extend(condition);
+        binds.extend(values);
+
+        let sql = format!(
+            "SELECT id, notebook_id, section_id, parent_page_id, title, content, tags, order_index, created_at, updated_at, metadata FROM pages {} ORDER BY updated_at DESC, id DESC",
+            where_clause(&conditions)
+        );
+
+        let mut query = sqlx::query(&sql);
+        for value in &binds {
+            query = query.bind(value);
+        }
+        let rows = query.fetch_all(&self.pool).await?;
+
+        let terms = parsed.as_ref().map(|q| q.positive_terms()).unwrap_or_default();
+        let limit = request.limit.unwrap_or(usize::MAX).max(1);
+        let mut pages = Vec::new();
+        let mut next_cursor = None;
+        for row in rows {
+            if pages.len() >= limit {
+                next_cursor = pages.last().map(|m: &PageSearchMatch| encode_cursor(&UpdatedCursor {
+                    updated_at: m.page.updated_at.to_rfc3339(),
+                    id: m.page.id.clone(),
+                })).transpose()?;
+                break;
+            }
+
+            let content: String = row.get("content");
+            let decrypted_content = if let Some(ref enc) = self.encryption_manager {
+                enc.decrypt_string(&content)?
+            } else {
+                content
+            };
+
+            let id: String = row.get("id");
+            let title: String = row.get("title");
+            let notebook_id: String = row.get("notebook_id");
+            let section_id: Option<String> = row.get("section_id");
+            let tags: Vec<String> = serde_json::from_str(&row.get::<String, _>("tags"))?;
+            let transcriptions = self.get_page_transcriptions(&id).await?;
+            let attachment_texts = self.get_attachment_texts("page_id", &id).await?;
+            let mut searchable: Vec<&str> = transcriptions.iter().map(|t| t.as_str()).collect();
+            let transcription_count = searchable.len();
+            searchable.extend(attachment_texts.iter().map(|t| t.as_str()));
+
+            let document = SearchDocument {
+                title: &title,
+                content: &decrypted_content,
+                tags: &tags,
+                notebook_id: Some(&notebook_id),
+                section_id: section_id.as_deref(),
+                extra_texts: &searchable,
+            };
+            if !parsed.as_ref().map(|q| q.matches(&document)).unwrap_or(true) {
+                continue;
+            }
+            let (transcriptions, attachment_texts) = searchable.split_at(transcription_count);
+            let matched_fields = match_fields(&terms, &title, &decrypted_content, transcriptions, attachment_texts);
+
+            let page = Page {
+                id,
+                notebook_id,
+                section_id,
+                parent_page_id: row.get("parent_page_id"),
+                title,
+                content: decrypted_content,
+                tags,
+                order_index: row.get("order_index"),
+                created_at: DateTime::parse_from_rfc3339(&row.get::<String, _>("created_at"))?.with_timezone(&Utc),
+                updated_at: DateTime::parse_from_rfc3339(&row.get::<String, _>("updated_at"))?.with_timezone(&Utc),
+                voice_annotations: Vec::new(),
+                media_attachments: Vec::new(),
+                page_links: Vec::new(),
+                subpages: Vec::new(),
+                metadata: serde_json::from_str(&row.get::<String, _>("metadata"))?,
+            };
+            pages.push(PageSearchMatch { page, matched_fields });
+        }
+
+        Ok(SearchPage { items: pages, next_cursor })
+    }
+
+    pub async fn get_page_transcriptions(&self, page_id: &str) -> AppResult<Vec<String>> {
+        let rows = sqlx::query("SELECT transcription FROM voice_annotations WHERE page_id = ?")
+            .bind(page_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows.iter().map(|row| row.get("transcription")).collect())
+    }
+
+    /// Ids of pages matching the filters, most recently updated first.
+    pub async fn get_page_ids_in_scope(&self, filters: &SearchFilters) -> AppResult<Vec<String>> {
+        let (conditions, binds) = filter_conditions(filters);
+        let sql = format!("SELECT id FROM pages {} ORDER BY updated_at DESC", where_clause(&conditions));
+
+        let mut query = sqlx::query(&sql);
+        for value in &binds {
+            query = query.bind(value);
+        }
+        let rows = query.fetch_all(&self.pool).await?;
+        Ok(rows.iter().map(|row| row.get("id")).collect())
+    }
+
+    /// Page counts, word counts and tag usage for pages created within `range`, computed
+    /// from metadata so page content is never decrypted.
+    pub async fn get_usage_stats(&self, range: &StatsRange) -> AppResult<UsageStats> {
+        let filters = SearchFilters {
+            created_after: range.from,
+            created_before: range.to,
+            ..Default::default()
+        };
+        let (conditions, binds) = filter_conditions(&filters);
+        let conditions: Vec<String> = conditions.iter().map(|c| format!("p.{}", c)).collect();
+        let scope = where_clause(&conditions);
+
+        let sql = format!(
+            "SELECT substr(p.created_at, 1, 10) AS day, COUNT(*) AS pages FROM pages p {} GROUP BY day ORDER BY day",
+            scope
+        );
+        let mut query = sqlx::query(&sql);
+        for value in &binds {
+            query = query.bind(value);
+        }
+        let pages_per_day = query
+            .fetch_all(&self.pool)
+            .await?
+            .iter()
+            .map(|row| PagesPerDay {
+                date: row.get("day"),
+                pages_created: row.get::<i64, _>("pages") as u32,
+            })
+            .collect();
+
+        let sql = format!(
+            r#"
+            SELECT n.id, n.title, COUNT(*) AS pages,
+                   COALESCE(SUM(json_extract(p.metadata, '$.word_count')), 0) AS words
+            FROM pages p
+            JOIN notebooks n ON n.id = p.notebook_id
+            {}
+            GROUP BY n.id
+            ORDER BY words DESC, n.title
+            "#,
+            scope
+        );
+        let mut query = sqlx::query(&sql);
+        for value in &binds {
+            query = query.bind(value);
+        }
+        let words_per_notebook = query
+            .fetch_all(&self.pool)
+            .await?
+            .iter()
+            .map(|row| NotebookWordCount {
+                notebook_id: row.get("id"),
+                notebook_title: row.get("title"),
+                page_count: row.get::<i64, _>("pages") as u32,
+                word_count: row.get::<i64, _>("words") as u64,
+            })
+            .collect();
+
+        let sql = format!(
+            r#"
+            SELECT substr(p.created_at, 1, 10) AS day, t.value AS tag, COUNT(*) AS pages
+            FROM pages p, json_each(p.tags) t
+            {}
+            GROUP BY day, tag
+            ORDER BY day, tag
+            "#,
+            scope
+        );
+        let mut query = sqlx::query(&sql);
+        for value in &binds {
+            query = query.bind(value);
+        }
+        let tag_usage = query
+            .fetch_all(&self.pool)
+            .await?
+            .iter()
+            .map(|row| TagUsageDay {
+                date: row.get("day"),
+                tag: row.get("tag"),
+                page_count: row.get::<i64, _>("pages") as u32,
+            })
+            .collect();
+
+        Ok(UsageStats { range: range.clone(), pages_per_day, words_per_notebook, tag_usage })
+    }
+
+    /// Creation time and sentiment of each page in a notebook that has one. Pages saved
+    /// before sentiment was stored are scored from their content.
+    pub async fn get_page_sentiments(&self, notebook_id: &str) -> AppResult<Vec<(DateTime<Utc>, f64)>> {
+        let rows = sqlx::query(
+            "SELECT created_at, content, json_extract(metadata, '$.sentiment') AS sentiment, json_type(metadata, '$.sentiment') AS stored FROM pages WHERE notebook_id = ?"
+        )
+        .bind(notebook_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut scores = Vec::new();
+        for row in rows {
+            // A stored null means the page was scored and had no opinion words
+            let score = match row.get::<Option<String>, _>("stored") {
+                Some(_) => row.get::<Option<f64>, _>("sentiment"),
+                None => {
+                    let content: String = row.get("content");
+                    let content = match self.encryption_manager {
+                        Some(ref enc) => enc.decrypt_string(&content)?,
+                        None => content,
+                    };
+                    sentiment::score(&content)
+                }
+            };
+            if let Some(score) = score {
+                let created_at = DateTime::parse_from_rfc3339(&row.get::<String, _>("created_at"))?.with_timezone(&Utc);
+                scores.push((created_at, score));
+            }
+        }
+        Ok(scores)
+    }
+
+    /// OCR, PDF and handwriting text extracted from attachments owned by a note or page.
+    async fn get_attachment_texts(&self, owner_column: &'static str, owner_id: &str) -> AppResult<Vec<String>> {
+        let sql = format!("SELECT metadata FROM media_attachments WHERE {} = ?", owner_column);
+        let rows = sqlx::query(&sql)
+            .bind(owner_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        let mut texts = Vec::new();
+        for row in rows {
+            let metadata: MediaMetadata = serde_json::from_str(&row.get::<String, _>("metadata"))?;
+            texts.extend(metadata.extracted_text);
+        }
+        Ok(texts)
+    }
+
+    /// Ranks notes against the query with BM25. Scoring happens after decryption, so this
+    /// works the same whether or not encryption is enabled. Query terms are expanded with
+    /// close spellings from the notes' vocabulary, weighted by how close they are, so typos
+    /// still find results.
+    pub async fn keyword_search(&self, query: &str) -> AppResult<Vec<(Note, f64)>> {
+        let query_terms = tokenize(query);
+        if query_terms.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let notes = self.get_notes(Some(i64::MAX as usize), None).await?;
+        let documents: Vec<Vec<String>> = notes
+            .iter()
+            .map(|note| tokenize(&format!("{} {}", note.title, note.content)))
+            .collect();
+        let vocabulary: HashSet<&str> = documents.iter().flatten().map(|t| t.as_str()).collect();
+        let weighted_terms = expand_query_terms(&query_terms, &vocabulary);
+        let scores = bm25_scores(&documents, &weighted_terms);
+
+        let mut results: Vec<(Note, f64)> = notes
+            .into_iter()
+            .zip(scores)
+            .filter(|(_, score)| *score > 0.0)
+            .collect();
+        results.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+
+        Ok(results)
     }
 
     // Settings operations
@@ -629,14 +2201,40 @@ impl Database {
         }
     }
 
-    pub async fn set_setting(&self, key: &str, value: &str) -> AppResult<()> {
-        let encrypted_value = if let Some(ref enc) = self.encryption_manager {
-            enc.encrypt_string(value)?
-        } else {
-            value.to_string()
-        };
+    pub async fn get_security_config(&self) -> AppResult<SecurityConfig> {
+        match self.get_setting(exif::SECURITY_CONFIG_KEY).await? {
+            Some(value) => Ok(serde_json::from_str(&value)?),
+            None => Ok(SecurityConfig::default()),
+        }
+    }
 
-        sqlx::query(
+    pub async fn get_settings(&self) -> AppResult<Vec<(String, String)>> {
+        let rows = sqlx::query("SELECT key, value FROM settings ORDER BY key ASC")
+            .fetch_all(&self.pool)
+            .await?;
+
+        let mut settings = Vec::new();
+        for row in rows {
+            let value: String = row.get("value");
+            let decrypted_value = if let Some(ref enc) = self.encryption_manager {
+                enc.decrypt_string(&value)?
+            } else {
+                value
+            };
+            settings.push((row.get("key"), decrypted_value));
+        }
+
+        Ok(settings)
+    }
+
+    pub async fn set_setting(&self, key: &str, value: &str) -> AppResult<()> {
+        let encrypted_value = if let Some(ref enc) = self.encryption_manager {
+            enc.encrypt_string(value)?
+        } else {
+            value.to_string()
+        };
+
+        sqlx::query(
             r#"
             INSERT OR REPLACE INTO settings (key, value, updated_at)
             VALUES (?, ?, ?)
@@ -651,30 +2249,263 @@ impl Database {
         Ok(())
     }
 
+    pub async fn delete_setting(&self, key: &str) -> AppResult<()> {
+        sqlx::query("DELETE FROM settings WHERE key = ?")
+            .bind(key)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
     // Embedding operations
-    pub async fn store_embedding(&self, note_id: &str, embedding: &[f32]) -> AppResult<()> {
+    pub async fn store_embedding(&self, owner_id: &str, owner: EmbeddingOwner, model: &str, content_hash: &str, embedding: &[f32]) -> AppResult<()> {
         let embedding_bytes = embedding.iter()
             .flat_map(|f| f.to_le_bytes())
             .collect::<Vec<u8>>();
 
         sqlx::query(
             r#"
-            INSERT OR REPLACE INTO embeddings (note_id, embedding, created_at)
-            VALUES (?, ?, ?)
+            INSERT OR REPLACE INTO embeddings (owner_id, owner_type, embedding, model, content_hash, created_at)
+            VALUES (?, ?, ?, ?, ?, ?)
             "#
         )
-        .bind(note_id)
+        .bind(owner_id)
+        .bind(owner.as_str())
         .bind(&embedding_bytes)
+        .bind(model)
+        .bind(content_hash)
         .bind(&Utc::now().to_rfc3339())
         .execute(&self.pool)
         .await?;
 
+        if self.vector_index {
+            self.ensure_vector_table(embedding.len()).await?;
+            // vec0 tables don't support upserts
+            sqlx::query("DELETE FROM vec_embeddings WHERE owner_id = ?")
+                .bind(owner_id)
+                .execute(&self.pool)
+                .await?;
+            sqlx::query("INSERT INTO vec_embeddings (owner_id, model, embedding) VALUES (?, ?, ?)")
+                .bind(owner_id)
+                .bind(model)
+                .bind(&embedding_bytes)
+                .execute(&self.pool)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Whether the stored embedding already covers this exact content with this model.
+    pub async fn has_current_embedding(&self, owner_id: &str, model: &str, content_hash: &str) -> AppResult<bool> {
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM embeddings WHERE owner_id = ? AND model = ? AND content_hash = ?")
+            .bind(owner_id)
+            .bind(model)
+            .bind(content_hash)
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(count > 0)
+    }
+
+    pub fn has_vector_index(&self) -> bool {
+        self.vector_index
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether note content and audio are encrypted at rest.
+    pub fn is_encrypted(&self) -> bool {
+        self.encryption_manager.is_some()
+    }
+
+    /// The content key, for encrypting data that leaves the database such as sync documents.
+    pub fn encryption_manager(&self) -> Option<&EncryptionManager> {
+        self.encryption_manager.as_ref()
+    }
+
+    /// Whether the whole file is encrypted with SQLCipher, titles and links included.
+    pub fn is_file_encrypted(&self) -> bool {
+        self.file_encrypted
+    }
+
+    /// Key for opening the file on a separate connection, when it's encrypted as a whole.
+    pub fn file_key(&self) -> Option<String> {
+        self.encryption_manager.as_ref().filter(|_| self.file_encrypted).map(|m| m.database_key())
+    }
+
+    /// Closes every pooled connection, e.g. before the file is replaced.
+    pub async fn close(&self) {
+        self.pool.close().await;
+    }
+
+    /// Non-empty values in `table.column` and their total size in bytes, as stored.
+    /// `table` and `column` must be trusted identifiers.
+    pub async fn get_column_usage(&self, table: &str, column: &str) -> AppResult<(u64, u64)> {
+        let sql = format!(
+            "SELECT COUNT(*) AS count, COALESCE(SUM(length(CAST({column} AS BLOB))), 0) AS bytes FROM {table} WHERE length({column}) > 0",
+            table = table,
+            column = column
+        );
+        let row = sqlx::query(&sql).fetch_one(&self.pool).await?;
+        Ok((row.get::<i64, _>("count") as u64, row.get::<i64, _>("bytes") as u64))
+    }
+
+    /// Values of an encrypted column that don't decrypt with the loaded key: the count, their
+    /// size and up to `sample` of their ids. Rows are read in batches so large BLOBs aren't
+    /// all held at once. Nothing is checked without a key.
+    pub async fn get_undecryptable_values(&self, table: &str, id_column: &str, column: &str, blob: bool, sample: usize) -> AppResult<(u64, u64, Vec<String>)> {
+        let Some(ref enc) = self.encryption_manager else {
+            return Ok((0, 0, Vec::new()));
+        };
+        let sql = format!(
+            "SELECT {id} AS id, {column} AS value FROM {table} WHERE {id} > ? AND length({column}) > 0 ORDER BY {id} LIMIT 100",
+            id = id_column,
+            column = column,
+            table = table
+        );
+
+        let (mut count, mut bytes, mut ids) = (0, 0, Vec::new());
+        let mut after = String::new();
+        loop {
+            let rows = sqlx::query(&sql).bind(&after).fetch_all(&self.pool).await?;
+            let Some(last) = rows.last() else { break };
+            after = last.get("id");
+            for row in &rows {
+                let (failed, size) = if blob {
+                    let value: Vec<u8> = row.get("value");
+                    (enc.decrypt(&value).is_err(), value.len())
+                } else {
+                    let value: String = row.get("value");
+                    (enc.decrypt_string(&value).is_err(), value.len())
+                };
+                if failed {
+                    count += 1;
+                    bytes += size as u64;
+                    if ids.len() < sample {
+                        ids.push(row.get("id"));
+                    }
+                }
+            }
+        }
+        Ok((count, bytes, ids))
+    }
+
+    /// Switches KNN queries to the sqlite-vec index. The `embeddings` BLOB table stays the
+    /// source of truth, so existing rows are copied over here and the index can be dropped
+    /// again at any time. Returns the number of embeddings migrated.
+    pub async fn enable_vector_index(&mut self) -> AppResult<usize> {
+        let version: String = sqlx::query_scalar("SELECT vec_version()")
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| AppError::Configuration(format!("sqlite-vec extension is not available: {}", e)))?;
+        tracing::info!("Using sqlite-vec {} for embeddings", version);
+
+        let dimension: Option<i64> = sqlx::query_scalar("SELECT length(embedding) / 4 FROM embeddings ORDER BY created_at DESC LIMIT 1")
+            .fetch_optional(&self.pool)
+            .await?;
+        self.vector_index = true;
+
+        if let Some(dimension) = dimension {
+            self.ensure_vector_table(dimension as usize).await?;
+        }
+        let migrated = self.backfill_vector_table().await?;
+        Ok(migrated)
+    }
+
+    pub async fn disable_vector_index(&mut self) -> AppResult<()> {
+        sqlx::query("DROP TABLE IF EXISTS vec_embeddings").execute(&self.pool).await?;
+        sqlx::query("DELETE FROM settings WHERE key = ?")
+            .bind(VECTOR_INDEX_DIMENSION_KEY)
+            .execute(&self.pool)
+            .await?;
+        self.vector_index = false;
         Ok(())
     }
 
-    pub async fn get_embedding(&self, note_id: &str) -> AppResult<Option<Vec<f32>>> {
-        let row = sqlx::query("SELECT embedding FROM embeddings WHERE note_id = ?")
-            .bind(note_id)
+    /// (Re)creates `vec_embeddings` when the embedding dimension changes. Vectors from a
+    /// different model aren't comparable, so the old index is discarded. Models of the same
+    /// dimension share the table, partitioned by model so KNN only searches one of them.
+    async fn ensure_vector_table(&self, dimension: usize) -> AppResult<()> {
+        let current = self.get_setting(VECTOR_INDEX_DIMENSION_KEY).await?.and_then(|d| d.parse::<usize>().ok());
+        if current == Some(dimension) {
+            return Ok(());
+        }
+
+        sqlx::query("DROP TABLE IF EXISTS vec_embeddings").execute(&self.pool).await?;
+        sqlx::query(&format!(
+            "CREATE VIRTUAL TABLE vec_embeddings USING vec0(owner_id TEXT PRIMARY KEY, model TEXT partition key, embedding float[{}] distance_metric=cosine)",
+            dimension
+        ))
+        .execute(&self.pool)
+        .await?;
+        self.set_setting(VECTOR_INDEX_DIMENSION_KEY, &dimension.to_string()).await
+    }
+
+    /// Copies BLOB embeddings missing from the index and drops index rows whose note is gone.
+    /// Embeddings with no model yet are left out until `adopt_unlabelled_embeddings` names it.
+    async fn backfill_vector_table(&self) -> AppResult<usize> {
+        let Some(dimension) = self.get_setting(VECTOR_INDEX_DIMENSION_KEY).await?.and_then(|d| d.parse::<i64>().ok()) else {
+            return Ok(0);
+        };
+
+        sqlx::query("DELETE FROM vec_embeddings WHERE owner_id NOT IN (SELECT owner_id FROM embeddings)")
+            .execute(&self.pool)
+            .await?;
+        let result = sqlx::query(
+            r#"
+            INSERT INTO vec_embeddings (owner_id, model, embedding)
+            SELECT owner_id, model, embedding FROM embeddings
+            WHERE length(embedding) = ? * 4 AND model IS NOT NULL AND owner_id NOT IN (SELECT owner_id FROM vec_embeddings)
+            "#
+        )
+        .bind(dimension)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() as usize)
+    }
+
+    /// The `k` nearest notes and pages to `query` by cosine similarity, best first, among
+    /// embeddings from `model`. Requires the vector index.
+    pub async fn nearest_embeddings(&self, query: &[f32], model: &str, k: usize) -> AppResult<Vec<(String, EmbeddingOwner, f64)>> {
+        if !self.vector_index {
+            return Err(AppError::Configuration("Vector index is not enabled".to_string()));
+        }
+        let current = self.get_setting(VECTOR_INDEX_DIMENSION_KEY).await?.and_then(|d| d.parse::<usize>().ok());
+        if current != Some(query.len()) {
+            // Nothing indexed yet, or the query comes from a different model
+            return Ok(Vec::new());
+        }
+
+        let query_bytes: Vec<u8> = query.iter().flat_map(|f| f.to_le_bytes()).collect();
+        let rows = sqlx::query(
+            r#"
+            WITH knn AS (SELECT owner_id, distance FROM vec_embeddings WHERE embedding MATCH ? AND k = ? AND model = ?)
+            SELECT knn.owner_id, e.owner_type, knn.distance
+            FROM knn JOIN embeddings e ON e.owner_id = knn.owner_id
+            ORDER BY knn.distance
+            "#
+        )
+            .bind(&query_bytes)
+            .bind(k as i64)
+            .bind(model)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows
+            .iter()
+            .filter_map(|row| {
+                let owner = EmbeddingOwner::parse(&row.get::<String, _>("owner_type"))?;
+                Some((row.get::<String, _>("owner_id"), owner, 1.0 - row.get::<f64, _>("distance")))
+            })
+            .collect())
+    }
+
+    pub async fn get_embedding(&self, owner_id: &str) -> AppResult<Option<Vec<f32>>> {
+        let row = sqlx::query("SELECT embedding FROM embeddings WHERE owner_id = ?")
+            .bind(owner_id)
             .fetch_optional(&self.pool)
             .await?;
 
@@ -690,161 +2521,606 @@ impl Database {
         }
     }
 
-    pub async fn get_all_embeddings(&self) -> AppResult<Vec<(String, Vec<f32>)>> {
-        let rows = sqlx::query("SELECT note_id, embedding FROM embeddings")
+    /// Every note and page, which is everything that gets an embedding.
+    pub async fn get_embeddable_ids(&self) -> AppResult<Vec<(String, EmbeddingOwner)>> {
+        let rows = sqlx::query("SELECT id, 'note' AS owner_type FROM notes UNION ALL SELECT id, 'page' FROM pages")
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows
+            .iter()
+            .filter_map(|row| Some((row.get("id"), EmbeddingOwner::parse(&row.get::<String, _>("owner_type"))?)))
+            .collect())
+    }
+
+    /// Embeddings produced by `model`. Vectors from other models aren't comparable, even
+    /// when the dimension matches.
+    pub async fn get_all_embeddings(&self, model: &str) -> AppResult<Vec<(String, EmbeddingOwner, Vec<f32>)>> {
+        let rows = sqlx::query("SELECT owner_id, owner_type, embedding FROM embeddings WHERE model = ?")
+            .bind(model)
             .fetch_all(&self.pool)
             .await?;
 
         let mut embeddings = Vec::new();
         for row in rows {
-            let note_id: String = row.get("note_id");
+            let owner_id: String = row.get("owner_id");
+            let Some(owner) = EmbeddingOwner::parse(&row.get::<String, _>("owner_type")) else {
+                continue;
+            };
             let embedding_bytes: Vec<u8> = row.get("embedding");
             let embedding = embedding_bytes
                 .chunks(4)
                 .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
                 .collect();
-            embeddings.push((note_id, embedding));
+            embeddings.push((owner_id, owner, embedding));
         }
 
         Ok(embeddings)
     }
 
-    // Notebook operations
-    pub async fn create_notebook(&self, request: CreateNotebookRequest) -> AppResult<Notebook> {
-        let notebook = Notebook::new(request.title, request.description, request.color);
-        
-        sqlx::query(
+    /// Labels embeddings stored before models were recorded. They came from whichever model
+    /// was configured at the time, which is the best available guess.
+    pub async fn adopt_unlabelled_embeddings(&self, model: &str) -> AppResult<usize> {
+        let result = sqlx::query("UPDATE embeddings SET model = ? WHERE model IS NULL")
+            .bind(model)
+            .execute(&self.pool)
+            .await?;
+        if self.vector_index && result.rows_affected() > 0 {
+            self.backfill_vector_table().await?;
+        }
+        Ok(result.rows_affected() as usize)
+    }
+
+    /// Stored embeddings grouped by model and dimension, largest group first.
+    pub async fn get_embedding_model_counts(&self) -> AppResult<Vec<EmbeddingModelCount>> {
+        let rows = sqlx::query(
             r#"
-            INSERT INTO notebooks (id, title, description, color, order_index, created_at, updated_at, metadata)
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            SELECT COALESCE(model, '') AS model, length(embedding) / 4 AS dimension, COUNT(*) AS count
+            FROM embeddings
+            GROUP BY model, dimension
+            ORDER BY count DESC
             "#
         )
-        .bind(&notebook.id)
-        .bind(&notebook.title)
-        .bind(&notebook.description)
-        .bind(&notebook.color)
-        .bind(notebook.order_index)
-        .bind(&notebook.created_at.to_rfc3339())
-        .bind(&notebook.updated_at.to_rfc3339())
-        .bind(&serde_json::to_string(&notebook.metadata)?)
-        .execute(&self.pool)
+        .fetch_all(&self.pool)
         .await?;
 
-        Ok(notebook)
+        Ok(rows
+            .iter()
+            .map(|row| EmbeddingModelCount {
+                model: row.get("model"),
+                dimension: row.get::<i64, _>("dimension") as usize,
+                count: row.get::<i64, _>("count") as usize,
+            })
+            .collect())
     }
 
-    pub async fn get_notebooks(&self) -> AppResult<Vec<Notebook>> {
+    /// Notes and pages whose embedding came from a model other than `model`.
+    pub async fn get_stale_embedding_ids(&self, model: &str) -> AppResult<Vec<(String, EmbeddingOwner)>> {
+        let rows = sqlx::query("SELECT owner_id, owner_type FROM embeddings WHERE model IS NOT ?")
+            .bind(model)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows
+            .iter()
+            .filter_map(|row| Some((row.get("owner_id"), EmbeddingOwner::parse(&row.get::<String, _>("owner_type"))?)))
+            .collect())
+    }
+
+    /// Drops embeddings from other models; their notes and pages stay out of semantic
+    /// search until re-embedded.
+    pub async fn delete_stale_embeddings(&self, model: &str) -> AppResult<usize> {
+        let result = sqlx::query("DELETE FROM embeddings WHERE model IS NOT ?")
+            .bind(model)
+            .execute(&self.pool)
+            .await?;
+        if self.vector_index {
+            self.backfill_vector_table().await?;
+        }
+        Ok(result.rows_affected() as usize)
+    }
+
+    // Page link operations
+    /// Auto links from a page, best match first.
+    pub async fn get_links_of_type(&self, source_page_id: &str, link_type: PageLinkType) -> AppResult<Vec<PageLink>> {
         let rows = sqlx::query(
             r#"
-            SELECT id, title, description, color, order_index, created_at, updated_at, metadata
-            FROM notebooks
-            ORDER BY order_index ASC, created_at ASC
+            SELECT id, source_page_id, target_page_id, link_text, link_type, created_at
+            FROM page_links
+            WHERE source_page_id = ? AND link_type = ?
+            ORDER BY rowid
             "#
         )
+        .bind(source_page_id)
+        .bind(link_type.as_str())
         .fetch_all(&self.pool)
         .await?;
 
-        let mut notebooks = Vec::new();
+        let mut links = Vec::new();
         for row in rows {
-            let notebook = Notebook {
+            links.push(PageLink {
                 id: row.get("id"),
-                title: row.get("title"),
-                description: row.get("description"),
-                color: row.get("color"),
-                order_index: row.get("order_index"),
+                source_page_id: row.get("source_page_id"),
+                target_page_id: row.get("target_page_id"),
+                link_text: row.get("link_text"),
+                link_type: PageLinkType::parse(&row.get::<String, _>("link_type")).unwrap_or(link_type.clone()),
                 created_at: DateTime::parse_from_rfc3339(&row.get::<String, _>("created_at"))?.with_timezone(&Utc),
-                updated_at: DateTime::parse_from_rfc3339(&row.get::<String, _>("updated_at"))?.with_timezone(&Utc),
-                sections: Vec::new(), // Will be populated by get_notebook_hierarchy
-                metadata: serde_json::from_str(&row.get::<String, _>("metadata"))?,
-            };
-            notebooks.push(notebook);
+            });
         }
 
-        Ok(notebooks)
+        Ok(links)
     }
 
-    pub async fn get_notebook(&self, id: &str) -> AppResult<Option<Notebook>> {
-        let row = sqlx::query(
+    /// Replaces a page's links of one type with `targets` (page id, link text), keeping
+    /// their order. Links of other types are left alone.
+    pub async fn replace_links_of_type(&self, source_page_id: &str, link_type: PageLinkType, targets: &[(String, String)]) -> AppResult<Vec<PageLink>> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM page_links WHERE source_page_id = ? AND link_type = ?")
+            .bind(source_page_id)
+            .bind(link_type.as_str())
+            .execute(&mut *tx)
+            .await?;
+
+        let mut links = Vec::new();
+        for (target_page_id, title) in targets {
+            let link = PageLink::new(source_page_id.to_string(), target_page_id.clone(), title.clone(), link_type.clone());
+            // An existing link with the same text takes precedence
+            let result = sqlx::query(
+                r#"
+                INSERT OR IGNORE INTO page_links (id, source_page_id, target_page_id, link_text, link_type, created_at)
+                VALUES (?, ?, ?, ?, ?, ?)
+                "#
+            )
+            .bind(&link.id)
+            .bind(&link.source_page_id)
+            .bind(&link.target_page_id)
+            .bind(&link.link_text)
+            .bind(link.link_type.as_str())
+            .bind(&link.created_at.to_rfc3339())
+            .execute(&mut *tx)
+            .await?;
+            if result.rows_affected() > 0 {
+                links.push(link);
+            }
+        }
+        tx.commit().await?;
+
+        Ok(links)
+    }
+
+    /// A page's links both ways, parent and subpages. With `link_types`, only links of those
+    /// types are listed; `link_type_counts` always covers every link.
+    pub async fn get_page_relationships(&self, page_id: &str, link_types: Option<&[PageLinkType]>) -> AppResult<PageRelationships> {
+        let page = self.get_page(page_id).await?
+            .ok_or_else(|| AppError::NotFound(format!("Page with id {} not found", page_id)))?;
+        let rows = sqlx::query(
             r#"
-            SELECT id, title, description, color, order_index, created_at, updated_at, metadata
-            FROM notebooks
-            WHERE id = ?
+            SELECT id, source_page_id, target_page_id, link_text, link_type, created_at
+            FROM page_links
+            WHERE source_page_id = ? OR target_page_id = ?
+            ORDER BY rowid
             "#
         )
-        .bind(id)
-        .fetch_optional(&self.pool)
+        .bind(page_id)
+        .bind(page_id)
+        .fetch_all(&self.pool)
         .await?;
+        let links = rows.iter().map(page_link_from_row).collect::<AppResult<Vec<_>>>()?;
 
-        if let Some(row) = row {
-            let notebook = Notebook {
-                id: row.get("id"),
-                title: row.get("title"),
-                description: row.get("description"),
-                color: row.get("color"),
-                order_index: row.get("order_index"),
-                created_at: DateTime::parse_from_rfc3339(&row.get::<String, _>("created_at"))?.with_timezone(&Utc),
-                updated_at: DateTime::parse_from_rfc3339(&row.get::<String, _>("updated_at"))?.with_timezone(&Utc),
-                sections: Vec::new(),
-                metadata: serde_json::from_str(&row.get::<String, _>("metadata"))?,
+        let mut link_type_counts: Vec<LinkTypeCount> = Vec::new();
+        for link in &links {
+            let index = match link_type_counts.iter().position(|count| count.link_type == link.link_type) {
+                Some(index) => index,
+                None => {
+                    link_type_counts.push(LinkTypeCount { link_type: link.link_type.clone(), incoming: 0, outgoing: 0 });
+                    link_type_counts.len() - 1
+                }
             };
-            Ok(Some(notebook))
-        } else {
-            Ok(None)
+            if link.source_page_id == page_id {
+                link_type_counts[index].outgoing += 1;
+            } else {
+                link_type_counts[index].incoming += 1;
+            }
         }
-    }
-
-    pub async fn update_notebook(&self, request: UpdateNotebookRequest) -> AppResult<()> {
-        let mut query_parts = Vec::new();
-        let mut params = Vec::new();
 
-        if let Some(title) = &request.title {
-            query_parts.push("title = ?");
-            params.push(title.as_str());
-        }
-        if let Some(description) = &request.description {
-            query_parts.push("description = ?");
-            params.push(description.as_str());
-        }
-        if let Some(color) = &request.color {
-            query_parts.push("color = ?");
-            params.push(color.as_str());
-        }
-        if let Some(order_index) = &request.order_index {
-            query_parts.push("order_index = ?");
-            params.push(&order_index.to_string());
+        let (mut outgoing_links, mut incoming_links) = (Vec::new(), Vec::new());
+        for link in links {
+            if link_types.is_some_and(|types| !types.contains(&link.link_type)) {
+                continue;
+            }
+            if link.source_page_id == page_id {
+                outgoing_links.push(link);
+            } else {
+                incoming_links.push(link);
+            }
         }
 
-        if query_parts.is_empty() {
-            return Ok(());
+        let mut related_pages = Vec::new();
+        for link in outgoing_links.iter().filter(|link| link.link_type == PageLinkType::Auto) {
+            if let Some(related) = self.get_page(&link.target_page_id).await? {
+                related_pages.push(related);
+            }
         }
+        let parent_page = match &page.parent_page_id {
+            Some(parent_id) => self.get_page(parent_id).await?,
+            None => None,
+        };
+        let rows = sqlx::query(
+            r#"
+            SELECT id, notebook_id, section_id, parent_page_id, title, content, tags, order_index, created_at, updated_at, metadata
+            FROM pages
+            WHERE parent_page_id = ?
+            ORDER BY order_index ASC, created_at ASC
+            "#
+        )
+        .bind(page_id)
+        .fetch_all(&self.pool)
+        .await?;
 
-        query_parts.push("updated_at = ?");
-        let now = Utc::now().to_rfc3339();
-        params.push(&now);
-
-        let query = format!(
-            "UPDATE notebooks SET {} WHERE id = ?",
-            query_parts.join(", ")
-        );
+        Ok(PageRelationships {
+            page_id: page_id.to_string(),
+            outgoing_links,
+            incoming_links,
+            related_pages,
+            parent_page,
+            child_pages: self.pages_from_rows(rows)?,
+            link_type_counts,
+        })
+    }
 
-        let mut query_builder = sqlx::query(&query);
-        for param in params {
-            query_builder = query_builder.bind(param);
+    /// Keeps a suggested `Auto` link as a `Manual` one, so refreshing suggestions leaves it.
+    pub async fn promote_page_link(&self, id: &str) -> AppResult<PageLink> {
+        let row = sqlx::query(
+            "UPDATE page_links SET link_type = ? WHERE id = ? AND link_type = ? RETURNING id, source_page_id, target_page_id, link_text, link_type, created_at"
+        )
+        .bind(PageLinkType::Manual.as_str())
+        .bind(id)
+        .bind(PageLinkType::Auto.as_str())
+        .fetch_optional(&self.pool)
+        .await?;
+        match row {
+            Some(row) => page_link_from_row(&row),
+            None => Err(self.not_a_suggestion(id).await),
         }
-        query_builder = query_builder.bind(&request.id);
-
-        query_builder.execute(&self.pool).await?;
-        Ok(())
     }
 
-    pub async fn delete_notebook(&self, id: &str) -> AppResult<()> {
-        sqlx::query("DELETE FROM notebooks WHERE id = ?")
+    /// Removes a suggested `Auto` link and remembers its target, so it isn't suggested again.
+    pub async fn dismiss_page_link(&self, id: &str) -> AppResult<()> {
+        let mut tx = self.pool.begin().await?;
+        let row = sqlx::query("DELETE FROM page_links WHERE id = ? AND link_type = ? RETURNING source_page_id, target_page_id")
             .bind(id)
-            .execute(&self.pool)
+            .bind(PageLinkType::Auto.as_str())
+            .fetch_optional(&mut *tx)
             .await?;
-        Ok(())
-    }
-
+        let Some(row) = row else {
+            drop(tx);
+            return Err(self.not_a_suggestion(id).await);
+        };
+        sqlx::query(
+            r#"
+            INSERT OR IGNORE INTO dismissed_links (id, source_page_id, target_page_id, dismissed_at)
+            VALUES (?, ?, ?, ?)
+            "#
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(row.get::<String, _>("source_page_id"))
+        .bind(row.get::<String, _>("target_page_id"))
+        .bind(Utc::now().to_rfc3339())
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Pages the user has dismissed as suggestions for `source_page_id`.
+    pub async fn get_dismissed_link_targets(&self, source_page_id: &str) -> AppResult<HashSet<String>> {
+        let targets = sqlx::query_scalar("SELECT target_page_id FROM dismissed_links WHERE source_page_id = ?")
+            .bind(source_page_id)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(targets.into_iter().collect())
+    }
+
+    async fn not_a_suggestion(&self, id: &str) -> AppError {
+        let exists = sqlx::query_scalar::<_, String>("SELECT id FROM page_links WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await;
+        match exists {
+            Ok(Some(_)) => AppError::InvalidOperation(format!("Page link {} isn't a suggested link", id)),
+            Ok(None) => AppError::NotFound(format!("Page link {} not found", id)),
+            Err(e) => e.into(),
+        }
+    }
+
+    /// Links into a page from other pages, most recently updated source first, each with
+    /// the text around it. Suggested `Auto` links aren't references and are left out.
+    pub async fn get_backlinks(&self, page_id: &str) -> AppResult<Vec<Backlink>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT l.id, l.source_page_id, l.link_text, l.link_type, p.title, p.notebook_id, p.content
+            FROM page_links l
+            JOIN pages p ON p.id = l.source_page_id
+            WHERE l.target_page_id = ? AND l.link_type != ?
+            ORDER BY p.updated_at DESC, l.rowid
+            "#
+        )
+        .bind(page_id)
+        .bind(PageLinkType::Auto.as_str())
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut backlinks = Vec::new();
+        for row in rows {
+            let content: String = row.get("content");
+            let content = match self.encryption_manager {
+                Some(ref enc) => enc.decrypt_string(&content)?,
+                None => content,
+            };
+            let link_text: String = row.get("link_text");
+            let link_type: String = row.get("link_type");
+            backlinks.push(Backlink {
+                link_id: row.get("id"),
+                source_page_id: row.get("source_page_id"),
+                source_title: row.get("title"),
+                notebook_id: row.get("notebook_id"),
+                context: wikilinks::context(&content, &link_text),
+                link_text,
+                link_type: PageLinkType::parse(&link_type)
+                    .ok_or_else(|| AppError::InvalidFormat(format!("Unknown link type: {}", link_type)))?,
+            });
+        }
+        Ok(backlinks)
+    }
+
+    /// Pages (id, title), in one notebook or all, and the links touching them as (source,
+    /// target). Suggested `Auto` links aren't part of the graph.
+    pub async fn get_link_graph(&self, notebook_id: Option<&str>) -> AppResult<(Vec<(String, String)>, Vec<(String, String)>)> {
+        let pages = sqlx::query("SELECT id, title FROM pages WHERE ? IS NULL OR notebook_id = ? ORDER BY created_at, id")
+            .bind(notebook_id)
+            .bind(notebook_id)
+            .fetch_all(&self.pool)
+            .await?
+            .iter()
+            .map(|row| (row.get("id"), row.get("title")))
+            .collect();
+        let links = sqlx::query(
+            r#"
+            SELECT DISTINCT l.source_page_id, l.target_page_id
+            FROM page_links l
+            JOIN pages s ON s.id = l.source_page_id
+            JOIN pages t ON t.id = l.target_page_id
+            WHERE l.link_type != ? AND (? IS NULL OR s.notebook_id = ? OR t.notebook_id = ?)
+            "#
+        )
+        .bind(PageLinkType::Auto.as_str())
+        .bind(notebook_id)
+        .bind(notebook_id)
+        .bind(notebook_id)
+        .fetch_all(&self.pool)
+        .await?
+        .iter()
+        .map(|row| (row.get("source_page_id"), row.get("target_page_id")))
+        .collect();
+        Ok((pages, links))
+    }
+
+    // Map of content operations
+    pub async fn find_moc(&self, source: &MocSource) -> AppResult<Option<String>> {
+        let page_id = sqlx::query_scalar("SELECT page_id FROM mocs WHERE source = ?")
+            .bind(&serde_json::to_string(source)?)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(page_id)
+    }
+
+    pub async fn get_moc_source(&self, page_id: &str) -> AppResult<Option<MocSource>> {
+        let source: Option<String> = sqlx::query_scalar("SELECT source FROM mocs WHERE page_id = ?")
+            .bind(page_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        match source {
+            Some(source) => Ok(Some(serde_json::from_str(&source)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub async fn save_moc(&self, page_id: &str, source: &MocSource) -> AppResult<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO mocs (page_id, source, refreshed_at) VALUES (?, ?, ?)
+            ON CONFLICT(page_id) DO UPDATE SET source = excluded.source, refreshed_at = excluded.refreshed_at
+            "#
+        )
+        .bind(page_id)
+        .bind(&serde_json::to_string(source)?)
+        .bind(&Utc::now().to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Every page's id and title.
+    pub async fn get_page_titles(&self) -> AppResult<Vec<(String, String)>> {
+        let rows = sqlx::query("SELECT id, title FROM pages")
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows.iter().map(|row| (row.get("id"), row.get("title"))).collect())
+    }
+
+    pub async fn get_section_titles(&self) -> AppResult<HashMap<String, String>> {
+        let rows = sqlx::query("SELECT id, title FROM sections")
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows.iter().map(|row| (row.get("id"), row.get("title"))).collect())
+    }
+
+    // Notebook operations
+    pub async fn create_notebook(&self, request: CreateNotebookRequest) -> AppResult<Notebook> {
+        let notebook = Notebook::new(request.title, request.description, request.color);
+        
+        sqlx::query(
+            r#"
+            INSERT INTO notebooks (id, title, description, color, order_index, created_at, updated_at, metadata)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            "#
+        )
+        .bind(&notebook.id)
+        .bind(&notebook.title)
+        .bind(&notebook.description)
+        .bind(&notebook.color)
+        .bind(notebook.order_index)
+        .bind(&notebook.created_at.to_rfc3339())
+        .bind(&notebook.updated_at.to_rfc3339())
+        .bind(&serde_json::to_string(&notebook.metadata)?)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(notebook)
+    }
+
+    pub async fn get_notebooks(&self) -> AppResult<Vec<Notebook>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, title, description, color, order_index, created_at, updated_at, metadata
+            FROM notebooks
+            ORDER BY COALESCE(json_extract(metadata, '$.is_pinned'), 0) DESC, order_index ASC, created_at ASC
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut notebooks = Vec::new();
+        for row in rows {
+            let notebook = Notebook {
+                id: row.get("id"),
+                title: row.get("title"),
+                description: row.get("description"),
+                color: row.get("color"),
+                order_index: row.get("order_index"),
+                created_at: DateTime::parse_from_rfc3339(&row.get::<String, _>("created_at"))?.with_timezone(&Utc),
+                updated_at: DateTime::parse_from_rfc3339(&row.get::<String, _>("updated_at"))?.with_timezone(&Utc),
+                sections: Vec::new(), // Will be populated by get_notebook_hierarchy
+                metadata: serde_json::from_str(&row.get::<String, _>("metadata"))?,
+            };
+            notebooks.push(notebook);
+        }
+
+        Ok(notebooks)
+    }
+
+    pub async fn get_notebook(&self, id: &str) -> AppResult<Option<Notebook>> {
+        let row = sqlx::query(
+            r#"
+            SELECT id, title, description, color, order_index, created_at, updated_at, metadata
+            FROM notebooks
+            WHERE id = ?
+            "#
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        if let Some(row) = row {
+            let notebook = Notebook {
+                id: row.get("id"),
+                title: row.get("title"),
+                description: row.get("description"),
+                color: row.get("color"),
+                order_index: row.get("order_index"),
+                created_at: DateTime::parse_from_rfc3339(&row.get::<String, _>("created_at"))?.with_timezone(&Utc),
+                updated_at: DateTime::parse_from_rfc3339(&row.get::<String, _>("updated_at"))?.with_timezone(&Utc),
+                sections: Vec::new(),
+                metadata: serde_json::from_str(&row.get::<String, _>("metadata"))?,
+            };
+            Ok(Some(notebook))
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub async fn update_notebook(&self, request: UpdateNotebookRequest) -> AppResult<()> {
+        // Language and privacy settings live in the metadata JSON, so merge them into the stored metadata
+        let metadata_json = if request.language.is_some() || request.spell_check.is_some() || request.private.is_some() {
+            let mut metadata = self.get_notebook(&request.id).await?
+                .ok_or_else(|| AppError::NotFound(format!("Notebook with id {} not found", request.id)))?
+                .metadata;
+
+            if let Some(language) = &request.language {
+                metadata.language = normalize_language(language)?;
+            }
+            if let Some(spell_check) = request.spell_check {
+                metadata.spell_check = spell_check;
+            }
+            if let Some(private) = request.private {
+                metadata.private = private;
+            }
+            Some(serde_json::to_string(&metadata)?)
+        } else {
+            None
+        };
+
+        let mut query_parts = Vec::new();
+        let mut params = Vec::new();
+
+        if let Some(metadata_json) = &metadata_json {
+            query_parts.push("metadata = ?");
+            params.push(metadata_json.as_str());
+        }
+
+        if let Some(title) = &request.title {
+            query_parts.push("title = ?");
+            params.push(title.as_str());
+        }
+        if let Some(description) = &request.description {
+            query_parts.push("description = ?");
+            params.push(description.as_str());
+        }
+        if let Some(color) = &request.color {
+            query_parts.push("color = ?");
+            params.push(color.as_str());
+        }
+        if let Some(order_index) = &request.order_index {
+            query_parts.push("order_index = ?");
+            params.push(&order_index.to_string());
+        }
+
+        if query_parts.is_empty() {
+            return Ok(());
+        }
+
+        query_parts.push("updated_at = ?");
+        let now = Utc::now().to_rfc3339();
+        params.push(&now);
+
+        let query = format!(
+            "UPDATE notebooks SET {} WHERE id = ?",
+            query_parts.join(", ")
+        );
+
+        let mut query_builder = sqlx::query(&query);
+        for param in params {
+            query_builder = query_builder.bind(param);
+        }
+        query_builder = query_builder.bind(&request.id);
+
+        query_builder.execute(&self.pool).await?;
+        Ok(())
+    }
+
+    pub async fn delete_notebook(&self, id: &str) -> AppResult<()> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM notebooks WHERE id = ?")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        // Its trashed pages have nowhere to be restored to
+        sqlx::query("DELETE FROM trash WHERE notebook_id = ?")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
     // Section operations
     pub async fn create_section(&self, request: CreateSectionRequest) -> AppResult<Section> {
         let section = Section::new(request.notebook_id, request.title, request.color);
@@ -955,7 +3231,7 @@ impl Database {
             request.parent_page_id,
             request.title,
             request.content,
-            request.tags,
+            tags::normalize_tags(request.tags),
         );
         
         let encrypted_content = if let Some(ref enc) = self.encryption_manager {
@@ -994,7 +3270,7 @@ impl Database {
                 SELECT id, notebook_id, section_id, parent_page_id, title, content, tags, order_index, created_at, updated_at, metadata
                 FROM pages
                 WHERE notebook_id = ? AND section_id = ?
-                ORDER BY order_index ASC, created_at ASC
+                ORDER BY COALESCE(json_extract(metadata, '$.is_pinned'), 0) DESC, order_index ASC, created_at ASC
                 "#
             )
             .bind(notebook_id)
@@ -1007,7 +3283,7 @@ impl Database {
                 SELECT id, notebook_id, section_id, parent_page_id, title, content, tags, order_index, created_at, updated_at, metadata
                 FROM pages
                 WHERE notebook_id = ?
-                ORDER BY order_index ASC, created_at ASC
+                ORDER BY COALESCE(json_extract(metadata, '$.is_pinned'), 0) DESC, order_index ASC, created_at ASC
                 "#
             )
             .bind(notebook_id)
@@ -1015,6 +3291,38 @@ impl Database {
             .await?
         };
 
+        self.pages_from_rows(rows)
+    }
+
+    /// Pages of a notebook, or one of its sections, by last update, newest first; paged by
+    /// cursor like `list_notes`.
+    pub async fn list_pages(&self, notebook_id: &str, section_id: Option<&str>, limit: Option<usize>, cursor: Option<&str>) -> AppResult<SearchPage<Page>> {
+        let limit = limit.unwrap_or(50).max(1);
+        let mut conditions = vec!["notebook_id = ?".to_string()];
+        let mut binds = vec![notebook_id.to_string()];
+        if let Some(section_id) = section_id {
+            conditions.push("section_id = ?".to_string());
+            binds.push(section_id.to_string());
+        }
+        let (condition, values) = cursor_condition(cursor)?;
+        conditions.extend(condition);
+        binds.extend(values);
+
+        let sql = format!(
+            "SELECT id, notebook_id, section_id, parent_page_id, title, content, tags, order_index, created_at, updated_at, metadata FROM pages {} ORDER BY updated_at DESC, id DESC LIMIT ?",
+            where_clause(&conditions)
+        );
+        let mut query = sqlx::query(&sql);
+        for value in &binds {
+            query = query.bind(value);
+        }
+        let rows = query.bind((limit + 1) as i64).fetch_all(&self.pool).await?;
+        let mut pages = self.pages_from_rows(rows)?;
+        let next_cursor = next_cursor(&mut pages, limit, |page| (page.updated_at, page.id.clone()))?;
+        Ok(SearchPage { items: pages, next_cursor })
+    }
+
+    fn pages_from_rows(&self, rows: Vec<sqlx::sqlite::SqliteRow>) -> AppResult<Vec<Page>> {
         let mut pages = Vec::new();
         for row in rows {
             let content: String = row.get("content");
@@ -1090,33 +3398,566 @@ impl Database {
         }
     }
 
-    pub async fn update_page(&self, request: UpdatePageRequest) -> AppResult<()> {
-        let mut query_parts = Vec::new();
-        let mut params: Vec<Box<dyn ToString>> = Vec::new();
+    /// The page titled `title`, ignoring case, preferring one in `notebook_id` and then the
+    /// oldest.
+    pub async fn find_page_id_by_title(&self, title: &str, notebook_id: &str) -> AppResult<Option<String>> {
+        let id = sqlx::query_scalar(
+            "SELECT id FROM pages WHERE title = ? COLLATE NOCASE ORDER BY notebook_id = ? DESC, created_at ASC LIMIT 1"
+        )
+        .bind(title.trim())
+        .bind(notebook_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(id)
+    }
 
-        if let Some(title) = &request.title {
-            query_parts.push("title = ?");
-            params.push(Box::new(title.clone()));
-        }
-        if let Some(content) = &request.content {
-            let encrypted_content = if let Some(ref enc) = self.encryption_manager {
-                enc.encrypt_string(content)?
-            } else {
-                content.clone()
-            };
-            query_parts.push("content = ?");
-            params.push(Box::new(encrypted_content));
-        }
-        if let Some(tags) = &request.tags {
-            query_parts.push("tags = ?");
-            params.push(Box::new(serde_json::to_string(tags)?));
+    pub async fn get_page_by_zettel_id(&self, zettel_id: &str) -> AppResult<Option<Page>> {
+        let id: Option<String> = sqlx::query_scalar("SELECT id FROM pages WHERE json_extract(metadata, '$.zettel_id') = ?")
+            .bind(zettel_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        match id {
+            Some(id) => self.get_page(&id).await,
+            None => Ok(None),
         }
-        if let Some(order_index) = &request.order_index {
-            query_parts.push("order_index = ?");
-            params.push(Box::new(*order_index));
+    }
+
+    /// Gives a page a Zettel ID from its creation time, moving to the next free minute when
+    /// another page already has it. Pages that have an ID keep it.
+    pub async fn assign_zettel_id(&self, page_id: &str) -> AppResult<String> {
+        let page = self.get_page(page_id).await?
+            .ok_or_else(|| AppError::NotFound(format!("Page with id {} not found", page_id)))?;
+        if let Some(id) = page.metadata.zettel_id {
+            return Ok(id);
         }
 
-        if query_parts.is_empty() {
+        let mut id = zettel::zettel_id(page.created_at);
+        while self.get_page_by_zettel_id(&id).await?.is_some() {
+            id = zettel::next_zettel_id(&id)?;
+        }
+        sqlx::query("UPDATE pages SET metadata = json_set(metadata, '$.zettel_id', ?) WHERE id = ?")
+            .bind(&id)
+            .bind(page_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(id)
+    }
+
+    /// Confirms a page is still accurate without counting as an edit.
+    pub async fn mark_page_reviewed(&self, page_id: &str) -> AppResult<()> {
+        let result = sqlx::query("UPDATE pages SET metadata = json_set(metadata, '$.reviewed_at', ?) WHERE id = ?")
+            .bind(&Utc::now().to_rfc3339())
+            .bind(page_id)
+            .execute(&self.pool)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound(format!("Page with id {} not found", page_id)));
+        }
+        Ok(())
+    }
+
+    /// Id and title of every page outside private notebooks.
+    pub async fn get_public_page_titles(&self) -> AppResult<Vec<(String, String)>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT p.id, p.title
+            FROM pages p
+            JOIN notebooks n ON n.id = p.notebook_id
+            WHERE COALESCE(json_extract(n.metadata, '$.private'), 0) = 0
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|row| (row.get("id"), row.get("title"))).collect())
+    }
+
+    /// The title of page `id`, unless it's gone or in a private notebook.
+    pub async fn get_public_page_title(&self, id: &str) -> AppResult<Option<String>> {
+        let title = sqlx::query_scalar(
+            r#"
+            SELECT p.title
+            FROM pages p
+            JOIN notebooks n ON n.id = p.notebook_id
+            WHERE p.id = ? AND COALESCE(json_extract(n.metadata, '$.private'), 0) = 0
+            "#
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(title)
+    }
+
+    /// Records that a page was opened: in its metadata, its notebook's, and the recents list,
+    /// which keeps the `RECENT_PAGES_KEPT` latest pages.
+    pub async fn record_page_access(&self, page_id: &str) -> AppResult<()> {
+        let now = Utc::now().to_rfc3339();
+        let mut tx = self.pool.begin().await?;
+        let notebook_id: String = sqlx::query_scalar("UPDATE pages SET metadata = json_set(metadata, '$.last_accessed', ?) WHERE id = ? RETURNING notebook_id")
+            .bind(&now)
+            .bind(page_id)
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("Page with id {} not found", page_id)))?;
+        sqlx::query("UPDATE notebooks SET metadata = json_set(metadata, '$.last_accessed', ?) WHERE id = ?")
+            .bind(&now)
+            .bind(&notebook_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query(
+            r#"
+            INSERT INTO recent_pages (page_id, accessed_at) VALUES (?, ?)
+            ON CONFLICT(page_id) DO UPDATE SET accessed_at = excluded.accessed_at, access_count = access_count + 1
+            "#
+        )
+        .bind(page_id)
+        .bind(&now)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query("DELETE FROM recent_pages WHERE page_id NOT IN (SELECT page_id FROM recent_pages ORDER BY accessed_at DESC LIMIT ?)")
+            .bind(RECENT_PAGES_KEPT)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    pub async fn record_notebook_access(&self, notebook_id: &str) -> AppResult<()> {
+        let result = sqlx::query("UPDATE notebooks SET metadata = json_set(metadata, '$.last_accessed', ?) WHERE id = ?")
+            .bind(&Utc::now().to_rfc3339())
+            .bind(notebook_id)
+            .execute(&self.pool)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound(format!("Notebook with id {} not found", notebook_id)));
+        }
+        Ok(())
+    }
+
+    /// The most recently opened pages, newest first.
+    pub async fn get_recent_pages(&self, limit: usize) -> AppResult<Vec<RecentPage>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT r.page_id, p.notebook_id, p.title, r.accessed_at, r.access_count
+            FROM recent_pages r
+            JOIN pages p ON p.id = r.page_id
+            ORDER BY r.accessed_at DESC
+            LIMIT ?
+            "#
+        )
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut pages = Vec::new();
+        for row in rows {
+            pages.push(RecentPage {
+                page_id: row.get("page_id"),
+                notebook_id: row.get("notebook_id"),
+                title: row.get("title"),
+                accessed_at: DateTime::parse_from_rfc3339(&row.get::<String, _>("accessed_at"))?.with_timezone(&Utc),
+                access_count: row.get::<i64, _>("access_count") as u32,
+            });
+        }
+        Ok(pages)
+    }
+
+    pub async fn get_property_definitions(&self) -> AppResult<Vec<PropertyDefinition>> {
+        let rows = sqlx::query("SELECT name, property_type, options, created_at FROM page_property_definitions ORDER BY name")
+            .fetch_all(&self.pool)
+            .await?;
+        rows.iter().map(property_definition_from_row).collect()
+    }
+
+    pub async fn get_property_definition(&self, name: &str) -> AppResult<Option<PropertyDefinition>> {
+        let Some(name) = properties::normalize_name(name) else {
+            return Ok(None);
+        };
+        let row = sqlx::query("SELECT name, property_type, options, created_at FROM page_property_definitions WHERE name = ?")
+            .bind(&name)
+            .fetch_optional(&self.pool)
+            .await?;
+        row.as_ref().map(property_definition_from_row).transpose()
+    }
+
+    /// Creates a property or updates its options. A property's type can only change while
+    /// no page has a value for it.
+    pub async fn define_property(&self, request: &DefinePropertyRequest) -> AppResult<PropertyDefinition> {
+        let name = properties::normalize_name(&request.name)
+            .ok_or_else(|| AppError::InvalidFormat("Property name must be 1 to 64 characters".to_string()))?;
+        let options = match request.property_type {
+            PropertyType::Select => properties::normalize_options(&request.options),
+            _ => Vec::new(),
+        };
+
+        let mut tx = self.pool.begin().await?;
+        let existing: Option<String> = sqlx::query_scalar("SELECT property_type FROM page_property_definitions WHERE name = ?")
+            .bind(&name)
+            .fetch_optional(&mut *tx)
+            .await?;
+        if existing.is_some_and(|t| t != request.property_type.as_str()) {
+            let used: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM page_properties WHERE name = ?")
+                .bind(&name)
+                .fetch_one(&mut *tx)
+                .await?;
+            if used > 0 {
+                return Err(AppError::InvalidOperation(format!(
+                    "Property '{}' is set on {} pages; remove it from them before changing its type",
+                    name, used
+                )));
+            }
+        }
+        sqlx::query(
+            r#"
+            INSERT INTO page_property_definitions (name, property_type, options, created_at) VALUES (?, ?, ?, ?)
+            ON CONFLICT(name) DO UPDATE SET property_type = excluded.property_type, options = excluded.options
+            "#
+        )
+        .bind(&name)
+        .bind(request.property_type.as_str())
+        .bind(serde_json::to_string(&options)?)
+        .bind(Utc::now().to_rfc3339())
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        self.get_property_definition(&name).await?
+            .ok_or_else(|| AppError::NotFound(format!("Property {} not found", name)))
+    }
+
+    /// Deletes a property along with its value on every page; returns how many values went.
+    pub async fn delete_property_definition(&self, name: &str) -> AppResult<u64> {
+        let name = properties::normalize_name(name)
+            .ok_or_else(|| AppError::NotFound(format!("Property {} not found", name)))?;
+        let mut tx = self.pool.begin().await?;
+        let removed = sqlx::query("DELETE FROM page_properties WHERE name = ?")
+            .bind(&name)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        let result = sqlx::query("DELETE FROM page_property_definitions WHERE name = ?")
+            .bind(&name)
+            .execute(&mut *tx)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound(format!("Property {} not found", name)));
+        }
+        tx.commit().await?;
+        Ok(removed)
+    }
+
+    /// Sets a property on a page, defining it from the value's type on first use.
+    pub async fn set_page_property(&self, page_id: &str, name: &str, value: &PropertyValue) -> AppResult<PageProperty> {
+        let definition = match self.get_property_definition(name).await? {
+            Some(definition) => definition,
+            None => {
+                let options = match value {
+                    PropertyValue::Select(option) => vec![option.clone()],
+                    _ => Vec::new(),
+                };
+                self.define_property(&DefinePropertyRequest { name: name.to_string(), property_type: value.property_type(), options }).await?
+            }
+        };
+        properties::validate(&definition, value)?;
+
+        let exists: Option<String> = sqlx::query_scalar("SELECT id FROM pages WHERE id = ?")
+            .bind(page_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        if exists.is_none() {
+            return Err(AppError::NotFound(format!("Page with id {} not found", page_id)));
+        }
+
+        let (text_value, number_value) = properties::to_columns(value);
+        let text_value = match (&self.encryption_manager, text_value) {
+            (Some(enc), Some(text)) => Some(enc.encrypt_string(&text)?),
+            (_, text) => text,
+        };
+        let updated_at = Utc::now();
+        sqlx::query(
+            r#"
+            INSERT INTO page_properties (id, page_id, name, text_value, number_value, updated_at) VALUES (?, ?, ?, ?, ?, ?)
+            ON CONFLICT(page_id, name) DO UPDATE SET
+                text_value = excluded.text_value, number_value = excluded.number_value, updated_at = excluded.updated_at
+            "#
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(page_id)
+        .bind(&definition.name)
+        .bind(text_value)
+        .bind(number_value)
+        .bind(updated_at.to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(PageProperty { page_id: page_id.to_string(), name: definition.name, value: value.clone(), updated_at })
+    }
+
+    /// Returns whether the page had the property.
+    pub async fn remove_page_property(&self, page_id: &str, name: &str) -> AppResult<bool> {
+        let Some(name) = properties::normalize_name(name) else {
+            return Ok(false);
+        };
+        let result = sqlx::query("DELETE FROM page_properties WHERE page_id = ? AND name = ?")
+            .bind(page_id)
+            .bind(&name)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn get_page_properties(&self, page_id: &str) -> AppResult<Vec<PageProperty>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT v.page_id, v.name, d.property_type, v.text_value, v.number_value, v.updated_at
+            FROM page_properties v
+            JOIN page_property_definitions d ON d.name = v.name
+            WHERE v.page_id = ?
+            ORDER BY v.name
+            "#
+        )
+        .bind(page_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut page_properties = Vec::new();
+        for row in rows {
+            let property_type: String = row.get("property_type");
+            let property_type = PropertyType::parse(&property_type)
+                .ok_or_else(|| AppError::InvalidFormat(format!("Unknown property type: {}", property_type)))?;
+            page_properties.push(PageProperty {
+                page_id: row.get("page_id"),
+                name: row.get("name"),
+                value: properties::from_columns(property_type, self.decrypt_property_text(row.get("text_value"))?, row.get("number_value"))?,
+                updated_at: DateTime::parse_from_rfc3339(&row.get::<String, _>("updated_at"))?.with_timezone(&Utc),
+            });
+        }
+        Ok(page_properties)
+    }
+
+    /// Every page's value of a property, decrypted, by page id.
+    async fn get_property_values(&self, definition: &PropertyDefinition) -> AppResult<HashMap<String, PropertyValue>> {
+        let rows = sqlx::query("SELECT page_id, text_value, number_value FROM page_properties WHERE name = ?")
+            .bind(&definition.name)
+            .fetch_all(&self.pool)
+            .await?;
+        let mut values = HashMap::new();
+        for row in rows {
+            let text = self.decrypt_property_text(row.get("text_value"))?;
+            values.insert(row.get::<String, _>("page_id"), properties::from_columns(definition.property_type, text, row.get("number_value"))?);
+        }
+        Ok(values)
+    }
+
+    fn decrypt_property_text(&self, text: Option<String>) -> AppResult<Option<String>> {
+        match (&self.encryption_manager, text) {
+            (Some(enc), Some(text)) => Ok(Some(enc.decrypt_string(&text)?)),
+            (_, text) => Ok(text),
+        }
+    }
+
+    /// Pages matching every filter, optionally only in one notebook, most recently updated first.
+    pub async fn filter_pages_by_properties(&self, request: &FilterPagesRequest) -> AppResult<Vec<Page>> {
+        let mut conditions = Vec::new();
+        let mut binds = Vec::new();
+        if let Some(notebook_id) = &request.notebook_id {
+            conditions.push("p.notebook_id = ?".to_string());
+            binds.push(StoredValue::Text(notebook_id.clone()));
+        }
+        let mut text_filters = Vec::new();
+        for filter in &request.filters {
+            let definition = self.get_property_definition(&filter.name).await?
+                .ok_or_else(|| AppError::NotFound(format!("Property {} not found", filter.name)))?;
+            if let Some((condition, values)) = properties::filter_condition(filter, &definition)? {
+                conditions.push(condition);
+                binds.extend(values);
+            }
+            if properties::compares_text(filter, &definition) {
+                text_filters.push((filter, definition));
+            }
+        }
+
+        let sql = format!(
+            "SELECT p.id, p.notebook_id, p.section_id, p.parent_page_id, p.title, p.content, p.tags, p.order_index, p.created_at, p.updated_at, p.metadata FROM pages p {} ORDER BY p.updated_at DESC, p.id DESC",
+            where_clause(&conditions)
+        );
+        let mut query = sqlx::query(&sql);
+        for value in binds {
+            query = match value {
+                StoredValue::Real(number) => query.bind(number),
+                StoredValue::Text(text) => query.bind(text),
+                _ => return Err(AppError::InvalidFormat("Unsupported property filter value".to_string())),
+            };
+        }
+        let rows = query.fetch_all(&self.pool).await?;
+        let mut pages = self.pages_from_rows(rows)?;
+
+        // Text values may be encrypted, so they're compared once decrypted
+        for (filter, definition) in text_filters {
+            let values = self.get_property_values(&definition).await?;
+            pages.retain(|page| properties::value_matches(filter, values.get(&page.id)));
+        }
+        Ok(pages)
+    }
+
+    /// The oldest notebook with this title, ignoring case.
+    pub async fn find_notebook_by_title(&self, title: &str) -> AppResult<Option<Notebook>> {
+        let id: Option<String> = sqlx::query_scalar("SELECT id FROM notebooks WHERE title = ? COLLATE NOCASE ORDER BY created_at ASC LIMIT 1")
+            .bind(title.trim())
+            .fetch_optional(&self.pool)
+            .await?;
+        match id {
+            Some(id) => self.get_notebook(&id).await,
+            None => Ok(None),
+        }
+    }
+
+    pub async fn find_daily_page(&self, notebook_id: &str, date: NaiveDate) -> AppResult<Option<Page>> {
+        let id: Option<String> = sqlx::query_scalar("SELECT id FROM pages WHERE notebook_id = ? AND json_extract(metadata, '$.daily_date') = ? ORDER BY created_at ASC LIMIT 1")
+            .bind(notebook_id)
+            .bind(date.to_string())
+            .fetch_optional(&self.pool)
+            .await?;
+        match id {
+            Some(id) => self.get_page(&id).await,
+            None => Ok(None),
+        }
+    }
+
+    pub async fn set_daily_date(&self, page_id: &str, date: NaiveDate) -> AppResult<()> {
+        let result = sqlx::query("UPDATE pages SET metadata = json_set(metadata, '$.daily_date', ?) WHERE id = ?")
+            .bind(date.to_string())
+            .bind(page_id)
+            .execute(&self.pool)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound(format!("Page with id {} not found", page_id)));
+        }
+        Ok(())
+    }
+
+    pub async fn set_notebook_pinned(&self, notebook_id: &str, pinned: bool) -> AppResult<()> {
+        let result = sqlx::query("UPDATE notebooks SET metadata = json_set(metadata, '$.is_pinned', json(?)) WHERE id = ?")
+            .bind(pinned.to_string())
+            .bind(notebook_id)
+            .execute(&self.pool)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound(format!("Notebook with id {} not found", notebook_id)));
+        }
+        Ok(())
+    }
+
+    /// Pinned notebooks in their usual order and pinned pages by title, for the favorites list.
+    pub async fn get_pinned_items(&self) -> AppResult<PinnedItems> {
+        let notebooks = self.get_notebooks().await?
+            .into_iter()
+            .filter(|notebook| notebook.metadata.is_pinned)
+            .collect();
+        let pages = sqlx::query(
+            r#"
+            SELECT id, notebook_id, title
+            FROM pages
+            WHERE json_extract(metadata, '$.is_pinned') = 1
+            ORDER BY title COLLATE NOCASE, id
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .iter()
+        .map(|row| PinnedPage {
+            id: row.get("id"),
+            notebook_id: row.get("notebook_id"),
+            title: row.get("title"),
+        })
+        .collect();
+
+        Ok(PinnedItems { notebooks, pages })
+    }
+
+    pub async fn set_page_pinned(&self, page_id: &str, pinned: bool) -> AppResult<()> {
+        let result = sqlx::query("UPDATE pages SET metadata = json_set(metadata, '$.is_pinned', json(?)) WHERE id = ?")
+            .bind(pinned.to_string())
+            .bind(page_id)
+            .execute(&self.pool)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound(format!("Page with id {} not found", page_id)));
+        }
+        Ok(())
+    }
+
+    /// Pinned pages by title, then up to `recent_limit` other pages, most recently opened first.
+    pub async fn get_jump_list_entries(&self, recent_limit: usize) -> AppResult<Vec<JumpListEntry>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, title, COALESCE(json_extract(metadata, '$.is_pinned'), 0) AS pinned
+            FROM pages
+            WHERE json_extract(metadata, '$.is_pinned') = 1
+               OR json_extract(metadata, '$.last_accessed') IS NOT NULL
+            ORDER BY pinned DESC,
+                     CASE WHEN pinned = 1 THEN title COLLATE NOCASE END,
+                     json_extract(metadata, '$.last_accessed') DESC
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut entries = Vec::new();
+        let mut recent = 0;
+        for row in rows {
+            let pinned = row.get::<i64, _>("pinned") == 1;
+            if !pinned {
+                if recent == recent_limit {
+                    break;
+                }
+                recent += 1;
+            }
+            entries.push(JumpListEntry { page_id: row.get("id"), title: row.get("title"), pinned });
+        }
+        Ok(entries)
+    }
+
+    pub async fn update_page(&self, request: UpdatePageRequest) -> AppResult<()> {
+        let mut query_parts = Vec::new();
+        let mut params: Vec<Box<dyn ToString>> = Vec::new();
+
+        if let Some(title) = &request.title {
+            query_parts.push("title = ?");
+            params.push(Box::new(title.clone()));
+        }
+        if let Some(content) = &request.content {
+            let encrypted_content = if let Some(ref enc) = self.encryption_manager {
+                enc.encrypt_string(content)?
+            } else {
+                content.clone()
+            };
+            query_parts.push("content = ?");
+            params.push(Box::new(encrypted_content));
+        }
+        if let Some(tags) = &request.tags {
+            query_parts.push("tags = ?");
+            params.push(Box::new(serde_json::to_string(&tags::normalize_tags(tags.clone()))?));
+        }
+        if let Some(order_index) = &request.order_index {
+            query_parts.push("order_index = ?");
+            params.push(Box::new(*order_index));
+        }
+        if request.content.is_some() || request.language.is_some() {
+            let mut metadata = self.get_page(&request.id).await?
+                .ok_or_else(|| AppError::NotFound(format!("Page with id {} not found", request.id)))?
+                .metadata;
+            if let Some(language) = &request.language {
+                metadata.language = normalize_language(language)?;
+            }
+            if let Some(content) = &request.content {
+                metadata.analyze_content(content);
+            }
+            query_parts.push("metadata = ?");
+            params.push(Box::new(serde_json::to_string(&metadata)?));
+        }
+
+        if query_parts.is_empty() {
             return Ok(());
         }
 
@@ -1139,14 +3980,105 @@ impl Database {
         Ok(())
     }
 
-    pub async fn delete_page(&self, id: &str) -> AppResult<()> {
-        sqlx::query("DELETE FROM pages WHERE id = ?")
-            .bind(id)
-            .execute(&self.pool)
-            .await?;
+    /// Moves a page and its subpages into the trash, with everything deleting them would
+    /// cascade to: annotations, attachments, links, revisions, export history and embeddings.
+    pub async fn trash_page(&self, id: &str) -> AppResult<()> {
+        let mut tx = self.pool.begin().await?;
+        trash_page_tree(&mut tx, id).await?;
+        tx.commit().await?;
         Ok(())
     }
 
+    /// Applies one operation to many pages in a single transaction. Pages that don't exist
+    /// are skipped and reported, as are subpages already trashed along with their parent.
+    pub async fn bulk_update_pages(&self, request: &BulkUpdatePagesRequest) -> AppResult<BulkUpdateResult> {
+        let mut tx = self.pool.begin().await?;
+        if let BulkPageOperation::Move { notebook_id, section_id } = &request.operation {
+            let notebook = sqlx::query("SELECT id FROM notebooks WHERE id = ?").bind(notebook_id).fetch_optional(&mut *tx).await?;
+            if notebook.is_none() {
+                return Err(AppError::NotFound(format!("Notebook with id {} not found", notebook_id)));
+            }
+            if let Some(section_id) = section_id {
+                let section = sqlx::query("SELECT id FROM sections WHERE id = ? AND notebook_id = ?")
+                    .bind(section_id)
+                    .bind(notebook_id)
+                    .fetch_optional(&mut *tx)
+                    .await?;
+                if section.is_none() {
+                    return Err(AppError::NotFound(format!("Section {} not found in notebook {}", section_id, notebook_id)));
+                }
+            }
+        }
+
+        let selected: HashSet<&str> = request.page_ids.iter().map(String::as_str).collect();
+        let mut trashed: HashSet<String> = HashSet::new();
+        let mut result = BulkUpdateResult { updated: Vec::new(), missing: Vec::new() };
+        let now = Utc::now().to_rfc3339();
+        for id in &request.page_ids {
+            if result.updated.contains(id) || trashed.contains(id) {
+                continue;
+            }
+            let Some(row) = sqlx::query("SELECT tags, parent_page_id FROM pages WHERE id = ?")
+                .bind(id)
+                .fetch_optional(&mut *tx)
+                .await?
+            else {
+                result.missing.push(id.clone());
+                continue;
+            };
+
+            match &request.operation {
+                BulkPageOperation::AddTags { tags: added } | BulkPageOperation::RemoveTags { tags: added } => {
+                    let current: Vec<String> = serde_json::from_str(&row.get::<String, _>("tags"))?;
+                    let updated = match &request.operation {
+                        BulkPageOperation::AddTags { .. } => tags::add_tags(&current, added),
+                        _ => tags::remove_tags(&current, added),
+                    };
+                    if let Some(updated) = updated {
+                        sqlx::query("UPDATE pages SET tags = ?, updated_at = ? WHERE id = ?")
+                            .bind(serde_json::to_string(&updated)?)
+                            .bind(&now)
+                            .bind(id)
+                            .execute(&mut *tx)
+                            .await?;
+                    }
+                }
+                BulkPageOperation::Move { notebook_id, section_id } => {
+                    // Subpages move with their page; a page whose parent stays behind becomes top-level
+                    let parent: Option<String> = row.get("parent_page_id");
+                    let parent = parent.filter(|parent| selected.contains(parent.as_str()));
+                    sqlx::query("UPDATE pages SET parent_page_id = ?, updated_at = ? WHERE id = ?")
+                        .bind(&parent)
+                        .bind(&now)
+                        .bind(id)
+                        .execute(&mut *tx)
+                        .await?;
+                    sqlx::query(
+                        r#"
+                        WITH RECURSIVE tree(id) AS (
+                            SELECT ?
+                            UNION SELECT pages.id FROM pages JOIN tree ON pages.parent_page_id = tree.id
+                        )
+                        UPDATE pages SET notebook_id = ?, section_id = ? WHERE id IN (SELECT id FROM tree)
+                        "#
+                    )
+                    .bind(id)
+                    .bind(notebook_id)
+                    .bind(section_id)
+                    .execute(&mut *tx)
+                    .await?;
+                }
+                BulkPageOperation::Delete => {
+                    trashed.extend(trash_page_tree(&mut tx, id).await?);
+                }
+            }
+            result.updated.push(id.clone());
+        }
+
+        tx.commit().await?;
+        Ok(result)
+    }
+
     pub async fn move_page(&self, request: MovePageRequest) -> AppResult<()> {
         let mut query_parts = Vec::new();
         let mut params: Vec<String> = Vec::new();
@@ -1190,4 +4122,2113 @@ impl Database {
         query_builder.execute(&self.pool).await?;
         Ok(())
     }
-}
\ No newline at end of file
+    // Automation operations
+    pub async fn create_automation(&self, request: CreateAutomationRequest) -> AppResult<Automation> {
+        let automation = Automation::new(request.name, request.trigger, request.action);
+
+        sqlx::query(
+            r#"
+            INSERT INTO automations (id, name, enabled, trigger_config, action_config, created_at, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+            "#
+        )
+        .bind(&automation.id)
+        .bind(&automation.name)
+        .bind(automation.enabled)
+        .bind(&serde_json::to_string(&automation.trigger)?)
+        .bind(&serde_json::to_string(&automation.action)?)
+        .bind(&automation.created_at.to_rfc3339())
+        .bind(&automation.updated_at.to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(automation)
+    }
+
+    pub async fn get_automations(&self) -> AppResult<Vec<Automation>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, name, enabled, trigger_config, action_config, created_at, updated_at
+            FROM automations
+            ORDER BY created_at ASC
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut automations = Vec::new();
+        for row in rows {
+            let automation = Automation {
+                id: row.get("id"),
+                name: row.get("name"),
+                enabled: row.get("enabled"),
+                trigger: serde_json::from_str(&row.get::<String, _>("trigger_config"))?,
+                action: serde_json::from_str(&row.get::<String, _>("action_config"))?,
+                created_at: DateTime::parse_from_rfc3339(&row.get::<String, _>("created_at"))?.with_timezone(&Utc),
+                updated_at: DateTime::parse_from_rfc3339(&row.get::<String, _>("updated_at"))?.with_timezone(&Utc),
+            };
+            automations.push(automation);
+        }
+
+        Ok(automations)
+    }
+
+    pub async fn get_automation(&self, id: &str) -> AppResult<Option<Automation>> {
+        let automations = self.get_automations().await?;
+        Ok(automations.into_iter().find(|a| a.id == id))
+    }
+
+    pub async fn update_automation(&self, request: UpdateAutomationRequest) -> AppResult<()> {
+        let mut automation = self.get_automation(&request.id).await?
+            .ok_or_else(|| AppError::NotFound(format!("Automation with id {} not found", request.id)))?;
+
+        if let Some(name) = request.name {
+            automation.name = name;
+        }
+        if let Some(enabled) = request.enabled {
+            automation.enabled = enabled;
+        }
+        if let Some(trigger) = request.trigger {
+            automation.trigger = trigger;
+        }
+        if let Some(action) = request.action {
+            automation.action = action;
+        }
+        automation.updated_at = Utc::now();
+
+        sqlx::query(
+            r#"
+            UPDATE automations
+            SET name = ?, enabled = ?, trigger_config = ?, action_config = ?, updated_at = ?
+            WHERE id = ?
+            "#
+        )
+        .bind(&automation.name)
+        .bind(automation.enabled)
+        .bind(&serde_json::to_string(&automation.trigger)?)
+        .bind(&serde_json::to_string(&automation.action)?)
+        .bind(&automation.updated_at.to_rfc3339())
+        .bind(&automation.id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn delete_automation(&self, id: &str) -> AppResult<()> {
+        sqlx::query("DELETE FROM automations WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn record_automation_run(&self, automation_id: &str, event: &AutomationEvent, success: bool, output: Option<String>) -> AppResult<AutomationRun> {
+        let run = AutomationRun {
+            id: Uuid::new_v4().to_string(),
+            automation_id: automation_id.to_string(),
+            event: event.clone(),
+            success,
+            output,
+            executed_at: Utc::now(),
+        };
+
+        sqlx::query(
+            r#"
+            INSERT INTO automation_runs (id, automation_id, event, success, output, executed_at)
+            VALUES (?, ?, ?, ?, ?, ?)
+            "#
+        )
+        .bind(&run.id)
+        .bind(&run.automation_id)
+        .bind(&serde_json::to_string(&run.event)?)
+        .bind(run.success)
+        .bind(&run.output)
+        .bind(&run.executed_at.to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(run)
+    }
+
+    pub async fn get_automation_runs(&self, automation_id: Option<&str>, limit: usize) -> AppResult<Vec<AutomationRun>> {
+        let rows = if let Some(automation_id) = automation_id {
+            sqlx::query(
+                r#"
+                SELECT id, automation_id, event, success, output, executed_at
+                FROM automation_runs
+                WHERE automation_id = ?
+                ORDER BY executed_at DESC
+                LIMIT ?
+                "#
+            )
+            .bind(automation_id)
+            .bind(limit as i64)
+            .fetch_all(&self.pool)
+            .await?
+        } else {
+            sqlx::query(
+                r#"
+                SELECT id, automation_id, event, success, output, executed_at
+                FROM automation_runs
+                ORDER BY executed_at DESC
+                LIMIT ?
+                "#
+            )
+            .bind(limit as i64)
+            .fetch_all(&self.pool)
+            .await?
+        };
+
+        let mut runs = Vec::new();
+        for row in rows {
+            let run = AutomationRun {
+                id: row.get("id"),
+                automation_id: row.get("automation_id"),
+                event: serde_json::from_str(&row.get::<String, _>("event"))?,
+                success: row.get("success"),
+                output: row.get("output"),
+                executed_at: DateTime::parse_from_rfc3339(&row.get::<String, _>("executed_at"))?.with_timezone(&Utc),
+            };
+            runs.push(run);
+        }
+
+        Ok(runs)
+    }
+
+    // Bundle operations
+    pub async fn install_bundle(&self, manifest: &BundleManifest, signer_key: Option<String>, trusted: bool) -> AppResult<InstalledBundle> {
+        let now = Utc::now();
+        let mut tx = self.pool.begin().await?;
+
+        // Reinstalling a bundle replaces its previous contents
+        sqlx::query("DELETE FROM bundles WHERE id = ?")
+            .bind(&manifest.id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO bundles (id, name, version, author, description, signer_key, trusted, installed_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            "#
+        )
+        .bind(&manifest.id)
+        .bind(&manifest.name)
+        .bind(&manifest.version)
+        .bind(&manifest.author)
+        .bind(&manifest.description)
+        .bind(&signer_key)
+        .bind(trusted)
+        .bind(&now.to_rfc3339())
+        .execute(&mut *tx)
+        .await?;
+
+        for template in &manifest.templates {
+            sqlx::query(
+                r#"
+                INSERT INTO templates (id, bundle_id, name, description, title, content, tags, created_at)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+                "#
+            )
+            .bind(Uuid::new_v4().to_string())
+            .bind(&manifest.id)
+            .bind(&template.name)
+            .bind(&template.description)
+            .bind(&template.title)
+            .bind(&template.content)
+            .bind(&serde_json::to_string(&template.tags)?)
+            .bind(&now.to_rfc3339())
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        for snippet in &manifest.snippets {
+            sqlx::query(
+                r#"
+                INSERT INTO snippets (id, bundle_id, name, trigger, content, created_at)
+                VALUES (?, ?, ?, ?, ?, ?)
+                "#
+            )
+            .bind(Uuid::new_v4().to_string())
+            .bind(&manifest.id)
+            .bind(&snippet.name)
+            .bind(&snippet.trigger)
+            .bind(&snippet.content)
+            .bind(&now.to_rfc3339())
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        for prompt in &manifest.prompts {
+            sqlx::query(
+                r#"
+                INSERT INTO prompts (id, bundle_id, name, description, prompt, created_at)
+                VALUES (?, ?, ?, ?, ?, ?)
+                "#
+            )
+            .bind(Uuid::new_v4().to_string())
+            .bind(&manifest.id)
+            .bind(&prompt.name)
+            .bind(&prompt.description)
+            .bind(&prompt.prompt)
+            .bind(&now.to_rfc3339())
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(InstalledBundle {
+            id: manifest.id.clone(),
+            name: manifest.name.clone(),
+            version: manifest.version.clone(),
+            author: manifest.author.clone(),
+            description: manifest.description.clone(),
+            signer_key,
+            trusted,
+            template_count: manifest.templates.len() as u32,
+            snippet_count: manifest.snippets.len() as u32,
+            prompt_count: manifest.prompts.len() as u32,
+            installed_at: now,
+        })
+    }
+
+    pub async fn uninstall_bundle(&self, id: &str) -> AppResult<()> {
+        let result = sqlx::query("DELETE FROM bundles WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound(format!("Bundle with id {} not found", id)));
+        }
+        Ok(())
+    }
+
+    pub async fn get_bundles(&self) -> AppResult<Vec<InstalledBundle>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT b.id, b.name, b.version, b.author, b.description, b.signer_key, b.trusted, b.installed_at,
+                (SELECT COUNT(*) FROM templates WHERE bundle_id = b.id) AS template_count,
+                (SELECT COUNT(*) FROM snippets WHERE bundle_id = b.id) AS snippet_count,
+                (SELECT COUNT(*) FROM prompts WHERE bundle_id = b.id) AS prompt_count
+            FROM bundles b
+            ORDER BY b.name ASC
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut bundles = Vec::new();
+        for row in rows {
+            let bundle = InstalledBundle {
+                id: row.get("id"),
+                name: row.get("name"),
+                version: row.get("version"),
+                author: row.get("author"),
+                description: row.get("description"),
+                signer_key: row.get("signer_key"),
+                trusted: row.get("trusted"),
+                template_count: row.get::<i64, _>("template_count") as u32,
+                snippet_count: row.get::<i64, _>("snippet_count") as u32,
+                prompt_count: row.get::<i64, _>("prompt_count") as u32,
+                installed_at: DateTime::parse_from_rfc3339(&row.get::<String, _>("installed_at"))?.with_timezone(&Utc),
+            };
+            bundles.push(bundle);
+        }
+
+        Ok(bundles)
+    }
+
+    pub async fn get_templates(&self) -> AppResult<Vec<Template>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, bundle_id, name, description, title, content, tags, created_at
+            FROM templates
+            ORDER BY name ASC
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut templates = Vec::new();
+        for row in rows {
+            let template = Template {
+                id: row.get("id"),
+                bundle_id: row.get("bundle_id"),
+                name: row.get("name"),
+                description: row.get("description"),
+                title: row.get("title"),
+                content: row.get("content"),
+                tags: serde_json::from_str(&row.get::<String, _>("tags"))?,
+                created_at: DateTime::parse_from_rfc3339(&row.get::<String, _>("created_at"))?.with_timezone(&Utc),
+            };
+            templates.push(template);
+        }
+
+        Ok(templates)
+    }
+
+    pub async fn get_snippets(&self) -> AppResult<Vec<Snippet>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, bundle_id, name, trigger, content, created_at
+            FROM snippets
+            ORDER BY name ASC
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut snippets = Vec::new();
+        for row in rows {
+            let snippet = Snippet {
+                id: row.get("id"),
+                bundle_id: row.get("bundle_id"),
+                name: row.get("name"),
+                trigger: row.get("trigger"),
+                content: row.get("content"),
+                created_at: DateTime::parse_from_rfc3339(&row.get::<String, _>("created_at"))?.with_timezone(&Utc),
+            };
+            snippets.push(snippet);
+        }
+
+        Ok(snippets)
+    }
+
+    pub async fn get_prompts(&self) -> AppResult<Vec<PromptTemplate>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, bundle_id, name, description, prompt, created_at
+            FROM prompts
+            ORDER BY name ASC
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut prompts = Vec::new();
+        for row in rows {
+            let prompt = PromptTemplate {
+                id: row.get("id"),
+                bundle_id: row.get("bundle_id"),
+                name: row.get("name"),
+                description: row.get("description"),
+                prompt: row.get("prompt"),
+                created_at: DateTime::parse_from_rfc3339(&row.get::<String, _>("created_at"))?.with_timezone(&Utc),
+            };
+            prompts.push(prompt);
+        }
+
+        Ok(prompts)
+    }
+
+    // Language operations
+    pub async fn get_language_settings(&self, page_id: &str) -> AppResult<LanguageSettings> {
+        let page = self.get_page(page_id).await?
+            .ok_or_else(|| AppError::NotFound(format!("Page with id {} not found", page_id)))?;
+        let notebook = self.get_notebook(&page.notebook_id).await?;
+
+        Ok(LanguageSettings::resolve(
+            Some(&page.metadata),
+            notebook.as_ref().map(|n| &n.metadata),
+        ))
+    }
+
+    pub async fn get_notebook_language_settings(&self, notebook_id: &str) -> AppResult<LanguageSettings> {
+        let notebook = self.get_notebook(notebook_id).await?
+            .ok_or_else(|| AppError::NotFound(format!("Notebook with id {} not found", notebook_id)))?;
+
+        Ok(LanguageSettings::resolve(None, Some(&notebook.metadata)))
+    }
+
+    // Media operations
+    pub async fn upload_media(&self, request: UploadMediaRequest) -> AppResult<MediaAttachment> {
+        if request.page_id.is_none() && request.note_id.is_none() {
+            return Err(AppError::InvalidOperation("Media must be attached to a page or note".to_string()));
+        }
+        storage::check_file_size(&request.filename, request.file_data.len() as u64, self.max_file_size)?;
+
+        let mut file_data = request.file_data;
+        let mut mime_type = request.mime_type;
+        let mut warning = None;
+        if mime_type.starts_with("image/") && self.get_security_config().await?.strip_image_metadata {
+            match exif::strip_metadata(&file_data) {
+                Stripped::Removed(stripped) => file_data = stripped,
+                Stripped::Clean => {}
+                Stripped::Unsupported => {
+                    // Converting is the only way to be sure nothing identifying is left
+                    let upload = file_data.clone();
+                    let quality = self.image_config.jpeg_quality;
+                    let (converted, converted_type) = tokio::task::spawn_blocking(move || image_compression::reencode(&upload, quality))
+                        .await
+                        .map_err(|e| AppError::Unknown(format!("Image conversion task failed: {}", e)))?
+                        .map_err(|e| AppError::InvalidFormat(format!(
+                            "Metadata can't be removed from {}; convert it to JPEG or PNG, or turn off metadata stripping ({})",
+                            request.filename, e
+                        )))?;
+                    tracing::warn!("Converted {} from {} to {} to remove its metadata", request.filename, mime_type, converted_type);
+                    warning = Some(format!("Converted from {} to {} to remove its metadata", mime_type, converted_type));
+                    file_data = converted;
+                    mime_type = converted_type.to_string();
+                }
+            }
+        }
+
+        let config = self.image_config.clone();
+        let upload_type = mime_type.clone();
+        let upload = file_data.clone();
+        let compressed = tokio::task::spawn_blocking(move || image_compression::compress(&upload, &upload_type, &config))
+            .await
+            .map_err(|e| AppError::Unknown(format!("Image compression task failed: {}", e)))?
+            .unwrap_or_else(|e| {
+                // Stored as uploaded rather than refusing a file the decoder doesn't like
+                tracing::warn!("Storing {} uncompressed: {}", request.filename, e);
+                None
+            });
+        let (original, dimensions) = match compressed {
+            Some(compressed) => {
+                let original = std::mem::replace(&mut file_data, compressed.data);
+                (self.image_config.keep_original.then_some(original), Some((compressed.width, compressed.height)))
+            }
+            None => (None, None),
+        };
+
+        let mut media = MediaAttachment::new(
+            request.page_id,
+            request.note_id,
+            request.filename,
+            mime_type,
+            file_data,
+        );
+        media.position_in_content = request.position_in_content;
+        media.metadata.warning = warning;
+        if let Some((width, height)) = dimensions {
+            media.metadata.width = Some(width);
+            media.metadata.height = Some(height);
+        }
+        media.metadata.original_size = original.as_ref().map(|data| data.len() as u64);
+        if media.mime_type.starts_with("image/") {
+            let data = media.file_data.clone();
+            media.thumbnail_data = tokio::task::spawn_blocking(move || image_compression::thumbnail(&data))
+                .await
+                .map_err(|e| AppError::Unknown(format!("Thumbnail task failed: {}", e)))?
+                .map_err(|e| tracing::debug!("No thumbnail for {}: {}", media.original_filename, e))
+                .ok();
+        }
+
+        if let Some(ref page_id) = media.page_id {
+            self.check_page_quota(page_id, media.file_size + media.metadata.original_size.unwrap_or(0)).await?;
+        }
+
+        let (encrypted_data, encrypted_original, encrypted_thumbnail) = if let Some(ref enc) = self.encryption_manager {
+            (
+                enc.encrypt(&media.file_data)?,
+                original.map(|data| enc.encrypt(&data)).transpose()?,
+                media.thumbnail_data.as_ref().map(|data| enc.encrypt(data)).transpose()?,
+            )
+        } else {
+            (media.file_data.clone(), original, media.thumbnail_data.clone())
+        };
+
+        sqlx::query(
+            r#"
+            INSERT INTO media_attachments (id, page_id, note_id, filename, original_filename, mime_type, file_size, file_data, original_data, thumbnail_data, position_in_content, created_at, metadata)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#
+        )
+        .bind(&media.id)
+        .bind(&media.page_id)
+        .bind(&media.note_id)
+        .bind(&media.filename)
+        .bind(&media.original_filename)
+        .bind(&media.mime_type)
+        .bind(media.file_size as i64)
+        .bind(&encrypted_data)
+        .bind(&encrypted_original)
+        .bind(&encrypted_thumbnail)
+        .bind(media.position_in_content.map(|p| p as i64))
+        .bind(&media.created_at.to_rfc3339())
+        .bind(&serde_json::to_string(&media.metadata)?)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(media)
+    }
+
+    /// Set from `AppConfig::max_file_size`; uploads over it are rejected.
+    pub fn set_max_file_size(&mut self, max_file_size: u64) {
+        self.max_file_size = max_file_size;
+    }
+
+    /// Rejects storing `adding` more bytes on a page whose notebook would go over its quota.
+    async fn check_page_quota(&self, page_id: &str, adding: u64) -> AppResult<()> {
+        let page = self.get_page(page_id).await?
+            .ok_or_else(|| AppError::NotFound(format!("Page with id {} not found", page_id)))?;
+        let notebook = self.get_notebook(&page.notebook_id).await?
+            .ok_or_else(|| AppError::NotFound(format!("Notebook with id {} not found", page.notebook_id)))?;
+        if notebook.metadata.storage_quota.is_some() {
+            let used = self.get_notebook_storage_bytes(&notebook.id).await?;
+            storage::check_quota(&notebook.title, used, adding, notebook.metadata.storage_quota)?;
+        }
+        Ok(())
+    }
+
+    /// Bytes of attachments, kept originals and voice recordings on a notebook's pages.
+    pub async fn get_notebook_storage_bytes(&self, notebook_id: &str) -> AppResult<u64> {
+        let bytes: i64 = sqlx::query_scalar(
+            r#"
+            SELECT
+                (SELECT COALESCE(SUM(m.file_size + COALESCE(length(m.original_data), 0)), 0)
+                 FROM media_attachments m JOIN pages p ON p.id = m.page_id WHERE p.notebook_id = ?1)
+              + (SELECT COALESCE(SUM(length(v.audio_data)), 0)
+                 FROM voice_annotations v JOIN pages p ON p.id = v.page_id WHERE p.notebook_id = ?1)
+            "#
+        )
+        .bind(notebook_id)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(bytes as u64)
+    }
+
+    /// `None` removes the quota. Existing files stay even if they're already over it.
+    pub async fn set_notebook_quota(&self, notebook_id: &str, quota: Option<u64>) -> AppResult<()> {
+        let result = sqlx::query("UPDATE notebooks SET metadata = json_set(metadata, '$.storage_quota', json(?)) WHERE id = ?")
+            .bind(quota.map_or_else(|| "null".to_string(), |q| q.to_string()))
+            .bind(notebook_id)
+            .execute(&self.pool)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound(format!("Notebook with id {} not found", notebook_id)));
+        }
+        Ok(())
+    }
+
+    /// Attachment and recording bytes per notebook and per kind of media, largest first.
+    pub async fn get_storage_usage(&self) -> AppResult<StorageUsage> {
+        let mut by_notebook = Vec::new();
+        for notebook in self.get_notebooks().await? {
+            by_notebook.push(NotebookStorage {
+                bytes: self.get_notebook_storage_bytes(&notebook.id).await?,
+                quota: notebook.metadata.storage_quota,
+                notebook_id: notebook.id,
+                title: notebook.title,
+            });
+        }
+        by_notebook.sort_by(|a, b| b.bytes.cmp(&a.bytes));
+
+        let mut by_media_type: Vec<MediaTypeStorage> = Vec::new();
+        let rows = sqlx::query(
+            "SELECT mime_type, COUNT(*) AS count, SUM(file_size + COALESCE(length(original_data), 0)) AS bytes FROM media_attachments GROUP BY mime_type"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        for row in &rows {
+            let mime_type: String = row.get("mime_type");
+            let media_type = storage::media_type(&mime_type).to_string();
+            let (count, bytes) = (row.get::<i64, _>("count") as u64, row.get::<i64, _>("bytes") as u64);
+            match by_media_type.iter_mut().find(|usage| usage.media_type == media_type) {
+                Some(usage) => {
+                    usage.count += count;
+                    usage.bytes += bytes;
+                }
+                None => by_media_type.push(MediaTypeStorage { media_type, count, bytes }),
+            }
+        }
+        let voice = sqlx::query("SELECT COUNT(*) AS count, COALESCE(SUM(length(audio_data)), 0) AS bytes FROM voice_annotations")
+            .fetch_one(&self.pool)
+            .await?;
+        let voice_count = voice.get::<i64, _>("count") as u64;
+        if voice_count > 0 {
+            by_media_type.push(MediaTypeStorage { media_type: "voice".to_string(), count: voice_count, bytes: voice.get::<i64, _>("bytes") as u64 });
+        }
+        by_media_type.sort_by(|a, b| b.bytes.cmp(&a.bytes));
+
+        let unfiled_bytes: i64 = sqlx::query_scalar(
+            r#"
+            SELECT (SELECT COALESCE(SUM(file_size + COALESCE(length(original_data), 0)), 0) FROM media_attachments WHERE page_id IS NULL)
+                 + (SELECT COALESCE(SUM(length(audio_data)), 0) FROM voice_annotations WHERE page_id IS NULL)
+            "#
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(StorageUsage {
+            total_bytes: by_media_type.iter().map(|usage| usage.bytes).sum(),
+            by_notebook,
+            by_media_type,
+            unfiled_bytes: unfiled_bytes as u64,
+        })
+    }
+
+    /// Set from `AppConfig::images`; only affects later uploads.
+    pub fn set_image_config(&mut self, config: ImageConfig) {
+        self.image_config = config;
+    }
+
+    /// The upload as it was before compression, if `keep_original` was on at the time.
+    pub async fn get_media_original(&self, id: &str) -> AppResult<Option<Vec<u8>>> {
+        let data: Option<Option<Vec<u8>>> = sqlx::query_scalar("SELECT original_data FROM media_attachments WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+        match (data.flatten(), &self.encryption_manager) {
+            (Some(data), Some(enc)) => Ok(Some(enc.decrypt(&data)?)),
+            (data, _) => Ok(data),
+        }
+    }
+
+    pub async fn get_image_attachment_ids(&self) -> AppResult<Vec<String>> {
+        let ids = sqlx::query_scalar("SELECT id FROM media_attachments WHERE mime_type LIKE 'image/%' ORDER BY created_at")
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(ids)
+    }
+
+    pub async fn get_media_attachment(&self, id: &str) -> AppResult<Option<MediaAttachment>> {
+        let row = sqlx::query(
+            r#"
+            SELECT id, page_id, note_id, filename, original_filename, mime_type, file_size, file_data, thumbnail_data, position_in_content, created_at, metadata
+            FROM media_attachments
+            WHERE id = ?
+            "#
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        if let Some(row) = row {
+            let file_data: Vec<u8> = row.get("file_data");
+            let thumbnail_data: Option<Vec<u8>> = row.get("thumbnail_data");
+            let (decrypted_data, thumbnail_data) = if let Some(ref enc) = self.encryption_manager {
+                // A thumbnail that doesn't decrypt, e.g. synced from a device not yet
+                // migrated, is dropped rather than passed on; it can be made again
+                let thumbnail_data = thumbnail_data.and_then(|data| {
+                    enc.decrypt(&data).map_err(|e| tracing::debug!("Dropping unreadable thumbnail: {}", e)).ok()
+                });
+                (enc.decrypt(&file_data)?, thumbnail_data)
+            } else {
+                (file_data, thumbnail_data)
+            };
+
+            let media = MediaAttachment {
+                id: row.get("id"),
+                page_id: row.get("page_id"),
+                note_id: row.get("note_id"),
+                filename: row.get("filename"),
+                original_filename: row.get("original_filename"),
+                mime_type: row.get("mime_type"),
+                file_size: row.get::<i64, _>("file_size") as u64,
+                file_data: decrypted_data,
+                thumbnail_data,
+                position_in_content: row.get::<Option<i64>, _>("position_in_content").map(|p| p as u32),
+                created_at: DateTime::parse_from_rfc3339(&row.get::<String, _>("created_at"))?.with_timezone(&Utc),
+                metadata: serde_json::from_str(&row.get::<String, _>("metadata"))?,
+            };
+            Ok(Some(media))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// MIME type and size of an attachment, without loading it.
+    pub async fn get_media_size(&self, id: &str) -> AppResult<Option<(String, u64)>> {
+        let row = sqlx::query("SELECT mime_type, file_size FROM media_attachments WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.map(|row| (row.get("mime_type"), row.get::<i64, _>("file_size") as u64)))
+    }
+
+    /// Up to `length` bytes of an attachment from `start`. SQLite slices the blob itself, so
+    /// only the range leaves the database. Encrypted attachments can only be decrypted whole,
+    /// through `get_media_attachment`.
+    pub async fn get_media_range(&self, id: &str, start: u64, length: u64) -> AppResult<Option<Vec<u8>>> {
+        if self.encryption_manager.is_some() {
+            return Err(AppError::InvalidOperation("Encrypted attachments can't be read a range at a time".to_string()));
+        }
+
+        let data = sqlx::query_scalar::<_, Option<Vec<u8>>>("SELECT substr(file_data, ?, ?) FROM media_attachments WHERE id = ?")
+            .bind(start.min(i64::MAX as u64 - 1) as i64 + 1) // substr counts from 1
+            .bind(length.min(i64::MAX as u64) as i64)
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(data.map(Option::unwrap_or_default))
+    }
+
+    pub async fn get_media_metadata(&self, id: &str) -> AppResult<Option<MediaMetadata>> {
+        let row = sqlx::query("SELECT metadata FROM media_attachments WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        if let Some(row) = row {
+            Ok(Some(serde_json::from_str(&row.get::<String, _>("metadata"))?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub async fn update_media_metadata(&self, id: &str, metadata: &MediaMetadata) -> AppResult<()> {
+        let result = sqlx::query("UPDATE media_attachments SET metadata = ? WHERE id = ?")
+            .bind(&serde_json::to_string(metadata)?)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound(format!("Media attachment with id {} not found", id)));
+        }
+        Ok(())
+    }
+
+    // Snapshot operations
+    /// Every row of `table` exactly as stored, in column order. Encrypted content stays
+    /// encrypted. `table` must come from a fixed list, never from user input.
+    pub async fn get_raw_rows(&self, table: &str) -> AppResult<Vec<Vec<(String, StoredValue)>>> {
+        let rows = sqlx::query(&format!("SELECT * FROM {}", table))
+            .fetch_all(&self.pool)
+            .await?;
+
+        rows.iter().map(raw_row).collect()
+    }
+
+    /// Replaces the contents of each table with the given rows in one transaction. Tables
+    /// are listed parents first; foreign keys are only checked at commit.
+    pub async fn replace_raw_rows(&self, tables: &[(&str, Vec<Vec<(String, StoredValue)>>)]) -> AppResult<()> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("PRAGMA defer_foreign_keys = ON").execute(&mut *tx).await?;
+
+        for (table, _) in tables.iter().rev() {
+            sqlx::query(&format!("DELETE FROM {}", table)).execute(&mut *tx).await?;
+        }
+        for (table, rows) in tables {
+            for row in rows {
+                insert_raw_row(&mut tx, table, row).await?;
+            }
+        }
+        tx.commit().await?;
+
+        if self.vector_index {
+            // Restored vectors may differ under the same ids, so rebuild rather than patch
+            sqlx::query("DELETE FROM vec_embeddings").execute(&self.pool).await.ok();
+            self.backfill_vector_table().await?;
+        }
+        Ok(())
+    }
+
+    // Sync operations
+    /// Documents the next push sends: queued changes, then rows that were never synced,
+    /// such as those written before the queue existed.
+    pub async fn get_sync_changes(&self) -> AppResult<Vec<SyncChange>> {
+        let mut sql = "SELECT seq, doc_id, kind, deleted FROM sync_queue".to_string();
+        for table in SYNC_TABLES {
+            let doc_id = table.doc_id_sql("");
+            sql.push_str(&format!(
+                " UNION ALL SELECT NULL, {doc_id}, '{}', 0 FROM {} \
+                  WHERE {doc_id} NOT IN (SELECT doc_id FROM sync_revisions) AND {doc_id} NOT IN (SELECT doc_id FROM sync_queue)",
+                table.kind.as_str(),
+                table.name
+            ));
+        }
+        let rows = sqlx::query(&sql).fetch_all(&self.pool).await?;
+
+        let mut changes = Vec::with_capacity(rows.len());
+        for row in rows {
+            let kind: String = row.try_get(2)?;
+            let kind = SyncDocumentKind::parse(&kind)
+                .ok_or_else(|| AppError::InvalidFormat(format!("Unknown sync document kind: {}", kind)))?;
+            changes.push(SyncChange {
+                seq: row.try_get(0)?,
+                doc_id: row.try_get(1)?,
+                kind,
+                deleted: row.try_get::<i64, _>(3)? != 0,
+            });
+        }
+        Ok(changes)
+    }
+
+    /// A synced document's row exactly as stored, `None` once it's been deleted.
+    pub async fn get_sync_row(&self, kind: SyncDocumentKind, id: &str) -> AppResult<Option<Vec<(String, StoredValue)>>> {
+        let table = sync_table(kind);
+        let row = sqlx::query(&format!("SELECT * FROM {} WHERE {} = ?", table.name, table.doc_id_sql("")))
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+        row.as_ref().map(raw_row).transpose()
+    }
+
+    pub async fn get_sync_revision(&self, doc_id: &str) -> AppResult<Option<String>> {
+        let rev = sqlx::query_scalar("SELECT rev FROM sync_revisions WHERE doc_id = ?")
+            .bind(doc_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(rev)
+    }
+
+    /// Records `rev` as pushed, or just drops the change without one. The queue entry stays
+    /// if the document changed again since `change` was read.
+    pub async fn mark_synced(&self, change: &SyncChange, rev: Option<&str>) -> AppResult<()> {
+        let mut tx = self.pool.begin().await?;
+        if let Some(rev) = rev {
+            set_sync_revision(&mut tx, &change.doc_id, change.kind, rev).await?;
+        }
+        if let Some(seq) = change.seq {
+            sqlx::query("DELETE FROM sync_queue WHERE doc_id = ? AND seq = ?")
+                .bind(&change.doc_id)
+                .bind(seq)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Whether any synced document exists, i.e. whether content was written with this
+    /// device's key.
+    pub async fn has_synced_content(&self) -> AppResult<bool> {
+        for table in SYNC_TABLES {
+            let exists: bool = sqlx::query_scalar(&format!("SELECT EXISTS (SELECT 1 FROM {})", table.name))
+                .fetch_one(&self.pool)
+                .await?;
+            if exists {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Writes pulled documents in one transaction: rows are upserted parents first, then
+    /// deletions run children first. They're recorded as synced at their revision rather
+    /// than queued to be pushed back. Columns this schema doesn't have are dropped, so a
+    /// device on a newer version can still sync with this one. A row the database rejects
+    /// is rolled back on its own and returned, so the rest still apply.
+    pub async fn apply_synced_rows(&self, rows: &[SyncedRow]) -> AppResult<Vec<SyncFailure>> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("PRAGMA defer_foreign_keys = ON").execute(&mut *tx).await?;
+        let mut failed = Vec::new();
+
+        for table in SYNC_TABLES {
+            let columns: HashSet<String> = sqlx::query_scalar("SELECT name FROM pragma_table_info(?)")
+                .bind(table.name)
+                .fetch_all(&mut *tx)
+                .await?
+                .into_iter()
+                .collect();
+            for synced in rows.iter().filter(|synced| synced.kind == table.kind) {
+                if let Some(row) = &synced.row {
+                    let row: Vec<(String, StoredValue)> = row.iter().filter(|(name, _)| columns.contains(name)).cloned().collect();
+                    sqlx::query("SAVEPOINT synced_row").execute(&mut *tx).await?;
+                    let result = upsert_raw_row(&mut tx, table, &synced.id, &row).await;
+                    release_synced_row(&mut tx, synced, result, &mut failed).await?;
+                }
+            }
+        }
+        for table in SYNC_TABLES.iter().rev() {
+            for synced in rows.iter().filter(|synced| synced.kind == table.kind && synced.row.is_none()) {
+                sqlx::query("SAVEPOINT synced_row").execute(&mut *tx).await?;
+                let result = sqlx::query(&format!("DELETE FROM {} WHERE {} = ?", table.name, table.doc_id_sql("")))
+                    .bind(&synced.id)
+                    .execute(&mut *tx)
+                    .await
+                    .map(|_| ())
+                    .map_err(AppError::from);
+                release_synced_row(&mut tx, synced, result, &mut failed).await?;
+            }
+        }
+        for synced in rows {
+            if failed.iter().any(|failure: &SyncFailure| failure.id == synced.id && failure.kind == synced.kind) {
+                continue;
+            }
+            set_sync_revision(&mut tx, &synced.id, synced.kind, &synced.rev).await?;
+            sqlx::query("DELETE FROM sync_queue WHERE doc_id = ?")
+                .bind(&synced.id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(failed)
+    }
+
+    // Trash operations
+    /// Most recently deleted first.
+    pub async fn list_trash(&self) -> AppResult<Vec<TrashItem>> {
+        let rows = sqlx::query("SELECT id, item_type, title, notebook_id, page_count, deleted_at FROM trash ORDER BY deleted_at DESC")
+            .fetch_all(&self.pool)
+            .await?;
+        rows.iter().map(trash_item_from_row).collect()
+    }
+
+    /// Puts a trashed note or page back with everything trashed along with it. A page whose
+    /// parent page or section has since gone is restored to the top of its notebook, and
+    /// links to pages that no longer exist are dropped.
+    pub async fn restore_from_trash(&self, id: &str) -> AppResult<TrashItem> {
+        let mut tx = self.pool.begin().await?;
+        let row = sqlx::query("SELECT * FROM trash WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("No trashed item with id {}", id)))?;
+        let item = trash_item_from_row(&row)?;
+        let payload: Vec<(String, Vec<Vec<(String, StoredValue)>>)> = serde_json::from_str(&row.get::<String, _>("payload"))?;
+
+        if let Some(notebook_id) = &item.notebook_id {
+            if !row_exists(&mut tx, "notebooks", notebook_id).await? {
+                return Err(AppError::InvalidOperation(format!("The notebook of '{}' no longer exists", item.title)));
+            }
+        }
+
+        let restored_pages: HashSet<String> = payload
+            .iter()
+            .filter(|(table, _)| table == "pages")
+            .flat_map(|(_, rows)| rows.iter().filter_map(|row| raw_text(row, "id").map(str::to_string)))
+            .collect();
+        sqlx::query("PRAGMA defer_foreign_keys = ON").execute(&mut *tx).await?;
+        for (table, rows) in payload {
+            for mut row in rows {
+                match table.as_str() {
+                    "pages" => {
+                        let parent = raw_text(&row, "parent_page_id").map(str::to_string);
+                        if parent.is_some() && !page_present(&mut tx, &restored_pages, parent.as_deref()).await? {
+                            set_raw_value(&mut row, "parent_page_id", StoredValue::Null);
+                        }
+                        if let Some(section_id) = raw_text(&row, "section_id").map(str::to_string) {
+                            if !row_exists(&mut tx, "sections", &section_id).await? {
+                                set_raw_value(&mut row, "section_id", StoredValue::Null);
+                            }
+                        }
+                    }
+                    "page_links" => {
+                        let source = raw_text(&row, "source_page_id").map(str::to_string);
+                        let target = raw_text(&row, "target_page_id").map(str::to_string);
+                        if !page_present(&mut tx, &restored_pages, source.as_deref()).await?
+                            || !page_present(&mut tx, &restored_pages, target.as_deref()).await?
+                        {
+                            continue;
+                        }
+                    }
+                    _ => {}
+                }
+                insert_raw_row(&mut tx, &table, &row).await?;
+            }
+        }
+        sqlx::query("DELETE FROM trash WHERE id = ?").bind(id).execute(&mut *tx).await?;
+        tx.commit().await?;
+
+        if self.vector_index {
+            self.backfill_vector_table().await?;
+        }
+        Ok(item)
+    }
+
+    /// Permanently deletes everything in the trash; returns how many items were removed.
+    pub async fn empty_trash(&self) -> AppResult<u64> {
+        let result = sqlx::query("DELETE FROM trash").execute(&self.pool).await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Permanently deletes items trashed before `before`.
+    pub async fn purge_trash(&self, before: DateTime<Utc>) -> AppResult<u64> {
+        let result = sqlx::query("DELETE FROM trash WHERE deleted_at < ?")
+            .bind(&before.to_rfc3339())
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    // AI job operations
+    pub async fn insert_ai_job(&self, job: &AiJob) -> AppResult<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO ai_jobs (id, payload, priority, status, attempts, error, run_after, created_at, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#
+        )
+        .bind(&job.id)
+        .bind(&serde_json::to_string(&job.kind)?)
+        .bind(job.priority.as_i64())
+        .bind(job.status.as_str())
+        .bind(job.attempts as i64)
+        .bind(&job.error)
+        .bind(&job.run_after.to_rfc3339())
+        .bind(&job.created_at.to_rfc3339())
+        .bind(&job.updated_at.to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// A job for `kind` still waiting to run, so the same work isn't queued twice.
+    pub async fn find_queued_ai_job(&self, kind: &AiJobKind) -> AppResult<Option<AiJob>> {
+        let row = sqlx::query("SELECT * FROM ai_jobs WHERE status = 'queued' AND payload = ? LIMIT 1")
+            .bind(&serde_json::to_string(kind)?)
+            .fetch_optional(&self.pool)
+            .await?;
+        row.map(|row| ai_job_from_row(&row)).transpose()
+    }
+
+    pub async fn get_ai_job(&self, id: &str) -> AppResult<Option<AiJob>> {
+        let row = sqlx::query("SELECT * FROM ai_jobs WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+        row.map(|row| ai_job_from_row(&row)).transpose()
+    }
+
+    /// Most recently updated first.
+    pub async fn list_ai_jobs(&self, status: Option<AiJobStatus>, limit: usize) -> AppResult<Vec<AiJob>> {
+        let rows = match status {
+            Some(status) => {
+                sqlx::query("SELECT * FROM ai_jobs WHERE status = ? ORDER BY updated_at DESC LIMIT ?")
+                    .bind(status.as_str())
+                    .bind(limit as i64)
+                    .fetch_all(&self.pool)
+                    .await?
+            }
+            None => {
+                sqlx::query("SELECT * FROM ai_jobs ORDER BY updated_at DESC LIMIT ?")
+                    .bind(limit as i64)
+                    .fetch_all(&self.pool)
+                    .await?
+            }
+        };
+        rows.iter().map(ai_job_from_row).collect()
+    }
+
+    /// Marks the next due job running, highest priority first, then oldest, and counts the
+    /// attempt.
+    pub async fn claim_next_ai_job(&self) -> AppResult<Option<AiJob>> {
+        let now = Utc::now().to_rfc3339();
+        let row = sqlx::query(
+            r#"
+            UPDATE ai_jobs SET status = 'running', attempts = attempts + 1, updated_at = ?
+            WHERE id = (
+                SELECT id FROM ai_jobs
+                WHERE status = 'queued' AND run_after <= ?
+                ORDER BY priority DESC, created_at ASC
+                LIMIT 1
+            )
+            RETURNING *
+            "#
+        )
+        .bind(&now)
+        .bind(&now)
+        .fetch_optional(&self.pool)
+        .await?;
+        row.map(|row| ai_job_from_row(&row)).transpose()
+    }
+
+    /// Moves a job to `status`. Only jobs in one of `from` change, so a job can't be
+    /// finished after it was cancelled; returns whether it changed.
+    pub async fn set_ai_job_status(&self, id: &str, from: &[AiJobStatus], status: AiJobStatus, error: Option<&str>, run_after: Option<DateTime<Utc>>) -> AppResult<bool> {
+        let from: Vec<String> = from.iter().map(|s| format!("'{}'", s.as_str())).collect();
+        let sql = format!(
+            "UPDATE ai_jobs SET status = ?, error = COALESCE(?, error), run_after = COALESCE(?, run_after), updated_at = ? WHERE id = ? AND status IN ({})",
+            from.join(", ")
+        );
+        let result = sqlx::query(&sql)
+            .bind(status.as_str())
+            .bind(error)
+            .bind(run_after.map(|at| at.to_rfc3339()))
+            .bind(&Utc::now().to_rfc3339())
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Queued and running AI jobs.
+    pub async fn count_pending_ai_jobs(&self) -> AppResult<usize> {
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM ai_jobs WHERE status IN ('queued', 'running')")
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(count as usize)
+    }
+
+    /// Jobs left running by a previous session go back in the queue.
+    pub async fn requeue_running_ai_jobs(&self) -> AppResult<u64> {
+        let result = sqlx::query("UPDATE ai_jobs SET status = 'queued', updated_at = ? WHERE status = 'running'")
+            .bind(&Utc::now().to_rfc3339())
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Deletes completed and cancelled jobs last updated before `before`.
+    pub async fn prune_ai_jobs(&self, before: DateTime<Utc>) -> AppResult<u64> {
+        let result = sqlx::query("DELETE FROM ai_jobs WHERE status IN ('completed', 'cancelled') AND updated_at < ?")
+            .bind(&before.to_rfc3339())
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    // Page revision operations
+    /// Rewrites titles and content of several pages in one transaction, saving each page's
+    /// previous version as a revision first. `edits` are (original page, new title, new content).
+    pub async fn apply_page_edits(&self, edits: &[(Page, String, String)], reason: &str) -> AppResult<()> {
+        let now = Utc::now().to_rfc3339();
+        let encrypt = |content: &str| -> AppResult<String> {
+            match self.encryption_manager {
+                Some(ref enc) => enc.encrypt_string(content),
+                None => Ok(content.to_string()),
+            }
+        };
+
+        let mut tx = self.pool.begin().await?;
+        for (page, title, content) in edits {
+            sqlx::query(
+                r#"
+                INSERT INTO page_revisions (id, page_id, title, content, reason, created_at)
+                VALUES (?, ?, ?, ?, ?, ?)
+                "#
+            )
+            .bind(&Uuid::new_v4().to_string())
+            .bind(&page.id)
+            .bind(&page.title)
+            .bind(&encrypt(&page.content)?)
+            .bind(reason)
+            .bind(&now)
+            .execute(&mut *tx)
+            .await?;
+
+            let mut metadata = page.metadata.clone();
+            metadata.analyze_content(content);
+            sqlx::query("UPDATE pages SET title = ?, content = ?, metadata = ?, updated_at = ? WHERE id = ?")
+                .bind(title)
+                .bind(&encrypt(content)?)
+                .bind(&serde_json::to_string(&metadata)?)
+                .bind(&now)
+                .bind(&page.id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Newest first.
+    pub async fn get_page_revisions(&self, page_id: &str) -> AppResult<Vec<PageRevision>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, page_id, title, content, reason, created_at
+            FROM page_revisions
+            WHERE page_id = ?
+            ORDER BY created_at DESC
+            "#
+        )
+        .bind(page_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut revisions = Vec::new();
+        for row in rows {
+            let content: String = row.get("content");
+            let decrypted_content = if let Some(ref enc) = self.encryption_manager {
+                enc.decrypt_string(&content)?
+            } else {
+                content
+            };
+            revisions.push(PageRevision {
+                id: row.get("id"),
+                page_id: row.get("page_id"),
+                title: row.get("title"),
+                content: decrypted_content,
+                reason: row.get("reason"),
+                created_at: DateTime::parse_from_rfc3339(&row.get::<String, _>("created_at"))?.with_timezone(&Utc),
+            });
+        }
+
+        Ok(revisions)
+    }
+
+    // Export history operations
+    /// Records an export, replacing earlier exports of the page in the same format to the
+    /// same destination: only the latest is needed to regenerate a mirror.
+    pub async fn record_export(
+        &self,
+        page_id: &str,
+        format: &ExportFormat,
+        locale: Option<&str>,
+        destination: &Path,
+        path: &Path,
+    ) -> AppResult<ExportRecord> {
+        let record = ExportRecord {
+            id: Uuid::new_v4().to_string(),
+            page_id: page_id.to_string(),
+            format: format.clone(),
+            locale: locale.map(str::to_string),
+            destination: destination.to_path_buf(),
+            path: path.to_path_buf(),
+            exported_at: Utc::now(),
+        };
+
+        let format = serde_json::to_string(&record.format)?;
+        let destination = record.destination.to_string_lossy();
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM export_history WHERE page_id = ? AND destination = ? AND format = ?")
+            .bind(&record.page_id)
+            .bind(destination.as_ref())
+            .bind(&format)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query(
+            r#"
+            INSERT INTO export_history (id, page_id, format, locale, destination, path, exported_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+            "#
+        )
+        .bind(&record.id)
+        .bind(&record.page_id)
+        .bind(&format)
+        .bind(&record.locale)
+        .bind(destination.as_ref())
+        .bind(record.path.to_string_lossy().as_ref())
+        .bind(&record.exported_at.to_rfc3339())
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(record)
+    }
+
+    /// Deletes export records superseded by a later export of the same page and format to
+    /// the same destination, as `record_export` does for new ones.
+    pub async fn prune_export_history(&self) -> AppResult<u64> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM export_history
+            WHERE exported_at < (
+                SELECT MAX(exported_at) FROM export_history latest
+                WHERE latest.page_id = export_history.page_id
+                  AND latest.destination = export_history.destination
+                  AND latest.format = export_history.format
+            )
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Newest first.
+    pub async fn get_page_exports(&self, page_id: &str) -> AppResult<Vec<ExportRecord>> {
+        let rows = sqlx::query("SELECT * FROM export_history WHERE page_id = ? ORDER BY exported_at DESC")
+            .bind(page_id)
+            .fetch_all(&self.pool)
+            .await?;
+        rows.iter().map(export_record_from_row).collect()
+    }
+
+    /// The latest export of each page and format to `destination`.
+    pub async fn get_latest_exports(&self, destination: &Path) -> AppResult<Vec<ExportRecord>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT * FROM export_history h
+            WHERE destination = ? AND exported_at = (
+                SELECT MAX(exported_at) FROM export_history
+                WHERE page_id = h.page_id AND destination = h.destination AND format = h.format
+            )
+            ORDER BY page_id
+            "#
+        )
+        .bind(destination.to_string_lossy().as_ref())
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter().map(export_record_from_row).collect()
+    }
+
+    // Audit log operations
+    pub async fn record_audit_event(&self, action: &str, target: Option<&str>) -> AppResult<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO audit_log (id, action, target, created_at)
+            VALUES (?, ?, ?, ?)
+            "#
+        )
+        .bind(&Uuid::new_v4().to_string())
+        .bind(action)
+        .bind(target)
+        .bind(&Utc::now().to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_audit_log(&self, limit: usize) -> AppResult<Vec<AuditLogEntry>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, action, target, created_at
+            FROM audit_log
+            ORDER BY created_at DESC
+            LIMIT ?
+            "#
+        )
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut entries = Vec::new();
+        for row in rows {
+            entries.push(AuditLogEntry {
+                id: row.get("id"),
+                action: row.get("action"),
+                target: row.get("target"),
+                created_at: DateTime::parse_from_rfc3339(&row.get::<String, _>("created_at"))?.with_timezone(&Utc),
+            });
+        }
+
+        Ok(entries)
+    }
+}
+
+fn raw_row(row: &sqlx::sqlite::SqliteRow) -> AppResult<Vec<(String, StoredValue)>> {
+    let mut values = Vec::with_capacity(row.columns().len());
+    for column in row.columns() {
+        let index = column.ordinal();
+        let raw = row.try_get_raw(index)?;
+        let value = if raw.is_null() {
+            StoredValue::Null
+        } else {
+            match raw.type_info().name() {
+                "INTEGER" | "BOOLEAN" => StoredValue::Integer(row.try_get(index)?),
+                "REAL" => StoredValue::Real(row.try_get(index)?),
+                "BLOB" => StoredValue::Blob(general_purpose::STANDARD.encode(row.try_get::<Vec<u8>, _>(index)?)),
+                _ => StoredValue::Text(row.try_get(index)?),
+            }
+        };
+        values.push((column.name().to_string(), value));
+    }
+    Ok(values)
+}
+
+async fn insert_raw_row(tx: &mut sqlx::SqliteConnection, table: &str, row: &[(String, StoredValue)]) -> AppResult<()> {
+    let columns: Vec<&str> = row.iter().map(|(name, _)| name.as_str()).collect();
+    let placeholders = vec!["?"; columns.len()].join(", ");
+    let sql = format!("INSERT INTO {} ({}) VALUES ({})", table, columns.join(", "), placeholders);
+
+    bind_raw_row(sqlx::query(&sql), row)?.execute(&mut *tx).await?;
+    Ok(())
+}
+
+/// Inserts the row with document id `doc_id`, or updates it in place so rows that reference
+/// it keep existing. Rows with a natural key are matched on it and keep their local `id`;
+/// a local row whose key was changed on the other device is replaced. `row`'s columns must
+/// have been checked against the table.
+async fn upsert_raw_row(tx: &mut sqlx::SqliteConnection, table: &SyncTable, doc_id: &str, row: &[(String, StoredValue)]) -> AppResult<()> {
+    if table.doc_id(row).as_deref() != Some(doc_id) {
+        return Err(AppError::InvalidFormat(format!("Synced row for {} has a missing or different key", doc_id)));
+    }
+    let id = row.iter().find(|(name, _)| name == "id").map(|(_, value)| value);
+    if let (true, Some(StoredValue::Text(id))) = (table.has_natural_key(), id) {
+        sqlx::query(&format!("DELETE FROM {} WHERE id = ? AND {} != ?", table.name, table.doc_id_sql("")))
+            .bind(id)
+            .bind(doc_id)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    let columns: Vec<&str> = row.iter().map(|(name, _)| name.as_str()).collect();
+    let placeholders = vec!["?"; columns.len()].join(", ");
+    let updates: Vec<String> = columns.iter()
+        .filter(|column| !table.key.iter().any(|key| key == *column) && !(table.has_natural_key() && **column == "id"))
+        .map(|column| format!("{} = excluded.{}", column, column))
+        .collect();
+    let conflict = if updates.is_empty() { "DO NOTHING".to_string() } else { format!("DO UPDATE SET {}", updates.join(", ")) };
+    let sql = format!(
+        "INSERT INTO {} ({}) VALUES ({}) ON CONFLICT({}) {}",
+        table.name,
+        columns.join(", "),
+        placeholders,
+        table.key.join(", "),
+        conflict
+    );
+
+    bind_raw_row(sqlx::query(&sql), row)?.execute(&mut *tx).await?;
+    Ok(())
+}
+
+fn bind_raw_row<'q>(
+    mut query: sqlx::query::Query<'q, sqlx::Sqlite, sqlx::sqlite::SqliteArguments<'q>>,
+    row: &[(String, StoredValue)],
+) -> AppResult<sqlx::query::Query<'q, sqlx::Sqlite, sqlx::sqlite::SqliteArguments<'q>>> {
+    for (_, value) in row {
+        query = match value {
+            StoredValue::Null => query.bind(None::<String>),
+            StoredValue::Integer(v) => query.bind(*v),
+            StoredValue::Real(v) => query.bind(*v),
+            StoredValue::Text(v) => query.bind(v.clone()),
+            StoredValue::Blob(v) => query.bind(general_purpose::STANDARD.decode(v)
+                .map_err(|e| AppError::InvalidFormat(format!("Invalid stored blob: {}", e)))?),
+        };
+    }
+    Ok(query)
+}
+
+/// Ends the savepoint a pulled row was written in, rolling the row back if it failed.
+async fn release_synced_row(
+    tx: &mut sqlx::SqliteConnection,
+    synced: &SyncedRow,
+    result: AppResult<()>,
+    failed: &mut Vec<SyncFailure>,
+) -> AppResult<()> {
+    if let Err(e) = result {
+        sqlx::query("ROLLBACK TO synced_row").execute(&mut *tx).await?;
+        failed.push(SyncFailure { id: synced.id.clone(), kind: synced.kind, error: e.to_string() });
+    }
+    sqlx::query("RELEASE synced_row").execute(&mut *tx).await?;
+    Ok(())
+}
+
+async fn set_sync_revision(tx: &mut sqlx::SqliteConnection, doc_id: &str, kind: SyncDocumentKind, rev: &str) -> AppResult<()> {
+    sqlx::query("INSERT INTO sync_revisions (doc_id, kind, rev) VALUES (?, ?, ?) ON CONFLICT(doc_id) DO UPDATE SET rev = excluded.rev")
+        .bind(doc_id)
+        .bind(kind.as_str())
+        .bind(rev)
+        .execute(&mut *tx)
+        .await?;
+    Ok(())
+}
+
+/// Moves the page `id` and its subpages into the trash; returns the ids of every page moved.
+async fn trash_page_tree(tx: &mut sqlx::SqliteConnection, id: &str) -> AppResult<Vec<String>> {
+    let row = sqlx::query("SELECT title, notebook_id FROM pages WHERE id = ?")
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Page with id {} not found", id)))?;
+
+    let ids: Vec<String> = sqlx::query_scalar(
+        r#"
+        WITH RECURSIVE tree(id) AS (
+            SELECT ?
+            UNION SELECT pages.id FROM pages JOIN tree ON pages.parent_page_id = tree.id
+        )
+        SELECT id FROM tree
+        "#
+    )
+    .bind(id)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let mut payload = Vec::new();
+    for (table, condition) in [
+        ("pages", "id IN ({ids})"),
+        ("voice_annotations", "page_id IN ({ids})"),
+        ("media_attachments", "page_id IN ({ids})"),
+        ("page_links", "source_page_id IN ({ids}) OR target_page_id IN ({ids})"),
+        ("dismissed_links", "source_page_id IN ({ids}) OR target_page_id IN ({ids})"),
+        ("page_revisions", "page_id IN ({ids})"),
+        ("export_history", "page_id IN ({ids})"),
+        ("mocs", "page_id IN ({ids})"),
+        ("recent_pages", "page_id IN ({ids})"),
+        ("page_properties", "page_id IN ({ids})"),
+        ("embeddings", "owner_id IN ({ids})"),
+    ] {
+        payload.push((table.to_string(), select_raw_rows(tx, table, condition, &ids).await?));
+    }
+    let notebook_id: String = row.get("notebook_id");
+    insert_trash_row(tx, id, TrashItemType::Page, &row.get::<String, _>("title"), Some(&notebook_id), ids.len(), &payload).await?;
+    sqlx::query("DELETE FROM pages WHERE id = ?").bind(id).execute(&mut *tx).await?;
+    Ok(ids)
+}
+
+/// Rows of `table` matching `condition`, where each `{ids}` stands for the list of `ids`.
+/// `table` and `condition` must be fixed strings, never user input.
+async fn select_raw_rows(tx: &mut sqlx::SqliteConnection, table: &str, condition: &str, ids: &[String]) -> AppResult<Vec<Vec<(String, StoredValue)>>> {
+    let placeholders = vec!["?"; ids.len()].join(", ");
+    let sql = format!("SELECT * FROM {} WHERE {}", table, condition.replace("{ids}", &placeholders));
+    let mut query = sqlx::query(&sql);
+    for _ in 0..condition.matches("{ids}").count() {
+        for id in ids {
+            query = query.bind(id);
+        }
+    }
+    let rows = query.fetch_all(&mut *tx).await?;
+    rows.iter().map(raw_row).collect()
+}
+
+fn raw_text<'a>(row: &'a [(String, StoredValue)], column: &str) -> Option<&'a str> {
+    row.iter().find_map(|(name, value)| match value {
+        StoredValue::Text(text) if name == column => Some(text.as_str()),
+        _ => None,
+    })
+}
+
+fn set_raw_value(row: &mut [(String, StoredValue)], column: &str, value: StoredValue) {
+    if let Some((_, slot)) = row.iter_mut().find(|(name, _)| name == column) {
+        *slot = value;
+    }
+}
+
+async fn row_exists(tx: &mut sqlx::SqliteConnection, table: &str, id: &str) -> AppResult<bool> {
+    let count: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM {} WHERE id = ?", table))
+        .bind(id)
+        .fetch_one(&mut *tx)
+        .await?;
+    Ok(count > 0)
+}
+
+/// Whether a page is being restored alongside, or still exists.
+async fn page_present(tx: &mut sqlx::SqliteConnection, restored: &HashSet<String>, page_id: Option<&str>) -> AppResult<bool> {
+    match page_id {
+        Some(page_id) if restored.contains(page_id) => Ok(true),
+        Some(page_id) => row_exists(tx, "pages", page_id).await,
+        None => Ok(false),
+    }
+}
+
+async fn insert_trash_row(
+    tx: &mut sqlx::SqliteConnection,
+    id: &str,
+    item_type: TrashItemType,
+    title: &str,
+    notebook_id: Option<&str>,
+    page_count: usize,
+    payload: &[(String, Vec<Vec<(String, StoredValue)>>)],
+) -> AppResult<()> {
+    sqlx::query(
+        r#"
+        INSERT OR REPLACE INTO trash (id, item_type, title, notebook_id, page_count, payload, deleted_at)
+        VALUES (?, ?, ?, ?, ?, ?, ?)
+        "#
+    )
+    .bind(id)
+    .bind(item_type.as_str())
+    .bind(title)
+    .bind(notebook_id)
+    .bind(page_count as i64)
+    .bind(&serde_json::to_string(payload)?)
+    .bind(&Utc::now().to_rfc3339())
+    .execute(&mut *tx)
+    .await?;
+    Ok(())
+}
+
+/// Rewrites the `tags` array of every page and note that has one of `names`, where
+/// `rewrite` returns the new list or `None` to leave it. Returns (pages, notes) changed.
+async fn rewrite_tag_lists(
+    tx: &mut sqlx::SqliteConnection,
+    names: &[String],
+    rewrite: impl Fn(&[String]) -> Option<Vec<String>>,
+) -> AppResult<(usize, usize)> {
+    let placeholders = vec!["?"; names.len()].join(", ");
+    let mut updated = [0, 0];
+    for (index, table) in ["pages", "notes"].iter().enumerate() {
+        let sql = format!(
+            "SELECT id, tags FROM {} WHERE EXISTS (SELECT 1 FROM json_each(tags) WHERE json_each.value IN ({}))",
+            table, placeholders
+        );
+        let mut query = sqlx::query(&sql);
+        for name in names {
+            query = query.bind(name);
+        }
+        for row in query.fetch_all(&mut *tx).await? {
+            let current: Vec<String> = serde_json::from_str(&row.get::<String, _>("tags"))?;
+            if let Some(rewritten) = rewrite(&current) {
+                sqlx::query(&format!("UPDATE {} SET tags = ? WHERE id = ?", table))
+                    .bind(&serde_json::to_string(&rewritten)?)
+                    .bind(&row.get::<String, _>("id"))
+                    .execute(&mut *tx)
+                    .await?;
+                updated[index] += 1;
+            }
+        }
+    }
+    Ok((updated[0], updated[1]))
+}
+
+fn page_link_from_row(row: &sqlx::sqlite::SqliteRow) -> AppResult<PageLink> {
+    let link_type: String = row.get("link_type");
+    Ok(PageLink {
+        id: row.get("id"),
+        source_page_id: row.get("source_page_id"),
+        target_page_id: row.get("target_page_id"),
+        link_text: row.get("link_text"),
+        link_type: PageLinkType::parse(&link_type)
+            .ok_or_else(|| AppError::InvalidFormat(format!("Unknown link type: {}", link_type)))?,
+        created_at: DateTime::parse_from_rfc3339(&row.get::<String, _>("created_at"))?.with_timezone(&Utc),
+    })
+}
+
+fn tag_from_row(row: &sqlx::sqlite::SqliteRow) -> AppResult<Tag> {
+    Ok(Tag {
+        id: row.get("id"),
+        name: row.get("name"),
+        color: row.get("color"),
+        description: row.get("description"),
+        usage_count: row.get("usage_count"),
+        created_at: DateTime::parse_from_rfc3339(&row.get::<String, _>("created_at"))?.with_timezone(&Utc),
+        last_used: row.get::<Option<String>, _>("last_used")
+            .map(|s| DateTime::parse_from_rfc3339(&s).unwrap().with_timezone(&Utc)),
+    })
+}
+
+fn property_definition_from_row(row: &sqlx::sqlite::SqliteRow) -> AppResult<PropertyDefinition> {
+    let property_type: String = row.get("property_type");
+    Ok(PropertyDefinition {
+        property_type: PropertyType::parse(&property_type)
+            .ok_or_else(|| AppError::InvalidFormat(format!("Unknown property type: {}", property_type)))?,
+        name: row.get("name"),
+        options: serde_json::from_str(&row.get::<String, _>("options"))?,
+        created_at: DateTime::parse_from_rfc3339(&row.get::<String, _>("created_at"))?.with_timezone(&Utc),
+    })
+}
+
+fn voice_annotation_summary_from_row(row: &sqlx::sqlite::SqliteRow) -> AppResult<VoiceAnnotationSummary> {
+    let metadata: VoiceMetadata = serde_json::from_str(&row.get::<String, _>("metadata"))?;
+    Ok(VoiceAnnotationSummary {
+        id: row.get("id"),
+        note_id: row.get("note_id"),
+        page_id: row.get("page_id"),
+        transcription: row.get("transcription"),
+        timestamp: DateTime::parse_from_rfc3339(&row.get::<String, _>("timestamp"))?.with_timezone(&Utc),
+        duration: row.get("duration"),
+        words: metadata.words(),
+        metadata,
+    })
+}
+
+fn trash_item_from_row(row: &sqlx::sqlite::SqliteRow) -> AppResult<TrashItem> {
+    let item_type: String = row.get("item_type");
+    Ok(TrashItem {
+        id: row.get("id"),
+        item_type: TrashItemType::parse(&item_type).ok_or_else(|| AppError::InvalidFormat(format!("Unknown trash item type: {}", item_type)))?,
+        title: row.get("title"),
+        notebook_id: row.get("notebook_id"),
+        page_count: row.get::<i64, _>("page_count") as usize,
+        deleted_at: DateTime::parse_from_rfc3339(&row.get::<String, _>("deleted_at"))?.with_timezone(&Utc),
+    })
+}
+
+fn ai_job_from_row(row: &sqlx::sqlite::SqliteRow) -> AppResult<AiJob> {
+    let status: String = row.get("status");
+    Ok(AiJob {
+        id: row.get("id"),
+        kind: serde_json::from_str(&row.get::<String, _>("payload"))?,
+        priority: AiJobPriority::from_i64(row.get("priority")),
+        status: AiJobStatus::parse(&status).ok_or_else(|| AppError::InvalidFormat(format!("Unknown job status: {}", status)))?,
+        attempts: row.get::<i64, _>("attempts") as u32,
+        error: row.get("error"),
+        run_after: DateTime::parse_from_rfc3339(&row.get::<String, _>("run_after"))?.with_timezone(&Utc),
+        created_at: DateTime::parse_from_rfc3339(&row.get::<String, _>("created_at"))?.with_timezone(&Utc),
+        updated_at: DateTime::parse_from_rfc3339(&row.get::<String, _>("updated_at"))?.with_timezone(&Utc),
+    })
+}
+
+fn export_record_from_row(row: &sqlx::sqlite::SqliteRow) -> AppResult<ExportRecord> {
+    Ok(ExportRecord {
+        id: row.get("id"),
+        page_id: row.get("page_id"),
+        format: serde_json::from_str(&row.get::<String, _>("format"))?,
+        locale: row.get("locale"),
+        destination: row.get::<String, _>("destination").into(),
+        path: row.get::<String, _>("path").into(),
+        exported_at: DateTime::parse_from_rfc3339(&row.get::<String, _>("exported_at"))?.with_timezone(&Utc),
+    })
+}
+
+fn normalize_language(language: &str) -> AppResult<Option<String>> {
+    let language = language.trim();
+    if language.is_empty() {
+        return Ok(None);
+    }
+    if !is_valid_language_tag(language) {
+        return Err(AppError::InvalidFormat(format!("Invalid language tag: {}", language)));
+    }
+    Ok(Some(language.to_string()))
+}
+
+/// The signature SQLite calls auto extensions with, as in `sqlite3_auto_extension`.
+type AutoExtension = unsafe extern "C" fn(
+    *mut rusqlite::ffi::sqlite3,
+    *mut *mut std::os::raw::c_char,
+    *const rusqlite::ffi::sqlite3_api_routines,
+) -> std::os::raw::c_int;
+
+/// Makes sqlite-vec available on every connection opened afterwards. It is compiled in, so
+/// this costs nothing until the vector index is enabled.
+fn register_vector_extension() {
+    static REGISTER: std::sync::Once = std::sync::Once::new();
+    REGISTER.call_once(|| {
+        // Safety: sqlite-vec declares its entry point without parameters, but the C function
+        // is `sqlite3_vec_init(sqlite3*, char**, const sqlite3_api_routines*)` returning int,
+        // exactly `AutoExtension`, so only the Rust-side type changes. Both are plain C
+        // function pointers of the same size, and the function lives for the whole process.
+        let init = unsafe {
+            std::mem::transmute::<unsafe extern "C" fn(), AutoExtension>(sqlite_vec::sqlite3_vec_init as unsafe extern "C" fn())
+        };
+        // Safety: registering is thread safe in SQLite and happens once, before any
+        // connection that would load the extension is opened
+        let rc = unsafe { rusqlite::ffi::sqlite3_auto_extension(Some(init)) };
+        if rc != rusqlite::ffi::SQLITE_OK {
+            tracing::warn!("Failed to register the sqlite-vec extension: error {}", rc);
+        }
+    });
+}
+
+/// SQL conditions and their bound values for the filters. Column names are shared by
+/// the `notes` and `pages` tables; notebook and section only exist on pages.
+fn filter_conditions(filters: &SearchFilters) -> (Vec<String>, Vec<String>) {
+    let mut conditions = Vec::new();
+    let mut binds = Vec::new();
+
+    if let Some(ref notebook_id) = filters.notebook_id {
+        conditions.push("notebook_id = ?".to_string());
+        binds.push(notebook_id.clone());
+    }
+    if let Some(ref section_id) = filters.section_id {
+        conditions.push("section_id = ?".to_string());
+        binds.push(section_id.clone());
+    }
+    for tag in filters.tags.iter().flatten().filter_map(|tag| tags::normalize_tag(tag)) {
+        conditions.push("EXISTS (SELECT 1 FROM json_each(tags) WHERE json_each.value = ?)".to_string());
+        binds.push(tag);
+    }
+
+    // Timestamps are stored as UTC RFC 3339 strings, which sort chronologically
+    let ranges = [
+        ("created_at >= ?", filters.created_after),
+        ("created_at <= ?", filters.created_before),
+        ("updated_at >= ?", filters.updated_after),
+        ("updated_at <= ?", filters.updated_before),
+    ];
+    for (condition, value) in ranges {
+        if let Some(value) = value {
+            conditions.push(condition.to_string());
+            binds.push(value.to_rfc3339());
+        }
+    }
+
+    (conditions, binds)
+}
+
+/// Which searchable fields contain any of the query's positive terms.
+fn match_fields(terms: &[String], title: &str, content: &str, transcriptions: &[&str], attachment_texts: &[&str]) -> Vec<SearchMatchField> {
+    let terms: Vec<String> = terms.iter().map(|t| t.to_lowercase()).filter(|t| !t.is_empty()).collect();
+    if terms.is_empty() {
+        return Vec::new();
+    }
+
+    let contains = |text: &str| {
+        let text = text.to_lowercase();
+        terms.iter().any(|term| text.contains(term.as_str()))
+    };
+    let mut fields = Vec::new();
+    if contains(title) {
+        fields.push(SearchMatchField::Title);
+    }
+    if contains(content) {
+        fields.push(SearchMatchField::Content);
+    }
+    if transcriptions.iter().any(|t| contains(t)) {
+        fields.push(SearchMatchField::Transcription);
+    }
+    if attachment_texts.iter().any(|t| contains(t)) {
+        fields.push(SearchMatchField::AttachmentText);
+    }
+    fields
+}
+
+fn where_clause(conditions: &[String]) -> String {
+    if conditions.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}", conditions.join(" AND "))
+    }
+}
+
+const BM25_K1: f64 = 1.2;
+const BM25_B: f64 = 0.75;
+
+/// Lowercases and splits text on anything that isn't alphanumeric.
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|token| !token.is_empty())
+        .map(|token| token.to_lowercase())
+        .collect()
+}
+
+/// Okapi BM25 score of each document for the given query terms. Each term's contribution
+/// is scaled by its weight, which is how fuzzy expansions rank below exact matches.
+pub fn bm25_scores(documents: &[Vec<String>], query_terms: &[(String, f64)]) -> Vec<f64> {
+    if documents.is_empty() {
+        return Vec::new();
+    }
+
+    let doc_count = documents.len() as f64;
+    let avg_len = documents.iter().map(|d| d.len()).sum::<usize>() as f64 / doc_count;
+
+    // Keep the highest weight when a term appears more than once
+    let mut unique_terms: Vec<(&String, f64)> = Vec::new();
+    for (term, weight) in query_terms {
+        match unique_terms.iter_mut().find(|(t, _)| *t == term) {
+            Some(existing) => existing.1 = existing.1.max(*weight),
+            None => unique_terms.push((term, *weight)),
+        }
+    }
+
+    let idf: Vec<f64> = unique_terms
+        .iter()
+        .map(|(term, weight)| {
+            let containing = documents.iter().filter(|d| d.contains(*term)).count() as f64;
+            weight * ((doc_count - containing + 0.5) / (containing + 0.5) + 1.0).ln()
+        })
+        .collect();
+
+    documents
+        .iter()
+        .map(|document| {
+            let len_norm = if avg_len > 0.0 { document.len() as f64 / avg_len } else { 0.0 };
+            unique_terms
+                .iter()
+                .zip(&idf)
+                .map(|((term, _), idf)| {
+                    let tf = document.iter().filter(|t| t == *term).count() as f64;
+                    if tf == 0.0 {
+                        return 0.0;
+                    }
+                    idf * (tf * (BM25_K1 + 1.0)) / (tf + BM25_K1 * (1.0 - BM25_B + BM25_B * len_norm))
+                })
+                .sum()
+        })
+        .collect()
+}
+
+/// Maximum edit distance tolerated for a term; short words must match exactly.
+fn max_typo_distance(term: &str) -> usize {
+    match term.chars().count() {
+        0..=3 => 0,
+        4..=7 => 1,
+        _ => 2,
+    }
+}
+
+/// Confidence that `candidate` is what was meant by `term`: 1 for an exact match, lower
+/// for each edit, `None` if too far apart.
+pub fn fuzzy_confidence(term: &str, candidate: &str) -> Option<f64> {
+    if term == candidate {
+        return Some(1.0);
+    }
+
+    let max_distance = max_typo_distance(term);
+    let length = term.chars().count();
+    if max_distance == 0 || candidate.chars().count().abs_diff(length) > max_distance {
+        return None;
+    }
+
+    let distance = levenshtein(term, candidate);
+    (distance <= max_distance).then(|| 1.0 - distance as f64 / (length + 1) as f64)
+}
+
+/// Each query term with confidence 1, plus vocabulary words within typo distance of it.
+pub fn expand_query_terms(query_terms: &[String], vocabulary: &HashSet<&str>) -> Vec<(String, f64)> {
+    let mut expanded = Vec::new();
+    for term in query_terms {
+        expanded.push((term.clone(), 1.0));
+        for word in vocabulary {
+            if *word != term.as_str() {
+                if let Some(confidence) = fuzzy_confidence(term, word) {
+                    expanded.push((word.to_string(), confidence));
+                }
+            }
+        }
+    }
+    expanded
+}
+
+/// Average confidence over the query terms found (exactly or approximately) in the text;
+/// `None` when none are. Exact hits score 1.0, so approximate hits sort below them.
+pub fn match_confidence(query: &str, text: &str) -> Option<f64> {
+    let tokens = tokenize(text);
+    let tokens: HashSet<&str> = tokens.iter().map(|t| t.as_str()).collect();
+
+    let matches: Vec<f64> = tokenize(query)
+        .iter()
+        .filter_map(|term| {
+            tokens
+                .iter()
+                .filter_map(|token| fuzzy_confidence(term, token))
+                .fold(None, |best: Option<f64>, c| Some(best.map_or(c, |b| b.max(c))))
+        })
+        .collect();
+
+    if matches.is_empty() {
+        None
+    } else {
+        Some(matches.iter().sum::<f64>() / matches.len() as f64)
+    }
+}
+
+pub fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+
+    for (i, ca) in a.chars().enumerate() {
+        current[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    previous[b.len()]
+}
+
+/// Keyset cursor for lists ordered by `updated_at DESC, id DESC`.
+#[derive(Serialize, Deserialize)]
+struct UpdatedCursor {
+    updated_at: String,
+    id: String,
+}
+
+/// Condition selecting rows after `cursor` in `updated_at DESC, id DESC` order, with its values.
+fn cursor_condition(cursor: Option<&str>) -> AppResult<(Option<String>, Vec<String>)> {
+    let Some(cursor) = cursor else {
+        return Ok((None, Vec::new()));
+    };
+    let cursor: UpdatedCursor = decode_cursor(cursor)?;
+    Ok((
+        Some("(updated_at < ? OR (updated_at = ? AND id < ?))".to_string()),
+        vec![cursor.updated_at.clone(), cursor.updated_at, cursor.id],
+    ))
+}
+
+/// Trims `items`, fetched with one row more than `limit`, and returns the cursor for the
+/// next page when that extra row was there.
+fn next_cursor<T>(items: &mut Vec<T>, limit: usize, key: impl Fn(&T) -> (DateTime<Utc>, String)) -> AppResult<Option<String>> {
+    if items.len() <= limit {
+        return Ok(None);
+    }
+    items.truncate(limit);
+    let (updated_at, id) = key(&items[limit - 1]);
+    encode_cursor(&UpdatedCursor { updated_at: updated_at.to_rfc3339(), id }).map(Some)
+}
+
+/// Opaque, URL-safe pagination cursor.
+pub fn encode_cursor<T: Serialize>(cursor: &T) -> AppResult<String> {
+    Ok(general_purpose::URL_SAFE_NO_PAD.encode(serde_json::to_vec(cursor)?))
+}
+
+pub fn decode_cursor<T: DeserializeOwned>(cursor: &str) -> AppResult<T> {
+    let bytes = general_purpose::URL_SAFE_NO_PAD
+        .decode(cursor)
+        .map_err(|_| AppError::InvalidFormat("Invalid search cursor".to_string()))?;
+    serde_json::from_slice(&bytes).map_err(|_| AppError::InvalidFormat("Invalid search cursor".to_string()))
+}
+
+/// Byte ranges of words in the title and content matching a query term, exactly or
+/// within typo distance.
+pub fn highlight_spans(query: &str, title: &str, content: &str) -> Vec<HighlightSpan> {
+    let terms = tokenize(query);
+    if terms.is_empty() {
+        return Vec::new();
+    }
+
+    let mut spans = Vec::new();
+    for (field, text) in [(SearchMatchField::Title, title), (SearchMatchField::Content, content)] {
+        for (start, end) in word_ranges(text) {
+            let word = text[start..end].to_lowercase();
+            if terms.iter().any(|term| fuzzy_confidence(term, &word).is_some()) {
+                spans.push(HighlightSpan { field, start, end });
+            }
+        }
+    }
+    spans
+}
+
+/// Byte ranges of the alphanumeric runs in `text`, matching how `tokenize` splits.
+fn word_ranges(text: &str) -> Vec<(usize, usize)> {
+    let mut ranges = Vec::new();
+    let mut start = None;
+    for (index, c) in text.char_indices() {
+        match (c.is_alphanumeric(), start) {
+            (true, None) => start = Some(index),
+            (false, Some(s)) => {
+                ranges.push((s, index));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        ranges.push((s, text.len()));
+    }
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_levenshtein() {
+        assert_eq!(levenshtein("encrption", "encryption"), 1);
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+        assert_eq!(levenshtein("", "abc"), 3);
+    }
+
+    #[test]
+    fn test_typo_finds_document() {
+        let documents = vec![
+            tokenize("Notes on encryption at rest"),
+            tokenize("Grocery list"),
+        ];
+        let vocabulary: HashSet<&str> = documents.iter().flatten().map(|t| t.as_str()).collect();
+        let terms = expand_query_terms(&tokenize("encrption"), &vocabulary);
+        let scores = bm25_scores(&documents, &terms);
+
+        assert!(scores[0] > 0.0);
+        assert_eq!(scores[1], 0.0);
+    }
+
+    #[test]
+    fn test_exact_matches_are_more_confident() {
+        let exact = match_confidence("encryption", "About encryption").unwrap();
+        let approximate = match_confidence("encrption", "About encryption").unwrap();
+
+        assert_eq!(exact, 1.0);
+        assert!(approximate < exact && approximate > 0.8);
+        assert_eq!(match_confidence("cat", "About cars"), None);
+    }
+
+    #[test]
+    fn test_highlight_spans_use_byte_offsets() {
+        let content = "Café notes: encryption keys";
+        let spans = highlight_spans("encrption", "", content);
+
+        assert_eq!(spans.len(), 1);
+        assert_eq!(&content[spans[0].start..spans[0].end], "encryption");
+    }
+
+    #[test]
+    fn test_cursor_round_trip() {
+        let cursor = encode_cursor(&UpdatedCursor { updated_at: "2024-01-01T00:00:00+00:00".to_string(), id: "a".to_string() }).unwrap();
+        let decoded: UpdatedCursor = decode_cursor(&cursor).unwrap();
+        assert_eq!(decoded.id, "a");
+        assert!(decode_cursor::<UpdatedCursor>("not a cursor!").is_err());
+    }
+
+    #[test]
+    fn test_next_cursor_only_with_extra_row() {
+        let now = Utc::now();
+        let key = |id: &&str| (now, id.to_string());
+
+        let mut items = vec!["c", "b", "a"];
+        let cursor = next_cursor(&mut items, 2, key).unwrap().unwrap();
+        assert_eq!(items, vec!["c", "b"]);
+        let decoded: UpdatedCursor = decode_cursor(&cursor).unwrap();
+        assert_eq!(decoded.id, "b");
+
+        let mut items = vec!["b", "a"];
+        assert!(next_cursor(&mut items, 2, key).unwrap().is_none());
+        assert_eq!(items.len(), 2);
+        assert_eq!(cursor_condition(None).unwrap(), (None, Vec::new()));
+    }
+}
@@ -0,0 +1,183 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use chrono::Utc;
+use serde_json::{json, Value};
+use crate::{
+    AppError, AppResult,
+    models::AppConfig,
+    database::Database,
+    ai::AIService,
+};
+
+/// Only the end of each log file is included so bundles stay small enough to attach to an issue.
+const MAX_LOG_BYTES: u64 = 2 * 1024 * 1024;
+const REDACTED: &str = "[redacted]";
+const SENSITIVE_KEY_PARTS: &[&str] = &["password", "secret", "token", "api_key", "credential", "private", "key"];
+
+/// Writes a zip with logs, redacted config and settings, schema version, vault statistics and
+/// AI model info to `<data dir>/diagnostics` and returns its path. Note content is never read.
+pub async fn generate_bundle(database: &Database, ai_service: &AIService, config: &AppConfig) -> AppResult<PathBuf> {
+    let generated_at = Utc::now();
+
+    let system = json!({
+        "generated_at": generated_at.to_rfc3339(),
+        "app_version": env!("CARGO_PKG_VERSION"),
+        "os": std::env::consts::OS,
+        "arch": std::env::consts::ARCH,
+        "schema_version": database.schema_version().await?,
+    });
+
+    let settings: serde_json::Map<String, Value> = database
+        .get_settings()
+        .await?
+        .into_iter()
+        .map(|(key, value)| {
+            let value = if is_sensitive_key(&key) {
+                Value::String(REDACTED.to_string())
+            } else {
+                // Structured settings are redacted field by field
+                serde_json::from_str(&value).map(redact).unwrap_or(Value::String(value))
+            };
+            (key, value)
+        })
+        .collect();
+
+    let ai = json!({
+        "whisper_available": ai_service.is_whisper_available(),
+        "embedding_available": ai_service.is_embedding_available(),
+        "whisper_model": ai_service.get_whisper_model(),
+        "embedding_model": ai_service.get_embedding_model(),
+        "model_files": list_files(&config.ai_models_path)?,
+    });
+
+    let mut files: Vec<(String, Vec<u8>)> = vec![
+        ("system.json".to_string(), serde_json::to_vec_pretty(&system)?),
+        ("config.json".to_string(), serde_json::to_vec_pretty(&redact(serde_json::to_value(config)?))?),
+        ("settings.json".to_string(), serde_json::to_vec_pretty(&settings)?),
+        ("vault_stats.json".to_string(), serde_json::to_vec_pretty(&database.get_vault_stats().await?)?),
+        ("ai.json".to_string(), serde_json::to_vec_pretty(&ai)?),
+    ];
+
+    for entry in list_files(&config.logs_path)? {
+        let name = entry["name"].as_str().unwrap_or_default().to_string();
+        files.push((format!("logs/{}", name), read_tail(&config.logs_path.join(&name), MAX_LOG_BYTES)?));
+    }
+    // Crash reports are already sanitized when written
+    for entry in list_files(&config.crash_reports_path)? {
+        let name = entry["name"].as_str().unwrap_or_default().to_string();
+        files.push((format!("crash_reports/{}", name), std::fs::read(config.crash_reports_path.join(&name))?));
+    }
+
+    let output_dir = config
+        .database_path
+        .parent()
+        .map(|dir| dir.join("diagnostics"))
+        .ok_or_else(|| AppError::Configuration("Data directory is not configured".to_string()))?;
+    let output_path = output_dir.join(format!("deviseos-diagnostics-{}.zip", generated_at.format("%Y%m%d-%H%M%S")));
+
+    tokio::task::spawn_blocking(move || write_zip(&output_path, files).map(|_| output_path))
+        .await
+        .map_err(|e| AppError::Unknown(format!("Diagnostics task failed: {}", e)))?
+}
+
+fn write_zip(path: &Path, files: Vec<(String, Vec<u8>)>) -> AppResult<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let mut zip = zip::ZipWriter::new(std::fs::File::create(path)?);
+    let options = zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    for (name, data) in files {
+        zip.start_file(name, options)
+            .map_err(|e| AppError::Unknown(format!("Failed to write diagnostics bundle: {}", e)))?;
+        zip.write_all(&data)?;
+    }
+    zip.finish()
+        .map_err(|e| AppError::Unknown(format!("Failed to write diagnostics bundle: {}", e)))?;
+    Ok(())
+}
+
+/// Name and size of each regular file in a directory; an empty list if it doesn't exist.
+fn list_files(dir: &Path) -> AppResult<Vec<Value>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if metadata.is_file() {
+            files.push(json!({
+                "name": entry.file_name().to_string_lossy(),
+                "size": metadata.len(),
+            }));
+        }
+    }
+    files.sort_by(|a, b| a["name"].as_str().cmp(&b["name"].as_str()));
+    Ok(files)
+}
+
+fn read_tail(path: &Path, max_bytes: u64) -> AppResult<Vec<u8>> {
+    use std::io::{Read, Seek, SeekFrom};
+
+    let mut file = std::fs::File::open(path)?;
+    let length = file.metadata()?.len();
+    file.seek(SeekFrom::Start(length.saturating_sub(max_bytes)))?;
+    let mut data = Vec::new();
+    file.read_to_end(&mut data)?;
+    Ok(data)
+}
+
+fn is_sensitive_key(key: &str) -> bool {
+    let key = key.to_lowercase();
+    SENSITIVE_KEY_PARTS.iter().any(|part| key.contains(part))
+}
+
+/// Replaces the values of sensitive-looking fields anywhere in a JSON document.
+pub fn redact(value: Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .map(|(key, value)| {
+                    if is_sensitive_key(&key) && !value.is_null() {
+                        (key, Value::String(REDACTED.to_string()))
+                    } else {
+                        (key, redact(value))
+                    }
+                })
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.into_iter().map(redact).collect()),
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_nested_secrets() {
+        let value = json!({
+            "host": "broker.local",
+            "password": "hunter2",
+            "username": null,
+            "webhooks": [{"url": "https://example.com", "api_key": "abc"}],
+        });
+
+        let redacted = redact(value);
+        assert_eq!(redacted["host"], "broker.local");
+        assert_eq!(redacted["password"], REDACTED);
+        assert!(redacted["username"].is_null());
+        assert_eq!(redacted["webhooks"][0]["api_key"], REDACTED);
+        assert_eq!(redacted["webhooks"][0]["url"], "https://example.com");
+    }
+
+    #[test]
+    fn test_sensitive_setting_keys() {
+        assert!(is_sensitive_key("trusted_bundle_keys"));
+        assert!(is_sensitive_key("OpenAI_API_KEY"));
+        assert!(!is_sensitive_key("mqtt_config"));
+    }
+}
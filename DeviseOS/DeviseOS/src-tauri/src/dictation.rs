@@ -0,0 +1,223 @@
+use std::collections::HashMap;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::sync::{mpsc, Mutex};
+use uuid::Uuid;
+use crate::{
+    AppError, AppResult, AppState,
+    models::{DictationSession, DictationUpdate, LanguageSource},
+    ai::PCM_BYTES_PER_SECOND,
+    transcription::seconds,
+    vad,
+};
+
+pub const DICTATION_EVENT: &str = "dictation-text";
+
+/// Audio is gathered for at least this long before transcribing, so Whisper has a whole
+/// phrase to work with.
+const MIN_CHUNK_SECS: usize = 3;
+/// Talking without a pause for this long gets transcribed anyway.
+const MAX_CHUNK_SECS: usize = 15;
+/// Quiet after the last speech before it counts as a pause to cut at.
+const PAUSE_MS: usize = 300;
+/// A session that gets no audio for this long is ended, as if `stop` had been called.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Dictation sessions in progress. Each has a worker that transcribes its audio in order
+/// and reports the text in `dictation-text` events, so pushing audio never waits on
+/// Whisper. The editor inserts the text and saves the page itself; writing it here would
+/// race the editor's own saves.
+pub struct Dictation {
+    sessions: Mutex<HashMap<String, mpsc::UnboundedSender<Vec<u8>>>>,
+}
+
+impl Dictation {
+    pub fn new() -> Self {
+        Self { sessions: Mutex::new(HashMap::new()) }
+    }
+
+    /// Starts dictating into `page_id`, in `language`, the page's language when it has one
+    /// set or detected, or whatever is detected in the first speech.
+    pub async fn start(&self, app: AppHandle, page_id: &str, language: Option<String>) -> AppResult<DictationSession> {
+        let state = app.state::<AppState>();
+        if !state.ai_service.read().await.is_whisper_available() {
+            return Err(AppError::ModelNotFound("Whisper model not available".to_string()));
+        }
+        let language = match language.map(|language| language.trim().to_string()).filter(|language| !language.is_empty()) {
+            Some(language) => Some(language),
+            None => {
+                let settings = state.database.read().await.get_language_settings(page_id).await?;
+                (!matches!(settings.source, LanguageSource::Default)).then_some(settings.whisper_language)
+            }
+        };
+
+        let session = DictationSession {
+            session_id: Uuid::new_v4().to_string(),
+            page_id: page_id.to_string(),
+            language,
+        };
+        let (sender, receiver) = mpsc::unbounded_channel();
+        self.sessions.lock().await.insert(session.session_id.clone(), sender);
+        tauri::async_runtime::spawn(run(app.clone(), session.clone(), receiver));
+        tracing::info!("Started dictation {} into page {}", session.session_id, page_id);
+        Ok(session)
+    }
+
+    /// Queues microphone audio, 16 kHz mono 16-bit PCM, in the order it was recorded.
+    pub async fn push(&self, session_id: &str, audio: Vec<u8>) -> AppResult<()> {
+        let sessions = self.sessions.lock().await;
+        let sender = sessions.get(session_id)
+            .ok_or_else(|| AppError::NotFound(format!("Dictation session {} not found", session_id)))?;
+        sender.send(audio)
+            .map_err(|_| AppError::InvalidOperation(format!("Dictation session {} has ended", session_id)))
+    }
+
+    /// Stops taking audio. What was already pushed is still transcribed, and the last
+    /// `dictation-text` event has `finished` set.
+    pub async fn stop(&self, session_id: &str) -> AppResult<()> {
+        self.sessions.lock().await.remove(session_id)
+            .map(|_| ())
+            .ok_or_else(|| AppError::NotFound(format!("Dictation session {} not found", session_id)))
+    }
+}
+
+/// The next audio pushed, or `None` once the session was stopped. A session left idle for
+/// `IDLE_TIMEOUT` is stopped here.
+async fn next_audio(app: &AppHandle, session_id: &str, receiver: &mut mpsc::UnboundedReceiver<Vec<u8>>) -> Option<Vec<u8>> {
+    match tokio::time::timeout(IDLE_TIMEOUT, receiver.recv()).await {
+        Ok(received) => received,
+        Err(_) => {
+            tracing::info!("Stopping dictation {} after {} s without audio", session_id, IDLE_TIMEOUT.as_secs());
+            let _ = app.state::<AppState>().dictation.stop(session_id).await;
+            receiver.close();
+            None
+        }
+    }
+}
+
+async fn run(app: AppHandle, mut session: DictationSession, mut receiver: mpsc::UnboundedReceiver<Vec<u8>>) {
+    let state = app.state::<AppState>();
+    let mut buffer: Vec<u8> = Vec::new();
+    let mut transcript = String::new();
+    let mut offset = 0;
+
+    loop {
+        let received = next_audio(&app, &session.session_id, &mut receiver).await;
+        let finished = received.is_none();
+        if let Some(audio) = received {
+            buffer.extend_from_slice(&audio);
+        }
+        let cut = if finished {
+            Some(buffer.len() & !1)
+        } else {
+            cut_point(&buffer, MIN_CHUNK_SECS * PCM_BYTES_PER_SECOND, MAX_CHUNK_SECS * PCM_BYTES_PER_SECOND)
+        };
+        let Some(cut) = cut else {
+            continue;
+        };
+        let chunk: Vec<u8> = buffer.drain(..cut).collect();
+        let (start, end) = (offset, offset + chunk.len());
+        offset = end;
+
+        let (text, error) = match transcribe(&state, &mut session, &chunk).await {
+            Ok(text) => (text, None),
+            Err(e) => {
+                tracing::warn!("Failed to transcribe dictation {}: {}", session.session_id, e);
+                (String::new(), Some(e.to_string()))
+            }
+        };
+        if text.is_empty() && error.is_none() && !finished {
+            continue; // Silence
+        }
+        if !text.is_empty() {
+            if !transcript.is_empty() {
+                transcript.push(' ');
+            }
+            transcript.push_str(&text);
+        }
+
+        let update = DictationUpdate {
+            session_id: session.session_id.clone(),
+            page_id: session.page_id.clone(),
+            start_secs: seconds(start),
+            end_secs: seconds(end),
+            text,
+            transcript: transcript.clone(),
+            language: session.language.clone(),
+            error,
+            finished,
+        };
+        let _ = app.emit(DICTATION_EVENT, &update);
+        if finished {
+            break;
+        }
+    }
+    tracing::info!("Finished dictation {}", session.session_id);
+}
+
+/// The chunk's text, empty when it has no speech. The language detected in the first
+/// speech is kept for the rest of the session.
+async fn transcribe(state: &AppState, session: &mut DictationSession, chunk: &[u8]) -> AppResult<String> {
+    if vad::speech_segments(chunk).is_empty() {
+        return Ok(String::new());
+    }
+    // The lock is taken per chunk so model changes aren't blocked by a long session
+    let (text, _, detected) = state.ai_service.read().await
+        .transcribe_with_language(chunk, session.language.as_deref())
+        .await?;
+    if session.language.is_none() {
+        session.language = detected;
+    }
+    Ok(text.trim().to_string())
+}
+
+/// Where to cut buffered audio for transcription: after the last stretch of speech that
+/// has been followed by a pause, so words aren't split. Silence is cut whole. `None` to
+/// keep listening, until the buffer reaches `max_bytes` and is cut whole too.
+fn cut_point(audio: &[u8], min_bytes: usize, max_bytes: usize) -> Option<usize> {
+    if audio.len() < min_bytes {
+        return None;
+    }
+    if audio.len() >= max_bytes {
+        return Some(audio.len() & !1);
+    }
+    let segments = vad::speech_segments(audio);
+    if segments.is_empty() {
+        return Some(audio.len() & !1);
+    }
+    let pause_bytes = PAUSE_MS * PCM_BYTES_PER_SECOND / 1000;
+    segments.iter().rev().map(|&(_, end)| end).find(|&end| end + pause_bytes <= audio.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 440 Hz tone at `amplitude`, or near silence for small ones.
+    fn tone(secs: f64, amplitude: f64) -> Vec<u8> {
+        let samples = (secs * 16_000.0) as usize;
+        (0..samples)
+            .flat_map(|i| ((amplitude * (i as f64 * 440.0 * std::f64::consts::TAU / 16_000.0).sin()) as i16).to_le_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn test_cut_point() {
+        let (min, max) = (3 * PCM_BYTES_PER_SECOND, 15 * PCM_BYTES_PER_SECOND);
+        assert_eq!(cut_point(&tone(2.0, 5000.0), min, max), None);
+
+        // Cut after the speech that has ended, padding included
+        let paused = [tone(2.0, 5000.0), tone(1.0, 20.0)].concat();
+        let cut = cut_point(&paused, min, max).unwrap();
+        assert!((seconds(cut) - 2.2).abs() < 0.05);
+
+        // Still talking
+        let talking = [tone(1.0, 20.0), tone(3.0, 5000.0)].concat();
+        assert_eq!(cut_point(&talking, min, max), None);
+
+        let silence = tone(4.0, 20.0);
+        assert_eq!(cut_point(&silence, min, max), Some(silence.len()));
+        let long = tone(15.0, 5000.0);
+        assert_eq!(cut_point(&long, min, max), Some(long.len()));
+    }
+}
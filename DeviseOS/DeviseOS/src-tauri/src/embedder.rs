@@ -0,0 +1,70 @@
+use std::path::Path;
+use candle_core::{DType, Device, IndexOp, Tensor};
+use candle_nn::VarBuilder;
+use candle_transformers::models::bert::{BertModel, Config};
+use tokenizers::Tokenizer;
+use crate::{AppError, AppResult, models::EmbeddingModel};
+
+/// Longest input BERT-family models accept; longer text is embedded from its beginning.
+const MAX_INPUT_TOKENS: usize = 512;
+
+/// A sentence embedding model's weights, loaded from the files `model_downloads` installs.
+pub struct Embedder {
+    model: BertModel,
+    tokenizer: Tokenizer,
+    device: Device,
+    cls_pooling: bool, // BGE is trained on the first token's state rather than the mean
+}
+
+impl Embedder {
+    pub fn load(model: &EmbeddingModel, models_path: &Path, device: &Device) -> AppResult<Self> {
+        let name = model.model_name();
+        let config_path = models_path.join(format!("config-{}.json", name));
+        let config: Config = serde_json::from_str(&std::fs::read_to_string(&config_path)?)
+            .map_err(|e| AppError::AIProcessing(format!("Invalid model config {}: {}", config_path.display(), e)))?;
+
+        let weights_path = models_path.join(format!("embedding-{}.safetensors", name));
+        // Safety: the file is only read while mapped, and nothing else writes model files once installed
+        let weights = unsafe { VarBuilder::from_mmaped_safetensors(&[&weights_path], DType::F32, device) }
+            .map_err(|e| AppError::AIProcessing(format!("Invalid model weights {}: {}", weights_path.display(), e)))?;
+        let bert = BertModel::load(weights, &config)
+            .map_err(|e| AppError::AIProcessing(format!("Failed to load embedding model {}: {}", name, e)))?;
+
+        let tokenizer_path = models_path.join(format!("tokenizer-{}.json", name));
+        let tokenizer = Tokenizer::from_file(&tokenizer_path).map_err(|e| {
+            AppError::ModelNotFound(format!("Failed to load tokenizer {}: {}", tokenizer_path.display(), e))
+        })?;
+
+        tracing::info!("Loaded embedding model {}", name);
+        Ok(Self {
+            model: bert,
+            tokenizer,
+            device: device.clone(),
+            cls_pooling: matches!(model, EmbeddingModel::BGE),
+        })
+    }
+
+    /// A unit-length embedding of `text`. Blocks for as long as inference takes.
+    pub fn embed(&self, text: &str) -> AppResult<Vec<f32>> {
+        let encoding = self.tokenizer.encode(text, true)
+            .map_err(|e| AppError::AIProcessing(format!("Failed to tokenize text: {}", e)))?;
+        let ids = encoding.get_ids();
+        let ids = &ids[..ids.len().min(MAX_INPUT_TOKENS)];
+        self.forward(ids)
+            .map_err(|e| AppError::AIProcessing(format!("Embedding inference failed: {}", e)))
+    }
+
+    fn forward(&self, ids: &[u32]) -> candle_core::Result<Vec<f32>> {
+        let input_ids = Tensor::new(ids, &self.device)?.unsqueeze(0)?;
+        let token_type_ids = input_ids.zeros_like()?;
+        let hidden = self.model.forward(&input_ids, &token_type_ids, None)?.squeeze(0)?;
+        let pooled = if self.cls_pooling {
+            hidden.i(0)?
+        } else {
+            (hidden.sum(0)? / ids.len().max(1) as f64)?
+        };
+        let norm = pooled.sqr()?.sum_all()?.sqrt()?.to_scalar::<f32>()?;
+        let embedding: Vec<f32> = pooled.to_vec1()?;
+        Ok(if norm > 0.0 { embedding.into_iter().map(|value| value / norm).collect() } else { embedding })
+    }
+}
@@ -4,13 +4,18 @@ use aes_gcm::{
 };
 use argon2::{Argon2, PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
 use base64::{Engine as _, engine::general_purpose};
+use serde::{Deserialize, Serialize};
 use rand::RngCore;
+use sha2::{Digest, Sha256};
 use std::fs;
 use std::path::Path;
 use crate::AppError;
 
 pub type AppResult<T> = Result<T, AppError>;
 
+/// Shortest passphrase a vault key can be exported with.
+const MIN_PASSPHRASE_LENGTH: usize = 8;
+
 pub struct EncryptionManager {
     key: Key<Aes256Gcm>,
     cipher: Aes256Gcm,
@@ -54,23 +59,79 @@ impl EncryptionManager {
         Ok(Self { key: *key, cipher })
     }
 
+    /// Uses the first 32 bytes of an already derived key.
+    pub fn from_key_bytes(key_bytes: &[u8]) -> AppResult<Self> {
+        if key_bytes.len() < 32 {
+            return Err(AppError::Encryption("Key too short".to_string()));
+        }
+
+        let key = Key::<Aes256Gcm>::from_slice(&key_bytes[..32]);
+        let cipher = Aes256Gcm::new(key);
+
+        Ok(Self { key: *key, cipher })
+    }
+
     pub fn generate_key_file(key_path: &Path, master_password: &str) -> AppResult<()> {
         let salt = generate_salt()?;
-        let manager = Self::new(master_password, &salt)?;
-        
+        Self::new(master_password, &salt)?.write_key_file(key_path)
+    }
+
+    pub fn write_key_file(&self, key_path: &Path) -> AppResult<()> {
         // Create directory if it doesn't exist
         if let Some(parent) = key_path.parent() {
             fs::create_dir_all(parent)
                 .map_err(|e| AppError::Encryption(format!("Failed to create key directory: {}", e)))?;
         }
         
-        let key_bytes = manager.key.as_slice();
-        fs::write(key_path, key_bytes)
+        fs::write(key_path, self.key.as_slice())
             .map_err(|e| AppError::Encryption(format!("Failed to write key file: {}", e)))?;
         
         Ok(())
     }
 
+    /// Short fingerprint of the content key. Devices sharing a key get the same one, and it
+    /// reveals nothing about the key, so it can sit next to the data in a sync folder.
+    pub fn key_id(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(b"deviseos-key-id");
+        hasher.update(self.key.as_slice());
+        hasher.finalize().iter().take(8).map(|b| format!("{:02x}", b)).collect()
+    }
+
+    /// The content key sealed with `passphrase`, as text to carry to another device: base64
+    /// of the salt followed by the key encrypted under one derived from the passphrase.
+    pub fn export_key(&self, passphrase: &str) -> AppResult<String> {
+        if passphrase.chars().count() < MIN_PASSPHRASE_LENGTH {
+            return Err(AppError::Validation(format!("The passphrase needs at least {} characters", MIN_PASSPHRASE_LENGTH)));
+        }
+        let salt = generate_salt()?;
+        let sealed = Self::new(passphrase, &salt)?.encrypt(self.key.as_slice())?;
+        Ok(general_purpose::STANDARD.encode([salt, sealed].concat()))
+    }
+
+    /// Opens a key sealed by `export_key`.
+    pub fn import_key(exported: &str, passphrase: &str) -> AppResult<Self> {
+        let data = general_purpose::STANDARD.decode(exported.trim())
+            .map_err(|e| AppError::Encryption(format!("Invalid vault key: {}", e)))?;
+        if data.len() < 32 + 12 {
+            return Err(AppError::Encryption("Invalid vault key: too short".to_string()));
+        }
+        let (salt, sealed) = data.split_at(32);
+        let key = Self::new(passphrase, salt)?.decrypt(sealed)
+            .map_err(|_| AppError::Encryption("Wrong passphrase or damaged vault key".to_string()))?;
+        Self::from_key_bytes(&key)
+    }
+
+    /// Raw SQLCipher key for the whole database file, as a hex blob literal. Derived from
+    /// the content key so that one key file still unlocks everything, without reusing it.
+    pub fn database_key(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(b"deviseos-sqlcipher");
+        hasher.update(self.key.as_slice());
+        let hex: String = hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect();
+        format!("x'{}'", hex)
+    }
+
     pub fn encrypt(&self, data: &[u8]) -> AppResult<Vec<u8>> {
         let mut nonce_bytes = [0u8; 12];
         OsRng.fill_bytes(&mut nonce_bytes);
@@ -215,8 +276,8 @@ impl std::fmt::Debug for SecureString {
     }
 }
 
-// Encryption levels for different security requirements
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+// Encryption levels for different security requirements, ordered weakest to strongest
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum EncryptionLevel {
     None,
     Standard,  // AES-256-GCM
@@ -315,6 +376,29 @@ mod tests {
         assert_eq!(plaintext, decrypted);
     }
 
+    #[test]
+    fn test_database_key_is_derived() {
+        let manager = EncryptionManager::from_key_bytes(&[7u8; 32]).unwrap();
+        let key = manager.database_key();
+
+        assert_eq!(key.len(), 67);
+        assert!(key.starts_with("x'") && key.ends_with('\''));
+        assert!(!key.contains(&"07".repeat(32)));
+        assert_eq!(key, EncryptionManager::from_key_bytes(&[7u8; 32]).unwrap().database_key());
+    }
+
+    #[test]
+    fn test_key_export_round_trip() {
+        let manager = EncryptionManager::from_key_bytes(&[7u8; 32]).unwrap();
+        let exported = manager.export_key("correct horse").unwrap();
+
+        let imported = EncryptionManager::import_key(&exported, "correct horse").unwrap();
+        assert_eq!(imported.key_id(), manager.key_id());
+        assert_ne!(manager.key_id(), EncryptionManager::from_key_bytes(&[8u8; 32]).unwrap().key_id());
+        assert!(EncryptionManager::import_key(&exported, "wrong horse").is_err());
+        assert!(EncryptionManager::import_key("AAAA", "correct horse").is_err());
+    }
+
     #[test]
     fn test_password_hashing() {
         let password = "test_password";
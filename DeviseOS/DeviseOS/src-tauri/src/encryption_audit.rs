@@ -0,0 +1,207 @@
+use chrono::Utc;
+use crate::{
+    AppResult,
+    models::{AppConfig, DataCategory, EncryptionCoverageReport, FieldCoverage},
+    database::Database,
+};
+
+/// Ids of undecryptable rows listed per field.
+const SAMPLE_IDS: usize = 10;
+
+struct Field {
+    table: &'static str,
+    column: &'static str,
+    category: DataCategory,
+    /// Id column and whether the value is a BLOB, for fields written encrypted.
+    encrypted: Option<(&'static str, bool)>,
+}
+
+const fn plain(table: &'static str, column: &'static str, category: DataCategory) -> Field {
+    Field { table, column, category, encrypted: None }
+}
+
+const fn sealed(table: &'static str, column: &'static str, category: DataCategory, id_column: &'static str, blob: bool) -> Field {
+    Field { table, column, category, encrypted: Some((id_column, blob)) }
+}
+
+/// Every column holding user data, and whether `Database` encrypts it when a key is loaded.
+const FIELDS: &[Field] = &[
+    sealed("pages", "content", DataCategory::Content, "id", false),
+    sealed("notes", "content", DataCategory::Content, "id", false),
+    sealed("page_revisions", "content", DataCategory::Content, "id", false),
+    sealed("voice_annotations", "audio_data", DataCategory::Media, "id", true),
+    sealed("media_attachments", "file_data", DataCategory::Media, "id", true),
+    sealed("media_attachments", "original_data", DataCategory::Media, "id", true),
+    sealed("media_attachments", "thumbnail_data", DataCategory::Media, "id", true),
+    sealed("settings", "value", DataCategory::Settings, "key", false),
+    sealed("page_properties", "text_value", DataCategory::Content, "id", false),
+    plain("notebooks", "title", DataCategory::Titles),
+    plain("notebooks", "description", DataCategory::Titles),
+    plain("sections", "title", DataCategory::Titles),
+    plain("pages", "title", DataCategory::Titles),
+    plain("notes", "title", DataCategory::Titles),
+    plain("page_revisions", "title", DataCategory::Titles),
+    plain("page_links", "link_text", DataCategory::Titles),
+    plain("pages", "tags", DataCategory::Tags),
+    plain("notes", "tags", DataCategory::Tags),
+    plain("page_tags", "tag", DataCategory::Tags),
+    plain("note_tags", "tag", DataCategory::Tags),
+    plain("tags", "name", DataCategory::Tags),
+    plain("tags", "description", DataCategory::Tags),
+    plain("page_property_definitions", "options", DataCategory::Titles),
+    plain("voice_annotations", "transcription", DataCategory::Transcriptions),
+    plain("voice_annotations", "metadata", DataCategory::Transcriptions), // Timed segment text
+    plain("media_attachments", "original_filename", DataCategory::Media),
+    plain("media_attachments", "metadata", DataCategory::Media), // OCR and document text
+    plain("embeddings", "embedding", DataCategory::Embeddings),
+    plain("trash", "title", DataCategory::Titles),
+    plain("trash", "payload", DataCategory::Titles), // Trashed rows as stored: content stays sealed, titles and transcriptions don't
+    plain("templates", "content", DataCategory::Templates),
+    plain("snippets", "content", DataCategory::Templates),
+    plain("prompts", "prompt", DataCategory::Templates),
+    plain("automations", "action_config", DataCategory::Settings),
+    plain("automation_runs", "output", DataCategory::Logs),
+    plain("audit_log", "target", DataCategory::Logs),
+    plain("export_history", "path", DataCategory::Logs),
+];
+
+/// Which fields of the vault are encrypted and which are stored in plaintext, with values
+/// that fail to decrypt flagged and the share of stored bytes readable without the key.
+/// `encryption_enabled` only covers note and page content, property values, audio,
+/// attachments and settings; a SQLCipher database file covers every field.
+pub async fn audit_encryption_coverage(database: &Database, config: &AppConfig) -> AppResult<EncryptionCoverageReport> {
+    let key_loaded = database.is_encrypted();
+    let file_encrypted = database.is_file_encrypted();
+    let mut fields = Vec::new();
+    for field in FIELDS {
+        let (rows, bytes) = database.get_column_usage(field.table, field.column).await?;
+        let (undecryptable_rows, undecryptable_bytes, undecryptable_ids) = match field.encrypted {
+            Some((id_column, blob)) => database.get_undecryptable_values(field.table, id_column, field.column, blob, SAMPLE_IDS).await?,
+            None => (0, 0, Vec::new()),
+        };
+        fields.push(FieldCoverage {
+            table: field.table.to_string(),
+            column: field.column.to_string(),
+            category: field.category,
+            encrypted: file_encrypted || (key_loaded && field.encrypted.is_some()),
+            rows,
+            bytes,
+            undecryptable_rows,
+            undecryptable_bytes,
+            undecryptable_ids,
+        });
+    }
+
+    let (encrypted_bytes, plaintext_bytes) = byte_totals(&fields);
+    let total = encrypted_bytes + plaintext_bytes;
+    Ok(EncryptionCoverageReport {
+        encryption_enabled: config.encryption_enabled,
+        key_loaded,
+        file_encrypted,
+        warnings: warnings(config.encryption_enabled, key_loaded, &fields),
+        exposure: if total == 0 { 0.0 } else { plaintext_bytes as f64 / total as f64 },
+        encrypted_bytes,
+        plaintext_bytes,
+        fields,
+        checked_at: Utc::now(),
+    })
+}
+
+/// Values that don't decrypt count as plaintext: they may be left from before encryption
+/// was turned on.
+fn byte_totals(fields: &[FieldCoverage]) -> (u64, u64) {
+    fields.iter().fold((0, 0), |(encrypted, plaintext), field| {
+        if field.encrypted {
+            (encrypted + field.bytes - field.undecryptable_bytes, plaintext + field.undecryptable_bytes)
+        } else {
+            (encrypted, plaintext + field.bytes)
+        }
+    })
+}
+
+fn warnings(encryption_enabled: bool, key_loaded: bool, fields: &[FieldCoverage]) -> Vec<String> {
+    let mut warnings = Vec::new();
+    if !key_loaded {
+        warnings.push(if encryption_enabled {
+            "Encryption is enabled but no key is loaded; new data is stored in plaintext".to_string()
+        } else {
+            "Encryption is off; anyone with the vault file can read everything in it".to_string()
+        });
+        return warnings;
+    }
+
+    let mut exposed: Vec<&str> = Vec::new();
+    for field in fields.iter().filter(|field| !field.encrypted && field.rows > 0) {
+        let name = category_name(field.category);
+        if !exposed.contains(&name) {
+            exposed.push(name);
+        }
+    }
+    if !exposed.is_empty() {
+        warnings.push(format!("Stored in plaintext even with encryption on: {}", exposed.join(", ")));
+    }
+    for field in fields.iter().filter(|field| field.undecryptable_rows > 0) {
+        warnings.push(format!(
+            "Values in {}.{} that don't decrypt with the loaded key: {}",
+            field.table, field.column, field.undecryptable_rows
+        ));
+    }
+    warnings
+}
+
+fn category_name(category: DataCategory) -> &'static str {
+    match category {
+        DataCategory::Content => "content",
+        DataCategory::Titles => "titles",
+        DataCategory::Tags => "tags",
+        DataCategory::Transcriptions => "transcriptions",
+        DataCategory::Media => "media names, thumbnails and extracted text",
+        DataCategory::Embeddings => "embeddings",
+        DataCategory::Settings => "automation settings",
+        DataCategory::Templates => "templates, snippets and prompts",
+        DataCategory::Logs => "logs",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn coverage(table: &str, category: DataCategory, encrypted: bool, bytes: u64, undecryptable: u64) -> FieldCoverage {
+        FieldCoverage {
+            table: table.to_string(),
+            column: "value".to_string(),
+            category,
+            encrypted,
+            rows: u64::from(bytes > 0),
+            bytes,
+            undecryptable_rows: u64::from(undecryptable > 0),
+            undecryptable_bytes: undecryptable,
+            undecryptable_ids: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_byte_totals_count_undecryptable_as_plaintext() {
+        let fields = vec![
+            coverage("pages", DataCategory::Content, true, 900, 100),
+            coverage("tags", DataCategory::Tags, false, 50, 0),
+        ];
+        assert_eq!(byte_totals(&fields), (800, 150));
+    }
+
+    #[test]
+    fn test_warnings() {
+        let fields = vec![
+            coverage("pages", DataCategory::Content, true, 900, 100),
+            coverage("pages", DataCategory::Titles, false, 40, 0),
+            coverage("notes", DataCategory::Titles, false, 10, 0),
+            coverage("tags", DataCategory::Tags, false, 0, 0),
+        ];
+        assert_eq!(
+            warnings(true, true, &fields),
+            vec!["Stored in plaintext even with encryption on: titles", "Values in pages.value that don't decrypt with the loaded key: 1"]
+        );
+        assert_eq!(warnings(true, false, &fields).len(), 1);
+    }
+}
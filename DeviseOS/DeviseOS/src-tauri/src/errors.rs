@@ -41,6 +41,9 @@ pub enum AppError {
     #[error("Invalid operation: {0}")]
     InvalidOperation(String),
     
+    #[error("Validation error: {0}")]
+    Validation(String),
+    
     #[error("Timeout: {0}")]
     Timeout(String),
     
@@ -0,0 +1,334 @@
+use crate::{
+    AppResult,
+    models::{ImageLocationAudit, LocatedAttachment},
+    database::Database,
+};
+
+/// The vault's `SecurityConfig`, as JSON.
+pub const SECURITY_CONFIG_KEY: &str = "security_config";
+
+/// JPEG APP1 payloads carrying EXIF or XMP start with one of these.
+const EXIF_HEADER: &[u8] = b"Exif\0\0";
+const XMP_HEADER: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// GPSInfo in IFD0, and the latitude and longitude tags inside it
+const GPS_IFD_TAG: u16 = 0x8825;
+const GPS_LATITUDE_TAG: u16 = 0x0002;
+const GPS_LONGITUDE_TAG: u16 = 0x0004;
+
+/// What `strip_metadata` made of an image.
+#[derive(Debug, PartialEq)]
+pub enum Stripped {
+    /// The image without its metadata.
+    Removed(Vec<u8>),
+    /// There was nothing to remove.
+    Clean,
+    /// A format this can't edit, such as HEIC, AVIF or TIFF, or a malformed file. It may
+    /// still hold metadata.
+    Unsupported,
+}
+
+/// `data` without its EXIF, XMP and text metadata: GPS position, camera serials, capture
+/// software and the like. Pixels and colour profiles are untouched, but the EXIF
+/// orientation goes too. JPEG, PNG and WebP are edited; GIF and BMP have no EXIF.
+pub fn strip_metadata(data: &[u8]) -> Stripped {
+    let stripped = if data.starts_with(&[0xFF, 0xD8]) {
+        strip_jpeg(data)
+    } else if data.starts_with(PNG_SIGNATURE) {
+        strip_png(data)
+    } else if is_webp(data) {
+        strip_webp(data)
+    } else if data.starts_with(b"GIF8") || data.starts_with(b"BM") {
+        return Stripped::Clean;
+    } else {
+        None
+    };
+    match stripped {
+        Some(stripped) if stripped.len() != data.len() => Stripped::Removed(stripped),
+        Some(_) => Stripped::Clean,
+        None => Stripped::Unsupported,
+    }
+}
+
+/// Whether the image's EXIF records where it was taken.
+pub fn has_location(data: &[u8]) -> bool {
+    exif_block(data).is_some_and(|tiff| tiff_has_gps(tiff).unwrap_or(false))
+}
+
+fn is_webp(data: &[u8]) -> bool {
+    data.len() >= 12 && &data[..4] == b"RIFF" && &data[8..12] == b"WEBP"
+}
+
+/// JPEG marker segments up to the start of scan, as (marker, payload, whole segment).
+fn jpeg_segments(data: &[u8]) -> Option<(Vec<(u8, &[u8], &[u8])>, &[u8])> {
+    let mut segments = Vec::new();
+    let mut position = 2;
+    loop {
+        if data.get(position)? != &0xFF {
+            return None;
+        }
+        // Any number of 0xFF fill bytes may pad the space before a marker
+        while data.get(position + 1) == Some(&0xFF) {
+            position += 1;
+        }
+        let marker = *data.get(position + 1)?;
+        if marker == 0xDA {
+            // Entropy-coded data follows; everything from here is kept as it is
+            return Some((segments, &data[position..]));
+        }
+        if (0xD0..=0xD7).contains(&marker) || marker == 0x01 {
+            segments.push((marker, &[][..], &data[position..position + 2]));
+            position += 2;
+            continue;
+        }
+        let length = u16::from_be_bytes([*data.get(position + 2)?, *data.get(position + 3)?]) as usize;
+        let end = position + 2 + length;
+        if length < 2 || end > data.len() {
+            return None;
+        }
+        segments.push((marker, &data[position + 4..end], &data[position..end]));
+        position = end;
+    }
+}
+
+fn strip_jpeg(data: &[u8]) -> Option<Vec<u8>> {
+    let (segments, scan) = jpeg_segments(data)?;
+    let mut output = data[..2].to_vec();
+    for (marker, payload, segment) in segments {
+        let metadata = (marker == 0xE1 && (payload.starts_with(EXIF_HEADER) || payload.starts_with(XMP_HEADER)))
+            || marker == 0xED; // Photoshop IRB, where IPTC location fields live
+        if !metadata {
+            output.extend_from_slice(segment);
+        }
+    }
+    output.extend_from_slice(scan);
+    Some(output)
+}
+
+/// PNG chunks as (type, data, whole chunk).
+fn png_chunks(data: &[u8]) -> Option<Vec<(&[u8], &[u8], &[u8])>> {
+    let mut chunks = Vec::new();
+    let mut position = PNG_SIGNATURE.len();
+    while position < data.len() {
+        let length = u32::from_be_bytes(data.get(position..position + 4)?.try_into().ok()?) as usize;
+        let end = position.checked_add(12 + length)?;
+        if end > data.len() {
+            return None;
+        }
+        chunks.push((&data[position + 4..position + 8], &data[position + 8..position + 8 + length], &data[position..end]));
+        position = end;
+    }
+    Some(chunks)
+}
+
+fn strip_png(data: &[u8]) -> Option<Vec<u8>> {
+    let mut output = PNG_SIGNATURE.to_vec();
+    for (kind, _, chunk) in png_chunks(data)? {
+        if !matches!(kind, b"eXIf" | b"tEXt" | b"zTXt" | b"iTXt") {
+            output.extend_from_slice(chunk);
+        }
+    }
+    Some(output)
+}
+
+/// WebP RIFF chunks as (FourCC, data, whole chunk with padding).
+fn webp_chunks(data: &[u8]) -> Option<Vec<(&[u8], &[u8], &[u8])>> {
+    let mut chunks = Vec::new();
+    let mut position = 12;
+    while position < data.len() {
+        let length = u32::from_le_bytes(data.get(position + 4..position + 8)?.try_into().ok()?) as usize;
+        let end = position.checked_add(8 + length + length % 2)?;
+        if position + 8 + length > data.len() {
+            return None;
+        }
+        chunks.push((&data[position..position + 4], &data[position + 8..position + 8 + length], &data[position..end.min(data.len())]));
+        position = end;
+    }
+    Some(chunks)
+}
+
+fn strip_webp(data: &[u8]) -> Option<Vec<u8>> {
+    let mut body = b"WEBP".to_vec();
+    for (kind, _, chunk) in webp_chunks(data)? {
+        match kind {
+            b"EXIF" | b"XMP " => {}
+            b"VP8X" if chunk.len() > 8 => {
+                // The header flags which metadata chunks follow
+                let mut chunk = chunk.to_vec();
+                chunk[8] &= !(0x08 | 0x04);
+                body.extend_from_slice(&chunk);
+            }
+            _ => body.extend_from_slice(chunk),
+        }
+    }
+    let mut output = b"RIFF".to_vec();
+    output.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    output.extend_from_slice(&body);
+    Some(output)
+}
+
+/// The TIFF structure holding an image's EXIF tags.
+fn exif_block(data: &[u8]) -> Option<&[u8]> {
+    if data.starts_with(&[0xFF, 0xD8]) {
+        let (segments, _) = jpeg_segments(data)?;
+        segments
+            .into_iter()
+            .find(|(marker, payload, _)| *marker == 0xE1 && payload.starts_with(EXIF_HEADER))
+            .map(|(_, payload, _)| &payload[EXIF_HEADER.len()..])
+    } else if data.starts_with(PNG_SIGNATURE) {
+        png_chunks(data)?.into_iter().find(|(kind, _, _)| *kind == b"eXIf").map(|(_, data, _)| data)
+    } else if is_webp(data) {
+        let exif = webp_chunks(data)?.into_iter().find(|(kind, _, _)| *kind == b"EXIF").map(|(_, data, _)| data)?;
+        Some(exif.strip_prefix(EXIF_HEADER).unwrap_or(exif))
+    } else {
+        None
+    }
+}
+
+/// Whether IFD0 points at a GPS IFD that holds a latitude or longitude; some cameras write
+/// an empty one.
+fn tiff_has_gps(tiff: &[u8]) -> Option<bool> {
+    let big_endian = match tiff.get(..2)? {
+        b"II" => false,
+        b"MM" => true,
+        _ => return None,
+    };
+    let u16_at = |offset: usize| -> Option<u16> {
+        let bytes: [u8; 2] = tiff.get(offset..offset + 2)?.try_into().ok()?;
+        Some(if big_endian { u16::from_be_bytes(bytes) } else { u16::from_le_bytes(bytes) })
+    };
+    let u32_at = |offset: usize| -> Option<u32> {
+        let bytes: [u8; 4] = tiff.get(offset..offset + 4)?.try_into().ok()?;
+        Some(if big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) })
+    };
+    // Tag and value field of each 12-byte IFD entry
+    let entries = |offset: usize| -> Option<Vec<(u16, u32)>> {
+        let count = u16_at(offset)? as usize;
+        (0..count)
+            .map(|i| {
+                let entry = offset + 2 + i * 12;
+                Some((u16_at(entry)?, u32_at(entry + 8)?))
+            })
+            .collect()
+    };
+
+    if u16_at(2)? != 42 {
+        return None;
+    }
+    let ifd0 = entries(u32_at(4)? as usize)?;
+    let Some((_, gps_offset)) = ifd0.into_iter().find(|(tag, _)| *tag == GPS_IFD_TAG) else {
+        return Some(false);
+    };
+    let gps = entries(gps_offset as usize)?;
+    Some(gps.iter().any(|(tag, _)| *tag == GPS_LATITUDE_TAG || *tag == GPS_LONGITUDE_TAG))
+}
+
+/// Image attachments already in the vault whose EXIF still holds a GPS position, e.g.
+/// uploaded before `strip_image_metadata` was turned on. Images are read one at a time.
+pub async fn audit_image_locations(database: &Database) -> AppResult<ImageLocationAudit> {
+    let ids = database.get_image_attachment_ids().await?;
+    let mut with_location = Vec::new();
+    for id in &ids {
+        let Some(media) = database.get_media_attachment(id).await? else {
+            continue;
+        };
+        if has_location(&media.file_data) {
+            with_location.push(LocatedAttachment {
+                id: media.id,
+                original_filename: media.original_filename,
+                page_id: media.page_id,
+                note_id: media.note_id,
+            });
+        }
+    }
+    Ok(ImageLocationAudit { scanned: ids.len(), with_location })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Little-endian TIFF with IFD0 pointing at a GPS IFD holding a latitude.
+    fn tiff_with_gps() -> Vec<u8> {
+        let mut tiff = b"II".to_vec();
+        tiff.extend_from_slice(&42u16.to_le_bytes());
+        tiff.extend_from_slice(&8u32.to_le_bytes());
+        // IFD0 at 8: one entry, GPSInfo -> 26
+        tiff.extend_from_slice(&1u16.to_le_bytes());
+        tiff.extend_from_slice(&GPS_IFD_TAG.to_le_bytes());
+        tiff.extend_from_slice(&4u16.to_le_bytes());
+        tiff.extend_from_slice(&1u32.to_le_bytes());
+        tiff.extend_from_slice(&26u32.to_le_bytes());
+        tiff.extend_from_slice(&0u32.to_le_bytes());
+        // GPS IFD at 26: one latitude entry
+        tiff.extend_from_slice(&1u16.to_le_bytes());
+        tiff.extend_from_slice(&GPS_LATITUDE_TAG.to_le_bytes());
+        tiff.extend_from_slice(&[5, 0, 3, 0, 0, 0, 0, 0, 0, 0]);
+        tiff.extend_from_slice(&0u32.to_le_bytes());
+        tiff
+    }
+
+    fn jpeg_segment(marker: u8, payload: &[u8]) -> Vec<u8> {
+        let mut segment = vec![0xFF, marker];
+        segment.extend_from_slice(&((payload.len() + 2) as u16).to_be_bytes());
+        segment.extend_from_slice(payload);
+        segment
+    }
+
+    #[test]
+    fn test_jpeg_exif_is_found_and_stripped() {
+        let mut exif = EXIF_HEADER.to_vec();
+        exif.extend_from_slice(&tiff_with_gps());
+        let jfif = jpeg_segment(0xE0, b"JFIF\0\x01\x01\0\0\x01\0\x01\0\0");
+        let mut jpeg = vec![0xFF, 0xD8];
+        jpeg.extend_from_slice(&jfif);
+        jpeg.extend_from_slice(&jpeg_segment(0xE1, &exif));
+        jpeg.extend_from_slice(&[0xFF, 0xDA, 0x00, 0x02, 0x12, 0x34, 0xFF, 0xD9]);
+
+        assert!(has_location(&jpeg));
+        let Stripped::Removed(stripped) = strip_metadata(&jpeg) else { panic!("nothing was stripped") };
+        assert!(!has_location(&stripped));
+        let mut expected = vec![0xFF, 0xD8];
+        expected.extend_from_slice(&jfif);
+        expected.extend_from_slice(&[0xFF, 0xDA, 0x00, 0x02, 0x12, 0x34, 0xFF, 0xD9]);
+        assert_eq!(stripped, expected);
+        assert_eq!(strip_metadata(&stripped), Stripped::Clean);
+    }
+
+    #[test]
+    fn test_jpeg_fill_bytes_and_unsupported_formats() {
+        let mut exif = EXIF_HEADER.to_vec();
+        exif.extend_from_slice(&tiff_with_gps());
+        let mut jpeg = vec![0xFF, 0xD8, 0xFF, 0xFF];
+        jpeg.extend_from_slice(&jpeg_segment(0xE1, &exif));
+        jpeg.extend_from_slice(&[0xFF, 0xFF, 0xFF, 0xDA, 0x00, 0x02, 0x12, 0x34, 0xFF, 0xD9]);
+
+        assert!(has_location(&jpeg));
+        assert_eq!(strip_metadata(&jpeg), Stripped::Removed(vec![0xFF, 0xD8, 0xFF, 0xDA, 0x00, 0x02, 0x12, 0x34, 0xFF, 0xD9]));
+        assert_eq!(strip_metadata(b"II*\0\x08\0\0\0"), Stripped::Unsupported);
+        assert_eq!(strip_metadata(b"\0\0\0\x18ftypheic"), Stripped::Unsupported);
+        assert_eq!(strip_metadata(&[0xFF, 0xD8, 0x00]), Stripped::Unsupported);
+    }
+
+    #[test]
+    fn test_png_text_chunks_are_stripped() {
+        let chunk = |kind: &[u8], data: &[u8]| {
+            let mut chunk = (data.len() as u32).to_be_bytes().to_vec();
+            chunk.extend_from_slice(kind);
+            chunk.extend_from_slice(data);
+            chunk.extend_from_slice(&[0, 0, 0, 0]);
+            chunk
+        };
+        let mut png = PNG_SIGNATURE.to_vec();
+        png.extend(chunk(b"IHDR", &[0; 13]));
+        png.extend(chunk(b"eXIf", &tiff_with_gps()));
+        png.extend(chunk(b"tEXt", b"Author\0me"));
+        png.extend(chunk(b"IEND", &[]));
+
+        assert!(has_location(&png));
+        let Stripped::Removed(stripped) = strip_metadata(&png) else { panic!("nothing was stripped") };
+        assert_eq!(png_chunks(&stripped).unwrap().iter().map(|(kind, _, _)| *kind).collect::<Vec<_>>(), vec![&b"IHDR"[..], &b"IEND"[..]]);
+        assert_eq!(strip_metadata(b"GIF89a"), Stripped::Clean);
+    }
+}
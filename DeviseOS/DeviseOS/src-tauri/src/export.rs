@@ -0,0 +1,503 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use crate::{
+    AppError, AppResult,
+    models::{ExportFormat, ExportType, NotebookExport, Page, ReExportFailure, ReExportSummary, StatsFormat, StatsRange, TextDirection, UsageStats},
+    database::Database,
+    ai::AIService,
+    locale::{self, LocaleFormatter},
+    transclusion,
+};
+
+const PRINT_STYLES: &str = "body{font-family:system-ui,sans-serif;max-width:42rem;margin:2rem auto;line-height:1.6}\
+[dir=rtl]{text-align:right}[dir=ltr]{text-align:left}\
+.meta{color:#666;font-size:.875rem}\
+@page{margin:2cm}";
+
+/// What the previous `export_notebook` run to a destination wrote, kept next to the files.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct ExportManifest {
+    notebook_id: String,
+    format: Option<ExportFormat>,
+    locale: Option<String>,
+    exported_at: Option<DateTime<Utc>>,
+    pages: BTreeMap<String, ManifestEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct ManifestEntry {
+    file: String, // relative to the destination
+    updated_at: DateTime<Utc>,
+}
+
+/// Pages to write again, pages whose file is current, and files of pages that are gone.
+struct ManifestDiff<'a> {
+    changed: Vec<&'a Page>,
+    unchanged: Vec<(&'a Page, ManifestEntry)>,
+    deleted: Vec<ManifestEntry>,
+}
+
+/// Writes one page to `destination` in the requested format and returns the file path.
+/// The export is recorded so `re_export_all` can refresh it later. PDF is produced by
+/// printing `render_html` from the webview, so it isn't written here. With `translate_to`
+/// set, the title, content and transcriptions are translated into a `<title>.<lang>` file.
+pub async fn export_page(
+    database: &Database,
+    ai_service: &AIService,
+    page_id: &str,
+    format: &ExportFormat,
+    destination: &Path,
+    locale_override: Option<&str>,
+) -> AppResult<PathBuf> {
+    let mut page = database.get_page(page_id).await?
+        .ok_or_else(|| AppError::NotFound(format!("Page with id {} not found", page_id)))?;
+    let formatter = locale::formatter(database, locale_override).await?;
+    let mut transcriptions = if format.include_voice_annotations {
+        database.get_page_transcriptions(page_id).await?
+    } else {
+        Vec::new()
+    };
+
+    // JSON keeps the page as stored; the readable formats show embeds in place
+    if !matches!(format.format, ExportType::JSON) {
+        page.content = transclusion::resolve(database, &page).await?.content;
+    }
+
+    let stem = file_stem(&page.title, &page.id);
+    let mut language_suffix = String::new();
+    if let Some(target_lang) = &format.translate_to {
+        page.title = ai_service.translate_text(&page.title, target_lang).await?;
+        page.content = ai_service.translate_text(&page.content, target_lang).await?;
+        for transcription in transcriptions.iter_mut() {
+            *transcription = ai_service.translate_text(transcription, target_lang).await?;
+        }
+        page.metadata.language = Some(target_lang.trim().to_string());
+        page.metadata.direction = TextDirection::detect(&page.content);
+        language_suffix = format!(".{}", target_lang.trim());
+    }
+
+    let (extension, output) = match format.format {
+        ExportType::HTML => ("html", render_html(&page, format, &transcriptions, &formatter)),
+        ExportType::Markdown => ("md", render_markdown(&page, format, &transcriptions, &formatter)),
+        ExportType::TXT => ("txt", render_text(&page, format, &transcriptions)),
+        ExportType::JSON => ("json", serde_json::to_string_pretty(&page)?),
+        ExportType::PDF => {
+            return Err(AppError::InvalidFormat(
+                "PDF export is printed from the HTML rendering; use render_page_html".to_string(),
+            ))
+        }
+    };
+
+    tokio::fs::create_dir_all(destination).await?;
+    let file_name = |stem: &str| format!("{}{}.{}", stem, language_suffix, extension);
+    let mut path = destination.join(file_name(&stem));
+    // Pages with the same title would otherwise overwrite each other's file
+    let taken = database.get_latest_exports(destination).await?
+        .iter()
+        .any(|record| record.page_id != page_id && record.path == path);
+    if taken {
+        path = destination.join(file_name(&disambiguated_stem(&stem, page_id)));
+    }
+    tokio::fs::write(&path, output).await?;
+    database.record_export(page_id, format, locale_override, destination, &path).await?;
+    Ok(path)
+}
+
+/// Exports every page again whose latest export went to `previous_destination`, with the
+/// format and locale it was exported with. When a renamed page gets a new file name, the
+/// old file is removed so the mirror doesn't keep stale copies.
+pub async fn re_export_all(database: &Database, ai_service: &AIService, previous_destination: &Path) -> AppResult<ReExportSummary> {
+    let records = database.get_latest_exports(previous_destination).await?;
+    if records.is_empty() {
+        return Err(AppError::NotFound(format!("No exports to {} found", previous_destination.display())));
+    }
+
+    let mut summary = ReExportSummary { exported: Vec::new(), failed: Vec::new() };
+    for record in records {
+        match export_page(database, ai_service, &record.page_id, &record.format, &record.destination, record.locale.as_deref()).await {
+            Ok(path) => {
+                if path != record.path && record.path.starts_with(&record.destination) {
+                    let _ = tokio::fs::remove_file(&record.path).await;
+                }
+                summary.exported.push(path);
+            }
+            Err(e) => summary.failed.push(ReExportFailure { page_id: record.page_id, error: e.to_string() }),
+        }
+    }
+
+    tracing::info!("Re-exported {} pages to {} ({} failed)", summary.exported.len(), previous_destination.display(), summary.failed.len());
+    Ok(summary)
+}
+
+/// Exports every page of a notebook into `destination`. With `incremental`, only pages
+/// changed since the previous run to the same destination are written, based on the
+/// manifest that run left there; a different format or locale exports everything again.
+/// Either way, files of pages deleted since then are removed.
+pub async fn export_notebook(
+    database: &Database,
+    ai_service: &AIService,
+    notebook_id: &str,
+    format: &ExportFormat,
+    destination: &Path,
+    locale_override: Option<&str>,
+    incremental: bool,
+) -> AppResult<NotebookExport> {
+    if matches!(format.format, ExportType::PDF) {
+        return Err(AppError::InvalidFormat(
+            "PDF export is printed from the HTML rendering; use render_page_html".to_string(),
+        ));
+    }
+    database.get_notebook(notebook_id).await?
+        .ok_or_else(|| AppError::NotFound(format!("Notebook with id {} not found", notebook_id)))?;
+
+    let manifest_path = destination.join(manifest_file_name(notebook_id));
+    let previous = match tokio::fs::read(&manifest_path).await {
+        Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+            tracing::warn!("Ignoring unreadable export manifest {}: {}", manifest_path.display(), e);
+            ExportManifest::default()
+        }),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => ExportManifest::default(),
+        Err(e) => return Err(e.into()),
+    };
+    let same_settings = previous.format.as_ref().map(serde_json::to_value).transpose()? == Some(serde_json::to_value(format)?)
+        && previous.locale.as_deref() == locale_override;
+
+    let pages = database.get_pages(notebook_id, None).await?;
+    let diff = diff_manifest(&previous, &pages);
+    let mut manifest = ExportManifest {
+        notebook_id: notebook_id.to_string(),
+        format: Some(format.clone()),
+        locale: locale_override.map(str::to_string),
+        exported_at: Some(Utc::now()),
+        pages: BTreeMap::new(),
+    };
+    let mut result = NotebookExport { exported: Vec::new(), unchanged: 0, removed: Vec::new(), failed: Vec::new() };
+
+    let mut to_export = diff.changed;
+    for (page, entry) in diff.unchanged {
+        // A file removed at the destination is written again
+        if incremental && same_settings && tokio::fs::try_exists(destination.join(&entry.file)).await? {
+            manifest.pages.insert(page.id.clone(), entry);
+            result.unchanged += 1;
+        } else {
+            to_export.push(page);
+        }
+    }
+
+    for page in to_export {
+        match export_page(database, ai_service, &page.id, format, destination, locale_override).await {
+            Ok(path) => {
+                let file = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+                // A renamed page leaves its old file behind otherwise
+                if let Some(old) = previous.pages.get(&page.id).filter(|old| old.file != file) {
+                    remove_exported_file(destination, &old.file).await?;
+                }
+                manifest.pages.insert(page.id.clone(), ManifestEntry { file, updated_at: page.updated_at });
+                result.exported.push(path);
+            }
+            Err(e) => result.failed.push(ReExportFailure { page_id: page.id.clone(), error: e.to_string() }),
+        }
+    }
+
+    for entry in diff.deleted {
+        if let Some(path) = remove_exported_file(destination, &entry.file).await? {
+            result.removed.push(path);
+        }
+    }
+
+    tokio::fs::create_dir_all(destination).await?;
+    tokio::fs::write(&manifest_path, serde_json::to_string_pretty(&manifest)?).await?;
+    tracing::info!(
+        "Exported notebook {} to {}: {} written, {} unchanged, {} removed, {} failed",
+        notebook_id, destination.display(), result.exported.len(), result.unchanged, result.removed.len(), result.failed.len()
+    );
+    Ok(result)
+}
+
+/// One manifest per notebook, so several notebooks can share a destination.
+fn manifest_file_name(notebook_id: &str) -> String {
+    format!(".deviseos-export-{}.json", notebook_id)
+}
+
+fn diff_manifest<'a>(previous: &ExportManifest, pages: &'a [Page]) -> ManifestDiff<'a> {
+    let mut diff = ManifestDiff { changed: Vec::new(), unchanged: Vec::new(), deleted: Vec::new() };
+    for page in pages {
+        match previous.pages.get(&page.id) {
+            Some(entry) if entry.updated_at == page.updated_at => diff.unchanged.push((page, entry.clone())),
+            _ => diff.changed.push(page),
+        }
+    }
+    diff.deleted = previous
+        .pages
+        .iter()
+        .filter(|(id, _)| !pages.iter().any(|page| &page.id == *id))
+        .map(|(_, entry)| entry.clone())
+        .collect();
+    diff
+}
+
+/// Only plain file names from a manifest are removed, so an edited manifest can't point
+/// outside the destination.
+async fn remove_exported_file(destination: &Path, file: &str) -> AppResult<Option<PathBuf>> {
+    if file.is_empty() || Path::new(file).file_name().map(|name| name != file).unwrap_or(true) {
+        return Ok(None);
+    }
+    let path = destination.join(file);
+    match tokio::fs::remove_file(&path).await {
+        Ok(()) => Ok(Some(path)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Writes vault statistics for `range` to `destination` for analysis in external tools:
+/// one CSV per dataset, or a single JSON file. Returns the written paths.
+pub async fn export_stats(database: &Database, range: &StatsRange, format: StatsFormat, destination: &Path) -> AppResult<Vec<PathBuf>> {
+    let stats = database.get_usage_stats(range).await?;
+    tokio::fs::create_dir_all(destination).await?;
+
+    let files = match format {
+        StatsFormat::Json => vec![("vault_stats.json", serde_json::to_string_pretty(&stats)?)],
+        StatsFormat::Csv => stats_csv(&stats)?,
+    };
+    let mut paths = Vec::new();
+    for (name, output) in files {
+        let path = destination.join(name);
+        tokio::fs::write(&path, output).await?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+fn stats_csv(stats: &UsageStats) -> AppResult<Vec<(&'static str, String)>> {
+    Ok(vec![
+        ("pages_per_day.csv", to_csv(&stats.pages_per_day)?),
+        ("words_per_notebook.csv", to_csv(&stats.words_per_notebook)?),
+        ("tag_usage.csv", to_csv(&stats.tag_usage)?),
+    ])
+}
+
+/// Header row from the field names, then one row per record.
+fn to_csv<T: serde::Serialize>(records: &[T]) -> AppResult<String> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for record in records {
+        writer.serialize(record).map_err(|e| AppError::InvalidFormat(format!("CSV error: {}", e)))?;
+    }
+    let bytes = writer.into_inner().map_err(|e| e.into_error())?;
+    String::from_utf8(bytes).map_err(|e| AppError::InvalidFormat(e.to_string()))
+}
+
+/// Self-contained HTML for a page. Each paragraph carries its own `dir` so mixed
+/// Arabic/Hebrew and Latin content aligns correctly in browsers and when printed.
+pub fn render_html(page: &Page, format: &ExportFormat, transcriptions: &[String], formatter: &LocaleFormatter) -> String {
+    let language = page.metadata.language.as_deref().unwrap_or(formatter.locale());
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"{}\" dir=\"{}\">\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>{}</style>\n</head>\n<body>\n{}</body>\n</html>\n",
+        escape_html(language),
+        page_direction(page),
+        escape_html(&page.title),
+        PRINT_STYLES,
+        render_html_body(page, format, transcriptions, formatter)
+    )
+}
+
+/// Mixed pages keep an LTR frame; paragraphs override it.
+pub fn page_direction(page: &Page) -> &'static str {
+    match page.metadata.direction {
+        TextDirection::Rtl => "rtl",
+        _ => "ltr",
+    }
+}
+
+/// The `<body>` contents of `render_html`, for embedding a page in another document.
+pub fn render_html_body(page: &Page, format: &ExportFormat, transcriptions: &[String], formatter: &LocaleFormatter) -> String {
+    let mut body = String::new();
+    let title_direction = TextDirection::of_paragraph(&page.title).unwrap_or(page.metadata.direction);
+    body.push_str(&format!("<h1 dir=\"{}\">{}</h1>\n", title_direction.as_html(), escape_html(&page.title)));
+
+    for paragraph in paragraphs(&page.content) {
+        let direction = TextDirection::of_paragraph(paragraph).unwrap_or(page.metadata.direction);
+        let (tag, text) = heading(paragraph);
+        let lines: Vec<String> = text.lines().map(escape_html).collect();
+        body.push_str(&format!("<{tag} dir=\"{}\">{}</{tag}>\n", direction.as_html(), lines.join("<br>"), tag = tag));
+    }
+
+    if format.include_voice_annotations && !transcriptions.is_empty() {
+        body.push_str("<h2>Voice annotations</h2>\n");
+        for transcription in transcriptions {
+            let direction = TextDirection::of_paragraph(transcription).unwrap_or(TextDirection::Ltr);
+            body.push_str(&format!("<blockquote dir=\"{}\">{}</blockquote>\n", direction.as_html(), escape_html(transcription)));
+        }
+    }
+    if format.include_tags && !page.tags.is_empty() {
+        let tags: Vec<String> = page.tags.iter().map(|t| format!("<bdi>#{}</bdi>", escape_html(t))).collect();
+        body.push_str(&format!("<p class=\"meta\">{}</p>\n", tags.join(" ")));
+    }
+    if format.include_metadata {
+        body.push_str(&format!("<p class=\"meta\">{}</p>\n", escape_html(&metadata_line(page, formatter))));
+    }
+    body
+}
+
+fn render_markdown(page: &Page, format: &ExportFormat, transcriptions: &[String], formatter: &LocaleFormatter) -> String {
+    let mut output = format!("# {}\n\n{}\n", page.title, page.content.trim_end());
+    if format.include_voice_annotations && !transcriptions.is_empty() {
+        output.push_str("\n## Voice annotations\n\n");
+        for transcription in transcriptions {
+            output.push_str(&format!("> {}\n\n", transcription));
+        }
+    }
+    if format.include_tags && !page.tags.is_empty() {
+        let tags: Vec<String> = page.tags.iter().map(|t| format!("#{}", t)).collect();
+        output.push_str(&format!("\n{}\n", tags.join(" ")));
+    }
+    if format.include_metadata {
+        output.push_str(&format!("\n_{}_\n", metadata_line(page, formatter)));
+    }
+    output
+}
+
+fn render_text(page: &Page, format: &ExportFormat, transcriptions: &[String]) -> String {
+    let mut output = format!("{}\n\n{}\n", page.title, page.content.trim_end());
+    if format.include_voice_annotations {
+        for transcription in transcriptions {
+            output.push_str(&format!("\n{}\n", transcription));
+        }
+    }
+    if format.include_tags && !page.tags.is_empty() {
+        output.push_str(&format!("\n{}\n", page.tags.join(", ")));
+    }
+    output
+}
+
+fn metadata_line(page: &Page, formatter: &LocaleFormatter) -> String {
+    format!(
+        "{} · {} words · {}",
+        formatter.format_datetime(&page.updated_at),
+        formatter.format_count(page.metadata.word_count as u64),
+        formatter.format_reading_time(&page.content)
+    )
+}
+
+fn paragraphs(content: &str) -> impl Iterator<Item = &str> {
+    content.split("\n\n").map(|p| p.trim_matches('\n')).filter(|p| !p.trim().is_empty())
+}
+
+/// Markdown `#` headings become `h2`–`h4`; the page title is the only `h1`.
+fn heading(paragraph: &str) -> (&'static str, &str) {
+    let level = paragraph.chars().take_while(|c| *c == '#').count();
+    match (level, paragraph[level..].strip_prefix(' ')) {
+        (1, Some(text)) => ("h2", text),
+        (2, Some(text)) => ("h3", text),
+        (3..=6, Some(text)) => ("h4", text),
+        _ => ("p", paragraph),
+    }
+}
+
+pub fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+pub fn file_stem(title: &str, id: &str) -> String {
+    let stem: String = title
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' || c == ' ' { c } else { '_' })
+        .collect();
+    let stem = stem.trim();
+    if stem.is_empty() {
+        id.to_string()
+    } else {
+        stem.to_string()
+    }
+}
+
+/// `stem` with the start of the page id, for a page whose title another exported page has.
+fn disambiguated_stem(stem: &str, page_id: &str) -> String {
+    format!("{} ({})", stem, page_id.chars().take(8).collect::<String>())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{NotebookWordCount, PagesPerDay};
+
+    fn html_format() -> ExportFormat {
+        ExportFormat { format: ExportType::HTML, include_metadata: false, include_voice_annotations: false, include_tags: false, translate_to: None }
+    }
+
+    #[test]
+    fn test_direction_detection() {
+        assert_eq!(TextDirection::detect("Hello"), TextDirection::Ltr);
+        assert_eq!(TextDirection::detect("שלום עולם\n\n123"), TextDirection::Rtl);
+        assert_eq!(TextDirection::detect("مرحبا\n\nHello"), TextDirection::Mixed);
+        assert_eq!(TextDirection::of_paragraph("2024 - مرحبا"), Some(TextDirection::Rtl));
+        assert_eq!(TextDirection::of_paragraph("2024"), None);
+    }
+
+    #[test]
+    fn test_html_sets_direction_per_paragraph() {
+        let page = Page::new("nb".to_string(), None, None, "Notes".to_string(), "Hello\n\n# مرحبا\n\nשלום".to_string(), Vec::new());
+        let html = render_html(&page, &html_format(), &[], &LocaleFormatter::new("en-US"));
+
+        assert!(html.contains("<html lang=\"en-US\" dir=\"ltr\">"));
+        assert!(html.contains("<p dir=\"ltr\">Hello</p>"));
+        assert!(html.contains("<h2 dir=\"rtl\">مرحبا</h2>"));
+        assert!(html.contains("<p dir=\"rtl\">שלום</p>"));
+    }
+
+    #[test]
+    fn test_rtl_page_frame_and_escaping() {
+        let page = Page::new("nb".to_string(), None, None, "<b>".to_string(), "مرحبا & 1 < 2".to_string(), Vec::new());
+        let html = render_html(&page, &html_format(), &[], &LocaleFormatter::new("ar"));
+
+        assert!(html.contains("dir=\"rtl\">\n<head>"));
+        assert!(html.contains("مرحبا &amp; 1 &lt; 2"));
+        assert!(html.contains("<h1 dir=\"rtl\">&lt;b&gt;</h1>"));
+    }
+
+    #[test]
+    fn test_diff_manifest() {
+        let kept = Page::new("nb".to_string(), None, None, "Kept".to_string(), String::new(), Vec::new());
+        let edited = Page::new("nb".to_string(), None, None, "Edited".to_string(), String::new(), Vec::new());
+        let added = Page::new("nb".to_string(), None, None, "Added".to_string(), String::new(), Vec::new());
+        let entry = |file: &str, updated_at| ManifestEntry { file: file.to_string(), updated_at };
+
+        let mut previous = ExportManifest::default();
+        previous.pages.insert(kept.id.clone(), entry("Kept.md", kept.updated_at));
+        previous.pages.insert(edited.id.clone(), entry("Edited.md", edited.updated_at - chrono::Duration::minutes(5)));
+        previous.pages.insert("deleted".to_string(), entry("Deleted.md", kept.updated_at));
+
+        let pages = vec![kept.clone(), edited.clone(), added.clone()];
+        let diff = diff_manifest(&previous, &pages);
+        let changed: Vec<&str> = diff.changed.iter().map(|page| page.title.as_str()).collect();
+        assert_eq!(changed, vec!["Edited", "Added"]);
+        assert_eq!(diff.unchanged.len(), 1);
+        assert_eq!(diff.unchanged[0].1.file, "Kept.md");
+        assert_eq!(diff.deleted, vec![entry("Deleted.md", kept.updated_at)]);
+    }
+
+    #[test]
+    fn test_stats_csv_quotes_fields() {
+        let stats = UsageStats {
+            range: StatsRange::default(),
+            pages_per_day: vec![PagesPerDay { date: "2024-03-01".to_string(), pages_created: 2 }],
+            words_per_notebook: vec![NotebookWordCount {
+                notebook_id: "nb".to_string(),
+                notebook_title: "Work, \"Q1\"".to_string(),
+                page_count: 2,
+                word_count: 340,
+            }],
+            tag_usage: Vec::new(),
+        };
+        let files = stats_csv(&stats).unwrap();
+
+        assert_eq!(files[0].1, "date,pages_created\n2024-03-01,2\n");
+        assert_eq!(files[1].1, "notebook_id,notebook_title,page_count,word_count\nnb,\"Work, \"\"Q1\"\"\",2,340\n");
+        assert_eq!(files[2].1, "");
+    }
+}
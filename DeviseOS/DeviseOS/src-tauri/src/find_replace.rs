@@ -0,0 +1,159 @@
+use regex::{NoExpand, Regex, RegexBuilder};
+use tauri::{AppHandle, Emitter};
+use crate::{
+    AppError, AppResult,
+    models::{FindReplaceRequest, FindReplaceResult, PageReplacementPreview, ReplacementPreview, SearchMatchField},
+    database::Database,
+};
+
+pub const FIND_REPLACE_MATCH_EVENT: &str = "find-replace-match";
+const CONTEXT_BYTES: usize = 40;
+const REVISION_REASON: &str = "find_replace";
+const MAX_PATTERN_SIZE: usize = 1 << 20;
+
+/// Finds `query` in the titles and content of pages in scope, emitting a
+/// `find-replace-match` event per matching page as it goes. Unless this is a dry run, the
+/// replacements are then applied in one transaction, saving each page's previous version
+/// as a revision.
+pub async fn find_replace(app: &AppHandle, database: &Database, request: &FindReplaceRequest) -> AppResult<FindReplaceResult> {
+    let pattern = build_pattern(&request.query, request.regex, request.case_sensitive)?;
+
+    let mut result = FindReplaceResult { pages: Vec::new(), total_matches: 0, applied: false };
+    let mut edits = Vec::new();
+    for id in database.get_page_ids_in_scope(&request.scope).await? {
+        let Some(page) = database.get_page(&id).await? else {
+            continue;
+        };
+
+        let mut matches = preview_matches(SearchMatchField::Title, &page.title, &pattern, &request.replacement, request.regex);
+        matches.extend(preview_matches(SearchMatchField::Content, &page.content, &pattern, &request.replacement, request.regex));
+        if matches.is_empty() {
+            continue;
+        }
+
+        let preview = PageReplacementPreview { page_id: page.id.clone(), title: page.title.clone(), matches };
+        let _ = app.emit(FIND_REPLACE_MATCH_EVENT, &preview);
+        result.total_matches += preview.matches.len();
+        result.pages.push(preview);
+
+        if !request.dry_run {
+            let title = replace_text(&page.title, &pattern, &request.replacement, request.regex);
+            let content = replace_text(&page.content, &pattern, &request.replacement, request.regex);
+            edits.push((page, title, content));
+        }
+    }
+
+    if !edits.is_empty() {
+        database.apply_page_edits(&edits, REVISION_REASON).await?;
+        result.applied = true;
+        tracing::info!("Replaced {} matches across {} pages", result.total_matches, edits.len());
+    }
+    Ok(result)
+}
+
+pub fn build_pattern(query: &str, regex: bool, case_sensitive: bool) -> AppResult<Regex> {
+    if query.is_empty() {
+        return Err(AppError::InvalidFormat("Search text cannot be empty".to_string()));
+    }
+    let source = if regex { query.to_string() } else { regex::escape(query) };
+    let pattern = RegexBuilder::new(&source)
+        .case_insensitive(!case_sensitive)
+        .size_limit(MAX_PATTERN_SIZE)
+        .build()
+        .map_err(|e| AppError::InvalidFormat(format!("Invalid pattern: {}", e)))?;
+
+    // A pattern like `a*` would insert the replacement between every character
+    if pattern.is_match("") {
+        return Err(AppError::InvalidFormat("Pattern must not match empty text".to_string()));
+    }
+    Ok(pattern)
+}
+
+fn preview_matches(field: SearchMatchField, text: &str, pattern: &Regex, replacement: &str, regex: bool) -> Vec<ReplacementPreview> {
+    pattern
+        .captures_iter(text)
+        .filter_map(|captures| {
+            let matched = captures.get(0)?;
+            let replacement = if regex {
+                let mut expanded = String::new();
+                captures.expand(replacement, &mut expanded);
+                expanded
+            } else {
+                replacement.to_string()
+            };
+            Some(ReplacementPreview {
+                field,
+                start: matched.start(),
+                end: matched.end(),
+                matched: matched.as_str().to_string(),
+                replacement,
+                context: context(text, matched.start(), matched.end()),
+            })
+        })
+        .collect()
+}
+
+fn replace_text(text: &str, pattern: &Regex, replacement: &str, regex: bool) -> String {
+    if regex {
+        pattern.replace_all(text, replacement).into_owned()
+    } else {
+        pattern.replace_all(text, NoExpand(replacement)).into_owned()
+    }
+}
+
+/// The match with up to `CONTEXT_BYTES` either side, cut on character boundaries.
+fn context(text: &str, start: usize, end: usize) -> String {
+    let mut from = start.saturating_sub(CONTEXT_BYTES);
+    while !text.is_char_boundary(from) {
+        from -= 1;
+    }
+    let mut to = (end + CONTEXT_BYTES).min(text.len());
+    while !text.is_char_boundary(to) {
+        to += 1;
+    }
+    text[from..to].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_literal_replacement_is_escaped_and_case_insensitive() {
+        let pattern = build_pattern("Project (X)", false, false).unwrap();
+        let text = "project (x) and Project (X), not Project X";
+        assert_eq!(replace_text(text, &pattern, "$1 Apollo", false), "$1 Apollo and $1 Apollo, not Project X");
+
+        let matches = preview_matches(SearchMatchField::Content, text, &pattern, "Apollo", false);
+        assert_eq!(matches.len(), 2);
+        assert_eq!((matches[1].start, matches[1].end), (16, 27));
+        assert_eq!(matches[1].matched, "Project (X)");
+    }
+
+    #[test]
+    fn test_regex_replacement_expands_captures() {
+        let pattern = build_pattern(r"INV-(\d+)", true, true).unwrap();
+        let text = "Paid INV-042, inv-7 pending";
+        assert_eq!(replace_text(text, &pattern, "Invoice $1", true), "Paid Invoice 042, inv-7 pending");
+
+        let matches = preview_matches(SearchMatchField::Title, text, &pattern, "Invoice $1", true);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].replacement, "Invoice 042");
+    }
+
+    #[test]
+    fn test_rejects_empty_and_invalid_patterns() {
+        assert!(build_pattern("", false, false).is_err());
+        assert!(build_pattern("a*", true, false).is_err());
+        assert!(build_pattern("(", true, false).is_err());
+    }
+
+    #[test]
+    fn test_context_respects_char_boundaries() {
+        let text = format!("{}target{}", "é".repeat(30), "ü".repeat(30));
+        let start = text.find("target").unwrap();
+        let snippet = context(&text, start, start + 6);
+        assert!(snippet.contains("target"));
+        assert!(snippet.len() <= 6 + 2 * CONTEXT_BYTES + 2);
+    }
+}
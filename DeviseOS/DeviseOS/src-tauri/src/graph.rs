@@ -0,0 +1,131 @@
+use std::collections::{HashMap, HashSet};
+use crate::models::{GraphHub, GraphMetrics, GraphPage};
+
+/// Hubs listed in `GraphMetrics`.
+pub const HUB_COUNT: usize = 10;
+
+/// Orphans, hubs and connected components of the pages in scope (id, title), given every
+/// link (source, target) with at least one end among them. Links leaving the scope keep a
+/// page from being an orphan but don't join components; link direction is ignored there.
+pub fn metrics(notebook_id: Option<String>, pages: &[(String, String)], links: &[(String, String)]) -> GraphMetrics {
+    let in_scope: HashMap<&str, usize> = pages.iter().enumerate().map(|(i, (id, _))| (id.as_str(), i)).collect();
+    let links: HashSet<(&str, &str)> = links
+        .iter()
+        .filter(|(source, target)| source != target)
+        .map(|(source, target)| (source.as_str(), target.as_str()))
+        .collect();
+
+    let mut incoming = vec![0usize; pages.len()];
+    let mut outgoing = vec![0usize; pages.len()];
+    let mut components = DisjointSet::new(pages.len());
+    for (source, target) in &links {
+        let source = in_scope.get(source).copied();
+        let target = in_scope.get(target).copied();
+        if let Some(source) = source {
+            outgoing[source] += 1;
+        }
+        if let Some(target) = target {
+            incoming[target] += 1;
+        }
+        if let (Some(source), Some(target)) = (source, target) {
+            components.union(source, target);
+        }
+    }
+
+    let orphans = pages
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| incoming[*i] == 0 && outgoing[*i] == 0)
+        .map(|(_, (id, title))| GraphPage { id: id.clone(), title: title.clone() })
+        .collect();
+
+    let mut hubs: Vec<GraphHub> = pages
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| incoming[*i] > 0)
+        .map(|(i, (id, title))| GraphHub { id: id.clone(), title: title.clone(), incoming: incoming[i], outgoing: outgoing[i] })
+        .collect();
+    hubs.sort_by(|a, b| {
+        b.incoming.cmp(&a.incoming)
+            .then_with(|| b.outgoing.cmp(&a.outgoing))
+            .then_with(|| a.title.cmp(&b.title))
+    });
+    hubs.truncate(HUB_COUNT);
+
+    let mut groups: HashMap<usize, Vec<String>> = HashMap::new();
+    for (i, (id, _)) in pages.iter().enumerate() {
+        groups.entry(components.find(i)).or_default().push(id.clone());
+    }
+    let mut components: Vec<Vec<String>> = groups.into_values().collect();
+    components.sort_by(|a, b| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+
+    GraphMetrics {
+        notebook_id,
+        page_count: pages.len(),
+        link_count: links.len(),
+        orphans,
+        hubs,
+        components,
+    }
+}
+
+struct DisjointSet {
+    parents: Vec<usize>,
+}
+
+impl DisjointSet {
+    fn new(size: usize) -> Self {
+        Self { parents: (0..size).collect() }
+    }
+
+    fn find(&mut self, item: usize) -> usize {
+        let mut root = item;
+        while self.parents[root] != root {
+            root = self.parents[root];
+        }
+        // Point the whole path at the root so later finds are short
+        let mut item = item;
+        while self.parents[item] != root {
+            let next = self.parents[item];
+            self.parents[item] = root;
+            item = next;
+        }
+        root
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        if a != b {
+            self.parents[b] = a;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(values: &[&str]) -> Vec<(String, String)> {
+        values.iter().map(|v| (v.to_string(), v.to_uppercase())).collect()
+    }
+
+    fn links(values: &[(&str, &str)]) -> Vec<(String, String)> {
+        values.iter().map(|(s, t)| (s.to_string(), t.to_string())).collect()
+    }
+
+    #[test]
+    fn test_metrics() {
+        let pages = ids(&["a", "b", "c", "d", "e", "f"]);
+        // d links out of the notebook, so it isn't an orphan but stays on its own
+        let links = links(&[("a", "b"), ("c", "b"), ("b", "c"), ("a", "b"), ("d", "elsewhere"), ("f", "f")]);
+        let metrics = metrics(Some("nb".to_string()), &pages, &links);
+
+        assert_eq!(metrics.link_count, 4);
+        assert_eq!(metrics.orphans.iter().map(|p| p.id.as_str()).collect::<Vec<_>>(), vec!["e", "f"]);
+        assert_eq!(metrics.hubs[0].id, "b");
+        assert_eq!(metrics.hubs[0].incoming, 2);
+        assert_eq!(metrics.hubs.len(), 2);
+        assert_eq!(metrics.components[0], vec!["a", "b", "c"]);
+        assert_eq!(metrics.components.len(), 4);
+    }
+}
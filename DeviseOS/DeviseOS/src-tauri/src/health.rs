@@ -0,0 +1,181 @@
+use std::path::Path;
+use chrono::Utc;
+use crate::{
+    AppResult,
+    models::{AppConfig, ComponentHealth, HealthComponent, HealthStatus, SystemHealth},
+    database::Database,
+    ai::AIService,
+    cloud_sync::SyncService,
+    reindex,
+};
+
+/// Free space below these marks the data directory as degraded or failing.
+const LOW_DISK_BYTES: u64 = 1024 * 1024 * 1024;
+const CRITICAL_DISK_BYTES: u64 = 100 * 1024 * 1024;
+
+/// Status of every subsystem, with the overall status being the worst of them. Checks
+/// don't fail the call; a check that errors reports its component as `Error`.
+pub async fn check(database: &Database, ai_service: &AIService, sync: &SyncService, config: &AppConfig) -> AppResult<SystemHealth> {
+    let mut pending_jobs = 0;
+    let mut components = vec![
+        check_database(database).await,
+        check_encryption(database, config),
+        check_ai(ai_service),
+        check_sync(database, sync, config).await,
+        check_jobs(database, ai_service, &mut pending_jobs).await,
+    ];
+    let (disk, disk_available_bytes) = check_disk(config);
+    components.push(disk);
+
+    Ok(SystemHealth {
+        status: overall(&components),
+        components,
+        pending_jobs,
+        disk_available_bytes,
+        checked_at: Utc::now(),
+    })
+}
+
+fn component(component: HealthComponent, status: HealthStatus, message: impl Into<String>) -> ComponentHealth {
+    ComponentHealth { component, status, message: message.into() }
+}
+
+async fn check_database(database: &Database) -> ComponentHealth {
+    match database.schema_version().await {
+        Ok(version) => component(HealthComponent::Database, HealthStatus::Ok, format!("Connected, schema version {}", version)),
+        Err(e) => component(HealthComponent::Database, HealthStatus::Error, e.to_string()),
+    }
+}
+
+fn check_encryption(database: &Database, config: &AppConfig) -> ComponentHealth {
+    if !config.encryption_enabled {
+        component(HealthComponent::Encryption, HealthStatus::Disabled, "Encryption is off")
+    } else if !database.is_encrypted() {
+        component(HealthComponent::Encryption, HealthStatus::Error, "Encryption is enabled but no key is loaded")
+    } else if !config.encryption_key_path.exists() {
+        component(HealthComponent::Encryption, HealthStatus::Degraded, "The key is loaded but its key file is missing")
+    } else {
+        component(HealthComponent::Encryption, HealthStatus::Ok, format!("Key loaded ({:?})", config.encryption_level))
+    }
+}
+
+fn check_ai(ai_service: &AIService) -> ComponentHealth {
+    let models = [
+        ("speech recognition", ai_service.is_whisper_available()),
+        ("embeddings", ai_service.is_embedding_available()),
+        ("language model", ai_service.is_llm_available()),
+    ];
+    let missing: Vec<&str> = models.iter().filter(|(_, ready)| !ready).map(|(name, _)| *name).collect();
+    if missing.is_empty() {
+        component(HealthComponent::Ai, HealthStatus::Ok, "All models are ready")
+    } else {
+        component(HealthComponent::Ai, HealthStatus::Degraded, format!("Not loaded: {}", missing.join(", ")))
+    }
+}
+
+/// The sync folder has to still exist, and the last runs must have gone through in full.
+async fn check_sync(database: &Database, sync: &SyncService, config: &AppConfig) -> ComponentHealth {
+    if !config.sync_enabled {
+        return component(HealthComponent::Sync, HealthStatus::Disabled, "Sync is turned off by policy");
+    }
+    let status = match sync.status(database).await {
+        Ok(status) => status,
+        Err(e) => return component(HealthComponent::Sync, HealthStatus::Error, e.to_string()),
+    };
+    let Some(folder) = status.folder else {
+        return component(HealthComponent::Sync, HealthStatus::Disabled, "No sync folder is set");
+    };
+    if !folder.is_dir() {
+        return component(HealthComponent::Sync, HealthStatus::Error, format!("Sync folder {} is missing", folder.display()));
+    }
+
+    let failed: usize = [&status.last_push, &status.last_pull].iter()
+        .filter_map(|report| report.as_ref())
+        .map(|report| report.failed.len())
+        .sum();
+    if failed > 0 {
+        component(HealthComponent::Sync, HealthStatus::Degraded, format!("{} documents failed to sync through {}", failed, folder.display()))
+    } else {
+        component(
+            HealthComponent::Sync,
+            HealthStatus::Ok,
+            format!("Syncing through {}, {} changes waiting", folder.display(), status.pending_changes),
+        )
+    }
+}
+
+/// Queued AI jobs, a running re-index and embeddings left from another model count as
+/// pending work.
+async fn check_jobs(database: &Database, ai_service: &AIService, pending_jobs: &mut usize) -> ComponentHealth {
+    let running = reindex::is_running();
+    let queued = match database.count_pending_ai_jobs().await {
+        Ok(count) => count,
+        Err(e) => return component(HealthComponent::Jobs, HealthStatus::Error, e.to_string()),
+    };
+    let stale = match ai_service.embedding_model_name() {
+        Some(model) => match database.get_stale_embedding_ids(model).await {
+            Ok(ids) => ids.len(),
+            Err(e) => return component(HealthComponent::Jobs, HealthStatus::Error, e.to_string()),
+        },
+        None => 0,
+    };
+    *pending_jobs = usize::from(running) + queued + stale;
+
+    match (running, queued, stale) {
+        (true, _, _) => component(HealthComponent::Jobs, HealthStatus::Ok, "Embedding re-index running"),
+        (false, 0, 0) => component(HealthComponent::Jobs, HealthStatus::Ok, "No pending jobs"),
+        (false, queued, 0) => component(HealthComponent::Jobs, HealthStatus::Ok, format!("{} AI jobs queued", queued)),
+        (false, _, stale) => component(HealthComponent::Jobs, HealthStatus::Degraded, format!("{} embeddings need re-indexing", stale)),
+    }
+}
+
+fn check_disk(config: &AppConfig) -> (ComponentHealth, Option<u64>) {
+    let dir = config.database_path.parent().unwrap_or(Path::new("."));
+    match fs2::available_space(dir) {
+        Ok(available) => (component(HealthComponent::Disk, disk_status(available), format!("{} MB free", available / (1024 * 1024))), Some(available)),
+        Err(e) => (component(HealthComponent::Disk, HealthStatus::Error, format!("Can't read free space: {}", e)), None),
+    }
+}
+
+fn disk_status(available: u64) -> HealthStatus {
+    if available < CRITICAL_DISK_BYTES {
+        HealthStatus::Error
+    } else if available < LOW_DISK_BYTES {
+        HealthStatus::Degraded
+    } else {
+        HealthStatus::Ok
+    }
+}
+
+/// The worst status among components that are turned on.
+fn overall(components: &[ComponentHealth]) -> HealthStatus {
+    components
+        .iter()
+        .map(|c| c.status)
+        .filter(|status| *status != HealthStatus::Disabled)
+        .max()
+        .unwrap_or(HealthStatus::Ok)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overall_ignores_disabled() {
+        let components = vec![
+            component(HealthComponent::Database, HealthStatus::Ok, ""),
+            component(HealthComponent::Sync, HealthStatus::Disabled, ""),
+            component(HealthComponent::Ai, HealthStatus::Degraded, ""),
+        ];
+        assert_eq!(overall(&components), HealthStatus::Degraded);
+        assert_eq!(overall(&components[..2]), HealthStatus::Ok);
+    }
+
+    #[test]
+    fn test_disk_status() {
+        assert_eq!(disk_status(50 * 1024 * 1024), HealthStatus::Error);
+        assert_eq!(disk_status(500 * 1024 * 1024), HealthStatus::Degraded);
+        assert_eq!(disk_status(20 * 1024 * 1024 * 1024), HealthStatus::Ok);
+    }
+}
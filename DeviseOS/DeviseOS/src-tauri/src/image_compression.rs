@@ -0,0 +1,166 @@
+use std::io::Cursor;
+use image::{DynamicImage, ImageDecoder, ImageFormat, ImageReader};
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use crate::{
+    AppError, AppResult,
+    models::ImageConfig,
+};
+
+/// Longest side of a thumbnail, in pixels.
+const THUMBNAIL_SIZE: u32 = 256;
+const THUMBNAIL_QUALITY: u8 = 80;
+
+/// An upload after `compress`, with the dimensions it ended up at.
+pub struct CompressedImage {
+    pub data: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Scales `data` down so its longest side fits `config.max_dimension` and re-encodes
+/// JPEGs at `config.jpeg_quality`, whether or not they needed scaling. PNGs stay PNG,
+/// since they're usually screenshots or have transparency. The EXIF orientation is
+/// applied to the pixels first, as the re-encoded file has no EXIF. `None` for other
+/// formats, when compression is off, or when an image that didn't need scaling wouldn't
+/// get smaller; a scaled image is always kept so the dimension limit holds.
+pub fn compress(data: &[u8], mime_type: &str, config: &ImageConfig) -> AppResult<Option<CompressedImage>> {
+    let format = match mime_type {
+        "image/jpeg" | "image/jpg" => ImageFormat::Jpeg,
+        "image/png" => ImageFormat::Png,
+        _ => return Ok(None),
+    };
+    if !config.compress {
+        return Ok(None);
+    }
+
+    let mut image = decode(ImageReader::with_format(Cursor::new(data), format))?;
+    let scaled = scaled_size(image.width(), image.height(), config.max_dimension);
+    if let Some((width, height)) = scaled {
+        image = image.resize_exact(width, height, FilterType::Lanczos3);
+    }
+
+    let mut output = Vec::new();
+    match format {
+        ImageFormat::Jpeg => {
+            let quality = config.jpeg_quality.clamp(1, 100);
+            JpegEncoder::new_with_quality(&mut output, quality)
+                .encode_image(&DynamicImage::ImageRgb8(image.to_rgb8()))
+                .map_err(|e| AppError::InvalidFormat(format!("Failed to encode image: {}", e)))?;
+        }
+        _ => image
+            .write_to(&mut Cursor::new(&mut output), format)
+            .map_err(|e| AppError::InvalidFormat(format!("Failed to encode image: {}", e)))?,
+    }
+
+    if scaled.is_none() && output.len() >= data.len() {
+        return Ok(None);
+    }
+    Ok(Some(CompressedImage { data: output, width: image.width(), height: image.height() }))
+}
+
+/// A preview at most `THUMBNAIL_SIZE` on its longest side, for galleries and attachment
+/// lists: JPEG, or PNG when the image has transparency.
+pub fn thumbnail(data: &[u8]) -> AppResult<Vec<u8>> {
+    let reader = ImageReader::new(Cursor::new(data))
+        .with_guessed_format()
+        .map_err(|e| AppError::InvalidFormat(format!("Failed to read image: {}", e)))?;
+    let image = decode(reader)?.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE);
+
+    let mut output = Vec::new();
+    if image.color().has_alpha() {
+        image.write_to(&mut Cursor::new(&mut output), ImageFormat::Png)
+            .map_err(|e| AppError::InvalidFormat(format!("Failed to encode thumbnail: {}", e)))?;
+    } else {
+        JpegEncoder::new_with_quality(&mut output, THUMBNAIL_QUALITY)
+            .encode_image(&DynamicImage::ImageRgb8(image.to_rgb8()))
+            .map_err(|e| AppError::InvalidFormat(format!("Failed to encode thumbnail: {}", e)))?;
+    }
+    Ok(output)
+}
+
+/// Decodes `data` in any format the decoder knows and encodes its pixels afresh, as PNG
+/// when it has transparency and otherwise as JPEG at `jpeg_quality`, with the MIME type.
+/// The new file carries none of the original's metadata.
+pub fn reencode(data: &[u8], jpeg_quality: u8) -> AppResult<(Vec<u8>, &'static str)> {
+    let reader = ImageReader::new(Cursor::new(data))
+        .with_guessed_format()
+        .map_err(|e| AppError::InvalidFormat(format!("Failed to read image: {}", e)))?;
+    let image = decode(reader)?;
+
+    let mut output = Vec::new();
+    if image.color().has_alpha() {
+        image.write_to(&mut Cursor::new(&mut output), ImageFormat::Png)
+            .map_err(|e| AppError::InvalidFormat(format!("Failed to encode image: {}", e)))?;
+        Ok((output, "image/png"))
+    } else {
+        JpegEncoder::new_with_quality(&mut output, jpeg_quality.clamp(1, 100))
+            .encode_image(&DynamicImage::ImageRgb8(image.to_rgb8()))
+            .map_err(|e| AppError::InvalidFormat(format!("Failed to encode image: {}", e)))?;
+        Ok((output, "image/jpeg"))
+    }
+}
+
+/// Decodes with the EXIF orientation applied, so photos come out upright.
+fn decode(reader: ImageReader<Cursor<&[u8]>>) -> AppResult<DynamicImage> {
+    let mut decoder = reader
+        .into_decoder()
+        .map_err(|e| AppError::InvalidFormat(format!("Failed to decode image: {}", e)))?;
+    let orientation = decoder.orientation()
+        .map_err(|e| AppError::InvalidFormat(format!("Failed to read image orientation: {}", e)))?;
+    let mut image = DynamicImage::from_decoder(decoder)
+        .map_err(|e| AppError::InvalidFormat(format!("Failed to decode image: {}", e)))?;
+    image.apply_orientation(orientation);
+    Ok(image)
+}
+
+/// Dimensions that fit `max_dimension` on the longest side, keeping the aspect ratio.
+/// `None` when the image already fits or `max_dimension` is 0, meaning no limit.
+fn scaled_size(width: u32, height: u32, max_dimension: u32) -> Option<(u32, u32)> {
+    let longest = width.max(height);
+    if max_dimension == 0 || longest <= max_dimension {
+        return None;
+    }
+    let scale = max_dimension as f64 / longest as f64;
+    Some((
+        ((width as f64 * scale).round() as u32).max(1),
+        ((height as f64 * scale).round() as u32).max(1),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    #[test]
+    fn test_scaled_size() {
+        assert_eq!(scaled_size(4032, 3024, 2560), Some((2560, 1920)));
+        assert_eq!(scaled_size(3024, 4032, 2560), Some((1920, 2560)));
+        assert_eq!(scaled_size(10000, 1, 100), Some((100, 1)));
+        assert_eq!(scaled_size(2000, 1000, 2560), None);
+        assert_eq!(scaled_size(4032, 3024, 0), None);
+    }
+
+    #[test]
+    fn test_compress_downscales_jpeg() {
+        let photo = RgbImage::from_fn(800, 600, |x, y| Rgb([(x % 256) as u8, (y % 256) as u8, ((x * y) % 256) as u8]));
+        let mut jpeg = Vec::new();
+        JpegEncoder::new_with_quality(&mut jpeg, 100).encode_image(&photo).unwrap();
+
+        let config = ImageConfig { max_dimension: 400, jpeg_quality: 70, ..ImageConfig::default() };
+        let compressed = compress(&jpeg, "image/jpeg", &config).unwrap().unwrap();
+        assert_eq!((compressed.width, compressed.height), (400, 300));
+        assert!(compressed.data.len() < jpeg.len());
+        assert!(compress(&jpeg, "image/gif", &config).unwrap().is_none());
+
+        // Within the limit, it's still recompressed at the configured quality
+        let recompressed = compress(&jpeg, "image/jpeg", &ImageConfig { max_dimension: 1000, ..config.clone() }).unwrap().unwrap();
+        assert_eq!((recompressed.width, recompressed.height), (800, 600));
+        assert!(recompressed.data.len() < jpeg.len());
+        assert!(compress(&jpeg, "image/jpeg", &ImageConfig { compress: false, ..config }).unwrap().is_none());
+
+        let preview = image::load_from_memory(&thumbnail(&jpeg).unwrap()).unwrap();
+        assert_eq!((preview.width(), preview.height()), (256, 192));
+    }
+}
@@ -0,0 +1,309 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::sync::RwLock;
+use uuid::Uuid;
+use crate::{
+    AppError, AppResult, AppState,
+    models::{
+        AiJobKind, AiJobPriority, CreatePageRequest, ImportBatch, ImportKind, ImportProgress,
+        ImportStatus, LanguageSource, MediaAttachment, UploadMediaRequest, VoiceMetadata,
+    },
+    database::Database,
+    ai::AIService,
+    audio, storage,
+};
+
+pub const IMPORT_PROGRESS_EVENT: &str = "import-progress";
+
+/// Paths the user handed over by dropping them on a window or picking them in a file
+/// dialog. `attach_file_from_path` only reads these, so the webview can't name any file on
+/// disk. `import_files` checks them the same way. Each grant is good for one attachment
+/// or import.
+#[derive(Default)]
+pub struct FileGrants {
+    paths: Mutex<HashSet<PathBuf>>,
+}
+
+impl FileGrants {
+    pub fn grant(&self, paths: impl IntoIterator<Item = PathBuf>) {
+        self.paths.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).extend(paths);
+    }
+
+    /// Uses up the grant for `path`.
+    pub fn take(&self, path: &Path) -> AppResult<()> {
+        if self.paths.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).remove(path) {
+            Ok(())
+        } else {
+            Err(AppError::PermissionDenied(format!("{} wasn't dropped on the window or chosen in a file dialog", path.display())))
+        }
+    }
+}
+
+/// Starts importing dropped files in the background and returns immediately. Each file
+/// reports progress through `import-progress` events tagged with the returned import id.
+/// Transcription, OCR and PDF text extraction are queued as AI jobs rather than done here.
+pub fn spawn_import(
+    app: AppHandle,
+    database: Arc<RwLock<Database>>,
+    ai_service: Arc<RwLock<AIService>>,
+    paths: Vec<PathBuf>,
+    target_page_id: String,
+    max_file_size: u64,
+) -> ImportBatch {
+    let batch = ImportBatch {
+        import_id: Uuid::new_v4().to_string(),
+        total: paths.len(),
+    };
+    let import_id = batch.import_id.clone();
+
+    tauri::async_runtime::spawn(async move {
+        let total = paths.len();
+        for (index, path) in paths.into_iter().enumerate() {
+            let kind = classify(&path);
+            let mut progress = ImportProgress {
+                import_id: import_id.clone(),
+                path: path.clone(),
+                index,
+                total,
+                kind,
+                status: ImportStatus::Started,
+                created_id: None,
+                error: None,
+            };
+            let _ = app.emit(IMPORT_PROGRESS_EVENT, &progress);
+
+            let result = import_file(&app, &database, &ai_service, &path, kind, &target_page_id, max_file_size).await;
+            match result {
+                Ok(created_id) => {
+                    progress.status = ImportStatus::Completed;
+                    progress.created_id = Some(created_id);
+                }
+                Err(e) => {
+                    tracing::warn!("Failed to import {}: {}", path.display(), e);
+                    progress.status = ImportStatus::Failed;
+                    progress.error = Some(e.to_string());
+                }
+            }
+            let _ = app.emit(IMPORT_PROGRESS_EVENT, &progress);
+        }
+    });
+
+    batch
+}
+
+pub fn classify(path: &Path) -> ImportKind {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase())
+        .unwrap_or_default();
+
+    match extension.as_str() {
+        "md" | "markdown" | "txt" => return ImportKind::Markdown,
+        "pdf" => return ImportKind::Pdf,
+        _ => {}
+    }
+
+    match mime_type_for_path(path).split('/').next() {
+        Some("image") => ImportKind::Image,
+        Some("audio") => ImportKind::Audio,
+        _ => ImportKind::Attachment,
+    }
+}
+
+pub fn mime_type_for_path(path: &Path) -> String {
+    mime_guess::from_path(path)
+        .first_or_octet_stream()
+        .essence_str()
+        .to_string()
+}
+
+/// MIME type from the file's leading bytes, so files with a missing or wrong extension
+/// still play or display. Formats without a recognisable signature go by the extension.
+pub fn sniff_mime_type(data: &[u8], path: &Path) -> String {
+    let sniffed = match data {
+        [0x89, b'P', b'N', b'G', ..] => Some("image/png"),
+        [0xFF, 0xD8, 0xFF, ..] => Some("image/jpeg"),
+        [b'G', b'I', b'F', b'8', b'7' | b'9', b'a', ..] => Some("image/gif"),
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => Some("image/webp"),
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'A', b'V', b'E', ..] => Some("audio/wav"),
+        [b'%', b'P', b'D', b'F', b'-', ..] => Some("application/pdf"),
+        [b'I', b'D', b'3', ..] | [0xFF, 0xFB | 0xF3 | 0xF2, ..] => Some("audio/mpeg"),
+        [b'O', b'g', b'g', b'S', ..] => Some("audio/ogg"),
+        [b'f', b'L', b'a', b'C', ..] => Some("audio/flac"),
+        [0x1A, 0x45, 0xDF, 0xA3, ..] => Some("video/webm"),
+        [_, _, _, _, b'f', b't', b'y', b'p', brand @ ..] if brand.len() >= 4 => Some(match &brand[..4] {
+            b"M4A " | b"M4B " => "audio/mp4",
+            b"qt  " => "video/quicktime",
+            b"heic" | b"heix" | b"mif1" => "image/heic",
+            _ => "video/mp4",
+        }),
+        _ => None,
+    };
+    sniffed.map(str::to_string).unwrap_or_else(|| mime_type_for_path(path))
+}
+
+/// Stores the file at `path` as an attachment of `page_id`, reading it here rather than
+/// having the frontend send its bytes. PDF text is extracted for search.
+pub async fn attach_file(database: &Database, page_id: &str, path: &Path, max_file_size: u64) -> AppResult<MediaAttachment> {
+    let file_metadata = tokio::fs::metadata(path).await?;
+    if !file_metadata.is_file() {
+        return Err(AppError::InvalidOperation(format!("{} is not a file", path.display())));
+    }
+    storage::check_file_size(&path.display().to_string(), file_metadata.len(), max_file_size)?;
+
+    let file_data = tokio::fs::read(path).await?;
+    let mime_type = sniff_mime_type(&file_data, path);
+    let extracted_text = if mime_type == "application/pdf" { extract_pdf_text(&file_data) } else { None };
+
+    let mut media = database.upload_media(UploadMediaRequest {
+        page_id: Some(page_id.to_string()),
+        note_id: None,
+        filename: file_name(path),
+        mime_type,
+        file_data,
+        position_in_content: None,
+    }).await?;
+
+    if extracted_text.is_some() {
+        media.metadata.extracted_text = extracted_text;
+        database.update_media_metadata(&media.id, &media.metadata).await?;
+    }
+    Ok(media)
+}
+
+async fn import_file(
+    app: &AppHandle,
+    database: &Arc<RwLock<Database>>,
+    ai_service: &Arc<RwLock<AIService>>,
+    path: &Path,
+    kind: ImportKind,
+    target_page_id: &str,
+    max_file_size: u64,
+) -> AppResult<String> {
+    let size = tokio::fs::metadata(path).await?.len();
+    storage::check_file_size(&path.display().to_string(), size, max_file_size)?;
+
+    let database = database.read().await;
+    let target = database.get_page(target_page_id).await?
+        .ok_or_else(|| AppError::NotFound(format!("Page with id {} not found", target_page_id)))?;
+
+    match kind {
+        ImportKind::Markdown => {
+            let content = tokio::fs::read_to_string(path).await?;
+            let page = database.create_page(CreatePageRequest {
+                notebook_id: target.notebook_id.clone(),
+                section_id: target.section_id.clone(),
+                parent_page_id: Some(target.id.clone()),
+                title: markdown_title(&content, path),
+                content,
+                tags: Vec::new(),
+            }).await?;
+
+            app.state::<AppState>().page_created(&database, &page).await?;
+
+            Ok(page.id)
+        }
+        ImportKind::Audio => {
+            let audio_data = tokio::fs::read(path).await?;
+            let whisper_available = ai_service.read().await.is_whisper_available();
+            let transcription = if whisper_available {
+                String::new() // Filled in by the transcription job
+            } else {
+                "Audio transcription not available".to_string()
+            };
+
+            // Calculate duration (simplified, assumes 16kHz mono)
+            let duration = audio_data.len() as f64 / 32000.0;
+            // Recorded as what the file really is, so compressed audio is never taken for samples
+            let metadata = VoiceMetadata { format: audio::format_for_mime(&sniff_mime_type(&audio_data, path)), ..VoiceMetadata::default() };
+            let annotation = database.add_page_voice_annotation(&target.id, audio_data, transcription, duration, metadata).await?;
+
+            if whisper_available {
+                // The page's language, set or detected, picks the Whisper language
+                let language = database.get_language_settings(&target.id).await?;
+                let whisper_language = (!matches!(language.source, LanguageSource::Default)).then_some(language.whisper_language);
+                let kind = AiJobKind::Transcribe { annotation_id: annotation.id.clone(), language: whisper_language };
+                app.state::<AppState>().jobs.enqueue(&database, kind, AiJobPriority::Low).await?;
+            }
+            Ok(annotation.id)
+        }
+        ImportKind::Image | ImportKind::Pdf | ImportKind::Attachment => {
+            let file_data = tokio::fs::read(path).await?;
+            let media = database.upload_media(UploadMediaRequest {
+                page_id: Some(target.id.clone()),
+                note_id: None,
+                filename: file_name(path),
+                mime_type: sniff_mime_type(&file_data, path),
+                file_data,
+                position_in_content: None,
+            }).await?;
+
+            if matches!(kind, ImportKind::Image | ImportKind::Pdf) {
+                let kind = AiJobKind::ExtractText { media_id: media.id.clone() };
+                app.state::<AppState>().jobs.enqueue(&database, kind, AiJobPriority::Low).await?;
+            }
+            Ok(media.id)
+        }
+    }
+}
+
+/// Uses the first Markdown heading as the title, falling back to the file name.
+fn markdown_title(content: &str, path: &Path) -> String {
+    content
+        .lines()
+        .map(|line| line.trim())
+        .find(|line| line.starts_with('#'))
+        .map(|line| line.trim_start_matches('#').trim().to_string())
+        .filter(|title| !title.is_empty())
+        .unwrap_or_else(|| {
+            path.file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_else(|| "Imported page".to_string())
+        })
+}
+
+pub fn extract_pdf_text(data: &[u8]) -> Option<String> {
+    match pdf_extract::extract_text_from_mem(data) {
+        Ok(text) => Some(text.trim().to_string()).filter(|t| !t.is_empty()),
+        Err(e) => {
+            tracing::warn!("Failed to extract PDF text: {}", e);
+            None
+        }
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "imported-file".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_grants_are_used_once() {
+        let grants = FileGrants::default();
+        let dropped = PathBuf::from("/home/me/photo.jpg");
+        grants.grant([dropped.clone()]);
+        assert!(grants.take(Path::new("/etc/passwd")).is_err());
+        assert!(grants.take(&dropped).is_ok());
+        assert!(grants.take(&dropped).is_err());
+    }
+
+    #[test]
+    fn test_sniff_mime_type() {
+        let path = Path::new("upload.bin");
+        assert_eq!(sniff_mime_type(b"\x89PNG\r\n\x1a\n", path), "image/png");
+        assert_eq!(sniff_mime_type(b"RIFF\0\0\0\0WAVEfmt ", path), "audio/wav");
+        assert_eq!(sniff_mime_type(b"\0\0\0\x20ftypM4A \0", path), "audio/mp4");
+        assert_eq!(sniff_mime_type(b"\0\0\0\x20ftypisom", path), "video/mp4");
+        assert_eq!(sniff_mime_type(b"ID3\x04", Path::new("song.txt")), "audio/mpeg");
+        assert_eq!(sniff_mime_type(b"plain text", Path::new("notes.txt")), "text/plain");
+        assert_eq!(sniff_mime_type(b"", path), "application/octet-stream");
+    }
+}
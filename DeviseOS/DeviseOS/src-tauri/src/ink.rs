@@ -0,0 +1,136 @@
+use std::io::Cursor;
+use image::{GrayImage, ImageFormat, Luma};
+use crate::{
+    AppError, AppResult,
+    models::{InkDrawing, InkPoint, INK_MIME_TYPE},
+    database::Database,
+    ai::AIService,
+};
+
+/// Strokes are rendered at this height before recognition; handwriting OCR works best with
+/// lines roughly 30-60px tall.
+const RENDER_HEIGHT: f32 = 600.0;
+const DEFAULT_STROKE_WIDTH: f32 = 3.0;
+const MARGIN: u32 = 20;
+
+/// Recognizes handwriting in an ink attachment and stores the text in its metadata so it can
+/// be searched. Ink drawings are rasterized first; image attachments are recognized directly.
+/// Returns `None` when no text was found or the local recognizer is not installed.
+pub async fn recognize_ink(database: &Database, ai_service: &AIService, media_id: &str) -> AppResult<Option<String>> {
+    let media = database.get_media_attachment(media_id).await?
+        .ok_or_else(|| AppError::NotFound(format!("Media attachment with id {} not found", media_id)))?;
+
+    let image_data = if media.mime_type == INK_MIME_TYPE {
+        let drawing: InkDrawing = serde_json::from_slice(&media.file_data)?;
+        tokio::task::spawn_blocking(move || render_png(&drawing))
+            .await
+            .map_err(|e| AppError::Unknown(format!("Ink rendering task failed: {}", e)))??
+    } else if media.mime_type.starts_with("image/") {
+        media.file_data
+    } else {
+        return Err(AppError::InvalidFormat(format!("{} is not an ink drawing", media.original_filename)));
+    };
+
+    let text = ai_service.ocr_image(&image_data).await?;
+
+    let mut metadata = media.metadata;
+    metadata.extracted_text = text.clone();
+    database.update_media_metadata(media_id, &metadata).await?;
+
+    Ok(text)
+}
+
+fn render_png(drawing: &InkDrawing) -> AppResult<Vec<u8>> {
+    let image = render(drawing)?;
+    let mut png_data = Vec::new();
+    image.write_to(&mut Cursor::new(&mut png_data), ImageFormat::Png)
+        .map_err(|e| AppError::InvalidFormat(format!("Failed to encode ink drawing: {}", e)))?;
+    Ok(png_data)
+}
+
+/// Draws the strokes black on white, scaled to `RENDER_HEIGHT` with a margin around them.
+fn render(drawing: &InkDrawing) -> AppResult<GrayImage> {
+    if drawing.width <= 0.0 || drawing.height <= 0.0 {
+        return Err(AppError::InvalidFormat("Ink drawing has no size".to_string()));
+    }
+
+    let scale = RENDER_HEIGHT / drawing.height;
+    let width = (drawing.width * scale).ceil() as u32 + 2 * MARGIN;
+    let height = RENDER_HEIGHT.ceil() as u32 + 2 * MARGIN;
+    let mut image = GrayImage::from_pixel(width, height, Luma([255]));
+
+    let to_canvas = |point: &InkPoint| (point.x * scale + MARGIN as f32, point.y * scale + MARGIN as f32);
+
+    for stroke in &drawing.strokes {
+        let base_width = stroke.width.unwrap_or(DEFAULT_STROKE_WIDTH) * scale.max(1.0);
+        let radius = |point: &InkPoint| (base_width * point.pressure.unwrap_or(1.0).clamp(0.2, 1.0) / 2.0).max(1.0);
+
+        match stroke.points.as_slice() {
+            [] => {}
+            [point] => stamp(&mut image, to_canvas(point), radius(point)),
+            points => {
+                for pair in points.windows(2) {
+                    let (from, to) = (to_canvas(&pair[0]), to_canvas(&pair[1]));
+                    let r = (radius(&pair[0]) + radius(&pair[1])) / 2.0;
+                    let length = ((to.0 - from.0).powi(2) + (to.1 - from.1).powi(2)).sqrt();
+                    let steps = (length / (r / 2.0).max(0.5)).ceil().max(1.0) as u32;
+                    for step in 0..=steps {
+                        let t = step as f32 / steps as f32;
+                        stamp(&mut image, (from.0 + (to.0 - from.0) * t, from.1 + (to.1 - from.1) * t), r);
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(image)
+}
+
+/// Paints a filled black disc.
+fn stamp(image: &mut GrayImage, (cx, cy): (f32, f32), radius: f32) {
+    let (width, height) = image.dimensions();
+    let min_x = (cx - radius).floor().max(0.0) as u32;
+    let min_y = (cy - radius).floor().max(0.0) as u32;
+    let max_x = ((cx + radius).ceil() as u32).min(width.saturating_sub(1));
+    let max_y = ((cy + radius).ceil() as u32).min(height.saturating_sub(1));
+
+    for y in min_y..=max_y {
+        for x in min_x..=max_x {
+            let (dx, dy) = (x as f32 - cx, y as f32 - cy);
+            if dx * dx + dy * dy <= radius * radius {
+                image.put_pixel(x, y, Luma([0]));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::InkStroke;
+
+    fn point(x: f32, y: f32) -> InkPoint {
+        InkPoint { x, y, pressure: None }
+    }
+
+    #[test]
+    fn test_render_draws_strokes_within_bounds() {
+        let drawing = InkDrawing {
+            width: 100.0,
+            height: 50.0,
+            strokes: vec![InkStroke { points: vec![point(0.0, 25.0), point(100.0, 25.0)], width: None }],
+        };
+        let image = render(&drawing).unwrap();
+
+        assert_eq!(image.height(), RENDER_HEIGHT as u32 + 2 * MARGIN);
+        let middle = image.height() / 2;
+        assert_eq!(image.get_pixel(image.width() / 2, middle)[0], 0);
+        assert_eq!(image.get_pixel(image.width() / 2, MARGIN)[0], 255);
+    }
+
+    #[test]
+    fn test_render_rejects_empty_canvas() {
+        let drawing = InkDrawing { width: 0.0, height: 0.0, strokes: Vec::new() };
+        assert!(render(&drawing).is_err());
+    }
+}
@@ -0,0 +1,282 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use chrono::Utc;
+use tauri::{AppHandle, Emitter};
+use tokio::sync::{Notify, RwLock};
+use tokio_util::sync::CancellationToken;
+use crate::{
+    AppError, AppResult,
+    models::{AiJob, AiJobKind, AiJobPriority, AiJobStatus, EmbeddingOwner},
+    database::Database,
+    ai::{content_hash, AIService},
+    importer,
+};
+
+pub const AI_JOB_UPDATED_EVENT: &str = "ai-job-updated";
+
+/// Attempts before a job is marked failed; later attempts wait longer.
+const MAX_ATTEMPTS: u32 = 3;
+const RETRY_BASE_DELAY_SECS: i64 = 30;
+/// How often the worker looks for retries that became due without being woken.
+const POLL_INTERVAL: Duration = Duration::from_secs(15);
+/// Finished jobs are kept this long for status queries.
+const KEEP_FINISHED_DAYS: i64 = 7;
+
+/// Persistent queue of embedding, transcription and text extraction work. Commands enqueue and return
+/// straight away; a single worker runs jobs one at a time, highest priority first, taking
+/// the database lock only to read inputs and store results so CRUD is never blocked.
+pub struct JobQueue {
+    wake: Notify,
+    running: Mutex<HashMap<String, CancellationToken>>,
+    app: OnceLock<AppHandle>, // Set once the worker starts
+}
+
+impl JobQueue {
+    pub fn new() -> Self {
+        Self {
+            wake: Notify::new(),
+            running: Mutex::new(HashMap::new()),
+            app: OnceLock::new(),
+        }
+    }
+
+    /// Requeues jobs interrupted by the last shutdown, prunes old finished jobs and starts
+    /// the worker.
+    pub async fn start(self: &Arc<Self>, app: AppHandle, database: Arc<RwLock<Database>>, ai_service: Arc<RwLock<AIService>>) -> AppResult<()> {
+        if self.app.set(app).is_err() {
+            return Err(AppError::InvalidOperation("The AI job worker is already running".to_string()));
+        }
+        {
+            let database = database.read().await;
+            let requeued = database.requeue_running_ai_jobs().await?;
+            if requeued > 0 {
+                tracing::info!("Requeued {} interrupted AI jobs", requeued);
+            }
+            database.prune_ai_jobs(Utc::now() - chrono::Duration::days(KEEP_FINISHED_DAYS)).await?;
+        }
+
+        let queue = self.clone();
+        tauri::async_runtime::spawn(async move {
+            loop {
+                let next = database.read().await.claim_next_ai_job().await;
+                match next {
+                    Ok(Some(job)) => queue.run(&database, &ai_service, job).await,
+                    Ok(None) => {
+                        let _ = tokio::time::timeout(POLL_INTERVAL, queue.wake.notified()).await;
+                    }
+                    Err(e) => {
+                        tracing::warn!("Failed to claim the next AI job: {}", e);
+                        tokio::time::sleep(POLL_INTERVAL).await;
+                    }
+                }
+            }
+        });
+        Ok(())
+    }
+
+    /// Queues `kind`, or returns the job already waiting to do the same work; it reads the
+    /// latest content when it runs.
+    pub async fn enqueue(&self, database: &Database, kind: AiJobKind, priority: AiJobPriority) -> AppResult<AiJob> {
+        if let Some(job) = database.find_queued_ai_job(&kind).await? {
+            return Ok(job);
+        }
+        let job = AiJob::new(kind, priority);
+        database.insert_ai_job(&job).await?;
+        self.emit(&job);
+        self.wake.notify_one();
+        Ok(job)
+    }
+
+    /// Cancels a queued job, or stops a running one.
+    pub async fn cancel(&self, database: &Database, id: &str) -> AppResult<AiJob> {
+        if !database.set_ai_job_status(id, &[AiJobStatus::Queued, AiJobStatus::Running], AiJobStatus::Cancelled, None, None).await? {
+            return Err(finished_error(database, id).await);
+        }
+        if let Some(token) = self.running.lock().unwrap().get(id) {
+            token.cancel();
+        }
+        self.updated(database, id).await
+    }
+
+    /// Queues a failed or cancelled job again. It gets one more attempt, not a fresh set.
+    pub async fn retry(&self, database: &Database, id: &str) -> AppResult<AiJob> {
+        if !database.set_ai_job_status(id, &[AiJobStatus::Failed, AiJobStatus::Cancelled], AiJobStatus::Queued, None, Some(Utc::now())).await? {
+            return Err(match database.get_ai_job(id).await? {
+                Some(job) => AppError::InvalidOperation(format!("Job {} is {} and can't be retried", id, job.status.as_str())),
+                None => AppError::NotFound(format!("AI job {} not found", id)),
+            });
+        }
+        self.wake.notify_one();
+        self.updated(database, id).await
+    }
+
+    async fn run(&self, database: &Arc<RwLock<Database>>, ai_service: &Arc<RwLock<AIService>>, job: AiJob) {
+        let token = CancellationToken::new();
+        self.running.lock().unwrap().insert(job.id.clone(), token.clone());
+        self.emit(&job);
+
+        let result = tokio::select! {
+            result = execute(database, ai_service, &job.kind) => Some(result),
+            _ = token.cancelled() => None,
+        };
+        self.running.lock().unwrap().remove(&job.id);
+        let Some(result) = result else {
+            return; // `cancel` already recorded it
+        };
+
+        let database = database.read().await;
+        let (status, error, run_after) = match result {
+            Ok(()) => (AiJobStatus::Completed, None, None),
+            Err(e) => {
+                tracing::warn!("AI job {} failed (attempt {}): {}", job.id, job.attempts, e);
+                let status = if job.attempts < MAX_ATTEMPTS { AiJobStatus::Queued } else { AiJobStatus::Failed };
+                (status, Some(e.to_string()), Some(Utc::now() + retry_delay(job.attempts)))
+            }
+        };
+        match database.set_ai_job_status(&job.id, &[AiJobStatus::Running], status, error.as_deref(), run_after).await {
+            Ok(true) => {
+                if let Err(e) = self.updated(&database, &job.id).await {
+                    tracing::warn!("Failed to read AI job {}: {}", job.id, e);
+                }
+            }
+            Ok(false) => {} // Cancelled just as it finished
+            Err(e) => tracing::warn!("Failed to record the result of AI job {}: {}", job.id, e),
+        }
+    }
+
+    async fn updated(&self, database: &Database, id: &str) -> AppResult<AiJob> {
+        let job = database.get_ai_job(id).await?
+            .ok_or_else(|| AppError::NotFound(format!("AI job {} not found", id)))?;
+        self.emit(&job);
+        Ok(job)
+    }
+
+    fn emit(&self, job: &AiJob) {
+        if let Some(app) = self.app.get() {
+            let _ = app.emit(AI_JOB_UPDATED_EVENT, job);
+        }
+    }
+}
+
+async fn finished_error(database: &Database, id: &str) -> AppError {
+    match database.get_ai_job(id).await {
+        Ok(Some(job)) => AppError::InvalidOperation(format!("Job {} is already {}", id, job.status.as_str())),
+        Ok(None) => AppError::NotFound(format!("AI job {} not found", id)),
+        Err(e) => e,
+    }
+}
+
+/// Wait before attempt `attempts + 1`: 30s, 60s, 120s, ...
+fn retry_delay(attempts: u32) -> chrono::Duration {
+    chrono::Duration::seconds(RETRY_BASE_DELAY_SECS << attempts.saturating_sub(1).min(10))
+}
+
+async fn execute(database: &Arc<RwLock<Database>>, ai_service: &Arc<RwLock<AIService>>, kind: &AiJobKind) -> AppResult<()> {
+    match kind {
+        AiJobKind::Embed { owner_id, owner } => embed(database, ai_service, owner_id, *owner).await,
+        AiJobKind::Transcribe { annotation_id, language } => transcribe(database, ai_service, annotation_id, language.as_deref()).await,
+        AiJobKind::ExtractText { media_id } => extract_text(database, ai_service, media_id).await,
+    }
+}
+
+/// Embeds the current content of a note or page. Nothing to do if it was deleted, is
+/// unchanged, or no embedding model is loaded (a later re-index covers that).
+async fn embed(database: &Arc<RwLock<Database>>, ai_service: &Arc<RwLock<AIService>>, owner_id: &str, owner: EmbeddingOwner) -> AppResult<()> {
+    let content = {
+        let database = database.read().await;
+        match owner {
+            EmbeddingOwner::Note => database.get_note(owner_id).await?.map(|n| n.content),
+            EmbeddingOwner::Page => database.get_page(owner_id).await?.map(|p| p.content),
+        }
+    };
+    let Some(content) = content else {
+        return Ok(());
+    };
+
+    let ai_service = ai_service.read().await;
+    let Some(model) = ai_service.embedding_model_name() else {
+        return Ok(());
+    };
+    let hash = content_hash(&content);
+    if database.read().await.has_current_embedding(owner_id, model, &hash).await? {
+        return Ok(());
+    }
+    let embedding = ai_service.generate_embeddings(&content).await?;
+    database.read().await.store_embedding(owner_id, owner, model, &hash, &embedding).await
+}
+
+async fn transcribe(database: &Arc<RwLock<Database>>, ai_service: &Arc<RwLock<AIService>>, annotation_id: &str, language: Option<&str>) -> AppResult<()> {
+    let Some(annotation) = database.read().await.get_voice_annotation_pcm(annotation_id).await? else {
+        return Ok(());
+    };
+    if annotation.metadata.edited {
+        return Ok(()); // Corrected by hand while queued
+    }
+
+    let ai_service = ai_service.read().await;
+    if !ai_service.is_whisper_available() {
+        return Err(AppError::ModelNotFound("Whisper model not available".to_string()));
+    }
+    let (transcription, language, segments) = ai_service.transcribe_segments(&annotation.audio_data, language).await?;
+    match database.read().await.update_voice_transcription(annotation_id, &transcription, language, segments).await {
+        Err(AppError::NotFound(_)) => Ok(()), // Deleted while transcribing
+        result => result,
+    }
+}
+
+/// Stores the text of an imported image or PDF in its metadata so search can find it.
+/// Nothing to do if the attachment was deleted or has no text.
+async fn extract_text(database: &Arc<RwLock<Database>>, ai_service: &Arc<RwLock<AIService>>, media_id: &str) -> AppResult<()> {
+    let Some(media) = database.read().await.get_media_attachment(media_id).await? else {
+        return Ok(());
+    };
+
+    let text = if media.mime_type == "application/pdf" {
+        let data = media.file_data;
+        tokio::task::spawn_blocking(move || importer::extract_pdf_text(&data))
+            .await
+            .map_err(|e| AppError::Unknown(format!("PDF text extraction failed: {}", e)))?
+    } else if media.mime_type.starts_with("image/") {
+        ai_service.read().await.ocr_image(&media.file_data).await?
+    } else {
+        None
+    };
+    let Some(text) = text else {
+        return Ok(());
+    };
+
+    // Read again so changes made while extracting aren't overwritten
+    let database = database.read().await;
+    let Some(mut metadata) = database.get_media_metadata(media_id).await? else {
+        return Ok(());
+    };
+    metadata.extracted_text = Some(text);
+    match database.update_media_metadata(media_id, &metadata).await {
+        Err(AppError::NotFound(_)) => Ok(()), // Deleted while extracting
+        result => result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_delay_doubles() {
+        assert_eq!(retry_delay(1).num_seconds(), 30);
+        assert_eq!(retry_delay(2).num_seconds(), 60);
+        assert_eq!(retry_delay(3).num_seconds(), 120);
+        assert_eq!(retry_delay(0).num_seconds(), 30);
+        assert!(retry_delay(u32::MAX).num_seconds() > 0);
+    }
+
+    #[test]
+    fn test_job_kind_payload_is_stable() {
+        // Queued jobs are matched by their serialized payload
+        let kind = AiJobKind::Embed { owner_id: "p1".to_string(), owner: EmbeddingOwner::Page };
+        assert_eq!(serde_json::to_string(&kind).unwrap(), r#"{"type":"embed","owner_id":"p1","owner":"page"}"#);
+        assert_eq!(AiJobPriority::from_i64(AiJobPriority::High.as_i64()), AiJobPriority::High);
+        assert_eq!(AiJobStatus::parse(AiJobStatus::Cancelled.as_str()), Some(AiJobStatus::Cancelled));
+    }
+}
@@ -0,0 +1,212 @@
+use crate::{
+    AppError, AppResult,
+    models::JumpListEntry,
+    database::Database,
+    share::PAGE_LINK_PREFIX,
+};
+
+/// Recently opened pages listed besides the pinned ones.
+const RECENT_LIMIT: usize = 10;
+
+/// Emitted with a page id when a `deviseos://page/<id>` link is opened while the app runs.
+pub const OPEN_PAGE_EVENT: &str = "open-page";
+
+/// Replaces the pages registered with the OS (taskbar jump list on Windows, recent files on
+/// Linux) with the current pinned and recently opened pages. Each entry relaunches the app
+/// with a `deviseos://page/<id>` argument, see `launch_page_id`.
+pub async fn refresh(database: &Database) -> AppResult<()> {
+    let entries = database.get_jump_list_entries(RECENT_LIMIT).await?;
+    tokio::task::spawn_blocking(move || platform::update(&entries))
+        .await
+        .map_err(|e| AppError::Unknown(format!("Jump list task failed: {}", e)))?
+}
+
+/// The page a jump list entry asked to open, from the process arguments.
+pub fn launch_page_id() -> Option<String> {
+    std::env::args().find_map(|arg| page_id_from_link(&arg))
+}
+
+/// The page id in a `deviseos://page/<id>` link.
+pub fn page_id_from_link(link: &str) -> Option<String> {
+    link.strip_prefix(PAGE_LINK_PREFIX).map(|id| id.trim_end_matches('/').to_string()).filter(|id| !id.is_empty())
+}
+
+fn page_link(page_id: &str) -> String {
+    format!("{}{}", PAGE_LINK_PREFIX, page_id)
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use windows::{
+        core::{Interface, HSTRING, PROPVARIANT},
+        Win32::{
+            Storage::EnhancedStorage::PKEY_Title,
+            System::Com::{CoCreateInstance, CoInitializeEx, CLSCTX_INPROC_SERVER, COINIT_APARTMENTTHREADED},
+            UI::Shell::{
+                Common::{IObjectArray, IObjectCollection},
+                PropertiesSystem::IPropertyStore,
+                DestinationList, EnumerableObjectCollection, ICustomDestinationList, IShellLinkW, ShellLink,
+            },
+        },
+    };
+    use crate::{AppError, AppResult, models::JumpListEntry};
+    use super::page_link;
+
+    pub fn update(entries: &[JumpListEntry]) -> AppResult<()> {
+        let exe = std::env::current_exe()?;
+        // SAFETY: COM calls on a blocking thread initialized for COM first
+        unsafe { build_list(&exe, entries) }
+            .map_err(|e| AppError::InvalidOperation(format!("Failed to update jump list: {}", e)))
+    }
+
+    unsafe fn build_list(exe: &std::path::Path, entries: &[JumpListEntry]) -> windows::core::Result<()> {
+        // S_FALSE when this thread already initialized COM
+        CoInitializeEx(None, COINIT_APARTMENTTHREADED).ok()?;
+        let list: ICustomDestinationList = CoCreateInstance(&DestinationList, None, CLSCTX_INPROC_SERVER)?;
+        let mut max_slots = 0u32;
+        let _removed: IObjectArray = list.BeginList(&mut max_slots)?;
+
+        for (category, pinned) in [("Pinned", true), ("Recent", false)] {
+            let collection: IObjectCollection = CoCreateInstance(&EnumerableObjectCollection, None, CLSCTX_INPROC_SERVER)?;
+            for entry in entries.iter().filter(|entry| entry.pinned == pinned).take(max_slots as usize) {
+                let link: IShellLinkW = CoCreateInstance(&ShellLink, None, CLSCTX_INPROC_SERVER)?;
+                link.SetPath(&HSTRING::from(exe.as_os_str()))?;
+                link.SetArguments(&HSTRING::from(page_link(&entry.page_id)))?;
+                link.SetDescription(&HSTRING::from(entry.title.as_str()))?;
+                // The jump list shows the title property, not the description
+                let properties: IPropertyStore = link.cast()?;
+                properties.SetValue(&PKEY_Title, &PROPVARIANT::from(entry.title.as_str()))?;
+                properties.Commit()?;
+                collection.AddObject(&link)?;
+            }
+
+            let items: IObjectArray = collection.cast()?;
+            if items.GetCount()? > 0 {
+                // Fails for items the user removed from the list; the rest still commit
+                if let Err(e) = list.AppendCategory(&HSTRING::from(category), &items) {
+                    tracing::warn!("Skipping jump list category {}: {}", category, e);
+                }
+            }
+        }
+
+        list.CommitList()
+    }
+}
+
+/// GTK's recently-used list, which file choosers and most Linux desktops read. Only the
+/// app's own entries are replaced.
+#[cfg(target_os = "linux")]
+mod platform {
+    use std::path::PathBuf;
+    use std::sync::OnceLock;
+    use chrono::{DateTime, Duration, Utc};
+    use regex::Regex;
+    use crate::{AppError, AppResult, models::JumpListEntry, share::PAGE_LINK_PREFIX};
+    use super::page_link;
+
+    const APP_NAME: &str = "DeviseOS";
+    const EMPTY_XBEL: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<xbel version=\"1.0\"\n      xmlns:bookmark=\"http://www.freedesktop.org/standards/desktop-bookmarks\"\n      xmlns:mime=\"http://www.freedesktop.org/standards/shared-mime-info\"\n>\n</xbel>\n";
+
+    pub fn update(entries: &[JumpListEntry]) -> AppResult<()> {
+        let path = recent_files_path()
+            .ok_or_else(|| AppError::Configuration("No data directory for the recent files list".to_string()))?;
+        let existing = match std::fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => EMPTY_XBEL.to_string(),
+            Err(e) => return Err(e.into()),
+        };
+        let exec = format!("'{} %u'", std::env::current_exe()?.display());
+        let updated = rewrite_xbel(&existing, entries, &exec, Utc::now())?;
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        // Write then rename so other apps never read a half-written list
+        let temp = path.with_extension("xbel.deviseos");
+        std::fs::write(&temp, updated)?;
+        std::fs::rename(&temp, &path)?;
+        Ok(())
+    }
+
+    fn recent_files_path() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join("recently-used.xbel"))
+    }
+
+    fn own_bookmark_pattern() -> &'static Regex {
+        static PATTERN: OnceLock<Regex> = OnceLock::new();
+        PATTERN.get_or_init(|| {
+            Regex::new(&format!(r#"(?s)\s*<bookmark href="{}[^"]*".*?</bookmark>"#, regex::escape(PAGE_LINK_PREFIX)))
+                .expect("valid bookmark pattern")
+        })
+    }
+
+    /// Drops the app's previous entries and appends `entries`. Desktops sort by modification
+    /// time, so earlier entries get later timestamps to keep pinned pages on top.
+    pub(super) fn rewrite_xbel(existing: &str, entries: &[JumpListEntry], exec: &str, now: DateTime<Utc>) -> AppResult<String> {
+        let mut contents = own_bookmark_pattern().replace_all(existing, "").into_owned();
+        let end = contents.rfind("</xbel>")
+            .ok_or_else(|| AppError::InvalidFormat("Recent files list is not an XBEL document".to_string()))?;
+
+        let bookmarks: String = entries
+            .iter()
+            .enumerate()
+            .map(|(index, entry)| {
+                let stamp = (now - Duration::seconds(index as i64)).format("%Y-%m-%dT%H:%M:%SZ");
+                format!(
+                    "  <bookmark href=\"{href}\" added=\"{stamp}\" modified=\"{stamp}\" visited=\"{stamp}\">\n    <title>{title}</title>\n    <info>\n      <metadata owner=\"http://freedesktop.org\">\n        <mime:mime-type type=\"x-scheme-handler/deviseos\"/>\n        <bookmark:applications>\n          <bookmark:application name=\"{app}\" exec=\"{exec}\" modified=\"{stamp}\" count=\"1\"/>\n        </bookmark:applications>\n      </metadata>\n    </info>\n  </bookmark>\n",
+                    href = escape_xml(&page_link(&entry.page_id)),
+                    stamp = stamp,
+                    title = escape_xml(&entry.title),
+                    app = APP_NAME,
+                    exec = escape_xml(exec),
+                )
+            })
+            .collect();
+
+        contents.insert_str(end, &bookmarks);
+        Ok(contents)
+    }
+
+    fn escape_xml(text: &str) -> String {
+        text.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;")
+            .replace('\'', "&apos;")
+    }
+}
+
+/// The macOS Dock only lists file documents, and pages live inside the vault database, so
+/// there is nothing to register there.
+#[cfg(not(any(target_os = "windows", target_os = "linux")))]
+mod platform {
+    use crate::{AppResult, models::JumpListEntry};
+
+    pub fn update(_entries: &[JumpListEntry]) -> AppResult<()> {
+        Ok(())
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use super::*;
+
+    #[test]
+    fn test_rewrite_xbel_replaces_own_entries_only() {
+        let existing = "<?xml version=\"1.0\"?>\n<xbel version=\"1.0\">\n  <bookmark href=\"file:///home/a/report.pdf\" added=\"x\"><title>Report</title></bookmark>\n  <bookmark href=\"deviseos://page/old\" added=\"x\"><title>Old</title></bookmark>\n</xbel>\n";
+        let entries = vec![
+            JumpListEntry { page_id: "p1".to_string(), title: "Plans & <ideas>".to_string(), pinned: true },
+            JumpListEntry { page_id: "p2".to_string(), title: "Inbox".to_string(), pinned: false },
+        ];
+        let now = Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap();
+        let updated = platform::rewrite_xbel(existing, &entries, "'/usr/bin/deviseos %u'", now).unwrap();
+
+        assert!(updated.contains("file:///home/a/report.pdf"));
+        assert!(!updated.contains("deviseos://page/old"));
+        assert!(updated.contains("<title>Plans &amp; &lt;ideas&gt;</title>"));
+        assert!(updated.contains("href=\"deviseos://page/p1\" added=\"2024-03-01T12:00:00Z\""));
+        assert!(updated.contains("href=\"deviseos://page/p2\" added=\"2024-03-01T11:59:59Z\""));
+        assert!(updated.trim_end().ends_with("</xbel>"));
+    }
+}
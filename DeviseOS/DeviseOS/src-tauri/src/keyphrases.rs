@@ -0,0 +1,116 @@
+use std::collections::HashMap;
+use crate::models::Keyphrase;
+
+/// Candidate phrases longer than this are usually clauses, not key ideas.
+const MAX_PHRASE_WORDS: usize = 4;
+const MIN_WORD_CHARS: usize = 2;
+
+const STOP_WORDS: &[&str] = &[
+    "a", "about", "above", "after", "again", "against", "all", "also", "am", "among", "an",
+    "and", "any", "are", "as", "at", "be", "because", "been", "before", "being", "below",
+    "between", "both", "but", "by", "can", "could", "did", "do", "does", "doing", "done",
+    "down", "during", "each", "else", "etc", "even", "ever", "every", "few", "for", "from",
+    "further", "get", "gets", "got", "had", "has", "have", "having", "he", "her", "here",
+    "hers", "him", "his", "how", "however", "i", "if", "in", "into", "is", "it", "its",
+    "just", "let", "like", "made", "make", "many", "may", "me", "might", "more", "most",
+    "much", "must", "my", "need", "no", "nor", "not", "now", "of", "off", "on", "once",
+    "one", "only", "or", "other", "our", "ours", "out", "over", "own", "per", "really",
+    "same", "shall", "she", "should", "so", "some", "still", "such", "than", "that", "the",
+    "their", "theirs", "them", "then", "there", "these", "they", "this", "those", "through",
+    "to", "too", "under", "until", "up", "upon", "us", "use", "used", "using", "very", "via",
+    "was", "we", "well", "were", "what", "when", "where", "whether", "which", "while", "who",
+    "whom", "whose", "why", "will", "with", "within", "without", "would", "yes", "yet",
+    "you", "your", "yours",
+];
+
+/// The `top_n` key phrases of `text` by RAKE: runs of content words between stop words and
+/// punctuation, scored by how often their words occur and how many other words they occur
+/// with. Higher scores first; repeats of a phrase are merged, ignoring case.
+pub fn extract(text: &str, top_n: usize) -> Vec<Keyphrase> {
+    let phrases = candidate_phrases(text);
+
+    // Word degree counts the word itself, so single-word phrases still score
+    let mut frequency: HashMap<&str, f64> = HashMap::new();
+    let mut degree: HashMap<&str, f64> = HashMap::new();
+    for phrase in &phrases {
+        for word in phrase {
+            *frequency.entry(word.as_str()).or_default() += 1.0;
+            *degree.entry(word.as_str()).or_default() += phrase.len() as f64;
+        }
+    }
+
+    let mut scored: HashMap<String, f64> = HashMap::new();
+    for phrase in &phrases {
+        let score = phrase.iter().map(|word| degree[word.as_str()] / frequency[word.as_str()]).sum();
+        scored.insert(phrase.join(" "), score);
+    }
+
+    let mut keyphrases: Vec<Keyphrase> = scored
+        .into_iter()
+        .map(|(phrase, score)| Keyphrase { phrase, score })
+        .collect();
+    keyphrases.sort_by(|a, b| {
+        b.score.partial_cmp(&a.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| a.phrase.cmp(&b.phrase))
+    });
+    keyphrases.truncate(top_n);
+    keyphrases
+}
+
+/// Lowercased word runs split at stop words and punctuation. Markdown syntax and numbers
+/// aren't words; overlong runs are dropped rather than cut, as a cut would split an idea.
+fn candidate_phrases(text: &str) -> Vec<Vec<String>> {
+    let mut phrases = Vec::new();
+    let mut current: Vec<String> = Vec::new();
+    let mut flush = |current: &mut Vec<String>| {
+        if !current.is_empty() && current.len() <= MAX_PHRASE_WORDS {
+            phrases.push(std::mem::take(current));
+        }
+        current.clear();
+    };
+
+    for token in text.split_whitespace() {
+        let word = token.trim_matches(|c: char| !c.is_alphanumeric()).to_lowercase();
+        let is_word = word.chars().count() >= MIN_WORD_CHARS
+            && word.chars().any(char::is_alphabetic)
+            && word.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '\'');
+        if !is_word || STOP_WORDS.contains(&word.as_str()) {
+            flush(&mut current);
+            continue;
+        }
+        current.push(word);
+
+        // Punctuation after a word ends the phrase
+        if token.ends_with(|c: char| !c.is_alphanumeric() && c != '-' && c != '\'') {
+            flush(&mut current);
+        }
+    }
+    flush(&mut current);
+    phrases
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_candidate_phrases_split_at_stop_words_and_punctuation() {
+        let phrases = candidate_phrases("The **vector index** speeds up semantic search, for local models.");
+        let phrases: Vec<String> = phrases.iter().map(|p| p.join(" ")).collect();
+        assert_eq!(phrases, vec!["vector index", "speeds", "semantic search", "local models"]);
+    }
+
+    #[test]
+    fn test_multi_word_phrases_rank_first() {
+        let text = "Compatibility of systems of linear constraints over the set of natural numbers. \
+                    Criteria of compatibility of a system of linear Diophantine equations are considered.";
+        let keyphrases = extract(text, 3);
+
+        assert_eq!(keyphrases[0].phrase, "linear diophantine equations");
+        assert!(keyphrases.iter().any(|k| k.phrase == "linear constraints"));
+        assert!(keyphrases.windows(2).all(|pair| pair[0].score >= pair[1].score));
+        assert!(extract("", 5).is_empty());
+        assert!(extract("the and of", 5).is_empty());
+    }
+}
@@ -0,0 +1,141 @@
+use crate::models::DetectedLanguage;
+
+/// Fewer stop words than this and a Latin or Cyrillic text is too short to tell.
+const MIN_STOP_WORD_HITS: usize = 2;
+/// Share of letters a script must have to decide the language on its own.
+const MIN_SCRIPT_SHARE: f32 = 0.5;
+
+/// Common function words, which make up a large share of any running text.
+const STOP_WORDS: &[(&str, &[&str])] = &[
+    ("en", &["the", "and", "is", "are", "of", "to", "that", "it", "with", "for", "this", "was", "you", "have", "not", "be", "on", "we"]),
+    ("es", &["el", "los", "las", "que", "y", "es", "por", "para", "con", "una", "del", "se", "no", "está", "pero", "como", "muy", "lo"]),
+    ("fr", &["le", "les", "des", "et", "est", "une", "du", "pour", "pas", "dans", "ce", "qui", "sur", "avec", "je", "nous", "il", "au"]),
+    ("de", &["der", "die", "das", "und", "ist", "nicht", "ein", "eine", "zu", "mit", "den", "von", "ich", "sie", "auf", "für", "auch", "wir"]),
+    ("it", &["il", "di", "che", "è", "un", "per", "non", "del", "con", "sono", "della", "gli", "anche", "ma", "questo", "alla", "nel", "si"]),
+    ("pt", &["o", "os", "que", "é", "um", "uma", "para", "com", "não", "do", "da", "em", "mas", "você", "está", "muito", "isso", "ao"]),
+    ("nl", &["het", "een", "en", "van", "is", "dat", "niet", "op", "te", "met", "ik", "zijn", "voor", "ook", "maar", "wij", "er", "je"]),
+    ("sv", &["och", "att", "det", "som", "är", "på", "för", "med", "inte", "jag", "har", "av", "den", "till", "vi", "om", "men", "ett"]),
+    ("pl", &["i", "w", "na", "nie", "się", "że", "jest", "z", "do", "jak", "ale", "co", "tak", "dla", "są", "od", "to", "jestem"]),
+    ("ru", &["и", "в", "не", "на", "что", "он", "с", "как", "это", "по", "но", "они", "мы", "из", "за", "то", "все", "так"]),
+    ("uk", &["і", "в", "не", "на", "що", "він", "з", "як", "це", "та", "але", "вони", "ми", "із", "за", "до", "усі", "так"]),
+];
+
+#[derive(Clone, Copy, PartialEq)]
+enum Script {
+    Latin,
+    Cyrillic,
+    Greek,
+    Arabic,
+    Hebrew,
+    Devanagari,
+    Thai,
+    Hangul,
+    Kana,
+    Han,
+}
+
+fn script(c: char) -> Option<Script> {
+    match c as u32 {
+        0x0041..=0x024F if c.is_alphabetic() => Some(Script::Latin),
+        0x0370..=0x03FF => Some(Script::Greek),
+        0x0400..=0x04FF => Some(Script::Cyrillic),
+        0x0590..=0x05FF => Some(Script::Hebrew),
+        0x0600..=0x06FF | 0x0750..=0x077F => Some(Script::Arabic),
+        0x0900..=0x097F => Some(Script::Devanagari),
+        0x0E00..=0x0E7F => Some(Script::Thai),
+        0x1100..=0x11FF | 0xAC00..=0xD7AF => Some(Script::Hangul),
+        0x3040..=0x30FF => Some(Script::Kana),
+        0x4E00..=0x9FFF | 0x3400..=0x4DBF => Some(Script::Han),
+        _ => None,
+    }
+}
+
+/// The language of `text` as an ISO 639-1 code, which is also what Whisper expects. Scripts
+/// used by one language decide on their own; Latin and Cyrillic text is matched against
+/// stop word lists. `None` when the text is too short or mixed to tell.
+pub fn detect(text: &str) -> Option<DetectedLanguage> {
+    let scripts: Vec<Script> = text.chars().filter_map(script).collect();
+    if scripts.is_empty() {
+        return None;
+    }
+    let share = |wanted: &[Script]| scripts.iter().filter(|s| wanted.contains(s)).count() as f32 / scripts.len() as f32;
+
+    // Japanese mixes kana with Han characters; Chinese has no kana
+    let by_script = [
+        (&[Script::Kana, Script::Han][..], if scripts.contains(&Script::Kana) { "ja" } else { "zh" }),
+        (&[Script::Hangul][..], "ko"),
+        (&[Script::Arabic][..], if text.chars().any(|c| matches!(c, 'پ' | 'چ' | 'ژ' | 'گ')) { "fa" } else { "ar" }),
+        (&[Script::Hebrew][..], "he"),
+        (&[Script::Greek][..], "el"),
+        (&[Script::Devanagari][..], "hi"),
+        (&[Script::Thai][..], "th"),
+    ];
+    for (wanted, language) in by_script {
+        let portion = share(wanted);
+        if portion >= MIN_SCRIPT_SHARE {
+            return Some(DetectedLanguage { language: language.to_string(), confidence: portion });
+        }
+    }
+
+    let candidates: &[&str] = if share(&[Script::Cyrillic]) >= MIN_SCRIPT_SHARE {
+        &["ru", "uk"]
+    } else if share(&[Script::Latin]) >= MIN_SCRIPT_SHARE {
+        &["en", "es", "fr", "de", "it", "pt", "nl", "sv", "pl"]
+    } else {
+        return None;
+    };
+    by_stop_words(text, candidates)
+}
+
+fn by_stop_words(text: &str, candidates: &[&str]) -> Option<DetectedLanguage> {
+    let words: Vec<String> = text
+        .split(|c: char| !c.is_alphabetic())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+
+    let mut scores: Vec<(&str, usize)> = STOP_WORDS
+        .iter()
+        .filter(|(language, _)| candidates.contains(language))
+        .map(|(language, stop_words)| (*language, words.iter().filter(|word| stop_words.contains(&word.as_str())).count()))
+        .collect();
+    scores.sort_by(|a, b| b.1.cmp(&a.1));
+
+    let (language, best) = scores[0];
+    if best < MIN_STOP_WORD_HITS {
+        return None;
+    }
+    // Margin over the runner-up; closely related languages share many stop words
+    let runner_up = scores.get(1).map(|(_, hits)| *hits).unwrap_or(0);
+    Some(DetectedLanguage { language: language.to_string(), confidence: (best - runner_up) as f32 / best as f32 })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn language(text: &str) -> Option<String> {
+        detect(text).map(|detected| detected.language)
+    }
+
+    #[test]
+    fn test_detects_by_stop_words() {
+        assert_eq!(language("The meeting is on Monday and the budget was approved."), Some("en".to_string()));
+        assert_eq!(language("La reunión es el lunes y el presupuesto está aprobado."), Some("es".to_string()));
+        assert_eq!(language("Die Besprechung ist am Montag und das Budget ist genehmigt."), Some("de".to_string()));
+        assert_eq!(language("Привет, как дела? Это не так просто."), Some("ru".to_string()));
+    }
+
+    #[test]
+    fn test_detects_by_script() {
+        assert_eq!(language("東京で会議があります"), Some("ja".to_string()));
+        assert_eq!(language("我们明天开会"), Some("zh".to_string()));
+        assert_eq!(language("회의는 월요일입니다"), Some("ko".to_string()));
+    }
+
+    #[test]
+    fn test_short_text_is_undetermined() {
+        assert_eq!(language("Budget"), None);
+        assert_eq!(language("42 / 7"), None);
+    }
+}
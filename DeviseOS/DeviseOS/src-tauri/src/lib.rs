@@ -1,6 +1,8 @@
 use std::sync::Arc;
 use std::path::PathBuf;
-use tauri::{Manager, State};
+use tauri::{Emitter, Manager, State};
+use tauri_plugin_deep_link::DeepLinkExt;
+use tauri_plugin_dialog::DialogExt;
 use tokio::sync::RwLock;
 
 mod database;
@@ -8,9 +10,90 @@ mod models;
 mod encryption;
 mod ai;
 mod errors;
+mod automations;
+mod mqtt;
+mod scripting;
+mod bundles;
+mod paste;
+mod importer;
+mod screenshot;
+mod scanner;
+mod ink;
+mod policy;
+mod updates;
+mod crash;
+mod diagnostics;
+mod logging;
+mod locale;
+mod search;
+mod export;
+mod share;
+mod sync_protocol;
+mod reindex;
+mod web_viewer;
+mod snapshots;
+mod find_replace;
+mod content_scan;
+mod titles;
+mod tags;
+mod zettel;
+mod moc;
+mod transcription;
+mod stale;
+mod llm;
+mod embedder;
+mod settings;
+mod qa;
+mod jump_list;
+mod os_search;
+mod language;
+mod subtitles;
+mod web_bundle;
+mod writing;
+mod sql_console;
+mod health;
+mod sentiment;
+mod voice_match;
+mod vad;
+mod model_downloads;
+mod encryption_audit;
+mod jobs;
+mod keyphrases;
+mod trash;
+mod sqlcipher;
+mod maintenance;
+mod daily;
+mod properties;
+mod wikilinks;
+mod transclusion;
+mod graph;
+mod exif;
+mod media_stream;
+mod image_compression;
+mod storage;
+mod audio;
+mod voice_codec;
+mod dictation;
+mod voice_commands;
+mod cloud_sync;
+mod tasks;
 
-use database::Database;
+use database::{Database, VECTOR_INDEX_KEY};
+use titles::AUTO_TITLE_KEY;
+use zettel::ZETTEL_IDS_KEY;
 use ai::AIService;
+use automations::AutomationEngine;
+use mqtt::MqttPublisher;
+use scripting::ScriptRunner;
+use updates::UpdateChecker;
+use web_viewer::WebViewer;
+use snapshots::SnapshotStore;
+use jobs::JobQueue;
+use dictation::Dictation;
+use cloud_sync::SyncService;
+use media_stream::MediaCache;
+use writing::WritingAction;
+use importer::FileGrants;
 use encryption::EncryptionManager;
 use errors::{AppError, AppResult};
 use models::*;
@@ -18,19 +101,77 @@ use models::*;
 pub struct AppState {
     pub database: Arc<RwLock<Database>>,
     pub ai_service: Arc<RwLock<AIService>>,
+    pub automations: Arc<AutomationEngine>,
+    pub mqtt: Arc<MqttPublisher>,
+    pub scripts: Arc<ScriptRunner>,
+    pub updates: Arc<UpdateChecker>,
+    pub web_viewer: Arc<WebViewer>,
+    pub jobs: Arc<JobQueue>,
+    pub dictation: Arc<Dictation>,
+    pub sync: Arc<SyncService>,
+    pub media_cache: Arc<MediaCache>,
     pub config: AppConfig,
 }
 
 impl AppState {
     pub async fn new() -> AppResult<Self> {
-        let config = AppConfig::default();
+        let config = policy::load_config()?;
         
         // Ensure data directory exists
         if let Some(parent) = config.database_path.parent() {
             std::fs::create_dir_all(parent)?;
         }
         
-        // Initialize encryption if enabled
+        // Initialize database
+        let database = Self::open_database(&config).await?;
+        if config.sqlite.sqlcipher && !database.is_file_encrypted() {
+            tracing::warn!("The database file predates SQLCipher encryption; run encrypt_database_file to convert it");
+        }
+        let adopted = database.adopt_unlabelled_embeddings(config.embedding_model.model_name()).await?;
+        if adopted > 0 {
+            tracing::info!("Labelled {} existing embeddings as {}", adopted, config.embedding_model.model_name());
+        }
+        let embedding_status = reindex::embedding_model_status(&database, &config.embedding_model).await?;
+        if embedding_status.stale > 0 {
+            tracing::warn!(
+                "{} embeddings were produced by a different model than {} and are ignored until migrated",
+                embedding_status.stale,
+                embedding_status.current_model
+            );
+        }
+        
+        // Initialize AI service
+        let device_preference = database.get_setting(ai::AI_DEVICE_KEY).await?
+            .and_then(|value| AiDevicePreference::parse(&value))
+            .unwrap_or_default();
+        let llm_model_path = database.get_setting(llm::LLM_MODEL_PATH_KEY).await?
+            .map(PathBuf::from)
+            .unwrap_or_else(|| config.llm_model_path.clone());
+        let ai_service = AIService::new(device_preference, llm_model_path, config.ai_mode)?;
+        
+        // Initialize automation engine
+        let scripts = Arc::new(ScriptRunner::new(config.scripts_path.clone()));
+        let automations = AutomationEngine::new(scripts.clone())?;
+        
+        Ok(Self {
+            database: Arc::new(RwLock::new(database)),
+            ai_service: Arc::new(RwLock::new(ai_service)),
+            automations: Arc::new(automations),
+            mqtt: Arc::new(MqttPublisher::new()),
+            scripts,
+            updates: Arc::new(UpdateChecker::new()?),
+            web_viewer: Arc::new(WebViewer::new()),
+            jobs: Arc::new(JobQueue::new()),
+            dictation: Arc::new(Dictation::new()),
+            sync: Arc::new(SyncService::new()),
+            media_cache: Arc::new(MediaCache::default()),
+            config,
+        })
+    }
+
+    /// Opens the database with the content key, when encryption is enabled, and turns the
+    /// vector index back on if it was in use.
+    async fn open_database(config: &AppConfig) -> AppResult<Database> {
         let encryption_manager = if config.encryption_enabled {
             if !config.encryption_key_path.exists() {
                 // Generate new encryption key
@@ -41,18 +182,101 @@ impl AppState {
         } else {
             None
         };
+
+        let mut database = Database::new(&config.database_path, encryption_manager, &config.sqlite).await?;
+        database.set_image_config(config.images.clone());
+        database.set_max_file_size(config.max_file_size);
+        if database.get_setting(VECTOR_INDEX_KEY).await?.as_deref() == Some("true") {
+            // Brute-force search still works, so a missing extension shouldn't stop startup
+            if let Err(e) = database.enable_vector_index().await {
+                tracing::warn!("Vector index unavailable, falling back to in-memory search: {}", e);
+            }
+        }
+        Ok(database)
+    }
+
+    /// Fires automations and MQTT publishing for an event in the background so slow
+    /// scripts, webhooks or brokers never block the caller.
+    pub fn dispatch_automation_event(&self, event: AutomationEvent) {
+        let database = self.database.clone();
+        let automations = self.automations.clone();
+        let mqtt = self.mqtt.clone();
         
-        // Initialize database
-        let database = Database::new(&config.database_path, encryption_manager).await?;
-        
-        // Initialize AI service
-        let ai_service = AIService::new()?;
-        
-        Ok(Self {
-            database: Arc::new(RwLock::new(database)),
-            ai_service: Arc::new(RwLock::new(ai_service)),
-            config,
-        })
+        tauri::async_runtime::spawn(async move {
+            let database = database.read().await;
+            if let Err(e) = automations.dispatch(&database, &event).await {
+                tracing::warn!("Failed to dispatch automation event: {}", e);
+            }
+            if let Err(e) = mqtt.publish_event(&database, &event).await {
+                tracing::warn!("Failed to publish {} event to MQTT: {}", event.kind(), e);
+            }
+        });
+    }
+
+    /// Queues (re-)embedding of a note or page for the background AI worker. A failure to
+    /// queue is logged rather than failing the edit.
+    pub async fn queue_embedding(&self, database: &Database, owner_id: &str, owner: EmbeddingOwner, priority: AiJobPriority) {
+        let kind = AiJobKind::Embed { owner_id: owner_id.to_string(), owner };
+        if let Err(e) = self.jobs.enqueue(database, kind, priority).await {
+            tracing::warn!("Failed to queue embedding for {}: {}", owner_id, e);
+        }
+    }
+
+    /// What follows a change to a page's content, wherever it was made: re-embedding it and
+    /// linking the citations and wiki-links it now has.
+    pub async fn page_content_changed(&self, database: &Database, page_id: &str) -> AppResult<()> {
+        self.queue_embedding(database, page_id, EmbeddingOwner::Page, AiJobPriority::Normal).await;
+        zettel::link_citations(database, page_id).await?;
+        wikilinks::link_wikilinks(database, page_id).await
+    }
+
+    /// What follows an edit to a page, wherever it was made: the content hooks when its
+    /// content changed and, when it was renamed from the first title to the second, moving
+    /// `[[links]]` to the new title and refreshing the jump list and OS search stub.
+    pub async fn page_updated(&self, database: &Database, page_id: &str, content_changed: bool, rename: Option<(&str, &str)>) -> AppResult<()> {
+        if content_changed {
+            self.page_content_changed(database, page_id).await?;
+        }
+        let Some((old_title, new_title)) = rename.filter(|(old, new)| old.trim() != new.trim()) else {
+            return Ok(());
+        };
+        for id in wikilinks::follow_rename(database, page_id, old_title, new_title).await? {
+            self.queue_embedding(database, &id, EmbeddingOwner::Page, AiJobPriority::Low).await;
+        }
+        if let Err(e) = jump_list::refresh(database).await {
+            tracing::warn!("Failed to update jump list: {}", e);
+        }
+        if let Err(e) = os_search::refresh_page(database, page_id).await {
+            tracing::warn!("Failed to update OS search stubs: {}", e);
+        }
+        Ok(())
+    }
+
+    /// What follows creating a page, wherever it was created: the content hooks above, the
+    /// `page_created` automation event and the page's OS search stub.
+    pub async fn page_created(&self, database: &Database, page: &Page) -> AppResult<()> {
+        self.page_content_changed(database, &page.id).await?;
+        self.dispatch_automation_event(AutomationEvent::page_created(page));
+        if let Err(e) = os_search::refresh_page(database, &page.id).await {
+            tracing::warn!("Failed to update OS search stubs: {}", e);
+        }
+        Ok(())
+    }
+
+    /// Records a sensitive action when audit logging is enabled. When a policy requires the
+    /// audit log, a failed write fails the action.
+    pub async fn audit(&self, database: &Database, action: &str, target: Option<&str>) -> AppResult<()> {
+        if !self.config.audit_log_enabled {
+            return Ok(());
+        }
+        match database.record_audit_event(action, target).await {
+            Err(e) if self.config.policy.as_ref().map(|p| p.require_audit_log).unwrap_or(false) => Err(e),
+            Err(e) => {
+                tracing::warn!("Failed to record audit event {}: {}", action, e);
+                Ok(())
+            }
+            Ok(()) => Ok(()),
+        }
     }
 }
 
@@ -66,12 +290,7 @@ async fn create_note(
     let note = database.create_note(request.title, request.content, request.tags).await?;
     
     // Generate embeddings for the note
-    let ai_service = state.ai_service.read().await;
-    if ai_service.is_embedding_available() {
-        if let Ok(embeddings) = ai_service.generate_embeddings(&note.content).await {
-            let _ = database.store_embedding(&note.id, &embeddings).await;
-        }
-    }
+    state.queue_embedding(&database, &note.id, EmbeddingOwner::Note, AiJobPriority::Normal).await;
     
     Ok(note)
 }
@@ -87,6 +306,18 @@ async fn get_notes(
     Ok(notes)
 }
 
+/// Notes newest first; pass the returned `next_cursor` back to get the following page.
+#[tauri::command]
+async fn list_notes(
+    state: State<'_, AppState>,
+    limit: Option<usize>,
+    cursor: Option<String>,
+) -> Result<SearchPage<Note>, String> {
+    let database = state.database.read().await;
+    let notes = database.list_notes(limit, cursor.as_deref()).await?;
+    Ok(notes)
+}
+
 #[tauri::command]
 async fn get_note(
     state: State<'_, AppState>,
@@ -106,13 +337,8 @@ async fn update_note(
     database.update_note(&request.id, request.title, request.content.clone(), request.tags).await?;
     
     // Update embeddings if content changed
-    if let Some(content) = request.content {
-        let ai_service = state.ai_service.read().await;
-        if ai_service.is_embedding_available() {
-            if let Ok(embeddings) = ai_service.generate_embeddings(&content).await {
-                let _ = database.store_embedding(&request.id, &embeddings).await;
-            }
-        }
+    if request.content.is_some() {
+        state.queue_embedding(&database, &request.id, EmbeddingOwner::Note, AiJobPriority::Normal).await;
     }
     
     Ok(())
@@ -124,7 +350,8 @@ async fn delete_note(
     id: String,
 ) -> Result<(), String> {
     let database = state.database.read().await;
-    database.delete_note(&id).await?;
+    state.audit(&database, "delete_note", Some(&id)).await?;
+    database.trash_note(&id).await?;
     Ok(())
 }
 
@@ -132,9 +359,9 @@ async fn delete_note(
 async fn search_notes(
     state: State<'_, AppState>,
     request: SearchRequest,
-) -> Result<Vec<Note>, String> {
+) -> Result<SearchPage<NoteSearchMatch>, String> {
     let database = state.database.read().await;
-    let notes = database.search_notes(&request.query).await?;
+    let notes = database.search_notes(&request).await?;
     Ok(notes)
 }
 
@@ -143,7 +370,8 @@ async fn semantic_search(
     state: State<'_, AppState>,
     query: String,
     limit: Option<usize>,
-) -> Result<Vec<SearchResult>, String> {
+    cursor: Option<String>,
+) -> Result<SearchPage<SearchResult>, String> {
     let database = state.database.read().await;
     let ai_service = state.ai_service.read().await;
     
@@ -151,7 +379,20 @@ async fn semantic_search(
         return Err("Embedding model not available".to_string());
     }
     
-    let results = ai_service.semantic_search(&*database, &query, limit.unwrap_or(10)).await?;
+    let results = ai_service.semantic_search(&*database, &query, limit.unwrap_or(10), cursor.as_deref()).await?;
+    Ok(results)
+}
+
+#[tauri::command]
+async fn hybrid_search(
+    state: State<'_, AppState>,
+    request: HybridSearchRequest,
+) -> Result<Vec<SearchResult>, String> {
+    let database = state.database.read().await;
+    let ai_service = state.ai_service.read().await;
+
+    let weights = request.weights.unwrap_or_default();
+    let results = ai_service.hybrid_search(&*database, &request.query, request.limit.unwrap_or(10), weights).await?;
     Ok(results)
 }
 
@@ -159,6 +400,7 @@ async fn semantic_search(
 async fn transcribe_audio(
     state: State<'_, AppState>,
     audio_data: Vec<u8>,
+    language: Option<String>,
 ) -> Result<String, String> {
     let ai_service = state.ai_service.read().await;
     
@@ -166,508 +408,2739 @@ async fn transcribe_audio(
         return Err("Whisper model not available".to_string());
     }
     
-    let transcription = ai_service.transcribe_audio(&audio_data).await?;
+    let transcription = ai_service.transcribe_audio(&audio_data, language.as_deref()).await?;
     Ok(transcription)
 }
 
+/// Transcribes long recordings in windows, emitting `transcription-partial` events with
+/// partial transcripts for live captions.
+#[tauri::command]
+async fn transcribe_audio_stream(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    audio_data: Vec<u8>,
+    language: Option<String>,
+    window_secs: Option<u32>,
+) -> Result<TranscriptionStream, String> {
+    let stream = transcription::spawn_transcription(app, state.ai_service.clone(), audio_data, language, window_secs).await?;
+    Ok(stream)
+}
+
+/// Starts dictating into a page. The frontend records the microphone and sends it with
+/// `push_dictation_audio`; the text of each phrase is reported through `dictation-text`
+/// events for the editor to insert and save. Sessions end by themselves after a minute
+/// without audio.
+#[tauri::command]
+async fn start_dictation(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    page_id: String,
+    language: Option<String>,
+) -> Result<DictationSession, String> {
+    let session = state.dictation.start(app, &page_id, language).await?;
+    Ok(session)
+}
+
+/// Microphone audio for a dictation session, as 16 kHz mono 16-bit PCM.
+#[tauri::command]
+async fn push_dictation_audio(
+    state: State<'_, AppState>,
+    session_id: String,
+    audio_data: Vec<u8>,
+) -> Result<(), String> {
+    state.dictation.push(&session_id, audio_data).await?;
+    Ok(())
+}
+
+#[tauri::command]
+async fn stop_dictation(
+    state: State<'_, AppState>,
+    session_id: String,
+) -> Result<(), String> {
+    state.dictation.stop(&session_id).await?;
+    Ok(())
+}
+
+/// The language of `content` as an ISO 639-1 code, with a confidence.
+#[tauri::command]
+async fn detect_language(
+    state: State<'_, AppState>,
+    content: String,
+) -> Result<Option<DetectedLanguage>, String> {
+    let ai_service = state.ai_service.read().await;
+    Ok(ai_service.detect_language(&content))
+}
+
+/// Stores the recording on a note or a page straight away; its transcription is filled in
+/// by a background job, reported through `ai-job-updated` events. Page recordings are
+/// transcribed in the page's language when one is set or detected.
 #[tauri::command]
 async fn add_voice_annotation(
     state: State<'_, AppState>,
     request: VoiceAnnotationRequest,
 ) -> Result<VoiceAnnotation, String> {
-    let ai_service = state.ai_service.read().await;
-    
-    // Transcribe audio
-    let transcription = if ai_service.is_whisper_available() {
-        ai_service.transcribe_audio(&request.audio_data).await?
+    let database = state.database.read().await;
+    let language = match &request.page_id {
+        Some(page_id) => {
+            let language = database.get_language_settings(page_id).await?;
+            (!matches!(language.source, LanguageSource::Default)).then_some(language.whisper_language)
+        }
+        None => None,
+    };
+
+    let whisper_available = state.ai_service.read().await.is_whisper_available();
+    let transcription = if whisper_available {
+        String::new()
     } else {
         "Audio transcription not available".to_string()
     };
     
     // Calculate duration (simplified)
     let duration = request.audio_data.len() as f64 / 32000.0; // Assume 16kHz mono
+
+    let format = request.format
+        .or_else(|| audio::sniff_format(&request.audio_data))
+        .unwrap_or_else(|| voice_codec::PCM_FORMAT.to_string());
+    let metadata = VoiceMetadata { format, ..VoiceMetadata::default() };
     
     // Store voice annotation
-    let database = state.database.read().await;
-    let annotation = database.add_voice_annotation(
-        &request.note_id,
-        request.audio_data,
-        transcription,
-        duration,
-    ).await?;
+    let annotation = match (request.note_id, request.page_id) {
+        (Some(note_id), None) => database.add_voice_annotation(&note_id, request.audio_data, transcription, duration, metadata).await?,
+        (None, Some(page_id)) => database.add_page_voice_annotation(&page_id, request.audio_data, transcription, duration, metadata).await?,
+        _ => return Err(AppError::Validation("A voice annotation needs either a note_id or a page_id".to_string()).into()),
+    };
+    
+    if whisper_available {
+        let kind = AiJobKind::Transcribe { annotation_id: annotation.id.clone(), language };
+        state.jobs.enqueue(&database, kind, AiJobPriority::High).await?;
+    }
     
     Ok(annotation)
 }
 
+/// Runs a spoken command such as "new note titled groceries: milk, eggs" and returns what
+/// it was understood as and what was done.
 #[tauri::command]
-async fn suggest_tags(
+async fn process_voice_command(
     state: State<'_, AppState>,
-    content: String,
-) -> Result<Vec<String>, String> {
-    let ai_service = state.ai_service.read().await;
-    let suggestions = ai_service.suggest_tags(&content).await?;
-    Ok(suggestions)
+    audio_data: Vec<u8>,
+) -> Result<VoiceCommandResult, String> {
+    let result = voice_commands::process(&state, &audio_data).await?;
+    Ok(result)
 }
 
+/// Notebooks, tags and pages whose names sound like a spoken reference such as "my project
+/// alpha notebook", best match first, so voice commands don't need exact titles.
 #[tauri::command]
-async fn get_tags(
+async fn resolve_spoken_name(
     state: State<'_, AppState>,
-) -> Result<Vec<Tag>, String> {
+    phrase: String,
+    kinds: Option<Vec<SpokenNameKind>>,
+) -> Result<Vec<SpokenNameMatch>, String> {
     let database = state.database.read().await;
-    let tags = database.get_tags().await?;
-    Ok(tags)
-}
-
-#[tauri::command]
-async fn analyze_sentiment(
-    state: State<'_, AppState>,
-    content: String,
-) -> Result<f64, String> {
-    let ai_service = state.ai_service.read().await;
-    let sentiment = ai_service.analyze_sentiment(&content).await?;
-    Ok(sentiment)
+    let matches = voice_match::resolve(&database, &phrase, kinds.as_deref()).await?;
+    Ok(matches)
 }
 
 #[tauri::command]
-async fn extract_entities(
+async fn suggest_tags(
     state: State<'_, AppState>,
     content: String,
+    mode: Option<TagSuggestionMode>,
 ) -> Result<Vec<String>, String> {
     let ai_service = state.ai_service.read().await;
-    let entities = ai_service.extract_entities(&content).await?;
-    Ok(entities)
+    let suggestions = match mode.unwrap_or_default() {
+        TagSuggestionMode::Keywords => ai_service.suggest_tags(&content).await?,
+        TagSuggestionMode::Vocabulary => {
+            let database = state.database.read().await;
+            let vocabulary = database.get_tags().await?;
+            ai_service.suggest_tags_from_vocabulary(&content, &vocabulary).await?
+        }
+    };
+    Ok(suggestions)
 }
 
+/// The `top_n` (default 10) key phrases of `content`, best first.
 #[tauri::command]
-async fn generate_summary(
+async fn extract_keyphrases(
     state: State<'_, AppState>,
     content: String,
-) -> Result<Option<String>, String> {
+    top_n: Option<usize>,
+) -> Result<Vec<Keyphrase>, String> {
     let ai_service = state.ai_service.read().await;
-    let summary = ai_service.generate_summary(&content).await?;
-    Ok(summary)
+    let keyphrases = ai_service.extract_keyphrases(&content, top_n.unwrap_or(10)).await?;
+    Ok(keyphrases)
 }
 
 #[tauri::command]
-async fn process_note_ai(
+async fn get_tags(
     state: State<'_, AppState>,
-    content: String,
-) -> Result<AIProcessingResult, String> {
-    let ai_service = state.ai_service.read().await;
-    let result = ai_service.process_note(&content).await?;
-    Ok(result)
+) -> Result<Vec<Tag>, String> {
+    let database = state.database.read().await;
+    let tags = database.get_tags().await?;
+    Ok(tags)
 }
 
 #[tauri::command]
-async fn get_app_config(
+async fn suggest_tag_merges(
     state: State<'_, AppState>,
-) -> Result<AppConfig, String> {
-    Ok(state.config.clone())
+) -> Result<Vec<TagMergeSuggestion>, String> {
+    let database = state.database.read().await;
+    let tags = database.get_tags().await?;
+    Ok(tags::suggest_merges(&tags))
 }
 
 #[tauri::command]
-async fn set_setting(
+async fn merge_tags(
     state: State<'_, AppState>,
-    key: String,
-    value: String,
-) -> Result<(), String> {
+    merges: Vec<TagMerge>,
+) -> Result<TagMergeResult, String> {
     let database = state.database.read().await;
-    database.set_setting(&key, &value).await?;
-    Ok(())
+    let result = database.merge_tags(&merges).await?;
+    let targets: Vec<&str> = merges.iter().map(|m| m.target.as_str()).collect();
+    state.audit(&database, "merge_tags", Some(&targets.join(","))).await?;
+    Ok(result)
 }
 
+/// Renames, recolors or redescribes a tag; a rename updates every note and page using it.
 #[tauri::command]
-async fn get_setting(
+async fn update_tag(
     state: State<'_, AppState>,
-    key: String,
-) -> Result<Option<String>, String> {
+    request: UpdateTagRequest,
+) -> Result<Tag, String> {
     let database = state.database.read().await;
-    let value = database.get_setting(&key).await?;
-    Ok(value)
+    let tag = database.update_tag(&request).await?;
+    state.audit(&database, "update_tag", Some(&tag.id)).await?;
+    Ok(tag)
 }
 
+/// Deletes a tag and removes it from every note and page.
 #[tauri::command]
-async fn initialize_ai_models(
+async fn delete_tag(
     state: State<'_, AppState>,
+    id: String,
 ) -> Result<(), String> {
-    let mut ai_service = state.ai_service.write().await;
-    
-    // Initialize Whisper model
-    ai_service.initialize_whisper(
-        state.config.whisper_model.clone(),
-        &state.config.ai_models_path,
-    ).await?;
-    
-    // Initialize embedding model
-    ai_service.initialize_embedding_model(
-        state.config.embedding_model.clone(),
-        &state.config.ai_models_path,
-    ).await?;
-    
+    let database = state.database.read().await;
+    state.audit(&database, "delete_tag", Some(&id)).await?;
+    let (pages, notes) = database.delete_tag(&id).await?;
+    tracing::info!("Removed tag {} from {} pages and {} notes", id, pages, notes);
     Ok(())
 }
 
 #[tauri::command]
-async fn get_ai_status(
-    state: State<'_, AppState>,
-) -> Result<serde_json::Value, String> {
-    let ai_service = state.ai_service.read().await;
-    
-    Ok(serde_json::json!({
-        "whisper_available": ai_service.is_whisper_available(),
-        "embedding_available": ai_service.is_embedding_available(),
-        "whisper_model": ai_service.get_whisper_model(),
-        "embedding_model": ai_service.get_embedding_model(),
-    }))
-}
-
-// Notebook Management Commands
-
-#[tauri::command]
-async fn create_notebook(
+async fn get_pages_by_tag(
     state: State<'_, AppState>,
-    request: CreateNotebookRequest,
-) -> Result<Notebook, String> {
+    tag: String,
+    notebook_id: Option<String>,
+) -> Result<Vec<Page>, String> {
     let database = state.database.read().await;
-    let notebook = database.create_notebook(request).await?;
-    Ok(notebook)
+    let pages = database.get_pages_by_tag(&tag, notebook_id.as_deref()).await?;
+    Ok(pages)
 }
 
 #[tauri::command]
-async fn get_notebooks(
+async fn get_notes_by_tag(
     state: State<'_, AppState>,
-) -> Result<Vec<Notebook>, String> {
+    tag: String,
+) -> Result<Vec<Note>, String> {
     let database = state.database.read().await;
-    let notebooks = database.get_notebooks().await?;
-    Ok(notebooks)
+    let notes = database.get_notes_by_tag(&tag).await?;
+    Ok(notes)
 }
 
 #[tauri::command]
-async fn get_notebook(
-    state: State<'_, AppState>,
-    id: String,
-) -> Result<Option<Notebook>, String> {
+async fn get_property_definitions(state: State<'_, AppState>) -> Result<Vec<PropertyDefinition>, String> {
     let database = state.database.read().await;
-    let notebook = database.get_notebook(&id).await?;
-    Ok(notebook)
+    let definitions = database.get_property_definitions().await?;
+    Ok(definitions)
 }
 
 #[tauri::command]
-async fn update_notebook(
+async fn define_property(
     state: State<'_, AppState>,
-    request: UpdateNotebookRequest,
-) -> Result<(), String> {
+    request: DefinePropertyRequest,
+) -> Result<PropertyDefinition, String> {
     let database = state.database.read().await;
-    database.update_notebook(request).await?;
-    Ok(())
+    let definition = database.define_property(&request).await?;
+    Ok(definition)
 }
 
 #[tauri::command]
-async fn delete_notebook(
+async fn delete_property_definition(
     state: State<'_, AppState>,
-    id: String,
-) -> Result<(), String> {
+    name: String,
+) -> Result<u64, String> {
     let database = state.database.read().await;
-    database.delete_notebook(&id).await?;
-    Ok(())
+    let removed = database.delete_property_definition(&name).await?;
+    state.audit(&database, "delete_property_definition", Some(&name)).await?;
+    Ok(removed)
 }
 
 #[tauri::command]
-async fn get_notebook_hierarchy(
+async fn set_page_property(
     state: State<'_, AppState>,
-    id: String,
-) -> Result<NotebookHierarchy, String> {
+    page_id: String,
+    name: String,
+    value: PropertyValue,
+) -> Result<PageProperty, String> {
     let database = state.database.read().await;
-    let hierarchy = database.get_notebook_hierarchy(&id).await?;
-    Ok(hierarchy)
+    let property = database.set_page_property(&page_id, &name, &value).await?;
+    Ok(property)
 }
 
-// Section Management Commands
-
 #[tauri::command]
-async fn create_section(
+async fn remove_page_property(
     state: State<'_, AppState>,
-    request: CreateSectionRequest,
-) -> Result<Section, String> {
+    page_id: String,
+    name: String,
+) -> Result<bool, String> {
     let database = state.database.read().await;
-    let section = database.create_section(request).await?;
-    Ok(section)
+    let removed = database.remove_page_property(&page_id, &name).await?;
+    Ok(removed)
 }
 
 #[tauri::command]
-async fn get_sections(
+async fn get_page_properties(
     state: State<'_, AppState>,
-    notebook_id: String,
-) -> Result<Vec<Section>, String> {
+    page_id: String,
+) -> Result<Vec<PageProperty>, String> {
     let database = state.database.read().await;
-    let sections = database.get_sections(&notebook_id).await?;
-    Ok(sections)
+    let properties = database.get_page_properties(&page_id).await?;
+    Ok(properties)
 }
 
 #[tauri::command]
-async fn get_section(
+async fn filter_pages_by_properties(
     state: State<'_, AppState>,
-    id: String,
-) -> Result<Option<Section>, String> {
+    request: FilterPagesRequest,
+) -> Result<Vec<Page>, String> {
     let database = state.database.read().await;
-    let section = database.get_section(&id).await?;
-    Ok(section)
+    let pages = database.filter_pages_by_properties(&request).await?;
+    Ok(pages)
+}
+
+#[tauri::command]
+async fn analyze_sentiment(
+    state: State<'_, AppState>,
+    content: String,
+) -> Result<f64, String> {
+    let ai_service = state.ai_service.read().await;
+    let sentiment = ai_service.analyze_sentiment(&content).await?;
+    Ok(sentiment)
+}
+
+#[tauri::command]
+async fn extract_entities(
+    state: State<'_, AppState>,
+    content: String,
+) -> Result<Vec<String>, String> {
+    let ai_service = state.ai_service.read().await;
+    let entities = ai_service.extract_entities(&content).await?;
+    Ok(entities)
+}
+
+#[tauri::command]
+async fn generate_summary(
+    state: State<'_, AppState>,
+    content: String,
+) -> Result<Option<String>, String> {
+    let ai_service = state.ai_service.read().await;
+    let summary = ai_service.generate_summary(&content).await?;
+    Ok(summary)
+}
+
+/// Answers a question from the most relevant pages with the local language model.
+#[tauri::command]
+async fn chat_with_notes(
+    state: State<'_, AppState>,
+    question: String,
+    top_k: Option<usize>,
+) -> Result<ChatAnswer, String> {
+    let database = state.database.read().await;
+    let ai_service = state.ai_service.read().await;
+    let answer = ai_service.chat_with_notes(&database, &question, top_k.unwrap_or(5).clamp(1, 20)).await?;
+    Ok(answer)
+}
+
+/// Answers a question from one page and its voice transcriptions, with the supporting span.
+#[tauri::command]
+async fn ask_page(
+    state: State<'_, AppState>,
+    page_id: String,
+    question: String,
+) -> Result<PageAnswer, String> {
+    let database = state.database.read().await;
+    let ai_service = state.ai_service.read().await;
+    let answer = qa::ask_page(&database, &ai_service, &page_id, &question).await?;
+    Ok(answer)
+}
+
+/// Translates text into `target_lang` (a BCP-47 tag such as "fr" or "pt-BR").
+#[tauri::command]
+async fn translate_content(
+    state: State<'_, AppState>,
+    content: String,
+    target_lang: String,
+) -> Result<String, String> {
+    let ai_service = state.ai_service.read().await;
+    let translated = ai_service.translate_text(&content, &target_lang).await?;
+    Ok(translated)
+}
+
+#[tauri::command]
+async fn rewrite_text(
+    state: State<'_, AppState>,
+    content: String,
+    style: RewriteStyle,
+) -> Result<String, String> {
+    let ai_service = state.ai_service.read().await;
+    let rewritten = ai_service.rewrite_text(&content, style).await?;
+    Ok(rewritten)
+}
+
+#[tauri::command]
+async fn expand_text(
+    state: State<'_, AppState>,
+    content: String,
+) -> Result<String, String> {
+    let ai_service = state.ai_service.read().await;
+    let expanded = ai_service.expand_text(&content).await?;
+    Ok(expanded)
+}
+
+/// Suggested rewrite of the selected byte range; `style` covers tone (formal, casual) as
+/// well as concise and clearer rewrites.
+#[tauri::command]
+async fn ai_rewrite(
+    state: State<'_, AppState>,
+    content: String,
+    start: usize,
+    end: usize,
+    style: RewriteStyle,
+) -> Result<WritingSuggestion, String> {
+    let ai_service = state.ai_service.read().await;
+    let suggestion = ai_service.writing_suggestion(&content, start, end, WritingAction::Rewrite(style)).await?;
+    Ok(suggestion)
+}
+
+#[tauri::command]
+async fn ai_expand(
+    state: State<'_, AppState>,
+    content: String,
+    start: usize,
+    end: usize,
+) -> Result<WritingSuggestion, String> {
+    let ai_service = state.ai_service.read().await;
+    let suggestion = ai_service.writing_suggestion(&content, start, end, WritingAction::Expand).await?;
+    Ok(suggestion)
+}
+
+#[tauri::command]
+async fn ai_fix_grammar(
+    state: State<'_, AppState>,
+    content: String,
+    start: usize,
+    end: usize,
+) -> Result<WritingSuggestion, String> {
+    let ai_service = state.ai_service.read().await;
+    let suggestion = ai_service.writing_suggestion(&content, start, end, WritingAction::FixGrammar).await?;
+    Ok(suggestion)
+}
+
+#[tauri::command]
+async fn process_note_ai(
+    state: State<'_, AppState>,
+    content: String,
+) -> Result<AIProcessingResult, String> {
+    let ai_service = state.ai_service.read().await;
+    let result = ai_service.process_note(&content).await?;
+    Ok(result)
+}
+
+#[tauri::command]
+async fn get_app_config(
+    state: State<'_, AppState>,
+) -> Result<AppConfig, String> {
+    Ok(state.config.clone())
+}
+
+/// Values of registered settings are validated and normalized before they're stored.
+#[tauri::command]
+async fn set_setting(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    key: String,
+    value: String,
+) -> Result<(), String> {
+    policy::ensure_setting_unlocked(&state.config, &key)?;
+    settings::validate(&key, &value)?;
+    state.audit(&*state.database.read().await, "set_setting", Some(&key)).await?;
+    settings::set(&app, &state, &key, &value).await?;
+    Ok(())
+}
+
+/// Restores a setting's default and returns it.
+#[tauri::command]
+async fn reset_setting(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    key: String,
+) -> Result<Option<String>, String> {
+    policy::ensure_setting_unlocked(&state.config, &key)?;
+    state.audit(&*state.database.read().await, "reset_setting", Some(&key)).await?;
+    let default = settings::reset(&app, &state, &key).await?;
+    Ok(default)
+}
+
+#[tauri::command]
+async fn get_setting_schemas() -> Result<Vec<SettingSchema>, String> {
+    Ok(settings::schemas())
+}
+
+#[tauri::command]
+async fn get_locale(
+    state: State<'_, AppState>,
+) -> Result<String, String> {
+    let database = state.database.read().await;
+    let locale = locale::get_locale(&database).await?;
+    Ok(locale)
+}
+
+#[tauri::command]
+async fn set_locale(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    locale: String,
+) -> Result<(), String> {
+    settings::set(&app, &state, locale::LOCALE_KEY, &locale).await?;
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_compliance_policy(
+    state: State<'_, AppState>,
+) -> Result<Option<CompliancePolicy>, String> {
+    Ok(state.config.policy.clone())
+}
+
+#[tauri::command]
+async fn get_audit_log(
+    state: State<'_, AppState>,
+    limit: Option<usize>,
+) -> Result<Vec<AuditLogEntry>, String> {
+    let database = state.database.read().await;
+    let entries = database.get_audit_log(limit.unwrap_or(100)).await?;
+    Ok(entries)
+}
+
+#[tauri::command]
+async fn get_setting(
+    state: State<'_, AppState>,
+    key: String,
+) -> Result<Option<String>, String> {
+    let database = state.database.read().await;
+    let value = database.get_setting(&key).await?;
+    Ok(value.or_else(|| settings::default_value(&key).map(str::to_string)))
+}
+
+#[tauri::command]
+async fn initialize_ai_models(
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let (mode, device) = {
+        let ai_service = state.ai_service.read().await;
+        (ai_service.mode(), ai_service.device().clone())
+    };
+    let missing = [
+        model_downloads::whisper_id(&state.config.whisper_model),
+        model_downloads::embedding_id(&state.config.embedding_model),
+    ]
+    .iter()
+    .any(|id| !model_downloads::is_installed(&state.config.ai_models_path, id));
+    if missing && mode == AIMode::Real {
+        policy::ensure_cloud_ai_enabled(&state.config)?;
+    }
+
+    // Downloads and loading can take minutes; AI commands keep working meanwhile
+    let models = AIService::prepare_models(
+        mode,
+        device,
+        state.config.whisper_model.clone(),
+        state.config.embedding_model.clone(),
+        state.config.ai_models_path.clone(),
+    ).await?;
+    state.ai_service.write().await.install_models(models);
+
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_ai_status(
+    state: State<'_, AppState>,
+) -> Result<serde_json::Value, String> {
+    let ai_service = state.ai_service.read().await;
+    
+    Ok(serde_json::json!({
+        "mode": ai_service.mode(),
+        "whisper_available": ai_service.is_whisper_available(),
+        "embedding_available": ai_service.is_embedding_available(),
+        "whisper_model": ai_service.get_whisper_model(),
+        "embedding_model": ai_service.get_embedding_model(),
+        "llm_available": ai_service.is_llm_available(),
+        "llm_model_path": ai_service.llm_model_path(),
+    }))
+}
+
+#[tauri::command]
+async fn get_ai_device_info(
+    state: State<'_, AppState>,
+) -> Result<AiDeviceInfo, String> {
+    let ai_service = state.ai_service.read().await;
+    Ok(ai_service.device_info().clone())
+}
+
+#[tauri::command]
+async fn set_ai_device(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    preference: AiDevicePreference,
+) -> Result<AiDeviceInfo, String> {
+    settings::set(&app, &state, ai::AI_DEVICE_KEY, preference.as_str()).await?;
+    let info = state.ai_service.read().await.device_info().clone();
+    Ok(info)
+}
+
+/// Points summaries and rewriting at another GGUF model; it's loaded when first used.
+#[tauri::command]
+async fn set_llm_model_path(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    path: PathBuf,
+) -> Result<(), String> {
+    settings::set(&app, &state, llm::LLM_MODEL_PATH_KEY, &path.to_string_lossy()).await?;
+    Ok(())
+}
+
+/// Whisper and embedding models that can be downloaded into `ai_models_path`.
+#[tauri::command]
+async fn list_downloadable_models(
+    state: State<'_, AppState>,
+) -> Result<Vec<DownloadableModel>, String> {
+    Ok(model_downloads::list(&state.config.ai_models_path))
+}
+
+/// Downloads a model from Hugging Face in the background, resuming a partial download and
+/// verifying its SHA-256. Progress arrives as `model-download-progress` events.
+#[tauri::command]
+async fn download_model(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    model_id: String,
+) -> Result<ModelDownloadJob, String> {
+    policy::ensure_cloud_ai_enabled(&state.config)?;
+    let job = model_downloads::spawn_download(app, state.config.ai_models_path.clone(), model_id).await?;
+    Ok(job)
+}
+
+/// Deletes a downloaded model's files and returns the bytes freed.
+#[tauri::command]
+async fn delete_model(
+    state: State<'_, AppState>,
+    model_id: String,
+) -> Result<u64, String> {
+    let freed = model_downloads::delete(&state.config.ai_models_path, &model_id)?;
+    let database = state.database.read().await;
+    state.audit(&database, "delete_model", Some(&model_id)).await?;
+    Ok(freed)
+}
+
+// Notebook Management Commands
+
+#[tauri::command]
+async fn create_notebook(
+    state: State<'_, AppState>,
+    request: CreateNotebookRequest,
+) -> Result<Notebook, String> {
+    let database = state.database.read().await;
+    let notebook = database.create_notebook(request).await?;
+    Ok(notebook)
+}
+
+#[tauri::command]
+async fn get_notebooks(
+    state: State<'_, AppState>,
+) -> Result<Vec<Notebook>, String> {
+    let database = state.database.read().await;
+    let notebooks = database.get_notebooks().await?;
+    Ok(notebooks)
+}
+
+#[tauri::command]
+async fn get_notebook(
+    state: State<'_, AppState>,
+    id: String,
+) -> Result<Option<Notebook>, String> {
+    let database = state.database.read().await;
+    let notebook = database.get_notebook(&id).await?;
+    Ok(notebook)
+}
+
+#[tauri::command]
+async fn update_notebook(
+    state: State<'_, AppState>,
+    request: UpdateNotebookRequest,
+) -> Result<(), String> {
+    let database = state.database.read().await;
+    database.update_notebook(request).await?;
+    if let Err(e) = os_search::refresh(&database).await {
+        tracing::warn!("Failed to update OS search stubs: {}", e);
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn delete_notebook(
+    state: State<'_, AppState>,
+    id: String,
+) -> Result<(), String> {
+    let database = state.database.read().await;
+    state.audit(&database, "delete_notebook", Some(&id)).await?;
+    database.delete_notebook(&id).await?;
+    if let Err(e) = os_search::refresh(&database).await {
+        tracing::warn!("Failed to update OS search stubs: {}", e);
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_notebook_hierarchy(
+    state: State<'_, AppState>,
+    id: String,
+) -> Result<NotebookHierarchy, String> {
+    let database = state.database.read().await;
+    let hierarchy = database.get_notebook_hierarchy(&id).await?;
+    Ok(hierarchy)
+}
+
+#[tauri::command]
+async fn get_notebook_language_settings(
+    state: State<'_, AppState>,
+    notebook_id: String,
+) -> Result<LanguageSettings, String> {
+    let database = state.database.read().await;
+    let settings = database.get_notebook_language_settings(&notebook_id).await?;
+    Ok(settings)
+}
+
+// Section Management Commands
+
+#[tauri::command]
+async fn create_section(
+    state: State<'_, AppState>,
+    request: CreateSectionRequest,
+) -> Result<Section, String> {
+    let database = state.database.read().await;
+    let section = database.create_section(request).await?;
+    Ok(section)
+}
+
+#[tauri::command]
+async fn get_sections(
+    state: State<'_, AppState>,
+    notebook_id: String,
+) -> Result<Vec<Section>, String> {
+    let database = state.database.read().await;
+    let sections = database.get_sections(&notebook_id).await?;
+    Ok(sections)
+}
+
+#[tauri::command]
+async fn get_section(
+    state: State<'_, AppState>,
+    id: String,
+) -> Result<Option<Section>, String> {
+    let database = state.database.read().await;
+    let section = database.get_section(&id).await?;
+    Ok(section)
 }
 
 #[tauri::command]
 async fn update_section(
     state: State<'_, AppState>,
-    request: UpdateSectionRequest,
-) -> Result<(), String> {
+    request: UpdateSectionRequest,
+) -> Result<(), String> {
+    let database = state.database.read().await;
+    database.update_section(request).await?;
+    Ok(())
+}
+
+#[tauri::command]
+async fn delete_section(
+    state: State<'_, AppState>,
+    id: String,
+) -> Result<(), String> {
+    let database = state.database.read().await;
+    state.audit(&database, "delete_section", Some(&id)).await?;
+    database.delete_section(&id).await?;
+    Ok(())
+}
+
+// Page Management Commands
+
+#[tauri::command]
+async fn create_page(
+    state: State<'_, AppState>,
+    mut request: CreatePageRequest,
+) -> Result<Page, String> {
+    let database = state.database.read().await;
+    let ai_service = state.ai_service.read().await;
+    
+    // Quick captures arrive without a title
+    if request.title.trim().is_empty() && database.get_setting(AUTO_TITLE_KEY).await?.as_deref() == Some("true") {
+        if let Some(title) = ai_service.suggest_title(&request.content).await? {
+            request.title = title;
+        }
+    }
+    let mut page = database.create_page(request).await?;
+    if database.get_setting(ZETTEL_IDS_KEY).await?.as_deref() == Some("true") {
+        page.metadata.zettel_id = Some(database.assign_zettel_id(&page.id).await?);
+    }
+    state.page_created(&database, &page).await?;
+    
+    Ok(page)
+}
+
+/// Opens the daily note for `date` (today by default), creating it, and the journal
+/// notebook, from the daily template when needed.
+#[tauri::command]
+async fn get_or_create_daily_note(
+    state: State<'_, AppState>,
+    date: Option<chrono::NaiveDate>,
+) -> Result<Page, String> {
+    let database = state.database.read().await;
+    let date = date.unwrap_or_else(|| chrono::Local::now().date_naive());
+    let (page, created) = daily::get_or_create(&database, date).await?;
+    if created {
+        state.queue_embedding(&database, &page.id, EmbeddingOwner::Page, AiJobPriority::Normal).await;
+        state.dispatch_automation_event(AutomationEvent::page_created(&page));
+        if let Err(e) = os_search::refresh_page(&database, &page.id).await {
+            tracing::warn!("Failed to update OS search stubs: {}", e);
+        }
+    }
+    Ok(page)
+}
+
+#[tauri::command]
+async fn get_pages(
+    state: State<'_, AppState>,
+    notebook_id: String,
+    section_id: Option<String>,
+) -> Result<Vec<Page>, String> {
+    let database = state.database.read().await;
+    let pages = database.get_pages(&notebook_id, section_id.as_deref()).await?;
+    Ok(pages)
+}
+
+/// Pages by last update, newest first; pass the returned `next_cursor` back to get the
+/// following page.
+#[tauri::command]
+async fn list_pages(
+    state: State<'_, AppState>,
+    notebook_id: String,
+    section_id: Option<String>,
+    limit: Option<usize>,
+    cursor: Option<String>,
+) -> Result<SearchPage<Page>, String> {
+    let database = state.database.read().await;
+    let pages = database.list_pages(&notebook_id, section_id.as_deref(), limit, cursor.as_deref()).await?;
+    Ok(pages)
+}
+
+#[tauri::command]
+async fn get_page(
+    state: State<'_, AppState>,
+    id: String,
+) -> Result<Option<Page>, String> {
+    let database = state.database.read().await;
+    let mut page = database.get_page(&id).await?;
+    if let Some(page) = page.as_mut() {
+        page.voice_annotations = database.list_voice_annotations(None, Some(&page.id)).await?;
+    }
+    Ok(page)
+}
+
+#[tauri::command]
+async fn update_page(
+    state: State<'_, AppState>,
+    request: UpdatePageRequest,
+) -> Result<(), String> {
+    let database = state.database.read().await;
+    let old_page = if request.title.is_some() || request.content.is_some() {
+        database.get_page(&request.id).await?
+    } else {
+        None
+    };
+    database.update_page(request.clone()).await?;
+    
+    let rename = old_page.as_ref().map(|page| page.title.as_str()).zip(request.title.as_deref());
+    state.page_updated(&database, &request.id, request.content.is_some(), rename).await?;
+    if let (Some(old_page), Some(content)) = (&old_page, &request.content) {
+        let completed = tasks::completed_tasks(&old_page.content, content);
+        if !completed.is_empty() {
+            let page = database.get_page(&request.id).await?
+                .ok_or_else(|| AppError::NotFound(format!("Page with id {} not found", request.id)))?;
+            for task in completed {
+                state.dispatch_automation_event(AutomationEvent::task_completed(&page, task));
+            }
+        }
+    }
+    
+    Ok(())
+}
+
+#[tauri::command]
+async fn delete_page(
+    state: State<'_, AppState>,
+    id: String,
+) -> Result<(), String> {
+    let database = state.database.read().await;
+    state.audit(&database, "delete_page", Some(&id)).await?;
+    database.trash_page(&id).await?;
+    if let Err(e) = jump_list::refresh(&database).await {
+        tracing::warn!("Failed to update jump list: {}", e);
+    }
+    if let Err(e) = os_search::refresh(&database).await {
+        tracing::warn!("Failed to update OS search stubs: {}", e);
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn move_page(
+    state: State<'_, AppState>,
+    request: MovePageRequest,
+) -> Result<(), String> {
+    let database = state.database.read().await;
+    database.move_page(request).await?;
+    if let Err(e) = os_search::refresh(&database).await {
+        tracing::warn!("Failed to update OS search stubs: {}", e);
+    }
+    Ok(())
+}
+
+/// Adds or removes tags, moves or deletes many pages in one transaction, so a large
+/// selection is one round-trip. Ids that don't exist are returned in `missing`.
+#[tauri::command]
+async fn bulk_update_pages(
+    state: State<'_, AppState>,
+    request: BulkUpdatePagesRequest,
+) -> Result<BulkUpdateResult, String> {
+    let database = state.database.read().await;
+    if matches!(request.operation, BulkPageOperation::Delete) {
+        for id in &request.page_ids {
+            state.audit(&database, "delete_page", Some(id)).await?;
+        }
+    }
+    let result = database.bulk_update_pages(&request).await?;
+
+    if matches!(request.operation, BulkPageOperation::Move { .. } | BulkPageOperation::Delete) && !result.updated.is_empty() {
+        if let Err(e) = jump_list::refresh(&database).await {
+            tracing::warn!("Failed to update jump list: {}", e);
+        }
+        if let Err(e) = os_search::refresh(&database).await {
+            tracing::warn!("Failed to update OS search stubs: {}", e);
+        }
+    }
+    Ok(result)
+}
+
+#[tauri::command]
+async fn get_page_with_subpages(
+    state: State<'_, AppState>,
+    id: String,
+) -> Result<Option<PageWithSubpages>, String> {
+    let database = state.database.read().await;
+    let page_with_subpages = database.get_page_with_subpages(&id).await?;
+    Ok(page_with_subpages)
+}
+
+#[tauri::command]
+async fn get_language_settings(
+    state: State<'_, AppState>,
+    page_id: String,
+) -> Result<LanguageSettings, String> {
+    let database = state.database.read().await;
+    let settings = database.get_language_settings(&page_id).await?;
+    Ok(settings)
+}
+
+// Media Management Commands
+
+#[tauri::command]
+async fn upload_media(
+    state: State<'_, AppState>,
+    request: UploadMediaRequest,
+) -> Result<MediaAttachment, String> {
+    let database = state.database.read().await;
+    let media = database.upload_media(request).await?;
+    Ok(media)
+}
+
+/// Attaches a file from disk without its bytes crossing IPC. `path` must have been dropped
+/// on the window or picked with `choose_attachment_files`. The returned attachment has no
+/// `file_data`; it's served over `media://`.
+#[tauri::command]
+async fn attach_file_from_path(
+    state: State<'_, AppState>,
+    grants: State<'_, FileGrants>,
+    page_id: String,
+    path: PathBuf,
+) -> Result<MediaAttachment, String> {
+    grants.take(&path)?;
+    let database = state.database.read().await;
+    let mut media = importer::attach_file(&database, &page_id, &path, state.config.max_file_size).await?;
+    media.file_data = Vec::new();
+    Ok(media)
+}
+
+/// Opens a file dialog and returns the files picked, which `attach_file_from_path` may
+/// then read. Empty when the dialog is cancelled.
+#[tauri::command]
+async fn choose_attachment_files(
+    app: tauri::AppHandle,
+    grants: State<'_, FileGrants>,
+) -> Result<Vec<PathBuf>, String> {
+    let (sender, receiver) = tokio::sync::oneshot::channel();
+    app.dialog().file().pick_files(move |picked| {
+        let _ = sender.send(picked);
+    });
+    let paths: Vec<PathBuf> = receiver
+        .await
+        .map_err(|_| "The file dialog closed unexpectedly".to_string())?
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|picked| picked.into_path().ok())
+        .collect();
+    grants.grant(paths.iter().cloned());
+    Ok(paths)
+}
+
+/// Image attachments that still record where they were taken.
+#[tauri::command]
+async fn audit_image_locations(state: State<'_, AppState>) -> Result<ImageLocationAudit, String> {
+    let database = state.database.read().await;
+    let audit = exif::audit_image_locations(&database).await?;
+    Ok(audit)
+}
+
+/// The upload as it was before it was compressed, when `keep_original` kept it.
+#[tauri::command]
+async fn get_original_media(state: State<'_, AppState>, id: String) -> Result<Option<Vec<u8>>, String> {
+    let database = state.database.read().await;
+    let data = database.get_media_original(&id).await?;
+    Ok(data)
+}
+
+#[tauri::command]
+async fn get_security_config(state: State<'_, AppState>) -> Result<SecurityConfig, String> {
+    let database = state.database.read().await;
+    let config = database.get_security_config().await?;
+    Ok(config)
+}
+
+/// Applies to images uploaded from now on; `exif::audit_image_locations` finds older ones.
+#[tauri::command]
+async fn set_security_config(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    config: SecurityConfig,
+) -> Result<(), String> {
+    policy::ensure_setting_unlocked(&state.config, exif::SECURITY_CONFIG_KEY)?;
+    state.audit(&*state.database.read().await, "set_security_config", None).await?;
+    settings::set(&app, &state, exif::SECURITY_CONFIG_KEY, &serde_json::to_string(&config).map_err(AppError::from)?).await?;
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_media_attachments(
+    state: State<'_, AppState>,
+    page_id: Option<String>,
+    note_id: Option<String>,
+) -> Result<Vec<MediaAttachment>, String> {
+    let database = state.database.read().await;
+    let attachments = database.get_media_attachments(page_id.as_deref(), note_id.as_deref()).await?;
+    Ok(attachments)
+}
+
+#[tauri::command]
+async fn delete_media(
+    state: State<'_, AppState>,
+    id: String,
+) -> Result<(), String> {
+    let database = state.database.read().await;
+    database.delete_media(&id).await?;
+    Ok(())
+}
+
+#[tauri::command]
+async fn process_paste(
+    state: State<'_, AppState>,
+    payload: PastePayload,
+) -> Result<PasteResult, String> {
+    let database = state.database.read().await;
+    let result = paste::process_paste(&database, payload).await?;
+    Ok(result)
+}
+
+#[tauri::command]
+async fn import_files(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    grants: State<'_, FileGrants>,
+    request: ImportFilesRequest,
+) -> Result<ImportBatch, String> {
+    if request.paths.is_empty() {
+        return Err("No files to import".to_string());
+    }
+    for path in &request.paths {
+        grants.take(path)?;
+    }
+    
+    let batch = importer::spawn_import(
+        app,
+        state.database.clone(),
+        state.ai_service.clone(),
+        request.paths,
+        request.target_page_id,
+        state.config.max_file_size,
+    );
+    Ok(batch)
+}
+
+#[tauri::command]
+async fn capture_screenshot(
+    state: State<'_, AppState>,
+    request: CaptureScreenshotRequest,
+) -> Result<MediaAttachment, String> {
+    let database = state.database.read().await;
+    let media = screenshot::capture_to_page(&database, request).await?;
+    Ok(media)
+}
+
+#[tauri::command]
+async fn set_media_annotations(
+    state: State<'_, AppState>,
+    media_id: String,
+    annotations: Vec<ImageAnnotation>,
+) -> Result<(), String> {
+    let database = state.database.read().await;
+    screenshot::set_annotations(&database, &media_id, annotations).await?;
+    Ok(())
+}
+
+#[tauri::command]
+async fn process_document_photo(
+    state: State<'_, AppState>,
+    media_id: String,
+) -> Result<MediaAttachment, String> {
+    let database = state.database.read().await;
+    let ai_service = state.ai_service.read().await;
+    let scanned = scanner::process_document_photo(&database, &ai_service, &media_id).await?;
+    Ok(scanned)
+}
+
+#[tauri::command]
+async fn recognize_ink(
+    state: State<'_, AppState>,
+    media_id: String,
+) -> Result<Option<String>, String> {
+    let database = state.database.read().await;
+    let ai_service = state.ai_service.read().await;
+    let text = ink::recognize_ink(&database, &ai_service, &media_id).await?;
+    Ok(text)
+}
+
+// Page Link Management Commands
+
+#[tauri::command]
+async fn create_page_link(
+    state: State<'_, AppState>,
+    request: CreatePageLinkRequest,
+) -> Result<PageLink, String> {
+    let database = state.database.read().await;
+    let link = database.create_page_link(request).await?;
+    Ok(link)
+}
+
+#[tauri::command]
+async fn get_page_links(
+    state: State<'_, AppState>,
+    page_id: String,
+) -> Result<Vec<PageLink>, String> {
+    let database = state.database.read().await;
+    let links = database.get_page_links(&page_id).await?;
+    Ok(links)
+}
+
+#[tauri::command]
+async fn delete_page_link(
+    state: State<'_, AppState>,
+    id: String,
+) -> Result<(), String> {
+    let database = state.database.read().await;
+    database.delete_page_link(&id).await?;
+    Ok(())
+}
+
+/// A page's content with `![[Page]]`, `![[Page#Heading]]` and `![[Page#^block]]` embeds
+/// expanded recursively, for rendering. Embeds that would loop are left as links.
+#[tauri::command]
+async fn resolve_transclusions(
+    state: State<'_, AppState>,
+    page_id: String,
+) -> Result<ResolvedContent, String> {
+    let database = state.database.read().await;
+    let page = database.get_page(&page_id).await?
+        .ok_or_else(|| AppError::NotFound(format!("Page with id {} not found", page_id)))?;
+    let resolved = transclusion::resolve(&database, &page).await?;
+    Ok(resolved)
+}
+
+/// Orphans, hubs and connected groups of pages in a notebook, or the whole vault, for the
+/// vault health dashboard.
+#[tauri::command]
+async fn get_graph_metrics(
+    state: State<'_, AppState>,
+    notebook_id: Option<String>,
+) -> Result<GraphMetrics, String> {
+    let database = state.database.read().await;
+    let (pages, links) = database.get_link_graph(notebook_id.as_deref()).await?;
+    Ok(graph::metrics(notebook_id, &pages, &links))
+}
+
+/// Pages linking to this one, with the text around each link, for the backlinks panel.
+#[tauri::command]
+async fn get_backlinks(
+    state: State<'_, AppState>,
+    page_id: String,
+) -> Result<Vec<Backlink>, String> {
+    let database = state.database.read().await;
+    let backlinks = database.get_backlinks(&page_id).await?;
+    Ok(backlinks)
+}
+
+#[tauri::command]
+async fn get_page_relationships(
+    state: State<'_, AppState>,
+    page_id: String,
+    link_types: Option<Vec<PageLinkType>>,
+) -> Result<PageRelationships, String> {
+    let database = state.database.read().await;
+    let relationships = database.get_page_relationships(&page_id, link_types.as_deref()).await?;
+    Ok(relationships)
+}
+
+/// Keeps a suggested link, turning it into a manual one.
+#[tauri::command]
+async fn promote_page_link(
+    state: State<'_, AppState>,
+    id: String,
+) -> Result<PageLink, String> {
+    let database = state.database.read().await;
+    let link = database.promote_page_link(&id).await?;
+    Ok(link)
+}
+
+/// Drops a suggested link; its target isn't suggested for that page again.
+#[tauri::command]
+async fn dismiss_page_link(
+    state: State<'_, AppState>,
+    id: String,
+) -> Result<(), String> {
+    let database = state.database.read().await;
+    database.dismiss_page_link(&id).await?;
+    Ok(())
+}
+
+/// Related pages for the sidebar, stored as `Auto` links and refreshed after edits.
+#[tauri::command]
+async fn get_suggested_links(
+    state: State<'_, AppState>,
+    page_id: String,
+) -> Result<Vec<PageLink>, String> {
+    let database = state.database.read().await;
+    let ai_service = state.ai_service.read().await;
+    let links = ai_service.suggested_links(&database, &page_id).await?;
+    Ok(links)
+}
+
+#[tauri::command]
+async fn suggest_title(
+    state: State<'_, AppState>,
+    page_id: String,
+) -> Result<Option<String>, String> {
+    let database = state.database.read().await;
+    let page = database.get_page(&page_id).await?
+        .ok_or_else(|| AppError::NotFound(format!("Page with id {} not found", page_id)))?;
+    let ai_service = state.ai_service.read().await;
+    let title = ai_service.suggest_title(&page.content).await?;
+    Ok(title)
+}
+
+#[tauri::command]
+async fn set_auto_title_enabled(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    enabled: bool,
+) -> Result<(), String> {
+    settings::set(&app, &state, AUTO_TITLE_KEY, &enabled.to_string()).await?;
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_auto_title_enabled(
+    state: State<'_, AppState>,
+) -> Result<bool, String> {
+    let database = state.database.read().await;
+    Ok(database.get_setting(AUTO_TITLE_KEY).await?.as_deref() == Some("true"))
+}
+
+// Notebook Search and Stats Commands
+
+#[tauri::command]
+async fn search_notebook(
+    state: State<'_, AppState>,
+    request: NotebookSearchRequest,
+) -> Result<SearchPage<PageSearchMatch>, String> {
+    let database = state.database.read().await;
+    let pages = database.search_notebook(request).await?;
+    Ok(pages)
+}
+
+#[tauri::command]
+async fn get_notebook_stats(
+    state: State<'_, AppState>,
+    notebook_id: String,
+) -> Result<NotebookStats, String> {
+    let database = state.database.read().await;
+    let stats = database.get_notebook_stats(&notebook_id).await?;
+    Ok(stats)
+}
+
+/// Average page sentiment per day, week or month, for mood charts.
+#[tauri::command]
+async fn get_sentiment_trends(
+    state: State<'_, AppState>,
+    notebook_id: String,
+    granularity: Option<SentimentGranularity>,
+) -> Result<Vec<SentimentPoint>, String> {
+    let database = state.database.read().await;
+    database.get_notebook(&notebook_id).await?
+        .ok_or_else(|| AppError::NotFound(format!("Notebook with id {} not found", notebook_id)))?;
+    let scores = database.get_page_sentiments(&notebook_id).await?;
+    Ok(sentiment::trends(&scores, granularity.unwrap_or_default()))
+}
+
+// Reordering Commands
+
+#[tauri::command]
+async fn reorder_notebooks(
+    state: State<'_, AppState>,
+    request: ReorderItemsRequest,
+) -> Result<(), String> {
+    let database = state.database.read().await;
+    database.reorder_notebooks(request).await?;
+    Ok(())
+}
+
+#[tauri::command]
+async fn reorder_sections(
+    state: State<'_, AppState>,
+    request: ReorderItemsRequest,
+) -> Result<(), String> {
+    let database = state.database.read().await;
+    database.reorder_sections(request).await?;
+    Ok(())
+}
+
+#[tauri::command]
+async fn reorder_pages(
+    state: State<'_, AppState>,
+    request: ReorderItemsRequest,
+) -> Result<(), String> {
+    let database = state.database.read().await;
+    database.reorder_pages(request).await?;
+    Ok(())
+}
+
+// Automation Commands
+
+#[tauri::command]
+async fn create_automation(
+    state: State<'_, AppState>,
+    request: CreateAutomationRequest,
+) -> Result<Automation, String> {
+    state.automations.check_action(&request.action)?;
+    let database = state.database.read().await;
+    let automation = database.create_automation(request).await?;
+    Ok(automation)
+}
+
+#[tauri::command]
+async fn get_automations(
+    state: State<'_, AppState>,
+) -> Result<Vec<Automation>, String> {
+    let database = state.database.read().await;
+    let automations = database.get_automations().await?;
+    Ok(automations)
+}
+
+#[tauri::command]
+async fn update_automation(
+    state: State<'_, AppState>,
+    request: UpdateAutomationRequest,
+) -> Result<(), String> {
+    if let Some(action) = &request.action {
+        state.automations.check_action(action)?;
+    }
+    let database = state.database.read().await;
+    database.update_automation(request).await?;
+    Ok(())
+}
+
+#[tauri::command]
+async fn delete_automation(
+    state: State<'_, AppState>,
+    id: String,
+) -> Result<(), String> {
+    let database = state.database.read().await;
+    database.delete_automation(&id).await?;
+    Ok(())
+}
+
+#[tauri::command]
+async fn run_automation(
+    state: State<'_, AppState>,
+    id: String,
+    event: AutomationEvent,
+) -> Result<AutomationRun, String> {
+    let database = state.database.read().await;
+    let automation = database.get_automation(&id).await?
+        .ok_or_else(|| AppError::NotFound(format!("Automation with id {} not found", id)))?;
+    let run = state.automations.execute(&database, &automation, &event).await?;
+    Ok(run)
+}
+
+#[tauri::command]
+async fn get_automation_runs(
+    state: State<'_, AppState>,
+    automation_id: Option<String>,
+    limit: Option<usize>,
+) -> Result<Vec<AutomationRun>, String> {
+    let database = state.database.read().await;
+    let runs = database.get_automation_runs(automation_id.as_deref(), limit.unwrap_or(100)).await?;
+    Ok(runs)
+}
+
+// MQTT Integration Commands
+
+#[tauri::command]
+async fn get_mqtt_config(
+    state: State<'_, AppState>,
+) -> Result<MqttConfig, String> {
+    let database = state.database.read().await;
+    let config = state.mqtt.get_config(&database).await?;
+    Ok(config)
+}
+
+#[tauri::command]
+async fn set_mqtt_config(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    config: MqttConfig,
+) -> Result<(), String> {
+    policy::ensure_setting_unlocked(&state.config, mqtt::MQTT_CONFIG_KEY)?;
+    state.audit(&*state.database.read().await, "set_mqtt_config", None).await?;
+    settings::set(&app, &state, mqtt::MQTT_CONFIG_KEY, &serde_json::to_string(&config).map_err(AppError::from)?).await?;
+    Ok(())
+}
+
+#[tauri::command]
+async fn test_mqtt_connection(
+    state: State<'_, AppState>,
+    config: MqttConfig,
+) -> Result<(), String> {
+    state.mqtt.test_connection(&config).await?;
+    Ok(())
+}
+
+// Scripting Commands
+
+#[tauri::command]
+async fn list_scripts(
+    state: State<'_, AppState>,
+) -> Result<Vec<ScriptInfo>, String> {
+    let scripts = state.scripts.list_scripts()?;
+    Ok(scripts)
+}
+
+#[tauri::command]
+async fn run_script(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    name: String,
+    args: Option<serde_json::Value>,
+) -> Result<ScriptResult, String> {
+    // The runner takes its own read locks per call, so don't hold one here
+    let result = state.scripts.run(
+        app.clone(),
+        &name,
+        args.unwrap_or(serde_json::Value::Null),
+    ).await?;
+    Ok(result)
+}
+
+// Bundle Commands
+
+#[tauri::command]
+async fn install_bundle(
+    state: State<'_, AppState>,
+    request: InstallBundleRequest,
+) -> Result<InstalledBundle, String> {
+    let database = state.database.read().await;
+    let bundle = bundles::install_from_path(&database, &request.path, request.allow_unsigned).await?;
+    Ok(bundle)
+}
+
+#[tauri::command]
+async fn uninstall_bundle(
+    state: State<'_, AppState>,
+    id: String,
+) -> Result<(), String> {
+    let database = state.database.read().await;
+    database.uninstall_bundle(&id).await?;
+    Ok(())
+}
+
+#[tauri::command]
+async fn list_bundles(
+    state: State<'_, AppState>,
+) -> Result<Vec<InstalledBundle>, String> {
+    let database = state.database.read().await;
+    let bundles = database.get_bundles().await?;
+    Ok(bundles)
+}
+
+#[tauri::command]
+async fn get_templates(
+    state: State<'_, AppState>,
+) -> Result<Vec<Template>, String> {
+    let database = state.database.read().await;
+    let templates = database.get_templates().await?;
+    Ok(templates)
+}
+
+#[tauri::command]
+async fn get_snippets(
+    state: State<'_, AppState>,
+) -> Result<Vec<Snippet>, String> {
+    let database = state.database.read().await;
+    let snippets = database.get_snippets().await?;
+    Ok(snippets)
+}
+
+#[tauri::command]
+async fn get_prompts(
+    state: State<'_, AppState>,
+) -> Result<Vec<PromptTemplate>, String> {
+    let database = state.database.read().await;
+    let prompts = database.get_prompts().await?;
+    Ok(prompts)
+}
+
+// Update Check Commands
+
+#[tauri::command]
+async fn check_for_updates(
+    state: State<'_, AppState>,
+) -> Result<UpdateInfo, String> {
+    policy::ensure_update_checks_enabled(&state.config)?;
+    let database = state.database.read().await;
+    let info = state.updates.check_for_updates(&database).await?;
+    Ok(info)
+}
+
+#[tauri::command]
+async fn get_update_info(
+    state: State<'_, AppState>,
+) -> Result<UpdateInfo, String> {
+    let database = state.database.read().await;
+    let info = state.updates.get_update_info(&database).await?;
+    Ok(info)
+}
+
+#[tauri::command]
+async fn get_update_config(
+    state: State<'_, AppState>,
+) -> Result<UpdateCheckConfig, String> {
+    let database = state.database.read().await;
+    let config = state.updates.get_config(&database).await?;
+    Ok(config)
+}
+
+#[tauri::command]
+async fn set_update_config(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    config: UpdateCheckConfig,
+) -> Result<(), String> {
+    settings::set(&app, &state, updates::UPDATE_CONFIG_KEY, &serde_json::to_string(&config).map_err(AppError::from)?).await?;
+    Ok(())
+}
+
+// Crash Report Commands
+
+#[tauri::command]
+async fn list_crash_reports(
+    state: State<'_, AppState>,
+) -> Result<Vec<CrashReport>, String> {
+    let reports = crash::list_reports(&state.config.crash_reports_path)?;
+    Ok(reports)
+}
+
+#[tauri::command]
+async fn submit_crash_report(
+    state: State<'_, AppState>,
+    id: String,
+) -> Result<CrashReport, String> {
+    let database = state.database.read().await;
+    state.audit(&database, "submit_crash_report", Some(&id)).await?;
+    let report = crash::submit_report(&database, &state.config.crash_reports_path, &id).await?;
+    Ok(report)
+}
+
+// Diagnostics Commands
+
+/// Status of each subsystem for the status indicator; the overall status is the worst one.
+#[tauri::command]
+async fn get_system_health(
+    state: State<'_, AppState>,
+) -> Result<SystemHealth, String> {
+    let database = state.database.read().await;
+    let ai_service = state.ai_service.read().await;
+    let health = health::check(&database, &ai_service, &state.sync, &state.config).await?;
+    Ok(health)
+}
+
+#[tauri::command]
+async fn get_app_stats(
+    state: State<'_, AppState>,
+) -> Result<AppStats, String> {
+    let database = state.database.read().await;
+    let stats = database.get_app_stats().await?;
+    Ok(stats)
+}
+
+/// Attachment and recording bytes by notebook, with quotas, and by kind of media.
+#[tauri::command]
+async fn get_storage_usage(
+    state: State<'_, AppState>,
+) -> Result<StorageUsage, String> {
+    let database = state.database.read().await;
+    let usage = database.get_storage_usage().await?;
+    Ok(usage)
+}
+
+/// VACUUMs and ANALYZEs the database, reporting the space reclaimed and per-table sizes.
+/// Holds the database exclusively while it runs, as VACUUM needs every other connection idle.
+#[tauri::command]
+async fn run_maintenance(
+    state: State<'_, AppState>,
+) -> Result<MaintenanceReport, String> {
+    let database = state.database.write().await;
+    let report = maintenance::run(&database).await?;
+    state.audit(&database, "run_maintenance", None).await?;
+    Ok(report)
+}
+
+/// Which tables and fields are encrypted and which are plaintext, flagging values that
+/// don't decrypt. Encryption covers content, audio, attachments and settings only.
+#[tauri::command]
+async fn audit_encryption_coverage(
+    state: State<'_, AppState>,
+) -> Result<EncryptionCoverageReport, String> {
+    let database = state.database.read().await;
+    let report = encryption_audit::audit_encryption_coverage(&database, &state.config).await?;
+    Ok(report)
+}
+
+/// Converts the database file to SQLCipher so titles, tags and links are encrypted at
+/// rest too, then encrypts the snapshots taken before. Needs encryption enabled, as the
+/// file key is derived from the content key; other commands wait until the file has been
+/// rewritten and reopened.
+#[tauri::command]
+async fn encrypt_database_file(
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let mut database = state.database.write().await;
+    if database.is_file_encrypted() {
+        return Err("The database file is already encrypted".to_string());
+    }
+    if !database.is_encrypted() {
+        return Err("Turn on encryption before encrypting the database file".to_string());
+    }
+    sqlcipher::ensure_available()?;
+    let key = EncryptionManager::from_key_file(&state.config.encryption_key_path)?.database_key();
+
+    database.close().await;
+    let path = state.config.database_path.clone();
+    let result = tauri::async_runtime::spawn_blocking(move || sqlcipher::encrypt_file(&path, &key))
+        .await
+        .map_err(|e| AppError::Unknown(format!("Encryption task failed: {}", e)))
+        .and_then(|result| result);
+    // Reopen whichever file is in place now; a failed conversion leaves the original
+    *database = AppState::open_database(&state.config).await?;
+    result?;
+
+    state.audit(&database, "encrypt_database_file", None).await?;
+    SnapshotStore::new(&state.config.backup_path, &database).seal_existing().await?;
+    Ok(())
+}
+
+#[tauri::command]
+async fn generate_diagnostics_bundle(
+    state: State<'_, AppState>,
+) -> Result<PathBuf, String> {
+    let database = state.database.read().await;
+    let ai_service = state.ai_service.read().await;
+    let path = diagnostics::generate_bundle(&database, &ai_service, &state.config).await?;
+    Ok(path)
+}
+
+#[tauri::command]
+async fn set_log_level(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    level: String,
+) -> Result<(), String> {
+    settings::set(&app, &state, logging::LOG_LEVEL_KEY, &level).await?;
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_log_level() -> Result<String, String> {
+    let level = logging::get_level()?;
+    Ok(level)
+}
+
+#[tauri::command]
+async fn tail_logs(
+    lines: Option<usize>,
+) -> Result<Vec<String>, String> {
+    let lines = logging::tail(lines.unwrap_or(200))?;
+    Ok(lines)
+}
+
+/// Read-only SELECT over the `debug_*` views, for debugging and reporting. Needs developer
+/// mode; encrypted columns are redacted.
+#[tauri::command]
+async fn execute_readonly_sql(
+    state: State<'_, AppState>,
+    query: String,
+) -> Result<SqlQueryResult, String> {
+    let database = state.database.read().await;
+    let result = sql_console::execute_readonly(&database, &query).await?;
+    state.audit(&database, "execute_readonly_sql", None).await?;
+    Ok(result)
+}
+
+// Embedding Index Commands
+
+/// Turns the sqlite-vec embedding index on or off, migrating existing embeddings when enabled.
+/// Returns the number of embeddings copied into the index.
+#[tauri::command]
+async fn set_vector_index_enabled(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    enabled: bool,
+) -> Result<usize, String> {
+    match settings::set(&app, &state, VECTOR_INDEX_KEY, &enabled.to_string()).await? {
+        settings::Applied::VectorIndex { migrated } => Ok(migrated),
+        _ => Ok(0),
+    }
+}
+
+#[tauri::command]
+async fn get_vector_index_enabled(
+    state: State<'_, AppState>,
+) -> Result<bool, String> {
+    let database = state.database.read().await;
+    Ok(database.has_vector_index())
+}
+
+/// Rebuilds every embedding in the background, e.g. after switching embedding models.
+#[tauri::command]
+async fn reindex_embeddings(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<ReindexJob, String> {
+    let job = reindex::spawn_reindex(app, state.database.clone(), state.ai_service.clone(), false).await?;
+    Ok(job)
+}
+
+/// Compares stored embeddings against the loaded model, or the configured one if none is
+/// loaded yet.
+#[tauri::command]
+async fn get_embedding_model_status(
+    state: State<'_, AppState>,
+) -> Result<EmbeddingModelStatus, String> {
+    let model = state.ai_service.read().await.get_embedding_model().cloned()
+        .unwrap_or_else(|| state.config.embedding_model.clone());
+    let database = state.database.read().await;
+    let status = reindex::embedding_model_status(&database, &model).await?;
+    Ok(status)
+}
+
+/// Brings embeddings from a previous model in line with the loaded one. Returns the
+/// re-index job, or `None` when stale embeddings were dropped instead.
+#[tauri::command]
+async fn migrate_embeddings(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    strategy: EmbeddingMigration,
+) -> Result<Option<ReindexJob>, String> {
+    match strategy {
+        EmbeddingMigration::Reembed => {
+            let job = reindex::spawn_reindex(app, state.database.clone(), state.ai_service.clone(), true).await?;
+            Ok(Some(job))
+        }
+        EmbeddingMigration::Drop => {
+            let model = state.ai_service.read().await.get_embedding_model().cloned()
+                .unwrap_or_else(|| state.config.embedding_model.clone());
+            let database = state.database.read().await;
+            let dropped = database.delete_stale_embeddings(model.model_name()).await?;
+            tracing::info!("Dropped {} embeddings from other models", dropped);
+            Ok(None)
+        }
+    }
+}
+
+// Web Viewer Commands
+
+#[tauri::command]
+async fn start_web_viewer(
+    state: State<'_, AppState>,
+    request: StartWebViewerRequest,
+) -> Result<WebViewerStatus, String> {
+    let status = state.web_viewer.start(state.database.clone(), request).await?;
+    let database = state.database.read().await;
+    state.audit(&database, "start_web_viewer", status.url.as_deref()).await?;
+    Ok(status)
+}
+
+#[tauri::command]
+async fn stop_web_viewer(
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    state.web_viewer.stop().await;
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_web_viewer_status(
+    state: State<'_, AppState>,
+) -> Result<WebViewerStatus, String> {
+    Ok(state.web_viewer.status().await)
+}
+
+// Export Commands
+
+#[tauri::command]
+async fn export_page(
+    state: State<'_, AppState>,
+    page_id: String,
+    format: ExportFormat,
+    destination: PathBuf,
+    locale: Option<String>,
+) -> Result<PathBuf, String> {
+    let database = state.database.read().await;
+    let ai_service = state.ai_service.read().await;
+    let path = export::export_page(&database, &ai_service, &page_id, &format, &destination, locale.as_deref()).await?;
+    Ok(path)
+}
+
+/// Exports a notebook into a folder. Incremental runs, the default, only write pages changed
+/// since the previous export there.
+#[tauri::command]
+async fn export_notebook(
+    state: State<'_, AppState>,
+    notebook_id: String,
+    format: ExportFormat,
+    destination: PathBuf,
+    locale: Option<String>,
+    incremental: Option<bool>,
+) -> Result<NotebookExport, String> {
+    let database = state.database.read().await;
+    let ai_service = state.ai_service.read().await;
+    let export = export::export_notebook(
+        &database,
+        &ai_service,
+        &notebook_id,
+        &format,
+        &destination,
+        locale.as_deref(),
+        incremental.unwrap_or(true),
+    ).await?;
+    Ok(export)
+}
+
+#[tauri::command]
+async fn get_page_export_history(
+    state: State<'_, AppState>,
+    page_id: String,
+) -> Result<Vec<ExportRecord>, String> {
+    let database = state.database.read().await;
+    let history = database.get_page_exports(&page_id).await?;
+    Ok(history)
+}
+
+/// Refreshes an external mirror after edits by exporting its pages again.
+#[tauri::command]
+async fn re_export_all(
+    state: State<'_, AppState>,
+    previous_destination: PathBuf,
+) -> Result<ReExportSummary, String> {
+    let database = state.database.read().await;
+    let ai_service = state.ai_service.read().await;
+    let summary = export::re_export_all(&database, &ai_service, &previous_destination).await?;
+    Ok(summary)
+}
+
+/// Aggregate statistics for analysis in external tools, as CSV files or one JSON file.
+#[tauri::command]
+async fn export_stats_csv(
+    state: State<'_, AppState>,
+    range: StatsRange,
+    destination: PathBuf,
+    format: Option<StatsFormat>,
+) -> Result<Vec<PathBuf>, String> {
+    let database = state.database.read().await;
+    let paths = export::export_stats(&database, &range, format.unwrap_or_default(), &destination).await?;
+    Ok(paths)
+}
+
+/// Amplitude peaks for drawing the annotation's waveform.
+#[tauri::command]
+async fn get_waveform(
+    state: State<'_, AppState>,
+    annotation_id: String,
+) -> Result<Waveform, String> {
+    let database = state.database.read().await;
+    let waveform = audio::waveform(&database, &annotation_id).await?;
+    Ok(waveform)
+}
+
+/// Annotations of a note or a page, or every one when neither is given, without audio.
+#[tauri::command]
+async fn list_voice_annotations(
+    state: State<'_, AppState>,
+    note_id: Option<String>,
+    page_id: Option<String>,
+) -> Result<Vec<VoiceAnnotationSummary>, String> {
+    let database = state.database.read().await;
+    let annotations = database.list_voice_annotations(note_id.as_deref(), page_id.as_deref()).await?;
+    Ok(annotations)
+}
+
+#[tauri::command]
+async fn update_voice_annotation(
+    state: State<'_, AppState>,
+    request: UpdateVoiceAnnotationRequest,
+) -> Result<VoiceAnnotationSummary, String> {
+    let database = state.database.read().await;
+    let annotation = database.update_voice_annotation(request).await?;
+    Ok(annotation)
+}
+
+#[tauri::command]
+async fn delete_voice_annotation(
+    state: State<'_, AppState>,
+    id: String,
+) -> Result<(), String> {
+    let database = state.database.read().await;
+    state.audit(&database, "delete_voice_annotation", Some(&id)).await?;
+    database.delete_voice_annotation(&id).await?;
+    Ok(())
+}
+
+/// Re-encodes voice annotations recorded before they were stored as Opus and reports the
+/// space saved.
+#[tauri::command]
+async fn recompress_voice_annotations(
+    state: State<'_, AppState>,
+) -> Result<VoiceRecompressReport, String> {
+    let database = state.database.read().await;
+    state.audit(&database, "recompress_voice_annotations", None).await?;
+    let report = database.recompress_voice_annotations().await?;
+    state.media_cache.clear();
+    tracing::info!("Recompressed {} voice annotations, saving {} bytes", report.recompressed, report.bytes_saved);
+    Ok(report)
+}
+
+/// One annotation's audio, as WAV; Opus recordings are decoded first. Players can stream
+/// it from `media://localhost/voice/<annotation id>` instead.
+#[tauri::command]
+async fn get_voice_annotation_audio(
+    state: State<'_, AppState>,
+    annotation_id: String,
+) -> Result<VoiceAudio, String> {
+    let database = state.database.read().await;
+    let audio = database.get_voice_annotation_audio(&annotation_id).await?
+        .ok_or_else(|| AppError::NotFound(format!("Voice annotation with id {} not found", annotation_id)))?;
+    Ok(audio)
+}
+
+/// Timed segments of a voice annotation, translated when `target_lang` is given, for
+/// previewing subtitles.
+#[tauri::command]
+async fn translate_voice_annotation(
+    state: State<'_, AppState>,
+    annotation_id: String,
+    target_lang: Option<String>,
+) -> Result<Vec<SubtitleSegment>, String> {
+    let database = state.database.read().await;
+    let ai_service = state.ai_service.read().await;
+    let annotation = database.get_voice_annotation_pcm(&annotation_id).await?
+        .ok_or_else(|| AppError::NotFound(format!("Voice annotation with id {} not found", annotation_id)))?;
+    let segments = subtitles::segments(&ai_service, &annotation, target_lang.as_deref()).await?;
+    Ok(segments)
+}
+
+/// Writes a voice annotation's audio with an SRT or WebVTT subtitle file next to it.
+#[tauri::command]
+async fn export_voice_subtitles(
+    state: State<'_, AppState>,
+    annotation_id: String,
+    destination: PathBuf,
+    format: Option<SubtitleFormat>,
+    target_lang: Option<String>,
+) -> Result<SubtitleExport, String> {
+    let database = state.database.read().await;
+    let ai_service = state.ai_service.read().await;
+    let export = subtitles::export_subtitles(
+        &database,
+        &ai_service,
+        &annotation_id,
+        format.unwrap_or_default(),
+        target_lang.as_deref(),
+        &destination,
+    ).await?;
+    Ok(export)
+}
+
+/// HTML the webview prints to produce PDF exports.
+#[tauri::command]
+async fn render_page_html(
+    state: State<'_, AppState>,
+    page_id: String,
+    format: ExportFormat,
+    locale: Option<String>,
+) -> Result<String, String> {
+    let database = state.database.read().await;
+    let mut page = database.get_page(&page_id).await?
+        .ok_or_else(|| AppError::NotFound(format!("Page with id {} not found", page_id)))?;
+    page.content = transclusion::resolve(&database, &page).await?.content;
+    let formatter = locale::formatter(&database, locale.as_deref()).await?;
+    let transcriptions = if format.include_voice_annotations {
+        database.get_page_transcriptions(&page_id).await?
+    } else {
+        Vec::new()
+    };
+    Ok(export::render_html(&page, &format, &transcriptions, &formatter))
+}
+
+// Sharing Commands
+
+#[tauri::command]
+async fn generate_share_qr(
+    state: State<'_, AppState>,
+    page_id: String,
+    link_only: Option<bool>,
+) -> Result<ShareQrCode, String> {
+    let database = state.database.read().await;
+    let code = share::generate_share_qr(&database, &page_id, link_only.unwrap_or(false)).await?;
+    Ok(code)
+}
+
+#[tauri::command]
+async fn open_share_payload(
+    payload: String,
+    passcode: String,
+) -> Result<SharedPage, String> {
+    let page = share::open_payload(payload, passcode).await?;
+    Ok(page)
+}
+
+/// Static site of a notebook for hosting anywhere; readers decrypt it in the browser with
+/// the passphrase.
+#[tauri::command]
+async fn export_web_bundle(
+    state: State<'_, AppState>,
+    notebook_id: String,
+    passphrase: String,
+    destination: PathBuf,
+) -> Result<WebBundle, String> {
+    let database = state.database.read().await;
+    let bundle = web_bundle::export_web_bundle(&database, &notebook_id, &passphrase, &destination).await?;
+    state.audit(&database, "export_web_bundle", Some(&notebook_id)).await?;
+    Ok(bundle)
+}
+
+// Snapshot Commands
+
+#[tauri::command]
+async fn create_snapshot(
+    state: State<'_, AppState>,
+    name: String,
+) -> Result<SnapshotInfo, String> {
+    let database = state.database.read().await;
+    let snapshot = SnapshotStore::new(&state.config.backup_path, &database).create(&database, &name).await?;
+    state.audit(&database, "create_snapshot", Some(&snapshot.id)).await?;
+    Ok(snapshot)
+}
+
+#[tauri::command]
+async fn list_snapshots(
+    state: State<'_, AppState>,
+) -> Result<Vec<SnapshotInfo>, String> {
+    let database = state.database.read().await;
+    let snapshots = SnapshotStore::new(&state.config.backup_path, &database).list().await?;
+    Ok(snapshots)
+}
+
+#[tauri::command]
+async fn diff_snapshot(
+    state: State<'_, AppState>,
+    snapshot_id: String,
+) -> Result<SnapshotDiff, String> {
+    let database = state.database.read().await;
+    let diff = SnapshotStore::new(&state.config.backup_path, &database).diff(&database, &snapshot_id).await?;
+    Ok(diff)
+}
+
+#[tauri::command]
+async fn restore_snapshot(
+    state: State<'_, AppState>,
+    snapshot_id: String,
+) -> Result<SnapshotInfo, String> {
+    // Exclusive so no edit lands between the safety snapshot and the restore
+    let database = state.database.write().await;
+    let snapshot = SnapshotStore::new(&state.config.backup_path, &database).restore(&database, &snapshot_id).await?;
+    state.media_cache.clear();
+    state.audit(&database, "restore_snapshot", Some(&snapshot_id)).await?;
+    Ok(snapshot)
+}
+
+#[tauri::command]
+async fn delete_snapshot(
+    state: State<'_, AppState>,
+    snapshot_id: String,
+) -> Result<(), String> {
+    let database = state.database.read().await;
+    SnapshotStore::new(&state.config.backup_path, &database).delete(&snapshot_id).await?;
+    state.audit(&database, "delete_snapshot", Some(&snapshot_id)).await?;
+    Ok(())
+}
+
+// Sync Commands
+
+#[tauri::command]
+async fn get_sync_config(
+    state: State<'_, AppState>,
+) -> Result<SyncConfig, String> {
+    let database = state.database.read().await;
+    let config = state.sync.get_config(&database).await?;
+    Ok(config)
+}
+
+#[tauri::command]
+async fn set_sync_config(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    config: SyncConfig,
+) -> Result<(), String> {
+    policy::ensure_setting_unlocked(&state.config, cloud_sync::SYNC_CONFIG_KEY)?;
+    state.audit(&*state.database.read().await, "set_sync_config", None).await?;
+    settings::set(&app, &state, cloud_sync::SYNC_CONFIG_KEY, &serde_json::to_string(&config).map_err(AppError::from)?).await?;
+    Ok(())
+}
+
+/// Writes changes made since the last sync to the sync folder.
+#[tauri::command]
+async fn sync_to_cloud(
+    state: State<'_, AppState>,
+) -> Result<SyncReport, String> {
+    policy::ensure_sync_enabled(&state.config)?;
+    let database = state.database.read().await;
+    state.audit(&database, "sync_to_cloud", None).await?;
+    let report = state.sync.push(&database).await?;
+    Ok(report)
+}
+
+/// Applies what other devices wrote to the sync folder.
+#[tauri::command]
+async fn sync_from_cloud(
+    state: State<'_, AppState>,
+) -> Result<SyncReport, String> {
+    policy::ensure_sync_enabled(&state.config)?;
     let database = state.database.read().await;
-    database.update_section(request).await?;
-    Ok(())
+    state.audit(&database, "sync_from_cloud", None).await?;
+    let report = state.sync.pull(&database).await?;
+    Ok(report)
 }
 
 #[tauri::command]
-async fn delete_section(
+async fn get_sync_status(
     state: State<'_, AppState>,
-    id: String,
-) -> Result<(), String> {
+) -> Result<SyncStatus, String> {
     let database = state.database.read().await;
-    database.delete_section(&id).await?;
+    let status = state.sync.status(&database).await?;
+    Ok(status)
+}
+
+/// Seals the vault key with `passphrase` so another device can import it and sync with
+/// this one.
+#[tauri::command]
+async fn export_vault_key(
+    state: State<'_, AppState>,
+    passphrase: String,
+) -> Result<String, String> {
+    let database = state.database.read().await;
+    if !database.is_encrypted() {
+        return Err("Encryption is off, so there is no vault key to export".to_string());
+    }
+    state.audit(&database, "export_vault_key", None).await?;
+    let key_path = state.config.encryption_key_path.clone();
+    let exported = tauri::async_runtime::spawn_blocking(move || EncryptionManager::from_key_file(&key_path)?.export_key(&passphrase))
+        .await
+        .map_err(|e| AppError::Unknown(format!("Key export task failed: {}", e)))??;
+    Ok(exported)
+}
+
+/// Replaces this device's vault key with one exported on another device, so both can read
+/// the same sync folder. Only allowed before anything was written with the current key,
+/// which would otherwise become unreadable.
+#[tauri::command]
+async fn import_vault_key(
+    state: State<'_, AppState>,
+    exported: String,
+    passphrase: String,
+) -> Result<(), String> {
+    let mut database = state.database.write().await;
+    if !database.is_encrypted() {
+        return Err("Turn on encryption before importing a vault key".to_string());
+    }
+    if database.is_file_encrypted() || database.has_synced_content().await? {
+        return Err("This device already has content encrypted with its own key; import the vault key on a new device before adding anything".to_string());
+    }
+    let manager = tauri::async_runtime::spawn_blocking(move || EncryptionManager::import_key(&exported, &passphrase))
+        .await
+        .map_err(|e| AppError::Unknown(format!("Key import task failed: {}", e)))??;
+
+    database.close().await;
+    let result = manager.write_key_file(&state.config.encryption_key_path);
+    // Reopen with whichever key file is in place now
+    *database = AppState::open_database(&state.config).await?;
+    state.media_cache.clear();
+    result?;
+
+    state.audit(&database, "import_vault_key", None).await?;
     Ok(())
 }
 
-// Page Management Commands
+// Find and Replace Commands
 
+/// Previews matches as `find-replace-match` events, then applies them unless `dry_run`.
 #[tauri::command]
-async fn create_page(
+async fn find_replace(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    request: FindReplaceRequest,
+) -> Result<FindReplaceResult, String> {
+    // Exclusive so pages can't change between reading and rewriting them
+    let database = state.database.write().await;
+    let result = find_replace::find_replace(&app, &database, &request).await?;
+    if !result.applied {
+        return Ok(result);
+    }
+    state.audit(&database, "find_replace", Some(&request.query)).await?;
+
+    for preview in &result.pages {
+        let content_changed = preview.matches.iter().any(|m| m.field == SearchMatchField::Content);
+        let new_title = if preview.matches.iter().any(|m| m.field == SearchMatchField::Title) {
+            database.get_page(&preview.page_id).await?.map(|page| page.title)
+        } else {
+            None
+        };
+        let rename = new_title.as_deref().map(|new_title| (preview.title.as_str(), new_title));
+        state.page_updated(&database, &preview.page_id, content_changed, rename).await?;
+    }
+
+    Ok(result)
+}
+
+/// Regex extraction over decrypted page content, e.g. every invoice id in a notebook.
+#[tauri::command]
+async fn scan_content(
+    state: State<'_, AppState>,
+    pattern: String,
+    scope: Option<SearchFilters>,
+    case_sensitive: Option<bool>,
+) -> Result<ContentScanResult, String> {
+    let database = state.database.read().await;
+    let result = content_scan::scan_content(&database, &pattern, &scope.unwrap_or_default(), case_sensitive.unwrap_or(true)).await?;
+    Ok(result)
+}
+
+#[tauri::command]
+async fn get_page_revisions(
+    state: State<'_, AppState>,
+    page_id: String,
+) -> Result<Vec<PageRevision>, String> {
+    let database = state.database.read().await;
+    let revisions = database.get_page_revisions(&page_id).await?;
+    Ok(revisions)
+}
+
+// Zettelkasten Commands
+
+#[tauri::command]
+async fn create_zettel_note(
     state: State<'_, AppState>,
-    request: CreatePageRequest,
+    request: CreateZettelNoteRequest,
 ) -> Result<Page, String> {
     let database = state.database.read().await;
-    let page = database.create_page(request).await?;
-    
-    // Generate embeddings for the page content
-    let ai_service = state.ai_service.read().await;
-    if ai_service.is_embedding_available() {
-        if let Ok(embeddings) = ai_service.generate_embeddings(&page.content).await {
-            let _ = database.store_embedding(&page.id, &embeddings).await;
-        }
-    }
-    
+    let page = zettel::create_note(&database, request).await?;
+    state.page_created(&database, &page).await?;
+
     Ok(page)
 }
 
 #[tauri::command]
-async fn get_pages(
+async fn assign_zettel_id(
     state: State<'_, AppState>,
-    notebook_id: String,
-    section_id: Option<String>,
-) -> Result<Vec<Page>, String> {
+    page_id: String,
+) -> Result<String, String> {
     let database = state.database.read().await;
-    let pages = database.get_pages(&notebook_id, section_id.as_deref()).await?;
-    Ok(pages)
+    let id = database.assign_zettel_id(&page_id).await?;
+    Ok(id)
 }
 
+/// Follows a `[[202403011430]]` citation.
 #[tauri::command]
-async fn get_page(
+async fn get_page_by_zettel_id(
     state: State<'_, AppState>,
-    id: String,
+    zettel_id: String,
 ) -> Result<Option<Page>, String> {
     let database = state.database.read().await;
-    let page = database.get_page(&id).await?;
+    let page = database.get_page_by_zettel_id(&zettel_id).await?;
     Ok(page)
 }
 
 #[tauri::command]
-async fn update_page(
+async fn link_zettel_citations(
     state: State<'_, AppState>,
-    request: UpdatePageRequest,
+    page_id: String,
+) -> Result<Vec<PageLink>, String> {
+    let database = state.database.read().await;
+    let links = zettel::link_citations(&database, &page_id).await?;
+    Ok(links)
+}
+
+#[tauri::command]
+async fn set_zettel_ids_enabled(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    enabled: bool,
 ) -> Result<(), String> {
+    settings::set(&app, &state, ZETTEL_IDS_KEY, &enabled.to_string()).await?;
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_zettel_ids_enabled(
+    state: State<'_, AppState>,
+) -> Result<bool, String> {
     let database = state.database.read().await;
-    database.update_page(request.clone()).await?;
-    
-    // Update embeddings if content changed
-    if let Some(content) = request.content {
-        let ai_service = state.ai_service.read().await;
-        if ai_service.is_embedding_available() {
-            if let Ok(embeddings) = ai_service.generate_embeddings(&content).await {
-                let _ = database.store_embedding(&request.id, &embeddings).await;
-            }
+    Ok(database.get_setting(ZETTEL_IDS_KEY).await?.as_deref() == Some("true"))
+}
+
+// Map of Content Commands
+
+/// Builds or refreshes an index page listing the pages of a tag, section or cluster.
+#[tauri::command]
+async fn generate_moc(
+    state: State<'_, AppState>,
+    request: GenerateMocRequest,
+) -> Result<Page, String> {
+    let database = state.database.read().await;
+    let ai_service = state.ai_service.read().await;
+    let (page, created) = moc::generate_moc(&database, &ai_service, request).await?;
+    if created {
+        state.page_created(&database, &page).await?;
+    } else {
+        state.page_content_changed(&database, &page.id).await?;
+        if let Err(e) = os_search::refresh_page(&database, &page.id).await {
+            tracing::warn!("Failed to update OS search stubs: {}", e);
         }
     }
-    
-    Ok(())
+    Ok(page)
 }
 
 #[tauri::command]
-async fn delete_page(
+async fn refresh_moc(
     state: State<'_, AppState>,
-    id: String,
+    page_id: String,
+) -> Result<Page, String> {
+    let database = state.database.read().await;
+    let ai_service = state.ai_service.read().await;
+    let page = moc::refresh_moc(&database, &ai_service, &page_id).await?;
+    state.page_content_changed(&database, &page.id).await?;
+    if let Err(e) = os_search::refresh_page(&database, &page.id).await {
+        tracing::warn!("Failed to update OS search stubs: {}", e);
+    }
+    Ok(page)
+}
+
+// Stale Content Commands
+
+/// Pages likely out of date, for review.
+#[tauri::command]
+async fn get_stale_pages(
+    state: State<'_, AppState>,
+) -> Result<Vec<StalePage>, String> {
+    let database = state.database.read().await;
+    let ai_service = state.ai_service.read().await;
+    let pages = stale::find_stale_pages(&database, &ai_service, chrono::Utc::now()).await?;
+    Ok(pages)
+}
+
+#[tauri::command]
+async fn mark_page_reviewed(
+    state: State<'_, AppState>,
+    page_id: String,
 ) -> Result<(), String> {
     let database = state.database.read().await;
-    database.delete_page(&id).await?;
+    database.mark_page_reviewed(&page_id).await?;
     Ok(())
 }
 
+// Jump List Commands
+
+/// Marks a page and its notebook as opened, adds it to the recents list and lists it in the
+/// OS jump list / recent documents.
 #[tauri::command]
-async fn move_page(
+async fn record_page_access(
     state: State<'_, AppState>,
-    request: MovePageRequest,
+    page_id: String,
 ) -> Result<(), String> {
     let database = state.database.read().await;
-    database.move_page(request).await?;
+    database.record_page_access(&page_id).await?;
+    if let Err(e) = jump_list::refresh(&database).await {
+        tracing::warn!("Failed to update jump list: {}", e);
+    }
     Ok(())
 }
 
 #[tauri::command]
-async fn get_page_with_subpages(
+async fn record_notebook_access(
     state: State<'_, AppState>,
-    id: String,
-) -> Result<Option<PageWithSubpages>, String> {
+    notebook_id: String,
+) -> Result<(), String> {
     let database = state.database.read().await;
-    let page_with_subpages = database.get_page_with_subpages(&id).await?;
-    Ok(page_with_subpages)
+    database.record_notebook_access(&notebook_id).await?;
+    Ok(())
 }
 
-// Media Management Commands
-
+/// Recently opened pages, newest first, for a "Jump back in" view.
 #[tauri::command]
-async fn upload_media(
+async fn get_recent_pages(
     state: State<'_, AppState>,
-    request: UploadMediaRequest,
-) -> Result<MediaAttachment, String> {
+    limit: Option<usize>,
+) -> Result<Vec<RecentPage>, String> {
     let database = state.database.read().await;
-    let media = database.upload_media(request).await?;
-    Ok(media)
+    let pages = database.get_recent_pages(limit.unwrap_or(20)).await?;
+    Ok(pages)
 }
 
 #[tauri::command]
-async fn get_media_attachments(
+async fn set_notebook_pinned(
     state: State<'_, AppState>,
-    page_id: Option<String>,
-    note_id: Option<String>,
-) -> Result<Vec<MediaAttachment>, String> {
+    notebook_id: String,
+    pinned: bool,
+) -> Result<(), String> {
     let database = state.database.read().await;
-    let attachments = database.get_media_attachments(page_id.as_deref(), note_id.as_deref()).await?;
-    Ok(attachments)
+    database.set_notebook_pinned(&notebook_id, pinned).await?;
+    Ok(())
 }
 
+/// Caps how much its pages' attachments and recordings may take up; `None` lifts the cap.
 #[tauri::command]
-async fn delete_media(
+async fn set_notebook_quota(
     state: State<'_, AppState>,
-    id: String,
+    notebook_id: String,
+    quota: Option<u64>,
 ) -> Result<(), String> {
     let database = state.database.read().await;
-    database.delete_media(&id).await?;
+    state.audit(&database, "set_notebook_quota", Some(&notebook_id)).await?;
+    database.set_notebook_quota(&notebook_id, quota).await?;
     Ok(())
 }
 
-// Page Link Management Commands
-
+/// Pinned notebooks and pages for the favorites sidebar.
 #[tauri::command]
-async fn create_page_link(
+async fn get_pinned_items(
     state: State<'_, AppState>,
-    request: CreatePageLinkRequest,
-) -> Result<PageLink, String> {
+) -> Result<PinnedItems, String> {
     let database = state.database.read().await;
-    let link = database.create_page_link(request).await?;
-    Ok(link)
+    let items = database.get_pinned_items().await?;
+    Ok(items)
 }
 
 #[tauri::command]
-async fn get_page_links(
+async fn set_page_pinned(
     state: State<'_, AppState>,
     page_id: String,
-) -> Result<Vec<PageLink>, String> {
+    pinned: bool,
+) -> Result<(), String> {
     let database = state.database.read().await;
-    let links = database.get_page_links(&page_id).await?;
-    Ok(links)
+    database.set_page_pinned(&page_id, pinned).await?;
+    if let Err(e) = jump_list::refresh(&database).await {
+        tracing::warn!("Failed to update jump list: {}", e);
+    }
+    Ok(())
 }
 
 #[tauri::command]
-async fn delete_page_link(
+async fn get_jump_list_pages(
     state: State<'_, AppState>,
-    id: String,
-) -> Result<(), String> {
+    recent_limit: Option<usize>,
+) -> Result<Vec<JumpListEntry>, String> {
     let database = state.database.read().await;
-    database.delete_page_link(&id).await?;
-    Ok(())
+    let entries = database.get_jump_list_entries(recent_limit.unwrap_or(10)).await?;
+    Ok(entries)
 }
 
+/// The page to open when the app was launched from a jump list entry.
 #[tauri::command]
-async fn get_page_relationships(
+async fn get_launch_page() -> Result<Option<String>, String> {
+    Ok(jump_list::launch_page_id())
+}
+
+// OS Search Commands
+
+/// Turns on stub files for OS-level search (in `folder`, or Documents/DeviseOS Pages) and
+/// writes them, or turns it off and removes them. Pages in private notebooks never get a stub.
+#[tauri::command]
+async fn set_os_search_enabled(
+    app: tauri::AppHandle,
     state: State<'_, AppState>,
-    page_id: String,
-) -> Result<PageRelationships, String> {
+    enabled: bool,
+    folder: Option<PathBuf>,
+) -> Result<Option<OsSearchSync>, String> {
+    if !enabled {
+        settings::reset(&app, &state, os_search::OS_SEARCH_FOLDER_KEY).await?;
+        return Ok(None);
+    }
+
+    let folder = folder.or_else(os_search::default_folder)
+        .ok_or_else(|| AppError::Configuration("No documents folder for OS search stubs".to_string()))?;
+    match settings::set(&app, &state, os_search::OS_SEARCH_FOLDER_KEY, &folder.to_string_lossy()).await? {
+        settings::Applied::OsSearch(result) => Ok(result),
+        _ => Ok(None),
+    }
+}
+
+#[tauri::command]
+async fn sync_os_search(
+    state: State<'_, AppState>,
+) -> Result<Option<OsSearchSync>, String> {
     let database = state.database.read().await;
-    let relationships = database.get_page_relationships(&page_id).await?;
-    Ok(relationships)
+    let result = os_search::refresh(&database).await?;
+    Ok(result)
 }
 
-// Notebook Search and Stats Commands
+#[tauri::command]
+async fn get_os_search_folder(
+    state: State<'_, AppState>,
+) -> Result<Option<PathBuf>, String> {
+    let database = state.database.read().await;
+    Ok(database.get_setting(os_search::OS_SEARCH_FOLDER_KEY).await?.map(PathBuf::from))
+}
+
+// Trash Commands
 
 #[tauri::command]
-async fn search_notebook(
+async fn list_trash(
     state: State<'_, AppState>,
-    request: NotebookSearchRequest,
-) -> Result<Vec<Page>, String> {
+) -> Result<Vec<TrashItem>, String> {
     let database = state.database.read().await;
-    let pages = database.search_notebook(request).await?;
-    Ok(pages)
+    trash::purge_expired(&database).await?;
+    let items = database.list_trash().await?;
+    Ok(items)
 }
 
 #[tauri::command]
-async fn get_notebook_stats(
+async fn restore_from_trash(
     state: State<'_, AppState>,
-    notebook_id: String,
-) -> Result<NotebookStats, String> {
+    id: String,
+) -> Result<TrashItem, String> {
     let database = state.database.read().await;
-    let stats = database.get_notebook_stats(&notebook_id).await?;
-    Ok(stats)
+    let item = database.restore_from_trash(&id).await?;
+    state.audit(&database, "restore_from_trash", Some(&id)).await?;
+
+    if item.item_type == TrashItemType::Page {
+        if let Err(e) = jump_list::refresh(&database).await {
+            tracing::warn!("Failed to update jump list: {}", e);
+        }
+        if let Err(e) = os_search::refresh(&database).await {
+            tracing::warn!("Failed to update OS search stubs: {}", e);
+        }
+    }
+    Ok(item)
 }
 
-// Reordering Commands
+/// Permanently deletes everything in the trash; returns how many items were removed.
+#[tauri::command]
+async fn empty_trash(
+    state: State<'_, AppState>,
+) -> Result<u64, String> {
+    let database = state.database.read().await;
+    state.audit(&database, "empty_trash", None).await?;
+    let removed = database.empty_trash().await?;
+    Ok(removed)
+}
+
+// AI Job Commands
 
 #[tauri::command]
-async fn reorder_notebooks(
+async fn get_ai_job(
     state: State<'_, AppState>,
-    request: ReorderItemsRequest,
-) -> Result<(), String> {
+    id: String,
+) -> Result<Option<AiJob>, String> {
     let database = state.database.read().await;
-    database.reorder_notebooks(request).await?;
-    Ok(())
+    let job = database.get_ai_job(&id).await?;
+    Ok(job)
 }
 
+/// Most recently updated first, optionally only those with `status`.
 #[tauri::command]
-async fn reorder_sections(
+async fn list_ai_jobs(
     state: State<'_, AppState>,
-    request: ReorderItemsRequest,
-) -> Result<(), String> {
+    status: Option<AiJobStatus>,
+    limit: Option<usize>,
+) -> Result<Vec<AiJob>, String> {
     let database = state.database.read().await;
-    database.reorder_sections(request).await?;
-    Ok(())
+    let jobs = database.list_ai_jobs(status, limit.unwrap_or(100)).await?;
+    Ok(jobs)
 }
 
 #[tauri::command]
-async fn reorder_pages(
+async fn cancel_ai_job(
     state: State<'_, AppState>,
-    request: ReorderItemsRequest,
-) -> Result<(), String> {
+    id: String,
+) -> Result<AiJob, String> {
     let database = state.database.read().await;
-    database.reorder_pages(request).await?;
-    Ok(())
+    let job = state.jobs.cancel(&database, &id).await?;
+    Ok(job)
+}
+
+#[tauri::command]
+async fn retry_ai_job(
+    state: State<'_, AppState>,
+    id: String,
+) -> Result<AiJob, String> {
+    let database = state.database.read().await;
+    let job = state.jobs.retry(&database, &id).await?;
+    Ok(job)
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    let default_config = AppConfig::default();
+    crash::install_panic_hook(default_config.crash_reports_path.clone());
+    if let Err(e) = logging::init(&default_config.logs_path) {
+        eprintln!("Failed to initialize file logging: {}", e);
+    }
+
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
+        .plugin(tauri_plugin_deep_link::init())
+        .plugin(tauri_plugin_dialog::init())
+        .manage(FileGrants::default())
+        .on_window_event(|window, event| {
+            // Dropped files may be attached from disk; see `attach_file_from_path`
+            if let tauri::WindowEvent::DragDrop(tauri::DragDropEvent::Drop { paths, .. }) = event {
+                window.state::<FileGrants>().grant(paths.iter().cloned());
+            }
+        })
+        .register_asynchronous_uri_scheme_protocol(media_stream::SCHEME, |ctx, request, responder| {
+            let app_handle = ctx.app_handle().clone();
+            tauri::async_runtime::spawn(async move {
+                responder.respond(media_stream::respond(&app_handle, request).await);
+            });
+        })
         .setup(|app| {
+            // Stubs in the OS search index and jump list entries open pages through
+            // `deviseos://`; installers register it, but dev and Linux builds need it at runtime
+            #[cfg(any(target_os = "linux", all(debug_assertions, windows)))]
+            if let Err(e) = app.deep_link().register_all() {
+                tracing::warn!("Failed to register the deviseos:// handler: {}", e);
+            }
+            let link_handle = app.handle().clone();
+            app.deep_link().on_open_url(move |event| {
+                for page_id in event.urls().iter().filter_map(|url| jump_list::page_id_from_link(url.as_str())) {
+                    let _ = link_handle.emit(jump_list::OPEN_PAGE_EVENT, page_id);
+                }
+            });
+
             let app_handle = app.handle();
             
             tauri::async_runtime::spawn(async move {
                 match AppState::new().await {
                     Ok(state) => {
+                        let database = state.database.clone();
+                        let updates = state.updates.clone();
+                        let update_checks_enabled = state.config.update_checks_enabled;
+                        let jobs = state.jobs.clone();
+                        let ai_service = state.ai_service.clone();
                         app_handle.manage(state);
                         tracing::info!("DeviseOS initialized successfully");
+
+                        if let Err(e) = jobs.start(app_handle.clone(), database.clone(), ai_service).await {
+                            tracing::error!("Failed to start the AI job worker: {}", e);
+                        }
+
+                        let database = database.read().await;
+                        if let Ok(Some(level)) = database.get_setting(logging::LOG_LEVEL_KEY).await {
+                            if let Err(e) = logging::set_level(&level) {
+                                tracing::warn!("Ignoring saved log level: {}", e);
+                            }
+                        }
+                        if update_checks_enabled {
+                            if let Err(e) = updates.check_if_due(&database).await {
+                                tracing::debug!("Scheduled update check failed: {}", e);
+                            }
+                        }
+                        if let Err(e) = jump_list::refresh(&database).await {
+                            tracing::warn!("Failed to update jump list: {}", e);
+                        }
+                        if let Err(e) = os_search::refresh(&database).await {
+                            tracing::warn!("Failed to update OS search stubs: {}", e);
+                        }
+                        if let Err(e) = trash::purge_expired(&database).await {
+                            tracing::warn!("Failed to purge expired trash: {}", e);
+                        }
                     }
                     Err(e) => {
                         tracing::error!("Failed to initialize DeviseOS: {}", e);
@@ -681,24 +3154,61 @@ pub fn run() {
         .invoke_handler(tauri::generate_handler![
             create_note,
             get_notes,
+            list_notes,
             get_note,
             update_note,
             delete_note,
             search_notes,
             semantic_search,
+            hybrid_search,
             transcribe_audio,
+            transcribe_audio_stream,
+            start_dictation,
+            push_dictation_audio,
+            stop_dictation,
+            detect_language,
             add_voice_annotation,
+            resolve_spoken_name,
+            process_voice_command,
             suggest_tags,
+            extract_keyphrases,
             get_tags,
+            suggest_tag_merges,
+            merge_tags,
+            update_tag,
+            delete_tag,
+            get_pages_by_tag,
+            get_notes_by_tag,
             analyze_sentiment,
             extract_entities,
             generate_summary,
+            rewrite_text,
+            expand_text,
+            // Writing assistant
+            ai_rewrite,
+            ai_expand,
+            ai_fix_grammar,
+            translate_content,
+            chat_with_notes,
+            ask_page,
             process_note_ai,
             get_app_config,
             set_setting,
             get_setting,
+            reset_setting,
+            get_setting_schemas,
+            get_locale,
+            set_locale,
+            get_compliance_policy,
+            get_audit_log,
             initialize_ai_models,
             get_ai_status,
+            get_ai_device_info,
+            set_ai_device,
+            set_llm_model_path,
+            list_downloadable_models,
+            download_model,
+            delete_model,
             // Notebook Management
             create_notebook,
             get_notebooks,
@@ -706,6 +3216,7 @@ pub fn run() {
             update_notebook,
             delete_notebook,
             get_notebook_hierarchy,
+            get_notebook_language_settings,
             // Section Management
             create_section,
             get_sections,
@@ -714,28 +3225,186 @@ pub fn run() {
             delete_section,
             // Page Management
             create_page,
+            get_or_create_daily_note,
             get_pages,
+            list_pages,
             get_page,
             update_page,
             delete_page,
             move_page,
+            bulk_update_pages,
             get_page_with_subpages,
+            get_language_settings,
+            // Page Properties
+            get_property_definitions,
+            define_property,
+            delete_property_definition,
+            set_page_property,
+            remove_page_property,
+            get_page_properties,
+            filter_pages_by_properties,
             // Media Management
             upload_media,
+            attach_file_from_path,
+            choose_attachment_files,
             get_media_attachments,
+            audit_image_locations,
+            get_original_media,
+            get_security_config,
+            set_security_config,
             delete_media,
+            process_paste,
+            import_files,
+            capture_screenshot,
+            set_media_annotations,
+            process_document_photo,
+            recognize_ink,
             // Page Link Management
             create_page_link,
             get_page_links,
             delete_page_link,
             get_page_relationships,
+            promote_page_link,
+            dismiss_page_link,
+            get_backlinks,
+            resolve_transclusions,
+            get_graph_metrics,
+            get_suggested_links,
+            suggest_title,
+            set_auto_title_enabled,
+            get_auto_title_enabled,
             // Notebook Search and Stats
             search_notebook,
             get_notebook_stats,
+            get_sentiment_trends,
             // Reordering
             reorder_notebooks,
             reorder_sections,
             reorder_pages,
+            // Automations
+            create_automation,
+            get_automations,
+            update_automation,
+            delete_automation,
+            run_automation,
+            get_automation_runs,
+            // MQTT Integration
+            get_mqtt_config,
+            set_mqtt_config,
+            test_mqtt_connection,
+            // Scripting
+            list_scripts,
+            run_script,
+            // Bundles
+            install_bundle,
+            uninstall_bundle,
+            list_bundles,
+            get_templates,
+            get_snippets,
+            get_prompts,
+            // Updates
+            check_for_updates,
+            get_update_info,
+            get_update_config,
+            set_update_config,
+            // Crash Reports
+            list_crash_reports,
+            submit_crash_report,
+            // Diagnostics
+            get_system_health,
+            get_app_stats,
+            get_storage_usage,
+            run_maintenance,
+            audit_encryption_coverage,
+            encrypt_database_file,
+            generate_diagnostics_bundle,
+            set_log_level,
+            get_log_level,
+            tail_logs,
+            execute_readonly_sql,
+            // Embedding Index
+            set_vector_index_enabled,
+            get_vector_index_enabled,
+            reindex_embeddings,
+            get_embedding_model_status,
+            migrate_embeddings,
+            // Web Viewer
+            start_web_viewer,
+            stop_web_viewer,
+            get_web_viewer_status,
+            // Export
+            export_page,
+            export_notebook,
+            render_page_html,
+            get_page_export_history,
+            re_export_all,
+            export_stats_csv,
+            translate_voice_annotation,
+            get_voice_annotation_audio,
+            get_waveform,
+            list_voice_annotations,
+            update_voice_annotation,
+            delete_voice_annotation,
+            recompress_voice_annotations,
+            export_voice_subtitles,
+            // Sharing
+            generate_share_qr,
+            open_share_payload,
+            export_web_bundle,
+            // Snapshots
+            create_snapshot,
+            list_snapshots,
+            diff_snapshot,
+            restore_snapshot,
+            delete_snapshot,
+            // Sync
+            get_sync_config,
+            set_sync_config,
+            sync_to_cloud,
+            sync_from_cloud,
+            get_sync_status,
+            export_vault_key,
+            import_vault_key,
+            // Find and Replace
+            find_replace,
+            scan_content,
+            get_page_revisions,
+            // Zettelkasten
+            create_zettel_note,
+            assign_zettel_id,
+            get_page_by_zettel_id,
+            link_zettel_citations,
+            set_zettel_ids_enabled,
+            get_zettel_ids_enabled,
+            // Map of Content
+            generate_moc,
+            refresh_moc,
+            // Stale Content
+            get_stale_pages,
+            mark_page_reviewed,
+            // Jump List
+            record_page_access,
+            record_notebook_access,
+            get_recent_pages,
+            set_page_pinned,
+            set_notebook_pinned,
+            set_notebook_quota,
+            get_pinned_items,
+            get_jump_list_pages,
+            get_launch_page,
+            // OS Search
+            set_os_search_enabled,
+            sync_os_search,
+            get_os_search_folder,
+            // Trash
+            list_trash,
+            restore_from_trash,
+            empty_trash,
+            // AI Jobs
+            get_ai_job,
+            list_ai_jobs,
+            cancel_ai_job,
+            retry_ai_job,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
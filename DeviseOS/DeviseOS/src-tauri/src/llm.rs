@@ -0,0 +1,413 @@
+use std::path::{Path, PathBuf};
+use candle_core::{quantized::gguf_file, Device, Tensor};
+use candle_transformers::{generation::LogitsProcessor, models::quantized_llama::ModelWeights};
+use tokenizers::Tokenizer;
+use crate::{
+    AppError, AppResult,
+    models::RewriteStyle,
+};
+
+/// Setting that overrides `AppConfig::llm_model_path`.
+pub const LLM_MODEL_PATH_KEY: &str = "llm_model_path";
+
+/// Prompt and completion together stay within this many tokens; a long prompt loses the end
+/// of its document, never its instructions, unless it must be kept whole.
+const MAX_CONTEXT_TOKENS: usize = 4096;
+const TEMPERATURE: f64 = 0.3;
+const SAMPLING_SEED: u64 = 299_792_458;
+/// End-of-turn tokens of common chat models, for GGUF files that don't declare one.
+const EOS_TOKENS: &[&str] = &["</s>", "<|eot_id|>", "<|end_of_text|>", "<|im_end|>", "<|endoftext|>"];
+
+/// Instructions around a document. Only the document is shortened to fit the model's
+/// context, so what to do with it is never cut off.
+#[derive(Debug, Clone)]
+pub struct Prompt {
+    pub before: String,
+    pub document: String,
+    pub after: String,
+    /// The whole document must be read and the completion must finish, as in translation;
+    /// a document that doesn't fit or a completion that runs out of tokens is an error.
+    pub keep_whole: bool,
+}
+
+impl Prompt {
+    pub fn text(&self) -> String {
+        format!("{}{}{}", self.before, self.document, self.after)
+    }
+}
+
+/// A quantized Llama-family model loaded from a GGUF file.
+pub struct LocalLlm {
+    model: ModelWeights,
+    tokenizer: Tokenizer,
+    device: Device,
+    eos_token: Option<u32>,
+}
+
+impl LocalLlm {
+    /// Loads `path` and the tokenizer next to it (see `tokenizer_path`).
+    pub fn load(path: &Path, device: &Device) -> AppResult<Self> {
+        let mut file = std::fs::File::open(path)?;
+        let content = gguf_file::Content::read(&mut file)
+            .map_err(|e| AppError::AIProcessing(format!("Invalid GGUF file {}: {}", path.display(), e)))?;
+        let declared_eos = content
+            .metadata
+            .get("tokenizer.ggml.eos_token_id")
+            .and_then(|value| value.to_u32().ok());
+        let model = ModelWeights::from_gguf(content, &mut file, device)
+            .map_err(|e| AppError::AIProcessing(format!("Failed to load language model: {}", e)))?;
+
+        let tokenizer_path = tokenizer_path(path);
+        let tokenizer = Tokenizer::from_file(&tokenizer_path).map_err(|e| {
+            AppError::ModelNotFound(format!("Failed to load tokenizer {}: {}", tokenizer_path.display(), e))
+        })?;
+        let eos_token = declared_eos.or_else(|| EOS_TOKENS.iter().find_map(|token| tokenizer.token_to_id(token)));
+
+        tracing::info!("Loaded language model {}", path.display());
+        Ok(Self { model, tokenizer, device: device.clone(), eos_token })
+    }
+
+    /// Completes `prompt` with up to `max_tokens` new tokens. Blocks for as long as
+    /// inference takes, so async callers run it on a blocking thread.
+    pub fn generate(&mut self, prompt: &Prompt, max_tokens: usize) -> AppResult<String> {
+        let before = self.encode(&prompt.before, true)?;
+        let document = self.encode(&prompt.document, false)?;
+        let after = self.encode(&prompt.after, false)?;
+        let budget = MAX_CONTEXT_TOKENS.saturating_sub(max_tokens);
+        let tokens = fit_prompt(&before, &document, &after, budget).ok_or_else(|| {
+            AppError::AIProcessing("The prompt's instructions alone don't fit the language model's context".to_string())
+        })?;
+        if tokens.len() < before.len() + document.len() + after.len() {
+            if prompt.keep_whole {
+                return Err(AppError::AIProcessing("The text is too long for the language model's context".to_string()));
+            }
+            tracing::debug!("Shortened a {}-token document to fit the language model's context", document.len());
+        }
+        let tokens = tokens.as_slice();
+
+        let mut sampler = LogitsProcessor::new(SAMPLING_SEED, Some(TEMPERATURE), None);
+        let mut output = Vec::new();
+        let mut finished = false;
+        let mut next = self.sample(&mut sampler, tokens, 0)?;
+        for index in 0..max_tokens {
+            if Some(next) == self.eos_token {
+                finished = true;
+                break;
+            }
+            output.push(next);
+            if index + 1 < max_tokens {
+                next = self.sample(&mut sampler, &[next], tokens.len() + index)?;
+            }
+        }
+        if prompt.keep_whole && !finished {
+            return Err(AppError::AIProcessing(format!("The language model's reply was cut off at {} tokens", max_tokens)));
+        }
+
+        self.tokenizer.decode(&output, true)
+            .map_err(|e| AppError::AIProcessing(format!("Failed to decode completion: {}", e)))
+    }
+
+    fn encode(&self, text: &str, add_special_tokens: bool) -> AppResult<Vec<u32>> {
+        let encoding = self.tokenizer.encode(text, add_special_tokens)
+            .map_err(|e| AppError::AIProcessing(format!("Failed to tokenize prompt: {}", e)))?;
+        Ok(encoding.get_ids().to_vec())
+    }
+
+    /// Runs `tokens` starting at `position` (0 resets the KV cache) and samples the next one.
+    fn sample(&mut self, sampler: &mut LogitsProcessor, tokens: &[u32], position: usize) -> AppResult<u32> {
+        self.forward(sampler, tokens, position)
+            .map_err(|e| AppError::AIProcessing(format!("Language model inference failed: {}", e)))
+    }
+
+    fn forward(&mut self, sampler: &mut LogitsProcessor, tokens: &[u32], position: usize) -> candle_core::Result<u32> {
+        let input = Tensor::new(tokens, &self.device)?.unsqueeze(0)?;
+        let logits = self.model.forward(&input, position)?.squeeze(0)?;
+        sampler.sample(&logits)
+    }
+}
+
+/// The prompt's tokens within `budget`, dropping the end of the document as needed; `None`
+/// when the text around the document doesn't fit on its own.
+fn fit_prompt(before: &[u32], document: &[u32], after: &[u32], budget: usize) -> Option<Vec<u32>> {
+    let room = budget.checked_sub(before.len() + after.len())?;
+    let document = &document[..document.len().min(room)];
+    Some([before, document, after].concat())
+}
+
+/// `model.tokenizer.json` for `model.gguf` when present, else `tokenizer.json` in the same
+/// directory.
+pub fn tokenizer_path(model_path: &Path) -> PathBuf {
+    let specific = model_path.with_extension("tokenizer.json");
+    if specific.exists() {
+        specific
+    } else {
+        model_path.with_file_name("tokenizer.json")
+    }
+}
+
+pub fn summary_prompt(text: &str) -> Prompt {
+    instruction("Summarize the following note in two or three sentences. Reply with the summary only.", text)
+}
+
+pub fn title_prompt(text: &str) -> Prompt {
+    instruction(
+        "Write a short title, at most eight words, for the note that begins as follows. Use the \
+         note's language. Reply with the title only.",
+        text,
+    )
+}
+
+pub fn rewrite_prompt(text: &str, style: RewriteStyle) -> Prompt {
+    let task = match style {
+        RewriteStyle::Concise => "Rewrite the following text to be more concise, keeping every key point.",
+        RewriteStyle::Clearer => "Rewrite the following text so it is clearer and easier to follow.",
+        RewriteStyle::Formal => "Rewrite the following text in a formal tone.",
+        RewriteStyle::Casual => "Rewrite the following text in a casual, friendly tone.",
+    };
+    instruction(&format!("{} Keep its language and Markdown formatting. Reply with the rewritten text only.", task), text)
+}
+
+pub fn expand_prompt(text: &str) -> Prompt {
+    instruction(
+        "Expand the following notes into fuller prose, adding explanation but no new facts. \
+         Keep their language and Markdown formatting. Reply with the expanded text only.",
+        text,
+    )
+}
+
+pub fn grammar_prompt(text: &str) -> Prompt {
+    instruction(
+        "Correct the spelling, grammar and punctuation of the following text without changing \
+         its meaning, tone or Markdown formatting. Reply with the corrected text only.",
+        text,
+    )
+}
+
+pub fn translate_prompt(text: &str, target_lang: &str) -> Prompt {
+    let prompt = instruction(
+        &format!(
+            "Translate the following text into the language with BCP-47 tag \"{}\". Keep its \
+             Markdown formatting, links and code unchanged. Reply with the translation only.",
+            target_lang
+        ),
+        text,
+    );
+    Prompt { keep_whole: true, ..prompt }
+}
+
+/// Source text for one translation request and what separated it from the previous one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TranslationChunk {
+    pub separator: &'static str,
+    pub text: String,
+}
+
+/// Splits `text` at paragraph breaks into chunks of at most `max_chars` bytes so each
+/// translation fits the model's context. A longer paragraph is split after its sentences,
+/// or else at spaces, so no text is left out.
+pub fn translation_chunks(text: &str, max_chars: usize) -> Vec<TranslationChunk> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    let flush = |chunks: &mut Vec<TranslationChunk>, text: String, separator: &'static str| {
+        let separator = if chunks.is_empty() { "" } else { separator };
+        chunks.push(TranslationChunk { separator, text });
+    };
+    for paragraph in text.split("\n\n").filter(|p| !p.trim().is_empty()) {
+        if !current.is_empty() && current.len() + paragraph.len() + 2 > max_chars {
+            flush(&mut chunks, std::mem::take(&mut current), "\n\n");
+        }
+        if paragraph.len() > max_chars {
+            for (index, (separator, piece)) in split_paragraph(paragraph, max_chars).into_iter().enumerate() {
+                flush(&mut chunks, piece.to_string(), if index == 0 { "\n\n" } else { separator });
+            }
+            continue;
+        }
+        if !current.is_empty() {
+            current.push_str("\n\n");
+        }
+        current.push_str(paragraph);
+    }
+    if !current.is_empty() {
+        flush(&mut chunks, current, "\n\n");
+    }
+    chunks
+}
+
+/// Pieces of at most `max_chars` bytes, each with the text that separated it from the one
+/// before: a space, or nothing after a CJK full stop or a cut mid-word.
+fn split_paragraph(paragraph: &str, max_chars: usize) -> Vec<(&'static str, &str)> {
+    let mut pieces = Vec::new();
+    let mut separator = "";
+    let mut rest = paragraph.trim();
+    while rest.len() > max_chars {
+        let mut end = max_chars;
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        if end == 0 {
+            end = rest.chars().next().map(char::len_utf8).unwrap_or(rest.len());
+        }
+        let window = &rest[..end];
+        let cut = window
+            .char_indices()
+            .filter(|&(index, c)| match c {
+                '。' | '！' | '？' => true,
+                '.' | '!' | '?' => rest[index + 1..].starts_with(char::is_whitespace),
+                _ => false,
+            })
+            .map(|(index, c)| index + c.len_utf8())
+            .last()
+            .or_else(|| window.rfind(char::is_whitespace))
+            .filter(|&cut| cut > 0)
+            .unwrap_or(end);
+        let (piece, remainder) = rest.split_at(cut);
+        pieces.push((separator, piece.trim_end()));
+        separator = if remainder.starts_with(char::is_whitespace) { " " } else { "" };
+        rest = remainder.trim_start();
+    }
+    if !rest.is_empty() {
+        pieces.push((separator, rest));
+    }
+    pieces
+}
+
+/// A question answered from numbered sources, each (title, excerpt), cited as `[n]`.
+pub fn chat_prompt(question: &str, sources: &[(String, String)]) -> Prompt {
+    let context: Vec<String> = sources
+        .iter()
+        .enumerate()
+        .map(|(index, (title, excerpt))| format!("[{}] {}\n{}", index + 1, title, excerpt.trim()))
+        .collect();
+    question_about(
+        "Answer the question using only the numbered notes below. Cite the notes you use \
+         like [1]. If the notes don't contain the answer, say so.\n\nNotes:\n\n",
+        &context.join("\n\n"),
+        question,
+    )
+}
+
+/// Asks for an answer from `text` alone followed by a `Quote:` line with the supporting
+/// sentence, so the answer can be highlighted in the page.
+pub fn page_qa_prompt(question: &str, text: &str) -> Prompt {
+    question_about(
+        "Answer the question using only the note below. Then, on a new line starting with \
+         \"Quote:\", copy the exact sentence from the note that supports the answer. If the \
+         note doesn't answer it, reply \"Not found\".\n\nNote:\n",
+        text,
+        question,
+    )
+}
+
+/// Splits a `page_qa_prompt` completion into the answer and the quoted sentence. `None`
+/// when the model found no answer.
+pub fn split_quote(completion: &str) -> Option<(String, Option<String>)> {
+    let (answer, quote) = match completion.split_once("Quote:") {
+        Some((answer, quote)) => (answer.trim(), Some(quote.trim().trim_matches('"').trim())),
+        None => (completion.trim(), None),
+    };
+    if answer.is_empty() || answer.trim_end_matches('.').eq_ignore_ascii_case("not found") {
+        return None;
+    }
+    Some((answer.to_string(), quote.filter(|q| !q.is_empty()).map(str::to_string)))
+}
+
+/// Zero-based indexes of the sources cited as `[n]` in `answer`, in order of first citation.
+/// Numbers outside `1..=source_count` are ignored.
+pub fn cited_sources(answer: &str, source_count: usize) -> Vec<usize> {
+    let mut cited = Vec::new();
+    for part in answer.split('[').skip(1) {
+        let Some((inside, _)) = part.split_once(']') else {
+            continue;
+        };
+        // Also "[1, 3]"
+        for number in inside.split(',').filter_map(|n| n.trim().parse::<usize>().ok()) {
+            if (1..=source_count).contains(&number) && !cited.contains(&(number - 1)) {
+                cited.push(number - 1);
+            }
+        }
+    }
+    cited
+}
+
+/// Alpaca-style framing, which instruction-tuned models generally follow, with `text` as
+/// the document.
+fn instruction(task: &str, text: &str) -> Prompt {
+    Prompt {
+        before: format!("### Instruction:\n{}\n\n### Input:\n", task),
+        document: text.trim().to_string(),
+        after: "\n\n### Response:\n".to_string(),
+        keep_whole: false,
+    }
+}
+
+/// Like `instruction`, but the document is part of the task and `question` is the input.
+fn question_about(task: &str, document: &str, question: &str) -> Prompt {
+    Prompt {
+        before: format!("### Instruction:\n{}", task),
+        document: document.trim().to_string(),
+        after: format!("\n\n### Input:\n{}\n\n### Response:\n", question.trim()),
+        keep_whole: false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prompt_framing() {
+        let prompt = rewrite_prompt("  Ship it fri  ", RewriteStyle::Formal).text();
+        assert!(prompt.starts_with("### Instruction:\nRewrite the following text in a formal tone."));
+        assert!(prompt.ends_with("### Input:\nShip it fri\n\n### Response:\n"));
+
+        let prompt = page_qa_prompt("When?", "We launch in March.");
+        assert_eq!(prompt.document, "We launch in March.");
+        assert!(prompt.after.ends_with("### Input:\nWhen?\n\n### Response:\n"));
+    }
+
+    #[test]
+    fn test_fit_prompt_shortens_only_the_document() {
+        assert_eq!(fit_prompt(&[1, 2], &[10, 11, 12, 13], &[3], 5), Some(vec![1, 2, 10, 11, 3]));
+        assert_eq!(fit_prompt(&[1, 2], &[10], &[3], 5), Some(vec![1, 2, 10, 3]));
+        assert_eq!(fit_prompt(&[1, 2], &[10], &[3], 2), None);
+    }
+
+    #[test]
+    fn test_cited_sources() {
+        assert_eq!(cited_sources("Yes [2], see also [1, 2] and [7]. [x]", 3), vec![1, 0]);
+        assert_eq!(cited_sources("No citations", 3), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn test_translation_chunks() {
+        let text = "# Plan\n\nFirst paragraph.\n\n\n\nSecond paragraph here.\n\nThird.";
+        let texts: Vec<String> = translation_chunks(text, 30).into_iter().map(|chunk| chunk.text).collect();
+        assert_eq!(texts, vec!["# Plan\n\nFirst paragraph.".to_string(), "Second paragraph here.\n\nThird.".to_string()]);
+    }
+
+    #[test]
+    fn test_translation_chunks_split_long_paragraphs() {
+        let chunks = translation_chunks("Intro.\n\nOne two. Three four five six. Seven", 20);
+        let pieces: Vec<(&str, &str)> = chunks.iter().map(|chunk| (chunk.separator, chunk.text.as_str())).collect();
+        assert_eq!(pieces, vec![("", "Intro."), ("\n\n", "One two."), (" ", "Three four five six."), (" ", "Seven")]);
+
+        let chunks = translation_chunks("一二三。四五六。七八", 12);
+        let pieces: Vec<(&str, &str)> = chunks.iter().map(|chunk| (chunk.separator, chunk.text.as_str())).collect();
+        assert_eq!(pieces, vec![("", "一二三。"), ("", "四五六。"), ("", "七八")]);
+    }
+
+    #[test]
+    fn test_split_quote() {
+        assert_eq!(
+            split_quote("In March.\nQuote: \"We launch in March.\""),
+            Some(("In March.".to_string(), Some("We launch in March.".to_string())))
+        );
+        assert_eq!(split_quote("Not found."), None);
+    }
+
+    #[test]
+    fn test_tokenizer_path_falls_back_to_directory() {
+        let path = tokenizer_path(Path::new("/models/mistral-7b.Q4_K_M.gguf"));
+        assert_eq!(path, PathBuf::from("/models/tokenizer.json"));
+    }
+}
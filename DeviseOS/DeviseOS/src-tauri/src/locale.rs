@@ -0,0 +1,173 @@
+use chrono::{DateTime, Local, Utc};
+use crate::{
+    AppError, AppResult,
+    models::{is_valid_language_tag, primary_language_subtag},
+    database::Database,
+};
+
+/// User preference holding the BCP 47 locale used for generated text (exports, digests, reports).
+pub const LOCALE_KEY: &str = "locale";
+pub const DEFAULT_LOCALE: &str = "en-US";
+
+const WORDS_PER_MINUTE: usize = 200;
+/// CJK text is read by character rather than by word.
+const CJK_CHARS_PER_MINUTE: usize = 500;
+
+struct Conventions {
+    date: &'static str,
+    time: &'static str,
+    decimal_separator: &'static str,
+    group_separator: &'static str,
+    reading_time: &'static str, // `{n}` is replaced with the minute count
+}
+
+/// Lookup order: full tag, then primary language subtag.
+const CONVENTIONS: &[(&str, Conventions)] = &[
+    ("en-US", Conventions { date: "%m/%d/%Y", time: "%-I:%M %p", decimal_separator: ".", group_separator: ",", reading_time: "{n} min read" }),
+    ("en", Conventions { date: "%d/%m/%Y", time: "%H:%M", decimal_separator: ".", group_separator: ",", reading_time: "{n} min read" }),
+    ("de", Conventions { date: "%d.%m.%Y", time: "%H:%M", decimal_separator: ",", group_separator: ".", reading_time: "{n} Min. Lesezeit" }),
+    ("fr", Conventions { date: "%d/%m/%Y", time: "%H:%M", decimal_separator: ",", group_separator: "\u{202F}", reading_time: "{n} min de lecture" }),
+    ("es", Conventions { date: "%d/%m/%Y", time: "%H:%M", decimal_separator: ",", group_separator: ".", reading_time: "{n} min de lectura" }),
+    ("it", Conventions { date: "%d/%m/%Y", time: "%H:%M", decimal_separator: ",", group_separator: ".", reading_time: "{n} min di lettura" }),
+    ("pt", Conventions { date: "%d/%m/%Y", time: "%H:%M", decimal_separator: ",", group_separator: ".", reading_time: "{n} min de leitura" }),
+    ("nl", Conventions { date: "%d-%m-%Y", time: "%H:%M", decimal_separator: ",", group_separator: ".", reading_time: "{n} min leestijd" }),
+    ("ru", Conventions { date: "%d.%m.%Y", time: "%H:%M", decimal_separator: ",", group_separator: "\u{00A0}", reading_time: "{n} мин чтения" }),
+    ("ja", Conventions { date: "%Y/%m/%d", time: "%H:%M", decimal_separator: ".", group_separator: ",", reading_time: "{n}分で読めます" }),
+    ("zh", Conventions { date: "%Y/%m/%d", time: "%H:%M", decimal_separator: ".", group_separator: ",", reading_time: "阅读时间 {n} 分钟" }),
+    ("ko", Conventions { date: "%Y. %m. %d.", time: "%H:%M", decimal_separator: ".", group_separator: ",", reading_time: "{n}분 분량" }),
+    ("ar", Conventions { date: "%d/%m/%Y", time: "%H:%M", decimal_separator: "٫", group_separator: "٬", reading_time: "{n} دقيقة قراءة" }),
+    ("he", Conventions { date: "%d.%m.%Y", time: "%H:%M", decimal_separator: ".", group_separator: ",", reading_time: "{n} דקות קריאה" }),
+];
+
+/// Formats dates, counts and reading times for one locale. Unknown locales fall back to
+/// the conventions of their language, then to `en-US`.
+pub struct LocaleFormatter {
+    locale: String,
+    conventions: &'static Conventions,
+}
+
+impl LocaleFormatter {
+    pub fn new(locale: &str) -> Self {
+        let primary = primary_language_subtag(locale);
+        let conventions = CONVENTIONS
+            .iter()
+            .find(|(tag, _)| tag.eq_ignore_ascii_case(locale))
+            .or_else(|| CONVENTIONS.iter().find(|(tag, _)| *tag == primary))
+            .or_else(|| CONVENTIONS.iter().find(|(tag, _)| *tag == DEFAULT_LOCALE))
+            .map(|(_, conventions)| conventions)
+            .expect("default locale conventions are defined");
+
+        Self {
+            locale: locale.to_string(),
+            conventions,
+        }
+    }
+
+    pub fn locale(&self) -> &str {
+        &self.locale
+    }
+
+    /// Date in the user's local time zone.
+    pub fn format_date(&self, date: &DateTime<Utc>) -> String {
+        date.with_timezone(&Local).format(self.conventions.date).to_string()
+    }
+
+    pub fn format_datetime(&self, date: &DateTime<Utc>) -> String {
+        let local = date.with_timezone(&Local);
+        format!("{} {}", local.format(self.conventions.date), local.format(self.conventions.time))
+    }
+
+    pub fn format_count(&self, count: u64) -> String {
+        let digits = count.to_string();
+        let mut grouped = String::new();
+        for (index, digit) in digits.chars().enumerate() {
+            if index > 0 && (digits.len() - index) % 3 == 0 {
+                grouped.push_str(self.conventions.group_separator);
+            }
+            grouped.push(digit);
+        }
+        grouped
+    }
+
+    pub fn format_decimal(&self, value: f64, precision: usize) -> String {
+        let formatted = format!("{:.*}", precision, value.abs());
+        let (integer, fraction) = formatted.split_once('.').unwrap_or((&formatted, ""));
+        let sign = if value < 0.0 && formatted.chars().any(|c| c.is_ascii_digit() && c != '0') { "-" } else { "" };
+        let integer = self.format_count(integer.parse().unwrap_or(0));
+
+        if fraction.is_empty() {
+            format!("{}{}", sign, integer)
+        } else {
+            format!("{}{}{}{}", sign, integer, self.conventions.decimal_separator, fraction)
+        }
+    }
+
+    /// Estimated reading time, at least one minute.
+    pub fn format_reading_time(&self, text: &str) -> String {
+        self.conventions.reading_time.replace("{n}", &reading_minutes(text).to_string())
+    }
+}
+
+pub fn reading_minutes(text: &str) -> usize {
+    let cjk_chars = text.chars().filter(|c| is_cjk(*c)).count();
+    let words = text
+        .split_whitespace()
+        .filter(|word| !word.chars().all(is_cjk))
+        .count();
+
+    let minutes = words as f64 / WORDS_PER_MINUTE as f64 + cjk_chars as f64 / CJK_CHARS_PER_MINUTE as f64;
+    (minutes.ceil() as usize).max(1)
+}
+
+fn is_cjk(c: char) -> bool {
+    matches!(c as u32,
+        0x3040..=0x30FF |   // Hiragana, Katakana
+        0x3400..=0x4DBF |   // CJK Extension A
+        0x4E00..=0x9FFF |   // CJK Unified Ideographs
+        0xAC00..=0xD7AF)    // Hangul syllables
+}
+
+pub async fn get_locale(database: &Database) -> AppResult<String> {
+    Ok(database.get_setting(LOCALE_KEY).await?.unwrap_or_else(|| DEFAULT_LOCALE.to_string()))
+}
+
+/// Formatter for generated output: an explicit per-export locale wins over the preference.
+pub async fn formatter(database: &Database, override_locale: Option<&str>) -> AppResult<LocaleFormatter> {
+    let locale = match override_locale.map(str::trim).filter(|l| !l.is_empty()) {
+        Some(locale) if is_valid_language_tag(locale) => locale.to_string(),
+        Some(locale) => return Err(AppError::InvalidFormat(format!("Invalid locale: {}", locale))),
+        None => get_locale(database).await?,
+    };
+    Ok(LocaleFormatter::new(&locale))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_count_grouping() {
+        assert_eq!(LocaleFormatter::new("en-US").format_count(1234567), "1,234,567");
+        assert_eq!(LocaleFormatter::new("de-DE").format_count(1234567), "1.234.567");
+        assert_eq!(LocaleFormatter::new("fr").format_count(999), "999");
+    }
+
+    #[test]
+    fn test_decimal_separator() {
+        assert_eq!(LocaleFormatter::new("en-US").format_decimal(1234.5, 1), "1,234.5");
+        assert_eq!(LocaleFormatter::new("de").format_decimal(-1234.5, 2), "-1.234,50");
+    }
+
+    #[test]
+    fn test_unknown_locale_falls_back() {
+        assert_eq!(LocaleFormatter::new("xx-YY").format_count(1000), "1,000");
+        assert_eq!(LocaleFormatter::new("en-GB").format_reading_time("word"), "1 min read");
+    }
+
+    #[test]
+    fn test_reading_minutes() {
+        assert_eq!(reading_minutes(""), 1);
+        assert_eq!(reading_minutes(&"word ".repeat(450)), 3);
+        assert_eq!(reading_minutes(&"漢".repeat(1000)), 2);
+    }
+}
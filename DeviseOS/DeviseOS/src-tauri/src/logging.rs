@@ -0,0 +1,115 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::{fmt, layer::SubscriberExt, reload, util::SubscriberInitExt, EnvFilter, Registry};
+use crate::{AppError, AppResult};
+
+pub const LOG_LEVEL_KEY: &str = "log_level";
+const LOG_FILE_PREFIX: &str = "deviseos";
+const LOG_FILE_SUFFIX: &str = "log";
+const MAX_LOG_FILES: usize = 7;
+pub const DEFAULT_LEVEL: &str = "info";
+pub const MAX_TAIL_LINES: usize = 5000;
+
+struct LogController {
+    reload_handle: reload::Handle<EnvFilter, Registry>,
+    level: Mutex<String>,
+    logs_path: PathBuf,
+    // Flushes buffered lines when dropped; kept for the life of the process
+    _guard: WorkerGuard,
+}
+
+static CONTROLLER: OnceLock<LogController> = OnceLock::new();
+
+/// Sends tracing output to stderr and to daily-rotated files in `logs_path`, keeping the
+/// last `MAX_LOG_FILES` days. The level starts from `RUST_LOG` if set.
+pub fn init(logs_path: &Path) -> AppResult<()> {
+    std::fs::create_dir_all(logs_path)?;
+
+    let level = std::env::var("RUST_LOG").unwrap_or_else(|_| DEFAULT_LEVEL.to_string());
+    let filter = EnvFilter::try_new(&level).unwrap_or_else(|_| EnvFilter::new(DEFAULT_LEVEL));
+    let (filter, reload_handle) = reload::Layer::new(filter);
+
+    let appender = RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix(LOG_FILE_PREFIX)
+        .filename_suffix(LOG_FILE_SUFFIX)
+        .max_log_files(MAX_LOG_FILES)
+        .build(logs_path)
+        .map_err(|e| AppError::Configuration(format!("Failed to create log file: {}", e)))?;
+    let (file_writer, guard) = tracing_appender::non_blocking(appender);
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt::layer().with_writer(file_writer).with_ansi(false))
+        .with(fmt::layer().with_writer(std::io::stderr))
+        .try_init()
+        .map_err(|e| AppError::Configuration(format!("Failed to initialize logging: {}", e)))?;
+
+    let _ = CONTROLLER.set(LogController {
+        reload_handle,
+        level: Mutex::new(level),
+        logs_path: logs_path.to_path_buf(),
+        _guard: guard,
+    });
+    Ok(())
+}
+
+fn controller() -> AppResult<&'static LogController> {
+    CONTROLLER
+        .get()
+        .ok_or_else(|| AppError::Configuration("Logging is not initialized".to_string()))
+}
+
+/// Changes the active filter, e.g. `debug` or `info,deviseos_lib=trace`.
+pub fn set_level(level: &str) -> AppResult<()> {
+    let filter = EnvFilter::try_new(level)
+        .map_err(|e| AppError::InvalidFormat(format!("Invalid log level {}: {}", level, e)))?;
+
+    let controller = controller()?;
+    controller
+        .reload_handle
+        .reload(filter)
+        .map_err(|e| AppError::Configuration(format!("Failed to change log level: {}", e)))?;
+    *controller.level.lock().unwrap() = level.to_string();
+
+    tracing::info!("Log level set to {}", level);
+    Ok(())
+}
+
+pub fn get_level() -> AppResult<String> {
+    Ok(controller()?.level.lock().unwrap().clone())
+}
+
+/// Returns up to `lines` of the most recent log output, oldest first, continuing into
+/// earlier rotated files when the current one is short.
+pub fn tail(lines: usize) -> AppResult<Vec<String>> {
+    let lines = lines.min(MAX_TAIL_LINES);
+    let mut files: Vec<PathBuf> = std::fs::read_dir(&controller()?.logs_path)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .map(|name| name.starts_with(LOG_FILE_PREFIX))
+                .unwrap_or(false)
+        })
+        .collect();
+    // Rotated files are date-suffixed, so name order is chronological
+    files.sort();
+
+    let mut collected: Vec<String> = Vec::new();
+    for path in files.iter().rev() {
+        if collected.len() >= lines {
+            break;
+        }
+        let contents = std::fs::read_to_string(path)?;
+        let needed = lines - collected.len();
+        let mut chunk: Vec<String> = contents.lines().rev().take(needed).map(|l| l.to_string()).collect();
+        chunk.reverse();
+        chunk.append(&mut collected);
+        collected = chunk;
+    }
+
+    Ok(collected)
+}
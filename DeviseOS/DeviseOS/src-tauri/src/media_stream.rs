@@ -0,0 +1,225 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Manager};
+use tauri::http::{header, Request, Response, StatusCode};
+use crate::{AppResult, AppState, database::Database};
+
+/// URI scheme attachments are served under: `media://localhost/<id>`, or
+/// `http://media.localhost/<id>` on Windows, as `convertFileSrc(id, "media")` builds it.
+/// Voice recordings are under `voice/<annotation id>`, as WAV.
+pub const SCHEME: &str = "media";
+
+/// Path prefix of voice recordings.
+const VOICE_PREFIX: &str = "voice/";
+
+/// Most bytes sent for one request. Players ask for the rest as they need it.
+const MAX_CHUNK: u64 = 1024 * 1024;
+
+/// Decrypted or decoded bytes `MediaCache` holds at most; the newest file is kept even when
+/// it's larger on its own.
+const CACHE_BYTES: usize = 256 * 1024 * 1024;
+
+/// A file that had to be decrypted or decoded whole before any of it could be served.
+pub struct CachedMedia {
+    mime_type: String,
+    data: Vec<u8>,
+}
+
+/// Recently served files that can't be read a range at a time: encrypted attachments and
+/// voice recordings. A player seeking through one sends many range requests, and each
+/// would otherwise decrypt the whole file again.
+#[derive(Default)]
+pub struct MediaCache {
+    entries: Mutex<VecDeque<(String, Arc<CachedMedia>)>>, // Most recently used first
+}
+
+impl MediaCache {
+    fn get(&self, key: &str) -> Option<Arc<CachedMedia>> {
+        let mut entries = self.entries.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let index = entries.iter().position(|(cached, _)| cached == key)?;
+        let entry = entries.remove(index)?;
+        let media = Arc::clone(&entry.1);
+        entries.push_front(entry);
+        Some(media)
+    }
+
+    fn insert(&self, key: &str, mime_type: String, data: Vec<u8>) -> Arc<CachedMedia> {
+        let media = Arc::new(CachedMedia { mime_type, data });
+        let mut entries = self.entries.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        entries.retain(|(cached, _)| cached != key);
+        entries.push_front((key.to_string(), Arc::clone(&media)));
+        while entries.len() > 1 && entries.iter().map(|(_, media)| media.data.len()).sum::<usize>() > CACHE_BYTES {
+            entries.pop_back();
+        }
+        media
+    }
+
+    /// Drops everything, for when stored audio or the key it's encrypted with changes.
+    pub fn clear(&self) {
+        self.entries.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clear();
+    }
+}
+
+/// Where a response's bytes come from.
+enum Source {
+    /// A plain attachment; SQLite reads just the requested range.
+    Stored { mime_type: String, total: u64 },
+    Cached(Arc<CachedMedia>),
+}
+
+/// `None` when there's nothing at `path`.
+async fn source(cache: &MediaCache, database: &Database, path: &str) -> AppResult<Option<Source>> {
+    if let Some(cached) = cache.get(path) {
+        // Attachments can be deleted while cached; recordings are checked when they're loaded
+        if path.starts_with(VOICE_PREFIX) || database.get_media_size(path).await?.is_some() {
+            return Ok(Some(Source::Cached(cached)));
+        }
+    }
+
+    if let Some(annotation_id) = path.strip_prefix(VOICE_PREFIX) {
+        let Some(audio) = database.get_voice_annotation_audio(annotation_id).await? else {
+            return Ok(None);
+        };
+        return Ok(Some(Source::Cached(cache.insert(path, audio.mime_type, audio.data))));
+    }
+
+    let Some((mime_type, total)) = database.get_media_size(path).await? else {
+        return Ok(None);
+    };
+    if !database.is_encrypted() {
+        return Ok(Some(Source::Stored { mime_type, total }));
+    }
+    let Some(media) = database.get_media_attachment(path).await? else {
+        return Ok(None);
+    };
+    Ok(Some(Source::Cached(cache.insert(path, media.mime_type, media.file_data))))
+}
+
+/// First and last byte, inclusive, of a `Range: bytes=...` header against a file of
+/// `total` bytes. Only the first range of a multi-range request is served. `None` when the
+/// range can't be satisfied.
+pub fn parse_range(header: &str, total: u64) -> Option<(u64, u64)> {
+    let spec = header.trim().strip_prefix("bytes=")?.split(',').next()?.trim();
+    let (start, end) = spec.split_once('-')?;
+    let (start, end) = match (start.trim(), end.trim()) {
+        ("", suffix) => {
+            let suffix: u64 = suffix.parse().ok()?;
+            if suffix == 0 {
+                return None;
+            }
+            (total.saturating_sub(suffix), total.checked_sub(1)?)
+        }
+        (start, "") => (start.parse().ok()?, total.checked_sub(1)?),
+        (start, end) => (start.parse().ok()?, end.parse::<u64>().ok()?.min(total.checked_sub(1)?)),
+    };
+    (start <= end && start < total).then_some((start, end))
+}
+
+/// Serves an attachment's or a recording's bytes for `<audio>`, `<video>` and `<img>`
+/// sources. Range requests get `206 Partial Content` of at most `MAX_CHUNK` bytes.
+pub async fn respond(app: &AppHandle, request: Request<Vec<u8>>) -> Response<Vec<u8>> {
+    let Some(state) = app.try_state::<AppState>() else {
+        return status(StatusCode::SERVICE_UNAVAILABLE);
+    };
+    let path = request.uri().path().trim_matches('/').to_string();
+    let range = request.headers().get(header::RANGE).and_then(|value| value.to_str().ok()).map(str::to_string);
+
+    let database = state.database.read().await;
+    let source = match source(&state.media_cache, &database, &path).await {
+        Ok(Some(source)) => source,
+        Ok(None) => return status(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::warn!("Failed to look up media {}: {}", path, e);
+            return status(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    let (mime_type, total) = match &source {
+        Source::Stored { mime_type, total } => (mime_type.clone(), *total),
+        Source::Cached(media) => (media.mime_type.clone(), media.data.len() as u64),
+    };
+
+    let (start, end, partial) = match range {
+        Some(range) => match parse_range(&range, total) {
+            Some((start, end)) => (start, end.min(start + MAX_CHUNK - 1), true),
+            None => {
+                return Response::builder()
+                    .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                    .header(header::CONTENT_RANGE, format!("bytes */{}", total))
+                    .body(Vec::new())
+                    .unwrap_or_else(|_| status(StatusCode::RANGE_NOT_SATISFIABLE));
+            }
+        },
+        // Whole file, for images and anything else that doesn't ask for a range
+        None => (0, total.saturating_sub(1), false),
+    };
+    let length = if total == 0 { 0 } else { end - start + 1 };
+
+    let data = match source {
+        Source::Cached(media) => media.data[start as usize..(start + length) as usize].to_vec(),
+        Source::Stored { .. } => match database.get_media_range(&path, start, length).await {
+            Ok(Some(data)) => data,
+            Ok(None) => return status(StatusCode::NOT_FOUND),
+            Err(e) => {
+                tracing::warn!("Failed to read media {}: {}", path, e);
+                return status(StatusCode::INTERNAL_SERVER_ERROR);
+            }
+        },
+    };
+
+    let mut response = Response::builder()
+        .header(header::CONTENT_TYPE, mime_type)
+        .header(header::ACCEPT_RANGES, "bytes")
+        .header(header::CONTENT_LENGTH, data.len());
+    response = if partial {
+        response
+            .status(StatusCode::PARTIAL_CONTENT)
+            .header(header::CONTENT_RANGE, format!("bytes {}-{}/{}", start, (start + data.len() as u64).saturating_sub(1), total))
+    } else {
+        response.status(StatusCode::OK)
+    };
+    response.body(data).unwrap_or_else(|_| status(StatusCode::INTERNAL_SERVER_ERROR))
+}
+
+fn status(code: StatusCode) -> Response<Vec<u8>> {
+    let mut response = Response::new(Vec::new());
+    *response.status_mut() = code;
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(parse_range("bytes=0-499", 1000), Some((0, 499)));
+        assert_eq!(parse_range("bytes=500-", 1000), Some((500, 999)));
+        assert_eq!(parse_range("bytes=-200", 1000), Some((800, 999)));
+        assert_eq!(parse_range("bytes=-2000", 1000), Some((0, 999)));
+        assert_eq!(parse_range("bytes=900-5000", 1000), Some((900, 999)));
+        assert_eq!(parse_range("bytes=0-1, 5-9", 1000), Some((0, 1)));
+    }
+
+    #[test]
+    fn test_cache_evicts_least_recently_used() {
+        let cache = MediaCache::default();
+        cache.insert("a", "audio/wav".to_string(), vec![0; CACHE_BYTES / 2]);
+        cache.insert("b", "audio/wav".to_string(), vec![0; CACHE_BYTES / 2]);
+        assert!(cache.get("a").is_some());
+        cache.insert("c", "audio/wav".to_string(), vec![0; 1]);
+        assert!(cache.get("b").is_none());
+        assert!(cache.get("a").is_some() && cache.get("c").is_some());
+
+        cache.insert("d", "audio/wav".to_string(), vec![0; CACHE_BYTES + 1]);
+        assert!(cache.get("d").is_some() && cache.get("a").is_none());
+    }
+
+    #[test]
+    fn test_unsatisfiable_ranges() {
+        assert_eq!(parse_range("bytes=1000-", 1000), None);
+        assert_eq!(parse_range("bytes=5-2", 1000), None);
+        assert_eq!(parse_range("bytes=-0", 1000), None);
+        assert_eq!(parse_range("bytes=0-", 0), None);
+        assert_eq!(parse_range("items=0-1", 1000), None);
+    }
+}
@@ -0,0 +1,192 @@
+use std::collections::BTreeMap;
+use crate::{
+    AppError, AppResult,
+    models::{CreatePageRequest, GenerateMocRequest, MocSource, Page, PageLinkType, SearchFilters, UpdatePageRequest},
+    database::Database,
+    ai::AIService,
+    tags, titles,
+};
+
+/// Most pages a cluster MOC lists besides the page it's built around.
+const MOC_CLUSTER_LIMIT: usize = 30;
+const MOC_TAG: &str = "moc";
+/// Heading for pages without a section, or without tags when grouping a section.
+const UNGROUPED: &str = "Other";
+
+struct MocEntry {
+    title: String,
+    zettel_id: Option<String>,
+    summary: Option<String>,
+}
+
+/// Builds an index page for `request.source` with a link and a one-line summary per page.
+/// A MOC for the same source is refreshed in place rather than created twice; the flag
+/// says whether the page is new.
+pub async fn generate_moc(database: &Database, ai_service: &AIService, request: GenerateMocRequest) -> AppResult<(Page, bool)> {
+    let source = match request.source {
+        MocSource::Tag(tag) => MocSource::Tag(
+            tags::normalize_tag(&tag).ok_or_else(|| AppError::InvalidFormat("Tag cannot be empty".to_string()))?,
+        ),
+        source => source,
+    };
+    let existing = database.find_moc(&source).await?;
+    let created = existing.is_none();
+    let page = write_moc(database, ai_service, &source, existing, request.notebook_id, request.section_id).await?;
+    Ok((page, created))
+}
+
+/// Rebuilds a MOC page from the source it was generated for.
+pub async fn refresh_moc(database: &Database, ai_service: &AIService, page_id: &str) -> AppResult<Page> {
+    let source = database.get_moc_source(page_id).await?
+        .ok_or_else(|| AppError::NotFound(format!("Page {} is not a map of content", page_id)))?;
+    let page = database.get_page(page_id).await?
+        .ok_or_else(|| AppError::NotFound(format!("Page with id {} not found", page_id)))?;
+    write_moc(database, ai_service, &source, Some(page.id), page.notebook_id, page.section_id).await
+}
+
+async fn write_moc(
+    database: &Database,
+    ai_service: &AIService,
+    source: &MocSource,
+    existing: Option<String>,
+    notebook_id: String,
+    section_id: Option<String>,
+) -> AppResult<Page> {
+    let section_titles = database.get_section_titles().await?;
+    let (label, pages) = match source {
+        MocSource::Tag(tag) => {
+            let filters = SearchFilters { tags: Some(vec![tag.clone()]), ..Default::default() };
+            (format!("#{}", tag), pages_in_scope(database, &filters).await?)
+        }
+        MocSource::Section(id) => {
+            let title = section_titles.get(id)
+                .ok_or_else(|| AppError::NotFound(format!("Section with id {} not found", id)))?;
+            let filters = SearchFilters { section_id: Some(id.clone()), ..Default::default() };
+            (title.clone(), pages_in_scope(database, &filters).await?)
+        }
+        MocSource::Cluster(page_id) => {
+            let seed = database.get_page(page_id).await?
+                .ok_or_else(|| AppError::NotFound(format!("Page with id {} not found", page_id)))?;
+            let related = ai_service.related_pages(database, page_id, MOC_CLUSTER_LIMIT).await?
+                .ok_or_else(|| AppError::AIProcessing(format!("Page {} has no embedding yet", page_id)))?;
+            let mut pages = Vec::new();
+            for (id, _) in related {
+                pages.extend(database.get_page(&id).await?);
+            }
+            let label = seed.title.clone();
+            pages.insert(0, seed);
+            (label, pages)
+        }
+    };
+    let pages: Vec<Page> = pages.into_iter().filter(|page| Some(&page.id) != existing.as_ref()).collect();
+
+    let mut groups: BTreeMap<String, Vec<MocEntry>> = BTreeMap::new();
+    for page in &pages {
+        let group = match source {
+            MocSource::Section(_) => page.tags.first().cloned(),
+            _ => page.section_id.as_ref().and_then(|id| section_titles.get(id).cloned()),
+        };
+        let entry = MocEntry {
+            title: page.title.clone(),
+            zettel_id: page.metadata.zettel_id.clone(),
+            summary: titles::summary_line(&page.content),
+        };
+        groups.entry(group.unwrap_or_else(|| UNGROUPED.to_string())).or_default().push(entry);
+    }
+    let mut groups: Vec<(String, Vec<MocEntry>)> = groups
+        .into_iter()
+        .map(|(name, mut entries)| {
+            entries.sort_by(|a, b| a.title.to_lowercase().cmp(&b.title.to_lowercase()));
+            (name, entries)
+        })
+        .collect();
+    // Ungrouped pages go last
+    groups.sort_by_key(|(name, _)| name == UNGROUPED);
+
+    let title = format!("MOC: {}", label);
+    let content = render_moc(&label, &groups);
+    let page_id = match existing {
+        Some(id) => {
+            database.update_page(UpdatePageRequest {
+                id: id.clone(),
+                title: Some(title),
+                content: Some(content),
+                tags: None,
+                order_index: None,
+                language: None,
+            }).await?;
+            id
+        }
+        None => {
+            database.create_page(CreatePageRequest {
+                notebook_id,
+                section_id,
+                parent_page_id: None,
+                title,
+                content,
+                tags: vec![MOC_TAG.to_string()],
+            }).await?.id
+        }
+    };
+
+    let targets: Vec<(String, String)> = pages.iter().map(|page| (page.id.clone(), page.title.clone())).collect();
+    database.replace_links_of_type(&page_id, PageLinkType::Related, &targets).await?;
+    database.save_moc(&page_id, source).await?;
+
+    database.get_page(&page_id).await?
+        .ok_or_else(|| AppError::NotFound(format!("Page with id {} not found", page_id)))
+}
+
+async fn pages_in_scope(database: &Database, filters: &SearchFilters) -> AppResult<Vec<Page>> {
+    let mut pages = Vec::new();
+    for id in database.get_page_ids_in_scope(filters).await? {
+        pages.extend(database.get_page(&id).await?);
+    }
+    Ok(pages)
+}
+
+/// Markdown with one `##` heading per group. Pages with a Zettel ID are linked by ID so
+/// the links survive renames.
+fn render_moc(label: &str, groups: &[(String, Vec<MocEntry>)]) -> String {
+    let mut output = format!("# Map of content: {}\n", label);
+    if groups.is_empty() {
+        output.push_str("\nNo pages yet.\n");
+    }
+    for (name, entries) in groups {
+        output.push_str(&format!("\n## {}\n\n", name));
+        for entry in entries {
+            let link = match &entry.zettel_id {
+                Some(id) => format!("[[{} {}]]", id, entry.title),
+                None => format!("[[{}]]", entry.title),
+            };
+            match &entry.summary {
+                Some(summary) => output.push_str(&format!("- {} — {}\n", link, summary)),
+                None => output.push_str(&format!("- {}\n", link)),
+            }
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_moc() {
+        let groups = vec![
+            ("Reading".to_string(), vec![
+                MocEntry { title: "Atomic Habits".to_string(), zettel_id: Some("202403011430".to_string()), summary: Some("Small wins compound".to_string()) },
+                MocEntry { title: "Deep Work".to_string(), zettel_id: None, summary: None },
+            ]),
+            (UNGROUPED.to_string(), vec![
+                MocEntry { title: "Inbox".to_string(), zettel_id: None, summary: Some("Loose ends".to_string()) },
+            ]),
+        ];
+        assert_eq!(
+            render_moc("#habits", &groups),
+            "# Map of content: #habits\n\n## Reading\n\n- [[202403011430 Atomic Habits]] — Small wins compound\n- [[Deep Work]]\n\n## Other\n\n- [[Inbox]] — Loose ends\n"
+        );
+        assert!(render_moc("#empty", &[]).contains("No pages yet."));
+    }
+}
@@ -0,0 +1,482 @@
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use reqwest::{StatusCode, header};
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Emitter};
+use tokio::io::AsyncWriteExt;
+use crate::{
+    AppError, AppResult,
+    models::{DownloadableModel, EmbeddingModel, ModelDownloadJob, ModelDownloadProgress, ModelKind, WhisperModel},
+};
+
+pub const MODEL_DOWNLOAD_PROGRESS_EVENT: &str = "model-download-progress";
+
+const HF_BASE_URL: &str = "https://huggingface.co";
+const WHISPER_REPO: &str = "ggerganov/whisper.cpp";
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+/// A stalled transfer fails after this long without data; it can be resumed.
+const READ_TIMEOUT: Duration = Duration::from_secs(60);
+/// Free space left over after a download, so it can't fill the disk.
+const DISK_HEADROOM_BYTES: u64 = 256 * 1024 * 1024;
+const PROGRESS_STEP_BYTES: u64 = 1024 * 1024;
+const PART_SUFFIX: &str = ".part";
+
+/// Models being downloaded, so each is fetched by one task at a time.
+static ACTIVE: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// SHA-256 of model files by their URL, fixed here rather than taken from the server that
+/// also serves the file. Files not listed are installed with a warning.
+const PINNED_SHA256: &[(&str, &str)] = &[
+    ("https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-tiny.bin", "be07e048e1e599ad46341c8d2a135645097a538221678b7acdd1b1919c6e1b21"),
+    ("https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-base.bin", "60ed5bc3dd14eea856493d334349b405782ddcaf0028d4b5df4088345fba2efe"),
+    ("https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-small.bin", "1be3a9b2063867b937e64e2ec7483364a79917e157fa98c5d94b5c1fffea987b"),
+    ("https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-medium.bin", "6c14d5adee5f86394037b4e4e8b59f1673b6cee10e3cf0b11bbdbee79c156208"),
+    ("https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-large-v3.bin", "64d182b440b98d5203c4f9bd541544d84c605196c4f7b845dfa11fb23594d1e2"),
+    ("https://huggingface.co/sentence-transformers/all-MiniLM-L6-v2/resolve/main/model.safetensors", "53aa51172d142c89d9012cce15ae4d6cc0ca6895895114379cacb4fab128d9db"),
+];
+
+struct ModelFile {
+    file_name: String, // In `ai_models_path`, as `AIService` loads it
+    url: String,
+}
+
+impl ModelFile {
+    fn new(file_name: String, url: String) -> Self {
+        Self { file_name, url }
+    }
+
+    fn sha256(&self) -> Option<&'static str> {
+        PINNED_SHA256.iter().find(|(url, _)| *url == self.url).map(|(_, sha256)| *sha256)
+    }
+}
+
+struct CatalogueEntry {
+    id: String,
+    kind: ModelKind,
+    name: &'static str,
+    download_bytes: u64,
+    files: Vec<ModelFile>,
+}
+
+pub fn whisper_id(model: &WhisperModel) -> String {
+    format!("whisper-{}", model.model_name())
+}
+
+pub fn embedding_id(model: &EmbeddingModel) -> String {
+    format!("embedding-{}", model.model_name())
+}
+
+/// Whether every file of `model_id` is in `models_path`.
+pub fn is_installed(models_path: &Path, model_id: &str) -> bool {
+    list(models_path).iter().any(|model| model.id == model_id && model.installed)
+}
+
+fn catalogue() -> Vec<CatalogueEntry> {
+    let whisper = [WhisperModel::Tiny, WhisperModel::Base, WhisperModel::Small, WhisperModel::Medium, WhisperModel::Large]
+        .into_iter()
+        .map(|model| CatalogueEntry {
+            id: whisper_id(&model),
+            kind: ModelKind::Whisper,
+            name: model.model_name(),
+            download_bytes: model.model_size(),
+            files: vec![ModelFile::new(format!("whisper-{}.bin", model.model_name()), hf_url(WHISPER_REPO, model.hf_file()))],
+        });
+    let embedding = [EmbeddingModel::MiniLM, EmbeddingModel::BGE, EmbeddingModel::E5]
+        .into_iter()
+        .map(|model| CatalogueEntry {
+            id: embedding_id(&model),
+            kind: ModelKind::Embedding,
+            name: model.model_name(),
+            download_bytes: model.model_size(),
+            files: vec![
+                ModelFile::new(format!("embedding-{}.safetensors", model.model_name()), hf_url(model.hf_repo(), "model.safetensors")),
+                ModelFile::new(format!("tokenizer-{}.json", model.model_name()), hf_url(model.hf_repo(), "tokenizer.json")),
+                ModelFile::new(format!("config-{}.json", model.model_name()), hf_url(model.hf_repo(), "config.json")),
+            ],
+        });
+    whisper.chain(embedding).collect()
+}
+
+fn hf_url(repo: &str, file: &str) -> String {
+    format!("{}/{}/resolve/main/{}", HF_BASE_URL, repo, file)
+}
+
+fn find(model_id: &str) -> AppResult<CatalogueEntry> {
+    catalogue()
+        .into_iter()
+        .find(|entry| entry.id == model_id)
+        .ok_or_else(|| AppError::NotFound(format!("Unknown model: {}", model_id)))
+}
+
+/// Every model that can be downloaded, with what is already in `models_path`.
+pub fn list(models_path: &Path) -> Vec<DownloadableModel> {
+    let active = ACTIVE.lock().unwrap().clone();
+    catalogue()
+        .into_iter()
+        .map(|entry| {
+            let sizes: Vec<Option<u64>> = entry.files.iter().map(|file| file_size(&models_path.join(&file.file_name))).collect();
+            DownloadableModel {
+                downloading: active.contains(&entry.id),
+                installed: sizes.iter().all(Option::is_some),
+                installed_bytes: sizes.iter().flatten().sum(),
+                id: entry.id,
+                kind: entry.kind,
+                name: entry.name.to_string(),
+                download_bytes: entry.download_bytes,
+            }
+        })
+        .collect()
+}
+
+/// Downloads a model in the background and returns immediately. Progress is reported
+/// through `model-download-progress` events; the last one has `finished` or `error` set.
+pub async fn spawn_download(app: AppHandle, models_path: PathBuf, model_id: String) -> AppResult<ModelDownloadJob> {
+    let entry = find(&model_id)?;
+    let guard = ActiveDownload::start(&model_id)?;
+    let job = ModelDownloadJob { model_id: model_id.clone(), files: entry.files.len() };
+
+    tauri::async_runtime::spawn(async move {
+        let _guard = guard;
+        let emit = |progress: ModelDownloadProgress| {
+            let _ = app.emit(MODEL_DOWNLOAD_PROGRESS_EVENT, &progress);
+        };
+        if let Err(e) = download_entry(&models_path, &entry, &emit).await {
+            tracing::warn!("Failed to download model {}: {}", model_id, e);
+            emit(ModelDownloadProgress {
+                model_id,
+                file: String::new(),
+                downloaded_bytes: 0,
+                total_bytes: None,
+                finished: false,
+                error: Some(e.to_string()),
+            });
+        }
+    });
+
+    Ok(job)
+}
+
+/// Downloads a model and waits for it, logging progress instead of emitting events.
+pub async fn download(models_path: &Path, model_id: &str) -> AppResult<()> {
+    let entry = find(model_id)?;
+    let _guard = ActiveDownload::start(model_id)?;
+    download_entry(models_path, &entry, &|progress: ModelDownloadProgress| {
+        tracing::debug!("Downloading {}: {} of {:?} bytes", progress.file, progress.downloaded_bytes, progress.total_bytes);
+    })
+    .await
+}
+
+/// Removes a model's files, and any partial download, from `models_path`. Returns the
+/// bytes freed. A loaded model stays usable until the app restarts.
+pub fn delete(models_path: &Path, model_id: &str) -> AppResult<u64> {
+    let entry = find(model_id)?;
+    if ACTIVE.lock().unwrap().iter().any(|id| id == model_id) {
+        return Err(AppError::InvalidOperation(format!("{} is being downloaded", model_id)));
+    }
+
+    let mut freed = 0;
+    for file in &entry.files {
+        for path in [models_path.join(&file.file_name), part_path(&models_path.join(&file.file_name))] {
+            if let Some(size) = file_size(&path) {
+                std::fs::remove_file(&path)?;
+                freed += size;
+            }
+        }
+    }
+    tracing::info!("Deleted model {} ({} bytes)", model_id, freed);
+    Ok(freed)
+}
+
+/// Marks a model as downloading until dropped.
+struct ActiveDownload(String);
+
+impl ActiveDownload {
+    fn start(model_id: &str) -> AppResult<Self> {
+        let mut active = ACTIVE.lock().unwrap();
+        if active.iter().any(|id| id == model_id) {
+            return Err(AppError::InvalidOperation(format!("{} is already being downloaded", model_id)));
+        }
+        active.push(model_id.to_string());
+        Ok(Self(model_id.to_string()))
+    }
+}
+
+impl Drop for ActiveDownload {
+    fn drop(&mut self) {
+        ACTIVE.lock().unwrap().retain(|id| *id != self.0);
+    }
+}
+
+/// What the Hub reports about a file before downloading it.
+struct RemoteFile {
+    size: Option<u64>,
+}
+
+async fn download_entry(models_path: &Path, entry: &CatalogueEntry, emit: &(dyn Fn(ModelDownloadProgress) + Sync)) -> AppResult<()> {
+    tokio::fs::create_dir_all(models_path).await?;
+    let client = http_client(reqwest::redirect::Policy::default())?;
+    let metadata_client = http_client(reqwest::redirect::Policy::none())?;
+
+    // Look everything up first so a missing file or a full disk fails before any transfer
+    let mut pending = Vec::new();
+    let mut needed = 0;
+    for file in &entry.files {
+        let destination = models_path.join(&file.file_name);
+        let remote = remote_file(&metadata_client, &file.url).await?;
+        // Files of the wrong size, such as mock-mode placeholders, are replaced
+        if file_size(&destination).is_some_and(|size| remote.size.unwrap_or(size) == size) {
+            continue;
+        }
+        let partial = file_size(&part_path(&destination)).unwrap_or(0);
+        needed += remote.size.unwrap_or(entry.download_bytes).saturating_sub(partial);
+        pending.push((file, destination, remote));
+    }
+    ensure_disk_space(models_path, needed)?;
+
+    for (file, destination, remote) in pending {
+        download_file(&client, &entry.id, file, &destination, &remote, emit).await?;
+    }
+
+    emit(ModelDownloadProgress {
+        model_id: entry.id.clone(),
+        file: String::new(),
+        downloaded_bytes: 0,
+        total_bytes: None,
+        finished: true,
+        error: None,
+    });
+    tracing::info!("Model {} is installed in {}", entry.id, models_path.display());
+    Ok(())
+}
+
+fn http_client(redirects: reqwest::redirect::Policy) -> AppResult<reqwest::Client> {
+    reqwest::Client::builder()
+        .connect_timeout(CONNECT_TIMEOUT)
+        .read_timeout(READ_TIMEOUT)
+        .user_agent("DeviseOS")
+        .https_only(true)
+        .redirect(redirects)
+        .build()
+        .map_err(|e| AppError::Network(format!("Failed to create HTTP client: {}", e)))
+}
+
+/// Size from the Hub's response headers. Files stored with Git LFS, which model weights are,
+/// report it in `X-Linked-Size` on the redirect to the CDN.
+async fn remote_file(client: &reqwest::Client, url: &str) -> AppResult<RemoteFile> {
+    let response = client
+        .head(url)
+        .send()
+        .await
+        .map_err(|e| AppError::Network(format!("Failed to look up {}: {}", url, e)))?;
+    if !response.status().is_success() && !response.status().is_redirection() {
+        return Err(AppError::Network(format!("Looking up {} returned {}", url, response.status())));
+    }
+
+    let headers = response.headers();
+    let value = |name: &str| headers.get(name).and_then(|v| v.to_str().ok()).map(str::to_string);
+    let size = value("x-linked-size").or_else(|| value(header::CONTENT_LENGTH.as_str())).and_then(|v| v.parse().ok());
+    Ok(RemoteFile { size })
+}
+
+fn ensure_disk_space(models_path: &Path, needed: u64) -> AppResult<()> {
+    let available = fs2::available_space(models_path)?;
+    if available < needed + DISK_HEADROOM_BYTES {
+        return Err(AppError::InvalidOperation(format!(
+            "Not enough disk space: the download needs {} MB and {} MB are free",
+            (needed + DISK_HEADROOM_BYTES) / (1024 * 1024),
+            available / (1024 * 1024)
+        )));
+    }
+    Ok(())
+}
+
+/// Downloads into `<file>.part`, picking up where an earlier attempt stopped when the server
+/// supports ranges, then checks the pinned SHA-256 and moves the file into place.
+async fn download_file(
+    client: &reqwest::Client,
+    model_id: &str,
+    file: &ModelFile,
+    destination: &Path,
+    remote: &RemoteFile,
+    emit: &(dyn Fn(ModelDownloadProgress) + Sync),
+) -> AppResult<()> {
+    let part = part_path(destination);
+    let mut downloaded = file_size(&part).unwrap_or(0);
+    if remote.size.is_some_and(|size| downloaded > size) {
+        downloaded = 0;
+    }
+
+    let mut request = client.get(&file.url);
+    if downloaded > 0 {
+        request = request.header(header::RANGE, format!("bytes={}-", downloaded));
+    }
+    let mut response = request
+        .send()
+        .await
+        .map_err(|e| AppError::Network(format!("Failed to download {}: {}", file.file_name, e)))?;
+
+    let complete = match response.status() {
+        StatusCode::PARTIAL_CONTENT => false,
+        // The range starts at the end: the earlier attempt got everything
+        StatusCode::RANGE_NOT_SATISFIABLE if remote.size == Some(downloaded) => true,
+        status if status.is_success() => {
+            downloaded = 0;
+            false
+        }
+        status => return Err(AppError::Network(format!("Downloading {} returned {}", file.file_name, status))),
+    };
+
+    if !complete {
+        let mut output = tokio::fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(downloaded > 0)
+            .truncate(downloaded == 0)
+            .open(&part)
+            .await?;
+        let mut progress = ModelDownloadProgress {
+            model_id: model_id.to_string(),
+            file: file.file_name.clone(),
+            downloaded_bytes: downloaded,
+            total_bytes: remote.size,
+            finished: false,
+            error: None,
+        };
+        let mut reported = downloaded;
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .map_err(|e| AppError::Network(format!("Download of {} was interrupted: {}", file.file_name, e)))?
+        {
+            output.write_all(&chunk).await?;
+            downloaded += chunk.len() as u64;
+            if downloaded - reported >= PROGRESS_STEP_BYTES {
+                progress.downloaded_bytes = downloaded;
+                emit(progress.clone());
+                reported = downloaded;
+            }
+        }
+        output.flush().await?;
+        progress.downloaded_bytes = downloaded;
+        emit(progress);
+    }
+
+    if let Some(expected) = remote.size.filter(|size| *size != downloaded) {
+        return Err(AppError::Network(format!("Download of {} stopped at {} of {} bytes", file.file_name, downloaded, expected)));
+    }
+    match file.sha256() {
+        Some(expected) => {
+            let actual = sha256_file(part.clone()).await?;
+            if actual != expected {
+                tokio::fs::remove_file(&part).await?;
+                return Err(AppError::InvalidFormat(format!("{} failed its SHA-256 check", file.file_name)));
+            }
+        }
+        None => tracing::warn!("No SHA-256 published for {}; installed unverified", file.file_name),
+    }
+
+    tokio::fs::rename(&part, destination).await?;
+    Ok(())
+}
+
+async fn sha256_file(path: PathBuf) -> AppResult<String> {
+    tokio::task::spawn_blocking(move || {
+        let mut input = std::fs::File::open(&path)?;
+        let mut hasher = Sha256::new();
+        let mut buffer = vec![0; 1024 * 1024];
+        loop {
+            let read = input.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
+        }
+        Ok(hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect())
+    })
+    .await
+    .map_err(|e| AppError::Unknown(format!("Checksum task failed: {}", e)))?
+}
+
+/// Whether `path` starts like a whisper.cpp GGML model, so a truncated or placeholder file
+/// is caught when models are initialized rather than on first transcription.
+pub fn is_ggml_file(path: &Path) -> AppResult<bool> {
+    let mut magic = [0; 4];
+    let mut file = std::fs::File::open(path)?;
+    if file.read(&mut magic)? < magic.len() {
+        return Ok(false);
+    }
+    // whisper.cpp writes its magic, 0x67676d6c, as a little-endian u32
+    Ok(u32::from_le_bytes(magic) == 0x6767_6d6c)
+}
+
+fn part_path(destination: &Path) -> PathBuf {
+    let mut name = destination.file_name().unwrap_or_default().to_os_string();
+    name.push(PART_SUFFIX);
+    destination.with_file_name(name)
+}
+
+fn file_size(path: &Path) -> Option<u64> {
+    std::fs::metadata(path).ok().filter(|m| m.is_file()).map(|m| m.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pinned_hashes() {
+        for (url, sha256) in PINNED_SHA256 {
+            assert!(catalogue().iter().flat_map(|entry| &entry.files).any(|file| file.url == *url), "{} isn't in the catalogue", url);
+            assert!(sha256.len() == 64 && sha256.chars().all(|c| c.is_ascii_hexdigit() && !c.is_ascii_uppercase()));
+        }
+        let tiny = find(&whisper_id(&WhisperModel::Tiny)).unwrap();
+        assert!(tiny.files[0].sha256().is_some());
+    }
+
+    #[test]
+    fn test_catalogue_matches_loaded_files() {
+        let catalogue = catalogue();
+        let whisper = catalogue.iter().find(|entry| entry.id == whisper_id(&WhisperModel::Large)).unwrap();
+        assert_eq!(whisper.files[0].file_name, "whisper-large.bin");
+        assert_eq!(whisper.files[0].url, "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-large-v3.bin");
+
+        let embedding = find(&embedding_id(&EmbeddingModel::MiniLM)).unwrap();
+        assert_eq!(embedding.files.iter().map(|f| f.file_name.as_str()).collect::<Vec<_>>(), vec!["embedding-all-MiniLM-L6-v2.safetensors", "tokenizer-all-MiniLM-L6-v2.json", "config-all-MiniLM-L6-v2.json"]);
+        assert!(find("whisper-huge").is_err());
+    }
+
+    #[test]
+    fn test_ggml_magic() {
+        let dir = std::env::temp_dir().join(format!("deviseos-ggml-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let model = dir.join("whisper-tiny.bin");
+        std::fs::write(&model, [0x6c, 0x6d, 0x67, 0x67, 1, 2, 3]).unwrap();
+        assert!(is_ggml_file(&model).unwrap());
+        std::fs::write(&model, b"placeholder whisper model").unwrap();
+        assert!(!is_ggml_file(&model).unwrap());
+        std::fs::write(&model, b"gg").unwrap();
+        assert!(!is_ggml_file(&model).unwrap());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_list_and_delete() {
+        let dir = std::env::temp_dir().join(format!("deviseos-models-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("whisper-tiny.bin"), b"weights").unwrap();
+        std::fs::write(dir.join("whisper-base.bin.part"), b"half").unwrap();
+
+        let models = list(&dir);
+        let tiny = models.iter().find(|m| m.id == "whisper-tiny").unwrap();
+        assert!(tiny.installed);
+        assert_eq!(tiny.installed_bytes, 7);
+        assert!(!models.iter().find(|m| m.id == "whisper-base").unwrap().installed);
+
+        assert_eq!(delete(&dir, "whisper-tiny").unwrap(), 7);
+        assert_eq!(delete(&dir, "whisper-base").unwrap(), 4);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+        std::fs::remove_dir(dir).unwrap();
+    }
+}
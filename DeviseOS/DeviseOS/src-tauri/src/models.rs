@@ -1,6 +1,7 @@
 use serde::{Deserialize, Serialize};
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 use uuid::Uuid;
+use crate::{encryption::EncryptionLevel, language, sentiment, sync_protocol::SyncDocumentKind};
 
 // Notebook structure
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -41,6 +42,14 @@ pub struct NotebookMetadata {
     pub total_word_count: u32,
     pub last_accessed: Option<DateTime<Utc>>,
     pub is_pinned: bool,
+    #[serde(default)]
+    pub language: Option<String>, // BCP-47 tag, e.g. "en" or "pt-BR"
+    #[serde(default = "default_true")]
+    pub spell_check: bool,
+    #[serde(default)]
+    pub private: bool, // Kept out of OS-level search
+    #[serde(default)]
+    pub storage_quota: Option<u64>, // Bytes of attachments and recordings its pages may hold
 }
 
 impl Default for NotebookMetadata {
@@ -51,10 +60,18 @@ impl Default for NotebookMetadata {
             total_word_count: 0,
             last_accessed: None,
             is_pinned: false,
+            language: None,
+            spell_check: true,
+            private: false,
+            storage_quota: None,
         }
     }
 }
 
+fn default_true() -> bool {
+    true
+}
+
 // Section structure
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Section {
@@ -98,7 +115,7 @@ pub struct Page {
     pub order_index: i32,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
-    pub voice_annotations: Vec<VoiceAnnotation>,
+    pub voice_annotations: Vec<VoiceAnnotationSummary>, // Audio via get_voice_annotation_audio
     pub media_attachments: Vec<MediaAttachment>,
     pub page_links: Vec<PageLink>,
     pub subpages: Vec<Page>,
@@ -138,6 +155,15 @@ impl Page {
                 reading_time: (word_count / 200).max(1),
                 version: 1,
                 depth_level: if parent_page_id.is_some() { 1 } else { 0 },
+                language: None,
+                direction: TextDirection::detect(&content),
+                detected_language: language::detect(&content).map(|detected| detected.language),
+                sentiment: sentiment::score(&content),
+                zettel_id: None,
+                reviewed_at: None,
+                last_accessed: None,
+                is_pinned: false,
+                daily_date: None,
             },
         }
     }
@@ -145,9 +171,7 @@ impl Page {
     pub fn update_content(&mut self, content: String) {
         self.content = content;
         self.updated_at = Utc::now();
-        self.metadata.word_count = self.content.split_whitespace().count() as u32;
-        self.metadata.character_count = self.content.len() as u32;
-        self.metadata.reading_time = (self.metadata.word_count / 200).max(1);
+        self.metadata.analyze_content(&self.content);
         self.metadata.version += 1;
     }
 }
@@ -159,6 +183,88 @@ pub struct PageMetadata {
     pub reading_time: u32, // minutes
     pub version: u32,
     pub depth_level: u32,
+    #[serde(default)]
+    pub language: Option<String>, // Overrides the notebook language when set
+    #[serde(default)]
+    pub direction: TextDirection, // Computed from content on save
+    #[serde(default)]
+    pub detected_language: Option<String>, // ISO 639-1, computed from content on save
+    #[serde(default)]
+    pub sentiment: Option<f64>, // -1.0 to 1.0, computed from content on save; None without opinion words
+    #[serde(default)]
+    pub zettel_id: Option<String>, // Timestamp-based ID for citation links, e.g. "202403011430"
+    #[serde(default)]
+    pub reviewed_at: Option<DateTime<Utc>>, // Last confirmed still accurate
+    #[serde(default)]
+    pub last_accessed: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub is_pinned: bool,
+    #[serde(default)]
+    pub daily_date: Option<NaiveDate>, // Set on the daily note for this day
+}
+
+impl PageMetadata {
+    /// Recomputes everything derived from the content: word and character counts, reading
+    /// time, direction, detected language and sentiment.
+    pub fn analyze_content(&mut self, content: &str) {
+        self.word_count = content.split_whitespace().count() as u32;
+        self.character_count = content.len() as u32;
+        self.reading_time = (self.word_count / 200).max(1);
+        self.direction = TextDirection::detect(content);
+        self.detected_language = language::detect(content).map(|detected| detected.language);
+        self.sentiment = sentiment::score(content);
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TextDirection {
+    #[default]
+    Ltr,
+    Rtl,
+    Mixed, // Paragraphs differ; renderers decide per paragraph
+}
+
+impl TextDirection {
+    /// Direction of a whole text from its paragraphs. Paragraphs without strong
+    /// characters (numbers, punctuation) don't count.
+    pub fn detect(text: &str) -> Self {
+        let mut directions = text.split("\n\n").filter_map(Self::of_paragraph);
+        match directions.next() {
+            None => Self::Ltr,
+            Some(first) if directions.all(|d| d == first) => first,
+            Some(_) => Self::Mixed,
+        }
+    }
+
+    /// Direction of the first strongly-directional character, like HTML `dir="auto"`.
+    pub fn of_paragraph(text: &str) -> Option<Self> {
+        text.chars().find_map(|c| {
+            if is_rtl_char(c) {
+                Some(Self::Rtl)
+            } else if c.is_alphabetic() {
+                Some(Self::Ltr)
+            } else {
+                None
+            }
+        })
+    }
+
+    pub fn as_html(&self) -> &'static str {
+        match self {
+            Self::Ltr => "ltr",
+            Self::Rtl => "rtl",
+            Self::Mixed => "auto",
+        }
+    }
+}
+
+/// Hebrew, Arabic, Syriac, Thaana, NKo and their presentation forms.
+fn is_rtl_char(c: char) -> bool {
+    matches!(c as u32,
+        0x0590..=0x08FF |
+        0xFB1D..=0xFDFF |
+        0xFE70..=0xFEFF)
 }
 
 // Media attachment structure
@@ -212,6 +318,14 @@ pub struct MediaMetadata {
     pub width: Option<u32>,
     pub height: Option<u32>,
     pub is_embedded: bool,
+    #[serde(default)]
+    pub extracted_text: Option<String>, // OCR or document text, used for search
+    #[serde(default)]
+    pub annotations: Vec<ImageAnnotation>, // Vector overlays drawn on top of the image
+    #[serde(default)]
+    pub original_size: Option<u64>, // Set when the uncompressed upload was kept
+    #[serde(default)]
+    pub warning: Option<String>, // Set when the upload was converted to another format, e.g. to remove metadata
 }
 
 impl Default for MediaMetadata {
@@ -222,10 +336,58 @@ impl Default for MediaMetadata {
             width: None,
             height: None,
             is_embedded: true,
+            extracted_text: None,
+            annotations: Vec::new(),
+            original_size: None,
+            warning: None,
         }
     }
 }
 
+// Overlay shapes are stored as vectors in image pixel coordinates, so the original image stays untouched
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImageAnnotation {
+    pub id: String,
+    pub shape: AnnotationShape,
+    pub color: String,
+    pub stroke_width: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum AnnotationShape {
+    Arrow { from: (f32, f32), to: (f32, f32) },
+    Rectangle { x: f32, y: f32, width: f32, height: f32 },
+    Ellipse { x: f32, y: f32, width: f32, height: f32 },
+    Freehand { points: Vec<(f32, f32)> },
+    Text { x: f32, y: f32, text: String, font_size: f32 },
+}
+
+// Stylus input stored as a media attachment with mime type `INK_MIME_TYPE`
+pub const INK_MIME_TYPE: &str = "application/vnd.deviseos.ink+json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InkDrawing {
+    pub width: f32,
+    pub height: f32,
+    pub strokes: Vec<InkStroke>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InkStroke {
+    pub points: Vec<InkPoint>,
+    #[serde(default)]
+    pub width: Option<f32>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct InkPoint {
+    pub x: f32,
+    pub y: f32,
+    #[serde(default)]
+    pub pressure: Option<f32>,
+}
+
 // Page link structure
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PageLink {
@@ -237,12 +399,36 @@ pub struct PageLink {
     pub created_at: DateTime<Utc>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum PageLinkType {
     Manual,      // User-created link
     Auto,        // AI-detected relationship
     Reference,   // Citation or reference
     Related,     // Suggested related content
+    Wiki,        // `[[Title]]` in the source page's content
+}
+
+impl PageLinkType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PageLinkType::Manual => "manual",
+            PageLinkType::Auto => "auto",
+            PageLinkType::Reference => "reference",
+            PageLinkType::Related => "related",
+            PageLinkType::Wiki => "wiki",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "manual" => Some(PageLinkType::Manual),
+            "auto" => Some(PageLinkType::Auto),
+            "reference" => Some(PageLinkType::Reference),
+            "related" => Some(PageLinkType::Related),
+            "wiki" => Some(PageLinkType::Wiki),
+            _ => None,
+        }
+    }
 }
 
 impl PageLink {
@@ -313,7 +499,9 @@ pub struct NoteMetadata {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VoiceAnnotation {
     pub id: String,
-    pub note_id: String,
+    pub note_id: Option<String>,
+    #[serde(default)]
+    pub page_id: Option<String>,
     pub audio_data: Vec<u8>,
     pub transcription: String,
     pub timestamp: DateTime<Utc>,
@@ -321,11 +509,57 @@ pub struct VoiceAnnotation {
     pub metadata: VoiceMetadata,
 }
 
+// An annotation without its audio, for listing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VoiceAnnotationSummary {
+    pub id: String,
+    pub note_id: Option<String>,
+    pub page_id: Option<String>,
+    pub transcription: String,
+    pub timestamp: DateTime<Utc>,
+    pub duration: f64, // seconds
+    pub metadata: VoiceMetadata,
+    pub words: Vec<TranscribedWord>, // The whole transcript, timed, so clicking a word can seek
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Waveform {
+    pub annotation_id: String,
+    pub duration: f64, // seconds
+    pub peaks: Vec<f32>,
+}
+
+// Outcome of re-encoding recordings stored before voice annotations were kept as Opus
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct VoiceRecompressReport {
+    pub recompressed: u32,
+    pub skipped: u32, // Not mono PCM at a rate Opus takes, or failed to encode
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+    pub bytes_saved: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdateVoiceAnnotationRequest {
+    pub id: String,
+    pub transcription: String,
+}
+
+// A recording's audio on its own, ready for an <audio> element
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VoiceAudio {
+    pub annotation_id: String,
+    pub mime_type: String,
+    pub duration: f64, // seconds
+    pub data: Vec<u8>,
+}
+
 impl VoiceAnnotation {
     pub fn new(note_id: String, audio_data: Vec<u8>, transcription: String, duration: f64) -> Self {
         Self {
             id: Uuid::new_v4().to_string(),
-            note_id,
+            note_id: Some(note_id),
+            page_id: None,
             audio_data,
             transcription,
             timestamp: Utc::now(),
@@ -341,6 +575,32 @@ pub struct VoiceMetadata {
     pub channels: u32,
     pub format: String,
     pub quality: f32, // 0.0 to 1.0
+    #[serde(default)]
+    pub language: Option<String>, // ISO 639-1, given or detected when transcribed
+    #[serde(default)]
+    pub segments: Vec<VoiceSegment>, // Speech found by voice-activity detection, with its text
+    #[serde(default)]
+    pub edited: bool, // Transcription corrected by hand, so transcribing again won't replace it
+    #[serde(default)]
+    pub peaks: Vec<f32>, // Downsampled amplitude, 0 to 1, for drawing the waveform
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VoiceSegment {
+    pub start_secs: f64,
+    pub end_secs: f64,
+    pub text: String,
+    #[serde(default)]
+    pub words: Vec<TranscribedWord>, // Timed from the start of the recording
+}
+
+// A transcribed word, so clicking it can seek the audio to where it was spoken
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TranscribedWord {
+    pub text: String,
+    pub start_secs: f64,
+    pub end_secs: f64,
+    pub confidence: f32, // 0.0 to 1.0
 }
 
 impl Default for VoiceMetadata {
@@ -350,10 +610,22 @@ impl Default for VoiceMetadata {
             channels: 1,
             format: "wav".to_string(),
             quality: 0.8,
+            language: None,
+            segments: Vec::new(),
+            edited: false,
+            peaks: Vec::new(),
         }
     }
 }
 
+impl VoiceMetadata {
+    /// Every timed word across the segments, in order. Empty when the transcription was
+    /// corrected by hand or made before word timings were kept.
+    pub fn words(&self) -> Vec<TranscribedWord> {
+        self.segments.iter().flat_map(|segment| segment.words.iter().cloned()).collect()
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Tag {
     pub id: String,
@@ -379,12 +651,87 @@ impl Tag {
     }
 }
 
+// Which table an embedding belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EmbeddingOwner {
+    Note,
+    Page,
+}
+
+impl EmbeddingOwner {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EmbeddingOwner::Note => "note",
+            EmbeddingOwner::Page => "page",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "note" => Some(EmbeddingOwner::Note),
+            "page" => Some(EmbeddingOwner::Page),
+            _ => None,
+        }
+    }
+}
+
+// Exactly one of `note` and `page` is set
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SearchResult {
-    pub note: Note,
+    pub note: Option<Note>,
+    pub page: Option<Page>,
     pub relevance_score: f64,
     pub matched_terms: Vec<String>,
     pub snippet: String,
+    pub match_confidence: Option<f64>, // 1.0 for exact term matches, lower for typo matches, None if no term matched
+    pub highlights: Vec<HighlightSpan>,
+}
+
+// Byte range of a matched word within a note's title or content
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HighlightSpan {
+    pub field: SearchMatchField,
+    pub start: usize,
+    pub end: usize,
+}
+
+// One page of search results; pass `next_cursor` back to continue after the last item
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchPage<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct HybridSearchWeights {
+    pub keyword: f64,
+    pub semantic: f64,
+}
+
+impl Default for HybridSearchWeights {
+    fn default() -> Self {
+        Self {
+            keyword: 0.5,
+            semantic: 0.5,
+        }
+    }
+}
+
+impl HybridSearchWeights {
+    /// Scales the weights to sum to 1, falling back to the defaults if they can't be.
+    pub fn normalized(&self) -> Self {
+        let keyword = self.keyword.max(0.0);
+        let semantic = self.semantic.max(0.0);
+        let total = keyword + semantic;
+        if total <= 0.0 || !total.is_finite() {
+            return Self::default();
+        }
+        Self {
+            keyword: keyword / total,
+            semantic: semantic / total,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -402,6 +749,8 @@ pub struct ExportFormat {
     pub include_metadata: bool,
     pub include_voice_annotations: bool,
     pub include_tags: bool,
+    #[serde(default)]
+    pub translate_to: Option<String>, // BCP-47 tag; writes a translated copy, the page is unchanged
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -413,6 +762,30 @@ pub enum ExportType {
     TXT,
 }
 
+/// Whether AI features run real models or simulated ones.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AIMode {
+    #[default]
+    Real, // Needs model files; features without a working model fail instead of faking output
+    Mock, // Deterministic placeholder transcriptions and embeddings, for tests and demos
+}
+
+impl AIMode {
+    pub const ENV_VAR: &'static str = "DEVISEOS_AI_MODE";
+
+    /// `DEVISEOS_AI_MODE=mock` or `real` when set; otherwise mock in debug builds so tests
+    /// run without model files, real in release builds.
+    pub fn from_env() -> Self {
+        match std::env::var(Self::ENV_VAR).map(|mode| mode.trim().to_lowercase()).as_deref() {
+            Ok("mock") => AIMode::Mock,
+            Ok("real") => AIMode::Real,
+            _ if cfg!(debug_assertions) => AIMode::Mock,
+            _ => AIMode::Real,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppConfig {
     pub database_path: std::path::PathBuf,
@@ -420,10 +793,136 @@ pub struct AppConfig {
     pub encryption_key_path: std::path::PathBuf,
     pub ai_models_path: std::path::PathBuf,
     pub backup_path: std::path::PathBuf,
+    pub scripts_path: std::path::PathBuf,
+    pub crash_reports_path: std::path::PathBuf,
+    pub logs_path: std::path::PathBuf,
     pub whisper_model: WhisperModel,
     pub embedding_model: EmbeddingModel,
+    pub llm_model_path: std::path::PathBuf, // GGUF model, used when the file exists
+    #[serde(default)]
+    pub ai_mode: AIMode,
+    #[serde(default)]
+    pub sqlite: SqliteConfig,
+    #[serde(default)]
+    pub images: ImageConfig,
     pub max_file_size: u64, // bytes
     pub auto_backup_interval: u64, // minutes
+    pub encryption_level: EncryptionLevel,
+    pub cloud_ai_enabled: bool, // AI features may reach the network; today only model downloads do
+    pub sync_enabled: bool,
+    pub update_checks_enabled: bool,
+    pub audit_log_enabled: bool,
+    pub policy: Option<CompliancePolicy>, // Set when a policies.json was found
+}
+
+// SQLite connection settings, applied to every pooled connection. WAL lets reads continue
+// while background jobs write.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SqliteConfig {
+    pub journal_mode: SqliteJournalMode,
+    pub synchronous: SqliteSynchronous,
+    pub busy_timeout_ms: u64, // How long a locked database is retried before SQLITE_BUSY
+    pub cache_size_kib: u32,  // Page cache per connection
+    pub sqlcipher: bool, // Create new databases encrypted as a whole; existing ones need `encrypt_database_file`
+}
+
+impl Default for SqliteConfig {
+    fn default() -> Self {
+        Self {
+            journal_mode: SqliteJournalMode::Wal,
+            synchronous: SqliteSynchronous::Normal, // Durable with WAL except for the last commits on power loss
+            busy_timeout_ms: 5000,
+            cache_size_kib: 16 * 1024,
+            sqlcipher: false,
+        }
+    }
+}
+
+// How uploaded photos are shrunk before they're stored
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ImageConfig {
+    pub compress: bool,
+    pub max_dimension: u32, // Longest side in pixels; larger images are scaled down
+    pub jpeg_quality: u8,   // 1-100
+    pub keep_original: bool, // Store the upload as it was alongside the compressed copy
+}
+
+impl Default for ImageConfig {
+    fn default() -> Self {
+        Self {
+            compress: true,
+            max_dimension: 2560,
+            jpeg_quality: 85,
+            keep_original: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SqliteJournalMode {
+    Wal,
+    Delete,
+    Truncate,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SqliteSynchronous {
+    Off,
+    Normal,
+    Full,
+}
+
+// Administrator-managed policy loaded from policies.json; overrides user configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompliancePolicy {
+    #[serde(default)]
+    pub compliance_mode: bool,
+    #[serde(default)]
+    pub min_encryption_level: Option<EncryptionLevel>,
+    #[serde(default)]
+    pub disable_cloud_ai: bool,
+    #[serde(default)]
+    pub disable_sync: bool,
+    #[serde(default)]
+    pub disable_update_checks: bool,
+    #[serde(default)]
+    pub require_audit_log: bool,
+    #[serde(default)]
+    pub locked_settings: Vec<String>, // Setting keys users may not change
+}
+
+impl CompliancePolicy {
+    /// Compliance mode implies every restriction, regardless of the individual flags.
+    pub fn effective(&self) -> Self {
+        if !self.compliance_mode {
+            return self.clone();
+        }
+        Self {
+            compliance_mode: true,
+            min_encryption_level: Some(self.min_encryption_level.unwrap_or(EncryptionLevel::Standard).max(EncryptionLevel::Standard)),
+            disable_cloud_ai: true,
+            disable_sync: true,
+            disable_update_checks: true,
+            require_audit_log: true,
+            locked_settings: self.locked_settings.clone(),
+        }
+    }
+
+    pub fn is_setting_locked(&self, key: &str) -> bool {
+        self.locked_settings.iter().any(|locked| locked == key)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditLogEntry {
+    pub id: String,
+    pub action: String,
+    pub target: Option<String>,
+    pub created_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -455,6 +954,17 @@ impl WhisperModel {
             WhisperModel::Large => "large",
         }
     }
+
+    /// The GGML weights in the whisper.cpp repository on Hugging Face.
+    pub fn hf_file(&self) -> &'static str {
+        match self {
+            WhisperModel::Tiny => "ggml-tiny.bin",
+            WhisperModel::Base => "ggml-base.bin",
+            WhisperModel::Small => "ggml-small.bin",
+            WhisperModel::Medium => "ggml-medium.bin",
+            WhisperModel::Large => "ggml-large-v3.bin",
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -473,6 +983,23 @@ impl EmbeddingModel {
         }
     }
 
+    /// Hugging Face repository with the model's `model.safetensors` and `tokenizer.json`.
+    pub fn hf_repo(&self) -> &'static str {
+        match self {
+            EmbeddingModel::MiniLM => "sentence-transformers/all-MiniLM-L6-v2",
+            EmbeddingModel::BGE => "BAAI/bge-small-en-v1.5",
+            EmbeddingModel::E5 => "intfloat/multilingual-e5-small",
+        }
+    }
+
+    pub fn model_size(&self) -> u64 {
+        match self {
+            EmbeddingModel::MiniLM => 91_000_000, // ~91MB
+            EmbeddingModel::BGE => 134_000_000,   // ~134MB
+            EmbeddingModel::E5 => 471_000_000,    // ~471MB
+        }
+    }
+
     pub fn embedding_dimension(&self) -> usize {
         match self {
             EmbeddingModel::MiniLM => 384,
@@ -494,14 +1021,88 @@ impl Default for AppConfig {
             encryption_key_path: data_dir.join("encryption.key"),
             ai_models_path: data_dir.join("models"),
             backup_path: data_dir.join("backups"),
+            scripts_path: data_dir.join("scripts"),
+            crash_reports_path: data_dir.join("crash_reports"),
+            logs_path: data_dir.join("logs"),
             whisper_model: WhisperModel::Base,
             embedding_model: EmbeddingModel::MiniLM,
+            llm_model_path: data_dir.join("models").join("llm.gguf"),
+            ai_mode: AIMode::from_env(),
+            sqlite: SqliteConfig::default(),
+            images: ImageConfig::default(),
             max_file_size: 100 * 1024 * 1024, // 100MB
             auto_backup_interval: 60, // 1 hour
+            encryption_level: EncryptionLevel::Standard,
+            cloud_ai_enabled: true, // A policy can turn these off
+            sync_enabled: true, // Nothing syncs until a folder is set
+            update_checks_enabled: true,
+            audit_log_enabled: false,
+            policy: None,
+        }
+    }
+}
+
+// Effective language settings for a page, resolved from page override -> notebook -> default
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LanguageSettings {
+    pub language: String,
+    pub source: LanguageSource,
+    pub spell_check_enabled: bool,
+    pub whisper_language: String,      // ISO 639-1 code expected by Whisper
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum LanguageSource {
+    Page,
+    Notebook,
+    Detected, // From the page content, when neither page nor notebook sets one
+    Default,
+}
+
+pub const DEFAULT_LANGUAGE: &str = "en";
+
+impl LanguageSettings {
+    pub fn resolve(page: Option<&PageMetadata>, notebook: Option<&NotebookMetadata>) -> Self {
+        let (language, source) = match (
+            page.and_then(|p| p.language.clone()),
+            notebook.and_then(|n| n.language.clone()),
+        ) {
+            (Some(language), _) => (language, LanguageSource::Page),
+            (None, Some(language)) => (language, LanguageSource::Notebook),
+            (None, None) => match page.and_then(|p| p.detected_language.clone()) {
+                Some(language) => (language, LanguageSource::Detected),
+                None => (DEFAULT_LANGUAGE.to_string(), LanguageSource::Default),
+            },
+        };
+        Self {
+            whisper_language: primary_language_subtag(&language),
+            spell_check_enabled: notebook.map(|n| n.spell_check).unwrap_or(true),
+            language,
+            source,
         }
     }
 }
 
+/// "pt-BR" -> "pt", "zh_Hant" -> "zh"
+pub fn primary_language_subtag(language: &str) -> String {
+    language
+        .split(|c| c == '-' || c == '_')
+        .next()
+        .unwrap_or(DEFAULT_LANGUAGE)
+        .to_lowercase()
+}
+
+/// Accepts simple BCP-47 tags like "en", "de-CH" or "zh-Hant-TW".
+pub fn is_valid_language_tag(language: &str) -> bool {
+    let mut parts = language.split('-');
+    let primary_ok = parts
+        .next()
+        .map(|p| (2..=3).contains(&p.len()) && p.chars().all(|c| c.is_ascii_alphabetic()))
+        .unwrap_or(false);
+
+    primary_ok && parts.all(|p| (2..=8).contains(&p.len()) && p.chars().all(|c| c.is_ascii_alphanumeric()))
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SyncStatus {
     pub last_sync: Option<DateTime<Utc>>,
@@ -524,9 +1125,53 @@ pub struct AppStats {
     pub total_voice_annotations: u32,
     pub total_tags: u32,
     pub database_size: u64,
+    pub free_bytes: u64, // Left by deletions; `run_maintenance` reclaims it
     pub oldest_note: Option<DateTime<Utc>>,
     pub newest_note: Option<DateTime<Utc>>,
     pub most_used_tags: Vec<(String, u32)>,
+    pub table_sizes: Vec<TableSize>,
+    pub last_maintenance: Option<DateTime<Utc>>,
+}
+
+// Storage usage models
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StorageUsage {
+    pub total_bytes: u64,
+    pub by_notebook: Vec<NotebookStorage>,
+    pub by_media_type: Vec<MediaTypeStorage>,
+    pub unfiled_bytes: u64, // Attached to quick notes rather than pages
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotebookStorage {
+    pub notebook_id: String,
+    pub title: String,
+    pub bytes: u64,
+    pub quota: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MediaTypeStorage {
+    pub media_type: String, // "image", "audio", "video", ... or "voice" for recordings
+    pub count: u64,
+    pub bytes: u64,
+}
+
+// Database maintenance models
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TableSize {
+    pub table: String,
+    pub bytes: u64, // Including the table's indexes
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MaintenanceReport {
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+    pub reclaimed_bytes: u64,
+    pub table_sizes: Vec<TableSize>,
+    pub duration_ms: u64,
+    pub ran_at: DateTime<Utc>,
 }
 
 // Request/Response types for Tauri commands
@@ -550,12 +1195,75 @@ pub struct SearchRequest {
     pub query: String,
     pub limit: Option<usize>,
     pub offset: Option<usize>,
+    #[serde(default)]
+    pub cursor: Option<String>, // From a previous `SearchPage::next_cursor`
+    #[serde(flatten)]
+    pub filters: SearchFilters,
+}
+
+/// Optional constraints shared by note and page search. Every tag listed must be present.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SearchFilters {
+    #[serde(default)]
+    pub tags: Option<Vec<String>>,
+    #[serde(default)]
+    pub notebook_id: Option<String>,
+    #[serde(default)]
+    pub section_id: Option<String>,
+    #[serde(default)]
+    pub created_after: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub created_before: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub updated_after: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub updated_before: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SearchMatchField {
+    Title,
+    Content,
+    Transcription,  // Voice annotation transcript
+    AttachmentText, // OCR, PDF or handwriting text extracted from a media attachment
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NoteSearchMatch {
+    pub note: Note,
+    pub matched_fields: Vec<SearchMatchField>, // Empty when the query was empty
+    pub highlights: Vec<HighlightSpan>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PageSearchMatch {
+    pub page: Page,
+    pub matched_fields: Vec<SearchMatchField>,
+}
+
+impl SearchFilters {
+    /// Notes predate notebooks, so any notebook or section constraint excludes them.
+    pub fn excludes_notes(&self) -> bool {
+        self.notebook_id.is_some() || self.section_id.is_some()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HybridSearchRequest {
+    pub query: String,
+    pub limit: Option<usize>,
+    pub weights: Option<HybridSearchWeights>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct VoiceAnnotationRequest {
-    pub note_id: String,
+    #[serde(default)]
+    pub note_id: Option<String>,
+    #[serde(default)]
+    pub page_id: Option<String>, // Exactly one of the two
     pub audio_data: Vec<u8>,
+    #[serde(default)]
+    pub format: Option<String>, // e.g. "mp3"; sniffed from the bytes when omitted, or 16 kHz mono PCM without a container
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -579,6 +1287,12 @@ pub struct UpdateNotebookRequest {
     pub description: Option<String>,
     pub color: Option<String>,
     pub order_index: Option<i32>,
+    #[serde(default)]
+    pub language: Option<String>, // Empty string clears the language
+    #[serde(default)]
+    pub spell_check: Option<bool>,
+    #[serde(default)]
+    pub private: Option<bool>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -613,6 +1327,8 @@ pub struct UpdatePageRequest {
     pub content: Option<String>,
     pub tags: Option<Vec<String>>,
     pub order_index: Option<i32>,
+    #[serde(default)]
+    pub language: Option<String>, // Empty string removes the page override
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -624,6 +1340,28 @@ pub struct MovePageRequest {
     pub new_order_index: Option<i32>,
 }
 
+// Bulk operation models
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BulkPageOperation {
+    AddTags { tags: Vec<String> },
+    RemoveTags { tags: Vec<String> },
+    Move { notebook_id: String, section_id: Option<String> }, // No section moves to the notebook root
+    Delete, // Into the trash, with subpages
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BulkUpdatePagesRequest {
+    pub page_ids: Vec<String>,
+    pub operation: BulkPageOperation,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BulkUpdateResult {
+    pub updated: Vec<String>,
+    pub missing: Vec<String>, // Ids that weren't found; the rest were still updated
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ReorderItemsRequest {
     pub items: Vec<ReorderItem>,
@@ -659,6 +1397,10 @@ pub struct NotebookSearchRequest {
     pub query: String,
     pub include_sections: Option<Vec<String>>,
     pub limit: Option<usize>,
+    #[serde(default)]
+    pub cursor: Option<String>, // From a previous `SearchPage::next_cursor`
+    #[serde(flatten)]
+    pub filters: SearchFilters,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -697,12 +1439,1665 @@ pub struct NotebookStats {
     pub last_activity: Option<DateTime<Utc>>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VaultStats {
+    pub notes: u64,
+    pub notebooks: u64,
+    pub sections: u64,
+    pub pages: u64,
+    pub media_attachments: u64,
+    pub media_bytes: u64,
+    pub voice_annotations: u64,
+    pub embeddings: u64,
+    pub tags: u64,
+    pub database_bytes: u64,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct PageRelationships {
     pub page_id: String,
     pub outgoing_links: Vec<PageLink>,
     pub incoming_links: Vec<PageLink>,
-    pub related_pages: Vec<Page>,
+    pub related_pages: Vec<Page>, // Targets of the page's suggested `Auto` links
     pub parent_page: Option<Page>,
     pub child_pages: Vec<Page>,
-}
\ No newline at end of file
+    pub link_type_counts: Vec<LinkTypeCount>, // Links in and out by type, before filtering
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LinkTypeCount {
+    pub link_type: PageLinkType,
+    pub incoming: usize,
+    pub outgoing: usize,
+}
+
+// A link into a page, with where it sits in the linking page
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Backlink {
+    pub link_id: String,
+    pub source_page_id: String,
+    pub source_title: String,
+    pub notebook_id: String,
+    pub link_text: String,
+    pub link_type: PageLinkType,
+    pub context: Option<String>, // None when the link text isn't in the source content
+}
+
+// A page's content with its `![[embeds]]` replaced by what they embed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResolvedContent {
+    pub page_id: String,
+    pub content: String,
+    pub embedded_page_ids: Vec<String>,
+    pub cycles: Vec<String>,  // Titles left as links because they'd embed themselves
+    pub missing: Vec<String>, // Titles, or `Title#fragment`, that didn't resolve
+    pub truncated: bool,      // Embeds past the count or size limits were left as links
+}
+
+// Automation structures
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Automation {
+    pub id: String,
+    pub name: String,
+    pub enabled: bool,
+    pub trigger: AutomationTrigger,
+    pub action: AutomationAction,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Automation {
+    pub fn new(name: String, trigger: AutomationTrigger, action: AutomationAction) -> Self {
+        let now = Utc::now();
+
+        Self {
+            id: Uuid::new_v4().to_string(),
+            name,
+            enabled: true,
+            trigger,
+            action,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum AutomationTrigger {
+    PageCreated { tag: Option<String> }, // Any new page, or only pages carrying this tag
+}
+
+impl AutomationTrigger {
+    pub fn matches(&self, event: &AutomationEvent) -> bool {
+        match (self, event) {
+            (AutomationTrigger::PageCreated { tag }, AutomationEvent::PageCreated { tags, .. }) => {
+                match tag {
+                    Some(tag) => tags.iter().any(|t| t.eq_ignore_ascii_case(tag)),
+                    None => true,
+                }
+            }
+            (AutomationTrigger::PageCreated { .. }, _) => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum AutomationAction {
+    RunScript {
+        #[serde(alias = "path")]
+        script: String, // File name in the scripts directory
+        args: Vec<String>,
+    },
+    Webhook {
+        url: String,
+    },
+    CreatePage {
+        notebook_id: String,
+        section_id: Option<String>,
+        title_template: String,   // Supports {{title}} and {{date}} placeholders
+        content_template: String,
+    },
+}
+
+// Events emitted by the app that automations can react to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum AutomationEvent {
+    PageCreated {
+        page_id: String,
+        notebook_id: String,
+        title: String,
+        tags: Vec<String>,
+    },
+    TaskCompleted {
+        page_id: String,
+        notebook_id: String,
+        title: String, // Of the page
+        task: String,
+    },
+}
+
+impl AutomationEvent {
+    pub fn page_created(page: &Page) -> Self {
+        AutomationEvent::PageCreated {
+            page_id: page.id.clone(),
+            notebook_id: page.notebook_id.clone(),
+            title: page.title.clone(),
+            tags: page.tags.clone(),
+        }
+    }
+
+    pub fn task_completed(page: &Page, task: String) -> Self {
+        AutomationEvent::TaskCompleted {
+            page_id: page.id.clone(),
+            notebook_id: page.notebook_id.clone(),
+            title: page.title.clone(),
+            task,
+        }
+    }
+
+    pub fn title(&self) -> &str {
+        match self {
+            AutomationEvent::PageCreated { title, .. } | AutomationEvent::TaskCompleted { title, .. } => title,
+        }
+    }
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AutomationEvent::PageCreated { .. } => "page_created",
+            AutomationEvent::TaskCompleted { .. } => "task_completed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AutomationRun {
+    pub id: String,
+    pub automation_id: String,
+    pub event: AutomationEvent,
+    pub success: bool,
+    pub output: Option<String>,
+    pub executed_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateAutomationRequest {
+    pub name: String,
+    pub trigger: AutomationTrigger,
+    pub action: AutomationAction,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdateAutomationRequest {
+    pub id: String,
+    pub name: Option<String>,
+    pub enabled: Option<bool>,
+    pub trigger: Option<AutomationTrigger>,
+    pub action: Option<AutomationAction>,
+}
+
+// MQTT integration settings, stored as JSON under the `mqtt_config` setting
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MqttConfig {
+    pub enabled: bool,
+    pub host: String,
+    pub port: u16,
+    pub client_id: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub topic_prefix: String,
+    pub events: Vec<String>, // Event kinds to publish: "page_created", "task_completed"
+}
+
+impl Default for MqttConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            host: "localhost".to_string(),
+            port: 1883,
+            client_id: "deviseos".to_string(),
+            username: None,
+            password: None,
+            topic_prefix: "deviseos".to_string(),
+            events: vec!["page_created".to_string()],
+        }
+    }
+}
+
+// Cloud sync settings, stored as JSON under the `sync_config` setting
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SyncConfig {
+    pub folder: Option<std::path::PathBuf>, // Kept in step between devices by a cloud drive
+}
+
+// Outcome of one push to or pull from the sync folder
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncReport {
+    pub documents: usize, // Written to the other side, deletions included
+    pub deleted: usize,
+    pub conflicts: usize, // Changed on both sides since the last sync
+    pub failed: Vec<SyncFailure>, // Skipped this run and tried again on the next
+    pub finished_at: DateTime<Utc>,
+}
+
+// A document a push or pull couldn't send or apply
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncFailure {
+    pub id: String,
+    pub kind: SyncDocumentKind,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncStatus {
+    pub configured: bool,
+    pub folder: Option<std::path::PathBuf>,
+    pub pending_changes: usize, // Documents the next push would send
+    pub last_push: Option<SyncReport>,
+    pub last_pull: Option<SyncReport>,
+}
+
+// A document waiting to be pushed
+#[derive(Debug, Clone)]
+pub struct SyncChange {
+    pub seq: Option<i64>, // None for rows never synced or queued, e.g. written before the queue existed
+    pub doc_id: String,
+    pub kind: SyncDocumentKind,
+    pub deleted: bool,
+}
+
+// A pulled document to write: its row as stored, or `None` for a deletion
+#[derive(Debug, Clone)]
+pub struct SyncedRow {
+    pub kind: SyncDocumentKind,
+    pub id: String,
+    pub rev: String,
+    pub row: Option<Vec<(String, StoredValue)>>,
+}
+
+// Crash report written by the panic handler. Contains no note content: the panic message
+// has quoted text redacted and the backtrace holds only symbols and source locations.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrashReport {
+    pub id: String,
+    pub created_at: DateTime<Utc>,
+    pub app_version: String,
+    pub os: String,
+    pub arch: String,
+    pub thread: Option<String>,
+    pub message: String,
+    pub location: Option<String>,
+    pub backtrace: Vec<String>,
+    pub submitted_at: Option<DateTime<Utc>>,
+}
+
+// Update check settings, stored as JSON under the `update_check_config` setting
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateCheckConfig {
+    pub enabled: bool,
+    pub manifest_url: String, // Static JSON describing the latest release; must be HTTPS
+    pub check_interval_hours: u64,
+}
+
+impl Default for UpdateCheckConfig {
+    fn default() -> Self {
+        Self {
+            // No release manifest is published yet, so checks stay off until one is configured
+            enabled: false,
+            manifest_url: String::new(),
+            check_interval_hours: 24,
+        }
+    }
+}
+
+// Contents of the release manifest served at `UpdateCheckConfig::manifest_url`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReleaseManifest {
+    pub version: String,
+    pub release_notes: Option<String>,
+    pub download_url: Option<String>,
+    pub published_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateInfo {
+    pub current_version: String,
+    pub latest: Option<ReleaseManifest>,
+    pub update_available: bool,
+    pub checked_at: Option<DateTime<Utc>>,
+}
+
+// User scripts (Rhai) stored in the scripts directory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScriptInfo {
+    pub name: String,
+    pub path: std::path::PathBuf,
+    pub size: u64,
+    pub modified_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScriptResult {
+    pub name: String,
+    pub output: Vec<String>, // Lines written with print()/debug()
+    pub return_value: serde_json::Value,
+    pub duration_ms: u64,
+}
+
+// Templates, snippets and prompts (user-created or installed from bundles)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Template {
+    pub id: String,
+    pub bundle_id: Option<String>,
+    pub name: String,
+    pub description: Option<String>,
+    pub title: String,
+    pub content: String,
+    pub tags: Vec<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snippet {
+    pub id: String,
+    pub bundle_id: Option<String>,
+    pub name: String,
+    pub trigger: String, // Text the editor expands, e.g. ";date"
+    pub content: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptTemplate {
+    pub id: String,
+    pub bundle_id: Option<String>,
+    pub name: String,
+    pub description: Option<String>,
+    pub prompt: String,
+    pub created_at: DateTime<Utc>,
+}
+
+// Shareable bundle format: a bundle.json manifest, either standalone or inside a zip
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleManifest {
+    pub format_version: u32,
+    pub id: String,
+    pub name: String,
+    pub version: String,
+    pub author: Option<String>,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub templates: Vec<BundleTemplate>,
+    #[serde(default)]
+    pub snippets: Vec<BundleSnippet>,
+    #[serde(default)]
+    pub prompts: Vec<BundlePrompt>,
+    pub signature: Option<BundleSignature>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleTemplate {
+    pub name: String,
+    pub description: Option<String>,
+    pub title: String,
+    pub content: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleSnippet {
+    pub name: String,
+    pub trigger: String,
+    pub content: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundlePrompt {
+    pub name: String,
+    pub description: Option<String>,
+    pub prompt: String,
+}
+
+// Ed25519 signature over the manifest serialized with `signature` set to null
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleSignature {
+    pub public_key: String, // base64
+    pub signature: String,  // base64
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstalledBundle {
+    pub id: String,
+    pub name: String,
+    pub version: String,
+    pub author: Option<String>,
+    pub description: Option<String>,
+    pub signer_key: Option<String>,
+    pub trusted: bool,
+    pub template_count: u32,
+    pub snippet_count: u32,
+    pub prompt_count: u32,
+    pub installed_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InstallBundleRequest {
+    pub path: std::path::PathBuf,
+    pub allow_unsigned: bool,
+}
+
+// Smart paste
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PastePayload {
+    pub page_id: Option<String>,
+    pub text: Option<String>,
+    pub html: Option<String>,
+    pub image_data: Option<Vec<u8>>,
+    pub image_mime_type: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum PasteKind {
+    Html,
+    Url,
+    Code,
+    Table,
+    Image,
+    PlainText,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PasteResult {
+    pub kind: PasteKind,
+    pub markdown: String,
+    pub detected_language: Option<String>, // Set for code blocks
+    pub attachment_id: Option<String>,     // Set for pasted images
+}
+
+// File import pipeline
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImportFilesRequest {
+    pub paths: Vec<std::path::PathBuf>,
+    pub target_page_id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportBatch {
+    pub import_id: String,
+    pub total: usize,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum ImportKind {
+    Markdown,   // Becomes a subpage of the target page
+    Image,      // Attachment with OCR text
+    Pdf,        // Attachment with extracted text
+    Audio,      // Voice annotation with transcription
+    Attachment, // Anything else is attached as-is
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ImportStatus {
+    Started,
+    Completed,
+    Failed,
+}
+
+// Payload of the `import-progress` event, emitted once when a file starts and once when it finishes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportProgress {
+    pub import_id: String,
+    pub path: std::path::PathBuf,
+    pub index: usize,
+    pub total: usize,
+    pub kind: ImportKind,
+    pub status: ImportStatus,
+    pub created_id: Option<String>, // Page, media or voice annotation id
+    pub error: Option<String>,
+}
+
+// Screenshot capture
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct CaptureRegion {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CaptureScreenshotRequest {
+    pub page_id: String,
+    pub monitor_index: Option<usize>,
+    pub region: Option<CaptureRegion>, // Whole monitor when omitted
+    pub position_in_content: Option<u32>,
+}
+
+// Page sharing via QR code
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ShareQrKind {
+    DeepLink,         // Opens the page on a device that already has the vault
+    EncryptedPayload, // Carries the page itself; needs the passcode to open
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShareQrCode {
+    pub kind: ShareQrKind,
+    pub payload: String,          // The text encoded in the QR code
+    pub png_data: Vec<u8>,
+    pub passcode: Option<String>, // Shown next to the code, never encoded in it
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SharedPage {
+    pub title: String,
+    pub content: String,
+    pub tags: Vec<String>,
+}
+
+// Embedding re-index, run in the background after switching embedding models
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReindexJob {
+    pub job_id: String,
+    pub total: usize,
+}
+
+// Payload of the `reindex-progress` event, emitted after each note or page
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReindexProgress {
+    pub job_id: String,
+    pub done: usize,
+    pub total: usize,
+    pub current_title: String,
+    pub failed: usize,
+    pub finished: bool,
+}
+
+// Read-only web viewer served on the local network
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StartWebViewerRequest {
+    pub port: Option<u16>, // Any free port when omitted
+    pub password: String,
+    pub notebook_ids: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebViewerStatus {
+    pub running: bool,
+    pub url: Option<String>,
+    pub fingerprint: Option<String>, // SHA-256 of the self-signed certificate, to check in the browser
+    pub notebook_ids: Vec<String>,
+}
+
+// A column value exactly as stored in SQLite; blobs are base64 encoded
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "lowercase")]
+pub enum StoredValue {
+    Null,
+    Integer(i64),
+    Real(f64),
+    Text(String),
+    Blob(String),
+}
+
+// Named snapshot of the whole vault
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotInfo {
+    pub id: String,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub page_count: usize,
+    pub new_objects: usize, // Rows not already stored by an earlier snapshot
+    pub new_bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotPageChange {
+    pub id: String,
+    pub title: String,
+}
+
+// Differences from a snapshot to the current vault
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotDiff {
+    pub snapshot: SnapshotInfo,
+    pub added_pages: Vec<SnapshotPageChange>,
+    pub removed_pages: Vec<SnapshotPageChange>,
+    pub modified_pages: Vec<SnapshotPageChange>,
+    pub other_changed_rows: usize, // Notebooks, sections, media and other non-page rows
+}
+
+// Embedding model migration
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EmbeddingMigration {
+    Reembed, // Replace stale vectors in the background; they're ignored by search meanwhile
+    Drop,    // Delete stale vectors now, e.g. when no model is available to re-embed
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmbeddingModelCount {
+    pub model: String, // Empty for embeddings stored before models were recorded
+    pub dimension: usize,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmbeddingModelStatus {
+    pub current_model: String,
+    pub current_dimension: usize,
+    pub models: Vec<EmbeddingModelCount>,
+    pub stale: usize, // Embeddings from any other model or dimension
+}
+
+// Page export history, used to refresh external mirrors after edits
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportRecord {
+    pub id: String,
+    pub page_id: String,
+    pub format: ExportFormat,
+    pub locale: Option<String>,
+    pub destination: std::path::PathBuf,
+    pub path: std::path::PathBuf,
+    pub exported_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReExportFailure {
+    pub page_id: String,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReExportSummary {
+    pub exported: Vec<std::path::PathBuf>,
+    pub failed: Vec<ReExportFailure>,
+}
+
+// Vault-wide find and replace
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FindReplaceRequest {
+    pub query: String,
+    pub replacement: String,
+    #[serde(default)]
+    pub scope: SearchFilters,
+    #[serde(default)]
+    pub regex: bool, // `$1`-style capture references are expanded in the replacement
+    #[serde(default)]
+    pub case_sensitive: bool,
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+// One match with byte offsets into the original title or content
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReplacementPreview {
+    pub field: SearchMatchField,
+    pub start: usize,
+    pub end: usize,
+    pub matched: String,
+    pub replacement: String,
+    pub context: String,
+}
+
+// Payload of the `find-replace-match` event, emitted per matching page
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PageReplacementPreview {
+    pub page_id: String,
+    pub title: String,
+    pub matches: Vec<ReplacementPreview>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FindReplaceResult {
+    pub pages: Vec<PageReplacementPreview>,
+    pub total_matches: usize,
+    pub applied: bool, // False for dry runs
+}
+
+// Earlier version of a page, saved before bulk edits
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PageRevision {
+    pub id: String,
+    pub page_id: String,
+    pub title: String,
+    pub content: String,
+    pub reason: String,
+    pub created_at: DateTime<Utc>,
+}
+
+// Regex extraction across page content
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContentScanMatch {
+    pub page_id: String,
+    pub page_title: String,
+    pub start: usize, // Byte offsets into the page content
+    pub end: usize,
+    pub text: String,
+    pub groups: Vec<Option<String>>, // Numbered capture groups, starting at 1
+    pub named_groups: std::collections::HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContentScanResult {
+    pub matches: Vec<ContentScanMatch>,
+    pub pages_scanned: usize,
+    pub truncated: bool, // Stopped at the match limit
+}
+
+// Compute device for local AI inference
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AiDevicePreference {
+    #[default]
+    Auto, // CUDA, then Metal, then CPU
+    Cpu,
+    Cuda,
+    Metal,
+}
+
+impl AiDevicePreference {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AiDevicePreference::Auto => "auto",
+            AiDevicePreference::Cpu => "cpu",
+            AiDevicePreference::Cuda => "cuda",
+            AiDevicePreference::Metal => "metal",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "auto" => Some(AiDevicePreference::Auto),
+            "cpu" => Some(AiDevicePreference::Cpu),
+            "cuda" => Some(AiDevicePreference::Cuda),
+            "metal" => Some(AiDevicePreference::Metal),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AiDeviceKind {
+    Cpu,
+    Cuda,
+    Metal,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AiDeviceInfo {
+    pub preference: AiDevicePreference,
+    pub active: AiDeviceKind,
+    pub cuda_available: bool,
+    pub metal_available: bool,
+    pub fallback_reason: Option<String>, // Why the preferred device isn't the active one
+}
+
+// Duplicate tag merging
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TagMergeReason {
+    Case,    // Same name ignoring case and spacing
+    Plural,  // Singular and plural forms
+    Similar, // One letter apart, likely a typo
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TagMergeSuggestion {
+    pub target: String, // The most used tag of the group
+    pub sources: Vec<String>,
+    pub reason: TagMergeReason,
+    pub usage_count: u32, // Combined usage of the group
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TagMerge {
+    pub target: String,
+    pub sources: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateTagRequest {
+    pub id: String,
+    pub name: Option<String>, // Rewrites notes and pages using the old name
+    pub color: Option<String>,
+    pub description: Option<String>, // Empty string clears it
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TagMergeResult {
+    pub pages_updated: usize,
+    pub notes_updated: usize,
+    pub tags_removed: usize,
+}
+
+// Vault statistics export
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StatsRange {
+    #[serde(default)]
+    pub from: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub to: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StatsFormat {
+    #[default]
+    Csv,  // One file per dataset
+    Json, // All datasets in one file
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PagesPerDay {
+    pub date: String, // YYYY-MM-DD, UTC
+    pub pages_created: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotebookWordCount {
+    pub notebook_id: String,
+    pub notebook_title: String,
+    pub page_count: u32,
+    pub word_count: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TagUsageDay {
+    pub date: String, // YYYY-MM-DD, UTC
+    pub tag: String,
+    pub page_count: u32, // Pages created that day with the tag
+}
+
+/// Aggregates only; no titles or content of pages leave the vault.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageStats {
+    pub range: StatsRange,
+    pub pages_per_day: Vec<PagesPerDay>,
+    pub words_per_notebook: Vec<NotebookWordCount>,
+    pub tag_usage: Vec<TagUsageDay>,
+}
+
+// Zettelkasten notes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ZettelNoteType {
+    Literature, // Notes on a source, in your own words
+    Permanent,  // One idea, written to stand on its own
+}
+
+impl ZettelNoteType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ZettelNoteType::Literature => "literature",
+            ZettelNoteType::Permanent => "permanent",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateZettelNoteRequest {
+    pub notebook_id: String,
+    pub section_id: Option<String>,
+    pub note_type: ZettelNoteType,
+    pub title: String,
+    #[serde(default)]
+    pub source: Option<String>, // Book, article or URL for literature notes
+}
+
+// Maps of content
+/// What a map of content (MOC) lists.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "lowercase")]
+pub enum MocSource {
+    Tag(String),     // Pages with the tag, grouped by section
+    Section(String), // Pages in the section (folder), grouped by first tag
+    Cluster(String), // Pages similar to this page, grouped by section
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GenerateMocRequest {
+    pub source: MocSource,
+    pub notebook_id: String, // Where a new MOC page is created
+    #[serde(default)]
+    pub section_id: Option<String>,
+}
+
+// Streaming transcription, started by `transcribe_audio_stream`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranscriptionStream {
+    pub stream_id: String,
+    pub windows: usize,
+    pub duration_secs: f64,
+}
+
+// Payload of the `transcription-partial` event, emitted after each window
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PartialTranscript {
+    pub stream_id: String,
+    pub index: usize,
+    pub start_secs: f64,
+    pub end_secs: f64,
+    pub text: String,       // This window only
+    pub transcript: String, // Everything so far
+    pub language: Option<String>, // Given, or detected from the first window with speech
+    pub error: Option<String>,
+    pub finished: bool,
+}
+
+// Live dictation into a page, started by `start_dictation`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DictationSession {
+    pub session_id: String,
+    pub page_id: String,
+    pub language: Option<String>, // Given, the page's, or detected from the first speech
+}
+
+// Payload of the `dictation-text` event, emitted for each transcribed phrase
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DictationUpdate {
+    pub session_id: String,
+    pub page_id: String,
+    pub start_secs: f64, // From the start of the session
+    pub end_secs: f64,
+    pub text: String,       // For the editor to insert
+    pub transcript: String, // Everything dictated in the session
+    pub language: Option<String>,
+    pub error: Option<String>,
+    pub finished: bool,
+}
+
+// Stale content detection
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum StaleReason {
+    OldDates { latest: NaiveDate },                                  // Most recent date the page mentions
+    Superseded { page_id: String, title: String, similarity: f64 }, // A newer page on the same topic
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StalePage {
+    pub page_id: String,
+    pub title: String,
+    pub updated_at: DateTime<Utc>,
+    pub reasons: Vec<StaleReason>,
+}
+
+// Local language model rewriting
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RewriteStyle {
+    Concise,
+    Clearer,
+    Formal,
+    Casual,
+}
+
+// Question answering over notes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatCitation {
+    pub page_id: String,
+    pub title: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatAnswer {
+    pub answer: String,
+    pub citations: Vec<ChatCitation>, // Pages the answer cites, in order of first citation
+}
+
+// Typed settings registry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SettingType {
+    Bool,
+    Locale,    // BCP 47 tag
+    LogFilter, // e.g. "info" or "info,deviseos_lib=debug"
+    AiDevice,  // See `AiDevicePreference`
+    HttpsUrl,
+    Path,
+    Json,
+    Days, // Whole days, 0 or more
+    Text, // Any non-empty text
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SettingSchema {
+    pub key: String,
+    pub setting_type: SettingType,
+    pub default: Option<String>,
+}
+
+// Payload of the `setting-changed` event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SettingChange {
+    pub key: String,
+    pub value: Option<String>, // The effective value; the default after a reset
+}
+
+// Page question answering
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AnswerMethod {
+    Llm,        // Generated by the local language model
+    Extractive, // The best matching sentence
+    Rules,      // Rule-based edits, without a language model
+}
+
+/// Byte range in the page content or one of its voice transcriptions.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SupportingSpan {
+    pub field: SearchMatchField, // Content or Transcription
+    pub transcription_index: Option<usize>,
+    pub start: usize,
+    pub end: usize,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PageAnswer {
+    pub answer: Option<String>, // None when the page doesn't seem to answer it
+    pub span: Option<SupportingSpan>,
+    pub method: AnswerMethod,
+}
+
+// OS jump list / recent documents models
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JumpListEntry {
+    pub page_id: String,
+    pub title: String,
+    pub pinned: bool,
+}
+
+// Favorites models
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PinnedItems {
+    pub notebooks: Vec<Notebook>,
+    pub pages: Vec<PinnedPage>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PinnedPage {
+    pub id: String,
+    pub notebook_id: String,
+    pub title: String,
+}
+
+// Recents models
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecentPage {
+    pub page_id: String,
+    pub notebook_id: String,
+    pub title: String,
+    pub accessed_at: DateTime<Utc>,
+    pub access_count: u32, // Opens since it entered the recents list
+}
+
+// OS search integration models
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OsSearchSync {
+    pub folder: std::path::PathBuf,
+    pub written: usize, // New or changed stubs
+    pub removed: usize,
+}
+
+// Language detection models
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DetectedLanguage {
+    pub language: String, // ISO 639-1
+    pub confidence: f32,  // 0.0 to 1.0
+}
+
+// Keyphrase models
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Keyphrase {
+    pub phrase: String, // Lower case
+    pub score: f64,     // RAKE score; only comparable within one text
+}
+
+// Voice annotation subtitle models
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SubtitleFormat {
+    #[default]
+    Srt,
+    Vtt, // WebVTT, for HTML5 video and audio players
+}
+
+impl SubtitleFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            SubtitleFormat::Srt => "srt",
+            SubtitleFormat::Vtt => "vtt",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubtitleSegment {
+    pub index: usize, // 1-based, as numbered in SRT
+    pub start_secs: f64,
+    pub end_secs: f64,
+    pub text: String,
+    pub translation: Option<String>,
+    #[serde(default)]
+    pub words: Vec<TranscribedWord>, // Of `text`, when the recording has word timings
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubtitleExport {
+    pub subtitle_path: std::path::PathBuf,
+    pub audio_path: std::path::PathBuf,
+    pub segments: Vec<SubtitleSegment>,
+}
+
+// Encrypted web bundle models
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebBundle {
+    pub path: std::path::PathBuf, // index.html
+    pub page_count: usize,
+}
+
+// Tag suggestion models
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TagSuggestionMode {
+    #[default]
+    Keywords, // frequent words and names in the content
+    Vocabulary, // existing tags, ranked by semantic similarity
+}
+
+// Notebook export models
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotebookExport {
+    pub exported: Vec<std::path::PathBuf>,
+    pub unchanged: usize, // skipped by an incremental export
+    pub removed: Vec<std::path::PathBuf>, // files of pages deleted since the previous export
+    pub failed: Vec<ReExportFailure>,
+}
+
+// Writing assistant models
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WritingSuggestion {
+    pub start: usize, // Byte range of the selection in the content
+    pub end: usize,
+    pub original: String,
+    pub replacement: String,
+    pub method: AnswerMethod,
+}
+
+// Developer query console models
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SqlQueryResult {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<serde_json::Value>>,
+    pub truncated: bool, // More rows matched than are returned
+}
+
+// System health models
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    Disabled, // Turned off by configuration; doesn't affect the overall status
+    Ok,
+    Degraded,
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthComponent {
+    Database,
+    Encryption,
+    Ai,
+    Sync,
+    Jobs,
+    Disk,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ComponentHealth {
+    pub component: HealthComponent,
+    pub status: HealthStatus,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SystemHealth {
+    pub status: HealthStatus, // Worst status among enabled components
+    pub components: Vec<ComponentHealth>,
+    pub pending_jobs: usize,
+    pub disk_available_bytes: Option<u64>,
+    pub checked_at: DateTime<Utc>,
+}
+
+// Sentiment trend models
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SentimentGranularity {
+    #[default]
+    Day,
+    Week, // Starting Monday
+    Month,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SentimentPoint {
+    pub period_start: String, // YYYY-MM-DD, UTC
+    pub average: f64,
+    pub entry_count: usize, // Pages with a sentiment score in the period
+}
+
+// Spoken name resolution models
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SpokenNameKind {
+    Notebook,
+    Tag,
+    Page,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpokenNameMatch {
+    pub kind: SpokenNameKind,
+    pub id: String,
+    pub name: String,
+    pub score: f64, // 1.0 for an exact match after dropping filler words
+}
+
+// Voice command models
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "intent", rename_all = "snake_case")]
+pub enum VoiceIntent {
+    CreateNote { title: String, content: String, tags: Vec<String> }, // "new note titled groceries: milk, eggs"
+    AppendToPage { page_name: String, text: String },                 // "add to shopping list: bread"
+    Unrecognized,
+}
+
+impl VoiceIntent {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            VoiceIntent::CreateNote { .. } => "create_note",
+            VoiceIntent::AppendToPage { .. } => "append_to_page",
+            VoiceIntent::Unrecognized => "unrecognized",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum VoiceCommandAction {
+    CreatedNote { note_id: String, title: String },
+    AppendedToPage { page_id: String, title: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VoiceCommandResult {
+    pub transcription: String,
+    pub intent: VoiceIntent,
+    pub action: Option<VoiceCommandAction>,
+    pub reason: Option<String>, // Why nothing was done, when `action` is None
+}
+
+// AI model download models
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ModelKind {
+    Whisper,
+    Embedding,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DownloadableModel {
+    pub id: String, // e.g. "whisper-base", "embedding-all-MiniLM-L6-v2"
+    pub kind: ModelKind,
+    pub name: String,
+    pub download_bytes: u64, // Approximate
+    pub installed: bool, // Every file is in `ai_models_path`
+    pub installed_bytes: u64,
+    pub downloading: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelDownloadJob {
+    pub model_id: String,
+    pub files: usize,
+}
+
+// Payload of the `model-download-progress` event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelDownloadProgress {
+    pub model_id: String,
+    pub file: String,
+    pub downloaded_bytes: u64, // Of this file, including bytes from an earlier attempt
+    pub total_bytes: Option<u64>,
+    pub finished: bool, // Set on the last event, after every file is verified
+    pub error: Option<String>,
+}
+
+// Encryption coverage models
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DataCategory {
+    Content,
+    Titles,
+    Tags,
+    Transcriptions,
+    Media,
+    Embeddings,
+    Settings,
+    Templates,
+    Logs,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FieldCoverage {
+    pub table: String,
+    pub column: String,
+    pub category: DataCategory,
+    pub encrypted: bool, // Written encrypted when a key is loaded
+    pub rows: u64, // Non-empty values
+    pub bytes: u64,
+    pub undecryptable_rows: u64, // Encrypted fields whose value doesn't decrypt with the loaded key
+    pub undecryptable_bytes: u64,
+    pub undecryptable_ids: Vec<String>, // The first few, for follow-up
+}
+
+// Per-vault privacy settings, stored as JSON under the `security_config` setting
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SecurityConfig {
+    pub strip_image_metadata: bool, // Drop EXIF/XMP, including GPS position, from images on upload
+}
+
+// Image attachments whose EXIF still records where they were taken
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImageLocationAudit {
+    pub scanned: usize,
+    pub with_location: Vec<LocatedAttachment>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LocatedAttachment {
+    pub id: String,
+    pub original_filename: String,
+    pub page_id: Option<String>,
+    pub note_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EncryptionCoverageReport {
+    pub encryption_enabled: bool,
+    pub key_loaded: bool,
+    pub fields: Vec<FieldCoverage>,
+    pub encrypted_bytes: u64,
+    pub plaintext_bytes: u64, // Includes values that don't decrypt, which may be left from before encryption
+    pub exposure: f64, // Share of stored bytes readable without the key, 0.0 to 1.0
+    pub file_encrypted: bool, // The whole database file is encrypted with SQLCipher
+    pub warnings: Vec<String>,
+    pub checked_at: DateTime<Utc>,
+}
+
+// Background AI job models
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AiJobKind {
+    Embed { owner_id: String, owner: EmbeddingOwner },
+    Transcribe { annotation_id: String, language: Option<String> }, // ISO 639-1; None auto-detects
+    ExtractText { media_id: String }, // OCR for images, the text layer for PDFs
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AiJobPriority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+impl AiJobPriority {
+    pub fn as_i64(&self) -> i64 {
+        match self {
+            AiJobPriority::Low => 0,
+            AiJobPriority::Normal => 1,
+            AiJobPriority::High => 2,
+        }
+    }
+
+    pub fn from_i64(value: i64) -> Self {
+        match value {
+            i64::MIN..=0 => AiJobPriority::Low,
+            1 => AiJobPriority::Normal,
+            _ => AiJobPriority::High,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AiJobStatus {
+    Queued,
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+impl AiJobStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AiJobStatus::Queued => "queued",
+            AiJobStatus::Running => "running",
+            AiJobStatus::Completed => "completed",
+            AiJobStatus::Failed => "failed",
+            AiJobStatus::Cancelled => "cancelled",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "queued" => Some(AiJobStatus::Queued),
+            "running" => Some(AiJobStatus::Running),
+            "completed" => Some(AiJobStatus::Completed),
+            "failed" => Some(AiJobStatus::Failed),
+            "cancelled" => Some(AiJobStatus::Cancelled),
+            _ => None,
+        }
+    }
+}
+
+// Also the payload of the `ai-job-updated` event, emitted on every status change
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AiJob {
+    pub id: String,
+    pub kind: AiJobKind,
+    pub priority: AiJobPriority,
+    pub status: AiJobStatus,
+    pub attempts: u32,
+    pub error: Option<String>, // From the last failed attempt
+    pub run_after: DateTime<Utc>, // Retries wait before running again
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl AiJob {
+    pub fn new(kind: AiJobKind, priority: AiJobPriority) -> Self {
+        let now = Utc::now();
+        Self {
+            id: Uuid::new_v4().to_string(),
+            kind,
+            priority,
+            status: AiJobStatus::Queued,
+            attempts: 0,
+            error: None,
+            run_after: now,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+}
+
+// Trash models
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrashItemType {
+    Note,
+    Page,
+}
+
+impl TrashItemType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TrashItemType::Note => "note",
+            TrashItemType::Page => "page",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "note" => Some(TrashItemType::Note),
+            "page" => Some(TrashItemType::Page),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashItem {
+    pub id: String, // Of the deleted note or page
+    pub item_type: TrashItemType,
+    pub title: String,
+    pub notebook_id: Option<String>, // Pages only
+    pub page_count: usize, // Pages trashed with it, including itself; 0 for notes
+    pub deleted_at: DateTime<Utc>,
+}
+
+// Page property models
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PropertyType {
+    Text,
+    Number,
+    Date,
+    Select,
+}
+
+impl PropertyType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PropertyType::Text => "text",
+            PropertyType::Number => "number",
+            PropertyType::Date => "date",
+            PropertyType::Select => "select",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "text" => Some(PropertyType::Text),
+            "number" => Some(PropertyType::Number),
+            "date" => Some(PropertyType::Date),
+            "select" => Some(PropertyType::Select),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "lowercase")]
+pub enum PropertyValue {
+    Text(String),
+    Number(f64),
+    Date(NaiveDate),
+    Select(String),
+}
+
+impl PropertyValue {
+    pub fn property_type(&self) -> PropertyType {
+        match self {
+            PropertyValue::Text(_) => PropertyType::Text,
+            PropertyValue::Number(_) => PropertyType::Number,
+            PropertyValue::Date(_) => PropertyType::Date,
+            PropertyValue::Select(_) => PropertyType::Select,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PropertyDefinition {
+    pub name: String,
+    pub property_type: PropertyType,
+    pub options: Vec<String>, // Allowed values of a select property; empty otherwise
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DefinePropertyRequest {
+    pub name: String,
+    pub property_type: PropertyType,
+    #[serde(default)]
+    pub options: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PageProperty {
+    pub page_id: String,
+    pub name: String,
+    pub value: PropertyValue,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PropertyOperator {
+    Equals,
+    NotEquals, // Also matches pages without the property
+    LessThan,
+    GreaterThan,
+    Contains, // Text only, ignoring case
+    IsSet,
+    IsNotSet,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PropertyFilter {
+    pub name: String,
+    pub operator: PropertyOperator,
+    pub value: Option<PropertyValue>, // Unused by IsSet and IsNotSet
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FilterPagesRequest {
+    pub filters: Vec<PropertyFilter>, // All must match
+    pub notebook_id: Option<String>,
+}
+
+// Link graph models
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GraphPage {
+    pub id: String,
+    pub title: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GraphHub {
+    pub id: String,
+    pub title: String,
+    pub incoming: usize, // Pages linking here
+    pub outgoing: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GraphMetrics {
+    pub notebook_id: Option<String>, // None for the whole vault
+    pub page_count: usize,
+    pub link_count: usize,
+    pub orphans: Vec<GraphPage>, // No links in or out
+    pub hubs: Vec<GraphHub>,     // Most linked to first
+    pub components: Vec<Vec<String>>, // Page ids of each connected group, largest first
+}
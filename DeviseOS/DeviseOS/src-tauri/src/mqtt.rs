@@ -0,0 +1,86 @@
+use std::time::Duration;
+use chrono::Utc;
+use rumqttc::{AsyncClient, Event, MqttOptions, Packet, QoS};
+use crate::{
+    AppError, AppResult,
+    models::{AutomationEvent, MqttConfig},
+    database::Database,
+};
+
+pub const MQTT_CONFIG_KEY: &str = "mqtt_config";
+const PUBLISH_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct MqttPublisher;
+
+impl MqttPublisher {
+    pub fn new() -> Self {
+        Self
+    }
+
+    pub async fn get_config(&self, database: &Database) -> AppResult<MqttConfig> {
+        match database.get_setting(MQTT_CONFIG_KEY).await? {
+            Some(value) => Ok(serde_json::from_str(&value)?),
+            None => Ok(MqttConfig::default()),
+        }
+    }
+
+    /// Rejects a configuration that can't work, when the setting is validated.
+    pub fn check_config(config: &MqttConfig) -> Result<(), String> {
+        if config.host.trim().is_empty() {
+            return Err("MQTT host must not be empty".to_string());
+        }
+        Ok(())
+    }
+
+    /// Publishes the event if the integration is enabled and the event kind is selected.
+    pub async fn publish_event(&self, database: &Database, event: &AutomationEvent) -> AppResult<()> {
+        let config = self.get_config(database).await?;
+        if !config.enabled || !config.events.iter().any(|kind| kind == event.kind()) {
+            return Ok(());
+        }
+
+        let payload = serde_json::json!({
+            "event": event,
+            "published_at": Utc::now().to_rfc3339(),
+        });
+        let topic = format!("{}/{}", config.topic_prefix.trim_end_matches('/'), event.kind());
+
+        self.publish(&config, &topic, serde_json::to_vec(&payload)?).await
+    }
+
+    /// Publishes a test message so users can verify broker settings from the UI.
+    pub async fn test_connection(&self, config: &MqttConfig) -> AppResult<()> {
+        let topic = format!("{}/test", config.topic_prefix.trim_end_matches('/'));
+        self.publish(config, &topic, b"DeviseOS test message".to_vec()).await
+    }
+
+    async fn publish(&self, config: &MqttConfig, topic: &str, payload: Vec<u8>) -> AppResult<()> {
+        let mut options = MqttOptions::new(&config.client_id, &config.host, config.port);
+        options.set_keep_alive(Duration::from_secs(30));
+        if let (Some(username), Some(password)) = (&config.username, &config.password) {
+            options.set_credentials(username, password);
+        }
+
+        // Events are infrequent, so connect per publish instead of keeping a session open
+        let (client, mut eventloop) = AsyncClient::new(options, 10);
+        client
+            .publish(topic, QoS::AtLeastOnce, false, payload)
+            .await
+            .map_err(|e| AppError::Network(format!("MQTT publish failed: {}", e)))?;
+
+        let acknowledged = tokio::time::timeout(PUBLISH_TIMEOUT, async {
+            loop {
+                match eventloop.poll().await {
+                    Ok(Event::Incoming(Packet::PubAck(_))) => return Ok(()),
+                    Ok(_) => continue,
+                    Err(e) => return Err(AppError::Network(format!("MQTT connection failed: {}", e))),
+                }
+            }
+        })
+        .await
+        .map_err(|_| AppError::Timeout(format!("MQTT broker {}:{} did not acknowledge", config.host, config.port)))?;
+
+        let _ = client.disconnect().await;
+        acknowledged
+    }
+}
@@ -0,0 +1,169 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use crate::{
+    AppResult,
+    models::OsSearchSync,
+    database::Database,
+    export::file_stem,
+    share::PAGE_LINK_PREFIX,
+};
+
+/// Folder the stubs are written to; OS search integration is off while unset.
+pub const OS_SEARCH_FOLDER_KEY: &str = "os_search_folder";
+/// Stubs written to that folder, as JSON of page id to file name. Only these are ever
+/// removed, so other files in the folder are safe whatever their extension.
+const OS_SEARCH_STUBS_KEY: &str = "os_search_stubs";
+
+/// Shortcut formats the platform's indexer picks up and opens through the `deviseos://`
+/// handler.
+#[cfg(target_os = "windows")]
+const STUB_EXTENSION: &str = "url";
+#[cfg(target_os = "macos")]
+const STUB_EXTENSION: &str = "webloc";
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+const STUB_EXTENSION: &str = "desktop";
+
+type Manifest = BTreeMap<String, String>;
+
+/// Documents is indexed by default on both Windows Search and Spotlight.
+pub fn default_folder() -> Option<PathBuf> {
+    dirs::document_dir().map(|dir| dir.join("DeviseOS Pages"))
+}
+
+/// Syncs the stubs when OS search integration is enabled.
+pub async fn refresh(database: &Database) -> AppResult<Option<OsSearchSync>> {
+    match database.get_setting(OS_SEARCH_FOLDER_KEY).await? {
+        Some(folder) => Ok(Some(sync(database, Path::new(&folder)).await?)),
+        None => Ok(None),
+    }
+}
+
+/// Updates the stub of one page after it was created, saved or moved, without touching
+/// the rest of the folder.
+pub async fn refresh_page(database: &Database, page_id: &str) -> AppResult<()> {
+    let Some(folder) = database.get_setting(OS_SEARCH_FOLDER_KEY).await? else {
+        return Ok(());
+    };
+    let folder = Path::new(&folder);
+    let mut manifest = load_manifest(database).await?;
+    let previous = manifest.remove(page_id);
+
+    if let Some(title) = database.get_public_page_title(page_id).await? {
+        let name = stub_file_name(&title, page_id);
+        tokio::fs::create_dir_all(folder).await?;
+        write_stub(&folder.join(&name), &stub_contents(&title, page_id)).await?;
+        manifest.insert(page_id.to_string(), name);
+    }
+    if let Some(previous) = previous.filter(|previous| !manifest.values().any(|name| name == previous)) {
+        remove_stub(folder, &previous).await?;
+    }
+    save_manifest(database, &manifest).await
+}
+
+/// Writes one stub per page outside private notebooks into `folder`, holding only the title
+/// and a deep link, and removes stubs of pages that are gone or became private.
+pub async fn sync(database: &Database, folder: &Path) -> AppResult<OsSearchSync> {
+    let pages = database.get_public_page_titles().await?;
+    tokio::fs::create_dir_all(folder).await?;
+
+    let mut result = OsSearchSync { folder: folder.to_path_buf(), written: 0, removed: 0 };
+    let mut expected = Manifest::new();
+    for (id, title) in pages {
+        let name = stub_file_name(&title, &id);
+        if write_stub(&folder.join(&name), &stub_contents(&title, &id)).await? {
+            result.written += 1;
+        }
+        expected.insert(id, name);
+    }
+
+    for name in load_manifest(database).await?.into_values() {
+        if !expected.values().any(|kept| *kept == name) && remove_stub(folder, &name).await? {
+            result.removed += 1;
+        }
+    }
+    save_manifest(database, &expected).await?;
+    tracing::info!("Synced OS search stubs in {}: {} written, {} removed", folder.display(), result.written, result.removed);
+    Ok(result)
+}
+
+/// Removes the stubs written to `folder`, leaving other files in place.
+pub async fn clear(database: &Database, folder: &Path) -> AppResult<usize> {
+    let mut removed = 0;
+    for name in load_manifest(database).await?.into_values() {
+        if remove_stub(folder, &name).await? {
+            removed += 1;
+        }
+    }
+    database.delete_setting(OS_SEARCH_STUBS_KEY).await?;
+    Ok(removed)
+}
+
+/// Unchanged stubs are left alone so the indexer doesn't reprocess them; returns whether
+/// the file was written.
+async fn write_stub(path: &Path, contents: &str) -> AppResult<bool> {
+    if tokio::fs::read_to_string(path).await.ok().as_deref() == Some(contents) {
+        return Ok(false);
+    }
+    tokio::fs::write(path, contents).await?;
+    Ok(true)
+}
+
+async fn remove_stub(folder: &Path, name: &str) -> AppResult<bool> {
+    // Names come from the manifest, but never follow one out of the folder
+    if Path::new(name).file_name().and_then(|file| file.to_str()) != Some(name) {
+        return Ok(false);
+    }
+    match tokio::fs::remove_file(folder.join(name)).await {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+async fn load_manifest(database: &Database) -> AppResult<Manifest> {
+    match database.get_setting(OS_SEARCH_STUBS_KEY).await? {
+        Some(value) => Ok(serde_json::from_str(&value)?),
+        None => Ok(Manifest::new()),
+    }
+}
+
+async fn save_manifest(database: &Database, manifest: &Manifest) -> AppResult<()> {
+    database.set_setting(OS_SEARCH_STUBS_KEY, &serde_json::to_string(manifest)?).await
+}
+
+/// The title is what users search for; the short id keeps pages with the same title apart.
+fn stub_file_name(title: &str, id: &str) -> String {
+    let short_id: String = id.chars().take(8).collect();
+    format!("{} ({}).{}", file_stem(title, id), short_id, STUB_EXTENSION)
+}
+
+fn stub_contents(title: &str, id: &str) -> String {
+    let link = format!("{}{}", PAGE_LINK_PREFIX, id);
+    if cfg!(target_os = "windows") {
+        format!("[InternetShortcut]\r\nURL={}\r\n", link)
+    } else if cfg!(target_os = "macos") {
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" \"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n<plist version=\"1.0\">\n<dict>\n\t<key>URL</key>\n\t<string>{}</string>\n</dict>\n</plist>\n",
+            link
+        )
+    } else {
+        let name: String = title.chars().map(|c| if c.is_control() { ' ' } else { c }).collect();
+        format!("[Desktop Entry]\nType=Link\nName={}\nURL={}\n", name.trim(), link)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stub_holds_only_title_and_link() {
+        let id = "3f2a9c1e-0000-4000-8000-000000000000";
+        assert_eq!(stub_file_name("Q3 plan: draft", id), format!("Q3 plan_ draft (3f2a9c1e).{}", STUB_EXTENSION));
+
+        // A line break in the title can't add keys to the stub
+        let contents = stub_contents("Q3 plan\nURL=https://example.com", id);
+        assert!(contents.contains("deviseos://page/3f2a9c1e-0000-4000-8000-000000000000"));
+        assert!(!contents.lines().any(|line| line == "URL=https://example.com"));
+    }
+}
@@ -0,0 +1,203 @@
+use chrono::Utc;
+use crate::{
+    AppError, AppResult,
+    models::{PasteKind, PastePayload, PasteResult, UploadMediaRequest},
+    database::Database,
+};
+
+const MIN_TABLE_ROWS: usize = 2;
+const MIN_CODE_SCORE: i32 = 3;
+
+/// Converts clipboard content into Markdown suitable for inserting into a page.
+/// Images are stored as attachments on `payload.page_id` and referenced via `media://`.
+pub async fn process_paste(database: &Database, payload: PastePayload) -> AppResult<PasteResult> {
+    if let Some(image_data) = payload.image_data {
+        let page_id = payload.page_id
+            .ok_or_else(|| AppError::InvalidOperation("Pasting an image requires a target page".to_string()))?;
+        let mime_type = payload.image_mime_type.unwrap_or_else(|| "image/png".to_string());
+        let extension = mime_type.rsplit('/').next().unwrap_or("png").to_string();
+
+        let media = database.upload_media(UploadMediaRequest {
+            page_id: Some(page_id),
+            note_id: None,
+            filename: format!("pasted-{}.{}", Utc::now().format("%Y%m%d-%H%M%S"), extension),
+            mime_type,
+            file_data: image_data,
+            position_in_content: None,
+        }).await?;
+
+        return Ok(PasteResult {
+            kind: PasteKind::Image,
+            markdown: format!("![Media](media://{})", media.id),
+            detected_language: None,
+            attachment_id: Some(media.id),
+        });
+    }
+
+    if let Some(html) = payload.html.filter(|h| !h.trim().is_empty()) {
+        return Ok(PasteResult {
+            kind: PasteKind::Html,
+            markdown: html_to_markdown(&html),
+            detected_language: None,
+            attachment_id: None,
+        });
+    }
+
+    let text = payload.text.unwrap_or_default();
+    Ok(convert_text(&text))
+}
+
+/// Classifies plain text and converts it: URL -> link, CSV/TSV -> table, code -> fenced block.
+pub fn convert_text(text: &str) -> PasteResult {
+    let trimmed = text.trim();
+
+    if is_url(trimmed) {
+        return PasteResult {
+            kind: PasteKind::Url,
+            markdown: format!("[{}]({})", url_label(trimmed), trimmed),
+            detected_language: None,
+            attachment_id: None,
+        };
+    }
+
+    if let Some(table) = delimited_to_table(trimmed) {
+        return PasteResult {
+            kind: PasteKind::Table,
+            markdown: table,
+            detected_language: None,
+            attachment_id: None,
+        };
+    }
+
+    if let Some(language) = detect_code_language(trimmed) {
+        return PasteResult {
+            kind: PasteKind::Code,
+            markdown: format!("```{}\n{}\n```", language, text.trim_end()),
+            detected_language: Some(language.to_string()),
+            attachment_id: None,
+        };
+    }
+
+    PasteResult {
+        kind: PasteKind::PlainText,
+        markdown: text.to_string(),
+        detected_language: None,
+        attachment_id: None,
+    }
+}
+
+pub fn html_to_markdown(html: &str) -> String {
+    html2md::parse_html(html).trim().to_string()
+}
+
+fn is_url(text: &str) -> bool {
+    (text.starts_with("http://") || text.starts_with("https://"))
+        && !text.contains(char::is_whitespace)
+        && text.len() > "https://".len()
+}
+
+fn url_label(url: &str) -> String {
+    let without_scheme = url.split("://").nth(1).unwrap_or(url);
+    without_scheme.trim_end_matches('/').to_string()
+}
+
+/// Turns comma- or tab-separated text with a consistent column count into a Markdown table.
+pub fn delimited_to_table(text: &str) -> Option<String> {
+    let delimiter = if text.contains('\t') { b'\t' } else { b',' };
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .delimiter(delimiter)
+        .flexible(true)
+        .from_reader(text.as_bytes());
+
+    let rows: Vec<Vec<String>> = reader
+        .records()
+        .collect::<Result<Vec<_>, _>>()
+        .ok()?
+        .into_iter()
+        .map(|record| record.iter().map(|field| field.trim().replace('|', "\\|")).collect())
+        .collect();
+
+    let columns = rows.first()?.len();
+    if rows.len() < MIN_TABLE_ROWS || columns < 2 || rows.iter().any(|row| row.len() != columns) {
+        return None;
+    }
+
+    let mut table = String::new();
+    table.push_str(&format!("| {} |\n", rows[0].join(" | ")));
+    table.push_str(&format!("|{}\n", " --- |".repeat(columns)));
+    for row in &rows[1..] {
+        table.push_str(&format!("| {} |\n", row.join(" | ")));
+    }
+
+    Some(table.trim_end().to_string())
+}
+
+/// Scores the text against simple per-language markers; returns the best match if it
+/// looks enough like code.
+pub fn detect_code_language(text: &str) -> Option<&'static str> {
+    let markers: &[(&str, &[&str])] = &[
+        ("rust", &["fn ", "let mut ", "impl ", "pub struct", "::", "-> ", "match ", "#[derive"]),
+        ("python", &["def ", "import ", "elif ", "self.", "__init__", "print(", "None", "):\n"]),
+        ("javascript", &["function ", "const ", "=> ", "console.log", "let ", "require(", "export "]),
+        ("typescript", &["interface ", ": string", ": number", "export type", "import type"]),
+        ("java", &["public class", "private ", "System.out", "void ", "@Override"]),
+        ("c", &["#include", "int main", "printf(", "malloc(", "->"]),
+        ("go", &["func ", "package ", ":= ", "fmt.", "go func"]),
+        ("sql", &["SELECT ", " FROM ", " WHERE ", "INSERT INTO", "CREATE TABLE", "JOIN "]),
+        ("bash", &["#!/bin/", "echo ", "fi\n", "$(", "export ", "sudo "]),
+        ("html", &["<div", "</", "<html", "class=\"", "<span"]),
+        ("json", &["\": ", "{\n", "\"],"]),
+    ];
+
+    let mut best: Option<(&'static str, i32)> = None;
+    for (language, tokens) in markers {
+        let score: i32 = tokens.iter().filter(|token| text.contains(*token)).count() as i32;
+        if best.map(|(_, s)| score > s).unwrap_or(true) {
+            best = Some((language, score));
+        }
+    }
+
+    // Structural hints shared by most languages
+    let structural = text.lines().filter(|line| {
+        let line = line.trim_end();
+        line.ends_with(';') || line.ends_with('{') || line.ends_with('}') || line.starts_with("    ")
+    }).count() as i32;
+
+    best.filter(|(_, score)| score + structural.min(3) >= MIN_CODE_SCORE && *score > 0)
+        .map(|(language, _)| language)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_url_becomes_link() {
+        let result = convert_text("https://example.com/docs/");
+        assert!(matches!(result.kind, PasteKind::Url));
+        assert_eq!(result.markdown, "[example.com/docs](https://example.com/docs/)");
+    }
+
+    #[test]
+    fn test_csv_becomes_table() {
+        let table = delimited_to_table("name,qty\nmilk,2\neggs,12").unwrap();
+        assert_eq!(table, "| name | qty |\n| --- | --- |\n| milk | 2 |\n| eggs | 12 |");
+    }
+
+    #[test]
+    fn test_prose_with_commas_is_not_a_table() {
+        assert!(delimited_to_table("Hello, world.\nThis is a sentence").is_none());
+    }
+
+    #[test]
+    fn test_code_language_detection() {
+        let rust = "fn main() {\n    let mut x = 1;\n    println!(\"{}\", x);\n}";
+        assert_eq!(detect_code_language(rust), Some("rust"));
+
+        let sql = "SELECT id, title FROM pages WHERE notebook_id = ?;";
+        assert_eq!(detect_code_language(sql), Some("sql"));
+
+        assert_eq!(detect_code_language("Remember to buy milk and eggs."), None);
+    }
+}
@@ -0,0 +1,167 @@
+use std::path::{Path, PathBuf};
+use crate::{
+    AppError, AppResult,
+    models::{AppConfig, CompliancePolicy},
+    encryption::EncryptionLevel,
+};
+
+pub const POLICY_FILE_NAME: &str = "policies.json";
+
+/// Builds the app configuration, applying an administrator policy if one is deployed.
+/// A policy file that exists but can't be parsed is an error rather than silently ignored,
+/// so a broken deployment never runs unrestricted.
+pub fn load_config() -> AppResult<AppConfig> {
+    let mut config = AppConfig::default();
+
+    if let Some(path) = policy_paths(&config).into_iter().find(|p| p.exists()) {
+        let policy = read_policy(&path)?;
+        tracing::info!("Applying compliance policy from {}", path.display());
+        apply_policy(&mut config, policy);
+    }
+
+    Ok(config)
+}
+
+/// System-wide location first so it wins over a copy in the user's data directory.
+fn policy_paths(config: &AppConfig) -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+
+    #[cfg(target_os = "linux")]
+    paths.push(PathBuf::from("/etc/deviseos").join(POLICY_FILE_NAME));
+    #[cfg(target_os = "macos")]
+    paths.push(PathBuf::from("/Library/Application Support/DeviseOS").join(POLICY_FILE_NAME));
+    #[cfg(target_os = "windows")]
+    if let Some(program_data) = std::env::var_os("ProgramData") {
+        paths.push(PathBuf::from(program_data).join("DeviseOS").join(POLICY_FILE_NAME));
+    }
+
+    if let Some(data_dir) = config.database_path.parent() {
+        paths.push(data_dir.join(POLICY_FILE_NAME));
+    }
+
+    paths
+}
+
+fn read_policy(path: &Path) -> AppResult<CompliancePolicy> {
+    let contents = std::fs::read_to_string(path)?;
+    serde_json::from_str(&contents)
+        .map_err(|e| AppError::Configuration(format!("Invalid policy file {}: {}", path.display(), e)))
+}
+
+pub fn apply_policy(config: &mut AppConfig, policy: CompliancePolicy) {
+    let policy = policy.effective();
+
+    if let Some(min_level) = policy.min_encryption_level {
+        if min_level > EncryptionLevel::None {
+            config.encryption_enabled = true;
+        }
+        config.encryption_level = config.encryption_level.max(min_level);
+    }
+    if policy.disable_cloud_ai {
+        config.cloud_ai_enabled = false;
+    }
+    if policy.disable_sync {
+        config.sync_enabled = false;
+    }
+    if policy.disable_update_checks {
+        config.update_checks_enabled = false;
+    }
+    if policy.require_audit_log {
+        config.audit_log_enabled = true;
+    }
+
+    config.policy = Some(policy);
+}
+
+/// Rejects changes to settings the policy has locked.
+pub fn ensure_setting_unlocked(config: &AppConfig, key: &str) -> AppResult<()> {
+    match config.policy {
+        Some(ref policy) if policy.is_setting_locked(key) => Err(AppError::PermissionDenied(format!(
+            "Setting {} is locked by the administrator policy",
+            key
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Rejects AI features reaching the network, which today means downloading models, once
+/// the policy has turned cloud AI off.
+pub fn ensure_cloud_ai_enabled(config: &AppConfig) -> AppResult<()> {
+    if config.cloud_ai_enabled {
+        Ok(())
+    } else {
+        Err(AppError::PermissionDenied(format!(
+            "Cloud AI is turned off by the administrator policy; copy model files into {} instead of downloading them",
+            config.ai_models_path.display()
+        )))
+    }
+}
+
+/// Rejects checking for updates once the policy has turned it off.
+pub fn ensure_update_checks_enabled(config: &AppConfig) -> AppResult<()> {
+    if config.update_checks_enabled {
+        Ok(())
+    } else {
+        Err(AppError::PermissionDenied("Update checks are turned off by the administrator policy".to_string()))
+    }
+}
+
+/// Rejects syncing once the policy has turned it off.
+pub fn ensure_sync_enabled(config: &AppConfig) -> AppResult<()> {
+    if config.sync_enabled {
+        Ok(())
+    } else {
+        Err(AppError::PermissionDenied("Sync is turned off by the administrator policy".to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(json: &str) -> CompliancePolicy {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn test_compliance_mode_enforces_everything() {
+        let mut config = AppConfig::default();
+        config.encryption_enabled = false;
+        config.encryption_level = EncryptionLevel::None;
+        config.cloud_ai_enabled = true;
+        config.sync_enabled = true;
+        config.update_checks_enabled = true;
+
+        apply_policy(&mut config, policy(r#"{"compliance_mode": true}"#));
+
+        assert!(config.encryption_enabled);
+        assert_eq!(config.encryption_level, EncryptionLevel::Standard);
+        assert!(!config.cloud_ai_enabled);
+        assert!(ensure_cloud_ai_enabled(&config).is_err());
+        assert!(!config.sync_enabled);
+        assert!(ensure_sync_enabled(&config).is_err());
+        assert!(ensure_update_checks_enabled(&config).is_err());
+        assert!(config.audit_log_enabled);
+    }
+
+    #[test]
+    fn test_minimum_level_never_lowers_encryption() {
+        let mut config = AppConfig::default();
+        config.encryption_level = EncryptionLevel::Military;
+
+        apply_policy(&mut config, policy(r#"{"min_encryption_level": "High"}"#));
+
+        assert_eq!(config.encryption_level, EncryptionLevel::Military);
+    }
+
+    #[test]
+    fn test_locked_settings_are_rejected() {
+        let mut config = AppConfig::default();
+        assert!(ensure_setting_unlocked(&config, "mqtt_config").is_ok());
+
+        apply_policy(&mut config, policy(r#"{"locked_settings": ["mqtt_config"]}"#));
+
+        assert!(ensure_setting_unlocked(&config, "mqtt_config").is_err());
+        assert!(ensure_setting_unlocked(&config, "theme").is_ok());
+    }
+}
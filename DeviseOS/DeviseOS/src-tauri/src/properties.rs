@@ -0,0 +1,248 @@
+use chrono::NaiveDate;
+use crate::{
+    AppError, AppResult,
+    models::{PropertyDefinition, PropertyFilter, PropertyOperator, PropertyType, PropertyValue, StoredValue},
+};
+
+const MAX_NAME_CHARS: usize = 64;
+
+/// Canonical form a property name is stored in: single spaces, lower case, so "Due date"
+/// and "due  date" are one property.
+pub fn normalize_name(name: &str) -> Option<String> {
+    let name = name.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase();
+    if name.is_empty() || name.chars().count() > MAX_NAME_CHARS {
+        None
+    } else {
+        Some(name)
+    }
+}
+
+/// Trimmed select options without empties or repeats, in the order given.
+pub fn normalize_options(options: &[String]) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::new();
+    for option in options.iter().map(|o| o.trim()).filter(|o| !o.is_empty()) {
+        if !normalized.iter().any(|existing| existing == option) {
+            normalized.push(option.to_string());
+        }
+    }
+    normalized
+}
+
+/// Checks `value` can be stored under `definition`.
+pub fn validate(definition: &PropertyDefinition, value: &PropertyValue) -> AppResult<()> {
+    if value.property_type() != definition.property_type {
+        return Err(AppError::InvalidFormat(format!(
+            "Property '{}' holds {} values, not {}",
+            definition.name,
+            definition.property_type.as_str(),
+            value.property_type().as_str()
+        )));
+    }
+    match value {
+        PropertyValue::Number(number) if !number.is_finite() => {
+            Err(AppError::InvalidFormat(format!("Property '{}' must be a finite number", definition.name)))
+        }
+        PropertyValue::Select(option) if !definition.options.contains(option) => Err(AppError::InvalidFormat(format!(
+            "'{}' isn't an option of property '{}'",
+            option, definition.name
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// The `(text_value, number_value)` columns a value is stored in. Dates are ISO 8601 text,
+/// which sorts chronologically.
+pub fn to_columns(value: &PropertyValue) -> (Option<String>, Option<f64>) {
+    match value {
+        PropertyValue::Text(text) | PropertyValue::Select(text) => (Some(text.clone()), None),
+        PropertyValue::Date(date) => (Some(date.to_string()), None),
+        PropertyValue::Number(number) => (None, Some(*number)),
+    }
+}
+
+pub fn from_columns(property_type: PropertyType, text: Option<String>, number: Option<f64>) -> AppResult<PropertyValue> {
+    let missing = || AppError::InvalidFormat(format!("Stored {} property has no value", property_type.as_str()));
+    Ok(match property_type {
+        PropertyType::Text => PropertyValue::Text(text.ok_or_else(missing)?),
+        PropertyType::Select => PropertyValue::Select(text.ok_or_else(missing)?),
+        PropertyType::Number => PropertyValue::Number(number.ok_or_else(missing)?),
+        PropertyType::Date => {
+            let text = text.ok_or_else(missing)?;
+            PropertyValue::Date(NaiveDate::parse_from_str(&text, "%Y-%m-%d")
+                .map_err(|e| AppError::InvalidFormat(format!("Invalid stored date '{}': {}", text, e)))?)
+        }
+    }
+}
+
+/// SQL condition on pages aliased `p` for `filter`, with its bind values in order.
+/// `definition` is the filtered property's; the filter value must be of its type. Text,
+/// select and date values may be stored encrypted, so comparing them only narrows the
+/// pages to those with the property (nothing for `NotEquals`), and `value_matches`
+/// finishes the comparison after decryption.
+pub fn filter_condition(filter: &PropertyFilter, definition: &PropertyDefinition) -> AppResult<Option<(String, Vec<StoredValue>)>> {
+    const HAS_PROPERTY: &str = "SELECT 1 FROM page_properties pp WHERE pp.page_id = p.id AND pp.name = ?";
+    let name = StoredValue::Text(definition.name.clone());
+    match filter.operator {
+        PropertyOperator::IsSet => return Ok(Some((format!("EXISTS ({})", HAS_PROPERTY), vec![name]))),
+        PropertyOperator::IsNotSet => return Ok(Some((format!("NOT EXISTS ({})", HAS_PROPERTY), vec![name]))),
+        _ => {}
+    }
+
+    let value = filter_value(filter, definition)?;
+    check_operator(filter.operator, definition)?;
+    let PropertyValue::Number(number) = value else {
+        return Ok((filter.operator != PropertyOperator::NotEquals).then(|| (format!("EXISTS ({})", HAS_PROPERTY), vec![name])));
+    };
+    let comparison = match filter.operator {
+        PropertyOperator::LessThan => "<",
+        PropertyOperator::GreaterThan => ">",
+        _ => "=", // Equals and NotEquals; nothing else applies to numbers
+    };
+    let negate = if filter.operator == PropertyOperator::NotEquals { "NOT " } else { "" };
+    Ok(Some((
+        format!("{}EXISTS ({} AND pp.number_value {} ?)", negate, HAS_PROPERTY, comparison),
+        vec![name, StoredValue::Real(*number)],
+    )))
+}
+
+/// Whether `filter` compares stored text and so must also be checked with `value_matches`.
+pub fn compares_text(filter: &PropertyFilter, definition: &PropertyDefinition) -> bool {
+    !matches!(filter.operator, PropertyOperator::IsSet | PropertyOperator::IsNotSet)
+        && definition.property_type != PropertyType::Number
+}
+
+/// Whether a page whose value of the property is `value` passes `filter`, for a filter
+/// `filter_condition` accepted.
+pub fn value_matches(filter: &PropertyFilter, value: Option<&PropertyValue>) -> bool {
+    let (value, expected) = match (filter.operator, value, filter.value.as_ref()) {
+        (PropertyOperator::IsSet, value, _) => return value.is_some(),
+        (PropertyOperator::IsNotSet, value, _) => return value.is_none(),
+        (operator, None, _) => return operator == PropertyOperator::NotEquals,
+        (_, Some(_), None) => return false,
+        (_, Some(value), Some(expected)) => (value, expected),
+    };
+    match (filter.operator, value, expected) {
+        (PropertyOperator::Equals, _, _) => value == expected,
+        (PropertyOperator::NotEquals, _, _) => value != expected,
+        (PropertyOperator::LessThan, PropertyValue::Date(a), PropertyValue::Date(b)) => a < b,
+        (PropertyOperator::GreaterThan, PropertyValue::Date(a), PropertyValue::Date(b)) => a > b,
+        (PropertyOperator::LessThan, PropertyValue::Number(a), PropertyValue::Number(b)) => a < b,
+        (PropertyOperator::GreaterThan, PropertyValue::Number(a), PropertyValue::Number(b)) => a > b,
+        (PropertyOperator::Contains, PropertyValue::Text(text), PropertyValue::Text(needle)) => {
+            text.to_lowercase().contains(&needle.to_lowercase())
+        }
+        _ => false,
+    }
+}
+
+fn filter_value<'a>(filter: &'a PropertyFilter, definition: &PropertyDefinition) -> AppResult<&'a PropertyValue> {
+    let value = filter.value.as_ref().ok_or_else(|| {
+        AppError::InvalidFormat(format!("Filter on property '{}' needs a value", definition.name))
+    })?;
+    if value.property_type() != definition.property_type {
+        return Err(AppError::InvalidFormat(format!(
+            "Property '{}' holds {} values, not {}",
+            definition.name,
+            definition.property_type.as_str(),
+            value.property_type().as_str()
+        )));
+    }
+    Ok(value)
+}
+
+fn check_operator(operator: PropertyOperator, definition: &PropertyDefinition) -> AppResult<()> {
+    let applies = matches!(
+        (operator, definition.property_type),
+        (PropertyOperator::Equals | PropertyOperator::NotEquals | PropertyOperator::IsSet | PropertyOperator::IsNotSet, _)
+            | (PropertyOperator::LessThan | PropertyOperator::GreaterThan, PropertyType::Number | PropertyType::Date)
+            | (PropertyOperator::Contains, PropertyType::Text)
+    );
+    if !applies {
+        return Err(AppError::InvalidOperation(format!(
+            "{:?} doesn't apply to {} property '{}'",
+            operator,
+            definition.property_type.as_str(),
+            definition.name
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn definition(property_type: PropertyType, options: &[&str]) -> PropertyDefinition {
+        PropertyDefinition {
+            name: "status".to_string(),
+            property_type,
+            options: options.iter().map(|o| o.to_string()).collect(),
+            created_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_normalize_name_and_options() {
+        assert_eq!(normalize_name("  Due   Date "), Some("due date".to_string()));
+        assert_eq!(normalize_name("   "), None);
+        assert_eq!(normalize_name(&"x".repeat(65)), None);
+        let options = vec![" Todo ".to_string(), "".to_string(), "Done".to_string(), "Todo".to_string()];
+        assert_eq!(normalize_options(&options), vec!["Todo", "Done"]);
+    }
+
+    #[test]
+    fn test_validate_checks_type_and_options() {
+        let select = definition(PropertyType::Select, &["Todo", "Done"]);
+        assert!(validate(&select, &PropertyValue::Select("Done".to_string())).is_ok());
+        assert!(validate(&select, &PropertyValue::Select("Blocked".to_string())).is_err());
+        assert!(validate(&select, &PropertyValue::Text("Done".to_string())).is_err());
+        let number = definition(PropertyType::Number, &[]);
+        assert!(validate(&number, &PropertyValue::Number(3.5)).is_ok());
+        assert!(validate(&number, &PropertyValue::Number(f64::NAN)).is_err());
+    }
+
+    #[test]
+    fn test_columns_round_trip() {
+        let date = PropertyValue::Date(NaiveDate::from_ymd_opt(2024, 3, 9).unwrap());
+        let (text, number) = to_columns(&date);
+        assert_eq!(text.as_deref(), Some("2024-03-09"));
+        assert_eq!(from_columns(PropertyType::Date, text, number).unwrap(), date);
+        let (text, number) = to_columns(&PropertyValue::Number(2.0));
+        assert_eq!(from_columns(PropertyType::Number, text, number).unwrap(), PropertyValue::Number(2.0));
+        assert!(from_columns(PropertyType::Text, None, None).is_err());
+    }
+
+    #[test]
+    fn test_filter_condition() {
+        let number = definition(PropertyType::Number, &[]);
+        let filter = PropertyFilter { name: "status".to_string(), operator: PropertyOperator::GreaterThan, value: Some(PropertyValue::Number(3.0)) };
+        let (sql, binds) = filter_condition(&filter, &number).unwrap().unwrap();
+        assert!(sql.starts_with("EXISTS (") && sql.contains("pp.number_value > ?"));
+        assert!(matches!(binds.as_slice(), [StoredValue::Text(name), StoredValue::Real(v)] if name == "status" && *v == 3.0));
+        assert!(!compares_text(&filter, &number));
+
+        let select = definition(PropertyType::Select, &["Todo"]);
+        let filter = PropertyFilter { name: "status".to_string(), operator: PropertyOperator::NotEquals, value: Some(PropertyValue::Select("Todo".to_string())) };
+        assert!(filter_condition(&filter, &select).unwrap().is_none());
+        assert!(compares_text(&filter, &select));
+        let filter = PropertyFilter { name: "status".to_string(), operator: PropertyOperator::LessThan, value: Some(PropertyValue::Select("Todo".to_string())) };
+        assert!(filter_condition(&filter, &select).is_err());
+        let filter = PropertyFilter { name: "status".to_string(), operator: PropertyOperator::IsSet, value: None };
+        assert_eq!(filter_condition(&filter, &select).unwrap().unwrap().1.len(), 1);
+    }
+
+    #[test]
+    fn test_matches_compares_decrypted_values() {
+        let filter = |operator, value| PropertyFilter { name: "status".to_string(), operator, value: Some(value) };
+        let text = PropertyValue::Text("Waiting on Legal".to_string());
+        assert!(value_matches(&filter(PropertyOperator::Contains, PropertyValue::Text("legal".to_string())), Some(&text)));
+        assert!(!value_matches(&filter(PropertyOperator::Equals, PropertyValue::Text("legal".to_string())), Some(&text)));
+        assert!(value_matches(&filter(PropertyOperator::NotEquals, PropertyValue::Text("legal".to_string())), None));
+
+        let due = PropertyValue::Date(NaiveDate::from_ymd_opt(2024, 3, 9).unwrap());
+        let later = PropertyValue::Date(NaiveDate::from_ymd_opt(2024, 4, 1).unwrap());
+        assert!(value_matches(&filter(PropertyOperator::LessThan, later.clone()), Some(&due)));
+        assert!(!value_matches(&filter(PropertyOperator::GreaterThan, later), Some(&due)));
+    }
+}
@@ -0,0 +1,170 @@
+use std::collections::HashSet;
+use crate::{
+    AppError, AppResult,
+    models::{AnswerMethod, PageAnswer, SearchMatchField, SupportingSpan},
+    database::{tokenize, Database},
+    ai::AIService,
+};
+
+/// Question words and fillers that say nothing about where the answer is.
+const QUESTION_STOP_WORDS: &[&str] = &[
+    "what", "when", "where", "who", "whom", "why", "how", "which", "is", "are", "was", "were",
+    "do", "does", "did", "the", "a", "an", "of", "to", "in", "on", "at", "for", "and", "or",
+    "my", "i", "we", "it", "this", "that", "with", "about", "there", "be", "can", "should",
+];
+
+/// A text the answer may come from: the page content or one of its voice transcriptions.
+struct Source<'a> {
+    field: SearchMatchField,
+    transcription_index: Option<usize>,
+    text: &'a str,
+}
+
+/// Answers `question` from one page's content and voice transcriptions. With a local
+/// language model the answer is generated and the sentence it quotes is highlighted;
+/// otherwise the best matching sentence is the answer.
+pub async fn ask_page(database: &Database, ai_service: &AIService, page_id: &str, question: &str) -> AppResult<PageAnswer> {
+    let page = database.get_page(page_id).await?
+        .ok_or_else(|| AppError::NotFound(format!("Page with id {} not found", page_id)))?;
+    let transcriptions = database.get_page_transcriptions(page_id).await?;
+
+    let mut sources = vec![Source { field: SearchMatchField::Content, transcription_index: None, text: &page.content }];
+    sources.extend(transcriptions.iter().enumerate().map(|(index, text)| Source {
+        field: SearchMatchField::Transcription,
+        transcription_index: Some(index),
+        text,
+    }));
+
+    if ai_service.is_llm_available() {
+        let context: Vec<&str> = sources.iter().map(|source| source.text).collect();
+        let answer = ai_service.answer_from_text(question, &context.join("\n\n")).await?;
+        return Ok(match answer {
+            Some((answer, quote)) => PageAnswer {
+                answer: Some(answer),
+                span: quote.and_then(|quote| find_quote(&sources, &quote)).or_else(|| best_span(question, &sources)),
+                method: AnswerMethod::Llm,
+            },
+            None => PageAnswer { answer: None, span: None, method: AnswerMethod::Llm },
+        });
+    }
+
+    let span = best_span(question, &sources);
+    Ok(PageAnswer {
+        answer: span.as_ref().map(|span| span.text.clone()),
+        span,
+        method: AnswerMethod::Extractive,
+    })
+}
+
+/// The sentence sharing the most question terms, preferring shorter sentences on ties.
+fn best_span(question: &str, sources: &[Source]) -> Option<SupportingSpan> {
+    let terms: HashSet<String> = tokenize(question)
+        .into_iter()
+        .filter(|term| !QUESTION_STOP_WORDS.contains(&term.as_str()))
+        .collect();
+    if terms.is_empty() {
+        return None;
+    }
+
+    let mut best: Option<(usize, usize, SupportingSpan)> = None;
+    for source in sources {
+        for (start, end) in sentences(source.text) {
+            let text = &source.text[start..end];
+            let tokens: HashSet<String> = tokenize(text).into_iter().collect();
+            let score = terms.iter().filter(|term| tokens.contains(*term)).count();
+            if score == 0 {
+                continue;
+            }
+            let better = match &best {
+                Some((best_score, best_len, _)) => score > *best_score || (score == *best_score && text.len() < *best_len),
+                None => true,
+            };
+            if better {
+                best = Some((score, text.len(), span(source, start, end)));
+            }
+        }
+    }
+    best.map(|(_, _, span)| span)
+}
+
+/// Where the model's quote appears, ignoring ASCII case.
+fn find_quote(sources: &[Source], quote: &str) -> Option<SupportingSpan> {
+    let needle = quote.to_ascii_lowercase();
+    sources.iter().find_map(|source| {
+        let start = source.text.to_ascii_lowercase().find(&needle)?;
+        Some(span(source, start, start + needle.len()))
+    })
+}
+
+fn span(source: &Source, start: usize, end: usize) -> SupportingSpan {
+    SupportingSpan {
+        field: source.field,
+        transcription_index: source.transcription_index,
+        start,
+        end,
+        text: source.text[start..end].to_string(),
+    }
+}
+
+/// Byte ranges of sentences, split at line breaks and at sentence punctuation followed by
+/// whitespace, with surrounding whitespace trimmed.
+fn sentences(text: &str) -> Vec<(usize, usize)> {
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((index, c)) = chars.next() {
+        let next_is_space = chars.peek().map(|(_, next)| next.is_whitespace()).unwrap_or(true);
+        let end = match c {
+            '\n' => Some(index),
+            '.' | '!' | '?' if next_is_space => Some(index + 1),
+            _ => None,
+        };
+        if let Some(end) = end {
+            ranges.push((start, end));
+            start = end;
+        }
+    }
+    ranges.push((start, text.len()));
+
+    ranges
+        .into_iter()
+        .filter_map(|(start, end)| {
+            let slice = &text[start..end];
+            let trimmed_start = start + (slice.len() - slice.trim_start().len());
+            let trimmed_end = end - (slice.len() - slice.trim_end().len());
+            (trimmed_start < trimmed_end).then_some((trimmed_start, trimmed_end))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sources<'a>(content: &'a str, transcription: &'a str) -> Vec<Source<'a>> {
+        vec![
+            Source { field: SearchMatchField::Content, transcription_index: None, text: content },
+            Source { field: SearchMatchField::Transcription, transcription_index: Some(0), text: transcription },
+        ]
+    }
+
+    #[test]
+    fn test_best_span_finds_supporting_sentence() {
+        let content = "# Launch\nThe team met on Monday. The launch date moved to March 3. Budget is unchanged.";
+        let transcription = "um so the launch is in March I think";
+        let span = best_span("When is the launch date?", &sources(content, transcription)).unwrap();
+
+        assert_eq!(span.field, SearchMatchField::Content);
+        assert_eq!(span.text, "The launch date moved to March 3.");
+        assert_eq!(&content[span.start..span.end], span.text);
+        assert!(best_span("What is it?", &sources(content, transcription)).is_none());
+    }
+
+    #[test]
+    fn test_find_quote_in_transcription() {
+        let span = find_quote(&sources("Notes", "Call Dana about the Lease."), "call dana about the lease.").unwrap();
+        assert_eq!(span.field, SearchMatchField::Transcription);
+        assert_eq!(span.transcription_index, Some(0));
+        assert_eq!(span.text, "Call Dana about the Lease.");
+    }
+}
@@ -0,0 +1,163 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tauri::{AppHandle, Emitter};
+use tokio::sync::RwLock;
+use uuid::Uuid;
+use crate::{
+    AppError, AppResult,
+    models::{EmbeddingModel, EmbeddingModelCount, EmbeddingModelStatus, EmbeddingOwner, ReindexJob, ReindexProgress},
+    database::Database,
+    ai::{content_hash, AIService},
+};
+
+pub const REINDEX_PROGRESS_EVENT: &str = "reindex-progress";
+
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Regenerates embeddings for every note and page in the background and returns
+/// immediately, or with `stale_only` just those embedded by a different model.
+/// Progress is reported through `reindex-progress` events; only one re-index runs at a
+/// time. Locks are taken per item so editing stays responsive meanwhile.
+pub async fn spawn_reindex(
+    app: AppHandle,
+    database: Arc<RwLock<Database>>,
+    ai_service: Arc<RwLock<AIService>>,
+    stale_only: bool,
+) -> AppResult<ReindexJob> {
+    let model = ai_service.read().await.embedding_model_name()
+        .ok_or_else(|| AppError::ModelNotFound("No embedding model is loaded".to_string()))?;
+    let running = RunningReindex::start()?;
+
+    let ids = {
+        let database = database.read().await;
+        if stale_only {
+            database.get_stale_embedding_ids(model).await?
+        } else {
+            database.get_embeddable_ids().await?
+        }
+    };
+    let job = ReindexJob {
+        job_id: Uuid::new_v4().to_string(),
+        total: ids.len(),
+    };
+    let job_id = job.job_id.clone();
+
+    tauri::async_runtime::spawn(async move {
+        // Held until the task ends, even if it panics
+        let _running = running;
+        let mut progress = ReindexProgress {
+            job_id,
+            done: 0,
+            total: ids.len(),
+            current_title: String::new(),
+            failed: 0,
+            finished: false,
+        };
+
+        for (id, owner) in ids {
+            match reindex_one(&database, &ai_service, &id, owner).await {
+                Ok(Some(title)) => progress.current_title = title,
+                Ok(None) => {} // Deleted since the job started
+                Err(e) => {
+                    tracing::warn!("Failed to re-index {}: {}", id, e);
+                    progress.failed += 1;
+                }
+            }
+            progress.done += 1;
+            let _ = app.emit(REINDEX_PROGRESS_EVENT, &progress);
+        }
+
+        progress.finished = true;
+        let _ = app.emit(REINDEX_PROGRESS_EVENT, &progress);
+        tracing::info!("Re-indexed {} embeddings ({} failed)", progress.done - progress.failed, progress.failed);
+    });
+
+    Ok(job)
+}
+
+/// Marks a re-index as running until dropped.
+struct RunningReindex;
+
+impl RunningReindex {
+    fn start() -> AppResult<Self> {
+        if RUNNING.swap(true, Ordering::SeqCst) {
+            return Err(AppError::InvalidOperation("An embedding re-index is already running".to_string()));
+        }
+        Ok(Self)
+    }
+}
+
+impl Drop for RunningReindex {
+    fn drop(&mut self) {
+        RUNNING.store(false, Ordering::SeqCst);
+    }
+}
+
+pub fn is_running() -> bool {
+    RUNNING.load(Ordering::SeqCst)
+}
+
+/// Stored embeddings compared against `model`. Anything from another model or with another
+/// dimension is stale and ignored by semantic search until migrated.
+pub async fn embedding_model_status(database: &Database, model: &EmbeddingModel) -> AppResult<EmbeddingModelStatus> {
+    let models = database.get_embedding_model_counts().await?;
+    Ok(EmbeddingModelStatus {
+        current_model: model.model_name().to_string(),
+        current_dimension: model.embedding_dimension(),
+        stale: count_stale(&models, model),
+        models,
+    })
+}
+
+fn count_stale(models: &[EmbeddingModelCount], model: &EmbeddingModel) -> usize {
+    models
+        .iter()
+        .filter(|m| m.model != model.model_name() || m.dimension != model.embedding_dimension())
+        .map(|m| m.count)
+        .sum()
+}
+
+/// Returns the title of the re-indexed note or page, or `None` if it no longer exists.
+async fn reindex_one(
+    database: &Arc<RwLock<Database>>,
+    ai_service: &Arc<RwLock<AIService>>,
+    id: &str,
+    owner: EmbeddingOwner,
+) -> AppResult<Option<String>> {
+    let (title, content) = {
+        let database = database.read().await;
+        let item = match owner {
+            EmbeddingOwner::Note => database.get_note(id).await?.map(|n| (n.title, n.content)),
+            EmbeddingOwner::Page => database.get_page(id).await?.map(|p| (p.title, p.content)),
+        };
+        match item {
+            Some(item) => item,
+            None => return Ok(None),
+        }
+    };
+
+    let ai_service = ai_service.read().await;
+    let model = ai_service.embedding_model_name()
+        .ok_or_else(|| AppError::ModelNotFound("No embedding model is loaded".to_string()))?;
+    let embedding = ai_service.generate_embeddings(&content).await?;
+    database.read().await.store_embedding(id, owner, model, &content_hash(&content), &embedding).await?;
+    Ok(Some(title))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_count_stale() {
+        let count = |model: &str, dimension, count| EmbeddingModelCount { model: model.to_string(), dimension, count };
+        let models = vec![
+            count("all-MiniLM-L6-v2", 384, 10),
+            count("multilingual-e5-small", 384, 4),
+            count("all-MiniLM-L6-v2", 768, 1),
+        ];
+        assert_eq!(count_stale(&models, &EmbeddingModel::MiniLM), 5);
+        assert_eq!(count_stale(&models, &EmbeddingModel::E5), 11);
+        assert_eq!(count_stale(&[], &EmbeddingModel::BGE), 0);
+    }
+}
@@ -0,0 +1,357 @@
+use std::io::Cursor;
+use image::{GrayImage, ImageFormat, Luma};
+use crate::{
+    AppError, AppResult,
+    models::{MediaAttachment, UploadMediaRequest},
+    database::Database,
+    ai::AIService,
+};
+
+/// Longest side of the downscaled copy used to find the page outline.
+const DETECTION_SIZE: u32 = 800;
+/// The detected outline must cover at least this share of the photo to be trusted.
+const MIN_PAGE_AREA_RATIO: f64 = 0.2;
+/// Percentiles clipped when stretching contrast.
+const CONTRAST_CLIP: f64 = 0.01;
+
+type Point = (f64, f64);
+
+/// Turns a photo of a paper document into a flattened, high-contrast "scan" stored as a new
+/// attachment next to the original, with OCR text in its metadata when Tesseract is available.
+pub async fn process_document_photo(database: &Database, ai_service: &AIService, media_id: &str) -> AppResult<MediaAttachment> {
+    let original = database.get_media_attachment(media_id).await?
+        .ok_or_else(|| AppError::NotFound(format!("Media attachment with id {} not found", media_id)))?;
+
+    if !original.mime_type.starts_with("image/") {
+        return Err(AppError::InvalidFormat(format!("{} is not an image", original.original_filename)));
+    }
+
+    let file_data = original.file_data;
+    let (png_data, width, height) = tokio::task::spawn_blocking(move || scan(&file_data))
+        .await
+        .map_err(|e| AppError::Unknown(format!("Document scan task failed: {}", e)))??;
+
+    let extracted_text = ai_service.ocr_image(&png_data).await.unwrap_or_else(|e| {
+        tracing::warn!("OCR failed for scanned document {}: {}", media_id, e);
+        None
+    });
+
+    let stem = original.original_filename
+        .rsplit_once('.')
+        .map(|(stem, _)| stem.to_string())
+        .unwrap_or(original.original_filename.clone());
+
+    let mut scanned = database.upload_media(UploadMediaRequest {
+        page_id: original.page_id,
+        note_id: original.note_id,
+        filename: format!("{}-scan.png", stem),
+        mime_type: "image/png".to_string(),
+        file_data: png_data,
+        position_in_content: original.position_in_content,
+    }).await?;
+
+    scanned.metadata.width = Some(width);
+    scanned.metadata.height = Some(height);
+    scanned.metadata.alt_text = original.metadata.alt_text;
+    scanned.metadata.extracted_text = extracted_text;
+    database.update_media_metadata(&scanned.id, &scanned.metadata).await?;
+
+    Ok(scanned)
+}
+
+fn scan(data: &[u8]) -> AppResult<(Vec<u8>, u32, u32)> {
+    let photo = image::load_from_memory(data)
+        .map_err(|e| AppError::InvalidFormat(format!("Failed to decode image: {}", e)))?
+        .to_luma8();
+
+    let corners = detect_page(&photo).unwrap_or_else(|| full_frame(&photo));
+    let mut page = warp_perspective(&photo, &corners);
+    stretch_contrast(&mut page);
+
+    let (width, height) = page.dimensions();
+    let mut png_data = Vec::new();
+    page.write_to(&mut Cursor::new(&mut png_data), ImageFormat::Png)
+        .map_err(|e| AppError::InvalidFormat(format!("Failed to encode scanned image: {}", e)))?;
+
+    Ok((png_data, width, height))
+}
+
+/// Finds the page outline as [top-left, top-right, bottom-right, bottom-left] in photo
+/// coordinates. Paper is assumed brighter than the surface it lies on: edges are found with a
+/// Sobel filter, the page interior is separated with an Otsu threshold, and the corners are the
+/// extreme points of the bright region that sit on an edge.
+fn detect_page(photo: &GrayImage) -> Option<[Point; 4]> {
+    let (width, height) = photo.dimensions();
+    let scale = (DETECTION_SIZE as f64 / width.max(height) as f64).min(1.0);
+    let small = image::imageops::resize(
+        photo,
+        ((width as f64 * scale).round() as u32).max(1),
+        ((height as f64 * scale).round() as u32).max(1),
+        image::imageops::FilterType::Triangle,
+    );
+    let small = image::imageops::blur(&small, 2.0);
+
+    let threshold = otsu_threshold(&small);
+    let edges = sobel_magnitude(&small);
+    let edge_threshold = otsu_threshold(&edges);
+
+    let (sw, sh) = small.dimensions();
+    let mut extremes: Option<[(i64, Point); 4]> = None;
+    let mut bright_pixels = 0u64;
+
+    for y in 0..sh {
+        for x in 0..sw {
+            if small.get_pixel(x, y)[0] <= threshold {
+                continue;
+            }
+            bright_pixels += 1;
+            if edges.get_pixel(x, y)[0] <= edge_threshold {
+                continue;
+            }
+
+            let (xi, yi) = (x as i64, y as i64);
+            let point = (x as f64, y as f64);
+            // Scores for which corner each point is most extreme towards
+            let scores = [-(xi + yi), xi - yi, xi + yi, yi - xi];
+            let current = extremes.get_or_insert([(scores[0], point), (scores[1], point), (scores[2], point), (scores[3], point)]);
+            for (slot, score) in current.iter_mut().zip(scores) {
+                if score > slot.0 {
+                    *slot = (score, point);
+                }
+            }
+        }
+    }
+
+    if (bright_pixels as f64) < (sw as f64 * sh as f64) * MIN_PAGE_AREA_RATIO {
+        return None;
+    }
+
+    let corners = extremes?.map(|(_, (x, y))| (x / scale, y / scale));
+    if quad_area(&corners) < (width as f64 * height as f64) * MIN_PAGE_AREA_RATIO {
+        return None;
+    }
+    Some(corners)
+}
+
+fn full_frame(photo: &GrayImage) -> [Point; 4] {
+    let (w, h) = (photo.width() as f64 - 1.0, photo.height() as f64 - 1.0);
+    [(0.0, 0.0), (w, 0.0), (w, h), (0.0, h)]
+}
+
+fn otsu_threshold(image: &GrayImage) -> u8 {
+    let mut histogram = [0u64; 256];
+    for pixel in image.pixels() {
+        histogram[pixel[0] as usize] += 1;
+    }
+
+    let total = image.pixels().len() as f64;
+    let weighted_sum: f64 = histogram.iter().enumerate().map(|(i, &c)| i as f64 * c as f64).sum();
+
+    let mut background_weight = 0.0;
+    let mut background_sum = 0.0;
+    let mut best = (0u8, 0.0f64);
+
+    for (level, &count) in histogram.iter().enumerate() {
+        background_weight += count as f64;
+        if background_weight == 0.0 {
+            continue;
+        }
+        let foreground_weight = total - background_weight;
+        if foreground_weight == 0.0 {
+            break;
+        }
+
+        background_sum += level as f64 * count as f64;
+        let background_mean = background_sum / background_weight;
+        let foreground_mean = (weighted_sum - background_sum) / foreground_weight;
+        let variance = background_weight * foreground_weight * (background_mean - foreground_mean).powi(2);
+        if variance > best.1 {
+            best = (level as u8, variance);
+        }
+    }
+
+    best.0
+}
+
+fn sobel_magnitude(image: &GrayImage) -> GrayImage {
+    let (width, height) = image.dimensions();
+    let mut edges = GrayImage::new(width, height);
+    if width < 3 || height < 3 {
+        return edges;
+    }
+
+    let at = |x: u32, y: u32| image.get_pixel(x, y)[0] as f64;
+    for y in 1..height - 1 {
+        for x in 1..width - 1 {
+            let gx = at(x + 1, y - 1) + 2.0 * at(x + 1, y) + at(x + 1, y + 1)
+                - at(x - 1, y - 1) - 2.0 * at(x - 1, y) - at(x - 1, y + 1);
+            let gy = at(x - 1, y + 1) + 2.0 * at(x, y + 1) + at(x + 1, y + 1)
+                - at(x - 1, y - 1) - 2.0 * at(x, y - 1) - at(x + 1, y - 1);
+            let magnitude = (gx * gx + gy * gy).sqrt().min(255.0);
+            edges.put_pixel(x, y, Luma([magnitude as u8]));
+        }
+    }
+    edges
+}
+
+fn quad_area(corners: &[Point; 4]) -> f64 {
+    let mut area = 0.0;
+    for i in 0..4 {
+        let (x1, y1) = corners[i];
+        let (x2, y2) = corners[(i + 1) % 4];
+        area += x1 * y2 - x2 * y1;
+    }
+    area.abs() / 2.0
+}
+
+fn distance(a: Point, b: Point) -> f64 {
+    ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)).sqrt()
+}
+
+/// Maps the quadrilateral onto an upright rectangle sized from its longest edges.
+fn warp_perspective(photo: &GrayImage, corners: &[Point; 4]) -> GrayImage {
+    let [tl, tr, br, bl] = *corners;
+    let width = distance(tl, tr).max(distance(bl, br)).round().max(1.0) as u32;
+    let height = distance(tl, bl).max(distance(tr, br)).round().max(1.0) as u32;
+
+    let (w, h) = ((width - 1).max(1) as f64, (height - 1).max(1) as f64);
+    let destination = [(0.0, 0.0), (w, 0.0), (w, h), (0.0, h)];
+
+    let homography = match homography(&destination, corners) {
+        Some(h) => h,
+        None => return photo.clone(),
+    };
+
+    let mut output = GrayImage::new(width, height);
+    for y in 0..height {
+        for x in 0..width {
+            let (sx, sy) = apply_homography(&homography, (x as f64, y as f64));
+            output.put_pixel(x, y, Luma([sample_bilinear(photo, sx, sy)]));
+        }
+    }
+    output
+}
+
+/// Solves for the 3x3 homography (with h33 = 1) taking each `from` point to its `to` point.
+fn homography(from: &[Point; 4], to: &[Point; 4]) -> Option<[f64; 9]> {
+    let mut system = [[0.0f64; 9]; 8];
+    for i in 0..4 {
+        let (x, y) = from[i];
+        let (u, v) = to[i];
+        system[2 * i] = [x, y, 1.0, 0.0, 0.0, 0.0, -u * x, -u * y, u];
+        system[2 * i + 1] = [0.0, 0.0, 0.0, x, y, 1.0, -v * x, -v * y, v];
+    }
+
+    // Gaussian elimination with partial pivoting
+    for column in 0..8 {
+        let pivot = (column..8).max_by(|&a, &b| {
+            system[a][column].abs().partial_cmp(&system[b][column].abs()).unwrap_or(std::cmp::Ordering::Equal)
+        })?;
+        if system[pivot][column].abs() < 1e-12 {
+            return None;
+        }
+        system.swap(column, pivot);
+
+        for row in 0..8 {
+            if row != column {
+                let factor = system[row][column] / system[column][column];
+                for k in column..9 {
+                    system[row][k] -= factor * system[column][k];
+                }
+            }
+        }
+    }
+
+    let mut h = [0.0; 9];
+    for i in 0..8 {
+        h[i] = system[i][8] / system[i][i];
+    }
+    h[8] = 1.0;
+    Some(h)
+}
+
+fn apply_homography(h: &[f64; 9], (x, y): Point) -> Point {
+    let w = h[6] * x + h[7] * y + h[8];
+    ((h[0] * x + h[1] * y + h[2]) / w, (h[3] * x + h[4] * y + h[5]) / w)
+}
+
+fn sample_bilinear(image: &GrayImage, x: f64, y: f64) -> u8 {
+    let max_x = image.width() as f64 - 1.0;
+    let max_y = image.height() as f64 - 1.0;
+    let (x, y) = (x.clamp(0.0, max_x), y.clamp(0.0, max_y));
+
+    let (x0, y0) = (x.floor() as u32, y.floor() as u32);
+    let (x1, y1) = ((x0 + 1).min(max_x as u32), (y0 + 1).min(max_y as u32));
+    let (fx, fy) = (x - x0 as f64, y - y0 as f64);
+
+    let p = |px: u32, py: u32| image.get_pixel(px, py)[0] as f64;
+    let top = p(x0, y0) * (1.0 - fx) + p(x1, y0) * fx;
+    let bottom = p(x0, y1) * (1.0 - fx) + p(x1, y1) * fx;
+    (top * (1.0 - fy) + bottom * fy).round() as u8
+}
+
+/// Stretches levels so the darkest and brightest 1% of pixels become black and white.
+fn stretch_contrast(image: &mut GrayImage) {
+    let mut histogram = [0u64; 256];
+    for pixel in image.pixels() {
+        histogram[pixel[0] as usize] += 1;
+    }
+
+    let clip = (image.pixels().len() as f64 * CONTRAST_CLIP) as u64;
+    let low = clipped_level(&histogram, 0..256usize, clip);
+    let high = clipped_level(&histogram, (0..256usize).rev(), clip);
+
+    if high <= low {
+        return;
+    }
+    for pixel in image.pixels_mut() {
+        let value = ((pixel[0] as f64 - low) / (high - low) * 255.0).clamp(0.0, 255.0);
+        pixel[0] = value as u8;
+    }
+}
+
+/// First level, walking in the given order, past which more than `clip` pixels have been seen.
+fn clipped_level(histogram: &[u64; 256], levels: impl Iterator<Item = usize>, clip: u64) -> f64 {
+    let mut seen = 0;
+    for level in levels {
+        seen += histogram[level];
+        if seen > clip {
+            return level as f64;
+        }
+    }
+    0.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_homography_maps_corners() {
+        let from = [(0.0, 0.0), (100.0, 0.0), (100.0, 50.0), (0.0, 50.0)];
+        let to = [(10.0, 5.0), (120.0, 15.0), (110.0, 80.0), (5.0, 70.0)];
+        let h = homography(&from, &to).unwrap();
+
+        for (source, target) in from.iter().zip(to.iter()) {
+            let (x, y) = apply_homography(&h, *source);
+            assert!((x - target.0).abs() < 1e-6 && (y - target.1).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_otsu_splits_bimodal_image() {
+        let image = GrayImage::from_fn(10, 10, |x, _| if x < 5 { Luma([30]) } else { Luma([220]) });
+        let threshold = otsu_threshold(&image);
+        assert!((30..220).contains(&threshold));
+    }
+
+    #[test]
+    fn test_detects_bright_page_on_dark_background() {
+        let photo = GrayImage::from_fn(200, 200, |x, y| {
+            if (40..160).contains(&x) && (30..170).contains(&y) { Luma([230]) } else { Luma([20]) }
+        });
+        let [tl, _, br, _] = detect_page(&photo).unwrap();
+        assert!(distance(tl, (40.0, 30.0)) < 6.0);
+        assert!(distance(br, (159.0, 169.0)) < 6.0);
+    }
+}
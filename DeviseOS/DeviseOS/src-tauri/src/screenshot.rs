@@ -0,0 +1,95 @@
+use std::io::Cursor;
+use chrono::Utc;
+use image::{ImageFormat, RgbaImage};
+use crate::{
+    AppError, AppResult,
+    models::{CaptureRegion, CaptureScreenshotRequest, ImageAnnotation, MediaAttachment, UploadMediaRequest},
+    database::Database,
+};
+
+/// Captures a monitor (optionally cropped to a region) and stores it as a PNG attachment on the page.
+pub async fn capture_to_page(database: &Database, request: CaptureScreenshotRequest) -> AppResult<MediaAttachment> {
+    if database.get_page(&request.page_id).await?.is_none() {
+        return Err(AppError::NotFound(format!("Page with id {} not found", request.page_id)));
+    }
+
+    let monitor_index = request.monitor_index.unwrap_or(0);
+    let region = request.region;
+
+    // Screen capture APIs are blocking
+    let (png_data, width, height) = tokio::task::spawn_blocking(move || capture_png(monitor_index, region))
+        .await
+        .map_err(|e| AppError::Unknown(format!("Screenshot task failed: {}", e)))??;
+
+    let mut media = database.upload_media(UploadMediaRequest {
+        page_id: Some(request.page_id),
+        note_id: None,
+        filename: format!("screenshot-{}.png", Utc::now().format("%Y%m%d-%H%M%S")),
+        mime_type: "image/png".to_string(),
+        file_data: png_data,
+        position_in_content: request.position_in_content,
+    }).await?;
+
+    media.metadata.width = Some(width);
+    media.metadata.height = Some(height);
+    database.update_media_metadata(&media.id, &media.metadata).await?;
+
+    Ok(media)
+}
+
+/// Replaces the annotation overlays stored alongside an image attachment.
+pub async fn set_annotations(database: &Database, media_id: &str, annotations: Vec<ImageAnnotation>) -> AppResult<()> {
+    let mut metadata = database.get_media_metadata(media_id).await?
+        .ok_or_else(|| AppError::NotFound(format!("Media attachment with id {} not found", media_id)))?;
+
+    for annotation in &annotations {
+        if annotation.stroke_width <= 0.0 {
+            return Err(AppError::InvalidFormat(format!("Annotation {} has an invalid stroke width", annotation.id)));
+        }
+    }
+
+    metadata.annotations = annotations;
+    database.update_media_metadata(media_id, &metadata).await
+}
+
+fn capture_png(monitor_index: usize, region: Option<CaptureRegion>) -> AppResult<(Vec<u8>, u32, u32)> {
+    let monitors = xcap::Monitor::all()
+        .map_err(|e| AppError::PermissionDenied(format!("Screen capture unavailable: {}", e)))?;
+    let monitor = monitors
+        .get(monitor_index)
+        .ok_or_else(|| AppError::NotFound(format!("Monitor {} not found", monitor_index)))?;
+
+    let image = monitor
+        .capture_image()
+        .map_err(|e| AppError::PermissionDenied(format!("Screen capture failed: {}", e)))?;
+
+    let image = match region {
+        Some(region) => crop(&image, region)?,
+        None => image,
+    };
+
+    let (width, height) = image.dimensions();
+    let mut png_data = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut png_data), ImageFormat::Png)
+        .map_err(|e| AppError::InvalidFormat(format!("Failed to encode screenshot: {}", e)))?;
+
+    Ok((png_data, width, height))
+}
+
+fn crop(image: &RgbaImage, region: CaptureRegion) -> AppResult<RgbaImage> {
+    let (width, height) = image.dimensions();
+    let fits = region.width > 0
+        && region.height > 0
+        && region.x.saturating_add(region.width) <= width
+        && region.y.saturating_add(region.height) <= height;
+
+    if !fits {
+        return Err(AppError::InvalidOperation(format!(
+            "Region {}x{}+{}+{} is outside the {}x{} screen",
+            region.width, region.height, region.x, region.y, width, height
+        )));
+    }
+
+    Ok(image::imageops::crop_imm(image, region.x, region.y, region.width, region.height).to_image())
+}
@@ -0,0 +1,281 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use chrono::{DateTime, Utc};
+use rhai::{Dynamic, Engine, EvalAltResult, Scope};
+use tauri::{AppHandle, Manager};
+use tokio::runtime::Handle;
+use crate::{
+    AppError, AppResult, AppState,
+    models::{CreatePageRequest, Page, ScriptInfo, ScriptResult, UpdatePageRequest},
+    tags,
+};
+
+const SCRIPT_EXTENSION: &str = "rhai";
+const MAX_OPERATIONS: u64 = 5_000_000;
+const MAX_CALL_LEVELS: usize = 64;
+const MAX_STRING_SIZE: usize = 10 * 1024 * 1024;
+const MAX_ARRAY_SIZE: usize = 100_000;
+
+type ScriptFnResult<T> = Result<T, Box<EvalAltResult>>;
+
+pub struct ScriptRunner {
+    scripts_path: PathBuf,
+}
+
+impl ScriptRunner {
+    pub fn new(scripts_path: PathBuf) -> Self {
+        Self { scripts_path }
+    }
+
+    pub fn list_scripts(&self) -> AppResult<Vec<ScriptInfo>> {
+        if !self.scripts_path.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut scripts = Vec::new();
+        for entry in std::fs::read_dir(&self.scripts_path)? {
+            let path = entry?.path();
+            if !is_script_file(&path) {
+                continue;
+            }
+
+            let metadata = std::fs::metadata(&path)?;
+            scripts.push(ScriptInfo {
+                name: path.file_stem().unwrap_or_default().to_string_lossy().into_owned(),
+                size: metadata.len(),
+                modified_at: metadata.modified().ok().map(DateTime::<Utc>::from),
+                path,
+            });
+        }
+
+        scripts.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(scripts)
+    }
+
+    /// Runs `<scripts_path>/<name>.rhai` on a blocking thread. Scripts see their arguments as
+    /// the `ARGS` constant and can only touch the vault through the functions registered below,
+    /// which follow their changes with the same hooks as the matching commands.
+    pub async fn run(&self, app: AppHandle, name: &str, args: serde_json::Value) -> AppResult<ScriptResult> {
+        let path = self.script_path(name)?;
+        let source = tokio::fs::read_to_string(&path)
+            .await
+            .map_err(|_| AppError::NotFound(format!("Script {} not found", name)))?;
+
+        let name = name.to_string();
+        let handle = Handle::current();
+
+        tokio::task::spawn_blocking(move || execute_script(handle, app, name, source, args))
+            .await
+            .map_err(|e| AppError::Unknown(format!("Script task failed: {}", e)))?
+    }
+
+    fn script_path(&self, name: &str) -> AppResult<PathBuf> {
+        check_script_name(name)?;
+        Ok(self.scripts_path.join(format!("{}.{}", name, SCRIPT_EXTENSION)))
+    }
+
+    /// Resolves the external program an automation runs. Like Rhai scripts, these can only
+    /// live directly in the scripts directory, so a stored automation can't name any other
+    /// binary on the system.
+    pub fn executable_path(&self, name: &str) -> AppResult<PathBuf> {
+        check_script_name(name)?;
+        Ok(self.scripts_path.join(name))
+    }
+}
+
+fn check_script_name(name: &str) -> AppResult<()> {
+    // Script names map directly to files, so reject anything that could escape the directory
+    let valid = !name.is_empty()
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return Err(AppError::InvalidFormat(format!("Invalid script name: {}", name)));
+    }
+    Ok(())
+}
+
+fn execute_script(
+    handle: Handle,
+    app: AppHandle,
+    name: String,
+    source: String,
+    args: serde_json::Value,
+) -> AppResult<ScriptResult> {
+    let started = Instant::now();
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let engine = build_engine(handle, app, output.clone());
+
+    let mut scope = Scope::new();
+    scope.push_constant("ARGS", to_dynamic(&args)?);
+
+    let value = engine
+        .eval_with_scope::<Dynamic>(&mut scope, &source)
+        .map_err(|e| AppError::InvalidOperation(format!("Script {} failed: {}", name, e)))?;
+
+    let return_value = if value.is_unit() {
+        serde_json::Value::Null
+    } else {
+        rhai::serde::from_dynamic(&value)
+            .map_err(|e| AppError::InvalidFormat(format!("Unsupported script return value: {}", e)))?
+    };
+
+    let output = output.lock().map(|lines| lines.clone()).unwrap_or_default();
+
+    Ok(ScriptResult {
+        name,
+        output,
+        return_value,
+        duration_ms: started.elapsed().as_millis() as u64,
+    })
+}
+
+fn build_engine(handle: Handle, app: AppHandle, output: Arc<Mutex<Vec<String>>>) -> Engine {
+    let mut engine = Engine::new();
+
+    // Keep runaway scripts from hanging the app or exhausting memory
+    engine.set_max_operations(MAX_OPERATIONS);
+    engine.set_max_call_levels(MAX_CALL_LEVELS);
+    engine.set_max_string_size(MAX_STRING_SIZE);
+    engine.set_max_array_size(MAX_ARRAY_SIZE);
+    engine.set_max_map_size(MAX_ARRAY_SIZE);
+
+    let print_output = output.clone();
+    engine.on_print(move |text| {
+        if let Ok(mut lines) = print_output.lock() {
+            lines.push(text.to_string());
+        }
+    });
+    engine.on_debug(move |text, _, _| {
+        if let Ok(mut lines) = output.lock() {
+            lines.push(text.to_string());
+        }
+    });
+
+    let database = app.state::<AppState>().database.clone();
+    let (h, db) = (handle.clone(), database.clone());
+    engine.register_fn("get_notebooks", move || -> ScriptFnResult<Dynamic> {
+        let notebooks = h.block_on(async { db.read().await.get_notebooks().await }).map_err(script_error)?;
+        let notebooks: Vec<serde_json::Value> = notebooks
+            .into_iter()
+            .map(|n| serde_json::json!({ "id": n.id, "title": n.title }))
+            .collect();
+        to_dynamic(&notebooks).map_err(script_error)
+    });
+
+    let (h, db) = (handle.clone(), database.clone());
+    engine.register_fn("get_pages", move |notebook_id: &str| -> ScriptFnResult<Dynamic> {
+        let pages = h.block_on(async { db.read().await.get_pages(notebook_id, None).await }).map_err(script_error)?;
+        page_list(&pages)
+    });
+
+    let (h, db) = (handle.clone(), database.clone());
+    engine.register_fn("get_page", move |id: &str| -> ScriptFnResult<Dynamic> {
+        let page = h.block_on(async { db.read().await.get_page(id).await }).map_err(script_error)?;
+        match page {
+            Some(page) => to_dynamic(&page_value(&page, true)).map_err(script_error),
+            None => Ok(Dynamic::UNIT),
+        }
+    });
+
+    let (h, db) = (handle.clone(), database.clone());
+    engine.register_fn("find_pages", move |query: &str| -> ScriptFnResult<Dynamic> {
+        let query = query.to_lowercase();
+        let pages = h.block_on(async {
+            let database = db.read().await;
+            let mut matches = Vec::new();
+            for notebook in database.get_notebooks().await? {
+                for page in database.get_pages(&notebook.id, None).await? {
+                    if page.title.to_lowercase().contains(&query) || page.content.to_lowercase().contains(&query) {
+                        matches.push(page);
+                    }
+                }
+            }
+            Ok::<_, AppError>(matches)
+        }).map_err(script_error)?;
+        page_list(&pages)
+    });
+
+    let (h, a) = (handle.clone(), app.clone());
+    engine.register_fn("create_page", move |notebook_id: &str, title: &str, content: &str| -> ScriptFnResult<String> {
+        let request = CreatePageRequest {
+            notebook_id: notebook_id.to_string(),
+            section_id: None,
+            parent_page_id: None,
+            title: title.to_string(),
+            content: content.to_string(),
+            tags: Vec::new(),
+        };
+        let page = h.block_on(async {
+            let state = a.state::<AppState>();
+            let database = state.database.read().await;
+            let page = database.create_page(request).await?;
+            state.page_created(&database, &page).await?;
+            Ok::<_, AppError>(page)
+        }).map_err(script_error)?;
+        Ok(page.id)
+    });
+
+    let (h, a) = (handle, app);
+    engine.register_fn("add_tag", move |page_id: &str, tag: &str| -> ScriptFnResult<()> {
+        h.block_on(async {
+            let tag = tags::normalize_tag(tag)
+                .ok_or_else(|| AppError::InvalidFormat("Tag can't be empty".to_string()))?;
+            let state = a.state::<AppState>();
+            let database = state.database.read().await;
+            let page = database.get_page(page_id).await?
+                .ok_or_else(|| AppError::NotFound(format!("Page with id {} not found", page_id)))?;
+
+            if page.tags.contains(&tag) {
+                return Ok(());
+            }
+
+            let mut tags = page.tags;
+            tags.push(tag);
+            database.update_page(UpdatePageRequest {
+                id: page_id.to_string(),
+                title: None,
+                content: None,
+                tags: Some(tags),
+                order_index: None,
+                language: None,
+            }).await?;
+            state.page_updated(&database, page_id, false, None).await
+        }).map_err(script_error)
+    });
+
+    engine
+}
+
+fn page_value(page: &Page, include_content: bool) -> serde_json::Value {
+    let mut value = serde_json::json!({
+        "id": page.id,
+        "notebook_id": page.notebook_id,
+        "section_id": page.section_id,
+        "title": page.title,
+        "tags": page.tags,
+        "created_at": page.created_at.to_rfc3339(),
+        "updated_at": page.updated_at.to_rfc3339(),
+    });
+    if include_content {
+        value["content"] = serde_json::Value::String(page.content.clone());
+    }
+    value
+}
+
+fn page_list(pages: &[Page]) -> ScriptFnResult<Dynamic> {
+    let pages: Vec<serde_json::Value> = pages.iter().map(|p| page_value(p, false)).collect();
+    to_dynamic(&pages).map_err(script_error)
+}
+
+fn to_dynamic<T: serde::Serialize>(value: &T) -> AppResult<Dynamic> {
+    rhai::serde::to_dynamic(value)
+        .map_err(|e| AppError::InvalidFormat(format!("Failed to convert script value: {}", e)))
+}
+
+fn script_error(error: AppError) -> Box<EvalAltResult> {
+    error.to_string().into()
+}
+
+fn is_script_file(path: &Path) -> bool {
+    path.extension().and_then(|e| e.to_str()) == Some(SCRIPT_EXTENSION)
+}
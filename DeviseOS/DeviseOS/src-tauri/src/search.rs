@@ -0,0 +1,392 @@
+//! Query syntax shared by note and notebook search.
+//!
+//! ```text
+//! tag:project AND (title:"roadmap" OR meeting) -draft
+//! ```
+//!
+//! Terms are joined with `AND` when no operator is given; `NOT` or a leading `-` negates.
+//! Supported fields are `tag`, `title`, `content`, `notebook` and `section` (ids); an unknown
+//! `field:value` is searched as plain text. Content is encrypted at rest, so only
+//! metadata-only parts of a query compile to SQL; the full query is always re-checked
+//! against the decrypted document.
+
+use crate::{AppError, AppResult, tags};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Query {
+    Term(String),
+    Field(SearchField, String),
+    And(Vec<Query>),
+    Or(Vec<Query>),
+    Not(Box<Query>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchField {
+    Tag,
+    Title,
+    Content,
+    Notebook,
+    Section,
+}
+
+impl SearchField {
+    fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "tag" | "tags" => Some(Self::Tag),
+            "title" => Some(Self::Title),
+            "content" | "body" => Some(Self::Content),
+            "notebook" => Some(Self::Notebook),
+            "section" => Some(Self::Section),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchTable {
+    Notes,
+    Pages,
+}
+
+/// Decrypted view of a note or page that queries are evaluated against.
+pub struct SearchDocument<'a> {
+    pub title: &'a str,
+    pub content: &'a str,
+    pub tags: &'a [String],
+    pub notebook_id: Option<&'a str>,
+    pub section_id: Option<&'a str>,
+    pub extra_texts: &'a [&'a str], // Transcriptions and attachment text, matched by plain terms
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    LParen,
+    RParen,
+    And,
+    Or,
+    Not,
+    Term { field: Option<String>, value: String },
+}
+
+/// Parses a query string; `None` means the query is empty and everything matches.
+pub fn parse(input: &str) -> AppResult<Option<Query>> {
+    let tokens = lex(input)?;
+    if tokens.is_empty() {
+        return Ok(None);
+    }
+
+    let mut parser = Parser { tokens, position: 0 };
+    let query = parser.parse_or()?;
+    if parser.position < parser.tokens.len() {
+        return Err(AppError::InvalidFormat("Unexpected ')' in search query".to_string()));
+    }
+    Ok(Some(query))
+}
+
+fn lex(input: &str) -> AppResult<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token::LParen);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::RParen);
+            }
+            '-' => {
+                chars.next();
+                tokens.push(Token::Not);
+            }
+            '"' => {
+                chars.next();
+                tokens.push(Token::Term { field: None, value: read_quoted(&mut chars)? });
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || c == '(' || c == ')' {
+                        break;
+                    }
+                    chars.next();
+                    if c == ':' && !word.is_empty() {
+                        // field:value or field:"quoted value"
+                        let value = if chars.peek() == Some(&'"') {
+                            chars.next();
+                            read_quoted(&mut chars)?
+                        } else {
+                            read_word(&mut chars)
+                        };
+                        tokens.push(Token::Term { field: Some(std::mem::take(&mut word)), value });
+                        break;
+                    }
+                    word.push(c);
+                }
+
+                match word.as_str() {
+                    "" => {}
+                    "AND" => tokens.push(Token::And),
+                    "OR" => tokens.push(Token::Or),
+                    "NOT" => tokens.push(Token::Not),
+                    _ => tokens.push(Token::Term { field: None, value: word }),
+                }
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn read_quoted(chars: &mut std::iter::Peekable<std::str::Chars<'_>>) -> AppResult<String> {
+    let mut value = String::new();
+    for c in chars.by_ref() {
+        if c == '"' {
+            return Ok(value);
+        }
+        value.push(c);
+    }
+    Err(AppError::InvalidFormat("Unterminated quote in search query".to_string()))
+}
+
+fn read_word(chars: &mut std::iter::Peekable<std::str::Chars<'_>>) -> String {
+    let mut value = String::new();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() || c == '(' || c == ')' {
+            break;
+        }
+        value.push(c);
+        chars.next();
+    }
+    value
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn parse_or(&mut self) -> AppResult<Query> {
+        let mut branches = vec![self.parse_and()?];
+        while self.peek() == Some(&Token::Or) {
+            self.position += 1;
+            branches.push(self.parse_and()?);
+        }
+        Ok(if branches.len() == 1 { branches.remove(0) } else { Query::Or(branches) })
+    }
+
+    fn parse_and(&mut self) -> AppResult<Query> {
+        let mut parts = vec![self.parse_unary()?];
+        loop {
+            match self.peek() {
+                Some(Token::And) => {
+                    self.position += 1;
+                    parts.push(self.parse_unary()?);
+                }
+                // Implicit AND between adjacent terms
+                Some(Token::Term { .. }) | Some(Token::Not) | Some(Token::LParen) => parts.push(self.parse_unary()?),
+                _ => break,
+            }
+        }
+        Ok(if parts.len() == 1 { parts.remove(0) } else { Query::And(parts) })
+    }
+
+    fn parse_unary(&mut self) -> AppResult<Query> {
+        if self.peek() == Some(&Token::Not) {
+            self.position += 1;
+            return Ok(Query::Not(Box::new(self.parse_unary()?)));
+        }
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> AppResult<Query> {
+        let token = self.tokens.get(self.position).cloned()
+            .ok_or_else(|| AppError::InvalidFormat("Search query ends unexpectedly".to_string()))?;
+        self.position += 1;
+
+        match token {
+            Token::LParen => {
+                let inner = self.parse_or()?;
+                if self.peek() != Some(&Token::RParen) {
+                    return Err(AppError::InvalidFormat("Missing ')' in search query".to_string()));
+                }
+                self.position += 1;
+                Ok(inner)
+            }
+            Token::Term { field: Some(field), value } => Ok(match SearchField::parse(&field) {
+                Some(SearchField::Tag) => Query::Field(SearchField::Tag, tags::normalize_tag(&value).unwrap_or(value)),
+                Some(field) => Query::Field(field, value),
+                None => Query::Term(format!("{}:{}", field, value)),
+            }),
+            Token::Term { field: None, value } => Ok(Query::Term(value)),
+            other => Err(AppError::InvalidFormat(format!("Unexpected {:?} in search query", other))),
+        }
+    }
+}
+
+impl Query {
+    /// SQL for this query if it only touches unencrypted columns.
+    pub fn to_sql(&self, table: SearchTable) -> Option<(String, Vec<String>)> {
+        match self {
+            Query::Term(_) => None,
+            Query::Field(field, value) => match (field, table) {
+                (SearchField::Tag, _) => Some((
+                    "EXISTS (SELECT 1 FROM json_each(tags) WHERE json_each.value = ? COLLATE NOCASE)".to_string(),
+                    vec![value.clone()],
+                )),
+                (SearchField::Title, _) => Some(("title LIKE ?".to_string(), vec![format!("%{}%", value)])),
+                (SearchField::Notebook, SearchTable::Pages) => Some(("notebook_id = ?".to_string(), vec![value.clone()])),
+                (SearchField::Section, SearchTable::Pages) => Some(("section_id = ?".to_string(), vec![value.clone()])),
+                _ => None,
+            },
+            Query::And(parts) | Query::Or(parts) => {
+                let joiner = if matches!(self, Query::And(_)) { " AND " } else { " OR " };
+                let mut clauses = Vec::new();
+                let mut binds = Vec::new();
+                for part in parts {
+                    let (clause, part_binds) = part.to_sql(table)?;
+                    clauses.push(clause);
+                    binds.extend(part_binds);
+                }
+                Some((format!("({})", clauses.join(joiner)), binds))
+            }
+            // `NOT` of a NULL column is NULL, dropping rows `matches` would keep, e.g. pages
+            // outside any section for `-section:x`; negations are left to `matches`
+            Query::Not(_) => None,
+        }
+    }
+
+    /// The largest part of the query that can narrow rows in SQL: the whole query if it
+    /// compiles, otherwise the compilable parts of a top-level AND.
+    pub fn sql_prefilter(&self, table: SearchTable) -> Option<(String, Vec<String>)> {
+        if let Some(sql) = self.to_sql(table) {
+            return Some(sql);
+        }
+        match self {
+            Query::And(parts) => {
+                let compiled: Vec<_> = parts.iter().filter_map(|p| p.to_sql(table)).collect();
+                if compiled.is_empty() {
+                    return None;
+                }
+                let clauses: Vec<String> = compiled.iter().map(|(clause, _)| clause.clone()).collect();
+                let binds = compiled.into_iter().flat_map(|(_, binds)| binds).collect();
+                Some((format!("({})", clauses.join(" AND ")), binds))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn matches(&self, document: &SearchDocument<'_>) -> bool {
+        let contains = |text: &str, value: &str| text.to_lowercase().contains(&value.to_lowercase());
+
+        match self {
+            Query::Term(value) => {
+                contains(document.title, value)
+                    || contains(document.content, value)
+                    || document.extra_texts.iter().any(|text| contains(text, value))
+            }
+            Query::Field(SearchField::Tag, value) => document.tags.iter().any(|tag| tag.eq_ignore_ascii_case(value)),
+            Query::Field(SearchField::Title, value) => contains(document.title, value),
+            Query::Field(SearchField::Content, value) => contains(document.content, value),
+            Query::Field(SearchField::Notebook, value) => document.notebook_id == Some(value.as_str()),
+            Query::Field(SearchField::Section, value) => document.section_id == Some(value.as_str()),
+            Query::And(parts) => parts.iter().all(|part| part.matches(document)),
+            Query::Or(parts) => parts.iter().any(|part| part.matches(document)),
+            Query::Not(inner) => !inner.matches(document),
+        }
+    }
+
+    /// Text the user is looking for (not negated), for highlighting and match reporting.
+    pub fn positive_terms(&self) -> Vec<String> {
+        match self {
+            Query::Term(value)
+            | Query::Field(SearchField::Title, value)
+            | Query::Field(SearchField::Content, value) => vec![value.clone()],
+            Query::Field(_, _) | Query::Not(_) => Vec::new(),
+            Query::And(parts) | Query::Or(parts) => parts.iter().flat_map(|p| p.positive_terms()).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn document<'a>(title: &'a str, content: &'a str, tags: &'a [String]) -> SearchDocument<'a> {
+        SearchDocument { title, content, tags, notebook_id: None, section_id: None, extra_texts: &[] }
+    }
+
+    #[test]
+    fn test_parse_boolean_and_fields() {
+        let query = parse(r#"tag:project AND (title:"road map" OR meeting)"#).unwrap().unwrap();
+        assert_eq!(
+            query,
+            Query::And(vec![
+                Query::Field(SearchField::Tag, "project".to_string()),
+                Query::Or(vec![
+                    Query::Field(SearchField::Title, "road map".to_string()),
+                    Query::Term("meeting".to_string()),
+                ]),
+            ])
+        );
+        assert_eq!(parse("tag:#Project").unwrap().unwrap(), Query::Field(SearchField::Tag, "project".to_string()));
+    }
+
+    #[test]
+    fn test_implicit_and_and_negation() {
+        let query = parse("budget -draft unknown:x").unwrap().unwrap();
+        assert_eq!(
+            query,
+            Query::And(vec![
+                Query::Term("budget".to_string()),
+                Query::Not(Box::new(Query::Term("draft".to_string()))),
+                Query::Term("unknown:x".to_string()),
+            ])
+        );
+        assert_eq!(parse("   ").unwrap(), None);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse("(meeting").is_err());
+        assert!(parse("meeting)").is_err());
+        assert!(parse("title:\"open").is_err());
+        assert!(parse("meeting OR").is_err());
+    }
+
+    #[test]
+    fn test_matches() {
+        let tags = vec!["Project".to_string()];
+        let query = parse(r#"tag:project AND (title:"roadmap" OR meeting)"#).unwrap().unwrap();
+
+        assert!(query.matches(&document("Q3 Roadmap", "", &tags)));
+        assert!(query.matches(&document("Notes", "weekly meeting", &tags)));
+        assert!(!query.matches(&document("Notes", "weekly meeting", &[])));
+        assert!(!query.matches(&document("Notes", "nothing", &tags)));
+    }
+
+    #[test]
+    fn test_sql_prefilter_skips_encrypted_fields() {
+        let query = parse("tag:project meeting").unwrap().unwrap();
+        let (sql, binds) = query.sql_prefilter(SearchTable::Notes).unwrap();
+        assert!(sql.contains("json_each"));
+        assert_eq!(binds, vec!["project".to_string()]);
+
+        assert!(parse("meeting").unwrap().unwrap().sql_prefilter(SearchTable::Notes).is_none());
+        assert!(parse("notebook:abc").unwrap().unwrap().to_sql(SearchTable::Notes).is_none());
+
+        let (sql, _) = parse("tag:project -section:abc").unwrap().unwrap().sql_prefilter(SearchTable::Pages).unwrap();
+        assert!(!sql.contains("section_id"));
+    }
+}
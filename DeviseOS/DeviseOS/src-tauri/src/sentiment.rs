@@ -0,0 +1,89 @@
+use std::collections::BTreeMap;
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc};
+use crate::models::{SentimentGranularity, SentimentPoint};
+
+const POSITIVE_WORDS: &[&str] = &[
+    "good", "great", "excellent", "amazing", "wonderful", "fantastic",
+    "love", "like", "enjoy", "happy", "joy", "success", "win", "best",
+    "awesome", "brilliant", "perfect", "outstanding", "superb", "marvelous",
+];
+
+const NEGATIVE_WORDS: &[&str] = &[
+    "bad", "terrible", "awful", "horrible", "hate", "dislike", "sad",
+    "angry", "frustrated", "fail", "lose", "worst", "problem", "issue",
+    "difficult", "hard", "challenging", "disappointing", "poor", "weak",
+];
+
+/// Sentiment from -1.0 (negative) to 1.0 (positive) by counting opinion words, or `None`
+/// when the text has none, so neutral entries don't flatten a trend.
+pub fn score(text: &str) -> Option<f64> {
+    let mut positive = 0;
+    let mut negative = 0;
+    for word in text.split_whitespace() {
+        let word = word.trim_matches(|c: char| !c.is_alphanumeric()).to_lowercase();
+        if POSITIVE_WORDS.contains(&word.as_str()) {
+            positive += 1;
+        } else if NEGATIVE_WORDS.contains(&word.as_str()) {
+            negative += 1;
+        }
+    }
+
+    let total = positive + negative;
+    (total > 0).then(|| (positive as f64 - negative as f64) / total as f64)
+}
+
+/// Average sentiment per day, week (starting Monday) or month, oldest first. Periods
+/// without scored entries are left out.
+pub fn trends(scores: &[(DateTime<Utc>, f64)], granularity: SentimentGranularity) -> Vec<SentimentPoint> {
+    let mut periods: BTreeMap<NaiveDate, (f64, usize)> = BTreeMap::new();
+    for (created_at, score) in scores {
+        let entry = periods.entry(period_start(created_at.date_naive(), granularity)).or_insert((0.0, 0));
+        entry.0 += score;
+        entry.1 += 1;
+    }
+
+    periods
+        .into_iter()
+        .map(|(start, (sum, count))| SentimentPoint {
+            period_start: start.format("%Y-%m-%d").to_string(),
+            average: sum / count as f64,
+            entry_count: count,
+        })
+        .collect()
+}
+
+fn period_start(date: NaiveDate, granularity: SentimentGranularity) -> NaiveDate {
+    match granularity {
+        SentimentGranularity::Day => date,
+        SentimentGranularity::Week => date - Duration::days(date.weekday().num_days_from_monday() as i64),
+        SentimentGranularity::Month => date.with_day(1).unwrap_or(date),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_score() {
+        assert_eq!(score("Great day, I love it!"), Some(1.0));
+        assert_eq!(score("Good start but a terrible, awful ending."), Some(-1.0 / 3.0));
+        assert_eq!(score("Met Sam at noon."), None);
+    }
+
+    #[test]
+    fn test_weekly_trends() {
+        let at = |date: &str| DateTime::parse_from_rfc3339(&format!("{}T09:00:00Z", date)).unwrap().with_timezone(&Utc);
+        // 2024-03-04 is a Monday
+        let scores = vec![(at("2024-03-05"), 1.0), (at("2024-03-10"), 0.0), (at("2024-03-11"), -0.5)];
+
+        let points = trends(&scores, SentimentGranularity::Week);
+        assert_eq!(points.len(), 2);
+        assert_eq!(points[0].period_start, "2024-03-04");
+        assert_eq!(points[0].average, 0.5);
+        assert_eq!(points[0].entry_count, 2);
+        assert_eq!(points[1].period_start, "2024-03-11");
+
+        assert_eq!(trends(&scores, SentimentGranularity::Month)[0].entry_count, 3);
+    }
+}
@@ -0,0 +1,253 @@
+use std::path::PathBuf;
+use tauri::{AppHandle, Emitter};
+use tokio::sync::Mutex;
+use tracing_subscriber::EnvFilter;
+use crate::{
+    AppError, AppResult, AppState,
+    models::{is_valid_language_tag, AiDevicePreference, MqttConfig, OsSearchSync, SecurityConfig, SettingChange, SettingSchema, SettingType, SyncConfig, UpdateCheckConfig},
+    database::VECTOR_INDEX_KEY,
+    ai::AI_DEVICE_KEY,
+    cloud_sync::{SyncService, SYNC_CONFIG_KEY},
+    crash::CRASH_REPORT_URL_KEY,
+    daily::{DAILY_NOTEBOOK_KEY, DAILY_TEMPLATE_KEY, DEFAULT_DAILY_NOTEBOOK},
+    exif::SECURITY_CONFIG_KEY,
+    llm::LLM_MODEL_PATH_KEY,
+    locale::{DEFAULT_LOCALE, LOCALE_KEY},
+    logging::{self, DEFAULT_LEVEL, LOG_LEVEL_KEY},
+    mqtt::{MqttPublisher, MQTT_CONFIG_KEY},
+    os_search::{self, OS_SEARCH_FOLDER_KEY},
+    policy,
+    sql_console::DEVELOPER_MODE_KEY,
+    titles::AUTO_TITLE_KEY,
+    trash::{self, DEFAULT_RETENTION_DAYS, TRASH_RETENTION_KEY},
+    updates::{UpdateChecker, UPDATE_CONFIG_KEY},
+    wikilinks::WIKILINK_STUBS_KEY,
+    zettel::ZETTEL_IDS_KEY,
+};
+
+pub const SETTING_CHANGED_EVENT: &str = "setting-changed";
+
+struct SettingSpec {
+    key: &'static str,
+    setting_type: SettingType,
+    default: Option<&'static str>,
+    /// Only for `SettingType::Json`: checks the value parses as the consumer's type and
+    /// makes sense as one.
+    json: Option<fn(&str) -> Result<(), String>>,
+}
+
+const fn spec(key: &'static str, setting_type: SettingType, default: Option<&'static str>) -> SettingSpec {
+    SettingSpec { key, setting_type, default, json: None }
+}
+
+/// Settings the backend reads. Keys not listed here are frontend preferences and are
+/// stored as given.
+const REGISTRY: &[SettingSpec] = &[
+    spec(AUTO_TITLE_KEY, SettingType::Bool, Some("false")),
+    spec(ZETTEL_IDS_KEY, SettingType::Bool, Some("false")),
+    spec(WIKILINK_STUBS_KEY, SettingType::Bool, Some("false")),
+    spec(VECTOR_INDEX_KEY, SettingType::Bool, Some("false")),
+    spec(LOCALE_KEY, SettingType::Locale, Some(DEFAULT_LOCALE)),
+    spec(LOG_LEVEL_KEY, SettingType::LogFilter, Some(DEFAULT_LEVEL)),
+    spec(AI_DEVICE_KEY, SettingType::AiDevice, Some("auto")),
+    spec(LLM_MODEL_PATH_KEY, SettingType::Path, None),
+    spec(CRASH_REPORT_URL_KEY, SettingType::HttpsUrl, None),
+    spec(OS_SEARCH_FOLDER_KEY, SettingType::Path, None),
+    spec(DEVELOPER_MODE_KEY, SettingType::Bool, Some("false")),
+    spec(TRASH_RETENTION_KEY, SettingType::Days, Some(DEFAULT_RETENTION_DAYS)),
+    spec(DAILY_NOTEBOOK_KEY, SettingType::Text, Some(DEFAULT_DAILY_NOTEBOOK)),
+    spec(DAILY_TEMPLATE_KEY, SettingType::Text, None),
+    SettingSpec { key: MQTT_CONFIG_KEY, setting_type: SettingType::Json, default: None, json: Some(mqtt_config) },
+    SettingSpec { key: SYNC_CONFIG_KEY, setting_type: SettingType::Json, default: None, json: Some(sync_config) },
+    SettingSpec { key: UPDATE_CONFIG_KEY, setting_type: SettingType::Json, default: None, json: Some(update_config) },
+    SettingSpec { key: SECURITY_CONFIG_KEY, setting_type: SettingType::Json, default: None, json: Some(parses_as::<SecurityConfig>) },
+];
+
+/// What applying a setting did besides storing it, for the commands that report it.
+pub enum Applied {
+    Stored,
+    VectorIndex { migrated: usize },
+    OsSearch(Option<OsSearchSync>),
+}
+
+/// Serializes validated writes so concurrent changes land, and are announced, in order.
+static WRITE_LOCK: Mutex<()> = Mutex::const_new(());
+
+pub fn schemas() -> Vec<SettingSchema> {
+    REGISTRY
+        .iter()
+        .map(|spec| SettingSchema {
+            key: spec.key.to_string(),
+            setting_type: spec.setting_type,
+            default: spec.default.map(str::to_string),
+        })
+        .collect()
+}
+
+pub fn default_value(key: &str) -> Option<&'static str> {
+    find(key).and_then(|spec| spec.default)
+}
+
+/// Validates and normalizes `value`, applies it to the running app, stores it and emits
+/// `setting-changed`. Every change to a setting goes through here or `reset`, so the
+/// dedicated commands behave the same as the generic ones. Takes the database lock itself,
+/// so callers mustn't hold it.
+pub async fn set(app: &AppHandle, state: &AppState, key: &str, value: &str) -> AppResult<Applied> {
+    policy::ensure_setting_unlocked(&state.config, key)?;
+    let value = validate(key, value)?;
+    let _guard = WRITE_LOCK.lock().await;
+    let applied = apply(state, key, Some(&value)).await?;
+    state.database.read().await.set_setting(key, &value).await?;
+    let _ = app.emit(SETTING_CHANGED_EVENT, &SettingChange { key: key.to_string(), value: Some(value) });
+    Ok(applied)
+}
+
+/// Removes the stored value so the default applies again, and returns the default.
+pub async fn reset(app: &AppHandle, state: &AppState, key: &str) -> AppResult<Option<String>> {
+    policy::ensure_setting_unlocked(&state.config, key)?;
+    let default = default_value(key).map(str::to_string);
+    let _guard = WRITE_LOCK.lock().await;
+    apply(state, key, default.as_deref()).await?;
+    state.database.read().await.delete_setting(key).await?;
+    let _ = app.emit(SETTING_CHANGED_EVENT, &SettingChange { key: key.to_string(), value: default.clone() });
+    Ok(default)
+}
+
+/// Brings the running app in line with a setting's new value, `None` when it's unset.
+/// Runs before the value is stored, so one that can't be applied isn't kept.
+async fn apply(state: &AppState, key: &str, value: Option<&str>) -> AppResult<Applied> {
+    match key {
+        AI_DEVICE_KEY => {
+            let preference = value.and_then(AiDevicePreference::parse).unwrap_or_default();
+            state.ai_service.write().await.set_device(preference);
+        }
+        LLM_MODEL_PATH_KEY => {
+            let path = match value {
+                Some(path) if !std::path::Path::new(path).is_file() => {
+                    return Err(AppError::ModelNotFound(format!("No model file at {}", path)));
+                }
+                Some(path) => PathBuf::from(path),
+                None => state.config.llm_model_path.clone(),
+            };
+            state.ai_service.write().await.set_llm_model_path(path);
+        }
+        LOG_LEVEL_KEY => logging::set_level(value.unwrap_or(DEFAULT_LEVEL))?,
+        VECTOR_INDEX_KEY => {
+            let mut database = state.database.write().await;
+            if value == Some("true") {
+                let migrated = database.enable_vector_index().await?;
+                return Ok(Applied::VectorIndex { migrated });
+            }
+            database.disable_vector_index().await?;
+            return Ok(Applied::VectorIndex { migrated: 0 });
+        }
+        OS_SEARCH_FOLDER_KEY => {
+            let database = state.database.read().await;
+            let folder = value.map(PathBuf::from);
+            let previous = database.get_setting(key).await?.map(PathBuf::from);
+            if let Some(previous) = previous.filter(|previous| Some(previous) != folder.as_ref()) {
+                os_search::clear(&database, &previous).await?;
+            }
+            let synced = match folder {
+                Some(folder) => Some(os_search::sync(&database, &folder).await?),
+                None => None,
+            };
+            return Ok(Applied::OsSearch(synced));
+        }
+        _ => {}
+    }
+    Ok(Applied::Stored)
+}
+
+pub fn validate(key: &str, value: &str) -> AppResult<String> {
+    let Some(spec) = find(key) else {
+        return Ok(value.to_string());
+    };
+    let invalid = |reason: &str| AppError::InvalidFormat(format!("Invalid value for {}: {}", key, reason));
+    let value = value.trim();
+
+    match spec.setting_type {
+        SettingType::Bool => match value {
+            "true" | "false" => Ok(value.to_string()),
+            _ => Err(invalid("expected true or false")),
+        },
+        SettingType::Locale if is_valid_language_tag(value) => Ok(value.to_string()),
+        SettingType::Locale => Err(invalid("expected a language tag like en-US")),
+        SettingType::LogFilter => EnvFilter::try_new(value)
+            .map(|_| value.to_string())
+            .map_err(|e| invalid(&e.to_string())),
+        SettingType::AiDevice => AiDevicePreference::parse(value)
+            .map(|preference| preference.as_str().to_string())
+            .ok_or_else(|| invalid("expected auto, cpu, cuda or metal")),
+        SettingType::HttpsUrl if value.is_empty() || value.starts_with("https://") => Ok(value.to_string()),
+        SettingType::HttpsUrl => Err(invalid("expected an https:// URL")),
+        SettingType::Path if !value.is_empty() => Ok(value.to_string()),
+        SettingType::Path => Err(invalid("expected a file path")),
+        SettingType::Text if !value.is_empty() => Ok(value.to_string()),
+        SettingType::Text => Err(invalid("expected text")),
+        SettingType::Days => trash::parse_days(value)
+            .map(|days| days.to_string())
+            .ok_or_else(|| invalid("expected a whole number of days")),
+        SettingType::Json => match spec.json {
+            Some(check) => check(value).map(|_| value.to_string()).map_err(|e| invalid(&e)),
+            None => serde_json::from_str::<serde_json::Value>(value)
+                .map(|_| value.to_string())
+                .map_err(|e| invalid(&e.to_string())),
+        },
+    }
+}
+
+fn find(key: &str) -> Option<&'static SettingSpec> {
+    REGISTRY.iter().find(|spec| spec.key == key)
+}
+
+fn parses_as<T: serde::de::DeserializeOwned>(value: &str) -> Result<(), String> {
+    serde_json::from_str::<T>(value).map(|_| ()).map_err(|e| e.to_string())
+}
+
+fn checked<T: serde::de::DeserializeOwned>(value: &str, check: fn(&T) -> Result<(), String>) -> Result<(), String> {
+    check(&serde_json::from_str::<T>(value).map_err(|e| e.to_string())?)
+}
+
+fn mqtt_config(value: &str) -> Result<(), String> {
+    checked::<MqttConfig>(value, MqttPublisher::check_config)
+}
+
+fn sync_config(value: &str) -> Result<(), String> {
+    checked::<SyncConfig>(value, SyncService::check_config)
+}
+
+fn update_config(value: &str) -> Result<(), String> {
+    checked::<UpdateCheckConfig>(value, UpdateChecker::check_config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validates_registered_settings() {
+        assert_eq!(validate(AUTO_TITLE_KEY, " true ").unwrap(), "true");
+        assert!(validate(AUTO_TITLE_KEY, "yes").is_err());
+        assert!(validate(LOCALE_KEY, "not a locale").is_err());
+        assert!(validate(CRASH_REPORT_URL_KEY, "http://example.com").is_err());
+        assert!(validate(MQTT_CONFIG_KEY, "{\"broker\": 1").is_err());
+        let mut mqtt = MqttConfig::default();
+        assert!(validate(MQTT_CONFIG_KEY, &serde_json::to_string(&mqtt).unwrap()).is_ok());
+        mqtt.host = " ".to_string();
+        assert!(validate(MQTT_CONFIG_KEY, &serde_json::to_string(&mqtt).unwrap()).is_err());
+        assert_eq!(validate(TRASH_RETENTION_KEY, "07").unwrap(), "7");
+        assert!(validate(TRASH_RETENTION_KEY, "a week").is_err());
+        assert!(validate(DAILY_NOTEBOOK_KEY, "  ").is_err());
+        assert_eq!(validate("sidebar_width", "not validated").unwrap(), "not validated");
+    }
+
+    #[test]
+    fn test_registry_defaults_are_valid() {
+        for spec in REGISTRY {
+            if let Some(default) = spec.default {
+                assert_eq!(validate(spec.key, default).unwrap(), default, "{}", spec.key);
+            }
+        }
+    }
+}
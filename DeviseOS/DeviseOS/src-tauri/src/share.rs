@@ -0,0 +1,152 @@
+use std::io::Cursor;
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
+use image::{ImageFormat, Luma};
+use qrcode::{EcLevel, QrCode};
+use rand::Rng;
+use crate::{
+    AppError, AppResult,
+    models::{Page, ShareQrCode, ShareQrKind, SharedPage},
+    database::Database,
+    encryption::{self, EncryptionManager},
+};
+
+pub const PAGE_LINK_PREFIX: &str = "deviseos://page/";
+const SHARE_PAYLOAD_PREFIX: &str = "deviseos://share/v1/";
+/// Longest payload encoded inline. Larger QR codes stop scanning reliably from a screen.
+const MAX_PAYLOAD_LENGTH: usize = 1200;
+const PASSCODE_LENGTH: usize = 10;
+// No 0/O or 1/I/L, so the passcode can be read off the screen and typed
+const PASSCODE_ALPHABET: &[u8] = b"ABCDEFGHJKMNPQRSTUVWXYZ23456789";
+const QR_MIN_SIZE: u32 = 320;
+
+/// QR code for handing a page to another device. Short pages are encrypted into the code
+/// itself with a one-off passcode; anything larger, or when `link_only` is set, gets a deep link.
+pub async fn generate_share_qr(database: &Database, page_id: &str, link_only: bool) -> AppResult<ShareQrCode> {
+    let page = database.get_page(page_id).await?
+        .ok_or_else(|| AppError::NotFound(format!("Page with id {} not found", page_id)))?;
+
+    let link = format!("{}{}", PAGE_LINK_PREFIX, page.id);
+    let encrypted = if link_only {
+        None
+    } else {
+        let passcode = generate_passcode();
+        let key_passcode = passcode.clone();
+        // Argon2 key derivation takes a noticeable fraction of a second
+        let payload = tokio::task::spawn_blocking(move || encrypt_payload(&page, &key_passcode))
+            .await
+            .map_err(|e| AppError::Unknown(format!("Share code task failed: {}", e)))??;
+        (payload.len() <= MAX_PAYLOAD_LENGTH).then_some((payload, passcode))
+    };
+
+    let (kind, payload, passcode) = match encrypted {
+        Some((payload, passcode)) => (ShareQrKind::EncryptedPayload, payload, Some(passcode)),
+        None => (ShareQrKind::DeepLink, link, None),
+    };
+
+    let qr_payload = payload.clone();
+    // QR rendering and PNG encoding are CPU bound
+    let png_data = tokio::task::spawn_blocking(move || render_png(&qr_payload))
+        .await
+        .map_err(|e| AppError::Unknown(format!("QR code task failed: {}", e)))??;
+
+    Ok(ShareQrCode { kind, payload, png_data, passcode })
+}
+
+fn generate_passcode() -> String {
+    let mut rng = rand::thread_rng();
+    (0..PASSCODE_LENGTH)
+        .map(|_| PASSCODE_ALPHABET[rng.gen_range(0..PASSCODE_ALPHABET.len())] as char)
+        .collect()
+}
+
+/// `deviseos://share/v1/<salt>/<nonce + ciphertext>`, both base64url. The key is derived
+/// from the passcode with Argon2, as for the vault key.
+fn encrypt_payload(page: &Page, passcode: &str) -> AppResult<String> {
+    let shared = SharedPage {
+        title: page.title.clone(),
+        content: page.content.clone(),
+        tags: page.tags.clone(),
+    };
+    let salt = encryption::generate_random_bytes(16)?;
+    let manager = EncryptionManager::new(passcode, &salt)?;
+    let ciphertext = manager.encrypt(&serde_json::to_vec(&shared)?)?;
+
+    Ok(format!(
+        "{}{}/{}",
+        SHARE_PAYLOAD_PREFIX,
+        URL_SAFE_NO_PAD.encode(salt),
+        URL_SAFE_NO_PAD.encode(ciphertext)
+    ))
+}
+
+/// Opens a scanned encrypted payload with the passcode shown on the sending device.
+pub async fn open_payload(payload: String, passcode: String) -> AppResult<SharedPage> {
+    // Argon2 key derivation takes a noticeable fraction of a second
+    tokio::task::spawn_blocking(move || decrypt_payload(&payload, &passcode))
+        .await
+        .map_err(|e| AppError::Unknown(format!("Share code task failed: {}", e)))?
+}
+
+fn decrypt_payload(payload: &str, passcode: &str) -> AppResult<SharedPage> {
+    let invalid = || AppError::InvalidFormat("Not a DeviseOS share code".to_string());
+    let (salt, ciphertext) = payload
+        .trim()
+        .strip_prefix(SHARE_PAYLOAD_PREFIX)
+        .and_then(|rest| rest.split_once('/'))
+        .ok_or_else(invalid)?;
+    let salt = URL_SAFE_NO_PAD.decode(salt).map_err(|_| invalid())?;
+    let ciphertext = URL_SAFE_NO_PAD.decode(ciphertext).map_err(|_| invalid())?;
+
+    let manager = EncryptionManager::new(&passcode.trim().to_uppercase(), &salt)?;
+    let plaintext = manager
+        .decrypt(&ciphertext)
+        .map_err(|_| AppError::PermissionDenied("Wrong passcode for share code".to_string()))?;
+    Ok(serde_json::from_slice(&plaintext)?)
+}
+
+fn render_png(payload: &str) -> AppResult<Vec<u8>> {
+    // Low error correction leaves the most room for data; codes are read from a screen, not print
+    let code = QrCode::with_error_correction_level(payload.as_bytes(), EcLevel::L)
+        .map_err(|e| AppError::InvalidFormat(format!("Failed to encode QR code: {}", e)))?;
+    let image = code.render::<Luma<u8>>().min_dimensions(QR_MIN_SIZE, QR_MIN_SIZE).build();
+
+    let mut png_data = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut png_data), ImageFormat::Png)
+        .map_err(|e| AppError::Unknown(format!("Failed to encode QR code image: {}", e)))?;
+    Ok(png_data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page(content: &str) -> Page {
+        Page::new("nb".to_string(), None, None, "Groceries".to_string(), content.to_string(), vec!["home".to_string()])
+    }
+
+    #[test]
+    fn test_payload_round_trip() {
+        let payload = encrypt_payload(&page("milk, eggs"), "ABCDEFGHJK").unwrap();
+        assert!(payload.starts_with(SHARE_PAYLOAD_PREFIX));
+        assert!(!payload.contains("milk"));
+
+        let shared = decrypt_payload(&payload, "abcdefghjk").unwrap();
+        assert_eq!(shared.content, "milk, eggs");
+        assert_eq!(shared.tags, vec!["home".to_string()]);
+    }
+
+    #[test]
+    fn test_wrong_passcode_rejected() {
+        let payload = encrypt_payload(&page("milk"), "ABCDEFGHJK").unwrap();
+        assert!(matches!(decrypt_payload(&payload, "ZZZZZZZZZZ"), Err(AppError::PermissionDenied(_))));
+        assert!(matches!(decrypt_payload("deviseos://page/123", "ABCDEFGHJK"), Err(AppError::InvalidFormat(_))));
+    }
+
+    #[test]
+    fn test_passcode_alphabet() {
+        let passcode = generate_passcode();
+        assert_eq!(passcode.len(), PASSCODE_LENGTH);
+        assert!(passcode.bytes().all(|b| PASSCODE_ALPHABET.contains(&b)));
+    }
+}
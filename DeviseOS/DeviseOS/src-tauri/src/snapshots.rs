@@ -0,0 +1,488 @@
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+use crate::{
+    AppError, AppResult,
+    models::{SnapshotDiff, SnapshotInfo, SnapshotPageChange, StoredValue},
+    database::{Database, SCHEMA_VERSION},
+    encryption::EncryptionManager,
+};
+
+/// Vault tables captured by a snapshot, parents first, with their primary key column.
+/// Settings, automations and bundles are configuration rather than content and stay as-is.
+const SNAPSHOT_TABLES: &[(&str, &str)] = &[
+    ("notebooks", "id"),
+    ("sections", "id"),
+    ("pages", "id"),
+    ("page_property_definitions", "name"),
+    ("page_properties", "id"),
+    ("notes", "id"),
+    ("voice_annotations", "id"),
+    ("media_attachments", "id"),
+    ("page_links", "id"),
+    ("dismissed_links", "id"),
+    ("page_revisions", "id"),
+    ("mocs", "page_id"),
+    ("tags", "id"),
+    ("embeddings", "owner_id"),
+    ("trash", "id"),
+];
+
+type Row = Vec<(String, StoredValue)>;
+/// Table -> row id -> object hash
+type TableHashes = BTreeMap<String, BTreeMap<String, String>>;
+
+/// A snapshot as stored. Snapshots of an encrypted database file keep their tables in
+/// `sealed_tables`, encrypted with the vault key, since row ids include property names.
+#[derive(Debug, Serialize, Deserialize)]
+struct ManifestFile {
+    info: SnapshotInfo,
+    schema_version: i64,
+    #[serde(default)]
+    tables: TableHashes,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sealed_tables: Option<String>,
+}
+
+#[derive(Debug)]
+struct Manifest {
+    info: SnapshotInfo,
+    schema_version: i64,
+    tables: TableHashes,
+    sealed: bool, // Objects are encrypted and named by a keyed hash
+}
+
+/// Snapshots live under the backup directory as manifests pointing into a shared,
+/// content-addressed object store: each row is stored once by its SHA-256, so a snapshot
+/// only writes rows that changed since any earlier one. When the database file is
+/// encrypted, so are new snapshots: objects are encrypted with the vault key and named by
+/// a hash keyed with it, so their names can't be matched against guessed rows.
+pub struct SnapshotStore<'a> {
+    root: PathBuf,
+    cipher: Option<&'a EncryptionManager>,
+    hash_key: Option<Vec<u8>>,
+}
+
+impl<'a> SnapshotStore<'a> {
+    pub fn new(backup_path: &Path, database: &'a Database) -> Self {
+        let cipher = database.encryption_manager().filter(|_| database.is_file_encrypted());
+        Self {
+            root: backup_path.to_path_buf(),
+            cipher,
+            hash_key: cipher.map(|c| c.database_key().into_bytes()),
+        }
+    }
+
+    pub async fn create(&self, database: &Database, name: &str) -> AppResult<SnapshotInfo> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(AppError::InvalidFormat("Snapshot name cannot be empty".to_string()));
+        }
+        if self.manifests().await?.iter().any(|m| m.info.name == name) {
+            return Err(AppError::InvalidOperation(format!("A snapshot named '{}' already exists", name)));
+        }
+
+        tokio::fs::create_dir_all(self.root.join("snapshots")).await?;
+        let sealed = self.cipher.is_some();
+        let mut tables = TableHashes::new();
+        let mut new_objects = 0;
+        let mut new_bytes = 0;
+        for (table, key) in SNAPSHOT_TABLES {
+            let mut hashes = BTreeMap::new();
+            for row in database.get_raw_rows(table).await? {
+                let (hash, written) = self.write_object(&row, sealed).await?;
+                if let Some(written) = written {
+                    new_objects += 1;
+                    new_bytes += written;
+                }
+                hashes.insert(row_id(&row, key)?, hash);
+            }
+            tables.insert(table.to_string(), hashes);
+        }
+
+        let info = SnapshotInfo {
+            id: Uuid::new_v4().to_string(),
+            name: name.to_string(),
+            created_at: Utc::now(),
+            page_count: tables.get("pages").map(|p| p.len()).unwrap_or(0),
+            new_objects,
+            new_bytes,
+        };
+        self.write_manifest(&Manifest { info: info.clone(), schema_version: SCHEMA_VERSION, tables, sealed }).await?;
+
+        tracing::info!("Created snapshot '{}' ({} new objects, {} bytes)", info.name, new_objects, new_bytes);
+        Ok(info)
+    }
+
+    /// Newest first.
+    pub async fn list(&self) -> AppResult<Vec<SnapshotInfo>> {
+        let mut snapshots: Vec<SnapshotInfo> = self.manifests().await?.into_iter().map(|m| m.info).collect();
+        snapshots.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        Ok(snapshots)
+    }
+
+    /// Pages added, removed or modified since the snapshot, plus a count of other rows
+    /// that changed. Only hashes are compared, so this reads no objects except for the
+    /// titles of removed pages.
+    pub async fn diff(&self, database: &Database, snapshot_id: &str) -> AppResult<SnapshotDiff> {
+        let manifest = self.manifest(snapshot_id).await?;
+        let hash_key = self.hash_key_for(manifest.sealed);
+        let mut current = TableHashes::new();
+        let mut current_titles = BTreeMap::new();
+        for (table, key) in SNAPSHOT_TABLES {
+            let mut hashes = BTreeMap::new();
+            for row in database.get_raw_rows(table).await? {
+                let id = row_id(&row, key)?;
+                if *table == "pages" {
+                    current_titles.insert(id.clone(), text_column(&row, "title"));
+                }
+                hashes.insert(id, encode_row(&row, hash_key)?.0);
+            }
+            current.insert(table.to_string(), hashes);
+        }
+
+        let changes = diff_tables(&manifest.tables, &current);
+        let current_change = |id: &String| SnapshotPageChange {
+            id: id.clone(),
+            title: current_titles.get(id).cloned().unwrap_or_default(),
+        };
+        let mut removed_pages = Vec::new();
+        for id in &changes.removed_pages {
+            let hash = &manifest.tables["pages"][id];
+            let row = self.read_object(hash, manifest.sealed).await?;
+            removed_pages.push(SnapshotPageChange { id: id.clone(), title: text_column(&row, "title") });
+        }
+
+        Ok(SnapshotDiff {
+            snapshot: manifest.info,
+            added_pages: changes.added_pages.iter().map(current_change).collect(),
+            removed_pages,
+            modified_pages: changes.modified_pages.iter().map(current_change).collect(),
+            other_changed_rows: changes.other_changed_rows,
+        })
+    }
+
+    /// Replaces the vault's content with the snapshot. The current state is snapshotted
+    /// first so a restore can itself be undone.
+    pub async fn restore(&self, database: &Database, snapshot_id: &str) -> AppResult<SnapshotInfo> {
+        let manifest = self.manifest(snapshot_id).await?;
+        if manifest.schema_version != SCHEMA_VERSION {
+            return Err(AppError::InvalidOperation(format!(
+                "Snapshot was taken with schema version {} and can't be restored into version {}",
+                manifest.schema_version, SCHEMA_VERSION
+            )));
+        }
+
+        // Read every object before touching the database so a missing one aborts cleanly
+        let mut tables = Vec::new();
+        for (table, _) in SNAPSHOT_TABLES {
+            let mut rows = Vec::new();
+            for hash in manifest.tables.get(*table).into_iter().flat_map(|t| t.values()) {
+                rows.push(self.read_object(hash, manifest.sealed).await?);
+            }
+            tables.push((*table, rows));
+        }
+
+        let safety_name = format!("Before restoring '{}' ({})", manifest.info.name, Utc::now().format("%Y-%m-%d %H:%M:%S"));
+        self.create(database, &safety_name).await?;
+        database.replace_raw_rows(&tables).await?;
+
+        tracing::info!("Restored snapshot '{}'", manifest.info.name);
+        Ok(manifest.info)
+    }
+
+    /// Deletes the manifest and any objects no other snapshot references.
+    pub async fn delete(&self, snapshot_id: &str) -> AppResult<()> {
+        let manifest = self.manifest(snapshot_id).await?;
+        tokio::fs::remove_file(self.manifest_path(&manifest.info.id)).await?;
+
+        let Some(referenced) = self.referenced().await? else {
+            tracing::warn!("Keeping the objects of snapshot '{}': other snapshots can't be read without the vault key", manifest.info.name);
+            return Ok(());
+        };
+        let mut removed = 0;
+        for hash in manifest.tables.values().flat_map(|t| t.values()) {
+            if !referenced.contains(hash) && tokio::fs::remove_file(self.object_path(hash)).await.is_ok() {
+                removed += 1;
+            }
+        }
+
+        tracing::info!("Deleted snapshot '{}' ({} objects freed)", manifest.info.name, removed);
+        Ok(())
+    }
+
+    /// Encrypts the snapshots taken before the database file was, then removes their
+    /// plaintext objects, so no copy of the vault stays readable in the backup directory.
+    /// Returns how many snapshots were encrypted.
+    pub async fn seal_existing(&self) -> AppResult<usize> {
+        if self.cipher.is_none() {
+            return Ok(0);
+        }
+
+        let mut sealed = 0;
+        for file in self.manifests().await? {
+            let manifest = self.open(file)?;
+            if manifest.sealed {
+                continue;
+            }
+            let mut tables = TableHashes::new();
+            for (table, rows) in &manifest.tables {
+                let mut hashes = BTreeMap::new();
+                for (id, hash) in rows {
+                    let row = self.read_object(hash, false).await?;
+                    hashes.insert(id.clone(), self.write_object(&row, true).await?.0);
+                }
+                tables.insert(table.clone(), hashes);
+            }
+            self.write_manifest(&Manifest { tables, sealed: true, ..manifest }).await?;
+            sealed += 1;
+        }
+
+        if sealed > 0 {
+            let removed = self.remove_unreferenced_objects().await?;
+            tracing::info!("Encrypted {} snapshots and removed {} plaintext objects", sealed, removed);
+        }
+        Ok(sealed)
+    }
+
+    async fn manifests(&self) -> AppResult<Vec<ManifestFile>> {
+        let dir = self.root.join("snapshots");
+        if !tokio::fs::try_exists(&dir).await? {
+            return Ok(Vec::new());
+        }
+
+        let mut manifests = Vec::new();
+        let mut entries = tokio::fs::read_dir(&dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            if entry.path().extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            match serde_json::from_slice(&tokio::fs::read(entry.path()).await?) {
+                Ok(manifest) => manifests.push(manifest),
+                Err(e) => tracing::warn!("Skipping unreadable snapshot {:?}: {}", entry.path(), e),
+            }
+        }
+        Ok(manifests)
+    }
+
+    async fn manifest(&self, snapshot_id: &str) -> AppResult<Manifest> {
+        let not_found = || AppError::NotFound(format!("Snapshot with id {} not found", snapshot_id));
+        // Ids are UUIDs; anything else could escape the snapshot directory
+        Uuid::parse_str(snapshot_id).map_err(|_| not_found())?;
+        let path = self.manifest_path(snapshot_id);
+        if !tokio::fs::try_exists(&path).await? {
+            return Err(not_found());
+        }
+        self.open(serde_json::from_slice(&tokio::fs::read(path).await?)?)
+    }
+
+    /// Decrypts the tables of a sealed manifest.
+    fn open(&self, file: ManifestFile) -> AppResult<Manifest> {
+        let tables = match &file.sealed_tables {
+            Some(sealed) => {
+                let cipher = self.cipher.ok_or_else(|| {
+                    AppError::Encryption(format!("Snapshot '{}' is encrypted and needs the database file encrypted with the vault key", file.info.name))
+                })?;
+                serde_json::from_str(&cipher.decrypt_string(sealed)?)?
+            }
+            None => file.tables,
+        };
+        Ok(Manifest { info: file.info, schema_version: file.schema_version, tables, sealed: file.sealed_tables.is_some() })
+    }
+
+    async fn write_manifest(&self, manifest: &Manifest) -> AppResult<()> {
+        let (tables, sealed_tables) = match self.cipher.filter(|_| manifest.sealed) {
+            Some(cipher) => (TableHashes::new(), Some(cipher.encrypt_string(&serde_json::to_string(&manifest.tables)?)?)),
+            None => (manifest.tables.clone(), None),
+        };
+        let file = ManifestFile { info: manifest.info.clone(), schema_version: manifest.schema_version, tables, sealed_tables };
+        tokio::fs::write(self.manifest_path(&manifest.info.id), serde_json::to_vec_pretty(&file)?).await?;
+        Ok(())
+    }
+
+    /// Every object hash some snapshot refers to, or None when a snapshot can't be read.
+    async fn referenced(&self) -> AppResult<Option<HashSet<String>>> {
+        let mut referenced = HashSet::new();
+        for file in self.manifests().await? {
+            let Ok(manifest) = self.open(file) else {
+                return Ok(None);
+            };
+            referenced.extend(manifest.tables.into_values().flat_map(|t| t.into_values()));
+        }
+        Ok(Some(referenced))
+    }
+
+    async fn remove_unreferenced_objects(&self) -> AppResult<usize> {
+        let Some(referenced) = self.referenced().await? else {
+            return Ok(0);
+        };
+        let dir = self.root.join("objects");
+        if !tokio::fs::try_exists(&dir).await? {
+            return Ok(0);
+        }
+
+        let mut removed = 0;
+        let mut prefixes = tokio::fs::read_dir(&dir).await?;
+        while let Some(prefix) = prefixes.next_entry().await? {
+            if !prefix.file_type().await?.is_dir() {
+                continue;
+            }
+            let mut objects = tokio::fs::read_dir(prefix.path()).await?;
+            while let Some(object) = objects.next_entry().await? {
+                let hash = format!("{}{}", prefix.file_name().to_string_lossy(), object.file_name().to_string_lossy());
+                if !referenced.contains(&hash) {
+                    tokio::fs::remove_file(object.path()).await?;
+                    removed += 1;
+                }
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Stores `row` unless an identical one already is, returning its hash and, when it
+    /// was new, the bytes written.
+    async fn write_object(&self, row: &Row, sealed: bool) -> AppResult<(String, Option<u64>)> {
+        let (hash, data) = encode_row(row, self.hash_key_for(sealed))?;
+        let path = self.object_path(&hash);
+        if tokio::fs::try_exists(&path).await? {
+            return Ok((hash, None));
+        }
+
+        let data = match self.cipher.filter(|_| sealed) {
+            Some(cipher) => cipher.encrypt(&data)?,
+            None => data,
+        };
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::write(&path, &data).await?;
+        Ok((hash, Some(data.len() as u64)))
+    }
+
+    async fn read_object(&self, hash: &str, sealed: bool) -> AppResult<Row> {
+        let data = tokio::fs::read(self.object_path(hash))
+            .await
+            .map_err(|_| AppError::NotFound(format!("Snapshot object {} is missing", hash)))?;
+        let data = match (sealed, self.cipher) {
+            (true, Some(cipher)) => cipher.decrypt(&data)?,
+            (true, None) => return Err(AppError::Encryption("Encrypted snapshots need the vault key".to_string())),
+            (false, _) => data,
+        };
+        Ok(serde_json::from_slice(&data)?)
+    }
+
+    fn hash_key_for(&self, sealed: bool) -> Option<&[u8]> {
+        self.hash_key.as_deref().filter(|_| sealed)
+    }
+
+    fn manifest_path(&self, snapshot_id: &str) -> PathBuf {
+        self.root.join("snapshots").join(format!("{}.json", snapshot_id))
+    }
+
+    fn object_path(&self, hash: &str) -> PathBuf {
+        self.root.join("objects").join(&hash[..2]).join(&hash[2..])
+    }
+}
+
+/// The row as JSON and its hash, keyed with `hash_key` for encrypted snapshots.
+fn encode_row(row: &Row, hash_key: Option<&[u8]>) -> AppResult<(String, Vec<u8>)> {
+    let data = serde_json::to_vec(row)?;
+    let mut hasher = Sha256::new();
+    if let Some(key) = hash_key {
+        hasher.update(b"deviseos-snapshot");
+        hasher.update(key);
+    }
+    hasher.update(&data);
+    let hash = hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect();
+    Ok((hash, data))
+}
+
+fn row_id(row: &Row, key: &str) -> AppResult<String> {
+    match row.iter().find(|(name, _)| name == key) {
+        Some((_, StoredValue::Text(id))) => Ok(id.clone()),
+        _ => Err(AppError::InvalidFormat(format!("Row without a text {} column", key))),
+    }
+}
+
+fn text_column(row: &Row, column: &str) -> String {
+    match row.iter().find(|(name, _)| name == column) {
+        Some((_, StoredValue::Text(text))) => text.clone(),
+        _ => String::new(),
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct TableChanges {
+    added_pages: Vec<String>,
+    removed_pages: Vec<String>,
+    modified_pages: Vec<String>,
+    other_changed_rows: usize,
+}
+
+fn diff_tables(before: &TableHashes, after: &TableHashes) -> TableChanges {
+    let empty = BTreeMap::new();
+    let mut changes = TableChanges::default();
+    for (table, _) in SNAPSHOT_TABLES {
+        let old = before.get(*table).unwrap_or(&empty);
+        let new = after.get(*table).unwrap_or(&empty);
+        let added = new.keys().filter(|id| !old.contains_key(*id));
+        let removed = old.keys().filter(|id| !new.contains_key(*id));
+        let modified = new.iter().filter(|(id, hash)| old.get(*id).is_some_and(|h| h != *hash)).map(|(id, _)| id);
+
+        if *table == "pages" {
+            changes.added_pages = added.cloned().collect();
+            changes.removed_pages = removed.cloned().collect();
+            changes.modified_pages = modified.cloned().collect();
+        } else {
+            changes.other_changed_rows += added.count() + removed.count() + modified.count();
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hashes(tables: &[(&str, &[(&str, &str)])]) -> TableHashes {
+        tables
+            .iter()
+            .map(|(table, rows)| {
+                (table.to_string(), rows.iter().map(|(id, hash)| (id.to_string(), hash.to_string())).collect())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_diff_tables() {
+        let before = hashes(&[("pages", &[("a", "1"), ("b", "2"), ("c", "3")]), ("tags", &[("t", "x")])]);
+        let after = hashes(&[("pages", &[("a", "1"), ("b", "9"), ("d", "4")]), ("notebooks", &[("n", "y")])]);
+
+        let changes = diff_tables(&before, &after);
+        assert_eq!(changes.added_pages, vec!["d"]);
+        assert_eq!(changes.removed_pages, vec!["c"]);
+        assert_eq!(changes.modified_pages, vec!["b"]);
+        assert_eq!(changes.other_changed_rows, 2);
+        assert_eq!(diff_tables(&after, &after), TableChanges::default());
+    }
+
+    #[test]
+    fn test_row_hash_is_content_addressed() {
+        let row = vec![
+            ("id".to_string(), StoredValue::Text("p1".to_string())),
+            ("title".to_string(), StoredValue::Text("Roadmap".to_string())),
+            ("archived".to_string(), StoredValue::Integer(0)),
+        ];
+        let (hash, data) = encode_row(&row, None).unwrap();
+        assert_eq!(hash.len(), 64);
+        assert_eq!(encode_row(&row.clone(), None).unwrap().0, hash);
+        assert_ne!(encode_row(&row, Some(&b"key"[..])).unwrap().0, hash);
+        assert_eq!(serde_json::from_slice::<Row>(&data).unwrap(), row);
+        assert_eq!(row_id(&row, "id").unwrap(), "p1");
+        assert_eq!(text_column(&row, "title"), "Roadmap");
+        assert!(row_id(&row, "owner_id").is_err());
+    }
+}
@@ -0,0 +1,186 @@
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use rusqlite::{Connection, OpenFlags, types::ValueRef};
+use rusqlite::hooks::{AuthAction, AuthContext, Authorization};
+use crate::{
+    AppError, AppResult,
+    models::SqlQueryResult,
+    database::Database,
+};
+
+/// Enables developer tools such as the query console.
+pub const DEVELOPER_MODE_KEY: &str = "developer_mode";
+
+const MAX_ROWS: usize = 500;
+const QUERY_TIMEOUT: Duration = Duration::from_secs(5);
+/// Shown instead of columns that are encrypted at rest.
+const ENCRYPTED: &str = "'[encrypted]'";
+
+/// Views the console can query. Settings, embeddings, automations and bundles are left out
+/// since they can hold credentials and keys; so are BLOB columns, which appear as sizes.
+/// `{encrypted:<column>}` is the column, or a placeholder when encryption is enabled.
+const VIEWS: &[(&str, &str)] = &[
+    ("debug_notebooks", "SELECT id, title, description, color, order_index, created_at, updated_at, metadata FROM main.notebooks"),
+    ("debug_sections", "SELECT id, notebook_id, title, color, order_index, created_at, updated_at FROM main.sections"),
+    ("debug_pages", "SELECT id, notebook_id, section_id, parent_page_id, title, {encrypted:content} AS content, tags, order_index, created_at, updated_at, metadata FROM main.pages"),
+    ("debug_notes", "SELECT id, title, {encrypted:content} AS content, tags, created_at, updated_at, metadata FROM main.notes"),
+    ("debug_voice_annotations", "SELECT id, page_id, note_id, transcription, timestamp, duration, length(audio_data) AS audio_bytes, metadata FROM main.voice_annotations"),
+    ("debug_media", "SELECT id, page_id, note_id, filename, original_filename, mime_type, file_size, position_in_content, created_at FROM main.media_attachments"),
+    ("debug_page_links", "SELECT id, source_page_id, target_page_id, link_text, link_type, created_at FROM main.page_links"),
+    ("debug_tags", "SELECT id, name, color, description, usage_count, created_at, last_used FROM main.tags"),
+    ("debug_export_history", "SELECT id, page_id, format, locale, destination, path, exported_at FROM main.export_history"),
+    ("debug_audit_log", "SELECT id, action, target, created_at FROM main.audit_log"),
+];
+
+/// Functions that reach outside the database.
+const BLOCKED_FUNCTIONS: &[&str] = &["load_extension", "readfile", "writefile", "edit", "fts3_tokenizer"];
+
+/// Runs one SELECT against the `debug_*` views on a separate read-only connection. Anything
+/// else, including reading the underlying tables directly, is refused by SQLite's
+/// authorizer. At most `MAX_ROWS` rows are returned.
+pub async fn execute_readonly(database: &Database, query: &str) -> AppResult<SqlQueryResult> {
+    if database.get_setting(DEVELOPER_MODE_KEY).await?.as_deref() != Some("true") {
+        return Err(AppError::PermissionDenied("The query console needs developer mode".to_string()));
+    }
+    let query = query.trim().trim_end_matches(';').to_string();
+    if query.is_empty() {
+        return Err(AppError::InvalidFormat("Empty query".to_string()));
+    }
+
+    let path = database.path().to_path_buf();
+    let encrypted = database.is_encrypted();
+    let file_key = database.file_key();
+    tokio::task::spawn_blocking(move || run(path, encrypted, file_key, &query))
+        .await
+        .map_err(|e| AppError::Unknown(format!("Query task failed: {}", e)))?
+}
+
+fn run(path: PathBuf, encrypted: bool, file_key: Option<String>, query: &str) -> AppResult<SqlQueryResult> {
+    let connection = Connection::open_with_flags(&path, OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX)
+        .map_err(|e| AppError::Unknown(format!("Failed to open database: {}", e)))?;
+    if let Some(key) = file_key {
+        connection
+            .execute_batch(&format!("PRAGMA key = \"{}\";", key))
+            .map_err(|e| AppError::Encryption(format!("Failed to unlock database: {}", e)))?;
+    }
+    // Temporary views live only on this connection, which can't write to the database
+    for (name, select) in VIEWS {
+        connection
+            .execute_batch(&format!("CREATE TEMP VIEW {} AS {}", name, view_sql(select, encrypted)))
+            .map_err(|e| AppError::Unknown(format!("Failed to create {}: {}", name, e)))?;
+    }
+    connection.authorizer(Some(authorize));
+    let deadline = Instant::now() + QUERY_TIMEOUT;
+    connection.progress_handler(10_000, Some(move || Instant::now() > deadline));
+
+    let invalid = |e: rusqlite::Error| AppError::InvalidFormat(format!("Query rejected: {}", e));
+    let mut statement = connection.prepare(query).map_err(invalid)?;
+    if !statement.readonly() {
+        return Err(AppError::PermissionDenied("Only SELECT queries are allowed".to_string()));
+    }
+    let columns: Vec<String> = statement.column_names().into_iter().map(str::to_string).collect();
+
+    let mut rows = statement.query([]).map_err(invalid)?;
+    let mut result = SqlQueryResult { columns, rows: Vec::new(), truncated: false };
+    loop {
+        let row = match rows.next() {
+            Ok(Some(row)) => row,
+            Ok(None) => break,
+            Err(rusqlite::Error::SqliteFailure(e, _)) if e.code == rusqlite::ErrorCode::OperationInterrupted => {
+                return Err(AppError::Timeout(format!("Query took longer than {} seconds", QUERY_TIMEOUT.as_secs())));
+            }
+            Err(e) => return Err(invalid(e)),
+        };
+        if result.rows.len() == MAX_ROWS {
+            result.truncated = true;
+            break;
+        }
+        let values = (0..result.columns.len())
+            .map(|index| row.get_ref(index).map(json_value))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(invalid)?;
+        result.rows.push(values);
+    }
+    Ok(result)
+}
+
+fn view_sql(select: &str, encrypted: bool) -> String {
+    let mut sql = select.to_string();
+    while let Some(start) = sql.find("{encrypted:") {
+        let end = start + sql[start..].find('}').expect("unterminated placeholder");
+        let column = sql[start + "{encrypted:".len()..end].to_string();
+        sql.replace_range(start..=end, if encrypted { ENCRYPTED } else { &column });
+    }
+    sql
+}
+
+/// Reads are allowed only through the console's views; CTE and subquery columns aren't
+/// table reads and need no check.
+fn authorize(context: AuthContext<'_>) -> Authorization {
+    match context.action {
+        AuthAction::Select | AuthAction::Recursive => Authorization::Allow,
+        AuthAction::Read { .. } if context.accessor.is_some_and(|view| VIEWS.iter().any(|(name, _)| *name == view)) => Authorization::Allow,
+        AuthAction::Function { function_name } if !BLOCKED_FUNCTIONS.contains(&function_name.to_lowercase().as_str()) => Authorization::Allow,
+        _ => Authorization::Deny,
+    }
+}
+
+fn json_value(value: ValueRef<'_>) -> serde_json::Value {
+    match value {
+        ValueRef::Null => serde_json::Value::Null,
+        ValueRef::Integer(i) => i.into(),
+        ValueRef::Real(f) => serde_json::Number::from_f64(f).map(serde_json::Value::Number).unwrap_or(serde_json::Value::Null),
+        ValueRef::Text(text) => String::from_utf8_lossy(text).into_owned().into(),
+        ValueRef::Blob(bytes) => format!("<{} bytes>", bytes.len()).into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vault() -> PathBuf {
+        let path = std::env::temp_dir().join(format!("deviseos-sql-console-{}.db", uuid::Uuid::new_v4()));
+        let connection = Connection::open(&path).unwrap();
+        let tables = [
+            "notebooks (id, title, description, color, order_index, created_at, updated_at, metadata)",
+            "sections (id, notebook_id, title, color, order_index, created_at, updated_at)",
+            "pages (id, notebook_id, section_id, parent_page_id, title, content, tags, order_index, created_at, updated_at, metadata)",
+            "notes (id, title, content, tags, created_at, updated_at, metadata)",
+            "voice_annotations (id, page_id, note_id, audio_data, transcription, timestamp, duration, metadata)",
+            "media_attachments (id, page_id, note_id, filename, original_filename, mime_type, file_size, position_in_content, created_at)",
+            "page_links (id, source_page_id, target_page_id, link_text, link_type, created_at)",
+            "tags (id, name, color, description, usage_count, created_at, last_used)",
+            "export_history (id, page_id, format, locale, destination, path, exported_at)",
+            "audit_log (id, action, target, created_at)",
+            "settings (key, value, updated_at)",
+        ];
+        for table in tables {
+            connection.execute_batch(&format!("CREATE TABLE {};", table)).unwrap();
+        }
+        connection
+            .execute_batch(
+                "INSERT INTO pages (id, title, content) VALUES ('p1', 'Plan', 'Ship it');
+                 INSERT INTO notes (id, title, content) VALUES ('n1', 'Secret', 'c2VjcmV0');
+                 INSERT INTO settings (key, value) VALUES ('mqtt_config', 'password');",
+            )
+            .unwrap();
+        path
+    }
+
+    #[test]
+    fn test_reads_only_through_views() {
+        let path = vault();
+
+        let result = run(path.clone(), true, None, "SELECT p.title, p.content, n.content FROM debug_pages p, debug_notes n").unwrap();
+        assert_eq!(result.columns, vec!["title", "content", "content"]);
+        assert_eq!(result.rows, vec![vec![serde_json::json!("Plan"), serde_json::json!("[encrypted]"), serde_json::json!("[encrypted]")]]);
+
+        assert!(run(path.clone(), true, None, "SELECT value FROM settings").is_err());
+        assert!(run(path.clone(), true, None, "SELECT title FROM debug_pages WHERE id IN (SELECT key FROM settings)").is_err());
+        assert!(run(path.clone(), true, None, "SELECT name FROM sqlite_master").is_err());
+        assert!(run(path.clone(), true, None, "DELETE FROM debug_pages").is_err());
+        assert!(run(path.clone(), true, None, "SELECT 1; DROP TABLE pages").is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+}
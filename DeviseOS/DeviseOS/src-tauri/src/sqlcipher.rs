@@ -0,0 +1,121 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use rusqlite::Connection;
+use crate::{AppError, AppResult};
+
+/// Every plaintext SQLite file starts with this; a SQLCipher file is random from byte 0.
+const SQLITE_HEADER: &[u8; 16] = b"SQLite format 3\0";
+
+/// Whether this build links SQLCipher rather than plain SQLite.
+pub fn is_available() -> bool {
+    cfg!(feature = "sqlcipher")
+}
+
+/// Whether the database at `path` is encrypted as a whole. Missing and empty files aren't.
+pub fn is_encrypted_file(path: &Path) -> AppResult<bool> {
+    let mut header = Vec::with_capacity(SQLITE_HEADER.len());
+    match File::open(path) {
+        Ok(file) => file.take(SQLITE_HEADER.len() as u64).read_to_end(&mut header)?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e.into()),
+    };
+    Ok(is_encrypted_header(&header))
+}
+
+fn is_encrypted_header(header: &[u8]) -> bool {
+    !header.is_empty() && header != SQLITE_HEADER
+}
+
+pub fn ensure_available() -> AppResult<()> {
+    if is_available() {
+        Ok(())
+    } else {
+        Err(AppError::Configuration("This build has no SQLCipher support; rebuild with the `sqlcipher` feature".to_string()))
+    }
+}
+
+/// Rewrites the plaintext database at `path` as a SQLCipher database keyed with `key` (a
+/// raw key literal from `EncryptionManager::database_key`). The copy is made next to the
+/// original with `sqlcipher_export` and checked before it replaces it, so a failure leaves
+/// the original untouched. Blocking; the pool must be closed first.
+pub fn encrypt_file(path: &Path, key: &str) -> AppResult<()> {
+    ensure_available()?;
+    if is_encrypted_file(path)? {
+        return Err(AppError::InvalidOperation("The database file is already encrypted".to_string()));
+    }
+
+    let target = sibling(path, "encrypting");
+    if target.exists() {
+        std::fs::remove_file(&target)?;
+    }
+    let result = export(path, &target, key).and_then(|()| verify(&target, key));
+    if let Err(e) = result {
+        let _ = std::fs::remove_file(&target);
+        return Err(e);
+    }
+
+    std::fs::rename(&target, path)?;
+    // The old WAL belongs to the plaintext file and must not be replayed into the new one
+    for suffix in ["wal", "shm"] {
+        let stale = sibling(path, suffix);
+        if stale.exists() {
+            std::fs::remove_file(&stale)?;
+        }
+    }
+    Ok(())
+}
+
+fn export(path: &Path, target: &Path, key: &str) -> AppResult<()> {
+    let failed = |e: rusqlite::Error| AppError::Encryption(format!("Failed to encrypt the database: {}", e));
+    let connection = Connection::open(path).map_err(failed)?;
+    // Fold the WAL into the main file so the export sees every committed change
+    connection.execute_batch("PRAGMA wal_checkpoint(TRUNCATE);").map_err(failed)?;
+    let version: i64 = connection.query_row("PRAGMA user_version", [], |row| row.get(0)).map_err(failed)?;
+
+    connection
+        .execute_batch(&format!(
+            "ATTACH DATABASE '{}' AS encrypted KEY \"{}\";",
+            target.to_string_lossy().replace('\'', "''"),
+            key
+        ))
+        .map_err(failed)?;
+    connection.query_row("SELECT sqlcipher_export('encrypted')", [], |_| Ok(())).map_err(failed)?;
+    connection
+        .execute_batch(&format!("PRAGMA encrypted.user_version = {}; DETACH DATABASE encrypted;", version))
+        .map_err(failed)?;
+    Ok(())
+}
+
+/// Opens the encrypted copy with the key and runs an integrity check over it.
+fn verify(target: &Path, key: &str) -> AppResult<()> {
+    let failed = |e: rusqlite::Error| AppError::Encryption(format!("The encrypted copy didn't open: {}", e));
+    let connection = Connection::open(target).map_err(failed)?;
+    connection.execute_batch(&format!("PRAGMA key = \"{}\";", key)).map_err(failed)?;
+    let check: String = connection.query_row("PRAGMA quick_check", [], |row| row.get(0)).map_err(failed)?;
+    if check != "ok" {
+        return Err(AppError::Encryption(format!("The encrypted copy failed its integrity check: {}", check)));
+    }
+    Ok(())
+}
+
+/// `path` with `suffix` appended after a dash, as SQLite names its WAL and shared-memory files.
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!("-{}", suffix));
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header_detection() {
+        assert!(!is_encrypted_header(b"SQLite format 3\0"));
+        assert!(!is_encrypted_header(b""));
+        assert!(is_encrypted_header(&[0x8f, 0x21, 0x04, 0x9a, 0x00, 0x11, 0x52, 0xe3, 0x3c, 0x70, 0x0d, 0x41, 0xb6, 0x29, 0x5e, 0x07]));
+        assert!(!is_encrypted_file(Path::new("/nonexistent/deviseos.db")).unwrap());
+        assert_eq!(sibling(Path::new("/data/deviseos.db"), "wal"), PathBuf::from("/data/deviseos.db-wal"));
+    }
+}
@@ -0,0 +1,101 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use regex::Regex;
+use crate::{
+    AppResult,
+    models::{Page, SearchFilters, StalePage, StaleReason},
+    database::Database,
+    ai::AIService,
+};
+
+/// A page whose most recent mentioned date is older than this is likely out of date.
+const OLD_DATE_DAYS: i64 = 365;
+/// How similar a newer page must be to count as covering the same topic.
+const SUPERSEDED_SIMILARITY: f64 = 0.85;
+/// How much more recently that page must have been updated.
+const SUPERSEDED_AFTER_DAYS: i64 = 30;
+
+/// Years and ISO dates, e.g. "2021" or "2021-06-30".
+static DATE_PATTERN: OnceLock<Regex> = OnceLock::new();
+
+/// Pages likely out of date, with the reasons, most flagged and least recently updated
+/// first. Pages marked reviewed since their last edit are only flagged again for pages
+/// that supersede them after the review. Superseded checks need embeddings and are
+/// skipped for pages without one.
+pub async fn find_stale_pages(database: &Database, ai_service: &AIService, now: DateTime<Utc>) -> AppResult<Vec<StalePage>> {
+    let mut pages: HashMap<String, Page> = HashMap::new();
+    for id in database.get_page_ids_in_scope(&SearchFilters::default()).await? {
+        if let Some(page) = database.get_page(&id).await? {
+            pages.insert(id, page);
+        }
+    }
+
+    let mut stale = Vec::new();
+    for page in pages.values() {
+        let reviewed_at = page.metadata.reviewed_at.filter(|reviewed| *reviewed >= page.updated_at);
+        let mut reasons = Vec::new();
+
+        if reviewed_at.is_none() {
+            if let Some(latest) = latest_referenced_date(&page.content) {
+                if latest < (now - Duration::days(OLD_DATE_DAYS)).date_naive() {
+                    reasons.push(StaleReason::OldDates { latest });
+                }
+            }
+        }
+
+        if ai_service.is_embedding_available() {
+            let newer_than = reviewed_at.unwrap_or(page.updated_at + Duration::days(SUPERSEDED_AFTER_DAYS));
+            let similar = ai_service.similar_pages(database, &page.id, SUPERSEDED_SIMILARITY).await?;
+            let newer = similar.into_iter().flatten().find_map(|(similarity, id)| {
+                let other = pages.get(&id)?;
+                (other.updated_at > newer_than).then(|| StaleReason::Superseded {
+                    page_id: other.id.clone(),
+                    title: other.title.clone(),
+                    similarity,
+                })
+            });
+            reasons.extend(newer);
+        }
+
+        if !reasons.is_empty() {
+            stale.push(StalePage {
+                page_id: page.id.clone(),
+                title: page.title.clone(),
+                updated_at: page.updated_at,
+                reasons,
+            });
+        }
+    }
+
+    stale.sort_by(|a, b| b.reasons.len().cmp(&a.reasons.len()).then_with(|| a.updated_at.cmp(&b.updated_at)));
+    Ok(stale)
+}
+
+/// The most recent date mentioned in `content`. A bare year counts as its last day.
+fn latest_referenced_date(content: &str) -> Option<NaiveDate> {
+    let pattern = DATE_PATTERN.get_or_init(|| Regex::new(r"\b((?:19|20)\d{2})(?:-(\d{2})-(\d{2}))?\b").unwrap());
+    pattern
+        .captures_iter(content)
+        .filter_map(|captures| {
+            let year: i32 = captures[1].parse().ok()?;
+            match (captures.get(2), captures.get(3)) {
+                (Some(month), Some(day)) => NaiveDate::from_ymd_opt(year, month.as_str().parse().ok()?, day.as_str().parse().ok()?),
+                _ => NaiveDate::from_ymd_opt(year, 12, 31),
+            }
+        })
+        .max()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latest_referenced_date() {
+        let content = "Pricing as of 2019-03-01, revised in 2021. Target: 2020-12-15. Ref #12019.";
+        assert_eq!(latest_referenced_date(content), NaiveDate::from_ymd_opt(2021, 12, 31));
+        assert_eq!(latest_referenced_date("Due 2022-02-30 or 2018-05-04"), NaiveDate::from_ymd_opt(2018, 5, 4));
+        assert_eq!(latest_referenced_date("No dates, just 300 words"), None);
+    }
+}
@@ -0,0 +1,74 @@
+use crate::{AppError, AppResult};
+
+/// Rejects an upload larger than `max_file_size`.
+pub fn check_file_size(filename: &str, size: u64, max_file_size: u64) -> AppResult<()> {
+    if size > max_file_size {
+        return Err(AppError::Validation(format!(
+            "{} is {}, over the {} limit per file",
+            filename,
+            format_bytes(size),
+            format_bytes(max_file_size)
+        )));
+    }
+    Ok(())
+}
+
+/// Rejects adding `adding` bytes to a notebook already holding `used` when that would go
+/// over its quota.
+pub fn check_quota(notebook_title: &str, used: u64, adding: u64, quota: Option<u64>) -> AppResult<()> {
+    match quota {
+        Some(quota) if used.saturating_add(adding) > quota => Err(AppError::Validation(format!(
+            "Notebook '{}' is using {} of its {} quota; this file needs {} more",
+            notebook_title,
+            format_bytes(used),
+            format_bytes(quota),
+            format_bytes(adding)
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// The top-level part of a MIME type, e.g. "image" for "image/png".
+pub fn media_type(mime_type: &str) -> &str {
+    match mime_type.split('/').next().map(str::trim) {
+        Some(kind) if !kind.is_empty() => kind,
+        _ => "application",
+    }
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limits() {
+        assert!(check_file_size("a.png", 100, 100).is_ok());
+        assert!(matches!(check_file_size("a.png", 101, 100), Err(AppError::Validation(_))));
+        assert!(check_quota("Work", 900, 100, Some(1000)).is_ok());
+        assert!(matches!(check_quota("Work", 900, 101, Some(1000)), Err(AppError::Validation(_))));
+        assert!(check_quota("Work", u64::MAX, 1, None).is_ok());
+    }
+
+    #[test]
+    fn test_media_type_and_format() {
+        assert_eq!(media_type("image/png"), "image");
+        assert_eq!(media_type("audio/ogg; codecs=opus"), "audio");
+        assert_eq!(media_type(""), "application");
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(100 * 1024 * 1024), "100.0 MB");
+    }
+}
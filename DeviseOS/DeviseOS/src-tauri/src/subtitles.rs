@@ -0,0 +1,238 @@
+use std::path::Path;
+use crate::{
+    AppError, AppResult,
+    models::{SubtitleExport, SubtitleFormat, SubtitleSegment, TranscribedWord, VoiceAnnotation},
+    database::Database,
+    ai::{join_words, offset_words, AIService, PCM_BYTES_PER_SECOND},
+    transcription::{seconds, windows},
+    audio,
+};
+
+/// Short enough to read as one caption.
+const SEGMENT_SECS: usize = 6;
+
+/// Timed segments of a voice annotation, translated into `target_lang` when given. Speech
+/// segments stored when it was transcribed give the timings: split into cues at sentence
+/// ends by their word timings, or by length for segments without them. Otherwise, with
+/// Whisper available the audio is transcribed again in short windows, and without it the
+/// stored transcription is spread over the recording by length.
+pub async fn segments(ai_service: &AIService, annotation: &VoiceAnnotation, target_lang: Option<&str>) -> AppResult<Vec<SubtitleSegment>> {
+    let timed = if !annotation.metadata.segments.is_empty() {
+        annotation.metadata.segments
+            .iter()
+            .flat_map(|segment| {
+                if !segment.words.is_empty() {
+                    return word_cues(&segment.words);
+                }
+                proportional_segments(&segment.text, segment.end_secs - segment.start_secs)
+                    .into_iter()
+                    .map(|(start, end, text)| (segment.start_secs + start, segment.start_secs + end, text, Vec::new()))
+                    .collect()
+            })
+            .collect::<Vec<_>>()
+    } else if ai_service.is_whisper_available() && !annotation.audio_data.is_empty() {
+        let mut timed = Vec::new();
+        for (start, end) in windows(annotation.audio_data.len(), SEGMENT_SECS * PCM_BYTES_PER_SECOND) {
+            let words = ai_service
+                .transcribe_words(&annotation.audio_data[start..end], annotation.metadata.language.as_deref())
+                .await?;
+            let words = offset_words(words, seconds(start));
+            timed.push((seconds(start), seconds(end), join_words(&words), words));
+        }
+        timed
+    } else {
+        proportional_segments(&annotation.transcription, annotation.duration)
+            .into_iter()
+            .map(|(start, end, text)| (start, end, text, Vec::new()))
+            .collect()
+    };
+    let mut segments = Vec::new();
+    for (start_secs, end_secs, text, words) in timed.into_iter().filter(|(_, _, text, _)| !text.is_empty()) {
+        let translation = match target_lang {
+            Some(target_lang) => Some(ai_service.translate_text(&text, target_lang).await?),
+            None => None,
+        };
+        segments.push(SubtitleSegment { index: segments.len() + 1, start_secs, end_secs, text, translation, words });
+    }
+    Ok(segments)
+}
+
+/// Writes the annotation's audio and a subtitle file next to it into `destination`, e.g.
+/// `voice-2024-03-01_143000.wav` and `voice-2024-03-01_143000.fr.srt`.
+pub async fn export_subtitles(
+    database: &Database,
+    ai_service: &AIService,
+    annotation_id: &str,
+    format: SubtitleFormat,
+    target_lang: Option<&str>,
+    destination: &Path,
+) -> AppResult<SubtitleExport> {
+    let annotation = database.get_voice_annotation_pcm(annotation_id).await?
+        .ok_or_else(|| AppError::NotFound(format!("Voice annotation with id {} not found", annotation_id)))?;
+    let target_lang = target_lang.map(str::trim).filter(|lang| !lang.is_empty());
+    let segments = segments(ai_service, &annotation, target_lang).await?;
+
+    tokio::fs::create_dir_all(destination).await?;
+    let stem = format!("voice-{}", annotation.timestamp.format("%Y-%m-%d_%H%M%S"));
+    let audio = audio::playable(&annotation.id, annotation.audio_data.clone(), annotation.duration, &annotation.metadata);
+    let extension = if audio.mime_type == "audio/wav" { "wav" } else { annotation.metadata.format.as_str() };
+    let audio_path = destination.join(format!("{}.{}", stem, extension));
+    tokio::fs::write(&audio_path, &audio.data).await?;
+
+    let subtitle_name = match target_lang {
+        Some(lang) => format!("{}.{}.{}", stem, lang, format.extension()),
+        None => format!("{}.{}", stem, format.extension()),
+    };
+    let subtitle_path = destination.join(subtitle_name);
+    tokio::fs::write(&subtitle_path, render(format, &segments)).await?;
+
+    tracing::info!("Exported {} subtitle segments for voice annotation {}", segments.len(), annotation_id);
+    Ok(SubtitleExport { subtitle_path, audio_path, segments })
+}
+
+/// Cues show the translation when there is one. WebVTT cues of the original text carry
+/// each word's start time, so players can highlight words as they're spoken.
+pub fn render(format: SubtitleFormat, segments: &[SubtitleSegment]) -> String {
+    let mut output = match format {
+        SubtitleFormat::Srt => String::new(),
+        SubtitleFormat::Vtt => "WEBVTT\n\n".to_string(),
+    };
+    for segment in segments {
+        let text = segment.translation.as_deref().unwrap_or(&segment.text);
+        let separator = match format {
+            SubtitleFormat::Srt => ',',
+            SubtitleFormat::Vtt => '.',
+        };
+        let (start, end) = (timestamp(segment.start_secs, separator), timestamp(segment.end_secs, separator));
+        // Blank lines end a cue in both formats
+        let text = match format {
+            SubtitleFormat::Vtt if segment.translation.is_none() && !segment.words.is_empty() => timed_words(&segment.words),
+            _ => text.lines().filter(|line| !line.trim().is_empty()).collect::<Vec<_>>().join("\n"),
+        };
+        match format {
+            SubtitleFormat::Srt => output.push_str(&format!("{}\n{} --> {}\n{}\n\n", segment.index, start, end, text)),
+            SubtitleFormat::Vtt => output.push_str(&format!("{} --> {}\n{}\n\n", start, end, text)),
+        }
+    }
+    output
+}
+
+/// Words in WebVTT cue text, each after the first preceded by its `<HH:MM:SS.mmm>` start.
+fn timed_words(words: &[TranscribedWord]) -> String {
+    words
+        .iter()
+        .enumerate()
+        .map(|(i, word)| {
+            let text = word.text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;");
+            if i == 0 {
+                text
+            } else {
+                format!("<{}>{}", timestamp(word.start_secs, '.'), text)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Timed words grouped into cues: one per sentence, split further once a cue would run
+/// past `SEGMENT_SECS`.
+fn word_cues(words: &[TranscribedWord]) -> Vec<(f64, f64, String, Vec<TranscribedWord>)> {
+    let mut cues = Vec::new();
+    let mut cue: Vec<TranscribedWord> = Vec::new();
+    for word in words {
+        if cue.first().is_some_and(|first| word.end_secs - first.start_secs > SEGMENT_SECS as f64) {
+            cues.push(std::mem::take(&mut cue));
+        }
+        cue.push(word.clone());
+        if word.text.trim_end().ends_with(['.', '?', '!']) {
+            cues.push(std::mem::take(&mut cue));
+        }
+    }
+    if !cue.is_empty() {
+        cues.push(cue);
+    }
+    cues.into_iter()
+        .map(|cue| (cue[0].start_secs, cue[cue.len() - 1].end_secs, join_words(&cue), cue))
+        .collect()
+}
+
+/// `HH:MM:SS,mmm` for SRT, `HH:MM:SS.mmm` for WebVTT.
+fn timestamp(secs: f64, separator: char) -> String {
+    let millis = (secs.max(0.0) * 1000.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02}{}{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        separator,
+        millis % 1000
+    )
+}
+
+/// Sentences of `text` with start and end times spread over `duration` by their length.
+fn proportional_segments(text: &str, duration: f64) -> Vec<(f64, f64, String)> {
+    let sentences: Vec<&str> = text
+        .split_inclusive(['.', '?', '!'])
+        .map(str::trim)
+        .filter(|sentence| !sentence.is_empty())
+        .collect();
+    let total: usize = sentences.iter().map(|sentence| sentence.chars().count()).sum();
+    if total == 0 {
+        return Vec::new();
+    }
+
+    let mut start = 0.0;
+    sentences
+        .into_iter()
+        .map(|sentence| {
+            let end = start + duration * sentence.chars().count() as f64 / total as f64;
+            let segment = (start, end, sentence.to_string());
+            start = end;
+            segment
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_srt_and_vtt() {
+        let segments = vec![
+            SubtitleSegment { index: 1, start_secs: 0.0, end_secs: 6.0, text: "Hello everyone.".to_string(), translation: Some("Bonjour à tous.".to_string()), words: Vec::new() },
+            SubtitleSegment { index: 2, start_secs: 6.0, end_secs: 3725.5, text: "Thanks.".to_string(), translation: None, words: Vec::new() },
+        ];
+        assert_eq!(
+            render(SubtitleFormat::Srt, &segments),
+            "1\n00:00:00,000 --> 00:00:06,000\nBonjour à tous.\n\n2\n00:00:06,000 --> 01:02:05,500\nThanks.\n\n"
+        );
+        assert!(render(SubtitleFormat::Vtt, &segments).starts_with("WEBVTT\n\n00:00:00.000 --> 00:00:06.000\nBonjour à tous.\n\n"));
+    }
+
+    fn word(text: &str, start_secs: f64, end_secs: f64) -> TranscribedWord {
+        TranscribedWord { text: text.to_string(), start_secs, end_secs, confidence: 0.9 }
+    }
+
+    #[test]
+    fn test_word_cues_and_timed_vtt() {
+        let words = vec![word("Hello", 1.0, 1.4), word("there.", 1.5, 2.0), word("Fish", 2.5, 2.8), word("&", 3.0, 3.1), word("chips", 9.0, 9.5)];
+        let cues = word_cues(&words);
+        assert_eq!(cues.iter().map(|cue| cue.2.as_str()).collect::<Vec<_>>(), vec!["Hello there.", "Fish &", "chips"]);
+        assert_eq!((cues[0].0, cues[0].1), (1.0, 2.0));
+
+        let segment = SubtitleSegment { index: 1, start_secs: 1.0, end_secs: 2.0, text: cues[0].2.clone(), translation: None, words: cues[0].3.clone() };
+        assert_eq!(render(SubtitleFormat::Vtt, &[segment.clone()]), "WEBVTT\n\n00:00:01.000 --> 00:00:02.000\nHello <00:00:01.500>there.\n\n");
+        assert!(render(SubtitleFormat::Srt, &[segment]).contains("\nHello there.\n"));
+        assert_eq!(timed_words(&cues[1].3), "Fish <00:00:03.000>&amp;");
+    }
+
+    #[test]
+    fn test_proportional_segments() {
+        let segments = proportional_segments("Short one. A much longer second sentence!", 10.0);
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[0].2, "Short one.");
+        assert!((segments[1].1 - 10.0).abs() < 1e-9);
+        assert!(segments[0].1 < segments[1].1 - segments[1].0);
+    }
+}
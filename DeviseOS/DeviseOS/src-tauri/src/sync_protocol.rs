@@ -0,0 +1,335 @@
+//! Wire format for replicating a vault between devices and sync backends.
+//!
+//! A replica, such as a mobile companion, syncs in three steps:
+//!
+//! 1. `changes`: the replica asks for changes after an opaque `since` sequence and receives
+//!    `{seq, id, kind, rev, deleted}` entries plus the `last_seq` to resume from.
+//! 2. `revs_diff`: the replica sends the revisions it saw and learns which ones it lacks.
+//! 3. `bulk_docs`: missing documents are pushed or pulled with their revisions as-is
+//!    (`new_edits: false`); the receiver never assigns new revisions to replicated docs.
+//!
+//! Every message carries `protocol`; a peer must reject versions it doesn't know.
+//! Revisions are `<generation>-<hash>` where the hash is the first 32 hex digits of
+//! SHA-256 over `parent rev, "\n", deleted ("0"/"1"), "\n", payload`. When two revisions
+//! conflict, the one with the higher generation wins, then the lexicographically larger
+//! hash, so every replica picks the same winner without coordination.
+//!
+//! Payloads are the document's JSON body, AES-GCM encrypted with the vault key and base64
+//! encoded when `encrypted` is set, so backends only ever store ciphertext.
+//!
+//! Fixtures under `tests/sync_fixtures` are the conformance suite: any change that alters
+//! how they parse or serialize is a breaking protocol change and needs a new version.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use crate::{
+    AppError, AppResult,
+    encryption::EncryptionManager,
+};
+
+pub const PROTOCOL_VERSION: u32 = 1;
+const REVISION_HASH_LENGTH: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncDocumentKind {
+    Notebook,
+    Section,
+    Page,
+    Note,
+    VoiceAnnotation,
+    MediaAttachment,
+    PageLink,
+    Tag,
+    PropertyDefinition,
+    PageProperty,
+}
+
+impl SyncDocumentKind {
+    /// The serialized name, e.g. `voice_annotation`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SyncDocumentKind::Notebook => "notebook",
+            SyncDocumentKind::Section => "section",
+            SyncDocumentKind::Page => "page",
+            SyncDocumentKind::Note => "note",
+            SyncDocumentKind::VoiceAnnotation => "voice_annotation",
+            SyncDocumentKind::MediaAttachment => "media_attachment",
+            SyncDocumentKind::PageLink => "page_link",
+            SyncDocumentKind::Tag => "tag",
+            SyncDocumentKind::PropertyDefinition => "property_definition",
+            SyncDocumentKind::PageProperty => "page_property",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "notebook" => Some(SyncDocumentKind::Notebook),
+            "section" => Some(SyncDocumentKind::Section),
+            "page" => Some(SyncDocumentKind::Page),
+            "note" => Some(SyncDocumentKind::Note),
+            "voice_annotation" => Some(SyncDocumentKind::VoiceAnnotation),
+            "media_attachment" => Some(SyncDocumentKind::MediaAttachment),
+            "page_link" => Some(SyncDocumentKind::PageLink),
+            "tag" => Some(SyncDocumentKind::Tag),
+            "property_definition" => Some(SyncDocumentKind::PropertyDefinition),
+            "page_property" => Some(SyncDocumentKind::PageProperty),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SyncDocument {
+    pub id: String,
+    pub kind: SyncDocumentKind,
+    pub rev: String,
+    #[serde(default)]
+    pub deleted: bool,
+    pub updated_at: String, // RFC 3339, informational only; revisions decide conflicts
+    pub encrypted: bool,
+    pub payload: String, // JSON body, or base64 ciphertext of it when encrypted
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChangeEntry {
+    pub seq: String,
+    pub id: String,
+    pub kind: SyncDocumentKind,
+    pub rev: String,
+    #[serde(default)]
+    pub deleted: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SyncMessage {
+    ChangesRequest {
+        protocol: u32,
+        since: Option<String>, // Opaque; omitted for a full replication
+        limit: Option<usize>,
+    },
+    ChangesResponse {
+        protocol: u32,
+        results: Vec<ChangeEntry>,
+        last_seq: String,
+        pending: usize, // Changes left after this batch
+    },
+    RevsDiffRequest {
+        protocol: u32,
+        revs: HashMap<String, Vec<String>>, // Document id -> revisions the sender has
+    },
+    RevsDiffResponse {
+        protocol: u32,
+        missing: HashMap<String, Vec<String>>, // Document id -> revisions the receiver lacks
+    },
+    BulkDocsRequest {
+        protocol: u32,
+        docs: Vec<SyncDocument>,
+        #[serde(default)]
+        new_edits: bool,
+    },
+    BulkDocsResponse {
+        protocol: u32,
+        results: Vec<BulkDocResult>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BulkDocResult {
+    pub id: String,
+    pub rev: String,
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl SyncMessage {
+    pub fn protocol(&self) -> u32 {
+        match self {
+            SyncMessage::ChangesRequest { protocol, .. }
+            | SyncMessage::ChangesResponse { protocol, .. }
+            | SyncMessage::RevsDiffRequest { protocol, .. }
+            | SyncMessage::RevsDiffResponse { protocol, .. }
+            | SyncMessage::BulkDocsRequest { protocol, .. }
+            | SyncMessage::BulkDocsResponse { protocol, .. } => *protocol,
+        }
+    }
+}
+
+/// Parses a message and rejects protocol versions this build doesn't speak.
+pub fn decode_message(data: &[u8]) -> AppResult<SyncMessage> {
+    let message: SyncMessage = serde_json::from_slice(data)?;
+    if message.protocol() != PROTOCOL_VERSION {
+        return Err(AppError::InvalidFormat(format!(
+            "Unsupported sync protocol version {} (expected {})",
+            message.protocol(),
+            PROTOCOL_VERSION
+        )));
+    }
+    Ok(message)
+}
+
+pub fn encode_message(message: &SyncMessage) -> AppResult<Vec<u8>> {
+    Ok(serde_json::to_vec(message)?)
+}
+
+pub fn next_revision(parent: Option<&str>, deleted: bool, payload: &str) -> AppResult<String> {
+    let generation = match parent {
+        Some(parent) => parse_revision(parent)?.0 + 1,
+        None => 1,
+    };
+
+    let mut hasher = Sha256::new();
+    hasher.update(parent.unwrap_or_default().as_bytes());
+    hasher.update(if deleted { b"\n1\n" } else { b"\n0\n" });
+    hasher.update(payload.as_bytes());
+    let hash: String = hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect();
+
+    Ok(format!("{}-{}", generation, &hash[..REVISION_HASH_LENGTH]))
+}
+
+pub fn parse_revision(rev: &str) -> AppResult<(u64, &str)> {
+    let invalid = || AppError::InvalidFormat(format!("Invalid revision: {}", rev));
+    let (generation, hash) = rev.split_once('-').ok_or_else(invalid)?;
+    let generation: u64 = generation.parse().map_err(|_| invalid())?;
+    if generation == 0 || hash.is_empty() || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(invalid());
+    }
+    Ok((generation, hash))
+}
+
+/// The deterministic winner among conflicting revisions of one document.
+pub fn winning_revision<'a>(revs: &[&'a str]) -> AppResult<Option<&'a str>> {
+    let mut winner: Option<(u64, &str, &'a str)> = None;
+    for rev in revs {
+        let (generation, hash) = parse_revision(rev)?;
+        if winner.map(|(g, h, _)| (generation, hash) > (g, h)).unwrap_or(true) {
+            winner = Some((generation, hash, rev));
+        }
+    }
+    Ok(winner.map(|(_, _, rev)| rev))
+}
+
+impl SyncDocument {
+    /// Builds the next revision of a document from its JSON body.
+    pub fn new(
+        id: &str,
+        kind: SyncDocumentKind,
+        parent_rev: Option<&str>,
+        body: Option<&Value>,
+        updated_at: &str,
+        encryption: Option<&EncryptionManager>,
+    ) -> AppResult<Self> {
+        let deleted = body.is_none();
+        let json = body.map(serde_json::to_string).transpose()?.unwrap_or_default();
+        let (encrypted, payload) = match encryption {
+            Some(enc) if !deleted => (true, enc.encrypt_string(&json)?),
+            _ => (false, json),
+        };
+
+        Ok(Self {
+            id: id.to_string(),
+            kind,
+            rev: next_revision(parent_rev, deleted, &payload)?,
+            deleted,
+            updated_at: updated_at.to_string(),
+            encrypted,
+            payload,
+        })
+    }
+
+    /// The JSON body, or `None` for a deletion.
+    pub fn body(&self, encryption: Option<&EncryptionManager>) -> AppResult<Option<Value>> {
+        if self.deleted {
+            return Ok(None);
+        }
+        let json = if self.encrypted {
+            let enc = encryption
+                .ok_or_else(|| AppError::Encryption("Encrypted sync document but no vault key".to_string()))?;
+            enc.decrypt_string(&self.payload)?
+        } else {
+            self.payload.clone()
+        };
+        Ok(Some(serde_json::from_str(&json)?))
+    }
+}
+
+/// Conformance suite. The fixtures are shared with other implementations of the protocol.
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHANGES_RESPONSE: &str = include_str!("../tests/sync_fixtures/changes_response.json");
+    const REVS_DIFF: &str = include_str!("../tests/sync_fixtures/revs_diff.json");
+    const BULK_DOCS: &str = include_str!("../tests/sync_fixtures/bulk_docs_request.json");
+    const REVISIONS: &str = include_str!("../tests/sync_fixtures/revisions.json");
+
+    /// Parsing and re-serializing a fixture must give back the same JSON.
+    fn assert_round_trip(fixture: &str) -> SyncMessage {
+        let message = decode_message(fixture.as_bytes()).unwrap();
+        let expected: Value = serde_json::from_str(fixture).unwrap();
+        let actual: Value = serde_json::from_slice(&encode_message(&message).unwrap()).unwrap();
+        assert_eq!(actual, expected);
+        message
+    }
+
+    #[test]
+    fn test_fixtures_round_trip() {
+        assert!(matches!(assert_round_trip(CHANGES_RESPONSE), SyncMessage::ChangesResponse { .. }));
+        assert!(matches!(assert_round_trip(REVS_DIFF), SyncMessage::RevsDiffRequest { .. }));
+        assert!(matches!(assert_round_trip(BULK_DOCS), SyncMessage::BulkDocsRequest { new_edits: false, .. }));
+    }
+
+    #[test]
+    fn test_revision_vectors() {
+        let vectors: Vec<Value> = serde_json::from_str(REVISIONS).unwrap();
+        for vector in vectors {
+            let rev = next_revision(
+                vector["parent"].as_str(),
+                vector["deleted"].as_bool().unwrap(),
+                vector["payload"].as_str().unwrap(),
+            )
+            .unwrap();
+            assert_eq!(rev, vector["rev"].as_str().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_winning_revision() {
+        assert_eq!(winning_revision(&["2-aa", "3-00", "3-0f"]).unwrap(), Some("3-0f"));
+        assert_eq!(winning_revision(&[]).unwrap(), None);
+        assert!(winning_revision(&["0-aa"]).is_err());
+        assert!(winning_revision(&["x"]).is_err());
+    }
+
+    #[test]
+    fn test_kind_names_match_serde() {
+        for kind in [SyncDocumentKind::Notebook, SyncDocumentKind::VoiceAnnotation, SyncDocumentKind::PageLink, SyncDocumentKind::PropertyDefinition] {
+            assert_eq!(serde_json::to_value(kind).unwrap(), Value::String(kind.as_str().to_string()));
+            assert_eq!(SyncDocumentKind::parse(kind.as_str()), Some(kind));
+        }
+        assert_eq!(SyncDocumentKind::parse("embedding"), None);
+    }
+
+    #[test]
+    fn test_rejects_unknown_protocol() {
+        let message = r#"{"type":"changes_request","protocol":99,"since":null,"limit":null}"#;
+        assert!(decode_message(message.as_bytes()).is_err());
+        assert!(decode_message(br#"{"type":"unknown","protocol":1}"#).is_err());
+    }
+
+    #[test]
+    fn test_document_body_round_trip() {
+        let body = serde_json::json!({"title": "Roadmap", "content": "Q3"});
+        let doc = SyncDocument::new("p1", SyncDocumentKind::Page, None, Some(&body), "2026-01-01T00:00:00Z", None).unwrap();
+        assert!(doc.rev.starts_with("1-"));
+        assert_eq!(doc.body(None).unwrap(), Some(body));
+
+        let deleted = SyncDocument::new("p1", SyncDocumentKind::Page, Some(&doc.rev), None, "2026-01-02T00:00:00Z", None).unwrap();
+        assert!(deleted.deleted && deleted.rev.starts_with("2-"));
+        assert_eq!(deleted.body(None).unwrap(), None);
+    }
+}
@@ -0,0 +1,210 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use crate::models::{Tag, TagMergeReason, TagMergeSuggestion};
+
+/// Names within this edit distance are suggested as typos of each other, but only when
+/// both are at least `MIN_TYPO_LENGTH` characters; short tags like "ux"/"ui" differ on purpose.
+const TYPO_DISTANCE: usize = 1;
+const MIN_TYPO_LENGTH: usize = 5;
+
+/// Canonical form a tag is stored in: no leading `#`, single spaces, lower case.
+pub fn normalize_tag(name: &str) -> Option<String> {
+    let name = name.trim().trim_start_matches('#');
+    let name = name.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase();
+    if name.is_empty() {
+        None
+    } else {
+        Some(name)
+    }
+}
+
+/// Normalizes each tag and drops empties and duplicates, keeping first-seen order.
+pub fn normalize_tags(tags: Vec<String>) -> Vec<String> {
+    let mut seen = HashSet::new();
+    tags.iter()
+        .filter_map(|tag| normalize_tag(tag))
+        .filter(|tag| seen.insert(tag.clone()))
+        .collect()
+}
+
+/// Rewrites a tag list through `mapping` (source -> target). `None` if nothing changed.
+pub fn apply_mapping(tags: &[String], mapping: &HashMap<String, String>) -> Option<Vec<String>> {
+    if !tags.iter().any(|tag| mapping.contains_key(tag)) {
+        return None;
+    }
+    let mut seen = HashSet::new();
+    Some(
+        tags.iter()
+            .map(|tag| mapping.get(tag).unwrap_or(tag).clone())
+            .filter(|tag| seen.insert(tag.clone()))
+            .collect(),
+    )
+}
+
+/// `tags` with `added` appended after normalizing. `None` if they were all there already.
+pub fn add_tags(tags: &[String], added: &[String]) -> Option<Vec<String>> {
+    let updated = normalize_tags(tags.iter().chain(added).cloned().collect());
+    (updated.as_slice() != tags).then_some(updated)
+}
+
+/// `tags` without any of `removed`, compared in normalized form. `None` if none were there.
+pub fn remove_tags(tags: &[String], removed: &[String]) -> Option<Vec<String>> {
+    let removed: HashSet<String> = removed.iter().filter_map(|tag| normalize_tag(tag)).collect();
+    let updated: Vec<String> = tags.iter()
+        .filter(|tag| !normalize_tag(tag).map(|tag| removed.contains(&tag)).unwrap_or(false))
+        .cloned()
+        .collect();
+    (updated.len() != tags.len()).then_some(updated)
+}
+
+/// Tag colors are CSS hex colors, `#rgb` or `#rrggbb`.
+pub fn is_valid_color(color: &str) -> bool {
+    color.strip_prefix('#')
+        .map(|hex| matches!(hex.len(), 3 | 6) && hex.chars().all(|c| c.is_ascii_hexdigit()))
+        .unwrap_or(false)
+}
+
+/// Groups tags that are probably the same: equal ignoring case and spacing, singular and
+/// plural forms, or one-letter typos. The most used tag of each group is the merge target.
+pub fn suggest_merges(tags: &[Tag]) -> Vec<TagMergeSuggestion> {
+    let mut groups: BTreeMap<String, Vec<&Tag>> = BTreeMap::new();
+    for tag in tags {
+        let key = normalize_tag(&tag.name).map(|n| singular(&n)).unwrap_or_default();
+        groups.entry(key).or_default().push(tag);
+    }
+
+    // Join groups whose keys are a typo apart
+    let keys: Vec<String> = groups.keys().cloned().collect();
+    let mut joined: HashSet<String> = HashSet::new();
+    let mut typo_groups: Vec<(Vec<&Tag>, bool)> = Vec::new();
+    for (i, key) in keys.iter().enumerate() {
+        if joined.contains(key) {
+            continue;
+        }
+        let mut members = groups[key].clone();
+        let mut typo = false;
+        for other in &keys[i + 1..] {
+            if !joined.contains(other)
+                && key.chars().count() >= MIN_TYPO_LENGTH
+                && other.chars().count() >= MIN_TYPO_LENGTH
+                && edit_distance(key, other) <= TYPO_DISTANCE
+            {
+                members.extend(groups[other].iter().copied());
+                joined.insert(other.clone());
+                typo = true;
+            }
+        }
+        typo_groups.push((members, typo));
+    }
+
+    let mut suggestions: Vec<TagMergeSuggestion> = typo_groups
+        .into_iter()
+        .filter(|(members, _)| members.len() > 1)
+        .map(|(mut members, typo)| {
+            members.sort_by(|a, b| b.usage_count.cmp(&a.usage_count).then_with(|| a.name.cmp(&b.name)));
+            let target = members[0].name.clone();
+            let reason = if typo {
+                TagMergeReason::Similar
+            } else if members.iter().all(|m| normalize_tag(&m.name) == normalize_tag(&target)) {
+                TagMergeReason::Case
+            } else {
+                TagMergeReason::Plural
+            };
+            TagMergeSuggestion {
+                sources: members[1..].iter().map(|m| m.name.clone()).collect(),
+                usage_count: members.iter().map(|m| m.usage_count).sum(),
+                target,
+                reason,
+            }
+        })
+        .collect();
+    suggestions.sort_by(|a, b| b.usage_count.cmp(&a.usage_count).then_with(|| a.target.cmp(&b.target)));
+    suggestions
+}
+
+/// English singular for the common plural endings; good enough to pair "works" with "work".
+fn singular(name: &str) -> String {
+    if let Some(stem) = name.strip_suffix("ies").filter(|s| s.len() > 1) {
+        format!("{}y", stem)
+    } else if let Some(stem) = name.strip_suffix("sses") {
+        format!("{}ss", stem)
+    } else if name.ends_with('s') && !name.ends_with("ss") && !name.ends_with("us") && !name.ends_with("is") && name.len() > 3 {
+        name[..name.len() - 1].to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tag(name: &str, usage_count: u32) -> Tag {
+        Tag { usage_count, ..Tag::new(name.to_string(), "#3B82F6".to_string()) }
+    }
+
+    #[test]
+    fn test_normalize_tags() {
+        let tags = vec!["  #Work ".to_string(), "work".to_string(), "Deep   Focus".to_string(), "#".to_string()];
+        assert_eq!(normalize_tags(tags), vec!["work", "deep focus"]);
+    }
+
+    #[test]
+    fn test_add_and_remove_tags() {
+        let tags = vec!["work".to_string(), "deep focus".to_string()];
+        assert_eq!(add_tags(&tags, &["#Ideas".to_string(), "WORK".to_string()]), Some(vec!["work".to_string(), "deep focus".to_string(), "ideas".to_string()]));
+        assert_eq!(add_tags(&tags, &["Work".to_string()]), None);
+        assert_eq!(remove_tags(&tags, &["#Deep  Focus".to_string()]), Some(vec!["work".to_string()]));
+        assert_eq!(remove_tags(&tags, &["ideas".to_string()]), None);
+    }
+
+    #[test]
+    fn test_is_valid_color() {
+        assert!(is_valid_color("#3B82F6"));
+        assert!(is_valid_color("#fff"));
+        assert!(!is_valid_color("3B82F6"));
+        assert!(!is_valid_color("#3B82F"));
+        assert!(!is_valid_color("#GGGGGG"));
+    }
+
+    #[test]
+    fn test_suggest_merges() {
+        let tags = vec![
+            tag("Work", 2), tag("work", 9), tag("works", 1),
+            tag("meeting", 5), tag("meetng", 1),
+            tag("ux", 3), tag("ui", 3),
+            tag("status", 1), tag("class", 1),
+        ];
+        let suggestions = suggest_merges(&tags);
+
+        assert_eq!(suggestions.len(), 2);
+        assert_eq!(suggestions[0].target, "work");
+        assert_eq!(suggestions[0].sources, vec!["Work", "works"]);
+        assert_eq!(suggestions[0].reason, TagMergeReason::Plural);
+        assert_eq!(suggestions[0].usage_count, 12);
+        assert_eq!(suggestions[1].target, "meeting");
+        assert_eq!(suggestions[1].reason, TagMergeReason::Similar);
+    }
+
+    #[test]
+    fn test_apply_mapping() {
+        let mapping: HashMap<String, String> = [("Work".to_string(), "work".to_string())].into_iter().collect();
+        let tags = vec!["Work".to_string(), "work".to_string(), "ideas".to_string()];
+        assert_eq!(apply_mapping(&tags, &mapping), Some(vec!["work".to_string(), "ideas".to_string()]));
+        assert_eq!(apply_mapping(&["ideas".to_string()], &mapping), None);
+    }
+}
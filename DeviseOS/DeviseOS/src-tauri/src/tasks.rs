@@ -0,0 +1,56 @@
+use std::collections::HashMap;
+
+/// Markdown task list items as `(checked, text)`: `- [ ] text`, `* [x] text` and so on,
+/// at any indent.
+fn tasks(content: &str) -> impl Iterator<Item = (bool, &str)> {
+    content.lines().filter_map(|line| {
+        let line = line.trim_start();
+        let rest = ["- ", "* ", "+ "].iter().find_map(|marker| line.strip_prefix(marker))?;
+        let (checked, text) = if let Some(text) = rest.strip_prefix("[ ] ") {
+            (false, text)
+        } else if let Some(text) = rest.strip_prefix("[x] ").or_else(|| rest.strip_prefix("[X] ")) {
+            (true, text)
+        } else {
+            return None;
+        };
+        let text = text.trim();
+        (!text.is_empty()).then_some((checked, text))
+    })
+}
+
+/// Tasks open in `before` and ticked in `after`, in the order they appear in `after`.
+/// Tasks are matched by their text, so ticking one of two identical items reports one.
+pub fn completed_tasks(before: &str, after: &str) -> Vec<String> {
+    let mut open: HashMap<&str, usize> = HashMap::new();
+    let mut done: HashMap<&str, usize> = HashMap::new();
+    for (checked, text) in tasks(before) {
+        let counts = if checked { &mut done } else { &mut open };
+        *counts.entry(text).or_default() += 1;
+    }
+
+    let mut completed = Vec::new();
+    for (_, text) in tasks(after).filter(|(checked, _)| *checked) {
+        if let Some(count) = done.get_mut(text).filter(|count| **count > 0) {
+            *count -= 1; // Already ticked before
+        } else if let Some(count) = open.get_mut(text).filter(|count| **count > 0) {
+            *count -= 1;
+            completed.push(text.to_string());
+        }
+    }
+    completed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_completed_tasks() {
+        let before = "# Today\n- [ ] Call Sam\n- [x] Pay rent\n  * [ ] Buy milk\n- [ ] Buy milk\n- Plain item";
+        let after = "# Today\n- [x] Call Sam\n- [x] Pay rent\n  * [X] Buy milk\n- [ ] Buy milk\n- [x] Plain item";
+        assert_eq!(completed_tasks(before, after), vec!["Call Sam", "Buy milk"]);
+        assert!(completed_tasks(after, after).is_empty());
+        assert!(completed_tasks(after, before).is_empty());
+        assert!(completed_tasks("", "- [x] New and done").is_empty());
+    }
+}
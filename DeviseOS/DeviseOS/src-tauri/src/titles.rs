@@ -0,0 +1,140 @@
+/// Setting that titles untitled pages from their content when they're saved.
+pub const AUTO_TITLE_KEY: &str = "auto_title_enabled";
+
+/// Only the start of a page is considered; titles describe what a page opens with.
+const TITLE_CHUNK_CHARS: usize = 2000;
+const MAX_TITLE_CHARS: usize = 60;
+const MAX_SUMMARY_CHARS: usize = 120;
+
+/// A concise title from the first heading, or else the first sentence, cut at a word
+/// boundary. `None` when the content has no usable text.
+pub fn derive_title(content: &str) -> Option<String> {
+    let chunk = opening(content);
+    let mut lines = chunk.lines().map(str::trim).filter(|line| !line.is_empty());
+
+    let heading = chunk.lines().find_map(|line| {
+        let line = line.trim();
+        let level = line.chars().take_while(|c| *c == '#').count();
+        match (level, line[level..].strip_prefix(' ')) {
+            (1..=6, Some(text)) if !text.trim().is_empty() => Some(text.trim().to_string()),
+            _ => None,
+        }
+    });
+    let text = match heading {
+        Some(heading) => heading,
+        None => first_sentence(strip_list_marker(lines.next()?)).to_string(),
+    };
+    let text = text.replace("**", "").replace("__", "").replace('`', "");
+    let text = text.trim();
+    if text.is_empty() {
+        return None;
+    }
+    Some(truncate_words(text, MAX_TITLE_CHARS))
+}
+
+/// The first sentence of body text, skipping headings, for one-line listings.
+pub fn summary_line(content: &str) -> Option<String> {
+    let chunk = opening(content);
+    let line = chunk
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#'))?;
+    let text = first_sentence(strip_list_marker(line)).replace("**", "").replace("__", "").replace('`', "");
+    let text = text.trim();
+    if text.is_empty() {
+        return None;
+    }
+    Some(truncate_words(text, MAX_SUMMARY_CHARS))
+}
+
+/// The start of `content` that titles are drawn from.
+pub fn opening(content: &str) -> String {
+    content.chars().take(TITLE_CHUNK_CHARS).collect()
+}
+
+/// A language model's title suggestion reduced to the title itself: its first line without
+/// a "Title:" label, heading marks, emphasis, quotes or a closing period.
+pub fn clean_title(completion: &str) -> Option<String> {
+    let line = completion.lines().map(str::trim).find(|line| !line.is_empty())?;
+    let line = line.strip_prefix("Title:").or_else(|| line.strip_prefix("title:")).unwrap_or(line);
+    let text = line.trim_start_matches('#').replace("**", "").replace("__", "").replace('`', "");
+    let text = text
+        .trim()
+        .trim_matches(|c| matches!(c, '"' | '\'' | '“' | '”' | '‘' | '’' | '*' | '_'))
+        .trim_end_matches('.')
+        .trim();
+    if text.is_empty() {
+        return None;
+    }
+    Some(truncate_words(text, MAX_TITLE_CHARS))
+}
+
+fn strip_list_marker(line: &str) -> &str {
+    let markers = ["- [ ] ", "- [x] ", "- ", "* ", "+ ", "> "];
+    markers.iter().find_map(|marker| line.strip_prefix(marker)).unwrap_or(line)
+}
+
+fn first_sentence(text: &str) -> &str {
+    let mut chars = text.char_indices().peekable();
+    while let Some((index, c)) = chars.next() {
+        let at_boundary = chars.peek().map(|(_, next)| next.is_whitespace()).unwrap_or(true);
+        if matches!(c, '.' | '!' | '?' | '。' | '！' | '？') && at_boundary {
+            // Keep question marks, they change what a title means
+            return if c == '?' || c == '？' { &text[..index + c.len_utf8()] } else { &text[..index] };
+        }
+    }
+    text
+}
+
+fn truncate_words(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let cut: String = text.chars().take(max_chars).collect();
+    let cut = match cut.rfind(char::is_whitespace) {
+        Some(space) if space > 0 => &cut[..space],
+        _ => cut.as_str(), // One long word, or a script without spaces
+    };
+    format!("{}…", cut.trim_end_matches(|c: char| c.is_whitespace() || c.is_ascii_punctuation()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prefers_first_heading() {
+        assert_eq!(derive_title("Some intro.\n\n## Q3 **Roadmap**\n\nDetails").as_deref(), Some("Q3 Roadmap"));
+        assert_eq!(derive_title("# Meeting notes\nAgenda").as_deref(), Some("Meeting notes"));
+    }
+
+    #[test]
+    fn test_falls_back_to_first_sentence() {
+        assert_eq!(derive_title("\n- Call the bank about v2.1 fees. Then email Sam").as_deref(), Some("Call the bank about v2.1 fees"));
+        assert_eq!(derive_title("Should we move the launch? Probably").as_deref(), Some("Should we move the launch?"));
+        assert_eq!(derive_title("   \n\n").as_deref(), None);
+        assert_eq!(derive_title("#hashtag only").as_deref(), Some("#hashtag only"));
+    }
+
+    #[test]
+    fn test_summary_skips_headings() {
+        assert_eq!(summary_line("# Habits\n\n## Idea\nSmall **wins** compound. More later").as_deref(), Some("Small wins compound"));
+        assert_eq!(summary_line("# Only a heading").as_deref(), None);
+    }
+
+    #[test]
+    fn test_cleans_model_titles() {
+        assert_eq!(clean_title("Title: \"Quarterly roadmap review.\"\nIt covers...").as_deref(), Some("Quarterly roadmap review"));
+        assert_eq!(clean_title("\n## **Trip to Lisbon**").as_deref(), Some("Trip to Lisbon"));
+        assert_eq!(clean_title("Should we move the launch?").as_deref(), Some("Should we move the launch?"));
+        assert_eq!(clean_title(" \"\" ").as_deref(), None);
+    }
+
+    #[test]
+    fn test_truncates_on_word_boundary() {
+        let title = derive_title(&"word ".repeat(40)).unwrap();
+        assert!(title.ends_with('…'));
+        assert!(title.chars().count() <= MAX_TITLE_CHARS + 1);
+        assert!(!title.contains("wor…"));
+    }
+}
@@ -0,0 +1,239 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::OnceLock;
+use regex::Regex;
+use crate::{
+    AppResult,
+    models::{Page, ResolvedContent},
+    database::Database,
+    wikilinks::normalize_title,
+};
+
+/// Embeds nested deeper than this stay links. Cycles are caught before that; this only
+/// bounds long chains.
+const MAX_DEPTH: usize = 8;
+/// Embeds expanded per page, and bytes of embedded content, before the rest stay links. A
+/// page embedding the same large page many times would otherwise grow without bound.
+const MAX_EMBEDS: usize = 200;
+const MAX_EMBEDDED_BYTES: usize = 4 * 1024 * 1024;
+
+/// `![[Title]]`, `![[Title#Heading]]` or `![[Title#^block-id]]`, with or without `|shown text`
+static EMBED_PATTERN: OnceLock<Regex> = OnceLock::new();
+
+fn embed_pattern() -> &'static Regex {
+    EMBED_PATTERN.get_or_init(|| Regex::new(r"!\[\[([^\[\]\n|#]+)(?:#([^\[\]\n|]*))?(?:\|[^\[\]\n]*)?\]\]").unwrap())
+}
+
+/// Titles embedded in `content`, once each, ignoring case.
+pub fn embedded_titles(content: &str) -> Vec<String> {
+    let mut titles: Vec<String> = Vec::new();
+    for captures in embed_pattern().captures_iter(content) {
+        let title = normalize_title(&captures[1]);
+        if !title.is_empty() && !titles.iter().any(|seen| seen.to_lowercase() == title.to_lowercase()) {
+            titles.push(title);
+        }
+    }
+    titles
+}
+
+/// The part of `content` named by the fragment after `#`: a heading's section, up to the
+/// next heading of the same or a higher level, or the line marked with `^block-id`.
+pub fn fragment(content: &str, fragment: &str) -> Option<String> {
+    let fragment = fragment.trim();
+    if let Some(block_id) = fragment.strip_prefix('^') {
+        let marker = format!(" ^{}", block_id);
+        return content
+            .lines()
+            .find(|line| line.trim_end().ends_with(&marker))
+            .map(|line| line.trim_end().trim_end_matches(&marker).to_string());
+    }
+
+    let mut lines = content.lines();
+    let section = loop {
+        let line = lines.next()?;
+        if let Some((level, text)) = heading(line) {
+            if normalize_title(text).to_lowercase() == normalize_title(fragment).to_lowercase() {
+                let mut section = vec![line];
+                section.extend(lines.take_while(|line| heading(line).map_or(true, |(next, _)| next > level)));
+                break section;
+            }
+        }
+    };
+    Some(section.join("\n").trim_end().to_string())
+}
+
+/// Level and text of a Markdown ATX heading.
+fn heading(line: &str) -> Option<(usize, &str)> {
+    let level = line.chars().take_while(|&c| c == '#').count();
+    let text = line[level..].strip_prefix(' ')?;
+    (1..=6).contains(&level).then_some((level, text.trim()))
+}
+
+/// `content` of the page `root_id` with its embeds replaced by what they embed, given the
+/// embedded pages by lowercased title as (id, content). Embeds that would repeat a page
+/// already being expanded are left as plain links, as are unknown pages and fragments and
+/// embeds past `MAX_EMBEDS` or `MAX_EMBEDDED_BYTES`.
+pub fn expand(root_id: &str, content: &str, pages: &HashMap<String, (String, String)>) -> ResolvedContent {
+    let mut expansion = Expansion {
+        pages,
+        embedded: Vec::new(),
+        cycles: Vec::new(),
+        missing: Vec::new(),
+        embeds: 0,
+        embedded_bytes: 0,
+        truncated: false,
+    };
+    let content = expansion.expand(content, &mut vec![root_id.to_string()]);
+    ResolvedContent {
+        page_id: root_id.to_string(),
+        content,
+        embedded_page_ids: expansion.embedded,
+        cycles: expansion.cycles,
+        missing: expansion.missing,
+        truncated: expansion.truncated,
+    }
+}
+
+struct Expansion<'a> {
+    pages: &'a HashMap<String, (String, String)>,
+    embedded: Vec<String>,
+    cycles: Vec<String>,
+    missing: Vec<String>,
+    embeds: usize,
+    embedded_bytes: usize, // Output is at most the root content plus this
+    truncated: bool,
+}
+
+impl Expansion<'_> {
+    fn expand(&mut self, content: &str, stack: &mut Vec<String>) -> String {
+        // Copied out so the pages borrowed below don't hold `self` across the recursion
+        let pages = self.pages;
+        let pattern = embed_pattern();
+        let mut output = String::with_capacity(content.len());
+        let mut last = 0;
+        for captures in pattern.captures_iter(content) {
+            let whole = captures.get(0).unwrap();
+            output.push_str(&content[last..whole.start()]);
+            last = whole.end();
+            let link = &whole.as_str()[1..];
+
+            let title = normalize_title(&captures[1]);
+            let Some((id, body)) = pages.get(&title.to_lowercase()) else {
+                push_unique(&mut self.missing, title);
+                output.push_str(whole.as_str());
+                continue;
+            };
+            if stack.contains(id) || stack.len() > MAX_DEPTH {
+                push_unique(&mut self.cycles, title);
+                output.push_str(link);
+                continue;
+            }
+            let body = match captures.get(2) {
+                Some(name) => match fragment(body, name.as_str()) {
+                    Some(section) => section,
+                    None => {
+                        push_unique(&mut self.missing, format!("{}#{}", title, name.as_str()));
+                        output.push_str(whole.as_str());
+                        continue;
+                    }
+                },
+                None => body.clone(),
+            };
+            if self.embeds >= MAX_EMBEDS || self.embedded_bytes + body.len() > MAX_EMBEDDED_BYTES {
+                self.truncated = true;
+                output.push_str(link);
+                continue;
+            }
+            self.embeds += 1;
+            self.embedded_bytes += body.len();
+
+            stack.push(id.clone());
+            output.push_str(&self.expand(&body, stack));
+            stack.pop();
+            push_unique(&mut self.embedded, id.clone());
+        }
+        output.push_str(&content[last..]);
+        output
+    }
+}
+
+fn push_unique(items: &mut Vec<String>, item: String) {
+    if !items.contains(&item) {
+        items.push(item);
+    }
+}
+
+/// Expands the embeds in `page`, loading each embedded page once. Titles resolve as
+/// `[[wikilinks]]` do, preferring the page's own notebook.
+pub async fn resolve(database: &Database, page: &Page) -> AppResult<ResolvedContent> {
+    let mut pages = HashMap::new();
+    let mut looked_up = HashSet::new();
+    let mut queue = embedded_titles(&page.content);
+    while let Some(title) = queue.pop() {
+        if !looked_up.insert(title.to_lowercase()) {
+            continue;
+        }
+        let Some(id) = database.find_page_id_by_title(&title, &page.notebook_id).await? else {
+            continue;
+        };
+        let Some(embedded) = database.get_page(&id).await? else {
+            continue;
+        };
+        queue.extend(embedded_titles(&embedded.content));
+        pages.insert(title.to_lowercase(), (embedded.id, embedded.content));
+    }
+    Ok(expand(&page.id, &page.content, &pages))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pages(entries: &[(&str, &str, &str)]) -> HashMap<String, (String, String)> {
+        entries
+            .iter()
+            .map(|(title, id, content)| (title.to_lowercase(), (id.to_string(), content.to_string())))
+            .collect()
+    }
+
+    #[test]
+    fn test_fragment() {
+        let content = "# Book\n\nIntro\n\n## Rules\n\nWork deeply\n\n### Rule 1\n\nFocus\n\n## Notes\n\nQuote ^q1";
+        assert_eq!(fragment(content, "rules").as_deref(), Some("## Rules\n\nWork deeply\n\n### Rule 1\n\nFocus"));
+        assert_eq!(fragment(content, "^q1").as_deref(), Some("Quote"));
+        assert_eq!(fragment(content, "Missing"), None);
+        assert_eq!(fragment("#hashtag line", "hashtag line"), None);
+    }
+
+    #[test]
+    fn test_expand_nested_embeds() {
+        let pages = pages(&[("Outer", "b", "Outer start ![[Inner#^k]] end"), ("Inner", "c", "skip\nkept ^k")]);
+        let resolved = expand("a", "Top: ![[outer]] and ![[Nowhere]]", &pages);
+        assert_eq!(resolved.content, "Top: Outer start kept end and ![[Nowhere]]");
+        assert_eq!(resolved.embedded_page_ids, vec!["c", "b"]);
+        assert_eq!(resolved.missing, vec!["Nowhere"]);
+        assert!(resolved.cycles.is_empty());
+    }
+
+    #[test]
+    fn test_expand_stops_at_cycles() {
+        let pages = pages(&[("A", "a", "A embeds ![[B]]"), ("B", "b", "B embeds ![[A]]")]);
+        let resolved = expand("a", "A embeds ![[B]]", &pages);
+        assert_eq!(resolved.content, "A embeds B embeds [[A]]");
+        assert_eq!(resolved.cycles, vec!["A"]);
+        assert_eq!(embedded_titles("![[B]] [[C]] ![[b|again]]"), vec!["B"]);
+    }
+
+    #[test]
+    fn test_expand_limits_output() {
+        let big = "x".repeat(MAX_EMBEDDED_BYTES / 3);
+        let big_pages = pages(&[("Big", "b", big.as_str())]);
+        let resolved = expand("a", "![[Big]] ![[Big]] ![[Big]] ![[Big]]", &big_pages);
+        assert_eq!(resolved.content, format!("{} {} {} [[Big]]", big, big, big));
+        assert!(resolved.truncated);
+
+        let small_pages = pages(&[("Small", "s", "s")]);
+        let resolved = expand("a", &"![[Small]]".repeat(MAX_EMBEDS + 1), &small_pages);
+        assert_eq!(resolved.content, format!("{}[[Small]]", "s".repeat(MAX_EMBEDS)));
+        assert!(resolved.truncated);
+    }
+}
@@ -0,0 +1,120 @@
+use std::sync::Arc;
+use tauri::{AppHandle, Emitter};
+use tokio::sync::RwLock;
+use uuid::Uuid;
+use crate::{
+    AppError, AppResult,
+    models::{PartialTranscript, TranscriptionStream},
+    ai::{AIService, PCM_BYTES_PER_SECOND},
+};
+
+pub const TRANSCRIPTION_PARTIAL_EVENT: &str = "transcription-partial";
+
+/// Whisper works on 30 second chunks, so that's the default window.
+const DEFAULT_WINDOW_SECS: u32 = 30;
+const MAX_WINDOW_SECS: u32 = 120;
+
+/// Transcribes long recordings window by window in the background and returns immediately.
+/// Each window's text is reported through a `transcription-partial` event with its
+/// timestamps, so captions can show up while the rest is still being processed. The last
+/// event has `finished` set; a failed window is reported with `error` and skipped. Without
+/// a `language`, the one detected in the first window with speech is used for the rest.
+pub async fn spawn_transcription(
+    app: AppHandle,
+    ai_service: Arc<RwLock<AIService>>,
+    audio_data: Vec<u8>,
+    language: Option<String>,
+    window_secs: Option<u32>,
+) -> AppResult<TranscriptionStream> {
+    if !ai_service.read().await.is_whisper_available() {
+        return Err(AppError::ModelNotFound("Whisper model not available".to_string()));
+    }
+    if audio_data.is_empty() {
+        return Err(AppError::InvalidAudioFormat("Audio is empty".to_string()));
+    }
+    let window_secs = window_secs.unwrap_or(DEFAULT_WINDOW_SECS).clamp(1, MAX_WINDOW_SECS);
+    let windows = windows(audio_data.len(), window_secs as usize * PCM_BYTES_PER_SECOND);
+
+    let stream = TranscriptionStream {
+        stream_id: Uuid::new_v4().to_string(),
+        windows: windows.len(),
+        duration_secs: seconds(audio_data.len()),
+    };
+    let stream_id = stream.stream_id.clone();
+
+    tauri::async_runtime::spawn(async move {
+        let mut language = language;
+        let mut transcript = String::new();
+        let last = windows.len() - 1;
+        for (index, (start, end)) in windows.into_iter().enumerate() {
+            // The lock is taken per window so model changes aren't blocked by long recordings
+            let result = ai_service.read().await
+                .transcribe_with_language(&audio_data[start..end], language.as_deref())
+                .await;
+            let (text, error) = match result {
+                Ok((text, _, detected)) => {
+                    if language.is_none() {
+                        language = detected;
+                    }
+                    (text.trim().to_string(), None)
+                }
+                Err(e) => {
+                    tracing::warn!("Failed to transcribe window {} of stream {}: {}", index, stream_id, e);
+                    (String::new(), Some(e.to_string()))
+                }
+            };
+            if !text.is_empty() {
+                if !transcript.is_empty() {
+                    transcript.push(' ');
+                }
+                transcript.push_str(&text);
+            }
+
+            let partial = PartialTranscript {
+                stream_id: stream_id.clone(),
+                index,
+                start_secs: seconds(start),
+                end_secs: seconds(end),
+                text,
+                transcript: transcript.clone(),
+                language: language.clone(),
+                error,
+                finished: index == last,
+            };
+            let _ = app.emit(TRANSCRIPTION_PARTIAL_EVENT, &partial);
+        }
+        tracing::info!("Finished streaming transcription {}", stream_id);
+    });
+
+    Ok(stream)
+}
+
+/// Byte ranges of consecutive windows, cut on sample boundaries.
+pub fn windows(len: usize, window_bytes: usize) -> Vec<(usize, usize)> {
+    let window_bytes = window_bytes.max(2) & !1;
+    (0..len)
+        .step_by(window_bytes)
+        .map(|start| (start, (start + window_bytes).min(len)))
+        .collect()
+}
+
+pub fn seconds(bytes: usize) -> f64 {
+    bytes as f64 / PCM_BYTES_PER_SECOND as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_windows_cover_audio() {
+        let len = 65 * PCM_BYTES_PER_SECOND + 10;
+        let ranges = windows(len, 30 * PCM_BYTES_PER_SECOND);
+
+        assert_eq!(ranges.len(), 3);
+        assert_eq!(ranges[1], (30 * PCM_BYTES_PER_SECOND, 60 * PCM_BYTES_PER_SECOND));
+        assert_eq!(ranges[2].1, len);
+        assert_eq!(seconds(ranges[2].0), 60.0);
+        assert_eq!(windows(5, 3), vec![(0, 2), (2, 4), (4, 5)]);
+    }
+}
@@ -0,0 +1,55 @@
+use chrono::{DateTime, Duration, Utc};
+use crate::{
+    AppResult,
+    database::Database,
+};
+
+/// Setting with how many days deleted notes and pages stay in the trash; 0 keeps them
+/// until the trash is emptied.
+pub const TRASH_RETENTION_KEY: &str = "trash_retention_days";
+pub const DEFAULT_RETENTION_DAYS: &str = "30";
+
+pub async fn retention_days(database: &Database) -> AppResult<u32> {
+    let value = database.get_setting(TRASH_RETENTION_KEY).await?;
+    Ok(parse_days(value.as_deref().unwrap_or(DEFAULT_RETENTION_DAYS)).unwrap_or(30))
+}
+
+/// Permanently deletes trash older than the retention period; returns how many items went.
+pub async fn purge_expired(database: &Database) -> AppResult<u64> {
+    let Some(cutoff) = cutoff(retention_days(database).await?, Utc::now()) else {
+        return Ok(0);
+    };
+    let purged = database.purge_trash(cutoff).await?;
+    if purged > 0 {
+        tracing::info!("Purged {} items from the trash", purged);
+    }
+    Ok(purged)
+}
+
+pub fn parse_days(value: &str) -> Option<u32> {
+    value.trim().parse().ok()
+}
+
+/// Items deleted before this are expired; `None` when they're kept indefinitely, including
+/// for periods reaching back past the earliest representable date.
+fn cutoff(retention_days: u32, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    if retention_days == 0 {
+        return None;
+    }
+    now.checked_sub_signed(Duration::days(retention_days as i64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cutoff() {
+        let now = Utc::now();
+        assert_eq!(cutoff(30, now), Some(now - Duration::days(30)));
+        assert_eq!(cutoff(0, now), None);
+        assert_eq!(cutoff(u32::MAX, now), None);
+        assert_eq!(parse_days(" 7 "), Some(7));
+        assert_eq!(parse_days("-1"), None);
+    }
+}
@@ -0,0 +1,192 @@
+use std::time::Duration;
+use chrono::Utc;
+use crate::{
+    AppError, AppResult,
+    models::{ReleaseManifest, UpdateCheckConfig, UpdateInfo},
+    database::Database,
+};
+
+pub const UPDATE_CONFIG_KEY: &str = "update_check_config";
+const UPDATE_INFO_KEY: &str = "update_info";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+const MAX_MANIFEST_SIZE: usize = 64 * 1024;
+
+pub const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Checks a static release manifest for new versions. The request carries no identifiers:
+/// no version, platform or install id in the URL or headers, and no cookies.
+pub struct UpdateChecker {
+    http_client: reqwest::Client,
+}
+
+impl UpdateChecker {
+    pub fn new() -> AppResult<Self> {
+        let http_client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .user_agent("DeviseOS")
+            .https_only(true)
+            .build()
+            .map_err(|e| AppError::Network(format!("Failed to create HTTP client: {}", e)))?;
+
+        Ok(Self { http_client })
+    }
+
+    pub async fn get_config(&self, database: &Database) -> AppResult<UpdateCheckConfig> {
+        match database.get_setting(UPDATE_CONFIG_KEY).await? {
+            Some(value) => Ok(serde_json::from_str(&value)?),
+            None => Ok(UpdateCheckConfig::default()),
+        }
+    }
+
+    /// Rejects a configuration that can't work, when the setting is validated.
+    pub fn check_config(config: &UpdateCheckConfig) -> Result<(), String> {
+        if config.enabled && config.manifest_url.is_empty() {
+            return Err("Update checks need a manifest URL".to_string());
+        }
+        if !config.manifest_url.is_empty() && !config.manifest_url.starts_with("https://") {
+            return Err("Update manifest URL must use HTTPS".to_string());
+        }
+        Ok(())
+    }
+
+    /// Returns the result of the last check without touching the network.
+    pub async fn get_update_info(&self, database: &Database) -> AppResult<UpdateInfo> {
+        let cached: Option<UpdateInfo> = match database.get_setting(UPDATE_INFO_KEY).await? {
+            Some(value) => serde_json::from_str(&value).ok(),
+            None => None,
+        };
+
+        // Re-evaluate against the running version in case the app was updated since
+        Ok(match cached {
+            Some(info) => build_info(info.latest, info.checked_at),
+            None => build_info(None, None),
+        })
+    }
+
+    pub async fn check_for_updates(&self, database: &Database) -> AppResult<UpdateInfo> {
+        let config = self.get_config(database).await?;
+        if !config.enabled {
+            return Err(AppError::InvalidOperation("Update checks are disabled".to_string()));
+        }
+
+        let manifest = self.fetch_manifest(&config.manifest_url).await?;
+        let info = build_info(Some(manifest), Some(Utc::now()));
+        database.set_setting(UPDATE_INFO_KEY, &serde_json::to_string(&info)?).await?;
+
+        Ok(info)
+    }
+
+    /// Runs a check if enabled and the configured interval has passed since the last one.
+    pub async fn check_if_due(&self, database: &Database) -> AppResult<Option<UpdateInfo>> {
+        let config = self.get_config(database).await?;
+        if !config.enabled {
+            return Ok(None);
+        }
+
+        let last_checked = self.get_update_info(database).await?.checked_at;
+        let interval = chrono::Duration::hours(config.check_interval_hours.max(1) as i64);
+        if last_checked.map(|at| Utc::now() - at < interval).unwrap_or(false) {
+            return Ok(None);
+        }
+
+        self.check_for_updates(database).await.map(Some)
+    }
+
+    async fn fetch_manifest(&self, url: &str) -> AppResult<ReleaseManifest> {
+        let mut response = self.http_client
+            .get(url)
+            .send()
+            .await
+            .map_err(|e| AppError::Network(format!("Update check failed: {}", e)))?;
+
+        if !response.status().is_success() {
+            return Err(AppError::Network(format!("Update check returned {}", response.status())));
+        }
+
+        let too_large = || AppError::InvalidFormat("Update manifest is too large".to_string());
+        if response.content_length().is_some_and(|len| len > MAX_MANIFEST_SIZE as u64) {
+            return Err(too_large());
+        }
+
+        // Stream the body so an endpoint that lies about (or omits) its length can't make us
+        // buffer more than the limit
+        let mut body = Vec::new();
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .map_err(|e| AppError::Network(format!("Failed to read update manifest: {}", e)))?
+        {
+            if body.len() + chunk.len() > MAX_MANIFEST_SIZE {
+                return Err(too_large());
+            }
+            body.extend_from_slice(&chunk);
+        }
+
+        let manifest: ReleaseManifest = serde_json::from_slice(&body)?;
+        if parse_version(&manifest.version).is_none() {
+            return Err(AppError::InvalidFormat(format!("Invalid release version: {}", manifest.version)));
+        }
+        Ok(manifest)
+    }
+}
+
+fn build_info(latest: Option<ReleaseManifest>, checked_at: Option<chrono::DateTime<Utc>>) -> UpdateInfo {
+    let update_available = latest
+        .as_ref()
+        .map(|manifest| is_newer(&manifest.version, CURRENT_VERSION))
+        .unwrap_or(false);
+
+    UpdateInfo {
+        current_version: CURRENT_VERSION.to_string(),
+        latest,
+        update_available,
+        checked_at,
+    }
+}
+
+/// Parses `major.minor.patch`, ignoring a leading `v` and any pre-release or build suffix.
+pub fn parse_version(version: &str) -> Option<(u64, u64, u64)> {
+    let core = version
+        .trim()
+        .trim_start_matches('v')
+        .split(['-', '+'])
+        .next()?;
+
+    let mut parts = core.split('.').map(|p| p.parse::<u64>());
+    let major = parts.next()?.ok()?;
+    let minor = parts.next().unwrap_or(Ok(0)).ok()?;
+    let patch = parts.next().unwrap_or(Ok(0)).ok()?;
+    if parts.next().is_some() {
+        return None;
+    }
+    Some((major, minor, patch))
+}
+
+pub fn is_newer(candidate: &str, current: &str) -> bool {
+    match (parse_version(candidate), parse_version(current)) {
+        (Some(candidate), Some(current)) => candidate > current,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!(parse_version("1.2.3"), Some((1, 2, 3)));
+        assert_eq!(parse_version("v2.0"), Some((2, 0, 0)));
+        assert_eq!(parse_version("0.3.1-beta.2"), Some((0, 3, 1)));
+        assert_eq!(parse_version("latest"), None);
+        assert_eq!(parse_version("1.2.3.4"), None);
+    }
+
+    #[test]
+    fn test_is_newer() {
+        assert!(is_newer("0.2.0", "0.1.9"));
+        assert!(is_newer("1.0.0", "0.99.99"));
+        assert!(!is_newer("0.1.0", "0.1.0"));
+        assert!(!is_newer("garbage", "0.1.0"));
+    }
+}
@@ -0,0 +1,125 @@
+use crate::{
+    ai::PCM_BYTES_PER_SECOND,
+    transcription::windows,
+};
+
+/// Energy is measured over 30 ms frames.
+const FRAME_BYTES: usize = PCM_BYTES_PER_SECOND * 30 / 1000;
+/// Pauses shorter than this stay inside a segment.
+const MIN_SILENCE_FRAMES: usize = 17; // ~500 ms
+/// Shorter bursts are clicks and bumps, not speech.
+const MIN_SPEECH_FRAMES: usize = 8; // ~250 ms
+/// Kept around each segment so word onsets and endings aren't clipped.
+const PADDING_FRAMES: usize = 7; // ~200 ms
+/// Whisper works on 30 second chunks, so longer segments are cut.
+const MAX_SEGMENT_SECS: usize = 30;
+/// RMS of 16-bit samples below which a frame is silence whatever the noise floor (~-40 dBFS).
+const SILENCE_RMS: f64 = 300.0;
+/// Speech must be this many times louder than the quietest frames.
+const NOISE_MARGIN: f64 = 3.0;
+
+/// Byte ranges of the stretches of speech in 16 kHz mono 16-bit PCM, in order and cut on
+/// sample boundaries. Empty when the recording is silent.
+pub fn speech_segments(audio: &[u8]) -> Vec<(usize, usize)> {
+    let energies: Vec<f64> = audio.chunks(FRAME_BYTES).map(rms).collect();
+    if energies.is_empty() {
+        return Vec::new();
+    }
+    let threshold = threshold(&energies);
+
+    // Runs of loud frames, joined across short pauses
+    let mut runs: Vec<(usize, usize)> = Vec::new();
+    for (frame, energy) in energies.iter().enumerate() {
+        if *energy < threshold {
+            continue;
+        }
+        match runs.last_mut() {
+            Some((_, end)) if frame - *end < MIN_SILENCE_FRAMES => *end = frame + 1,
+            _ => runs.push((frame, frame + 1)),
+        }
+    }
+
+    let mut segments: Vec<(usize, usize)> = Vec::new();
+    for (start, end) in runs.into_iter().filter(|(start, end)| end - start >= MIN_SPEECH_FRAMES) {
+        let start = start.saturating_sub(PADDING_FRAMES);
+        let end = (end + PADDING_FRAMES).min(energies.len());
+        match segments.last_mut() {
+            Some((_, previous_end)) if start <= *previous_end => *previous_end = end,
+            _ => segments.push((start, end)),
+        }
+    }
+
+    segments
+        .into_iter()
+        .flat_map(|(start, end)| {
+            let start = start * FRAME_BYTES;
+            let end = (end * FRAME_BYTES).min(audio.len());
+            windows(end - start, MAX_SEGMENT_SECS * PCM_BYTES_PER_SECOND)
+                .into_iter()
+                .map(move |(from, to)| (start + from, start + to))
+        })
+        .collect()
+}
+
+/// Above the noise floor when the recording has quiet parts to measure it from; otherwise
+/// it's all speech or all noise and only the absolute floor applies.
+fn threshold(energies: &[f64]) -> f64 {
+    let mut sorted = energies.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let quiet = sorted[sorted.len() / 10];
+    let loud = sorted[sorted.len() * 9 / 10];
+    if loud > quiet * NOISE_MARGIN {
+        (quiet * NOISE_MARGIN).max(SILENCE_RMS)
+    } else {
+        SILENCE_RMS
+    }
+}
+
+fn rms(frame: &[u8]) -> f64 {
+    let samples: Vec<f64> = frame.chunks_exact(2).map(|s| i16::from_le_bytes([s[0], s[1]]) as f64).collect();
+    if samples.is_empty() {
+        return 0.0;
+    }
+    (samples.iter().map(|s| s * s).sum::<f64>() / samples.len() as f64).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcription::seconds;
+
+    /// A 440 Hz tone at `amplitude`, or silence for 0.
+    fn tone(secs: f64, amplitude: f64) -> Vec<u8> {
+        let samples = (secs * 16_000.0) as usize;
+        (0..samples)
+            .flat_map(|i| {
+                let value = amplitude * (i as f64 * 440.0 * std::f64::consts::TAU / 16_000.0).sin();
+                (value as i16).to_le_bytes()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_speech_segments() {
+        let audio = [tone(1.0, 20.0), tone(1.0, 5000.0), tone(0.3, 20.0), tone(0.5, 5000.0), tone(1.5, 20.0), tone(0.1, 8000.0), tone(1.0, 20.0), tone(1.0, 4000.0)].concat();
+
+        let segments = speech_segments(&audio);
+        // The short pause is bridged and the click dropped
+        assert_eq!(segments.len(), 2);
+        assert!((seconds(segments[0].0) - 0.8).abs() < 0.05);
+        assert!((seconds(segments[0].1) - 3.0).abs() < 0.05);
+        assert!((seconds(segments[1].0) - 5.2).abs() < 0.05);
+        assert_eq!(segments[1].1, audio.len());
+        assert!(segments.iter().all(|(start, end)| start % 2 == 0 && end % 2 == 0));
+    }
+
+    #[test]
+    fn test_silence_and_long_speech() {
+        assert!(speech_segments(&tone(5.0, 20.0)).is_empty());
+        assert!(speech_segments(&[]).is_empty());
+
+        let segments = speech_segments(&tone(70.0, 3000.0));
+        assert_eq!(segments.len(), 3);
+        assert_eq!(seconds(segments[1].0), 30.0);
+    }
+}
@@ -0,0 +1,201 @@
+use std::io::Cursor;
+use ogg::{PacketReader, PacketWriteEndInfo, PacketWriter};
+use opus::{Application, Bitrate, Channels, Decoder, Encoder};
+use crate::{AppError, AppResult, models::VoiceMetadata, audio};
+
+/// `VoiceMetadata::format` of recordings stored as Ogg Opus.
+pub const OPUS_FORMAT: &str = "opus";
+/// `VoiceMetadata::format` a caller sets for bare 16-bit little-endian samples.
+pub const PCM_FORMAT: &str = "pcm";
+
+/// Plenty for speech: about a tenth of the 256 kbit/s of 16 kHz PCM.
+const BITRATE: i32 = 24_000;
+const FRAME_MS: usize = 20;
+/// Ogg Opus granule positions always count 48 kHz samples.
+const GRANULE_RATE: u64 = 48_000;
+const STREAM_SERIAL: u32 = 1;
+const MAX_FRAME_SAMPLES: usize = 5760; // 120 ms at 48 kHz
+
+/// `audio` as Ogg Opus, encoded off the async runtime; `metadata` then describes the Opus
+/// recording. `None`, with `metadata` untouched, unless `mono_pcm` is sure of the samples.
+pub async fn compress(audio: Vec<u8>, metadata: &mut VoiceMetadata) -> AppResult<Option<Vec<u8>>> {
+    let Some((_, sample_rate)) = mono_pcm(&audio, metadata) else {
+        return Ok(None);
+    };
+    let source = metadata.clone();
+    let encoded = tokio::task::spawn_blocking(move || match mono_pcm(&audio, &source) {
+        Some((pcm, sample_rate)) => encode(pcm, sample_rate),
+        None => Ok(None),
+    })
+    .await
+    .map_err(|e| AppError::Unknown(format!("Opus encoding task failed: {}", e)))??;
+
+    if encoded.is_some() {
+        metadata.format = OPUS_FORMAT.to_string();
+        metadata.sample_rate = sample_rate;
+        metadata.channels = 1;
+    }
+    Ok(encoded)
+}
+
+/// The bare samples of `audio` and their rate, only when it's certainly 16-bit mono PCM:
+/// a WAV whose header says so, or samples the caller marked as `pcm`. Compressed audio
+/// stored under a default format can't be told apart from samples, so nothing else counts.
+pub fn mono_pcm<'a>(audio: &'a [u8], metadata: &VoiceMetadata) -> Option<(&'a [u8], u32)> {
+    if audio.starts_with(b"RIFF") {
+        return audio::parse_wav(audio)
+            .filter(|wav| wav.pcm && wav.channels == 1 && wav.bits_per_sample == 16)
+            .map(|wav| (wav.data, wav.sample_rate));
+    }
+    (metadata.format == PCM_FORMAT && metadata.channels == 1).then_some((audio, metadata.sample_rate))
+}
+
+/// A stored recording back as the PCM it was recorded as, for transcription and playback.
+/// Opus is decoded off the async runtime, after which `metadata` describes the PCM.
+pub async fn to_pcm(audio: Vec<u8>, metadata: &mut VoiceMetadata) -> AppResult<Vec<u8>> {
+    if metadata.format != OPUS_FORMAT {
+        return Ok(audio);
+    }
+    let pcm = tokio::task::spawn_blocking(move || decode(&audio))
+        .await
+        .map_err(|e| AppError::Unknown(format!("Opus decoding task failed: {}", e)))??;
+    metadata.format = PCM_FORMAT.to_string();
+    Ok(pcm)
+}
+
+/// Encodes bare mono 16-bit PCM as Ogg Opus. `None` for sample rates Opus can't take, in
+/// which case the recording is stored as it is.
+pub fn encode(pcm: &[u8], sample_rate: u32) -> AppResult<Option<Vec<u8>>> {
+    if ![8_000, 12_000, 16_000, 24_000, 48_000].contains(&sample_rate) {
+        return Ok(None);
+    }
+    let samples: Vec<i16> = pcm.chunks_exact(2).map(|pair| i16::from_le_bytes([pair[0], pair[1]])).collect();
+
+    let mut encoder = Encoder::new(sample_rate, Channels::Mono, Application::Voip).map_err(codec_error)?;
+    encoder.set_bitrate(Bitrate::Bits(BITRATE)).map_err(codec_error)?;
+    let scale = GRANULE_RATE / sample_rate as u64;
+    let pre_skip = encoder.get_lookahead().map_err(codec_error)? as u64 * scale;
+
+    let mut writer = PacketWriter::new(Vec::new());
+    writer.write_packet(opus_head(pre_skip as u16, sample_rate), STREAM_SERIAL, PacketWriteEndInfo::EndPage, 0).map_err(codec_error)?;
+    writer.write_packet(opus_tags(), STREAM_SERIAL, PacketWriteEndInfo::EndPage, 0).map_err(codec_error)?;
+
+    let frame_samples = sample_rate as usize * FRAME_MS / 1000;
+    let frame_count = samples.len().div_ceil(frame_samples).max(1);
+    let mut frame = vec![0i16; frame_samples];
+    for index in 0..frame_count {
+        let start = index * frame_samples;
+        let end = (start + frame_samples).min(samples.len());
+        frame.fill(0); // The last frame is padded with silence, trimmed again by its granule
+        frame[..end.saturating_sub(start)].copy_from_slice(&samples[start.min(end)..end]);
+        let packet = encoder.encode_vec(&frame, 4000).map_err(codec_error)?;
+
+        let granule = pre_skip + end as u64 * scale;
+        let end_info = if index + 1 == frame_count { PacketWriteEndInfo::EndStream } else { PacketWriteEndInfo::NormalPacket };
+        writer.write_packet(packet, STREAM_SERIAL, end_info, granule).map_err(codec_error)?;
+    }
+    Ok(Some(writer.into_inner()))
+}
+
+/// Decodes Ogg Opus from `encode` back to 16-bit PCM at the rate it was recorded at.
+pub fn decode(ogg: &[u8]) -> AppResult<Vec<u8>> {
+    let mut reader = PacketReader::new(Cursor::new(ogg));
+    let head = reader.read_packet().map_err(codec_error)?
+        .ok_or_else(|| AppError::InvalidAudioFormat("Empty Opus stream".to_string()))?;
+    let (pre_skip, sample_rate) = parse_opus_head(&head.data)?;
+    let scale = GRANULE_RATE / sample_rate as u64;
+    reader.read_packet().map_err(codec_error)?; // OpusTags
+
+    let mut decoder = Decoder::new(sample_rate, Channels::Mono).map_err(codec_error)?;
+    let mut samples: Vec<i16> = Vec::new();
+    let mut buffer = vec![0i16; MAX_FRAME_SAMPLES];
+    let mut last_granule = 0;
+    while let Some(packet) = reader.read_packet().map_err(codec_error)? {
+        let decoded = decoder.decode(&packet.data, &mut buffer, false).map_err(codec_error)?;
+        samples.extend_from_slice(&buffer[..decoded]);
+        last_granule = packet.absgp_page();
+    }
+
+    let skip = (pre_skip / scale) as usize;
+    let length = (last_granule.saturating_sub(pre_skip) / scale) as usize;
+    let samples = samples.get(skip..).unwrap_or_default();
+    Ok(samples[..length.min(samples.len())].iter().flat_map(|sample| sample.to_le_bytes()).collect())
+}
+
+fn opus_head(pre_skip: u16, sample_rate: u32) -> Vec<u8> {
+    let mut head = b"OpusHead".to_vec();
+    head.push(1); // Version
+    head.push(1); // Channels
+    head.extend_from_slice(&pre_skip.to_le_bytes());
+    head.extend_from_slice(&sample_rate.to_le_bytes());
+    head.extend_from_slice(&0i16.to_le_bytes()); // Output gain
+    head.push(0); // Channel mapping family: mono or stereo
+    head
+}
+
+fn opus_tags() -> Vec<u8> {
+    let vendor = b"DeviseOS";
+    let mut tags = b"OpusTags".to_vec();
+    tags.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+    tags.extend_from_slice(vendor);
+    tags.extend_from_slice(&0u32.to_le_bytes()); // No user comments
+    tags
+}
+
+/// Pre-skip in 48 kHz samples and the original sample rate.
+fn parse_opus_head(head: &[u8]) -> AppResult<(u64, u32)> {
+    if head.len() < 19 || !head.starts_with(b"OpusHead") || head[9] != 1 {
+        return Err(AppError::InvalidAudioFormat("Not a mono Ogg Opus stream".to_string()));
+    }
+    let pre_skip = u16::from_le_bytes([head[10], head[11]]) as u64;
+    let sample_rate = u32::from_le_bytes([head[12], head[13], head[14], head[15]]);
+    if ![8_000, 12_000, 16_000, 24_000, 48_000].contains(&sample_rate) {
+        return Err(AppError::InvalidAudioFormat(format!("Unsupported Opus input rate {}", sample_rate)));
+    }
+    Ok((pre_skip, sample_rate))
+}
+
+fn codec_error(e: impl std::fmt::Display) -> AppError {
+    AppError::InvalidAudioFormat(format!("Opus: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_keeps_length() {
+        // Half a second of a 440 Hz tone, not a whole number of frames
+        let pcm: Vec<u8> = (0..8_100)
+            .map(|i| ((i as f64 * 440.0 * std::f64::consts::TAU / 16_000.0).sin() * 8_000.0) as i16)
+            .flat_map(|sample| sample.to_le_bytes())
+            .collect();
+        let ogg = encode(&pcm, 16_000).unwrap().unwrap();
+        assert!(ogg.starts_with(b"OggS"));
+        assert!(ogg.len() * 4 < pcm.len());
+        assert_eq!(decode(&ogg).unwrap().len(), pcm.len());
+    }
+
+    #[test]
+    fn test_only_certain_pcm_is_encoded() {
+        let pcm = vec![0u8; 3_200];
+        let raw = VoiceMetadata { format: PCM_FORMAT.to_string(), ..VoiceMetadata::default() };
+        assert_eq!(mono_pcm(&pcm, &raw), Some((&pcm[..], 16_000)));
+
+        // Bytes under the default format may be an imported MP3
+        assert_eq!(mono_pcm(b"ID3\x04\0\0", &VoiceMetadata::default()), None);
+        assert_eq!(mono_pcm(&pcm, &VoiceMetadata::default()), None);
+
+        // The WAV header decides, not the metadata
+        let wav = audio::wav(&pcm, 44_100, 1);
+        assert_eq!(mono_pcm(&wav, &VoiceMetadata::default()), Some((&pcm[..], 44_100)));
+        assert_eq!(mono_pcm(&audio::wav(&pcm, 16_000, 2), &raw), None);
+    }
+
+    #[test]
+    fn test_unsupported_rates_are_left_alone() {
+        assert!(encode(&[0; 100], 44_100).unwrap().is_none());
+        assert_eq!(parse_opus_head(&opus_head(312, 16_000)).unwrap(), (312, 16_000));
+        assert!(decode(b"not ogg").is_err());
+    }
+}
@@ -0,0 +1,201 @@
+use std::sync::OnceLock;
+use regex::Regex;
+use crate::{
+    AppError, AppResult, AppState,
+    models::{AiJobPriority, EmbeddingOwner, SpokenNameKind, UpdatePageRequest, VoiceCommandAction, VoiceCommandResult, VoiceIntent},
+    database::Database,
+    titles, voice_match,
+};
+
+/// "New note titled ...", "create a note called ...", "note: ...".
+static CREATE_NOTE_PATTERN: OnceLock<Regex> = OnceLock::new();
+/// "Add to <page>: ...", "append this to the <page>: ...".
+static APPEND_PATTERN: OnceLock<Regex> = OnceLock::new();
+/// "titled", "called" or "named" before a note title.
+static NAMED_PATTERN: OnceLock<Regex> = OnceLock::new();
+/// Between a spoken title and the note's content.
+static CONTENT_SEPARATOR: OnceLock<Regex> = OnceLock::new();
+/// "..., tagged shopping and errands" at the end of a command.
+static TAGGED_PATTERN: OnceLock<Regex> = OnceLock::new();
+static LIST_SEPARATOR: OnceLock<Regex> = OnceLock::new();
+
+/// Transcribes a spoken command, works out what it asks for and does it: creating a note or
+/// appending to the page whose name sounds closest. Anything else is returned unrecognized,
+/// with nothing done, so the frontend can fall back to a quick capture.
+pub async fn process(state: &AppState, audio: &[u8]) -> AppResult<VoiceCommandResult> {
+    let transcription = {
+        let ai_service = state.ai_service.read().await;
+        if !ai_service.is_whisper_available() {
+            return Err(AppError::ModelNotFound("Whisper model not available".to_string()));
+        }
+        ai_service.transcribe_audio(audio, None).await?.trim().to_string()
+    };
+    let intent = parse(&transcription);
+
+    let database = state.database.read().await;
+    let (action, reason) = match &intent {
+        VoiceIntent::CreateNote { title, content, .. } if title.is_empty() && content.is_empty() => {
+            (None, Some("The command didn't say what to put in the note".to_string()))
+        }
+        VoiceIntent::CreateNote { title, content, tags } => {
+            let note = database.create_note(title.clone(), content.clone(), tags.clone()).await?;
+            state.queue_embedding(&database, &note.id, EmbeddingOwner::Note, AiJobPriority::Normal).await;
+            (Some(VoiceCommandAction::CreatedNote { note_id: note.id, title: note.title }), None)
+        }
+        VoiceIntent::AppendToPage { page_name, text } => {
+            match voice_match::resolve(&database, page_name, Some(&[SpokenNameKind::Page][..])).await?.into_iter().next() {
+                Some(page) => {
+                    append_to_page(&database, &page.id, text).await?;
+                    state.page_content_changed(&database, &page.id).await?;
+                    (Some(VoiceCommandAction::AppendedToPage { page_id: page.id, title: page.name }), None)
+                }
+                None => (None, Some(format!("No page sounds like \"{}\"", page_name))),
+            }
+        }
+        VoiceIntent::Unrecognized => (None, Some("Not a recognized command".to_string())),
+    };
+
+    // Only the kind: the transcription and the note text it carries are private
+    tracing::debug!("Voice command recognized as {}", intent.kind());
+    Ok(VoiceCommandResult { transcription, intent, action, reason })
+}
+
+/// Adds `text` to the end of the page, on the same line as what came before it.
+async fn append_to_page(database: &Database, page_id: &str, text: &str) -> AppResult<()> {
+    let page = database.get_page(page_id).await?
+        .ok_or_else(|| AppError::NotFound(format!("Page with id {} not found", page_id)))?;
+    let separator = if page.content.is_empty() || page.content.ends_with(char::is_whitespace) { "" } else { " " };
+    database.update_page(UpdatePageRequest {
+        id: page_id.to_string(),
+        title: None,
+        content: Some(format!("{}{}{}", page.content, separator, text)),
+        tags: None,
+        order_index: None,
+        language: None,
+    }).await
+}
+
+/// The intent of a transcribed command. Whisper's capitals and closing punctuation are
+/// tolerated; titles get a capital first letter and are derived from the content when
+/// none was spoken.
+pub fn parse(text: &str) -> VoiceIntent {
+    let text = text.trim();
+    let append = APPEND_PATTERN.get_or_init(|| {
+        Regex::new(r"(?i)^(?:please\s+)?(?:add|append)\s+(?:this\s+)?to\s+(.+?)\s*:\s*(.+)$").expect("valid append pattern")
+    });
+    if let Some(captures) = append.captures(text) {
+        let page_name = clean(&captures[1]);
+        let page_name = page_name.strip_suffix(" page").unwrap_or(&page_name).to_string();
+        return VoiceIntent::AppendToPage { page_name, text: captures[2].trim().to_string() };
+    }
+
+    let create = CREATE_NOTE_PATTERN.get_or_init(|| {
+        Regex::new(r"(?i)^(?:please\s+)?(?:(?:create|make|start|add|take)\s+(?:a\s+)?(?:new\s+)?|new\s+)?note\b[\s,]*(.*)$")
+            .expect("valid note pattern")
+    });
+    let Some(captures) = create.captures(text) else {
+        return VoiceIntent::Unrecognized;
+    };
+    let rest = captures.get(1).map_or("", |m| m.as_str()).trim();
+
+    let named = NAMED_PATTERN.get_or_init(|| Regex::new(r"(?i)^(?:titled|called|named)\s+(.*)$").expect("valid title pattern"));
+    let (title, content) = match named.captures(rest) {
+        Some(captures) => {
+            let named = captures.get(1).map_or("", |m| m.as_str());
+            let separator = CONTENT_SEPARATOR.get_or_init(|| {
+                Regex::new(r"(?i)\s*(?::|\bthat says\b|\bsaying\b)\s*").expect("valid separator pattern")
+            });
+            match separator.find(named) {
+                Some(m) => (&named[..m.start()], &named[m.end()..]),
+                None => (named, ""),
+            }
+        }
+        None => ("", rest.trim_start_matches(':')),
+    };
+
+    // Tags close the command, after the content or after the title when there's none
+    let (title, content, tags) = if content.trim().is_empty() {
+        let (title, tags) = split_tags(title);
+        (title, String::new(), tags)
+    } else {
+        let (content, tags) = split_tags(content);
+        (title.to_string(), content, tags)
+    };
+    let title = match capitalize(&clean(&title)) {
+        title if title.is_empty() => titles::derive_title(&content).unwrap_or_default(),
+        title => title,
+    };
+    VoiceIntent::CreateNote { title, content, tags }
+}
+
+/// `text` without a trailing "tagged a, b and c", and those tags.
+fn split_tags(text: &str) -> (String, Vec<String>) {
+    let tagged = TAGGED_PATTERN.get_or_init(|| Regex::new(r"(?i)[\s,.;]*\btagged\s+(.+)$").expect("valid tag pattern"));
+    let Some(captures) = tagged.captures(text) else {
+        return (text.trim().to_string(), Vec::new());
+    };
+    let separator = LIST_SEPARATOR.get_or_init(|| Regex::new(r"(?i)\s*,\s*|\s+and\s+").expect("valid list pattern"));
+    let tags = separator
+        .split(&captures[1])
+        .map(clean)
+        .filter(|tag| !tag.is_empty())
+        .collect();
+    let start = captures.get(0).map_or(text.len(), |m| m.start());
+    (text[..start].trim().to_string(), tags)
+}
+
+fn clean(text: &str) -> String {
+    text.trim().trim_end_matches(['.', ',', '!', '?']).trim().to_string()
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create(title: &str, content: &str, tags: &[&str]) -> VoiceIntent {
+        VoiceIntent::CreateNote {
+            title: title.to_string(),
+            content: content.to_string(),
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_create_note_commands() {
+        assert_eq!(parse("new note titled groceries: milk, eggs"), create("Groceries", "milk, eggs", &[]));
+        assert_eq!(parse(" New note titled Groceries: milk, eggs."), create("Groceries", "milk, eggs.", &[]));
+        assert_eq!(
+            parse("Create a note called weekly review saying check the budget, tagged finance and planning."),
+            create("Weekly review", "check the budget", &["finance", "planning"])
+        );
+        assert_eq!(parse("Make a new note named Ideas tagged work."), create("Ideas", "", &["work"]));
+    }
+
+    #[test]
+    fn test_untitled_note_gets_title_from_content() {
+        let VoiceIntent::CreateNote { title, content, tags } = parse("Note: call the dentist tomorrow") else {
+            panic!("expected a note");
+        };
+        assert_eq!(content, "call the dentist tomorrow");
+        assert!(title.starts_with("call the dentist"));
+        assert!(tags.is_empty());
+    }
+
+    #[test]
+    fn test_append_and_unrecognized() {
+        assert_eq!(
+            parse("Add to the shopping list page: bread and butter."),
+            VoiceIntent::AppendToPage { page_name: "the shopping list".to_string(), text: "bread and butter.".to_string() }
+        );
+        assert_eq!(parse("What's the weather like?"), VoiceIntent::Unrecognized);
+        assert_eq!(parse("Notebook overview"), VoiceIntent::Unrecognized);
+    }
+}
@@ -0,0 +1,203 @@
+use crate::{
+    AppResult,
+    models::{SpokenNameKind, SpokenNameMatch},
+    database::{Database, levenshtein},
+};
+
+/// Matches scoring below this are dropped.
+const MIN_SCORE: f64 = 0.7;
+const MAX_MATCHES: usize = 5;
+/// Words that sound alike but are spelled differently still count as close.
+const PHONETIC_SCORE: f64 = 0.9;
+
+/// Words people say around a name: "my project alpha notebook".
+const FILLER_WORDS: &[&str] = &["my", "the", "a", "an", "our", "to", "in", "into", "under", "called", "named"];
+
+const KIND_WORDS: &[(&str, SpokenNameKind)] = &[
+    ("notebook", SpokenNameKind::Notebook),
+    ("notebooks", SpokenNameKind::Notebook),
+    ("tag", SpokenNameKind::Tag),
+    ("tags", SpokenNameKind::Tag),
+    ("tagged", SpokenNameKind::Tag),
+    ("hashtag", SpokenNameKind::Tag),
+    ("page", SpokenNameKind::Page),
+    ("pages", SpokenNameKind::Page),
+];
+
+const NUMBER_WORDS: &[(&str, &str)] = &[
+    ("zero", "0"), ("one", "1"), ("two", "2"), ("three", "3"), ("four", "4"), ("five", "5"),
+    ("six", "6"), ("seven", "7"), ("eight", "8"), ("nine", "9"), ("ten", "10"),
+];
+
+/// Notebooks, tags and pages whose names sound like `phrase`, best first. The kinds to
+/// search default to the ones the phrase names ("... notebook"), or all of them.
+pub async fn resolve(database: &Database, phrase: &str, kinds: Option<&[SpokenNameKind]>) -> AppResult<Vec<SpokenNameMatch>> {
+    let (words, hinted) = spoken_words(phrase);
+    let kinds = match kinds {
+        Some(kinds) if !kinds.is_empty() => kinds.to_vec(),
+        _ if !hinted.is_empty() => hinted,
+        _ => vec![SpokenNameKind::Notebook, SpokenNameKind::Tag, SpokenNameKind::Page],
+    };
+
+    let mut candidates: Vec<(SpokenNameKind, String, String)> = Vec::new();
+    if kinds.contains(&SpokenNameKind::Notebook) {
+        candidates.extend(database.get_notebooks().await?.into_iter().map(|n| (SpokenNameKind::Notebook, n.id, n.title)));
+    }
+    if kinds.contains(&SpokenNameKind::Tag) {
+        candidates.extend(database.get_tags().await?.into_iter().map(|t| (SpokenNameKind::Tag, t.id, t.name)));
+    }
+    if kinds.contains(&SpokenNameKind::Page) {
+        candidates.extend(database.get_page_titles().await?.into_iter().map(|(id, title)| (SpokenNameKind::Page, id, title)));
+    }
+
+    Ok(rank(&words, candidates))
+}
+
+fn rank(words: &[String], candidates: Vec<(SpokenNameKind, String, String)>) -> Vec<SpokenNameMatch> {
+    if words.is_empty() {
+        return Vec::new();
+    }
+    let mut matches: Vec<SpokenNameMatch> = candidates
+        .into_iter()
+        .filter_map(|(kind, id, name)| {
+            let score = name_score(words, &spoken_words(&name).0);
+            (score >= MIN_SCORE).then_some(SpokenNameMatch { kind, id, name, score })
+        })
+        .collect();
+    matches.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.name.cmp(&b.name)));
+    matches.truncate(MAX_MATCHES);
+    matches
+}
+
+/// Lower-case words of `text` with fillers dropped and number words as digits, plus the
+/// kinds it names. Kind words are dropped too unless nothing else is left.
+fn spoken_words(text: &str) -> (Vec<String>, Vec<SpokenNameKind>) {
+    let words: Vec<String> = text
+        .split(|c: char| !c.is_alphanumeric() && c != '\'')
+        .map(|word| word.trim_matches('\'').to_lowercase())
+        .filter(|word| !word.is_empty() && !FILLER_WORDS.contains(&word.as_str()))
+        .map(|word| NUMBER_WORDS.iter().find(|(spoken, _)| *spoken == word).map_or(word, |(_, digit)| digit.to_string()))
+        .collect();
+
+    let mut kinds = Vec::new();
+    let mut named: Vec<String> = Vec::new();
+    for word in &words {
+        match KIND_WORDS.iter().find(|(spoken, _)| spoken == word) {
+            Some((_, kind)) => {
+                if !kinds.contains(kind) {
+                    kinds.push(*kind);
+                }
+            }
+            None => named.push(word.clone()),
+        }
+    }
+    (if named.is_empty() { words } else { named }, kinds)
+}
+
+/// How well the spoken words cover the name and the name covers the spoken words, so
+/// "alpha" partly matches "Project Alpha" and "project alfa" nearly fully.
+fn name_score(spoken: &[String], name: &[String]) -> f64 {
+    if spoken.is_empty() || name.is_empty() {
+        return 0.0;
+    }
+    if spoken == name {
+        return 1.0;
+    }
+    // Speech recognition splits and joins compound words ("note book", "notebook")
+    let joined = word_score(&spoken.concat(), &name.concat());
+    let coverage = |from: &[String], to: &[String]| {
+        from.iter().map(|a| to.iter().map(|b| word_score(a, b)).fold(0.0, f64::max)).sum::<f64>() / from.len() as f64
+    };
+    let words = (coverage(spoken, name) + coverage(name, spoken)) / 2.0;
+    // Never rank a near miss level with an exact match
+    words.max(joined).min(0.99)
+}
+
+fn word_score(a: &str, b: &str) -> f64 {
+    if a == b {
+        return 1.0;
+    }
+    let length = a.chars().count().max(b.chars().count());
+    let spelled = 1.0 - levenshtein(a, b) as f64 / length as f64;
+    let phonetic = if soundex(a).is_some() && soundex(a) == soundex(b) { PHONETIC_SCORE } else { 0.0 };
+    spelled.max(phonetic)
+}
+
+/// American Soundex code ("alpha" and "alfa" are both A410); `None` for words without letters.
+fn soundex(word: &str) -> Option<String> {
+    let letters: Vec<char> = word.chars().filter(|c| c.is_ascii_alphabetic()).map(|c| c.to_ascii_lowercase()).collect();
+    let first = *letters.first()?;
+    let digit = |c: char| match c {
+        'b' | 'f' | 'p' | 'v' => Some('1'),
+        'c' | 'g' | 'j' | 'k' | 'q' | 's' | 'x' | 'z' => Some('2'),
+        'd' | 't' => Some('3'),
+        'l' => Some('4'),
+        'm' | 'n' => Some('5'),
+        'r' => Some('6'),
+        _ => None,
+    };
+
+    let mut code = first.to_ascii_uppercase().to_string();
+    let mut previous = digit(first);
+    for &c in &letters[1..] {
+        let current = digit(c);
+        if let Some(d) = current.filter(|_| current != previous) {
+            code.push(d);
+            if code.len() == 4 {
+                break;
+            }
+        }
+        // Letters separated by h or w share a code; a vowel in between doesn't
+        if c != 'h' && c != 'w' {
+            previous = current;
+        }
+    }
+    while code.len() < 4 {
+        code.push('0');
+    }
+    Some(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(kind: SpokenNameKind, name: &str) -> (SpokenNameKind, String, String) {
+        (kind, name.to_lowercase(), name.to_string())
+    }
+
+    #[test]
+    fn test_soundex() {
+        assert_eq!(soundex("Robert").as_deref(), Some("R163"));
+        assert_eq!(soundex("Ashcraft").as_deref(), Some("A261"));
+        assert_eq!(soundex("alpha"), soundex("alfa"));
+        assert_eq!(soundex("42"), None);
+    }
+
+    #[test]
+    fn test_spoken_words() {
+        let (words, kinds) = spoken_words("My Project Alpha notebook");
+        assert_eq!(words, vec!["project", "alpha"]);
+        assert_eq!(kinds, vec![SpokenNameKind::Notebook]);
+        assert_eq!(spoken_words("the notebook").0, vec!["notebook"]);
+        assert_eq!(spoken_words("phase two").0, vec!["phase", "2"]);
+    }
+
+    #[test]
+    fn test_rank() {
+        let candidates = vec![
+            candidate(SpokenNameKind::Notebook, "Project Alfa"),
+            candidate(SpokenNameKind::Notebook, "Project Beta"),
+            candidate(SpokenNameKind::Tag, "groceries"),
+            candidate(SpokenNameKind::Page, "Phase 2 Plan"),
+        ];
+
+        let matches = rank(&spoken_words("my project alpha notebook").0, candidates.clone());
+        assert_eq!(matches[0].name, "Project Alfa");
+        assert!(matches.iter().all(|m| m.name != "groceries"));
+
+        assert_eq!(rank(&spoken_words("grocerys").0, candidates.clone())[0].name, "groceries");
+        assert_eq!(rank(&spoken_words("phase two plan").0, candidates.clone())[0].score, 1.0);
+        assert!(rank(&spoken_words("vacation photos").0, candidates).is_empty());
+    }
+}
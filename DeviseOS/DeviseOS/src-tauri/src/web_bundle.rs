@@ -0,0 +1,220 @@
+use std::path::Path;
+use base64::{Engine as _, engine::general_purpose::STANDARD};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use crate::{
+    AppError, AppResult,
+    models::{ExportFormat, ExportType, WebBundle},
+    database::Database,
+    encryption::{self, EncryptionManager},
+    export, locale,
+};
+
+const MIN_PASSPHRASE_LENGTH: usize = 8;
+/// PBKDF2-HMAC-SHA256 rounds. Argon2, used for the vault, isn't available to WebCrypto in
+/// the browser, so the bundle uses the strongest KDF it can derive natively.
+const KDF_ITERATIONS: u32 = 600_000;
+const SALT_LENGTH: usize = 16;
+const BUNDLE_PLACEHOLDER: &str = "__DEVISEOS_BUNDLE__";
+
+/// The reader page. It decrypts the embedded payload with WebCrypto once the passphrase is
+/// entered; nothing is sent anywhere, so any static host (or opening the file) works.
+const READER_HTML: &str = r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<meta http-equiv="Content-Security-Policy" content="default-src 'none'; script-src 'unsafe-inline'; style-src 'unsafe-inline'">
+<meta name="robots" content="noindex">
+<title>Shared notebook</title>
+<style>
+body{font-family:system-ui,sans-serif;margin:0;display:flex;min-height:100vh;line-height:1.6}
+nav{width:16rem;padding:1rem;border-right:1px solid #ddd;overflow-y:auto}
+nav h2{font-size:.8rem;text-transform:uppercase;color:#666;margin:1rem 0 .25rem}
+nav a{display:block;padding:.15rem 0;color:inherit;text-decoration:none}
+nav a[aria-current]{font-weight:600}
+main{flex:1;max-width:42rem;padding:2rem}
+form{margin:20vh auto;text-align:center;width:100%}
+input{font-size:1rem;padding:.5rem;width:16rem}
+.meta,.error{color:#666;font-size:.875rem}.error{color:#b00020}
+[dir=rtl]{text-align:right}[dir=ltr]{text-align:left}
+</style>
+</head>
+<body>
+<form id="unlock">
+<p>This notebook is encrypted. Enter the passphrase to read it.</p>
+<input id="passphrase" type="password" autocomplete="off" autofocus>
+<button type="submit">Open</button>
+<p id="status" class="error"></p>
+</form>
+<script type="application/json" id="bundle">__DEVISEOS_BUNDLE__</script>
+<script>
+const bundle = JSON.parse(document.getElementById('bundle').textContent);
+const bytes = text => Uint8Array.from(atob(text), c => c.charCodeAt(0));
+
+async function decrypt(passphrase) {
+  const material = await crypto.subtle.importKey('raw', new TextEncoder().encode(passphrase), 'PBKDF2', false, ['deriveKey']);
+  const key = await crypto.subtle.deriveKey(
+    { name: 'PBKDF2', salt: bytes(bundle.salt), iterations: bundle.iterations, hash: 'SHA-256' },
+    material, { name: 'AES-GCM', length: 256 }, false, ['decrypt']);
+  const data = bytes(bundle.data);
+  const plain = await crypto.subtle.decrypt({ name: 'AES-GCM', iv: data.slice(0, 12) }, key, data.slice(12));
+  return JSON.parse(new TextDecoder().decode(plain));
+}
+
+function show(notebook) {
+  document.title = notebook.title;
+  const nav = document.createElement('nav');
+  const main = document.createElement('main');
+  let section;
+  for (const page of notebook.pages) {
+    if (page.section !== section) {
+      section = page.section;
+      const heading = document.createElement('h2');
+      heading.textContent = section || 'Pages';
+      nav.append(heading);
+    }
+    const link = document.createElement('a');
+    link.href = '#' + page.id;
+    link.textContent = page.title;
+    nav.append(link);
+  }
+  const open = () => {
+    const page = notebook.pages.find(p => '#' + p.id === location.hash) || notebook.pages[0];
+    if (!page) { main.textContent = 'This notebook has no pages.'; return; }
+    main.dir = page.dir;
+    // Rendered and escaped by DeviseOS before encryption
+    main.innerHTML = page.html;
+    for (const link of nav.querySelectorAll('a')) link.toggleAttribute('aria-current', link.hash === '#' + page.id);
+  };
+  window.addEventListener('hashchange', open);
+  document.body.replaceChildren(nav, main);
+  open();
+}
+
+document.getElementById('unlock').addEventListener('submit', async event => {
+  event.preventDefault();
+  const status = document.getElementById('status');
+  status.textContent = 'Decrypting…';
+  try {
+    show(await decrypt(document.getElementById('passphrase').value));
+  } catch (e) {
+    status.textContent = 'Wrong passphrase.';
+  }
+});
+</script>
+</body>
+</html>
+"#;
+
+/// Public part of the bundle; everything readable is inside `data`.
+#[derive(Serialize, Deserialize)]
+struct EncryptedBundle {
+    version: u32,
+    iterations: u32,
+    salt: String, // base64
+    data: String, // base64 of nonce + AES-256-GCM ciphertext
+}
+
+#[derive(Serialize, Deserialize)]
+struct BundleNotebook {
+    title: String,
+    pages: Vec<BundlePage>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BundlePage {
+    id: String,
+    title: String,
+    section: Option<String>,
+    dir: String,
+    html: String,
+}
+
+/// Writes `index.html` into `destination`: a read-only copy of the notebook that opens in
+/// any browser after entering `passphrase`. Titles and content are only in the encrypted
+/// payload.
+pub async fn export_web_bundle(database: &Database, notebook_id: &str, passphrase: &str, destination: &Path) -> AppResult<WebBundle> {
+    if passphrase.chars().count() < MIN_PASSPHRASE_LENGTH {
+        return Err(AppError::InvalidFormat(format!("Passphrase must be at least {} characters", MIN_PASSPHRASE_LENGTH)));
+    }
+    let notebook = database.get_notebook(notebook_id).await?
+        .ok_or_else(|| AppError::NotFound(format!("Notebook with id {} not found", notebook_id)))?;
+    let section_titles = database.get_section_titles().await?;
+    let formatter = locale::formatter(database, None).await?;
+    let format = ExportFormat {
+        format: ExportType::HTML,
+        include_metadata: false,
+        include_voice_annotations: false,
+        include_tags: true,
+        translate_to: None,
+    };
+
+    let mut pages = database.get_pages(notebook_id, None).await?;
+    // Group by section for the sidebar, keeping page order within each section
+    pages.sort_by_key(|page| page.section_id.as_ref().and_then(|id| section_titles.get(id)).cloned());
+    let content = BundleNotebook {
+        title: notebook.title,
+        pages: pages
+            .iter()
+            .map(|page| BundlePage {
+                id: page.id.clone(),
+                title: page.title.clone(),
+                section: page.section_id.as_ref().and_then(|id| section_titles.get(id)).cloned(),
+                dir: export::page_direction(page).to_string(),
+                html: export::render_html_body(page, &format, &[], &formatter),
+            })
+            .collect(),
+    };
+    let plaintext = serde_json::to_vec(&content)?;
+
+    // Key derivation is deliberately slow
+    let passphrase = passphrase.to_string();
+    let bundle = tokio::task::spawn_blocking(move || encrypt_bundle(&plaintext, &passphrase, KDF_ITERATIONS))
+        .await
+        .map_err(|e| AppError::Unknown(format!("Web bundle task failed: {}", e)))??;
+
+    tokio::fs::create_dir_all(destination).await?;
+    let path = destination.join("index.html");
+    tokio::fs::write(&path, READER_HTML.replace(BUNDLE_PLACEHOLDER, &serde_json::to_string(&bundle)?)).await?;
+
+    tracing::info!("Exported web bundle of notebook {} with {} pages", notebook_id, content.pages.len());
+    Ok(WebBundle { path, page_count: content.pages.len() })
+}
+
+fn encrypt_bundle(plaintext: &[u8], passphrase: &str, iterations: u32) -> AppResult<EncryptedBundle> {
+    let salt = encryption::generate_random_bytes(SALT_LENGTH)?;
+    let manager = EncryptionManager::from_key_bytes(&derive_key(passphrase, &salt, iterations))?;
+    Ok(EncryptedBundle {
+        version: 1,
+        iterations,
+        salt: STANDARD.encode(&salt),
+        data: STANDARD.encode(manager.encrypt(plaintext)?),
+    })
+}
+
+fn derive_key(passphrase: &str, salt: &[u8], iterations: u32) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    pbkdf2::pbkdf2_hmac::<Sha256>(passphrase.as_bytes(), salt, iterations, &mut key);
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bundle_round_trip() {
+        let bundle = encrypt_bundle(b"{\"title\":\"Trip plans\"}", "correct horse", 1_000).unwrap();
+        let json = serde_json::to_string(&bundle).unwrap();
+        assert!(!json.contains("Trip plans"));
+
+        let salt = STANDARD.decode(&bundle.salt).unwrap();
+        let data = STANDARD.decode(&bundle.data).unwrap();
+        let manager = EncryptionManager::from_key_bytes(&derive_key("correct horse", &salt, 1_000)).unwrap();
+        assert_eq!(manager.decrypt(&data).unwrap(), b"{\"title\":\"Trip plans\"}");
+
+        let wrong = EncryptionManager::from_key_bytes(&derive_key("wrong horse", &salt, 1_000)).unwrap();
+        assert!(wrong.decrypt(&data).is_err());
+    }
+}
@@ -0,0 +1,347 @@
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::{Duration, Instant};
+use axum::{
+    Router,
+    extract::{ConnectInfo, Path, Request, State},
+    http::{header, StatusCode},
+    middleware::{self, Next},
+    response::{Html, IntoResponse, Response},
+    routing::get,
+};
+use axum_server::{Handle, tls_rustls::RustlsConfig};
+use base64::{Engine as _, engine::general_purpose};
+use rand::RngCore;
+use rustls::pki_types::{PrivateKeyDer, PrivatePkcs8KeyDer};
+use sha2::{Digest, Sha256};
+use tokio::sync::{Mutex, RwLock};
+use crate::{
+    AppError, AppResult,
+    models::{ExportFormat, ExportType, StartWebViewerRequest, WebViewerStatus},
+    database::Database,
+    encryption::secure_compare,
+    export::{self, escape_html},
+    locale,
+};
+
+const MIN_PASSWORD_LENGTH: usize = 8;
+/// Wrong passwords a client may send before it is locked out.
+const MAX_FAILED_LOGINS: u32 = 5;
+/// How long a lockout lasts, counted from the last wrong password.
+const LOCKOUT: Duration = Duration::from_secs(5 * 60);
+const STYLES: &str = "body{font-family:system-ui,sans-serif;max-width:42rem;margin:2rem auto;line-height:1.6}a{color:#3B82F6}";
+
+struct ViewerState {
+    database: Arc<RwLock<Database>>,
+    notebook_ids: HashSet<String>,
+    password_salt: [u8; 16],
+    password_hash: Vec<u8>,
+    logins: StdMutex<LoginThrottle>,
+}
+
+struct RunningViewer {
+    url: String,
+    fingerprint: String,
+    notebook_ids: Vec<String>,
+    handle: Handle,
+}
+
+/// Serves selected notebooks as read-only HTML on the local network, behind HTTP basic
+/// auth over TLS. The certificate is self-signed and made fresh on each start; its
+/// fingerprint is shown next to the URL so it can be checked on the reading device.
+/// Nothing is writable and only the chosen notebooks are reachable.
+pub struct WebViewer {
+    running: Mutex<Option<RunningViewer>>,
+}
+
+impl WebViewer {
+    pub fn new() -> Self {
+        Self { running: Mutex::new(None) }
+    }
+
+    pub async fn start(&self, database: Arc<RwLock<Database>>, request: StartWebViewerRequest) -> AppResult<WebViewerStatus> {
+        if request.password.chars().count() < MIN_PASSWORD_LENGTH {
+            return Err(AppError::InvalidFormat(format!("Password must be at least {} characters", MIN_PASSWORD_LENGTH)));
+        }
+        if request.notebook_ids.is_empty() {
+            return Err(AppError::InvalidFormat("Select at least one notebook to share".to_string()));
+        }
+        {
+            let database = database.read().await;
+            for id in &request.notebook_ids {
+                if database.get_notebook(id).await?.is_none() {
+                    return Err(AppError::NotFound(format!("Notebook with id {} not found", id)));
+                }
+            }
+        }
+
+        let mut running = self.running.lock().await;
+        if let Some(previous) = running.take() {
+            previous.handle.shutdown();
+        }
+
+        let host = hostname::get().map(|h| h.to_string_lossy().into_owned()).unwrap_or_else(|_| "localhost".to_string());
+        let (tls, fingerprint) = tls_config(&host)?;
+
+        let mut password_salt = [0u8; 16];
+        rand::thread_rng().fill_bytes(&mut password_salt);
+        let state = Arc::new(ViewerState {
+            database,
+            notebook_ids: request.notebook_ids.iter().cloned().collect(),
+            password_hash: hash_password(&password_salt, &request.password),
+            password_salt,
+            logins: StdMutex::new(LoginThrottle::default()),
+        });
+        let app = Router::new()
+            .route("/", get(index))
+            .route("/notebooks/:id", get(notebook))
+            .route("/pages/:id", get(page))
+            .layer(middleware::from_fn_with_state(state.clone(), require_password))
+            .with_state(state);
+
+        let listener = std::net::TcpListener::bind(("0.0.0.0", request.port.unwrap_or(0)))?;
+        listener.set_nonblocking(true)?;
+        let port = listener.local_addr()?.port();
+        let handle = Handle::new();
+        let server = axum_server::from_tcp_rustls(listener, tls)
+            .handle(handle.clone())
+            .serve(app.into_make_service_with_connect_info::<SocketAddr>());
+        tauri::async_runtime::spawn(async move {
+            if let Err(e) = server.await {
+                tracing::warn!("Web viewer stopped: {}", e);
+            }
+        });
+
+        let url = format!("https://{}:{}", host, port);
+        tracing::info!("Web viewer listening on port {} for {} notebook(s)", port, request.notebook_ids.len());
+
+        *running = Some(RunningViewer {
+            url: url.clone(),
+            fingerprint: fingerprint.clone(),
+            notebook_ids: request.notebook_ids.clone(),
+            handle,
+        });
+        Ok(WebViewerStatus { running: true, url: Some(url), fingerprint: Some(fingerprint), notebook_ids: request.notebook_ids })
+    }
+
+    pub async fn stop(&self) {
+        if let Some(running) = self.running.lock().await.take() {
+            running.handle.shutdown();
+            tracing::info!("Web viewer stopped");
+        }
+    }
+
+    pub async fn status(&self) -> WebViewerStatus {
+        match &*self.running.lock().await {
+            Some(running) => WebViewerStatus {
+                running: true,
+                url: Some(running.url.clone()),
+                fingerprint: Some(running.fingerprint.clone()),
+                notebook_ids: running.notebook_ids.clone(),
+            },
+            None => WebViewerStatus { running: false, url: None, fingerprint: None, notebook_ids: Vec::new() },
+        }
+    }
+}
+
+/// A self-signed certificate for this machine's name, and its SHA-256 fingerprint as
+/// browsers show it.
+fn tls_config(host: &str) -> AppResult<(RustlsConfig, String)> {
+    let names = vec![host.to_string(), format!("{}.local", host), "localhost".to_string()];
+    let certified = rcgen::generate_simple_self_signed(names)
+        .map_err(|e| AppError::Encryption(format!("Failed to create the web viewer certificate: {}", e)))?;
+    let certificate = certified.cert.der().clone();
+    let fingerprint = fingerprint(&certificate);
+    let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(certified.key_pair.serialize_der()));
+
+    // Picked explicitly: other dependencies enable more than one rustls crypto provider
+    let config = rustls::ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .and_then(|builder| builder.with_no_client_auth().with_single_cert(vec![certificate], key))
+        .map_err(|e| AppError::Encryption(format!("Failed to configure TLS for the web viewer: {}", e)))?;
+    Ok((RustlsConfig::from_config(Arc::new(config)), fingerprint))
+}
+
+fn fingerprint(der: &[u8]) -> String {
+    Sha256::digest(der).iter().map(|b| format!("{:02X}", b)).collect::<Vec<_>>().join(":")
+}
+
+fn hash_password(salt: &[u8], password: &str) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(salt);
+    hasher.update(password.as_bytes());
+    hasher.finalize().to_vec()
+}
+
+/// Wrong passwords per client address. A client that sends `MAX_FAILED_LOGINS` of them is
+/// refused without checking until `LOCKOUT` has passed since its last one.
+#[derive(Default)]
+struct LoginThrottle {
+    failures: HashMap<IpAddr, (u32, Instant)>,
+}
+
+impl LoginThrottle {
+    fn is_locked(&self, ip: IpAddr, now: Instant) -> bool {
+        matches!(self.failures.get(&ip), Some((count, last)) if *count >= MAX_FAILED_LOGINS && now.duration_since(*last) < LOCKOUT)
+    }
+
+    fn failed(&mut self, ip: IpAddr, now: Instant) {
+        self.failures.retain(|_, (_, last)| now.duration_since(*last) < LOCKOUT);
+        let entry = self.failures.entry(ip).or_insert((0, now));
+        entry.0 += 1;
+        entry.1 = now;
+    }
+
+    fn succeeded(&mut self, ip: IpAddr) {
+        self.failures.remove(&ip);
+    }
+}
+
+async fn require_password(
+    State(state): State<Arc<ViewerState>>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let ip = client.ip();
+    if state.logins.lock().unwrap().is_locked(ip, Instant::now()) {
+        return (StatusCode::TOO_MANY_REQUESTS, "Too many wrong passwords, try again later").into_response();
+    }
+
+    let password = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Basic "))
+        .and_then(|encoded| general_purpose::STANDARD.decode(encoded).ok())
+        .and_then(|decoded| String::from_utf8(decoded).ok())
+        .and_then(|credentials| credentials.split_once(':').map(|(_, password)| password.to_string()));
+
+    let challenge = (StatusCode::UNAUTHORIZED, [(header::WWW_AUTHENTICATE, "Basic realm=\"DeviseOS\"")]);
+    match password {
+        Some(password) if secure_compare(&hash_password(&state.password_salt, &password), &state.password_hash) => {
+            state.logins.lock().unwrap().succeeded(ip);
+            next.run(request).await
+        }
+        Some(_) => {
+            tracing::debug!("Wrong web viewer password from {}", ip);
+            state.logins.lock().unwrap().failed(ip, Instant::now());
+            challenge.into_response()
+        }
+        // Browsers ask for the password after the first unauthenticated request, so that
+        // doesn't count as a failure
+        None => challenge.into_response(),
+    }
+}
+
+type PageResult = Result<Html<String>, (StatusCode, &'static str)>;
+
+fn error_response(error: AppError) -> (StatusCode, &'static str) {
+    match error {
+        AppError::NotFound(_) => (StatusCode::NOT_FOUND, "Not found"),
+        other => {
+            tracing::warn!("Web viewer request failed: {}", other);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Something went wrong")
+        }
+    }
+}
+
+fn layout(title: &str, body: &str) -> Html<String> {
+    Html(format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n<title>{}</title>\n<style>{}</style>\n</head>\n<body>\n{}</body>\n</html>\n",
+        escape_html(title),
+        STYLES,
+        body
+    ))
+}
+
+async fn index(State(state): State<Arc<ViewerState>>) -> PageResult {
+    let database = state.database.read().await;
+    let mut notebooks = database.get_notebooks().await.map_err(error_response)?;
+    notebooks.retain(|n| state.notebook_ids.contains(&n.id));
+
+    let mut body = String::from("<h1>Notebooks</h1>\n<ul>\n");
+    for notebook in notebooks {
+        body.push_str(&format!("<li><a href=\"/notebooks/{}\">{}</a></li>\n", notebook.id, escape_html(&notebook.title)));
+    }
+    body.push_str("</ul>\n");
+    Ok(layout("DeviseOS", &body))
+}
+
+async fn notebook(State(state): State<Arc<ViewerState>>, Path(id): Path<String>) -> PageResult {
+    if !state.notebook_ids.contains(&id) {
+        return Err((StatusCode::NOT_FOUND, "Not found"));
+    }
+    let database = state.database.read().await;
+    let notebook = database.get_notebook(&id).await.map_err(error_response)?
+        .ok_or((StatusCode::NOT_FOUND, "Not found"))?;
+    let sections = database.get_sections(&id).await.map_err(error_response)?;
+    let pages = database.get_pages(&id, None).await.map_err(error_response)?;
+
+    let page_list = |section_id: Option<&str>| {
+        let items: Vec<String> = pages
+            .iter()
+            .filter(|p| p.section_id.as_deref() == section_id)
+            .map(|p| format!("<li><a href=\"/pages/{}\">{}</a></li>", p.id, escape_html(&p.title)))
+            .collect();
+        format!("<ul>\n{}\n</ul>\n", items.join("\n"))
+    };
+
+    let mut body = format!("<p><a href=\"/\">Notebooks</a></p>\n<h1>{}</h1>\n", escape_html(&notebook.title));
+    body.push_str(&page_list(None));
+    for section in sections {
+        body.push_str(&format!("<h2>{}</h2>\n", escape_html(&section.title)));
+        body.push_str(&page_list(Some(&section.id)));
+    }
+    Ok(layout(&notebook.title, &body))
+}
+
+async fn page(State(state): State<Arc<ViewerState>>, Path(id): Path<String>) -> PageResult {
+    let database = state.database.read().await;
+    let page = database.get_page(&id).await.map_err(error_response)?
+        .filter(|p| state.notebook_ids.contains(&p.notebook_id))
+        .ok_or((StatusCode::NOT_FOUND, "Not found"))?;
+    let formatter = locale::formatter(&database, None).await.map_err(error_response)?;
+
+    let format = ExportFormat {
+        format: ExportType::HTML,
+        include_metadata: true,
+        include_voice_annotations: false,
+        include_tags: true,
+        translate_to: None,
+    };
+    // Same rendering as HTML export, so direction handling matches
+    Ok(Html(export::render_html(&page, &format, &[], &formatter)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_login_throttle_locks_out_after_repeated_failures() {
+        let mut throttle = LoginThrottle::default();
+        let client: IpAddr = "192.168.1.20".parse().unwrap();
+        let other: IpAddr = "192.168.1.21".parse().unwrap();
+        let now = Instant::now();
+
+        for _ in 0..MAX_FAILED_LOGINS - 1 {
+            throttle.failed(client, now);
+        }
+        assert!(!throttle.is_locked(client, now));
+        throttle.failed(client, now);
+        assert!(throttle.is_locked(client, now));
+        assert!(!throttle.is_locked(other, now));
+        assert!(!throttle.is_locked(client, now + LOCKOUT));
+
+        throttle.succeeded(client);
+        assert!(!throttle.is_locked(client, now));
+    }
+
+    #[test]
+    fn test_password_hash_is_salted() {
+        assert_ne!(hash_password(&[1; 16], "correct horse"), hash_password(&[2; 16], "correct horse"));
+        assert_eq!(hash_password(&[1; 16], "correct horse"), hash_password(&[1; 16], "correct horse"));
+    }
+}
@@ -0,0 +1,211 @@
+use std::sync::OnceLock;
+use regex::Regex;
+use crate::{
+    AppError, AppResult,
+    models::{CreatePageRequest, PageLink, PageLinkType, UpdatePageRequest},
+    database::Database,
+};
+
+/// Setting that creates an empty page for each `[[Title]]` no page has yet.
+pub const WIKILINK_STUBS_KEY: &str = "wikilink_stub_pages";
+
+/// Characters of context kept either side of a link in a backlink snippet.
+const CONTEXT_CHARS: usize = 80;
+
+/// `[[Title]]`, `[[Title#Heading]]` or `[[Title|shown text]]`, embedded with `!` or not
+static WIKILINK_PATTERN: OnceLock<Regex> = OnceLock::new();
+
+/// The same as `WIKILINK_PATTERN`, split into the opening, title and the rest of the link
+static RENAME_PATTERN: OnceLock<Regex> = OnceLock::new();
+
+/// Titles linked from `content`, in order of first appearance, ignoring case. Zettel ID
+/// citations are left to `zettel::citations`.
+pub fn titles(content: &str) -> Vec<String> {
+    let pattern = WIKILINK_PATTERN.get_or_init(|| Regex::new(r"\[\[([^\[\]\n|#]+)(?:#[^\[\]\n|]*)?(?:\|[^\[\]\n]*)?\]\]").unwrap());
+    let mut titles: Vec<String> = Vec::new();
+    for captures in pattern.captures_iter(content) {
+        let title = normalize_title(&captures[1]);
+        if title.is_empty() || is_citation(&title) {
+            continue;
+        }
+        if !titles.iter().any(|seen| seen.to_lowercase() == title.to_lowercase()) {
+            titles.push(title);
+        }
+    }
+    titles
+}
+
+fn is_citation(title: &str) -> bool {
+    let id = title.split(' ').next().unwrap_or_default();
+    id.len() == 12 && id.chars().all(|c| c.is_ascii_digit())
+}
+
+/// Stores the pages a page names in `[[Title]]` links as its `Wiki` links, so backlinks
+/// work without linking by hand. Titles resolve to a page in the same notebook first, then
+/// anywhere; unknown titles get a stub page when `WIKILINK_STUBS_KEY` is on and are
+/// skipped otherwise.
+pub async fn link_wikilinks(database: &Database, page_id: &str) -> AppResult<Vec<PageLink>> {
+    let page = database.get_page(page_id).await?
+        .ok_or_else(|| AppError::NotFound(format!("Page with id {} not found", page_id)))?;
+    let create_stubs = database.get_setting(WIKILINK_STUBS_KEY).await?.as_deref() == Some("true");
+
+    let mut targets = Vec::new();
+    for title in titles(&page.content) {
+        let target_id = match database.find_page_id_by_title(&title, &page.notebook_id).await? {
+            Some(id) => id,
+            None if create_stubs => {
+                let stub = database.create_page(CreatePageRequest {
+                    notebook_id: page.notebook_id.clone(),
+                    section_id: page.section_id.clone(),
+                    parent_page_id: None,
+                    title: title.clone(),
+                    content: String::new(),
+                    tags: Vec::new(),
+                }).await?;
+                tracing::debug!("Created stub page {} for a wiki link", stub.id);
+                stub.id
+            }
+            None => continue,
+        };
+        if target_id != page.id {
+            targets.push((target_id, format!("[[{}]]", title)));
+        }
+    }
+    database.replace_links_of_type(page_id, PageLinkType::Wiki, &targets).await
+}
+
+/// `content` with its links to `old_title` pointing at `new_title` instead, keeping any
+/// heading, shown text and embed `!`. `None` when nothing links to `old_title`, or when
+/// `new_title` can't be written in a link.
+pub fn rename(content: &str, old_title: &str, new_title: &str) -> Option<String> {
+    if !is_linkable(new_title) {
+        return None;
+    }
+    let pattern = RENAME_PATTERN.get_or_init(|| Regex::new(r"(!?\[\[)([^\[\]\n|#]+)((?:#[^\[\]\n|]*)?(?:\|[^\[\]\n]*)?\]\])").unwrap());
+    let old_title = normalize_title(old_title).to_lowercase();
+    let mut renamed = false;
+    let content = pattern.replace_all(content, |captures: &regex::Captures| {
+        if normalize_title(&captures[2]).to_lowercase() == old_title {
+            renamed = true;
+            format!("{}{}{}", &captures[1], new_title.trim(), &captures[3])
+        } else {
+            captures[0].to_string()
+        }
+    });
+    renamed.then(|| content.into_owned())
+}
+
+/// Whether `title` can be the target of a `[[Title]]` link. Links have no escaping, so `#`
+/// and `|` would start a heading or shown text and brackets would end the link early.
+pub fn is_linkable(title: &str) -> bool {
+    !title.trim().is_empty() && !title.contains(['[', ']', '#', '|', '\n'])
+}
+
+/// Single spaces, as titles are matched.
+pub fn normalize_title(title: &str) -> String {
+    title.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Rewrites the `[[Title]]` links in pages linking to `page_id` after it's renamed, so the
+/// references follow it. Returns the ids of the pages rewritten; none when the new title
+/// can't be written in a link, leaving those links as they were.
+pub async fn follow_rename(database: &Database, page_id: &str, old_title: &str, new_title: &str) -> AppResult<Vec<String>> {
+    if !is_linkable(new_title) {
+        tracing::warn!("Not rewriting links to page {}: its new title has characters wiki links can't contain", page_id);
+        return Ok(Vec::new());
+    }
+    let mut sources: Vec<String> = Vec::new();
+    for backlink in database.get_backlinks(page_id).await? {
+        if matches!(backlink.link_type, PageLinkType::Wiki) && !sources.contains(&backlink.source_page_id) {
+            sources.push(backlink.source_page_id);
+        }
+    }
+
+    let mut rewritten = Vec::new();
+    for source_id in sources {
+        let Some(source) = database.get_page(&source_id).await? else {
+            continue;
+        };
+        let Some(content) = rename(&source.content, old_title, new_title) else {
+            continue;
+        };
+        database.update_page(UpdatePageRequest {
+            id: source_id.clone(),
+            title: None,
+            content: Some(content),
+            tags: None,
+            order_index: None,
+            language: None,
+        }).await?;
+        link_wikilinks(database, &source_id).await?;
+        rewritten.push(source_id);
+    }
+    Ok(rewritten)
+}
+
+/// The text around `link_text` in `content`, from the line it's on, for showing a backlink
+/// in place. Wiki links match however they're written, with a heading or shown text.
+pub fn context(content: &str, link_text: &str) -> Option<String> {
+    let needle = match link_text.strip_prefix("[[").and_then(|t| t.strip_suffix("]]")) {
+        Some(title) => format!("[[{}", title),
+        None => link_text.to_string(),
+    };
+    if needle.trim().is_empty() {
+        return None;
+    }
+    let needle = needle.to_ascii_lowercase();
+    let line = content.lines().find(|line| line.to_ascii_lowercase().contains(&needle))?;
+    let position = line.to_ascii_lowercase().find(&needle)?;
+
+    let before: Vec<char> = line[..position].chars().collect();
+    let after: Vec<char> = line[position..].chars().collect();
+    let start = before.len().saturating_sub(CONTEXT_CHARS);
+    let end = (needle.chars().count() + CONTEXT_CHARS).min(after.len());
+    let mut snippet: String = before[start..].iter().chain(&after[..end]).collect();
+    snippet = snippet.trim().to_string();
+    if start > 0 {
+        snippet = format!("…{}", snippet);
+    }
+    if end < after.len() {
+        snippet.push('…');
+    }
+    Some(snippet)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_titles() {
+        let content = "See [[Deep Work]], [[deep work|again]] and [[Habits#Cues]].\n\
+            ![[Reading  list]] cites [[202403011430 On habits]] but not [[ ]] or [Plain](link).";
+        assert_eq!(titles(content), vec!["Deep Work", "Habits", "Reading list"]);
+        assert!(titles("[[unclosed").is_empty());
+    }
+
+    #[test]
+    fn test_context() {
+        let content = "# Notes\n\nAs [[deep work|Newport]] argues, focus compounds.\nUnrelated line.";
+        assert_eq!(context(content, "[[Deep Work]]").as_deref(), Some("As [[deep work|Newport]] argues, focus compounds."));
+        assert_eq!(context(content, "[[Shallow Work]]"), None);
+
+        let long = format!("{} see [[Habits]] {}", "é".repeat(100), "x".repeat(200));
+        let snippet = context(&long, "[[Habits]]").unwrap();
+        assert!(snippet.starts_with('…') && snippet.ends_with('…'));
+        assert!(snippet.contains("[[Habits]]"));
+    }
+
+    #[test]
+    fn test_rename() {
+        let content = "[[Deep Work]], ![[deep  work#Rules|the book]] and [[Deep Work II]].";
+        assert_eq!(
+            rename(content, "Deep Work", "Focus").as_deref(),
+            Some("[[Focus]], ![[Focus#Rules|the book]] and [[Deep Work II]].")
+        );
+        assert_eq!(rename(content, "Habits", "Focus"), None);
+        assert_eq!(rename(content, "Deep Work", "C# tips"), None);
+        assert_eq!(rename(content, "Deep Work", "A|B"), None);
+        assert_eq!(rename(content, "Deep Work", "Notes]] [[x"), None);
+    }
+}
@@ -0,0 +1,291 @@
+use crate::models::RewriteStyle;
+
+/// Writing assistant actions on a text selection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WritingAction {
+    Rewrite(RewriteStyle),
+    Expand,
+    FixGrammar,
+}
+
+/// Words that rarely add meaning, dropped when rewriting concisely.
+const FILLER_WORDS: &[&str] = &["really", "very", "basically", "actually", "just", "quite", "literally", "totally", "simply"];
+
+const WORDY_PHRASES: &[(&str, &str)] = &[
+    ("due to the fact that", "because"),
+    ("in order to", "to"),
+    ("at this point in time", "now"),
+    ("in the event that", "if"),
+    ("for the purpose of", "for"),
+    ("a large number of", "many"),
+    ("has the ability to", "can"),
+];
+
+/// Longer forms first, so "can't" isn't matched as "can".
+const CONTRACTIONS: &[(&str, &str)] = &[
+    ("cannot", "can't"),
+    ("will not", "won't"),
+    ("do not", "don't"),
+    ("does not", "doesn't"),
+    ("did not", "didn't"),
+    ("is not", "isn't"),
+    ("are not", "aren't"),
+    ("was not", "wasn't"),
+    ("were not", "weren't"),
+    ("have not", "haven't"),
+    ("should not", "shouldn't"),
+    ("would not", "wouldn't"),
+    ("could not", "couldn't"),
+    ("it is", "it's"),
+    ("that is", "that's"),
+    ("there is", "there's"),
+    ("I am", "I'm"),
+    ("I have", "I've"),
+    ("I will", "I'll"),
+    ("we are", "we're"),
+    ("we will", "we'll"),
+    ("you are", "you're"),
+    ("they are", "they're"),
+    ("let us", "let's"),
+];
+
+const INFORMAL_WORDS: &[(&str, &str)] = &[
+    ("gonna", "going to"),
+    ("wanna", "want to"),
+    ("gotta", "have to"),
+    ("kinda", "somewhat"),
+    ("yeah", "yes"),
+];
+
+/// Shorthand common in quick notes.
+const ABBREVIATIONS: &[(&str, &str)] = &[
+    ("w/o", "without"),
+    ("w/", "with"),
+    ("b/c", "because"),
+    ("e.g.", "for example"),
+    ("i.e.", "that is"),
+    ("approx.", "approximately"),
+    ("asap", "as soon as possible"),
+    ("fyi", "for your information"),
+    ("mtg", "meeting"),
+    ("govt", "government"),
+    ("->", " leads to "),
+];
+
+/// Rule-based rewrite, used when no language model is loaded.
+pub fn rewrite(text: &str, style: RewriteStyle) -> String {
+    let rewritten = match style {
+        RewriteStyle::Concise => remove_words(&replace_phrases(text, WORDY_PHRASES), FILLER_WORDS),
+        RewriteStyle::Clearer => replace_phrases(text, WORDY_PHRASES),
+        RewriteStyle::Formal => {
+            let expanded: Vec<(&str, &str)> = CONTRACTIONS.iter().map(|(long, short)| (*short, *long)).collect();
+            replace_phrases(&replace_phrases(text, &expanded), INFORMAL_WORDS)
+        }
+        RewriteStyle::Casual => replace_phrases(text, CONTRACTIONS),
+    };
+    fix_grammar(&rewritten)
+}
+
+/// Rule-based expansion: spells out note shorthand.
+pub fn expand(text: &str) -> String {
+    fix_grammar(&replace_phrases(text, ABBREVIATIONS))
+}
+
+/// Fixes spacing, repeated words, a lowercase "i" and sentence capitalization line by line,
+/// leaving indentation, code blocks, inline code and hard line breaks alone.
+pub fn fix_grammar(text: &str) -> String {
+    let mut in_code_block = false;
+    let lines: Vec<String> = text
+        .split('\n')
+        .map(|line| {
+            if line.trim_start().starts_with("```") {
+                in_code_block = !in_code_block;
+                return line.to_string();
+            }
+            if in_code_block {
+                line.to_string()
+            } else {
+                fix_line(line)
+            }
+        })
+        .collect();
+    lines.join("\n")
+}
+
+fn fix_line(line: &str) -> String {
+    let indent = &line[..line.len() - line.trim_start().len()];
+    let mut words: Vec<String> = Vec::new();
+    for word in split_words(line) {
+        let previous = words.last().map(|w| w.to_lowercase());
+        // "the the" is a typo, "had had" usually isn't
+        if previous.as_deref() == Some(word.to_lowercase().as_str()) && word.chars().all(char::is_alphabetic) && word != "had" && word != "that" {
+            continue;
+        }
+        let word = match word {
+            "i" | "i'm" | "i've" | "i'll" | "i'd" => format!("I{}", &word[1..]),
+            _ => word.to_string(),
+        };
+        // No space before punctuation
+        if !words.is_empty() && word.chars().all(|c| matches!(c, ',' | '.' | ';' | ':' | '!' | '?')) {
+            words.last_mut().unwrap().push_str(&word);
+            continue;
+        }
+        words.push(word);
+    }
+
+    let mut sentence_start = true;
+    for word in words.iter_mut() {
+        let is_marker = matches!(word.as_str(), "-" | "*" | "+" | ">") || word.chars().all(|c| c == '#') || is_ordered_marker(word);
+        if sentence_start && !is_marker && is_plain_word(word) {
+            let mut chars = word.chars();
+            if let Some(first) = chars.next() {
+                *word = first.to_uppercase().chain(chars).collect();
+            }
+        }
+        if !is_marker {
+            sentence_start = word.ends_with(['.', '!', '?']) && !is_abbreviation(word);
+        }
+    }
+
+    let mut fixed = indent.to_string();
+    fixed.push_str(&words.join(" "));
+    // Two trailing spaces are a Markdown line break
+    if !words.is_empty() && line.ends_with("  ") {
+        fixed.push_str("  ");
+    }
+    fixed
+}
+
+/// The line's words split at spaces and tabs, except inside inline code spans, which stay
+/// part of their word with their spacing intact.
+fn split_words(line: &str) -> Vec<&str> {
+    let mut words = Vec::new();
+    let mut start: Option<usize> = None;
+    let mut index = 0;
+    while let Some(c) = line[index..].chars().next() {
+        if c == '`' {
+            let ticks = line[index..].len() - line[index..].trim_start_matches('`').len();
+            start.get_or_insert(index);
+            // An unmatched run of backticks is literal text
+            index += match closing_ticks(&line[index + ticks..], ticks) {
+                Some(close) => ticks + close + ticks,
+                None => ticks,
+            };
+            continue;
+        }
+        if c == ' ' || c == '\t' {
+            if let Some(word_start) = start.take() {
+                words.push(&line[word_start..index]);
+            }
+        } else {
+            start.get_or_insert(index);
+        }
+        index += c.len_utf8();
+    }
+    if let Some(word_start) = start {
+        words.push(&line[word_start..]);
+    }
+    words
+}
+
+/// Where a code span opened with `ticks` backticks closes: the next run of exactly as many.
+fn closing_ticks(text: &str, ticks: usize) -> Option<usize> {
+    let mut offset = 0;
+    while let Some(found) = text[offset..].find('`') {
+        let at = offset + found;
+        let run = text[at..].len() - text[at..].trim_start_matches('`').len();
+        if run == ticks {
+            return Some(at);
+        }
+        offset = at + run;
+    }
+    None
+}
+
+/// Letters with optional trailing punctuation; URLs, paths, code and identifiers are kept as is.
+fn is_plain_word(word: &str) -> bool {
+    let core = word.trim_end_matches(|c: char| c.is_ascii_punctuation());
+    !core.is_empty() && core.chars().all(|c| c.is_alphabetic() || c == '\'')
+}
+
+fn is_ordered_marker(word: &str) -> bool {
+    word.len() > 1 && word.ends_with(['.', ')']) && word[..word.len() - 1].chars().all(|c| c.is_ascii_digit())
+}
+
+fn is_abbreviation(word: &str) -> bool {
+    matches!(word.to_lowercase().as_str(), "e.g." | "i.e." | "etc." | "vs." | "approx." | "mr." | "mrs." | "dr.")
+}
+
+/// Replaces whole-word, case-insensitive matches, keeping a capitalized first letter.
+fn replace_phrases(text: &str, phrases: &[(&str, &str)]) -> String {
+    let mut text = text.to_string();
+    for (from, to) in phrases {
+        let mut output = String::with_capacity(text.len());
+        let haystack = text.to_ascii_lowercase();
+        let needle = from.to_ascii_lowercase();
+        let mut position = 0;
+        while let Some(found) = haystack[position..].find(&needle) {
+            let start = position + found;
+            let end = start + needle.len();
+            let boundary_before = !starts_word(from) || text[..start].chars().next_back().map_or(true, |c| !c.is_alphanumeric());
+            let boundary_after = !ends_word(from) || text[end..].chars().next().map_or(true, |c| !c.is_alphanumeric() && c != '\'');
+            output.push_str(&text[position..start]);
+            if boundary_before && boundary_after {
+                let capitalized = text[start..].starts_with(|c: char| c.is_uppercase()) && !from.starts_with(|c: char| c.is_uppercase());
+                if capitalized {
+                    let mut chars = to.chars();
+                    output.extend(chars.next().map(|c| c.to_ascii_uppercase()));
+                    output.push_str(chars.as_str());
+                } else {
+                    output.push_str(to);
+                }
+            } else {
+                output.push_str(&text[start..end]);
+            }
+            position = end;
+        }
+        output.push_str(&text[position..]);
+        text = output;
+    }
+    text
+}
+
+fn starts_word(phrase: &str) -> bool {
+    phrase.starts_with(|c: char| c.is_alphanumeric())
+}
+
+fn ends_word(phrase: &str) -> bool {
+    phrase.ends_with(|c: char| c.is_alphanumeric())
+}
+
+fn remove_words(text: &str, words: &[&str]) -> String {
+    replace_phrases(text, &words.iter().map(|word| (*word, "")).collect::<Vec<_>>())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fix_grammar() {
+        assert_eq!(fix_grammar("i think  the the plan works .  it ships friday"), "I think the plan works. It ships friday");
+        assert_eq!(fix_grammar("- buy milk\n  1. call https://example.com/a"), "- Buy milk\n  1. Call https://example.com/a");
+        assert_eq!(fix_grammar("```\nlet x  = 1;\n```"), "```\nlet x  = 1;\n```");
+    }
+
+    #[test]
+    fn test_fix_grammar_keeps_code_spans_and_line_breaks() {
+        assert_eq!(fix_grammar("run `cargo  build --release`  now"), "Run `cargo  build --release` now");
+        assert_eq!(fix_grammar("use ``a ` b`` here"), "Use ``a ` b`` here");
+        assert_eq!(fix_grammar("first line  \nsecond line"), "First line  \nSecond line");
+        assert_eq!(fix_grammar("a ` stray tick"), "A ` stray tick");
+    }
+
+    #[test]
+    fn test_rule_based_rewrites() {
+        assert_eq!(rewrite("We don't know. It's really late", RewriteStyle::Formal), "We do not know. It is really late");
+        assert_eq!(rewrite("We do not know. It is late", RewriteStyle::Casual), "We don't know. It's late");
+        assert_eq!(rewrite("In order to ship we just need tests", RewriteStyle::Concise), "To ship we need tests");
+        assert_eq!(expand("mtg w/ Ana b/c of budget"), "Meeting with Ana because of budget");
+    }
+}
@@ -0,0 +1,120 @@
+use std::sync::OnceLock;
+use chrono::{DateTime, Duration, Utc};
+use regex::Regex;
+use crate::{
+    AppError, AppResult,
+    models::{CreatePageRequest, CreateZettelNoteRequest, Page, PageLink, PageLinkType, ZettelNoteType},
+    database::Database,
+};
+
+/// Setting that gives every new page a Zettel ID.
+pub const ZETTEL_IDS_KEY: &str = "zettel_ids_enabled";
+
+/// Minute resolution, the classic `YYYYMMDDHHMM` form.
+const ZETTEL_ID_FORMAT: &str = "%Y%m%d%H%M";
+
+/// `[[202403011430]]` or `[[202403011430 Some title]]`
+static CITATION_PATTERN: OnceLock<Regex> = OnceLock::new();
+
+/// The Zettel ID for a page created at `at`.
+pub fn zettel_id(at: DateTime<Utc>) -> String {
+    at.format(ZETTEL_ID_FORMAT).to_string()
+}
+
+/// The ID after `id`, one minute later, used when two pages are created in the same minute.
+pub fn next_zettel_id(id: &str) -> AppResult<String> {
+    let at = chrono::NaiveDateTime::parse_from_str(&format!("{}00", id), "%Y%m%d%H%M%S")
+        .map_err(|_| AppError::InvalidFormat(format!("Invalid Zettel ID: {}", id)))?;
+    Ok(zettel_id(at.and_utc() + Duration::minutes(1)))
+}
+
+/// Zettel IDs cited in `content`, in order of first appearance.
+pub fn citations(content: &str) -> Vec<String> {
+    let pattern = CITATION_PATTERN.get_or_init(|| Regex::new(r"\[\[(\d{12})(?:[ |][^\]\n]*)?\]\]").unwrap());
+    let mut ids: Vec<String> = Vec::new();
+    for captures in pattern.captures_iter(content) {
+        let id = captures[1].to_string();
+        if !ids.contains(&id) {
+            ids.push(id);
+        }
+    }
+    ids
+}
+
+/// Preset content and tags for a new note of `note_type`.
+pub fn template(note_type: ZettelNoteType, title: &str, source: Option<&str>) -> (String, Vec<String>) {
+    let content = match note_type {
+        ZettelNoteType::Literature => format!(
+            "# {}\n\nSource: {}\n\n## Summary\n\n\n## Key ideas\n\n\n## Quotes\n\n\n## My thoughts\n\n",
+            title,
+            source.unwrap_or("")
+        ),
+        ZettelNoteType::Permanent => format!(
+            "# {}\n\n## Idea\n\n\n## Why it matters\n\n\n## Related\n\n\n## Sources\n\n",
+            title
+        ),
+    };
+    (content, vec![note_type.as_str().to_string()])
+}
+
+/// Creates a literature or permanent note from its template and gives it a Zettel ID.
+pub async fn create_note(database: &Database, request: CreateZettelNoteRequest) -> AppResult<Page> {
+    let (content, tags) = template(request.note_type, &request.title, request.source.as_deref());
+    let page = database.create_page(CreatePageRequest {
+        notebook_id: request.notebook_id,
+        section_id: request.section_id,
+        parent_page_id: None,
+        title: request.title,
+        content,
+        tags,
+    }).await?;
+    database.assign_zettel_id(&page.id).await?;
+
+    database.get_page(&page.id).await?
+        .ok_or_else(|| AppError::NotFound(format!("Page with id {} not found", page.id)))
+}
+
+/// Stores the pages a page cites by Zettel ID as its `Reference` links, so backlinks work
+/// like any other link. Citations of unknown IDs are skipped.
+pub async fn link_citations(database: &Database, page_id: &str) -> AppResult<Vec<PageLink>> {
+    let page = database.get_page(page_id).await?
+        .ok_or_else(|| AppError::NotFound(format!("Page with id {} not found", page_id)))?;
+
+    let mut targets = Vec::new();
+    for id in citations(&page.content) {
+        if let Some(target) = database.get_page_by_zettel_id(&id).await? {
+            if target.id != page.id {
+                targets.push((target.id, format!("[[{}]]", id)));
+            }
+        }
+    }
+    database.replace_links_of_type(page_id, PageLinkType::Reference, &targets).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_zettel_ids() {
+        let at = Utc.with_ymd_and_hms(2024, 3, 1, 23, 59, 30).unwrap();
+        assert_eq!(zettel_id(at), "202403012359");
+        assert_eq!(next_zettel_id("202403012359").unwrap(), "202403020000");
+        assert!(next_zettel_id("2024").is_err());
+    }
+
+    #[test]
+    fn test_citations() {
+        let content = "See [[202403011430]] and [[202403011431 On habits]], again [[202403011430|here]].\n\
+            Not [[20240301]] or [[Some page]].";
+        assert_eq!(citations(content), vec!["202403011430", "202403011431"]);
+    }
+
+    #[test]
+    fn test_literature_template() {
+        let (content, tags) = template(ZettelNoteType::Literature, "Atomic Habits", Some("James Clear"));
+        assert!(content.starts_with("# Atomic Habits\n\nSource: James Clear"));
+        assert_eq!(tags, vec!["literature"]);
+    }
+}
@@ -18,6 +18,7 @@ import {
 } from 'lucide-react';
 import { useNotebooks, Notebook, Section, Page } from '../contexts/NotebookContext';
 import { useTabs } from '../contexts/TabContext';
+import { SearchPage } from '../contexts/NotesContext';
 import { Button } from './ui/Button';
 import { Input } from './ui/Input';
 import { Badge } from './ui/Badge';
@@ -156,13 +157,13 @@ export const AdvancedSearch: React.FC<AdvancedSearchProps> = ({ className = '' }
 
         // Use semantic search if available
         try {
-          const semanticResults = await invoke<any[]>('semantic_search', {
+          const semanticResults = await invoke<SearchPage<any>>('semantic_search', {
             query,
             limit: 20
           });
 
-          const processedResults = semanticResults.map(result => ({
-            page: result.note, // Note: backend returns 'note' but we treat as page
+          const processedResults = semanticResults.items.map(result => ({
+            page: result.page ?? result.note,
             notebook,
             relevanceScore: result.relevance_score,
             matchedTerms: result.matched_terms,
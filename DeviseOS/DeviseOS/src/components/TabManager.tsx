@@ -1,5 +1,6 @@
 import React, { useEffect } from 'react';
 import { useLocation, useNavigate } from 'react-router-dom';
+import { listen } from '@tauri-apps/api/event';
 import { useTabs } from '../contexts/TabContext';
 import { useNotebooks } from '../contexts/NotebookContext';
 import { TabBar } from './TabBar';
@@ -15,6 +16,14 @@ export const TabManager: React.FC<TabManagerProps> = ({ className = '' }) => {
   const location = useLocation();
   const navigate = useNavigate();
 
+  // deviseos:// links opened from OS search results or the jump list while the app runs
+  useEffect(() => {
+    const unlisten = listen<string>('open-page', (event) => navigate(`/page/${event.payload}`));
+    return () => {
+      unlisten.then((stop) => stop());
+    };
+  }, [navigate]);
+
   // Handle route changes and sync with tabs
   useEffect(() => {
     const path = location.pathname;
@@ -64,6 +64,13 @@ export interface SearchRequest {
   query: string;
   limit?: number;
   offset?: number;
+  cursor?: string; // From a previous SearchPage's next_cursor
+}
+
+// One page of results; pass next_cursor back to get the following one
+export interface SearchPage<T> {
+  items: T[];
+  next_cursor: string | null;
 }
 
 interface NotesContextType {
@@ -201,8 +208,8 @@ export const NotesProvider: React.FC<{ children: React.ReactNode }> = ({ childre
     try {
       setLoading(true);
       setError(null);
-      const results = await invoke<Note[]>('search_notes', { request });
-      return results;
+      const results = await invoke<SearchPage<{ note: Note }>>('search_notes', { request });
+      return results.items.map(match => match.note);
     } catch (error) {
       handleError(error, 'Failed to search notes');
       return [];
@@ -233,8 +240,8 @@ export const NotesProvider: React.FC<{ children: React.ReactNode }> = ({ childre
 
   const semanticSearch = async (query: string, limit?: number): Promise<any[]> => {
     try {
-      const results = await invoke<any[]>('semantic_search', { query, limit });
-      return results;
+      const results = await invoke<SearchPage<any>>('semantic_search', { query, limit });
+      return results.items;
     } catch (error) {
       handleError(error, 'Failed to perform semantic search');
       return [];